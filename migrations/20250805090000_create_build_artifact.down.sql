@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS build_artifact_build_id_name_idx;
+
+DROP TABLE IF EXISTS build_artifact;
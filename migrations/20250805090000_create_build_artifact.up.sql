@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS build_artifact (
+  id SERIAL PRIMARY KEY,
+  build_id INT NOT NULL,
+  name TEXT NOT NULL,
+  url TEXT NOT NULL,
+  size BIGINT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CONSTRAINT fk_build_id FOREIGN KEY (build_id) REFERENCES build(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS build_artifact_build_id_name_idx ON build_artifact (build_id, name);
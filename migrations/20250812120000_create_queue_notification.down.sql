@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS queue_notification_repo_pr_username_idx;
+
+DROP TABLE IF EXISTS queue_notification;
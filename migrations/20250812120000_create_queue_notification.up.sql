@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS queue_notification (
+  id SERIAL PRIMARY KEY,
+  repository TEXT NOT NULL,
+  pr_number BIGINT NOT NULL,
+  username TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS queue_notification_repo_pr_username_idx ON queue_notification (repository, pr_number, username);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS comment_outbox_repo_next_attempt_idx;
+
+DROP TABLE IF EXISTS comment_outbox;
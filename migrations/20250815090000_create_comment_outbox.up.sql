@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS comment_outbox (
+  id SERIAL PRIMARY KEY,
+  repository TEXT NOT NULL,
+  pr_number BIGINT NOT NULL,
+  text TEXT NOT NULL,
+  attempts INT NOT NULL DEFAULT 0,
+  next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS comment_outbox_repo_next_attempt_idx ON comment_outbox (repository, next_attempt_at);
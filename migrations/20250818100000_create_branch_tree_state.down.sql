@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS branch_tree_state_repo_branch_idx;
+
+DROP TABLE IF EXISTS branch_tree_state;
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS branch_tree_state (
+  id SERIAL PRIMARY KEY,
+  repository TEXT NOT NULL,
+  branch TEXT NOT NULL,
+  tree_state INT NULL,
+  treeclosed_src TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS branch_tree_state_repo_branch_idx ON branch_tree_state (repository, branch);
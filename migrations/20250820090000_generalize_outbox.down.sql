@@ -0,0 +1,6 @@
+DELETE FROM outbox WHERE payload::JSON->>'type' <> 'comment';
+UPDATE outbox SET payload = payload::JSON->>'text';
+
+ALTER TABLE outbox RENAME COLUMN payload TO text;
+ALTER INDEX outbox_repo_next_attempt_idx RENAME TO comment_outbox_repo_next_attempt_idx;
+ALTER TABLE outbox RENAME TO comment_outbox;
@@ -0,0 +1,5 @@
+ALTER TABLE comment_outbox RENAME TO outbox;
+ALTER INDEX comment_outbox_repo_next_attempt_idx RENAME TO outbox_repo_next_attempt_idx;
+ALTER TABLE outbox RENAME COLUMN text TO payload;
+
+UPDATE outbox SET payload = json_build_object('type', 'comment', 'text', payload)::TEXT;
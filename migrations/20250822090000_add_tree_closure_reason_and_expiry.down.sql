@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE branch_tree_state DROP COLUMN treeclosed_until;
+ALTER TABLE branch_tree_state DROP COLUMN treeclosed_reason;
+ALTER TABLE repository DROP COLUMN treeclosed_until;
+ALTER TABLE repository DROP COLUMN treeclosed_reason;
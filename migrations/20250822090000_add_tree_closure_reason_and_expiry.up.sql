@@ -0,0 +1,5 @@
+-- Add up migration script here
+ALTER TABLE repository ADD COLUMN treeclosed_reason TEXT;
+ALTER TABLE repository ADD COLUMN treeclosed_until TIMESTAMPTZ;
+ALTER TABLE branch_tree_state ADD COLUMN treeclosed_reason TEXT;
+ALTER TABLE branch_tree_state ADD COLUMN treeclosed_until TIMESTAMPTZ;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE pull_request ADD COLUMN delegation_expires_at TIMESTAMPTZ;
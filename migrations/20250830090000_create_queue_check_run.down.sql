@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS queue_check_run_repo_pr_idx;
+
+DROP TABLE IF EXISTS queue_check_run;
@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS queue_check_run (
+  id SERIAL PRIMARY KEY,
+  repository TEXT NOT NULL,
+  pr_number BIGINT NOT NULL,
+  check_run_id BIGINT NOT NULL,
+  head_sha TEXT NOT NULL,
+  position INT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS queue_check_run_repo_pr_idx ON queue_check_run (repository, pr_number);
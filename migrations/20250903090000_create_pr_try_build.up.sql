@@ -0,0 +1,16 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS pr_try_build (
+  build_id INT PRIMARY KEY,
+  pull_request_id INT NOT NULL,
+  jobs TEXT[] NOT NULL DEFAULT '{}',
+  CONSTRAINT fk_build_id FOREIGN KEY (build_id) REFERENCES build(id) ON DELETE CASCADE,
+  CONSTRAINT fk_pull_request_id FOREIGN KEY (pull_request_id) REFERENCES pull_request(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS pr_try_build_pull_request_id_idx ON pr_try_build (pull_request_id);
+
+-- Existing try builds ran the default jobs
+INSERT INTO pr_try_build (build_id, pull_request_id)
+SELECT try_build_id, id
+FROM pull_request
+WHERE try_build_id IS NOT NULL;
@@ -0,0 +1,13 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS build_retry (
+  id SERIAL PRIMARY KEY,
+  pull_request_id INT NOT NULL,
+  build_id INT NOT NULL,
+  retried_by TEXT NOT NULL,
+  reason TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  CONSTRAINT fk_pull_request_id FOREIGN KEY (pull_request_id) REFERENCES pull_request(id) ON DELETE CASCADE,
+  CONSTRAINT fk_build_id FOREIGN KEY (build_id) REFERENCES build(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS build_retry_pull_request_id_idx ON build_retry (pull_request_id);
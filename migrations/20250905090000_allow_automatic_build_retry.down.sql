@@ -0,0 +1,4 @@
+-- Add down migration script here
+DELETE FROM build_retry WHERE retried_by IS NULL;
+ALTER TABLE build_retry ALTER COLUMN retried_by SET DEFAULT '';
+ALTER TABLE build_retry ALTER COLUMN retried_by SET NOT NULL;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- Automatic retries of spurious failures are not requested by any user
+ALTER TABLE build_retry ALTER COLUMN retried_by DROP NOT NULL;
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS webhook_dead_letter (
+  id SERIAL PRIMARY KEY,
+  repository TEXT NOT NULL,
+  event_type TEXT NOT NULL,
+  payload TEXT NOT NULL,
+  error TEXT NOT NULL,
+  attempts INT NOT NULL DEFAULT 1,
+  replay_requested BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
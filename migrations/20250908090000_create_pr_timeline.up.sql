@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS pr_timeline (
+  repository TEXT NOT NULL,
+  pr_number INT NOT NULL,
+  opened_at TIMESTAMPTZ,
+  first_command_at TIMESTAMPTZ,
+  approved_at TIMESTAMPTZ,
+  queued_at TIMESTAMPTZ,
+  build_started_at TIMESTAMPTZ,
+  merged_at TIMESTAMPTZ,
+  PRIMARY KEY (repository, pr_number)
+);
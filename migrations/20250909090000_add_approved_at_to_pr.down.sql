@@ -0,0 +1 @@
+ALTER TABLE pull_request DROP COLUMN approved_at;
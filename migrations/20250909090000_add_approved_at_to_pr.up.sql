@@ -0,0 +1,3 @@
+ALTER TABLE pull_request ADD COLUMN approved_at TIMESTAMPTZ;
+-- PRs that are already approved start aging from now
+UPDATE pull_request SET approved_at = NOW() WHERE approved_by IS NOT NULL;
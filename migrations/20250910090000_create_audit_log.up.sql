@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS audit_log (
+  id SERIAL PRIMARY KEY,
+  repository TEXT NOT NULL,
+  pr_number BIGINT,
+  actor TEXT,
+  action TEXT NOT NULL,
+  details TEXT,
+  outcome TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_repository_pr_number_idx ON audit_log (repository, pr_number);
@@ -0,0 +1,2 @@
+ALTER TABLE repository DROP COLUMN deactivation_reason;
+ALTER TABLE repository DROP COLUMN deactivated_at;
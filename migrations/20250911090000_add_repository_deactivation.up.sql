@@ -0,0 +1,2 @@
+ALTER TABLE repository ADD COLUMN deactivated_at TIMESTAMPTZ;
+ALTER TABLE repository ADD COLUMN deactivation_reason TEXT;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE pr_try_build DROP COLUMN head_sha;
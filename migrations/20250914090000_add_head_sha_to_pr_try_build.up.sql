@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE pr_try_build ADD COLUMN head_sha TEXT;
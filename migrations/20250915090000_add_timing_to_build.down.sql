@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE build DROP COLUMN merged_at;
+ALTER TABLE build DROP COLUMN finished_at;
+ALTER TABLE build DROP COLUMN queued_at;
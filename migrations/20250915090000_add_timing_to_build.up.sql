@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE build ADD COLUMN queued_at TIMESTAMPTZ;
+ALTER TABLE build ADD COLUMN finished_at TIMESTAMPTZ;
+ALTER TABLE build ADD COLUMN merged_at TIMESTAMPTZ;
@@ -0,0 +1,8 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS scheduled_tree_closure (
+  repository TEXT NOT NULL,
+  starts_at TIMESTAMPTZ NOT NULL,
+  ends_at TIMESTAMPTZ NOT NULL,
+  finished BOOLEAN NOT NULL DEFAULT FALSE,
+  PRIMARY KEY (repository, starts_at)
+);
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE pull_request ADD COLUMN build_env TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE build ADD COLUMN artifacts_cleaned_at TIMESTAMPTZ;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE pull_request RENAME COLUMN park_reason TO hold_reason;
+ALTER TABLE pull_request DROP COLUMN parked;
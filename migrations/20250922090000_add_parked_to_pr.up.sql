@@ -0,0 +1,5 @@
+-- Add up migration script here
+ALTER TABLE pull_request ADD COLUMN parked BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE pull_request RENAME COLUMN hold_reason TO park_reason;
+-- PRs that were held with a reason were parked outside of the queue
+UPDATE pull_request SET parked = TRUE, held = FALSE WHERE held AND park_reason IS NOT NULL;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+ALTER TABLE pull_request ADD COLUMN approval_authors TEXT[] NOT NULL DEFAULT '{}';
+-- Approvals from before this migration did not record who commented them, so their approvers
+-- are counted, as before
+UPDATE pull_request
+SET approval_authors = string_to_array(approved_by, ',')
+WHERE approved_by IS NOT NULL;
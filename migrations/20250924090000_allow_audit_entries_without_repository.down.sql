@@ -0,0 +1,5 @@
+-- Add down migration script here
+DELETE FROM audit_log WHERE repository IS NULL;
+ALTER TABLE audit_log ALTER COLUMN repository SET DEFAULT '';
+ALTER TABLE audit_log ALTER COLUMN repository SET NOT NULL;
+ALTER TABLE audit_log ALTER COLUMN repository DROP DEFAULT;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- Decisions about requests to the administrative API that do not concern a single repository
+-- are recorded without a repository.
+ALTER TABLE audit_log ALTER COLUMN repository DROP NOT NULL;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE build_retry DROP COLUMN head_sha;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- The commit of the PR that was tested by the retried build, so that automatic retries can be
+-- counted separately for each approved commit
+ALTER TABLE build_retry ADD COLUMN head_sha TEXT;
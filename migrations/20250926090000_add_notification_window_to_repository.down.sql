@@ -0,0 +1,2 @@
+ALTER TABLE repository DROP COLUMN notification_window_count;
+ALTER TABLE repository DROP COLUMN notification_window_start;
@@ -0,0 +1,2 @@
+ALTER TABLE repository ADD COLUMN notification_window_start TIMESTAMPTZ;
+ALTER TABLE repository ADD COLUMN notification_window_count INT NOT NULL DEFAULT 0;
@@ -0,0 +1,163 @@
+//! Administrative commands for a bors instance.
+//! They work directly with the database of bors, so they can be used even when the bors server
+//! itself is not running or reachable.
+use anyhow::Context;
+use bors::{PgDbClient, SchemaStatus, expected_schema_version, get_schema_status, run_migrations};
+use clap::Parser;
+use sqlx::PgPool;
+
+#[derive(clap::Parser)]
+struct Opts {
+    /// Database connection string.
+    #[arg(long, env = "DATABASE_URL")]
+    db: String,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Immediately pause the merge queues of all repositories.
+    /// Running builds are not cancelled, but no PRs are merged and no new auto builds are started
+    /// until the stop is cleared.
+    EmergencyStop {
+        /// Why the merge queues are being stopped.
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Clear the emergency stop, so that the merge queues resume.
+    ClearEmergencyStop,
+    /// Show whether the emergency stop is active.
+    Status,
+    /// List webhooks whose handling has failed.
+    DeadLetters,
+    /// Replay webhooks whose handling has failed, e.g. after the bug that caused the failure was
+    /// fixed. The webhooks are replayed by the running bors instance within a few minutes.
+    ReplayDeadLetters {
+        /// The dead letter that should be replayed.
+        #[arg(long, conflicts_with = "all", required_unless_present = "all")]
+        id: Option<i32>,
+        /// Replay all dead letters.
+        #[arg(long)]
+        all: bool,
+    },
+    /// Apply pending database migrations.
+    Migrate {
+        /// Only print the SQL of the pending migrations, without applying them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+async fn run(opts: Opts) -> anyhow::Result<()> {
+    let pool = PgPool::connect(&opts.db)
+        .await
+        .context("Cannot connect to database")?;
+    let db = PgDbClient::new(pool.clone());
+
+    match opts.command {
+        Command::EmergencyStop { reason } => {
+            db.set_emergency_stop(reason.as_deref()).await?;
+            println!("Emergency stop activated, all merge queues are paused");
+        }
+        Command::ClearEmergencyStop => {
+            if db.clear_emergency_stop().await? {
+                println!("Emergency stop cleared, merge queues will resume");
+            } else {
+                println!("Emergency stop was not active");
+            }
+        }
+        Command::Status => match db.get_emergency_stop().await? {
+            Some(stop) => println!(
+                "Emergency stop active since {} (reason: {})",
+                stop.created_at,
+                stop.reason.as_deref().unwrap_or("not specified")
+            ),
+            None => println!("Emergency stop is not active"),
+        },
+        Command::DeadLetters => {
+            let dead_letters = db.get_dead_letters().await?;
+            if dead_letters.is_empty() {
+                println!("There are no dead letters");
+            }
+            for dead_letter in dead_letters {
+                println!(
+                    "#{} {} `{}` webhook, failed {} time(s) since {}{}\n  {}",
+                    dead_letter.id,
+                    dead_letter.repository,
+                    dead_letter.event_type,
+                    dead_letter.attempts,
+                    dead_letter.created_at,
+                    if dead_letter.replay_requested {
+                        " (replay requested)"
+                    } else {
+                        ""
+                    },
+                    dead_letter.error.lines().next().unwrap_or_default()
+                );
+            }
+        }
+        Command::ReplayDeadLetters { id, all: _ } => {
+            let requested = db.request_dead_letter_replay(id).await?;
+            match id {
+                Some(id) if requested == 0 => {
+                    return Err(anyhow::anyhow!("Dead letter {id} not found"));
+                }
+                _ => println!("Replay of {requested} dead letter(s) was requested"),
+            }
+        }
+        Command::Migrate { dry_run } => migrate(&pool, dry_run).await?,
+    }
+    Ok(())
+}
+
+async fn migrate(pool: &PgPool, dry_run: bool) -> anyhow::Result<()> {
+    match get_schema_status(pool).await? {
+        SchemaStatus::UpToDate { version } => {
+            println!("Database schema is up to date (version {version})");
+        }
+        SchemaStatus::Newer { unknown_versions } => {
+            return Err(anyhow::anyhow!(
+                "Database contains migrations {unknown_versions:?} that are unknown to this version of bors (expected schema version {})",
+                expected_schema_version()
+            ));
+        }
+        SchemaStatus::Outdated { pending } if dry_run => {
+            for migration in pending {
+                let destructive = if migration.is_destructive() {
+                    " (destructive)"
+                } else {
+                    ""
+                };
+                println!(
+                    "-- Migration {} {}{destructive}\n{}\n",
+                    migration.version,
+                    migration.description,
+                    migration.sql.trim_end()
+                );
+            }
+        }
+        SchemaStatus::Outdated { pending } => {
+            run_migrations(pool).await?;
+            println!(
+                "Applied {} migration(s), database schema is at version {}",
+                pending.len(),
+                expected_schema_version()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let opts = Opts::parse();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Cannot build tokio runtime");
+    if let Err(error) = runtime.block_on(run(opts)) {
+        eprintln!("Error: {error:?}");
+        std::process::exit(1);
+    }
+}
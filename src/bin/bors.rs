@@ -1,18 +1,20 @@
 use std::collections::HashMap;
 use std::io::IsTerminal;
-use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Context;
 use bors::{
-    BorsContext, BorsGlobalEvent, BorsProcess, CommandParser, PgDbClient, ServerState,
-    TeamApiClient, TreeState, WebhookSecret, create_app, create_bors_process, create_github_client,
-    load_repositories,
+    BorsContext, BorsGlobalEvent, BorsProcess, CommandParser, DeactivationReason, GitHubUrls,
+    ListenerConfig, PgDbClient, SchemaStatus, SecretsProvider, ServerState, TeamApiClient,
+    TreeState, WebhookSignature, create_bors_process, create_github_client, create_listener_app,
+    expected_schema_version, get_schema_status, load_repositories, run_migrations,
 };
 use clap::Parser;
 use sqlx::postgres::PgConnectOptions;
 use sqlx::{ConnectOptions, PgPool};
+use tokio::sync::watch;
 use tokio::time::Interval;
 use tracing::log::LevelFilter;
 use tracing_subscriber::filter::EnvFilter;
@@ -29,13 +31,22 @@ const PENDING_BUILDS_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 5);
 /// How often should the bot reload the mergeability status of PRs?
 const MERGEABILITY_STATUS_INTERVAL: Duration = Duration::from_secs(60 * 10);
 
-/// How often should the bot synchronize PR state.
-const PR_STATE_PERIODIC_REFRESH: Duration = Duration::from_secs(60 * 10);
+/// How often should the bot cancel workflow runs that do not belong to any pending build.
+const ZOMBIE_WORKFLOWS_INTERVAL: Duration = Duration::from_secs(60 * 15);
+
+/// How often should the bot clean up the artifacts of old try builds.
+const TRY_ARTIFACT_CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
 /// How often should the bot try to process the merge queue.
 /// It won't actually be executed more often than `MERGE_QUEUE_MAX_INTERVAL`.
 const MERGE_QUEUE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
 
+/// How often should the bot retry GitHub side effects that could not be performed.
+const OUTBOX_DELIVERY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often should the bot replay webhooks from the dead-letter queue whose replay was requested.
+const DEAD_LETTER_REPLAY_INTERVAL: Duration = Duration::from_secs(60 * 2);
+
 /// Longest duration between two ticks of the merge queue.
 const MERGE_QUEUE_MAX_INTERVAL: Duration = Duration::from_secs(30);
 
@@ -45,78 +56,285 @@ struct Opts {
     #[arg(long, env = "APP_ID")]
     app_id: u64,
 
-    /// Private key used to authenticate as a Github App.
-    #[arg(long, env = "PRIVATE_KEY")]
-    private_key: String,
-
-    /// Secret used to authenticate webhooks.
-    #[arg(long, env = "WEBHOOK_SECRET")]
-    webhook_secret: String,
+    /// Base URL of the GitHub REST API.
+    /// For GitHub Enterprise Server, use `https://<host>/api/v3`.
+    #[arg(long, env = "GITHUB_API_URL", default_value = "https://api.github.com")]
+    github_api_url: String,
+
+    /// URL of the GitHub GraphQL API.
+    /// Derived from the REST API URL by default.
+    #[arg(long, env = "GITHUB_GRAPHQL_URL")]
+    github_graphql_url: Option<String>,
+
+    /// URL of the GitHub web interface, used for links to repositories.
+    /// Derived from the REST API URL by default.
+    #[arg(long, env = "GITHUB_WEB_URL")]
+    github_web_url: Option<String>,
+
+    /// Algorithm with which GitHub signs webhooks.
+    #[arg(long, env = "WEBHOOK_SIGNATURE", value_enum, default_value_t = WebhookAlgorithm::Sha256)]
+    webhook_signature: WebhookAlgorithm,
+
+    /// Backend from which secrets (private key, webhook secret, API tokens) are loaded.
+    #[arg(long, env = "SECRETS_PROVIDER", value_enum, default_value_t = SecretsBackend::Env)]
+    secrets_provider: SecretsBackend,
+
+    /// Directory containing a file for each secret, used by the `directory` secrets provider.
+    #[arg(
+        long,
+        env = "SECRETS_DIR",
+        required_if_eq("secrets_provider", "directory")
+    )]
+    secrets_dir: Option<PathBuf>,
+
+    /// Address of the Vault server, used by the `vault` secrets provider.
+    #[arg(long, env = "VAULT_ADDR", required_if_eq("secrets_provider", "vault"))]
+    vault_addr: Option<String>,
+
+    /// Token used to authenticate to Vault.
+    #[arg(long, env = "VAULT_TOKEN", required_if_eq("secrets_provider", "vault"))]
+    vault_token: Option<String>,
+
+    /// API path of the key-value secret in Vault that contains the secrets.
+    #[arg(long, env = "VAULT_SECRET_PATH", default_value = "secret/data/bors")]
+    vault_secret_path: String,
+
+    /// Base URL of the Rust Team API, from which the review and try permissions of repositories
+    /// are loaded. If it is empty, only the permissions listed in the `[permissions]` section of
+    /// repository configs are used.
+    #[arg(
+        long,
+        env = "TEAM_API_URL",
+        default_value = "https://team-api.infra.rust-lang.org"
+    )]
+    team_api_url: String,
 
     /// Database connection string.
     #[arg(long, env = "DATABASE_URL")]
     db: String,
 
+    /// Which pending database migrations are applied automatically on startup.
+    #[arg(long, env = "AUTO_MIGRATE", value_enum, default_value_t = AutoMigrate::All)]
+    auto_migrate: AutoMigrate,
+
     /// Prefix used for bot commands in PR comments.
     #[arg(long, env = "CMD_PREFIX", default_value = "@bors")]
     cmd_prefix: String,
 
+    /// Addresses on which the HTTP server listens, in the `[<routes>@]<address>[/<prefix>]`
+    /// format. `<routes>` are `+`-separated groups of routes (`webhook`, `web` or `api`) served
+    /// on the address, all of them by default. If `<prefix>` is set, the routes are served under
+    /// it, e.g. `webhook+web@0.0.0.0:8080/bors,api@127.0.0.1:9000`.
+    #[arg(
+        long = "listen",
+        env = "LISTEN",
+        value_delimiter = ',',
+        default_value = "0.0.0.0:8080"
+    )]
+    listeners: Vec<ListenerConfig>,
+
     /// Web URL where the bot's website is deployed.
     #[arg(long, env = "WEB_URL", default_value = "http://localhost:8080")]
     web_url: String,
+
+    /// How often (in seconds) should the bot synchronize the state of PRs with GitHub, to heal
+    /// divergence caused by lost webhooks.
+    #[arg(long, env = "PR_STATE_REFRESH_INTERVAL", default_value_t = 60 * 10)]
+    pr_state_refresh_interval: u64,
 }
 
-/// Starts a server that receives GitHub webhooks and generates events into a queue
-/// that is then handled by the Bors process.
-async fn webhook_server(state: ServerState) -> anyhow::Result<()> {
-    let app = create_app(state);
+#[derive(clap::ValueEnum, Clone, Copy)]
+enum SecretsBackend {
+    /// Environment variables (`PRIVATE_KEY`, `WEBHOOK_SECRET`, `API_TOKEN`,
+    /// `REPOSITORY_API_TOKENS`, `ADMIN_TOKEN` and `OPERATOR_TOKEN`).
+    Env,
+    /// Files in a directory.
+    Directory,
+    /// HashiCorp Vault.
+    Vault,
+}
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
-    let listener = tokio::net::TcpListener::bind(addr)
-        .await
-        .context("Cannot create TCP/IP server socket")?;
+#[derive(clap::ValueEnum, Clone, Copy)]
+enum WebhookAlgorithm {
+    /// HMAC-SHA256 signatures (`X-Hub-Signature-256` header).
+    Sha256,
+    /// HMAC-SHA1 signatures (`X-Hub-Signature` header), for old GitHub Enterprise Server
+    /// installations.
+    Sha1,
+}
 
-    tracing::info!(
-        "Listening on http://0.0.0.0:{}",
-        listener.local_addr()?.port()
-    );
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq)]
+enum AutoMigrate {
+    /// Apply all pending migrations.
+    All,
+    /// Apply pending migrations only if none of them is destructive, otherwise refuse to start.
+    /// Useful when several instances share the database, e.g. during deployments.
+    Safe,
+    /// Never apply migrations, refuse to start if there are pending migrations.
+    None,
+}
 
-    axum::serve(listener, app).await?;
+impl Opts {
+    fn github_urls(&self) -> GitHubUrls {
+        GitHubUrls::new(
+            &self.github_api_url,
+            self.github_graphql_url.as_deref(),
+            self.github_web_url.as_deref(),
+        )
+    }
+
+    fn webhook_signature(&self) -> WebhookSignature {
+        match self.webhook_signature {
+            WebhookAlgorithm::Sha256 => WebhookSignature::Sha256,
+            WebhookAlgorithm::Sha1 => WebhookSignature::Sha1,
+        }
+    }
+
+    fn secrets_provider(&self) -> SecretsProvider {
+        match self.secrets_provider {
+            SecretsBackend::Env => SecretsProvider::Env,
+            SecretsBackend::Directory => {
+                SecretsProvider::Directory(self.secrets_dir.clone().unwrap_or_default())
+            }
+            SecretsBackend::Vault => SecretsProvider::Vault {
+                address: self.vault_addr.clone().unwrap_or_default(),
+                token: self.vault_token.clone().unwrap_or_default().into(),
+                path: self.vault_secret_path.clone(),
+            },
+        }
+    }
+}
+
+/// Starts a server that receives GitHub webhooks and generates events into a queue
+/// that is then handled by the Bors process. The server listens on all the given `listeners`.
+/// Once `shutdown` is set, the server stops accepting new connections and ends after the
+/// in-flight requests are handled.
+async fn webhook_server(
+    state: ServerState,
+    listeners: Vec<ListenerConfig>,
+    shutdown: watch::Receiver<bool>,
+) -> anyhow::Result<()> {
+    let state = Arc::new(state);
+    let mut servers = Vec::with_capacity(listeners.len());
+    for listener_config in listeners {
+        let app = create_listener_app(state.clone(), &listener_config);
+        let listener = tokio::net::TcpListener::bind(listener_config.address)
+            .await
+            .with_context(|| {
+                format!(
+                    "Cannot create TCP/IP server socket on {}",
+                    listener_config.address
+                )
+            })?;
+
+        tracing::info!(
+            "Listening on http://{}{}",
+            listener.local_addr()?,
+            listener_config.path_prefix
+        );
+        let shutdown = wait_for_shutdown(shutdown.clone());
+        servers.push(async move {
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown)
+                .await
+        });
+    }
+
+    futures::future::try_join_all(servers).await?;
+    Ok(())
+}
+
+/// Resolves once SIGTERM (e.g. from a deployment) or Ctrl+C is received.
+async fn shutdown_signal() -> anyhow::Result<()> {
+    let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .context("Cannot listen for SIGTERM")?;
+    tokio::select! {
+        res = tokio::signal::ctrl_c() => res.context("Cannot listen for Ctrl+C")?,
+        _ = sigterm.recv() => {}
+    }
     Ok(())
 }
 
-async fn initialize_db(connection_string: &str) -> anyhow::Result<PgDbClient> {
+async fn wait_for_shutdown(mut shutdown: watch::Receiver<bool>) {
+    // An error means that the sender was dropped, which also ends the process
+    let _ = shutdown.wait_for(|shutdown| *shutdown).await;
+}
+
+async fn initialize_db(
+    connection_string: &str,
+    auto_migrate: AutoMigrate,
+) -> anyhow::Result<PgDbClient> {
     let mut opts: PgConnectOptions = connection_string.parse()?;
     opts = opts.log_statements(LevelFilter::Trace);
     let db = PgPool::connect_with(opts)
         .await
         .context("Cannot connect to database")?;
 
-    sqlx::migrate!()
-        .run(&db)
-        .await
-        .context("Cannot run database migrations")?;
-
+    migrate_db(&db, auto_migrate).await?;
     Ok(PgDbClient::new(db))
 }
 
+/// Makes sure that the database schema matches the version expected by this binary, applying
+/// pending migrations allowed by `auto_migrate`.
+async fn migrate_db(db: &PgPool, auto_migrate: AutoMigrate) -> anyhow::Result<()> {
+    match get_schema_status(db).await? {
+        SchemaStatus::UpToDate { version } => {
+            tracing::info!("Database schema is up to date (version {version})");
+            Ok(())
+        }
+        SchemaStatus::Newer { unknown_versions } => Err(anyhow::anyhow!(
+            "Database contains migrations {unknown_versions:?} that are unknown to this version of bors (expected schema version {}). Was it migrated by a newer version?",
+            expected_schema_version()
+        )),
+        SchemaStatus::Outdated { pending } => {
+            let versions: Vec<i64> = pending.iter().map(|migration| migration.version).collect();
+            let destructive: Vec<i64> = pending
+                .iter()
+                .filter(|migration| migration.is_destructive())
+                .map(|migration| migration.version)
+                .collect();
+            match auto_migrate {
+                AutoMigrate::None => {
+                    return Err(anyhow::anyhow!(
+                        "Database has pending migrations {versions:?}, apply them with `bors-admin migrate`"
+                    ));
+                }
+                AutoMigrate::Safe if !destructive.is_empty() => {
+                    return Err(anyhow::anyhow!(
+                        "Database has pending destructive migrations {destructive:?}, apply them with `bors-admin migrate` once no older instances of bors are running"
+                    ));
+                }
+                AutoMigrate::All | AutoMigrate::Safe => {}
+            }
+            tracing::info!("Applying database migrations {versions:?}");
+            run_migrations(db).await
+        }
+    }
+}
+
 fn try_main(opts: Opts) -> anyhow::Result<()> {
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .context("Cannot build tokio runtime")?;
 
+    let secrets_provider = opts.secrets_provider();
+    let secrets = runtime
+        .block_on(secrets_provider.load())
+        .context("Cannot load secrets")?;
     let db = runtime
-        .block_on(initialize_db(&opts.db))
+        .block_on(initialize_db(&opts.db, opts.auto_migrate))
         .context("Cannot initialize database")?;
-    let team_api = TeamApiClient::default();
+    let team_api = if opts.team_api_url.is_empty() {
+        TeamApiClient::disabled()
+    } else {
+        TeamApiClient::new(opts.team_api_url.as_str())
+    };
+    let github_urls = opts.github_urls();
+    let webhook_signature = opts.webhook_signature();
     let (client, loaded_repos) = runtime.block_on(async {
-        let client = create_github_client(
-            opts.app_id.into(),
-            "https://api.github.com".to_string(),
-            opts.private_key.into(),
-        )?;
-        let repos = load_repositories(&client, &team_api).await?;
+        let client = create_github_client(opts.app_id.into(), &github_urls, secrets.private_key())?;
+        let repos = load_repositories(&client, &github_urls, &team_api).await?;
         Ok::<_, anyhow::Error>((client, repos))
     })?;
 
@@ -138,6 +356,13 @@ fn try_main(opts: Opts) -> anyhow::Result<()> {
             if let Err(error) = db.insert_repo_if_not_exists(&name, TreeState::Open).await {
                 tracing::warn!("Failed to insert repository {name}: {error:?}");
             }
+            // The app was installed again while bors was not running
+            if let Err(error) = db
+                .reactivate_repository(&name, DeactivationReason::Uninstalled)
+                .await
+            {
+                tracing::warn!("Failed to reactivate repository {name}: {error:?}");
+            }
         });
 
         repos.insert(name, Arc::new(repo));
@@ -148,8 +373,10 @@ fn try_main(opts: Opts) -> anyhow::Result<()> {
         CommandParser::new(opts.cmd_prefix.clone().into()),
         db.clone(),
         repos.clone(),
+        team_api,
         &opts.web_url,
-    );
+    )
+    .with_github_urls(github_urls.clone());
     let BorsProcess {
         repository_tx,
         global_tx,
@@ -158,11 +385,13 @@ fn try_main(opts: Opts) -> anyhow::Result<()> {
     } = create_bors_process(
         ctx,
         client,
-        team_api,
         chrono::Duration::from_std(MERGE_QUEUE_MAX_INTERVAL).unwrap(),
     );
 
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let refresh_shutdown = shutdown_rx.clone();
     let refresh_tx = global_tx.clone();
+    let pr_state_refresh_interval = Duration::from_secs(opts.pr_state_refresh_interval);
 
     fn make_interval(interval: Duration) -> Interval {
         let mut interval = tokio::time::interval(interval);
@@ -172,11 +401,15 @@ fn try_main(opts: Opts) -> anyhow::Result<()> {
     }
 
     let refresh_process = async move {
-        // Refresh state when starting the bot: first reload PRs from GitHub, then check their
-        // mergeability, then time out potentially stale builds, and then run the merge queue.
+        // Refresh state when starting the bot: first perform GitHub side effects that were
+        // interrupted by a restart, then reload PRs from GitHub, then check their mergeability,
+        // then catch up on workflow events of pending builds that were missed during the restart,
+        // then time out potentially stale builds, and then run the merge queue.
         let startup_events = [
+            BorsGlobalEvent::DeliverOutbox,
             BorsGlobalEvent::RefreshPullRequestState,
             BorsGlobalEvent::RefreshPullRequestMergeability,
+            BorsGlobalEvent::ReconcilePendingBuilds,
             BorsGlobalEvent::RefreshPendingBuilds,
             BorsGlobalEvent::ProcessMergeQueue,
         ];
@@ -188,10 +421,19 @@ fn try_main(opts: Opts) -> anyhow::Result<()> {
         let mut permissions_refresh = make_interval(PERMISSIONS_REFRESH_INTERVAL);
         let mut refresh_pending_builds = make_interval(PENDING_BUILDS_REFRESH_INTERVAL);
         let mut mergeability_status_refresh = make_interval(MERGEABILITY_STATUS_INTERVAL);
-        let mut prs_interval = make_interval(PR_STATE_PERIODIC_REFRESH);
+        let mut prs_interval = make_interval(pr_state_refresh_interval);
+        let mut zombie_workflows_interval = make_interval(ZOMBIE_WORKFLOWS_INTERVAL);
+        let mut try_artifact_cleanup_interval = make_interval(TRY_ARTIFACT_CLEANUP_INTERVAL);
         let mut merge_queue_interval = make_interval(MERGE_QUEUE_CHECK_INTERVAL);
+        let mut outbox_interval = make_interval(OUTBOX_DELIVERY_INTERVAL);
+        let mut dead_letter_interval = make_interval(DEAD_LETTER_REPLAY_INTERVAL);
         loop {
             tokio::select! {
+                _ = wait_for_shutdown(refresh_shutdown.clone()) => {
+                    // Dropping the sender lets the bors process finish once it handles the
+                    // remaining events
+                    return Ok(());
+                }
                 _ = config_refresh.tick() => {
                     refresh_tx.send(BorsGlobalEvent::RefreshConfig).await?;
                 }
@@ -207,9 +449,21 @@ fn try_main(opts: Opts) -> anyhow::Result<()> {
                 _ = prs_interval.tick() => {
                     refresh_tx.send(BorsGlobalEvent::RefreshPullRequestState).await?;
                 }
+                _ = zombie_workflows_interval.tick() => {
+                    refresh_tx.send(BorsGlobalEvent::CancelZombieWorkflows).await?;
+                }
+                _ = try_artifact_cleanup_interval.tick() => {
+                    refresh_tx.send(BorsGlobalEvent::CleanUpTryArtifacts).await?;
+                }
                 _ = merge_queue_interval.tick() => {
                     refresh_tx.send(BorsGlobalEvent::ProcessMergeQueue).await?;
                 }
+                _ = outbox_interval.tick() => {
+                    refresh_tx.send(BorsGlobalEvent::DeliverOutbox).await?;
+                }
+                _ = dead_letter_interval.tick() => {
+                    refresh_tx.send(BorsGlobalEvent::ReplayDeadLetters).await?;
+                }
             }
         }
     };
@@ -217,28 +471,54 @@ fn try_main(opts: Opts) -> anyhow::Result<()> {
     let state = ServerState::new(
         repository_tx,
         global_tx,
-        WebhookSecret::new(opts.webhook_secret),
+        secrets.webhook_secret(),
+        secrets.api_token(),
+        secrets.repository_api_tokens(),
+        secrets.role_tokens(),
         repos,
         db,
         opts.cmd_prefix.into(),
-    );
-    let server_process = webhook_server(state);
-
+    )
+    .with_secrets_provider(secrets_provider)
+    .with_github_app(opts.app_id.into(), github_urls)
+    .with_webhook_signature(webhook_signature);
+    let server_process = webhook_server(state, opts.listeners, shutdown_rx.clone());
+
+    // On shutdown, the webhook server stops accepting requests and the refresh generator stops.
+    // The bors process then handles the events that were already received, lets the merge queue
+    // finish its current tick, delivers pending comments and ends. If the server or the refresh
+    // generator ends for any other reason, bors shuts down in the same way.
     let fut = async move {
-        tokio::select! {
-            () = bors_process => {
-                tracing::warn!("Bors event handling process has ended");
-                Ok(())
-            },
-            res = refresh_process => {
+        let signal = async {
+            tokio::select! {
+                res = shutdown_signal() => {
+                    match res {
+                        Ok(()) => tracing::info!("Received shutdown signal, shutting down"),
+                        Err(error) => tracing::error!("Cannot wait for shutdown signal: {error:?}"),
+                    }
+                    shutdown_tx.send_replace(true);
+                }
+                _ = wait_for_shutdown(shutdown_rx) => {}
+            }
+        };
+        let refresh = async {
+            let res = refresh_process.await;
+            if !*shutdown_tx.borrow() {
                 tracing::warn!("Refresh generator has ended");
-                res
             }
-            res = server_process => {
+            shutdown_tx.send_replace(true);
+            res
+        };
+        let server = async {
+            let res = server_process.await;
+            if !*shutdown_tx.borrow() {
                 tracing::warn!("GitHub webhook listener has ended: {res:?}");
-                res
             }
-        }
+            shutdown_tx.send_replace(true);
+            res
+        };
+        let ((), refresh_res, server_res, ()) = tokio::join!(signal, refresh, server, bors_process);
+        refresh_res.and(server_res)
     };
 
     runtime.block_on(fut)?;
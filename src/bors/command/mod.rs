@@ -2,6 +2,7 @@ mod parser;
 use std::fmt;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
+use std::time::Duration;
 
 use crate::{database::DelegatedPermission, github::CommitSha};
 pub use parser::{CommandParseError, CommandParser};
@@ -37,14 +38,16 @@ pub enum Parent {
     CommitSha(CommitSha),
     /// Use last build's parent: parent="last"
     Last,
+    /// Latest commit of a branch: parent="<branch>"
+    Branch(String),
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Approver {
     /// The approver is the same as the comment author.
     Myself,
-    /// The approver is specified by the user.
-    Specified(String),
+    /// The approvers are specified by the user, e.g. `r=user1,user2`.
+    Specified(Vec<String>),
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -89,7 +92,7 @@ impl FromStr for RollupMode {
 /// When modifying commands, remember to also update:
 /// - `templates/help.html` (HTML help page)
 /// - `src/bors/handlers/help.rs` (the `@bors help` command output)
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum BorsCommand {
     /// Approve a commit.
     Approve {
@@ -99,37 +102,152 @@ pub enum BorsCommand {
         priority: Option<Priority>,
         /// Rollup status of the commit.
         rollup: Option<RollupMode>,
+        /// Whether the commits of the PR should be squashed into a single commit when merging.
+        squash: bool,
+        /// The commit that should be approved, possibly abbreviated to a prefix of its SHA.
+        /// If it is not specified, the current head of the PR is approved.
+        commit: Option<CommitSha>,
     },
     /// Unapprove a commit.
     Unapprove,
     /// Print help.
     Help,
     /// Ping the bot.
-    Ping,
+    Ping {
+        /// Whether the health of the components that bors depends on should be reported.
+        health: bool,
+    },
     /// Perform a try build.
     Try {
         /// Parent commit which should be used as the merge base.
         parent: Option<Parent>,
         /// The CI workflow to run.
         jobs: Vec<String>,
+        /// Commit of the PR which should be tried instead of its head.
+        commit: Option<CommitSha>,
     },
     /// Cancel a try build.
-    TryCancel,
+    TryCancel {
+        /// ID of the try build that should be cancelled. All running try builds of the PR are
+        /// cancelled if it is not specified.
+        build_id: Option<i32>,
+    },
     /// Set the priority of a PR.
     SetPriority(Priority),
     /// Get information about the current PR.
     Info,
     /// Delegate approval authority to the pull request author.
-    SetDelegate(DelegatedPermission),
+    SetDelegate {
+        permission: DelegatedPermission,
+        /// The delegation expires after this duration elapses.
+        duration: Option<Duration>,
+    },
     /// Revoke any previously granted delegation.
     Undelegate,
     /// Set the rollup mode of a PRstatus.
     SetRollupMode(RollupMode),
     /// Open the repository tree for merging.
-    OpenTree,
+    OpenTree {
+        /// Open the tree of this branch instead of the tree that applies to the PR.
+        branch: Option<String>,
+    },
     /// Set the tree closed with a priority level.
-    TreeClosed(Priority),
+    TreeClosed {
+        /// PRs with priority lower than this value cannot be merged.
+        priority: Priority,
+        /// The tree is reopened automatically after this duration elapses.
+        duration: Option<Duration>,
+        /// Why the tree was closed.
+        reason: Option<String>,
+        /// Close the tree of this branch instead of the tree that applies to the PR.
+        branch: Option<String>,
+    },
     /// Clear a failed auto build status from an approved PR.
     /// This will cause the merge queue to attempt to start a new auto build and retry merging the PR again.
-    Retry,
+    /// If the PR has no failed auto build, its failed try build is started again instead.
+    Retry {
+        /// Cancel the workflows of the failed auto build that are still running.
+        cancel_workflows: bool,
+        /// Why the build is retried, e.g. the spurious failure that was encountered.
+        reason: Option<String>,
+    },
+    /// Notify the comment author when the PR gets close to the front of the merge queue.
+    Notify,
+    /// Pause the merge queue, so that no new auto builds are started.
+    PauseQueue,
+    /// Resume a paused merge queue.
+    ResumeQueue,
+    /// Hold an approved PR at the top of the merge queue. Its auto build is started, but the PR is
+    /// not merged until the hold is released.
+    Hold,
+    /// Release a held PR, so that it can be merged.
+    ReleaseHold,
+    /// Keep an approved PR approved, but exclude it from the merge queue until it is unparked.
+    Park {
+        /// Why the PR is parked, shown in `info` and on the dashboard.
+        reason: Option<String>,
+    },
+    /// Unpark a parked PR, so that it enters the merge queue again.
+    Unpark,
+    /// Set build parameters of the PR, which are passed to its try and auto builds.
+    SetBuildEnv {
+        /// `(<key>, <value>)` pairs of the parameters. Other parameters of the PR are kept.
+        vars: Vec<(String, String)>,
+    },
+    /// Remove all build parameters of the PR.
+    ClearBuildEnv,
+    /// Synchronize the state of the PRs of the repository with GitHub.
+    Sync,
+    /// Reload the configuration and permissions of the repository right away, instead of waiting
+    /// for the next periodic refresh.
+    Reload,
+    /// Print the audit log of the PR.
+    History,
+    /// Open a PR that reverts this merged PR.
+    Revert {
+        /// If set, the revert PR is approved right away with this priority.
+        priority: Option<Priority>,
+    },
+    /// Cherry-pick the merged PR onto another branch and open a PR with the result. If the PR is
+    /// not merged yet, the backport is performed once it is merged.
+    Backport {
+        /// Branch onto which the PR is backported.
+        to: String,
+    },
+}
+
+impl BorsCommand {
+    /// Name of the command, used to identify it in the audit log.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BorsCommand::Approve { .. } => "approve",
+            BorsCommand::Unapprove => "unapprove",
+            BorsCommand::Help => "help",
+            BorsCommand::Ping { .. } => "ping",
+            BorsCommand::Try { .. } => "try",
+            BorsCommand::TryCancel { .. } => "try_cancel",
+            BorsCommand::SetPriority(_) => "priority",
+            BorsCommand::Info => "info",
+            BorsCommand::SetDelegate { .. } => "delegate",
+            BorsCommand::Undelegate => "undelegate",
+            BorsCommand::SetRollupMode(_) => "rollup",
+            BorsCommand::OpenTree { .. } => "tree_open",
+            BorsCommand::TreeClosed { .. } => "tree_closed",
+            BorsCommand::Retry { .. } => "retry",
+            BorsCommand::Notify => "notify",
+            BorsCommand::PauseQueue => "queue_pause",
+            BorsCommand::ResumeQueue => "queue_resume",
+            BorsCommand::Hold => "hold",
+            BorsCommand::ReleaseHold => "release_hold",
+            BorsCommand::Park { .. } => "park",
+            BorsCommand::Unpark => "unpark",
+            BorsCommand::SetBuildEnv { .. } => "env",
+            BorsCommand::ClearBuildEnv => "env_clear",
+            BorsCommand::Sync => "sync",
+            BorsCommand::Reload => "reload",
+            BorsCommand::History => "history",
+            BorsCommand::Revert { .. } => "revert",
+            BorsCommand::Backport { .. } => "backport",
+        }
+    }
 }
@@ -4,8 +4,9 @@ use crate::bors::command::{Approver, BorsCommand, CommandPrefix, Parent};
 use crate::database::DelegatedPermission;
 use crate::github::CommitSha;
 use pulldown_cmark::{Event, Parser, Tag, TagEnd, TextMergeStream};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
+use std::time::Duration;
 
 use super::{Priority, RollupMode};
 
@@ -57,13 +58,26 @@ impl CommandParser {
     /// Assumes that each command spands at most one line and that there are not more commands on
     /// each line.
     pub fn parse_commands(&self, text: &str) -> Vec<Result<BorsCommand, CommandParseError>> {
+        self.parse_commands_with_aliases(text, &HashMap::new())
+    }
+
+    /// Parses bors commands from the given string, replacing the command `aliases` of a
+    /// repository with their expansion first.
+    pub fn parse_commands_with_aliases(
+        &self,
+        text: &str,
+        aliases: &HashMap<String, String>,
+    ) -> Vec<Result<BorsCommand, CommandParseError>> {
         let segments = extract_text_from_markdown(text);
         segments
             .lines()
             .filter_map(|line| match line.find(self.prefix.as_ref()) {
                 Some(index) => {
                     let input = &line[index + self.prefix.as_ref().len()..];
-                    parse_command(input, &self.parsers)
+                    match expand_alias(input, aliases) {
+                        Some(expanded) => parse_command(&expanded, &self.parsers),
+                        None => parse_command(input, &self.parsers),
+                    }
                 }
                 None => None,
             })
@@ -71,6 +85,15 @@ impl CommandParser {
     }
 }
 
+/// Replaces the command at the start of `input` with its expansion, if it is an alias.
+fn expand_alias(input: &str, aliases: &HashMap<String, String>) -> Option<String> {
+    let input = input.trim_start();
+    let (command, arguments) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+    aliases
+        .get(command)
+        .map(|expansion| format!("{expansion} {arguments}"))
+}
+
 /// Extract text segments from a Markdown `text`.
 fn extract_text_from_markdown(text: &str) -> String {
     let md_parser = TextMergeStream::new(Parser::new(text));
@@ -79,11 +102,9 @@ fn extract_text_from_markdown(text: &str) -> String {
 
     for event in md_parser.into_iter() {
         match event {
-            Event::Text(text) | Event::Html(text) => {
-                // Only consider commands in raw text outside of wrapping elements
-                if stack.is_empty() {
-                    cleaned_text.push_str(&text);
-                }
+            // Only consider commands in raw text outside of wrapping elements
+            Event::Text(text) | Event::Html(text) if stack.is_empty() => {
+                cleaned_text.push_str(&text);
             }
             Event::SoftBreak | Event::HardBreak | Event::End(TagEnd::Paragraph) => {
                 cleaned_text.push('\n');
@@ -143,7 +164,17 @@ const DEFAULT_PARSERS: &[ParserFn] = &[
     parser_help,
     parser_ping,
     parser_retry,
+    parser_notify,
     parser_tree_ops,
+    parser_queue_ops,
+    parser_hold,
+    parser_park,
+    parser_build_env,
+    parser_sync,
+    parser_reload,
+    parser_history,
+    parser_revert,
+    parser_backport,
 ];
 
 const ONLY_TRY_PARSERS: &[ParserFn] = &[parser_try_cancel, parser_try];
@@ -173,7 +204,7 @@ fn parse_parts(input: &str) -> Result<Vec<CommandPart<'_>>, CommandParseError> {
     let mut parts = vec![];
     let mut seen_keys = HashSet::new();
 
-    for item in input.split_whitespace() {
+    for item in split_arguments(input) {
         // Stop parsing, as this is a command for another bot, such as `@rust-timer queue`.
         if item.starts_with('@') {
             break;
@@ -190,6 +221,10 @@ fn parse_parts(input: &str) -> Result<Vec<CommandPart<'_>>, CommandParseError> {
                     return Err(CommandParseError::DuplicateArg(key.to_string()));
                 }
                 seen_keys.insert(key);
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .unwrap_or(value);
                 parts.push(CommandPart::KeyValue { key, value });
             }
             None => parts.push(CommandPart::Bare(item)),
@@ -198,19 +233,49 @@ fn parse_parts(input: &str) -> Result<Vec<CommandPart<'_>>, CommandParseError> {
     Ok(parts)
 }
 
+/// Splits the input by whitespace, except for whitespace inside double quotes, so that values
+/// such as `reason="CI outage"` form a single argument.
+fn split_arguments(input: &str) -> Vec<&str> {
+    let mut arguments = vec![];
+    let mut start = None;
+    let mut in_quotes = false;
+    for (index, c) in input.char_indices() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        }
+        if c.is_whitespace() && !in_quotes {
+            if let Some(start) = start.take() {
+                arguments.push(&input[start..index]);
+            }
+        } else if start.is_none() {
+            start = Some(index);
+        }
+    }
+    if let Some(start) = start {
+        arguments.push(&input[start..]);
+    }
+    arguments
+}
+
 /// Parses:
-/// - "@bors r+ [p=<priority>] [rollup=<never|iffy|maybe|always>]"
-/// - "@bors r=<user> [p=<priority>] [rollup=<never|iffy|maybe|always>]"
+/// - "@bors r+ [<sha>] [p=<priority>] [rollup=<never|iffy|maybe|always>] [squash]"
+/// - "@bors r=<user> [<sha>] [p=<priority>] [rollup=<never|iffy|maybe|always>] [squash]"
 fn parser_approval(command: &CommandPart<'_>, parts: &[CommandPart<'_>]) -> ParseResult {
     let approver = match command {
         CommandPart::Bare("r+") => Approver::Myself,
         CommandPart::KeyValue { key: "r", value } => {
-            if value.is_empty() {
+            let mut approvers: Vec<String> = vec![];
+            for approver in value.split(',').filter(|approver| !approver.is_empty()) {
+                if !approvers.iter().any(|existing| existing == approver) {
+                    approvers.push(approver.to_string());
+                }
+            }
+            if approvers.is_empty() {
                 return Some(Err(CommandParseError::MissingArgValue {
                     arg: "r".to_string(),
                 }));
             }
-            Approver::Specified(value.to_string())
+            Approver::Specified(approvers)
         }
         _ => return None,
     };
@@ -225,13 +290,47 @@ fn parser_approval(command: &CommandPart<'_>, parts: &[CommandPart<'_>]) -> Pars
         Some(Err(e)) => return Some(Err(e)),
         None => None,
     };
+    let commit = match parse_approved_commit(parts) {
+        Some(Ok(sha)) => Some(sha),
+        Some(Err(e)) => return Some(Err(e)),
+        None => None,
+    };
+    let squash = parts
+        .iter()
+        .any(|part| matches!(part, CommandPart::Bare("squash")));
     Some(Ok(BorsCommand::Approve {
         approver,
         priority,
         rollup,
+        squash,
+        commit,
     }))
 }
 
+/// Parses the first bare hexadecimal argument (with at least 7 characters, the length of an
+/// abbreviated commit SHA) in `parts` as the commit SHA that should be approved. The SHA can be
+/// abbreviated, it is matched against the head of the PR when the command is handled.
+fn parse_approved_commit(parts: &[CommandPart<'_>]) -> ParseResult<CommitSha> {
+    parts
+        .iter()
+        .filter_map(|part| match part {
+            CommandPart::Bare(value)
+                if value.len() >= 7 && value.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                if value.len() > 40 {
+                    Some(Err(CommandParseError::ValidationError(
+                        "Approved commit has to be a valid commit SHA: SHA must have at most 40 characters"
+                            .to_string(),
+                    )))
+                } else {
+                    Some(Ok(CommitSha(value.to_ascii_lowercase())))
+                }
+            }
+            _ => None,
+        })
+        .next()
+}
+
 /// Parses "@bors r-"
 fn parser_unapprove(command: &CommandPart<'_>, _parts: &[CommandPart<'_>]) -> ParseResult {
     if let CommandPart::Bare("r-") = command {
@@ -250,23 +349,37 @@ fn parser_help(command: &CommandPart<'_>, _parts: &[CommandPart<'_>]) -> ParseRe
     }
 }
 
-/// Parses "@bors ping".
-fn parser_ping(command: &CommandPart<'_>, _parts: &[CommandPart<'_>]) -> ParseResult {
+/// Parses "@bors ping [health]".
+fn parser_ping(command: &CommandPart<'_>, parts: &[CommandPart<'_>]) -> ParseResult {
     if let CommandPart::Bare("ping") = command {
-        Some(Ok(BorsCommand::Ping))
+        let health = parts
+            .iter()
+            .any(|part| matches!(part, CommandPart::Bare("health")));
+        Some(Ok(BorsCommand::Ping { health }))
     } else {
         None
     }
 }
 
-fn parse_sha(input: &str) -> Result<CommitSha, String> {
-    if input.len() != 40 {
-        return Err("SHA must have exactly 40 characters".to_string());
-    }
-    Ok(CommitSha(input.to_string()))
+fn is_commit_sha(input: &str) -> bool {
+    input.len() == 40 && input.chars().all(|c| c.is_ascii_hexdigit())
 }
 
-/// Parses "@bors try <parent=sha>".
+/// Checks the most common rules of git reference names.
+fn is_valid_branch_name(input: &str) -> bool {
+    !input.is_empty()
+        && !input.starts_with(['-', '/', '.'])
+        && !input.ends_with(['/', '.'])
+        && !input.ends_with(".lock")
+        && !input.contains("..")
+        && !input.contains("//")
+        && !input.contains("@{")
+        && !input
+            .chars()
+            .any(|c| c.is_ascii_control() || c.is_whitespace() || "~^:?*[\\".contains(c))
+}
+
+/// Parses "@bors try <parent=sha|last|branch> <jobs=...> <commit=sha>".
 fn parser_try(command: &CommandPart<'_>, parts: &[CommandPart<'_>]) -> ParseResult {
     if *command != CommandPart::Bare("try") {
         return None;
@@ -274,6 +387,7 @@ fn parser_try(command: &CommandPart<'_>, parts: &[CommandPart<'_>]) -> ParseResu
 
     let mut parent = None;
     let mut jobs = Vec::new();
+    let mut commit = None;
 
     for part in parts {
         match part {
@@ -282,14 +396,17 @@ fn parser_try(command: &CommandPart<'_>, parts: &[CommandPart<'_>]) -> ParseResu
             }
             CommandPart::KeyValue { key, value } => match (*key, *value) {
                 ("parent", "last") => parent = Some(Parent::Last),
-                ("parent", value) => match parse_sha(value) {
-                    Ok(sha) => parent = Some(Parent::CommitSha(sha)),
-                    Err(error) => {
-                        return Some(Err(CommandParseError::ValidationError(format!(
-                            "Try parent has to be a valid commit SHA: {error}"
-                        ))));
-                    }
-                },
+                ("parent", value) if is_commit_sha(value) => {
+                    parent = Some(Parent::CommitSha(CommitSha(value.to_string())))
+                }
+                ("parent", value) if is_valid_branch_name(value) => {
+                    parent = Some(Parent::Branch(value.to_string()))
+                }
+                ("parent", value) => {
+                    return Some(Err(CommandParseError::ValidationError(format!(
+                        "Try parent has to be a commit SHA, `last` or a branch name, not `{value}`"
+                    ))));
+                }
                 ("jobs", value) => {
                     let raw_jobs: Vec<_> = value.split(',').map(|s| s.to_string()).collect();
                     if raw_jobs.is_empty() {
@@ -306,40 +423,69 @@ fn parser_try(command: &CommandPart<'_>, parts: &[CommandPart<'_>]) -> ParseResu
                     }
                     jobs = raw_jobs;
                 }
+                ("commit", value) if is_commit_sha(value) => {
+                    commit = Some(CommitSha(value.to_string()))
+                }
+                ("commit", value) => {
+                    return Some(Err(CommandParseError::ValidationError(format!(
+                        "Try commit has to be a commit SHA, not `{value}`"
+                    ))));
+                }
                 _ => {
                     return Some(Err(CommandParseError::UnknownArg(key.to_string())));
                 }
             },
         }
     }
-    Some(Ok(BorsCommand::Try { parent, jobs }))
+    Some(Ok(BorsCommand::Try {
+        parent,
+        jobs,
+        commit,
+    }))
 }
 
-/// Parses "@bors try cancel".
+/// Parses "@bors try cancel [<build id>]".
 fn parser_try_cancel(command: &CommandPart<'_>, parts: &[CommandPart<'_>]) -> ParseResult {
     match (command, parts) {
+        (CommandPart::Bare("try"), [CommandPart::Bare("cancel"), CommandPart::Bare(id), ..]) => {
+            match id.parse() {
+                Ok(build_id) => Some(Ok(BorsCommand::TryCancel {
+                    build_id: Some(build_id),
+                })),
+                Err(_) => Some(Err(CommandParseError::ValidationError(format!(
+                    "Try build ID has to be a number, not `{id}`"
+                )))),
+            }
+        }
         (CommandPart::Bare("try"), [CommandPart::Bare("cancel"), ..]) => {
-            Some(Ok(BorsCommand::TryCancel))
+            Some(Ok(BorsCommand::TryCancel { build_id: None }))
         }
         _ => None,
     }
 }
 
-/// Parses `@bors delegate=<try|review>` or `@bors delegate+`.
-fn parser_delegate(command: &CommandPart<'_>, _parts: &[CommandPart<'_>]) -> ParseResult {
-    match command {
-        CommandPart::Bare("delegate+") => {
-            Some(Ok(BorsCommand::SetDelegate(DelegatedPermission::Review)))
-        }
+/// Parses `@bors delegate=<try|review> [for=<duration>]` or `@bors delegate+ [for=<duration>]`.
+fn parser_delegate(command: &CommandPart<'_>, parts: &[CommandPart<'_>]) -> ParseResult {
+    let permission = match command {
+        CommandPart::Bare("delegate+") => DelegatedPermission::Review,
         CommandPart::KeyValue {
             key: "delegate",
             value,
         } => match DelegatedPermission::from_str(value) {
-            Ok(permission) => Some(Ok(BorsCommand::SetDelegate(permission))),
-            Err(error) => Some(Err(CommandParseError::ValidationError(error))),
+            Ok(permission) => permission,
+            Err(error) => return Some(Err(CommandParseError::ValidationError(error))),
         },
-        _ => None,
-    }
+        _ => return None,
+    };
+    let duration = match parse_for_duration(parts) {
+        Some(Ok(duration)) => Some(duration),
+        Some(Err(error)) => return Some(Err(error)),
+        None => None,
+    };
+    Some(Ok(BorsCommand::SetDelegate {
+        permission,
+        duration,
+    }))
 }
 
 /// Parses "@bors delegate-"
@@ -412,20 +558,48 @@ fn parser_info(command: &CommandPart<'_>, _parts: &[CommandPart<'_>]) -> ParseRe
     }
 }
 
-/// Parses `@bors retry`
-fn parser_retry(command: &CommandPart<'_>, _parts: &[CommandPart<'_>]) -> ParseResult {
+/// Parses `@bors retry [cancel-workflows] [reason=<reason>]`
+fn parser_retry(command: &CommandPart<'_>, parts: &[CommandPart<'_>]) -> ParseResult {
     if let CommandPart::Bare("retry") = command {
-        Some(Ok(BorsCommand::Retry))
+        let cancel_workflows = parts.contains(&CommandPart::Bare("cancel-workflows"));
+        let reason = parts.iter().find_map(|part| match part {
+            CommandPart::KeyValue {
+                key: "reason",
+                value,
+            } => Some(value.to_string()),
+            _ => None,
+        });
+        Some(Ok(BorsCommand::Retry {
+            cancel_workflows,
+            reason,
+        }))
     } else {
         None
     }
 }
 
-/// Parses `@bors treeclosed-`, `@bors treeopen` and `@bors treeclosed=<priority>`
-fn parser_tree_ops(command: &CommandPart<'_>, _parts: &[CommandPart<'_>]) -> ParseResult {
+/// Parses `@bors notify`
+fn parser_notify(command: &CommandPart<'_>, _parts: &[CommandPart<'_>]) -> ParseResult {
+    if let CommandPart::Bare("notify") = command {
+        Some(Ok(BorsCommand::Notify))
+    } else {
+        None
+    }
+}
+
+/// Parses `@bors treeclosed- [branch=<branch>]`, `@bors treeopen [branch=<branch>]` and
+/// `@bors treeclosed=<priority> [for=<duration>] [reason="<reason>"] [branch=<branch>]`
+fn parser_tree_ops(command: &CommandPart<'_>, parts: &[CommandPart<'_>]) -> ParseResult {
+    let branch = parts.iter().find_map(|part| match part {
+        CommandPart::KeyValue {
+            key: "branch",
+            value,
+        } => Some(value.to_string()),
+        _ => None,
+    });
     match command {
         CommandPart::Bare("treeclosed-") | CommandPart::Bare("treeopen") => {
-            Some(Ok(BorsCommand::OpenTree))
+            Some(Ok(BorsCommand::OpenTree { branch }))
         }
         CommandPart::KeyValue {
             key: "treeclosed",
@@ -435,18 +609,215 @@ fn parser_tree_ops(command: &CommandPart<'_>, _parts: &[CommandPart<'_>]) -> Par
                 Ok(p) => p,
                 Err(error) => return Some(Err(error)),
             };
-            Some(Ok(BorsCommand::TreeClosed(priority)))
+            let duration = match parse_for_duration(parts) {
+                Some(Ok(duration)) => Some(duration),
+                Some(Err(error)) => return Some(Err(error)),
+                None => None,
+            };
+            let reason = parts.iter().find_map(|part| match part {
+                CommandPart::KeyValue {
+                    key: "reason",
+                    value,
+                } => Some(value.to_string()),
+                _ => None,
+            });
+            Some(Ok(BorsCommand::TreeClosed {
+                priority,
+                duration,
+                reason,
+                branch,
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// Parses `@bors queue pause` and `@bors queue resume`
+fn parser_queue_ops(command: &CommandPart<'_>, parts: &[CommandPart<'_>]) -> ParseResult {
+    match (command, parts) {
+        (CommandPart::Bare("queue"), [CommandPart::Bare("pause"), ..]) => {
+            Some(Ok(BorsCommand::PauseQueue))
         }
+        (CommandPart::Bare("queue"), [CommandPart::Bare("resume"), ..]) => {
+            Some(Ok(BorsCommand::ResumeQueue))
+        }
+        (CommandPart::Bare("queue"), _) => Some(Err(CommandParseError::ValidationError(
+            "Use `queue pause` or `queue resume`".to_string(),
+        ))),
+        _ => None,
+    }
+}
+
+/// Parses `@bors hold` and `@bors release-hold`
+fn parser_hold(command: &CommandPart<'_>, _parts: &[CommandPart<'_>]) -> ParseResult {
+    match command {
+        CommandPart::Bare("hold") => Some(Ok(BorsCommand::Hold)),
+        CommandPart::Bare("release-hold") => Some(Ok(BorsCommand::ReleaseHold)),
         _ => None,
     }
 }
 
+/// Parses `@bors park [reason=<reason>]` and `@bors unpark`.
+/// `@bors unhold` is accepted as an alias of `@bors unpark`, because `@bors hold` holds a PR at the
+/// top of the queue instead of parking it, and has its own `@bors release-hold` counterpart.
+fn parser_park(command: &CommandPart<'_>, parts: &[CommandPart<'_>]) -> ParseResult {
+    match command {
+        CommandPart::Bare("park") => {
+            let reason = parts.iter().find_map(|part| match part {
+                CommandPart::KeyValue {
+                    key: "reason",
+                    value,
+                } => Some(value.to_string()),
+                _ => None,
+            });
+            Some(Ok(BorsCommand::Park { reason }))
+        }
+        CommandPart::Bare("unpark" | "unhold") => Some(Ok(BorsCommand::Unpark)),
+        _ => None,
+    }
+}
+
+/// Parses `@bors env <key>=<value> [<key>=<value>...]` and `@bors env-`
+fn parser_build_env(command: &CommandPart<'_>, parts: &[CommandPart<'_>]) -> ParseResult {
+    match command {
+        CommandPart::Bare("env") => {
+            let mut vars = vec![];
+            for part in parts {
+                match part {
+                    CommandPart::KeyValue { key, value } => {
+                        if !key
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                        {
+                            return Some(Err(CommandParseError::ValidationError(format!(
+                                "Build parameter `{key}` has to consist of letters, digits, `-` and `_`"
+                            ))));
+                        }
+                        vars.push((key.to_string(), value.to_string()));
+                    }
+                    CommandPart::Bare(arg) => {
+                        return Some(Err(CommandParseError::UnknownArg(arg.to_string())));
+                    }
+                }
+            }
+            if vars.is_empty() {
+                return Some(Err(CommandParseError::ValidationError(
+                    "Specify the build parameters as `<key>=<value>`, or use `env-` to remove them"
+                        .to_string(),
+                )));
+            }
+            Some(Ok(BorsCommand::SetBuildEnv { vars }))
+        }
+        CommandPart::Bare("env-") => Some(Ok(BorsCommand::ClearBuildEnv)),
+        _ => None,
+    }
+}
+
+/// Parses `@bors sync`
+fn parser_sync(command: &CommandPart<'_>, _parts: &[CommandPart<'_>]) -> ParseResult {
+    if let CommandPart::Bare("sync") = command {
+        Some(Ok(BorsCommand::Sync))
+    } else {
+        None
+    }
+}
+
+/// Parses `@bors reload`
+fn parser_reload(command: &CommandPart<'_>, _parts: &[CommandPart<'_>]) -> ParseResult {
+    if let CommandPart::Bare("reload") = command {
+        Some(Ok(BorsCommand::Reload))
+    } else {
+        None
+    }
+}
+
+/// Parses `@bors history`
+fn parser_history(command: &CommandPart<'_>, _parts: &[CommandPart<'_>]) -> ParseResult {
+    if let CommandPart::Bare("history") = command {
+        Some(Ok(BorsCommand::History))
+    } else {
+        None
+    }
+}
+
+/// Parses `@bors revert [p=<priority>]`
+fn parser_revert(command: &CommandPart<'_>, parts: &[CommandPart<'_>]) -> ParseResult {
+    if let CommandPart::Bare("revert") = command {
+        match parse_priority(parts) {
+            Some(Ok(priority)) => Some(Ok(BorsCommand::Revert {
+                priority: Some(priority),
+            })),
+            Some(Err(error)) => Some(Err(error)),
+            None => Some(Ok(BorsCommand::Revert { priority: None })),
+        }
+    } else {
+        None
+    }
+}
+
+/// Parses `@bors backport to=<branch>`
+fn parser_backport(command: &CommandPart<'_>, parts: &[CommandPart<'_>]) -> ParseResult {
+    if let CommandPart::Bare("backport") = command {
+        let to = parts.iter().find_map(|part| match part {
+            CommandPart::KeyValue { key: "to", value } => Some(value.to_string()),
+            _ => None,
+        });
+        match to {
+            Some(to) => Some(Ok(BorsCommand::Backport { to })),
+            None => Some(Err(CommandParseError::MissingArgValue {
+                arg: "to".to_string(),
+            })),
+        }
+    } else {
+        None
+    }
+}
+
+/// Parses the first occurrence of `for=<duration>` in `parts`.
+fn parse_for_duration(parts: &[CommandPart<'_>]) -> ParseResult<Duration> {
+    parts
+        .iter()
+        .filter_map(|part| match part {
+            CommandPart::KeyValue { key: "for", value } => Some(parse_duration(value)),
+            _ => None,
+        })
+        .next()
+}
+
+/// Parses a duration such as `30m`, `2h` or `1d`.
+fn parse_duration(value: &str) -> Result<Duration, CommandParseError> {
+    let error = || {
+        CommandParseError::ValidationError(format!(
+            "Invalid duration `{value}`. Use a number followed by s, m, h or d, e.g. `2h`"
+        ))
+    };
+    let split = value.len() - value.chars().last().map_or(0, |c| c.len_utf8());
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount.parse().map_err(|_| error())?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(error()),
+    };
+    if amount == 0 {
+        return Err(error());
+    }
+    amount
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(error)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::bors::command::parser::{CommandParseError, CommandParser};
     use crate::bors::command::{Approver, BorsCommand, Parent, RollupMode};
     use crate::database::DelegatedPermission;
     use crate::github::CommitSha;
+    use std::collections::HashMap;
+    use std::time::Duration;
 
     #[test]
     fn no_commands() {
@@ -503,6 +874,8 @@ mod tests {
                 approver: Approver::Myself,
                 priority: None,
                 rollup: None,
+                squash: false,
+                commit: None,
             })
         );
     }
@@ -515,32 +888,106 @@ mod tests {
         Ok(
             Approve {
                 approver: Specified(
-                    "user1",
+                    [
+                        "user1",
+                    ],
                 ),
                 priority: None,
                 rollup: None,
+                squash: false,
+                commit: None,
             },
         )
         "#);
     }
 
     #[test]
-    fn parse_approve_on_behalf_of_only_one_approver() {
-        let cmds = parse_commands("@bors r=user1,user2");
+    fn parse_approve_on_behalf_of_multiple_approvers() {
+        let cmds = parse_commands("@bors r=user1,user2,user1");
         assert_eq!(cmds.len(), 1);
         insta::assert_debug_snapshot!(cmds[0], @r#"
         Ok(
             Approve {
                 approver: Specified(
-                    "user1,user2",
+                    [
+                        "user1",
+                        "user2",
+                    ],
                 ),
                 priority: None,
                 rollup: None,
+                squash: false,
+                commit: None,
             },
         )
         "#);
     }
 
+    #[test]
+    fn parse_approve_commit() {
+        let cmds = parse_commands("@bors r+ ea9c1b050cc8b420c2c211d2177811e564a4dc60");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Approve {
+                approver: Approver::Myself,
+                priority: None,
+                rollup: None,
+                squash: false,
+                commit: Some(CommitSha(
+                    "ea9c1b050cc8b420c2c211d2177811e564a4dc60".to_string()
+                )),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_approve_commit_on_behalf_with_priority() {
+        let cmds = parse_commands("@bors r=user1 p=2 ea9c1b050cc8b420c2c211d2177811e564a4dc60");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Approve {
+                approver: Approver::Specified(vec!["user1".to_string()]),
+                priority: Some(2),
+                rollup: None,
+                squash: false,
+                commit: Some(CommitSha(
+                    "ea9c1b050cc8b420c2c211d2177811e564a4dc60".to_string()
+                )),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_approve_abbreviated_commit() {
+        let cmds = parse_commands("@bors r+ EA9C1B0");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Approve {
+                approver: Approver::Myself,
+                priority: None,
+                rollup: None,
+                squash: false,
+                commit: Some(CommitSha("ea9c1b0".to_string())),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_approve_too_long_commit() {
+        let cmds = parse_commands("@bors r+ ea9c1b050cc8b420c2c211d2177811e564a4dc60a");
+        assert_eq!(cmds.len(), 1);
+        insta::assert_debug_snapshot!(cmds[0], @r#"
+        Err(
+            ValidationError(
+                "Approved commit has to be a valid commit SHA: SHA must have at most 40 characters",
+            ),
+        )
+        "#);
+    }
+
     #[test]
     fn parse_approve_empty_reviewer() {
         let cmds = parse_commands("@bors r=");
@@ -554,6 +1001,19 @@ mod tests {
         "#);
     }
 
+    #[test]
+    fn parse_approve_only_commas() {
+        let cmds = parse_commands("@bors r=,");
+        assert_eq!(cmds.len(), 1);
+        insta::assert_debug_snapshot!(cmds[0], @r#"
+        Err(
+            MissingArgValue {
+                arg: "r",
+            },
+        )
+        "#);
+    }
+
     #[test]
     fn parse_approve_space_after_r() {
         let cmds = parse_commands("@bors r= user1");
@@ -576,7 +1036,25 @@ mod tests {
             Ok(BorsCommand::Approve {
                 approver: Approver::Myself,
                 priority: Some(1),
-                rollup: None
+                rollup: None,
+                squash: false,
+                commit: None,
+            })
+        )
+    }
+
+    #[test]
+    fn parse_approve_squash() {
+        let cmds = parse_commands("@bors r+ squash");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Approve {
+                approver: Approver::Myself,
+                priority: None,
+                rollup: None,
+                squash: true,
+                commit: None,
             })
         )
     }
@@ -588,9 +1066,11 @@ mod tests {
         assert_eq!(
             cmds[0],
             Ok(BorsCommand::Approve {
-                approver: Approver::Specified("user1".to_string()),
+                approver: Approver::Specified(vec!["user1".to_string()]),
                 priority: Some(2),
-                rollup: None
+                rollup: None,
+                squash: false,
+                commit: None,
             })
         )
     }
@@ -609,15 +1089,19 @@ mod tests {
             Ok(BorsCommand::Approve {
                 approver: Approver::Myself,
                 priority: Some(1),
-                rollup: None
+                rollup: None,
+                squash: false,
+                commit: None,
             })
         );
         assert_eq!(
             cmds[1],
             Ok(BorsCommand::Approve {
-                approver: Approver::Specified("user2".to_string()),
+                approver: Approver::Specified(vec!["user2".to_string()]),
                 priority: Some(2),
-                rollup: None
+                rollup: None,
+                squash: false,
+                commit: None,
             })
         );
     }
@@ -642,9 +1126,11 @@ mod tests {
         assert_eq!(
             cmds[0],
             Ok(BorsCommand::Approve {
-                approver: Approver::Specified("user1".to_string()),
+                approver: Approver::Specified(vec!["user1".to_string()]),
                 priority: Some(2),
-                rollup: None
+                rollup: None,
+                squash: false,
+                commit: None,
             })
         )
     }
@@ -767,7 +1253,9 @@ mod tests {
             Ok(BorsCommand::Approve {
                 approver: Approver::Myself,
                 priority: None,
-                rollup: Some(RollupMode::Always)
+                rollup: Some(RollupMode::Always),
+                squash: false,
+                commit: None,
             })
         )
     }
@@ -779,9 +1267,11 @@ mod tests {
         assert_eq!(
             cmds[0],
             Ok(BorsCommand::Approve {
-                approver: Approver::Specified("user1".to_string()),
+                approver: Approver::Specified(vec!["user1".to_string()]),
                 priority: None,
-                rollup: Some(RollupMode::Never)
+                rollup: Some(RollupMode::Never),
+                squash: false,
+                commit: None,
             })
         )
     }
@@ -793,9 +1283,11 @@ mod tests {
         assert_eq!(
             cmds[0],
             Ok(BorsCommand::Approve {
-                approver: Approver::Specified("user1".to_string()),
+                approver: Approver::Specified(vec!["user1".to_string()]),
                 priority: None,
-                rollup: Some(RollupMode::Always)
+                rollup: Some(RollupMode::Always),
+                squash: false,
+                commit: None,
             })
         )
     }
@@ -807,9 +1299,11 @@ mod tests {
         assert_eq!(
             cmds[0],
             Ok(BorsCommand::Approve {
-                approver: Approver::Specified("user1".to_string()),
+                approver: Approver::Specified(vec!["user1".to_string()]),
                 priority: None,
-                rollup: Some(RollupMode::Maybe)
+                rollup: Some(RollupMode::Maybe),
+                squash: false,
+                commit: None,
             })
         )
     }
@@ -828,15 +1322,19 @@ mod tests {
             Ok(BorsCommand::Approve {
                 approver: Approver::Myself,
                 priority: None,
-                rollup: Some(RollupMode::Always)
+                rollup: Some(RollupMode::Always),
+                squash: false,
+                commit: None,
             })
         );
         assert_eq!(
             cmds[1],
             Ok(BorsCommand::Approve {
-                approver: Approver::Specified("user2".to_string()),
+                approver: Approver::Specified(vec!["user2".to_string()]),
                 priority: None,
-                rollup: Some(RollupMode::Iffy)
+                rollup: Some(RollupMode::Iffy),
+                squash: false,
+                commit: None,
             })
         );
     }
@@ -930,7 +1428,9 @@ mod tests {
             Ok(BorsCommand::Approve {
                 approver: Approver::Myself,
                 priority: Some(1),
-                rollup: Some(RollupMode::Always)
+                rollup: Some(RollupMode::Always),
+                squash: false,
+                commit: None,
             })
         );
     }
@@ -944,7 +1444,9 @@ mod tests {
             Ok(BorsCommand::Approve {
                 approver: Approver::Myself,
                 priority: Some(1),
-                rollup: Some(RollupMode::Iffy)
+                rollup: Some(RollupMode::Iffy),
+                squash: false,
+                commit: None,
             })
         );
     }
@@ -974,14 +1476,21 @@ mod tests {
     fn parse_ping() {
         let cmds = parse_commands("@bors ping");
         assert_eq!(cmds.len(), 1);
-        assert!(matches!(cmds[0], Ok(BorsCommand::Ping)));
+        assert!(matches!(cmds[0], Ok(BorsCommand::Ping { health: false })));
+    }
+
+    #[test]
+    fn parse_ping_health() {
+        let cmds = parse_commands("@bors ping health");
+        assert_eq!(cmds.len(), 1);
+        assert!(matches!(cmds[0], Ok(BorsCommand::Ping { health: true })));
     }
 
     #[test]
     fn parse_ping_unknown_arg() {
         let cmds = parse_commands("@bors ping a");
         assert_eq!(cmds.len(), 1);
-        assert!(matches!(cmds[0], Ok(BorsCommand::Ping)));
+        assert!(matches!(cmds[0], Ok(BorsCommand::Ping { health: false })));
     }
 
     #[test]
@@ -999,6 +1508,7 @@ line two
             Try {
                 parent: None,
                 jobs: [],
+                commit: None,
             },
         )
         "###);
@@ -1013,6 +1523,7 @@ line two
             Try {
                 parent: None,
                 jobs: [],
+                commit: None,
             },
         )
         "###);
@@ -1028,7 +1539,8 @@ line two
                 parent: Some(Parent::CommitSha(CommitSha(
                     "ea9c1b050cc8b420c2c211d2177811e564a4dc60".to_string()
                 ))),
-                jobs: Vec::new()
+                jobs: Vec::new(),
+                commit: None,
             })
         );
     }
@@ -1041,22 +1553,66 @@ line two
             cmds[0],
             Ok(BorsCommand::Try {
                 parent: Some(Parent::Last),
-                jobs: Vec::new()
+                jobs: Vec::new(),
+                commit: None,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_try_parent_branch() {
+        let cmds = parse_commands("@bors try parent=release/1.90");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Try {
+                parent: Some(Parent::Branch("release/1.90".to_string())),
+                jobs: Vec::new(),
+                commit: None,
             })
         );
     }
 
     #[test]
     fn parse_try_parent_invalid() {
-        let cmds = parse_commands("@bors try parent=foo");
+        let cmds = parse_commands("@bors try parent=foo..bar");
         assert_eq!(cmds.len(), 1);
-        insta::assert_debug_snapshot!(cmds[0], @r###"
+        insta::assert_debug_snapshot!(cmds[0], @r#"
         Err(
             ValidationError(
-                "Try parent has to be a valid commit SHA: SHA must have exactly 40 characters",
+                "Try parent has to be a commit SHA, `last` or a branch name, not `foo..bar`",
             ),
         )
-        "###);
+        "#);
+    }
+
+    #[test]
+    fn parse_try_commit() {
+        let cmds = parse_commands("@bors try commit=ea9c1b050cc8b420c2c211d2177811e564a4dc60");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Try {
+                parent: None,
+                jobs: Vec::new(),
+                commit: Some(CommitSha(
+                    "ea9c1b050cc8b420c2c211d2177811e564a4dc60".to_string()
+                )),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_try_commit_invalid() {
+        let cmds = parse_commands("@bors try commit=main");
+        assert_eq!(cmds.len(), 1);
+        insta::assert_debug_snapshot!(cmds[0], @r#"
+        Err(
+            ValidationError(
+                "Try commit has to be a commit SHA, not `main`",
+            ),
+        )
+        "#);
     }
 
     #[test]
@@ -1067,7 +1623,8 @@ line two
             cmds[0],
             Ok(BorsCommand::Try {
                 parent: None,
-                jobs: vec!["ci".to_string(), "lint".to_string()]
+                jobs: vec!["ci".to_string(), "lint".to_string()],
+                commit: None,
             })
         );
     }
@@ -1080,7 +1637,8 @@ line two
             cmds[0],
             Ok(BorsCommand::Try {
                 parent: None,
-                jobs: vec!["ci-1".to_string(), "lint_2".to_string(), "foo*".to_string()]
+                jobs: vec!["ci-1".to_string(), "lint_2".to_string(), "foo*".to_string()],
+                commit: None,
             })
         );
     }
@@ -1094,7 +1652,8 @@ line two
             cmds[0],
             Ok(BorsCommand::Try {
                 parent: None,
-                jobs: vec!["*x86_64-msvc*".to_string()]
+                jobs: vec!["*x86_64-msvc*".to_string()],
+                commit: None,
             })
         );
     }
@@ -1109,7 +1668,8 @@ line two
             cmds[0],
             Ok(BorsCommand::Try {
                 parent: None,
-                jobs: vec!["*x86_64-msvc*".to_string(), "foo*".to_string()]
+                jobs: vec!["*x86_64-msvc*".to_string(), "foo*".to_string()],
+                commit: None,
             })
         );
     }
@@ -1154,6 +1714,7 @@ for the crater",
             Try {
                 parent: None,
                 jobs: [],
+                commit: None,
             },
         )
         ");
@@ -1186,6 +1747,7 @@ for the crater",
             Try {
                 parent: None,
                 jobs: [],
+                commit: None,
             },
         )
         "###)
@@ -1209,21 +1771,89 @@ for the crater",
     fn parse_retry() {
         let cmds = parse_commands("@bors retry");
         assert_eq!(cmds.len(), 1);
-        assert!(matches!(cmds[0], Ok(BorsCommand::Retry)));
+        assert!(matches!(
+            cmds[0],
+            Ok(BorsCommand::Retry {
+                cancel_workflows: false,
+                reason: None
+            })
+        ));
+    }
+
+    #[test]
+    fn parse_notify() {
+        let cmds = parse_commands("@bors notify");
+        assert_eq!(cmds.len(), 1);
+        assert!(matches!(cmds[0], Ok(BorsCommand::Notify)));
+    }
+
+    #[test]
+    fn parse_retry_cancel_workflows() {
+        let cmds = parse_commands("@bors retry cancel-workflows");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Retry {
+                cancel_workflows: true,
+                reason: None
+            })
+        );
+    }
+
+    #[test]
+    fn parse_retry_reason() {
+        let cmds = parse_commands(r#"@bors retry reason="network timeout""#);
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Retry {
+                cancel_workflows: false,
+                reason: Some("network timeout".to_string())
+            })
+        );
     }
 
     #[test]
     fn parse_retry_unknown_arg() {
         let cmds = parse_commands("@bors retry xyz");
         assert_eq!(cmds.len(), 1);
-        assert!(matches!(cmds[0], Ok(BorsCommand::Retry)));
+        assert!(matches!(
+            cmds[0],
+            Ok(BorsCommand::Retry {
+                cancel_workflows: false,
+                reason: None
+            })
+        ));
     }
 
     #[test]
     fn parse_try_cancel() {
         let cmds = parse_commands("@bors try cancel");
         assert_eq!(cmds.len(), 1);
-        assert!(matches!(cmds[0], Ok(BorsCommand::TryCancel)));
+        assert!(matches!(
+            cmds[0],
+            Ok(BorsCommand::TryCancel { build_id: None })
+        ));
+    }
+
+    #[test]
+    fn parse_try_cancel_build_id() {
+        let cmds = parse_commands("@bors try cancel 12");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::TryCancel { build_id: Some(12) }));
+    }
+
+    #[test]
+    fn parse_try_cancel_invalid_build_id() {
+        let cmds = parse_commands("@bors try cancel abc");
+        assert_eq!(cmds.len(), 1);
+        insta::assert_debug_snapshot!(cmds[0], @r#"
+        Err(
+            ValidationError(
+                "Try build ID has to be a number, not `abc`",
+            ),
+        )
+        "#);
     }
 
     #[test]
@@ -1232,7 +1862,10 @@ for the crater",
         assert_eq!(cmds.len(), 1);
         assert!(matches!(
             cmds[0],
-            Ok(BorsCommand::SetDelegate(DelegatedPermission::Review))
+            Ok(BorsCommand::SetDelegate {
+                permission: DelegatedPermission::Review,
+                duration: None
+            })
         ));
     }
 
@@ -1242,7 +1875,10 @@ for the crater",
         assert_eq!(cmds.len(), 1);
         assert!(matches!(
             cmds[0],
-            Ok(BorsCommand::SetDelegate(DelegatedPermission::Review))
+            Ok(BorsCommand::SetDelegate {
+                permission: DelegatedPermission::Review,
+                duration: None
+            })
         ));
     }
 
@@ -1252,7 +1888,33 @@ for the crater",
         assert_eq!(cmds.len(), 1);
         assert!(matches!(
             cmds[0],
-            Ok(BorsCommand::SetDelegate(DelegatedPermission::Try))
+            Ok(BorsCommand::SetDelegate {
+                permission: DelegatedPermission::Try,
+                duration: None
+            })
+        ));
+    }
+
+    #[test]
+    fn parse_delegate_with_duration() {
+        let cmds = parse_commands("@bors delegate=review for=7d");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::SetDelegate {
+                permission: DelegatedPermission::Review,
+                duration: Some(Duration::from_secs(7 * 24 * 60 * 60))
+            })
+        );
+    }
+
+    #[test]
+    fn parse_delegate_invalid_duration() {
+        let cmds = parse_commands("@bors delegate+ for=forever");
+        assert_eq!(cmds.len(), 1);
+        assert!(matches!(
+            cmds[0],
+            Err(CommandParseError::ValidationError(_))
         ));
     }
 
@@ -1269,7 +1931,10 @@ for the crater",
         assert_eq!(cmds.len(), 1);
         assert_eq!(
             cmds[0],
-            Ok(BorsCommand::SetDelegate(DelegatedPermission::Review))
+            Ok(BorsCommand::SetDelegate {
+                permission: DelegatedPermission::Review,
+                duration: None
+            })
         );
     }
 
@@ -1290,7 +1955,85 @@ for the crater",
     fn parse_tree_closed() {
         let cmds = parse_commands("@bors treeclosed=5");
         assert_eq!(cmds.len(), 1);
-        assert_eq!(cmds[0], Ok(BorsCommand::TreeClosed(5)));
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::TreeClosed {
+                priority: 5,
+                duration: None,
+                reason: None,
+                branch: None
+            })
+        );
+    }
+
+    #[test]
+    fn parse_tree_closed_with_duration_and_reason() {
+        let cmds = parse_commands(r#"@bors treeclosed=100 for=2h reason="CI outage""#);
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::TreeClosed {
+                priority: 100,
+                duration: Some(Duration::from_secs(2 * 60 * 60)),
+                reason: Some("CI outage".to_string()),
+                branch: None
+            })
+        );
+    }
+
+    #[test]
+    fn parse_tree_closed_unquoted_reason() {
+        let cmds = parse_commands("@bors treeclosed=100 reason=outage");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::TreeClosed {
+                priority: 100,
+                duration: None,
+                reason: Some("outage".to_string()),
+                branch: None
+            })
+        );
+    }
+
+    #[test]
+    fn parse_tree_closed_branch() {
+        let cmds = parse_commands("@bors treeclosed=100 branch=beta");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::TreeClosed {
+                priority: 100,
+                duration: None,
+                reason: None,
+                branch: Some("beta".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn parse_tree_open_branch() {
+        let cmds = parse_commands("@bors treeopen branch=beta");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::OpenTree {
+                branch: Some("beta".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn parse_tree_closed_invalid_duration() {
+        let cmds = parse_commands("@bors treeclosed=100 for=2w");
+        assert_eq!(cmds.len(), 1);
+        insta::assert_debug_snapshot!(cmds[0], @r#"
+        Err(
+            ValidationError(
+                "Invalid duration `2w`. Use a number followed by s, m, h or d, e.g. `2h`",
+            ),
+        )
+        "#);
     }
 
     #[test]
@@ -1319,14 +2062,14 @@ for the crater",
     fn parse_tree_closed_minus() {
         let cmds = parse_commands("@bors treeclosed-");
         assert_eq!(cmds.len(), 1);
-        assert_eq!(cmds[0], Ok(BorsCommand::OpenTree));
+        assert_eq!(cmds[0], Ok(BorsCommand::OpenTree { branch: None }));
     }
 
     #[test]
     fn parse_tree_closed_minus_alias() {
         let cmds = parse_commands("@bors treeopen");
         assert_eq!(cmds.len(), 1);
-        assert_eq!(cmds[0], Ok(BorsCommand::OpenTree));
+        assert_eq!(cmds[0], Ok(BorsCommand::OpenTree { branch: None }));
     }
 
     #[test]
@@ -1339,6 +2082,226 @@ for the crater",
         );
     }
 
+    #[test]
+    fn parse_queue_pause() {
+        let cmds = parse_commands("@bors queue pause");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::PauseQueue));
+    }
+
+    #[test]
+    fn parse_queue_resume() {
+        let cmds = parse_commands("@bors queue resume");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::ResumeQueue));
+    }
+
+    #[test]
+    fn parse_queue_missing_action() {
+        let cmds = parse_commands("@bors queue");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Err(CommandParseError::ValidationError(
+                "Use `queue pause` or `queue resume`".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_hold() {
+        let cmds = parse_commands("@bors hold");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Hold));
+    }
+
+    #[test]
+    fn parse_release_hold() {
+        let cmds = parse_commands("@bors release-hold");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::ReleaseHold));
+    }
+
+    #[test]
+    fn parse_park() {
+        let cmds = parse_commands("@bors park");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Park { reason: None }));
+    }
+
+    #[test]
+    fn parse_park_reason() {
+        let cmds = parse_commands(r#"@bors park reason="waiting for the release""#);
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Park {
+                reason: Some("waiting for the release".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn parse_unpark() {
+        let cmds = parse_commands("@bors unpark");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Unpark));
+    }
+
+    #[test]
+    fn parse_unhold() {
+        let cmds = parse_commands("@bors unhold");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Unpark));
+    }
+
+    #[test]
+    fn parse_build_env() {
+        let cmds = parse_commands("@bors env profile=debug-assertions target_cpu=native");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::SetBuildEnv {
+                vars: vec![
+                    ("profile".to_string(), "debug-assertions".to_string()),
+                    ("target_cpu".to_string(), "native".to_string())
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn parse_build_env_missing_vars() {
+        let cmds = parse_commands("@bors env");
+        assert_eq!(cmds.len(), 1);
+        insta::assert_debug_snapshot!(cmds[0], @r#"
+            Err(
+                ValidationError(
+                    "Specify the build parameters as `<key>=<value>`, or use `env-` to remove them",
+                ),
+            )
+            "#);
+    }
+
+    #[test]
+    fn parse_build_env_invalid_key() {
+        let cmds = parse_commands("@bors env pro.file=debug");
+        assert_eq!(cmds.len(), 1);
+        insta::assert_debug_snapshot!(cmds[0], @r#"
+            Err(
+                ValidationError(
+                    "Build parameter `pro.file` has to consist of letters, digits, `-` and `_`",
+                ),
+            )
+            "#);
+    }
+
+    #[test]
+    fn parse_clear_build_env() {
+        let cmds = parse_commands("@bors env-");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::ClearBuildEnv));
+    }
+
+    #[test]
+    fn parse_sync() {
+        let cmds = parse_commands("@bors sync");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Sync));
+    }
+
+    #[test]
+    fn parse_reload() {
+        let cmds = parse_commands("@bors reload");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Reload));
+    }
+
+    #[test]
+    fn parse_history() {
+        let cmds = parse_commands("@bors history");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::History));
+    }
+
+    #[test]
+    fn parse_revert() {
+        let cmds = parse_commands("@bors revert");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Revert { priority: None }));
+    }
+
+    #[test]
+    fn parse_revert_with_priority() {
+        let cmds = parse_commands("@bors revert p=100");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Revert {
+                priority: Some(100)
+            })
+        );
+    }
+
+    #[test]
+    fn parse_backport() {
+        let cmds = parse_commands("@bors backport to=beta");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Backport {
+                to: "beta".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_backport_missing_branch() {
+        let cmds = parse_commands("@bors backport");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Err(CommandParseError::MissingArgValue {
+                arg: "to".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_alias() {
+        let aliases = HashMap::from([("lgtm".to_string(), "r+ rollup=never".to_string())]);
+        let cmds = CommandParser::new("@bors".to_string().into())
+            .parse_commands_with_aliases("@bors lgtm p=1", &aliases);
+        assert_eq!(cmds.len(), 1);
+        insta::assert_debug_snapshot!(cmds[0], @r"
+        Ok(
+            Approve {
+                approver: Myself,
+                priority: Some(
+                    1,
+                ),
+                rollup: Some(
+                    Never,
+                ),
+                squash: false,
+                commit: None,
+            },
+        )
+        ");
+    }
+
+    #[test]
+    fn parse_alias_matches_whole_command() {
+        let aliases = HashMap::from([("lgtm".to_string(), "r+".to_string())]);
+        let cmds = CommandParser::new("@bors".to_string().into())
+            .parse_commands_with_aliases("@bors lgtm2", &aliases);
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Err(CommandParseError::UnknownCommand("lgtm2".to_string()))
+        );
+    }
+
     #[test]
     fn parse_in_html_command() {
         let cmds = parse_commands(
@@ -1354,7 +2317,8 @@ I am markdown HTML comment
             cmds[0],
             Ok(BorsCommand::Try {
                 parent: None,
-                jobs: vec![]
+                jobs: vec![],
+                commit: None,
             })
         );
     }
@@ -1,14 +1,21 @@
+use chrono::{DateTime, Utc};
 use itertools::Itertools;
 use octocrab::models::workflows::{Conclusion, Job};
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::time::Duration;
 
 use crate::bors::FailedWorkflowRun;
 use crate::bors::command::CommandPrefix;
-use crate::github::GithubRepoName;
-use crate::utils::text::pluralize;
+use crate::bors::health::{ComponentHealth, HealthReport};
+use crate::bors::localization::{Language, Message};
+use crate::config::UnreachableParentPolicy;
+use crate::github::{GithubRepoName, PullRequestNumber};
+use crate::utils::text::{
+    format_size, pluralize, replace_emoji_shortcodes, strip_emoji, strip_markdown_emphasis,
+};
 use crate::{
-    database::{WorkflowModel, WorkflowStatus},
+    database::{BuildArtifactModel, TreeState, WorkflowModel, WorkflowStatus},
     github::CommitSha,
 };
 
@@ -16,6 +23,8 @@ use crate::{
 pub struct Comment {
     text: String,
     metadata: Option<CommentMetadata>,
+    /// If set, the comment is recorded once it is posted, so that it can be hidden later.
+    tag: Option<CommentTag>,
 }
 
 #[derive(Serialize)]
@@ -24,10 +33,45 @@ pub enum CommentMetadata {
     TryBuildCompleted { merge_sha: String },
 }
 
+/// Formatting profile of the comments posted by bors.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CommentStyle {
+    /// Comments use emoji and Markdown formatting, in the style of homu.
+    #[default]
+    Emoji,
+    /// Comments do not contain emoji.
+    Plain,
+    /// Comments do not contain emoji, headings or bold text.
+    MinimalMarkdown,
+}
+
+/// Returns the status word describing the information conveyed by the given emoji shortcode.
+fn status_word(shortcode: &str) -> Option<&'static str> {
+    let word = match shortcode {
+        "sunny" | "white_check_mark" => "SUCCESS",
+        "broken_heart" | "x" | "boom" | "eyes" => "FAILURE",
+        "hourglass" | "question" => "PENDING",
+        "pushpin" => "APPROVED",
+        "warning" => "WARNING",
+        "exclamation" => "ERROR",
+        "key" | "lock" => "DENIED",
+        "clipboard" => "NOTE",
+        _ => return None,
+    };
+    Some(word)
+}
+
 /// A tag for a comment, used to identify the comment.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, serde::Deserialize)]
 pub enum CommentTag {
     TryBuildStarted,
+    /// The result of a try build.
+    TryBuildCompleted,
+    /// An auto build was started.
+    AutoBuildStarted,
+    /// An auto build has failed.
+    AutoBuildFailed,
 }
 
 impl Comment {
@@ -35,9 +79,45 @@ impl Comment {
         Self {
             text,
             metadata: None,
+            tag: None,
+        }
+    }
+
+    /// Tag the comment, so that it can be found and hidden once it becomes outdated.
+    pub fn with_tag(self, tag: CommentTag) -> Self {
+        Self {
+            tag: Some(tag),
+            ..self
         }
     }
 
+    pub fn tag(&self) -> Option<CommentTag> {
+        self.tag
+    }
+
+    /// Reformat the text of the comment according to the given `style`.
+    pub fn with_style(self, style: CommentStyle) -> Self {
+        let text = match style {
+            CommentStyle::Emoji => self.text,
+            CommentStyle::Plain => strip_emoji(&self.text),
+            CommentStyle::MinimalMarkdown => strip_markdown_emphasis(&strip_emoji(&self.text)),
+        };
+        Self { text, ..self }
+    }
+
+    /// Replace emoji that convey information with explicit status words (e.g. `SUCCESS`),
+    /// and remove all other emoji, so that the comment can be understood with a screen reader.
+    pub fn with_status_words(self) -> Self {
+        let text = strip_emoji(&replace_emoji_shortcodes(&self.text, status_word));
+        Self { text, ..self }
+    }
+
+    /// Append a signature line to the end of the comment.
+    pub fn with_signature(self, signature: &str) -> Self {
+        let text = format!("{}\n\n{signature}", self.text);
+        Self { text, ..self }
+    }
+
     pub fn render(&self) -> String {
         if let Some(metadata) = &self.metadata {
             format!(
@@ -53,8 +133,12 @@ impl Comment {
 
 pub fn try_build_succeeded_comment(
     workflows: &[WorkflowModel],
+    artifacts: &[BuildArtifactModel],
     commit_sha: CommitSha,
     parent_sha: CommitSha,
+    repo: &GithubRepoName,
+    pr: PullRequestNumber,
+    template: Option<&str>,
 ) -> Comment {
     use std::fmt::Write;
 
@@ -73,29 +157,210 @@ pub fn try_build_succeeded_comment(
         "Build commit: {commit_sha} (`{commit_sha}`, parent: `{parent_sha}`)",
     )
     .unwrap();
+    if !artifacts.is_empty() {
+        writeln!(text, "Artifacts: {}", format_artifacts(artifacts)).unwrap();
+    }
+    if let Some(template) = template {
+        let rendered = template
+            .replace("{sha}", &commit_sha.0)
+            .replace("{parent}", &parent_sha.0)
+            .replace("{pr}", &pr.to_string())
+            .replace("{repo}", &repo.to_string());
+        writeln!(text, "{}", rendered.trim_end()).unwrap();
+    }
 
     Comment {
         text,
         metadata: Some(CommentMetadata::TryBuildCompleted {
             merge_sha: commit_sha.to_string(),
         }),
+        tag: None,
     }
 }
 
-pub fn cant_find_last_parent_comment() -> Comment {
-    Comment::new(":exclamation: There was no previous build. Please set an explicit parent or remove the `parent=last` argument to use the default parent.".to_string())
+pub fn cant_find_last_parent_comment(language: Language) -> Comment {
+    Comment::new(Message::NoPreviousBuild.translate(language).to_string())
+}
+
+pub fn unknown_try_jobs_comment(unknown_jobs: &[&String], available_jobs: &[String]) -> Comment {
+    let unknown_jobs: Vec<String> = unknown_jobs.iter().map(|job| format!("`{job}`")).collect();
+    let available_jobs: Vec<String> = available_jobs
+        .iter()
+        .map(|job| format!("`{job}`"))
+        .collect();
+    Comment::new(format!(
+        ":exclamation: Unknown try jobs: {}. Available jobs: {}.",
+        unknown_jobs.join(", "),
+        available_jobs.join(", ")
+    ))
+}
+
+pub fn build_env_disabled_comment(language: Language) -> Comment {
+    Comment::new(Message::BuildEnvDisabled.translate(language).to_string())
+}
+
+pub fn build_env_not_allowed_comment(
+    key: &str,
+    value: &str,
+    allowed: &BTreeMap<String, Vec<String>>,
+    language: Language,
+) -> Comment {
+    let text = match allowed.get(key) {
+        Some(values) => Message::BuildEnvValueNotAllowed.format(
+            language,
+            &[
+                ("key", key),
+                ("value", value),
+                (
+                    "allowed",
+                    &values.iter().map(|value| format!("`{value}`")).join(", "),
+                ),
+            ],
+        ),
+        None => Message::UnknownBuildEnvParameter.format(
+            language,
+            &[
+                ("key", key),
+                (
+                    "allowed",
+                    &allowed.keys().map(|key| format!("`{key}`")).join(", "),
+                ),
+            ],
+        ),
+    };
+    Comment::new(text)
+}
+
+pub fn build_env_set_comment(build_env: &[String], language: Language) -> Comment {
+    let build_env = build_env.iter().map(|var| format!("`{var}`")).join(", ");
+    Comment::new(Message::BuildEnvSet.format(language, &[("build_env", &build_env)]))
+}
+
+pub fn build_env_cleared_comment(language: Language) -> Comment {
+    Comment::new(Message::BuildEnvCleared.translate(language).to_string())
+}
+
+pub fn no_build_env_comment(language: Language) -> Comment {
+    Comment::new(Message::NoBuildEnv.translate(language).to_string())
+}
+
+pub fn try_parent_branch_not_found_comment(branch: &str) -> Comment {
+    Comment::new(format!(
+        ":exclamation: Cannot find the parent branch `{branch}`. Please check that it exists in this repository."
+    ))
+}
+
+pub fn try_parent_unreachable_comment(
+    parent: &CommitSha,
+    base_branch: &str,
+    policy: UnreachableParentPolicy,
+) -> Comment {
+    let text = match policy {
+        UnreachableParentPolicy::Warn => format!(
+            ":warning: The parent commit `{parent}` is not reachable from the `{base_branch}` branch. The try build will be started, but its merge commit may contain changes that are not part of `{base_branch}`, or miss some of its changes."
+        ),
+        UnreachableParentPolicy::Refuse => format!(
+            ":exclamation: The parent commit `{parent}` is not reachable from the `{base_branch}` branch, so the try build was not started. Please use a commit of the `{base_branch}` branch as the parent."
+        ),
+    };
+    Comment::new(text)
+}
+
+pub fn try_commit_not_in_pr_comment(commit: &CommitSha) -> Comment {
+    Comment::new(format!(
+        ":exclamation: Commit {commit} is not a commit of this PR, so the try build was not started."
+    ))
+}
+
+pub fn fork_try_build_denied_comment(language: Language) -> Comment {
+    Comment::new(Message::ForkTryBuildDenied.translate(language).to_string())
+}
+
+pub fn fork_try_build_unrestricted_comment(
+    environment: &str,
+    jobs: &[String],
+    language: Language,
+) -> Comment {
+    let jobs = jobs.iter().map(|job| format!("`{job}`")).join(", ");
+    Comment::new(
+        Message::ForkTryBuildUnrestricted
+            .format(language, &[("environment", environment), ("jobs", &jobs)]),
+    )
+}
+
+pub fn draft_try_build_denied_comment(language: Language) -> Comment {
+    Comment::new(Message::DraftTryBuildDenied.translate(language).to_string())
+}
+
+pub fn try_builds_disabled_comment(language: Language) -> Comment {
+    Comment::new(Message::TryBuildsDisabled.translate(language).to_string())
 }
 
-pub fn no_try_build_in_progress_comment() -> Comment {
-    Comment::new(":exclamation: There is currently no try build in progress.".to_string())
+pub fn merge_queue_disabled_comment(language: Language) -> Comment {
+    Comment::new(Message::MergeQueueDisabled.translate(language).to_string())
+}
+
+pub fn no_try_build_in_progress_comment(language: Language) -> Comment {
+    Comment::new(
+        Message::NoTryBuildInProgress
+            .translate(language)
+            .to_string(),
+    )
 }
 
-pub fn try_build_cancelled_with_failed_workflow_cancel_comment() -> Comment {
+pub fn try_build_cancelled_with_failed_workflow_cancel_comment(language: Language) -> Comment {
     Comment::new(
-        "Try build was cancelled. It was not possible to cancel some workflows.".to_string(),
+        Message::TryBuildCancelledWithFailedWorkflowCancel
+            .translate(language)
+            .to_string(),
     )
 }
 
+pub fn retry_cancelled_workflows_comment(
+    build_kind: &str,
+    workflow_urls: impl Iterator<Item = String>,
+) -> Comment {
+    let mut comment = format!("Cancelled running workflows of the failed {build_kind} build:");
+    for url in workflow_urls {
+        comment += format!("\n- {url}").as_str();
+    }
+    Comment::new(comment)
+}
+
+pub fn build_retried_comment(build_kind: &str, reason: &str) -> Comment {
+    Comment::new(format!(
+        ":repeat: Retrying the failed {build_kind} build. Reason: {reason}"
+    ))
+}
+
+pub fn spurious_failure_retry_comment(
+    patterns: &[String],
+    attempt: u32,
+    max_attempts: u32,
+) -> Comment {
+    let patterns = patterns
+        .iter()
+        .map(|pattern| format!("`{pattern}`"))
+        .join(", ");
+    Comment::new(format!(
+        ":repeat: The auto build failed spuriously (matched {patterns}), retrying it (attempt {attempt}/{max_attempts})."
+    ))
+}
+
+pub fn rollup_failed_comment(
+    rollup: PullRequestNumber,
+    commit_sha: &CommitSha,
+    set_iffy: bool,
+) -> Comment {
+    let mut text = format!(
+        ":broken_heart: This PR was merged into the rollup #{rollup}, whose auto build ({commit_sha}) has failed. Please check whether the failure was caused by this PR."
+    );
+    if set_iffy {
+        text.push_str(" The rollup status of this PR was set to `iffy`.");
+    }
+    Comment::new(text)
+}
+
 pub fn try_build_cancelled_comment(workflow_urls: impl Iterator<Item = String>) -> Comment {
     let mut try_build_cancelled_comment =
         r#"Try build cancelled. Cancelled workflows:"#.to_string();
@@ -174,13 +439,32 @@ pub fn build_failed_comment(
 
 pub fn try_build_started_comment(
     head_sha: &CommitSha,
+    pr_head_sha: &CommitSha,
     merge_sha: &CommitSha,
     bot_prefix: &CommandPrefix,
     cancelled_workflow_urls: Vec<String>,
+    environment: Option<&str>,
+    concurrent_build_id: Option<i32>,
 ) -> Comment {
     use std::fmt::Write;
     let mut msg = format!(":hourglass: Trying commit {head_sha} with merge {merge_sha}…\n\n");
 
+    if head_sha != pr_head_sha {
+        writeln!(
+            msg,
+            ":information_source: This is an older commit of the PR, not its current head ({pr_head_sha}).\n"
+        )
+        .unwrap();
+    }
+
+    if let Some(environment) = environment {
+        writeln!(
+            msg,
+            ":lock: This PR comes from a fork, so its try build only runs workflow jobs that use the `{environment}` environment, and its try merge commit is marked with it.\n"
+        )
+        .unwrap();
+    }
+
     if !cancelled_workflow_urls.is_empty() {
         writeln!(
             msg,
@@ -189,10 +473,16 @@ pub fn try_build_started_comment(
         .unwrap();
     }
 
-    writeln!(
-        msg,
-        "To cancel the try build, run the command `{bot_prefix} try cancel`."
-    )
+    match concurrent_build_id {
+        Some(build_id) => writeln!(
+            msg,
+            "Other try builds of this PR are still running. To cancel only this try build, run the command `{bot_prefix} try cancel {build_id}`."
+        ),
+        None => writeln!(
+            msg,
+            "To cancel the try build, run the command `{bot_prefix} try cancel`."
+        ),
+    }
     .unwrap();
 
     Comment::new(msg)
@@ -209,6 +499,14 @@ pub fn append_workflow_links_to_comment(comment_content: &mut String, workflow_u
     }
 }
 
+pub fn already_merged_comment(base_branch: &str) -> Comment {
+    Comment::new(format!(
+        r":broom: The changes of this PR are already contained in the `{base_branch}` branch, e.g. because they were merged in a rollup or cherry-picked. Closing it instead of testing it again.
+
+Hint: If something is missing, reopen the PR, push the missing changes and approve it again."
+    ))
+}
+
 pub fn merge_conflict_comment(branch: &str) -> Comment {
     let message = format!(
         r#":lock: Merge conflict
@@ -244,27 +542,184 @@ handled during merge and rebase. This is normal, and you should still perform st
     Comment::new(message)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn approved_comment(
     web_url: &str,
     repo: &GithubRepoName,
     commit_sha: &CommitSha,
     reviewer: &str,
+    missing_approvals: Option<usize>,
+    full_queue_size: Option<usize>,
+    blocking_tree_state: Option<&TreeState>,
+    waiting_on_conflicts: bool,
+) -> Comment {
+    let queue_link = format!("[queue]({web_url}/queue/{})", repo.name());
+    let mut paragraphs = vec![format!(
+        ":pushpin: Commit {commit_sha} has been approved by `{reviewer}`"
+    )];
+    match (missing_approvals, full_queue_size) {
+        (Some(missing_approvals), _) => paragraphs.push(format!(
+            ":busts_in_silhouette: It needs {missing_approvals} more {} from other reviewers before it enters the {queue_link} for this repository.",
+            pluralize("approval", missing_approvals)
+        )),
+        (None, Some(max_queue_size)) => paragraphs.push(format!(
+            ":hourglass: The {queue_link} for this repository is full (it can contain at most {max_queue_size} {}), so this PR is waiting for queue space.",
+            pluralize("PR", max_queue_size)
+        )),
+        // The PR only enters the queue once its conflicts are resolved
+        (None, None) if waiting_on_conflicts => {}
+        (None, None) => paragraphs.push(format!(
+            "It is now in the {queue_link} for this repository."
+        )),
+    }
+    if let Some(tree_state) = blocking_tree_state.and_then(describe_closed_tree) {
+        paragraphs.push(format!(
+            ":no_entry: The tree is currently {tree_state}, so this PR will not be merged until the tree is reopened."
+        ));
+    }
+    if waiting_on_conflicts {
+        paragraphs.push(format!(
+            ":warning: This PR has merge conflicts, so it is waiting for them to be resolved. It will enter the {queue_link} once they are resolved. If the resolution changes the diff of the PR, it will need to be re-approved."
+        ));
+    }
+    let message = format!("{}\n", paragraphs.join("\n\n"));
+    Comment::new(message)
+}
+
+/// Describes a closed tree, e.g.
+/// `closed for PRs with priority less than 100 until 2025-08-22 12:00 UTC (reason: CI outage)`.
+/// Returns `None` if the tree is open.
+pub fn describe_closed_tree(tree_state: &TreeState) -> Option<String> {
+    let priority = tree_state.priority()?;
+    let mut description = format!("closed for PRs with priority less than {priority}");
+    if let Some(until) = tree_state.closed_until() {
+        description.push_str(&format!(" until {}", until.format("%Y-%m-%d %H:%M UTC")));
+    }
+    if let Some(reason) = tree_state.reason() {
+        description.push_str(&format!(" (reason: {reason})"));
+    }
+    Some(description)
+}
+
+pub fn tree_closed_blocked_pr_comment(
+    tree_state: &TreeState,
+    queue_branch: Option<&str>,
+) -> Comment {
+    let description = describe_closed_tree(tree_state).unwrap_or_default();
+    let tree = match queue_branch {
+        Some(branch) => format!("the tree of the `{branch}` branch"),
+        None => "the tree".to_string(),
+    };
+    let mut text = format!(
+        ":lock: This PR stays approved, but it will not be tested or merged for now, because {tree} is {description}."
+    );
+    if let Some(source) = tree_state.comment_source() {
+        text.push_str(&format!(" The tree was closed in {source}."));
+    }
+    Comment::new(text)
+}
+
+pub fn tree_reopened_unblocked_pr_comment(queue_branch: Option<&str>) -> Comment {
+    let tree = match queue_branch {
+        Some(branch) => format!("The tree of the `{branch}` branch"),
+        None => "The tree".to_string(),
+    };
+    Comment::new(format!(
+        ":unlock: {tree} is open again, so this PR is back in the merge queue."
+    ))
+}
+
+pub fn maintenance_window_started_comment(tree_state: &TreeState) -> Comment {
+    let description = describe_closed_tree(tree_state).unwrap_or_default();
+    Comment::new(format!(
+        ":construction: Tree {description} for a scheduled maintenance window."
+    ))
+}
+
+pub fn maintenance_window_ended_comment(language: Language) -> Comment {
+    Comment::new(
+        Message::MaintenanceWindowEnded
+            .translate(language)
+            .to_string(),
+    )
+}
+
+pub fn speculative_build_discarded_comment(language: Language) -> Comment {
+    Comment::new(
+        Message::SpeculativeBuildDiscarded
+            .translate(language)
+            .to_string(),
+    )
+}
+
+pub fn auto_build_base_moved_comment(
+    base_branch: &str,
+    parent: &str,
+    base_sha: &CommitSha,
 ) -> Comment {
     Comment::new(format!(
-        r":pushpin: Commit {commit_sha} has been approved by `{reviewer}`
+        ":recycle: The `{base_branch}` branch was updated from `{parent}` to `{base_sha}` after the auto build of this PR was started, so the tested commit cannot be merged. A new auto build will be started."
+    ))
+}
+
+pub fn queue_notification_registered_comment(username: &str, position: usize) -> Comment {
+    Comment::new(format!(
+        ":bell: `{username}` will be notified when this PR is among the first {position} PRs in the queue."
+    ))
+}
 
-It is now in the [queue]({web_url}/queue/{}) for this repository.
-",
+pub fn queue_position_notification_comment(
+    usernames: &[String],
+    position: usize,
+    web_url: &str,
+    repo: &GithubRepoName,
+) -> Comment {
+    let mentions = usernames.iter().map(|user| format!("@{user}")).join(" ");
+    Comment::new(format!(
+        "{mentions}: :bell: This PR is now at position {position} in the [queue]({web_url}/queue/{}). Please be around in case its build fails.",
         repo.name()
     ))
 }
 
-pub fn approve_non_open_pr_comment() -> Comment {
-    Comment::new(":clipboard: Only open, non-draft PRs can be approved.".to_string())
+pub fn approve_non_open_pr_comment(language: Language) -> Comment {
+    Comment::new(Message::ApproveNonOpenPr.translate(language).to_string())
 }
 
-pub fn unapprove_non_open_pr_comment() -> Comment {
-    Comment::new(":clipboard: Only unclosed PRs can be unapproved.".to_string())
+pub fn unapprove_non_open_pr_comment(language: Language) -> Comment {
+    Comment::new(Message::UnapproveNonOpenPr.translate(language).to_string())
+}
+
+/// Comment posted when an approved PR develops merge conflicts while it waits in the queue.
+pub fn approved_pr_conflicted_comment(base_branch: &str) -> Comment {
+    Comment::new(format!(
+        ":lock: This PR has developed merge conflicts with the `{base_branch}` branch, so it was removed from the queue. It will enter the queue again once they are resolved. If the resolution changes the diff of the PR, it will need to be re-approved."
+    ))
+}
+
+pub fn merge_conflicts_resolved_comment(web_url: &str, repo: &GithubRepoName) -> Comment {
+    Comment::new(format!(
+        ":arrow_forward: Merge conflicts were resolved, so this PR has entered the [queue]({web_url}/queue/{}).",
+        repo.name()
+    ))
+}
+
+/// Comment posted when the approval of a reopened PR is revoked.
+/// `new_head` is set if the approval was revoked because a new commit was pushed to the PR
+/// while it was closed.
+pub fn reopened_pr_unapproved_comment(new_head: Option<&CommitSha>) -> Comment {
+    let text = match new_head {
+        Some(head_sha) => format!(
+            ":warning: A new commit `{head_sha}` was pushed to the branch while the PR was closed, the PR will need to be re-approved."
+        ),
+        None => ":warning: The PR was reopened, it will need to be re-approved.".to_string(),
+    };
+    Comment::new(text)
+}
+
+pub fn approve_outdated_commit_comment(commit: &CommitSha, head_sha: &CommitSha) -> Comment {
+    Comment::new(format!(
+        ":clipboard: Commit {commit} is not the head of this PR (the head is {head_sha}), ignoring approval."
+    ))
 }
 
 pub fn approve_wip_title(keyword: &str) -> Comment {
@@ -287,26 +742,80 @@ pub fn approve_blocking_labels_present(blocking_labels: &[&str]) -> Comment {
     ))
 }
 
-pub fn delegate_try_builds_comment(delegatee: &str, bot_prefix: &CommandPrefix) -> Comment {
+pub fn approve_unchecked_items(items: &[&str]) -> Comment {
+    let items = items.iter().map(|item| format!("- [ ] {item}")).join("\n");
+    Comment::new(format!(
+        ":clipboard: This PR cannot be approved because the following items of the PR template are not checked:\n{items}\n\nHint: Tick them in the PR description, or explain why they do not apply."
+    ))
+}
+
+pub fn approve_lint_violations(violations: &[String]) -> Comment {
+    let violations = violations
+        .iter()
+        .map(|violation| format!("- {violation}"))
+        .join("\n");
+    Comment::new(format!(
+        ":clipboard: This PR cannot be approved because its title or description does not follow the rules of this repository:\n{violations}\n\nHint: Edit the PR title or description and approve it again."
+    ))
+}
+
+pub fn auto_build_lint_violations_comment(violations: &[String]) -> Comment {
+    let violations = violations
+        .iter()
+        .map(|violation| format!("- {violation}"))
+        .join("\n");
+    Comment::new(format!(
+        ":clipboard: The title or description of this PR does not follow the rules of this repository anymore, so it was unapproved:\n{violations}\n\nHint: Edit the PR title or description and approve it again."
+    ))
+}
+
+pub fn stale_approval_comment(approved_head_sha: &str) -> Comment {
+    Comment::new(format!(
+        ":warning: New commits were pushed to this PR after it was approved at `{approved_head_sha}`, so it was unapproved.\n\nHint: Review the new commits and approve the PR again."
+    ))
+}
+
+pub fn delegate_try_builds_comment(
+    delegatee: &str,
+    bot_prefix: &CommandPrefix,
+    expires_at: Option<DateTime<Utc>>,
+) -> Comment {
     Comment::new(format!(
         r":v: @{delegatee}, you can now perform try builds on this pull request!
 
 You can now post `{bot_prefix} try` to start a try build.
-    ",
+{}    ",
+        delegation_expiry_note(expires_at)
     ))
 }
 
 /// `delegatee` is the user who received the delegation privileges, while `delegator` is the user
 /// who gave these privileges to the `delegatee`.
-pub fn delegate_comment(delegatee: &str, delegator: &str, bot_prefix: &CommandPrefix) -> Comment {
+pub fn delegate_comment(
+    delegatee: &str,
+    delegator: &str,
+    bot_prefix: &CommandPrefix,
+    expires_at: Option<DateTime<Utc>>,
+) -> Comment {
     Comment::new(format!(
         r#":v: @{delegatee}, you can now approve this pull request!
 
 If @{delegator} told you to "`r=me`" after making some further change, then please make that change and post `{bot_prefix} r={delegator}`.
-"#
+{}"#,
+        delegation_expiry_note(expires_at)
     ))
 }
 
+fn delegation_expiry_note(expires_at: Option<DateTime<Utc>>) -> String {
+    match expires_at {
+        Some(expires_at) => format!(
+            "\n:hourglass: The delegation expires at {}.\n",
+            expires_at.format("%Y-%m-%d %H:%M UTC")
+        ),
+        None => String::new(),
+    }
+}
+
 pub fn build_timed_out_comment(timeout: Duration) -> Comment {
     Comment::new(format!(
         ":boom: Test timed out after `{}`s",
@@ -333,14 +842,53 @@ fn list_workflows_status(workflows: &[WorkflowModel]) -> String {
         .join("\n")
 }
 
-pub fn auto_build_started_comment(head_sha: &CommitSha, merge_sha: &CommitSha) -> Comment {
+pub fn auto_build_started_comment(
+    head_sha: &CommitSha,
+    merge_sha: &CommitSha,
+    waiting_behind: usize,
+    queue_url: &str,
+) -> Comment {
+    let waiting = match waiting_behind {
+        0 => "No PRs are".to_string(),
+        1 => "1 PR is".to_string(),
+        count => format!("{count} PRs are"),
+    };
+    Comment::new(format!(
+        r#":hourglass: Testing commit {head_sha} with merge {merge_sha}...
+
+{waiting} waiting behind it in the [queue]({queue_url})."#
+    ))
+}
+
+/// Describes the auto build that bors would start if the repository was not in dry-run mode.
+pub fn dry_run_auto_build_comment(
+    head_sha: &CommitSha,
+    base_sha: &CommitSha,
+    waiting_behind: usize,
+    queue_url: &str,
+) -> Comment {
+    let waiting = match waiting_behind {
+        0 => "No PRs are".to_string(),
+        1 => "1 PR is".to_string(),
+        count => format!("{count} PRs are"),
+    };
     Comment::new(format!(
-        ":hourglass: Testing commit {head_sha} with merge {merge_sha}..."
+        r#":clipboard: Dry run: bors would now test commit {head_sha} merged into {base_sha}, and merge it if the tests pass. Nothing was pushed.
+
+{waiting} waiting behind it in the [queue]({queue_url})."#
+    ))
+}
+
+/// Describes an action that bors would perform if the repository was not in dry-run mode.
+pub fn dry_run_comment(action: &str) -> Comment {
+    Comment::new(format!(
+        ":clipboard: Dry run: bors would now {action}. Nothing was pushed."
     ))
 }
 
 pub fn auto_build_succeeded_comment(
     workflows: &[WorkflowModel],
+    artifacts: &[BuildArtifactModel],
     approved_by: &str,
     merge_sha: &CommitSha,
     base_ref: &str,
@@ -350,16 +898,61 @@ pub fn auto_build_succeeded_comment(
         .map(|w| format!("[{}]({})", w.name, w.url))
         .collect::<Vec<_>>()
         .join(", ");
+    let artifacts = if artifacts.is_empty() {
+        String::new()
+    } else {
+        format!("Artifacts: {}\n", format_artifacts(artifacts))
+    };
 
     Comment::new(format!(
         r#":sunny: Test successful - {urls}
-Approved by: `{approved_by}`
+{artifacts}Approved by: `{approved_by}`
 Pushing {merge_sha} to `{base_ref}`..."#
     ))
 }
 
+/// Posted when bors closes a PR whose changes it has pushed as a squashed commit, which GitHub
+/// does not recognize as a merge of the PR.
+pub fn squash_merged_comment(commit_url: &str, merge_sha: &CommitSha, base_ref: &str) -> Comment {
+    Comment::new(format!(
+        ":twisted_rightwards_arrows: The changes of this PR were squashed into [{merge_sha}]({commit_url}), which was pushed to `{base_ref}`. Closing the PR, since GitHub does not recognize it as merged."
+    ))
+}
+
+fn format_artifacts(artifacts: &[BuildArtifactModel]) -> String {
+    artifacts
+        .iter()
+        .map(|artifact| match artifact.size {
+            Some(size) => format!(
+                "[{}]({}) ({})",
+                artifact.name,
+                artifact.url,
+                format_size(size as u64)
+            ),
+            None => format!("[{}]({})", artifact.name, artifact.url),
+        })
+        .join(", ")
+}
+
 pub fn auto_build_push_failed_comment(error: &str) -> Comment {
     Comment::new(format!(
         ":eyes: Test was successful, but fast-forwarding failed: {error}"
     ))
 }
+
+pub fn health_report_comment(report: &HealthReport) -> Comment {
+    let component = |name: &str, health: &ComponentHealth| {
+        let (emoji, status) = if health.healthy {
+            (":white_check_mark:", "healthy")
+        } else {
+            (":x:", "unhealthy")
+        };
+        format!("- {emoji} {name}: {status} ({})\n", health.details)
+    };
+    let mut text = String::from("Pong 🏓!\n\n");
+    text.push_str(&component("Database", &report.database));
+    text.push_str(&component("GitHub", &report.github));
+    text.push_str(&component("Webhooks", &report.webhooks));
+    text.push_str(&component("Merge queue", &report.merge_queue));
+    Comment::new(text.trim_end().to_string())
+}
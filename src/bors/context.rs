@@ -1,17 +1,55 @@
 use std::{
-    collections::HashMap,
-    sync::{Arc, RwLock},
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
 };
 
-use crate::{PgDbClient, bors::command::CommandParser, github::GithubRepoName};
+use crate::{
+    PgDbClient, TeamApiClient,
+    bors::command::CommandParser,
+    github::{GithubRepoName, api::GitHubUrls},
+};
 
 use super::RepositoryState;
+use super::hooks::CommandHook;
 
 pub struct BorsContext {
     pub parser: CommandParser,
     pub db: Arc<PgDbClient>,
     pub repositories: RwLock<HashMap<GithubRepoName, Arc<RepositoryState>>>,
+    /// Client used to load the permissions of users from the team API.
+    pub team_api: TeamApiClient,
     web_url: String,
+    command_hooks: Vec<Arc<dyn CommandHook>>,
+    /// URLs of the GitHub instance, used when repositories are reloaded.
+    github_urls: GitHubUrls,
+    /// Maximum random delay before the periodic refresh of a single repository starts.
+    refresh_jitter: Duration,
+    /// Periodic refreshes that are currently running in the background.
+    running_refreshes: Mutex<HashSet<RefreshKind>>,
+}
+
+/// A periodic refresh of all repositories that runs in the background.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RefreshKind {
+    Config,
+    Permissions,
+}
+
+/// Marks a running refresh. The refresh is considered to be finished once this is dropped.
+pub struct RunningRefresh {
+    ctx: Arc<BorsContext>,
+    kind: RefreshKind,
+}
+
+impl Drop for RunningRefresh {
+    fn drop(&mut self) {
+        self.ctx
+            .running_refreshes
+            .lock()
+            .unwrap()
+            .remove(&self.kind);
+    }
 }
 
 impl BorsContext {
@@ -19,6 +57,7 @@ impl BorsContext {
         parser: CommandParser,
         db: Arc<PgDbClient>,
         repositories: HashMap<GithubRepoName, Arc<RepositoryState>>,
+        team_api: TeamApiClient,
         web_url: &str,
     ) -> Self {
         let repositories = RwLock::new(repositories);
@@ -26,10 +65,59 @@ impl BorsContext {
             parser,
             db,
             repositories,
+            team_api,
             web_url: web_url.trim_end_matches('/').to_string(),
+            command_hooks: vec![],
+            github_urls: GitHubUrls::default(),
+            refresh_jitter: Duration::from_secs(10),
+            running_refreshes: Mutex::new(HashSet::new()),
         }
     }
 
+    /// Sets the URLs of the GitHub instance that bors talks to. Defaults to github.com.
+    pub fn with_github_urls(mut self, urls: GitHubUrls) -> Self {
+        self.github_urls = urls;
+        self
+    }
+
+    pub fn github_urls(&self) -> &GitHubUrls {
+        &self.github_urls
+    }
+
+    /// Sets the maximum random delay before the periodic refresh of a single repository starts,
+    /// so that the refreshes of many repositories do not hit GitHub in a single burst.
+    pub fn with_refresh_jitter(mut self, jitter: Duration) -> Self {
+        self.refresh_jitter = jitter;
+        self
+    }
+
+    pub fn refresh_jitter(&self) -> Duration {
+        self.refresh_jitter
+    }
+
+    /// Marks the given refresh as running. Returns `None` if it is already running, in which case
+    /// the new refresh should be skipped.
+    pub fn start_refresh(self: &Arc<Self>, kind: RefreshKind) -> Option<RunningRefresh> {
+        if !self.running_refreshes.lock().unwrap().insert(kind) {
+            return None;
+        }
+        Some(RunningRefresh {
+            ctx: Arc::clone(self),
+            kind,
+        })
+    }
+
+    /// Registers a hook that is executed around each bors command.
+    /// Hooks are executed in the order in which they were registered.
+    pub fn with_command_hook(mut self, hook: Arc<dyn CommandHook>) -> Self {
+        self.command_hooks.push(hook);
+        self
+    }
+
+    pub fn command_hooks(&self) -> &[Arc<dyn CommandHook>] {
+        &self.command_hooks
+    }
+
     /// Returns a URL where the bot's website is publicly accessible.
     pub fn get_web_url(&self) -> &str {
         &self.web_url
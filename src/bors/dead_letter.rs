@@ -0,0 +1,143 @@
+//! Dead-letter queue of webhooks.
+//!
+//! When the handling of a webhook fails, its raw payload is stored in the dead-letter queue,
+//! instead of being lost. Once the cause of the failure is fixed, an administrator can request
+//! the stored webhooks to be replayed, either through the administrative API or using
+//! `bors-admin`. Replayed webhooks are handled in the same way as when they were first received.
+
+use std::sync::Arc;
+
+use crate::PgDbClient;
+use crate::bors::event::BorsEvent;
+use crate::bors::handle_bors_repository_event;
+use crate::bors::merge_queue::MergeQueueSender;
+use crate::bors::mergeability_queue::MergeabilityQueueSender;
+use crate::bors::{BorsContext, RepositoryState};
+use crate::github::{GithubRepoName, WebhookPayload};
+
+/// Stores a webhook whose handling has failed with `error` in the dead-letter queue.
+pub(crate) async fn store_dead_letter(
+    db: &PgDbClient,
+    repo: &GithubRepoName,
+    webhook: &WebhookPayload,
+    error: &anyhow::Error,
+) {
+    if let Err(db_error) = db
+        .insert_dead_letter(
+            repo,
+            &webhook.event_type,
+            &webhook.body,
+            &format!("{error:?}"),
+        )
+        .await
+    {
+        tracing::error!(
+            "Cannot store `{}` webhook of {repo} in the dead-letter queue: {db_error:?}",
+            webhook.event_type
+        );
+    }
+}
+
+/// Replays webhooks of the given repository whose replay has been requested.
+/// Webhooks that are handled successfully are removed from the dead-letter queue, the others
+/// stay there until their replay is requested again.
+pub(super) async fn replay_dead_letters(
+    repo: Arc<RepositoryState>,
+    ctx: Arc<BorsContext>,
+    mergeability_queue_tx: MergeabilityQueueSender,
+    merge_queue_tx: MergeQueueSender,
+) -> anyhow::Result<()> {
+    let db = Arc::clone(&ctx.db);
+    for dead_letter in db.get_dead_letters_to_replay(repo.repository()).await? {
+        let webhook = WebhookPayload {
+            event_type: dead_letter.event_type.clone(),
+            body: dead_letter.payload.clone(),
+        };
+        let result = match webhook.parse() {
+            Ok(Some(BorsEvent::Repository(event))) => {
+                handle_bors_repository_event(
+                    event,
+                    Arc::clone(&ctx),
+                    mergeability_queue_tx.clone(),
+                    merge_queue_tx.clone(),
+                )
+                .await
+            }
+            Ok(_) => {
+                tracing::warn!(
+                    "Dead letter {} does not contain a repository event, dropping it",
+                    dead_letter.id
+                );
+                Ok(())
+            }
+            Err(error) => Err(error),
+        };
+        match result {
+            Ok(()) => {
+                tracing::info!("Dead letter {} was replayed", dead_letter.id);
+                db.delete_dead_letter(&dead_letter).await?;
+            }
+            Err(error) => {
+                tracing::warn!(
+                    "Replay of dead letter {} failed (attempt {}): {error:?}",
+                    dead_letter.id,
+                    dead_letter.attempts + 1
+                );
+                db.record_dead_letter_failure(&dead_letter, &format!("{error:?}"))
+                    .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{BorsTester, default_repo_name, run_test};
+
+    #[sqlx::test]
+    async fn replay_failed_webhook(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .with_dead_lettered_webhooks(async |tester: &mut BorsTester| {
+                    tester.post_comment("@bors ping").await
+                })
+                .await?;
+            assert_eq!(tester.db().get_dead_letters().await?.len(), 1);
+
+            // Webhooks are only replayed when requested
+            tester.replay_dead_letters().await;
+            assert_eq!(tester.db().get_dead_letters().await?.len(), 1);
+
+            tester.db().request_dead_letter_replay(None).await?;
+            tester.replay_dead_letters().await;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @"Pong 🏓!");
+            assert!(tester.db().get_dead_letters().await?.is_empty());
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn failed_replay_keeps_dead_letter(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let dead_letter = tester
+                .db()
+                .insert_dead_letter(&default_repo_name(), "issue_comment", "{}", "Error")
+                .await?;
+            tester
+                .db()
+                .request_dead_letter_replay(Some(dead_letter.id))
+                .await?;
+            tester.replay_dead_letters().await;
+
+            let dead_letters = tester.db().get_dead_letters().await?;
+            assert_eq!(dead_letters.len(), 1);
+            assert_eq!(dead_letters[0].attempts, 2);
+            assert!(!dead_letters[0].replay_requested);
+            assert!(dead_letters[0].error.contains("missing field"));
+            Ok(())
+        })
+        .await;
+    }
+}
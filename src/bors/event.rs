@@ -1,7 +1,11 @@
+use crate::bors::BuildArtifact;
 use crate::database::{WorkflowStatus, WorkflowType};
-use crate::github::{CommitSha, GithubRepoName, GithubUser, PullRequest, PullRequestNumber};
+use crate::github::{
+    CommitSha, GithubRepoName, GithubUser, PullRequest, PullRequestNumber, WebhookPayload,
+};
 use chrono::Duration;
-use octocrab::models::{CheckSuiteId, RunId};
+use octocrab::Octocrab;
+use octocrab::models::{CheckSuiteId, CommentId, RunId};
 
 #[derive(Debug)]
 pub enum BorsRepositoryEvent {
@@ -25,6 +29,8 @@ pub enum BorsRepositoryEvent {
     PullRequestAssigned(PullRequestAssigned),
     // When a pull request is unassigned from a user
     PullRequestUnassigned(PullRequestUnassigned),
+    // When a label is added to or removed from a pull request
+    PullRequestLabelsChanged(PullRequestLabelsChanged),
     // When a pull request is ready for review
     PullRequestReadyForReview(PullRequestReadyForReview),
     /// When there is a push to a branch. This includes when a commit is pushed, when a commit tag is pushed,
@@ -34,6 +40,15 @@ pub enum BorsRepositoryEvent {
     WorkflowStarted(WorkflowRunStarted),
     /// A workflow run on Github Actions or a check run from external CI system has been completed.
     WorkflowCompleted(WorkflowRunCompleted),
+    /// The result of a build has been reported by an external CI system through the build
+    /// result API.
+    BuildResultReported(BuildResultReported),
+    /// A commit status or a check run of an external CI system has changed.
+    CommitStatusChanged(CommitStatusChanged),
+    /// The repository was archived.
+    RepositoryArchived(GithubRepoName),
+    /// The repository was unarchived.
+    RepositoryUnarchived(GithubRepoName),
 }
 
 impl BorsRepositoryEvent {
@@ -49,14 +64,28 @@ impl BorsRepositoryEvent {
             BorsRepositoryEvent::PullRequestConvertedToDraft(payload) => &payload.repository,
             BorsRepositoryEvent::PullRequestAssigned(payload) => &payload.repository,
             BorsRepositoryEvent::PullRequestUnassigned(payload) => &payload.repository,
+            BorsRepositoryEvent::PullRequestLabelsChanged(payload) => &payload.repository,
             BorsRepositoryEvent::PullRequestReadyForReview(payload) => &payload.repository,
             BorsRepositoryEvent::PushToBranch(payload) => &payload.repository,
             BorsRepositoryEvent::WorkflowStarted(workflow) => &workflow.repository,
             BorsRepositoryEvent::WorkflowCompleted(workflow) => &workflow.repository,
+            BorsRepositoryEvent::BuildResultReported(payload) => &payload.repository,
+            BorsRepositoryEvent::CommitStatusChanged(payload) => &payload.repository,
+            BorsRepositoryEvent::RepositoryArchived(repository)
+            | BorsRepositoryEvent::RepositoryUnarchived(repository) => repository,
         }
     }
 }
 
+/// A repository event that is waiting to be handled.
+#[derive(Debug)]
+pub struct QueuedRepositoryEvent {
+    pub event: BorsRepositoryEvent,
+    /// The webhook that the event was parsed from, if it was received through a webhook.
+    /// If handling of the event fails, the webhook is stored in the dead-letter queue.
+    pub webhook: Option<WebhookPayload>,
+}
+
 #[derive(Debug)]
 pub enum BorsGlobalEvent {
     /// The configuration of some repository has been changed for the bot's Github App.
@@ -65,6 +94,10 @@ pub enum BorsGlobalEvent {
     RefreshConfig,
     /// Refresh the team permissions.
     RefreshPermissions,
+    /// Reload the configuration and permissions of a single repository right away.
+    ReloadRepository(GithubRepoName),
+    /// Replay workflow run events of pending builds that were missed while bors was not running.
+    ReconcilePendingBuilds,
     /// Examine pending builds and handle any issues (e.g. cancel builds that have been running for
     /// a long time).
     RefreshPendingBuilds,
@@ -72,8 +105,19 @@ pub enum BorsGlobalEvent {
     RefreshPullRequestMergeability,
     /// Periodic event that serves for synchronizing PR state.
     RefreshPullRequestState,
+    /// Cancel workflow runs on bors branches that do not belong to any pending build.
+    CancelZombieWorkflows,
+    /// Clean up the artifacts of try builds that are older than their retention period.
+    CleanUpTryArtifacts,
     /// Try to process the merge queue.
     ProcessMergeQueue,
+    /// Retry GitHub side effects recorded in the outbox that could not be performed.
+    DeliverOutbox,
+    /// Replay webhooks from the dead-letter queue whose replay has been requested.
+    ReplayDeadLetters,
+    /// The private key of the GitHub App was rotated. The given client, which uses the new key,
+    /// replaces the current app client, and the repositories are reloaded with it.
+    AppClientChanged(Box<Octocrab>),
 }
 
 #[derive(Debug)]
@@ -92,6 +136,33 @@ pub struct PullRequestComment {
     pub pr_number: PullRequestNumber,
     pub text: String,
     pub html_url: String,
+    /// The review thread that the comment was posted in, if it is a review comment.
+    /// Identified by the ID of the first comment of the thread.
+    pub review_thread: Option<CommentId>,
+    /// Verdict of the GitHub review that the comment was submitted with, if it is the body of an
+    /// approving or a change-requesting review.
+    pub review_verdict: Option<ReviewVerdict>,
+    /// The comment itself, if it can be reacted to.
+    /// Bodies of submitted reviews do not support reactions.
+    pub reaction_target: Option<ReactionTarget>,
+}
+
+/// A comment that supports reactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReactionTarget {
+    /// A comment in the conversation of a pull request.
+    IssueComment(CommentId),
+    /// A comment in a review thread of a pull request.
+    ReviewComment(CommentId),
+}
+
+/// Verdict of a submitted GitHub pull request review.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReviewVerdict {
+    /// The reviewer approved the given commit.
+    Approved { commit: CommitSha },
+    /// The reviewer requested changes.
+    ChangesRequested,
 }
 
 #[derive(Debug)]
@@ -104,6 +175,10 @@ pub struct PullRequestPushed {
 pub struct PullRequestEdited {
     pub repository: GithubRepoName,
     pub pull_request: PullRequest,
+    /// Previous title of the PR, if it was changed.
+    pub from_title: Option<String>,
+    /// Previous description of the PR, if it was changed.
+    pub from_body: Option<String>,
     pub from_base_sha: Option<CommitSha>,
 }
 
@@ -156,6 +231,12 @@ pub struct PullRequestUnassigned {
     pub pull_request: PullRequest,
 }
 
+#[derive(Debug)]
+pub struct PullRequestLabelsChanged {
+    pub repository: GithubRepoName,
+    pub pull_request: PullRequest,
+}
+
 #[derive(Debug)]
 pub struct PushToBranch {
     pub repository: GithubRepoName,
@@ -184,3 +265,28 @@ pub struct WorkflowRunCompleted {
     /// Check suite to which this workflow is attached.
     pub check_suite_id: CheckSuiteId,
 }
+
+#[derive(Debug)]
+pub struct BuildResultReported {
+    pub repository: GithubRepoName,
+    pub build_id: i32,
+    /// Name of the CI system (or job) that reported the result.
+    pub name: String,
+    /// Link to the CI run that produced the result.
+    pub url: String,
+    pub status: WorkflowStatus,
+    pub artifacts: Vec<BuildArtifact>,
+}
+
+#[derive(Debug)]
+pub struct CommitStatusChanged {
+    pub repository: GithubRepoName,
+    /// Branches that point to the commit.
+    pub branches: Vec<String>,
+    pub commit_sha: CommitSha,
+    /// Context (name) of the commit status or check run, e.g. `buildkite/pipeline`.
+    pub context: String,
+    /// Link to the CI run, if the CI system provided one.
+    pub url: Option<String>,
+    pub status: WorkflowStatus,
+}
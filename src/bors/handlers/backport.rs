@@ -0,0 +1,340 @@
+use std::sync::Arc;
+
+use crate::PgDbClient;
+use crate::bors::comment::dry_run_comment;
+use crate::bors::handlers::labels::handle_label_trigger;
+use crate::bors::handlers::{PullRequestData, deny_request, has_permission, reply_to_command};
+use crate::bors::{Comment, PullRequestStatus, RepositoryState};
+use crate::database::{BuildModel, BuildStatus, PullRequestModel};
+use crate::github::{
+    CommitSha, GithubUser, LabelTrigger, MergeResult, PullRequestNumber, cherry_pick,
+};
+use crate::permissions::PermissionType;
+
+/// Prefix of the branches from which backport PRs are opened. The branch of a backport PR is
+/// suffixed with the number of the backported PR and the name of the target branch.
+pub(super) const BACKPORT_BRANCH_PREFIX: &str = "automation/bors/backport-";
+
+/// Backports the given PR onto the `to` branch. If the PR was already merged by bors, its merge
+/// commit is cherry-picked right away, otherwise the backport is performed once it is merged.
+pub(super) async fn command_backport(
+    repo: Arc<RepositoryState>,
+    db: Arc<PgDbClient>,
+    pr: PullRequestData<'_>,
+    author: &GithubUser,
+    to: String,
+) -> anyhow::Result<()> {
+    if !has_permission(&repo, author, pr, PermissionType::Review).await? {
+        return deny_request(&repo, pr, author, PermissionType::Review).await;
+    }
+
+    if to == pr.github.base.name {
+        let comment = format!(":exclamation: This PR already targets the `{to}` branch.");
+        return reply_to_command(&repo, pr, Comment::new(comment)).await;
+    }
+    if let Err(error) = repo.client.get_branch_sha(&to).await {
+        tracing::warn!("Cannot resolve backport branch {to}: {error:?}");
+        return reply_to_command(&repo, pr, backport_branch_not_found_comment(&to)).await;
+    }
+
+    // Squash-merged PRs are closed on GitHub, but bors has marked them as merged
+    let status = match pr.db.pr_status {
+        PullRequestStatus::Merged => PullRequestStatus::Merged,
+        _ => pr.github.status,
+    };
+    let comment = match status {
+        PullRequestStatus::Merged => match merged_build(pr.db) {
+            Some(build) => {
+                backport_pr(&repo, &db, pr.number(), &pr.github.title, build, &to).await?
+            }
+            None => not_merged_by_bors_comment(),
+        },
+        PullRequestStatus::Open | PullRequestStatus::Draft => {
+            if !pr.db.backports.contains(&to) {
+                let mut backports = pr.db.backports.clone();
+                backports.push(to.clone());
+                db.set_backports(pr.db, &backports).await?;
+            }
+            Comment::new(format!(
+                ":calendar: This PR will be backported to `{to}` once it is merged."
+            ))
+        }
+        PullRequestStatus::Closed => not_merged_by_bors_comment(),
+    };
+    reply_to_command(&repo, pr, comment).await
+}
+
+/// Performs the backports that were requested before the PR was merged.
+pub(super) async fn backport_merged_pr(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    pr: &PullRequestModel,
+    title: &str,
+) -> anyhow::Result<()> {
+    if pr.backports.is_empty() {
+        return Ok(());
+    }
+    // Each requested backport is only attempted once
+    db.set_backports(pr, &[]).await?;
+
+    let Some(build) = merged_build(pr) else {
+        repo.post_comment(pr.number, not_merged_by_bors_comment())
+            .await?;
+        return Ok(());
+    };
+    for branch in &pr.backports {
+        let comment = match backport_pr(repo, db, pr.number, title, build, branch).await {
+            Ok(comment) => comment,
+            Err(error) => {
+                tracing::error!("Cannot backport PR {} to {branch}: {error:?}", pr.number);
+                Comment::new(format!(
+                    ":x: Encountered an error while backporting this PR to `{branch}`. It has to be backported manually."
+                ))
+            }
+        };
+        repo.post_comment(pr.number, comment).await?;
+    }
+    Ok(())
+}
+
+/// Only the auto build tells us which commit was merged, and what was its parent.
+pub(super) fn merged_build(pr: &PullRequestModel) -> Option<&BuildModel> {
+    pr.auto_build
+        .as_ref()
+        .filter(|build| build.status == BuildStatus::Success)
+}
+
+/// Cherry-picks the commit merged by `build` onto the `target` branch, and opens a PR with the
+/// result. Returns the comment that describes the outcome.
+async fn backport_pr(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    pr_number: PullRequestNumber,
+    title: &str,
+    build: &BuildModel,
+    target: &str,
+) -> anyhow::Result<Comment> {
+    if repo.config.load().dry_run {
+        return Ok(dry_run_comment(&format!(
+            "open a PR that backports #{pr_number} to `{target}`"
+        )));
+    }
+
+    let target_sha = match repo.client.get_branch_sha(target).await {
+        Ok(sha) => sha,
+        Err(error) => {
+            tracing::warn!("Cannot resolve backport branch {target}: {error:?}");
+            return Ok(backport_branch_not_found_comment(target));
+        }
+    };
+
+    let branch = format!("{BACKPORT_BRANCH_PREFIX}{pr_number}-{target}");
+    let merged_sha = CommitSha(build.commit_sha.clone());
+    let message = format!(
+        "[{target}] Backport #{pr_number} - {title}\n\nThis cherry-picks commit {merged_sha}, which merged #{pr_number}."
+    );
+    match cherry_pick(
+        &repo.client,
+        &branch,
+        &merged_sha,
+        &CommitSha(build.parent.clone()),
+        &target_sha,
+        &message,
+    )
+    .await?
+    {
+        MergeResult::Success(_) => {}
+        MergeResult::Conflict => {
+            return Ok(Comment::new(format!(
+                ":lock: This PR cannot be backported to `{target}` automatically, because it conflicts with the `{target}` branch. It has to be backported manually."
+            )));
+        }
+    }
+
+    let backport_pr = repo
+        .client
+        .create_pull_request(
+            &format!("[{target}] Backport #{pr_number}: {title}"),
+            &branch,
+            target,
+            &format!("Backport of #{pr_number} to `{target}`."),
+        )
+        .await?;
+    tracing::info!(
+        "Opened PR {} to backport PR {pr_number} to {target}",
+        backport_pr.number
+    );
+    handle_label_trigger(repo, db, backport_pr.number, LabelTrigger::Backport).await?;
+
+    Ok(Comment::new(format!(
+        ":arrow_heading_down: Opened #{} to backport this PR to `{target}`.",
+        backport_pr.number
+    )))
+}
+
+fn backport_branch_not_found_comment(branch: &str) -> Comment {
+    Comment::new(format!(
+        ":exclamation: Cannot backport this PR, because the branch `{branch}` does not exist."
+    ))
+}
+
+fn not_merged_by_bors_comment() -> Comment {
+    Comment::new(":exclamation: Only PRs that were merged by bors can be backported.".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::PullRequestNumber;
+    use crate::tests::{BorsBuilder, BorsTester, GitHubState, default_repo_name, run_test};
+
+    #[sqlx::test]
+    async fn backport_missing_branch(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors backport to=beta").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":exclamation: Cannot backport this PR, because the branch `beta` does not exist."
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn backport_pr_not_merged_by_bors(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.create_branch("beta").await;
+            tester.set_pr_status_merged(()).await?;
+            tester.post_comment("@bors backport to=beta").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":exclamation: Only PRs that were merged by bors can be backported."
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn backport_merged_pr(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+
+[labels]
+approved = ["+approved"]
+backport = ["+backport"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.create_branch("beta").await;
+                tester.approve(()).await?;
+                tester.start_and_finish_auto_build(()).await?;
+                tester.set_pr_status_merged(()).await?;
+
+                tester.post_comment("@bors backport to=beta").await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @":arrow_heading_down: Opened #2 to backport this PR to `beta`."
+                );
+                tester
+                    .get_pr_copy(2)
+                    .await
+                    .expect_added_labels(&["backport"]);
+
+                let repo = tester.get_repo(&default_repo_name()).await;
+                let repo = repo.lock();
+                let backport_pr = &repo.pull_requests[&2];
+                assert_eq!(backport_pr.title, "[beta] Backport #1: Title of PR 1");
+                assert_eq!(backport_pr.base_branch.get_name(), "beta");
+                insta::assert_snapshot!(
+                    repo.commit_messages[&backport_pr.head_sha],
+                    @r"
+                [beta] Backport #1 - Title of PR 1
+
+                This cherry-picks commit merge-0-pr-1, which merged #1.
+                "
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn backport_after_merge(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.create_branch("beta").await;
+            tester.post_comment("@bors backport to=beta").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":calendar: This PR will be backported to `beta` once it is merged."
+            );
+            tester
+                .wait_for_pr((), |pr| pr.backports == ["beta"])
+                .await?;
+
+            tester.approve(()).await?;
+            tester.start_and_finish_auto_build(()).await?;
+            tester.set_pr_status_merged(()).await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":arrow_heading_down: Opened #2 to backport this PR to `beta`."
+            );
+            let pr = tester
+                .db()
+                .get_pull_request(&default_repo_name(), PullRequestNumber(1))
+                .await?
+                .unwrap();
+            assert!(pr.backports.is_empty());
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn backport_after_squash_merge(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.create_branch("beta").await;
+            tester.post_comment("@bors backport to=beta").await?;
+            tester.expect_comments((), 1).await;
+            tester
+                .wait_for_pr((), |pr| pr.backports == ["beta"])
+                .await?;
+
+            tester.post_comment("@bors r+ squash").await?;
+            tester.expect_comments((), 1).await;
+            tester.start_and_finish_auto_build(()).await?;
+            tester.expect_comments((), 1).await;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":arrow_heading_down: Opened #2 to backport this PR to `beta`."
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn backport_conflict(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.create_branch("beta").await;
+            tester
+                .modify_branch("automation/bors/backport-1-beta", |branch| {
+                    branch.merge_conflict = true
+                })
+                .await;
+            tester.approve(()).await?;
+            tester.start_and_finish_auto_build(()).await?;
+            tester.set_pr_status_merged(()).await?;
+
+            tester.post_comment("@bors backport to=beta").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":lock: This PR cannot be backported to `beta` automatically, because it conflicts with the `beta` branch. It has to be backported manually."
+            );
+            Ok(())
+        })
+        .await;
+    }
+}
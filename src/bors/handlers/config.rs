@@ -0,0 +1,296 @@
+use std::collections::BTreeSet;
+
+use octocrab::params::checks::CheckRunConclusion;
+
+use crate::bors::RepositoryState;
+use crate::config::{CONFIG_FILE_PATH, RepositoryConfig};
+use crate::github::PullRequest;
+use crate::github::api::client::CheckRunOutput;
+
+pub(super) const CONFIG_CHECK_RUN_NAME: &str = "Bors config";
+
+/// If the pull request modifies the configuration file of bors (i.e. it is one of its modified
+/// `files`), load the new version of the file
+/// and report whether it is valid, and how it changes the effective configuration, using a
+/// check run on the head commit of the PR.
+pub(super) async fn validate_config_change(
+    repo: &RepositoryState,
+    pr: &PullRequest,
+    files: &[String],
+) -> anyhow::Result<()> {
+    if !files.iter().any(|file| file == CONFIG_FILE_PATH) {
+        return Ok(());
+    }
+
+    tracing::info!("PR {} modifies {CONFIG_FILE_PATH}", pr.number);
+    let content = repo.client.get_config_content(&pr.head.sha).await?;
+    let (conclusion, output) = match content {
+        Some(content) => match toml::from_str::<RepositoryConfig>(&content) {
+            Ok(config) => (
+                CheckRunConclusion::Success,
+                valid_config_output(&repo.config.load(), &config)?,
+            ),
+            Err(error) => (
+                CheckRunConclusion::Failure,
+                CheckRunOutput {
+                    title: "Invalid configuration".to_string(),
+                    summary: format!(
+                        "`{CONFIG_FILE_PATH}` cannot be loaded:\n\n```\n{}\n```",
+                        error.to_string().trim_end()
+                    ),
+                },
+            ),
+        },
+        None => (
+            CheckRunConclusion::Failure,
+            CheckRunOutput {
+                title: "Missing configuration".to_string(),
+                summary: format!(
+                    "`{CONFIG_FILE_PATH}` was removed, so bors would not be able to load the configuration of this repository."
+                ),
+            },
+        ),
+    };
+    repo.client
+        .create_completed_check_run(
+            CONFIG_CHECK_RUN_NAME,
+            &pr.head.sha,
+            conclusion,
+            output,
+            &pr.number.to_string(),
+        )
+        .await?;
+    Ok(())
+}
+
+fn valid_config_output(
+    current: &RepositoryConfig,
+    new: &RepositoryConfig,
+) -> anyhow::Result<CheckRunOutput> {
+    let changes = effective_config_changes(current, new)?;
+    let summary = if changes.is_empty() {
+        format!("`{CONFIG_FILE_PATH}` is valid. The effective settings are not changed.")
+    } else {
+        let mut summary =
+            format!("`{CONFIG_FILE_PATH}` is valid. The following settings are changed:\n");
+        for (key, old, new) in changes {
+            summary.push_str(&format!("\n- `{key}`: `{old}` -> `{new}`"));
+        }
+        summary
+    };
+    Ok(CheckRunOutput {
+        title: "Valid configuration".to_string(),
+        summary,
+    })
+}
+
+/// Return the top-level settings that differ between the two configurations, as
+/// (key, old value, new value) triples, in the same format as the configuration API.
+pub(super) fn effective_config_changes(
+    current: &RepositoryConfig,
+    new: &RepositoryConfig,
+) -> anyhow::Result<Vec<(String, String, String)>> {
+    let current = serde_json::to_value(current)?;
+    let new = serde_json::to_value(new)?;
+    let null = serde_json::Value::Null;
+
+    let keys: BTreeSet<&String> = current
+        .as_object()
+        .into_iter()
+        .chain(new.as_object())
+        .flat_map(|object| object.keys())
+        .collect();
+    Ok(keys
+        .into_iter()
+        .filter_map(|key| {
+            let old = current.get(key).unwrap_or(&null);
+            let new = new.get(key).unwrap_or(&null);
+            (old != new).then(|| (key.clone(), old.to_string(), new.to_string()))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bors::handlers::config::CONFIG_CHECK_RUN_NAME;
+    use crate::config::CONFIG_FILE_PATH;
+    use crate::tests::{
+        BorsBuilder, BorsTester, Comment, GitHubState, default_repo_name, run_test,
+    };
+
+    async fn config_check_runs(tester: &BorsTester) -> Vec<(String, String, Option<String>)> {
+        let repo = tester.default_repo().await;
+        repo.lock()
+            .check_runs
+            .iter()
+            .filter(|check_run| check_run.name == CONFIG_CHECK_RUN_NAME)
+            .map(|check_run| {
+                (
+                    check_run.head_sha.clone(),
+                    check_run.summary.clone(),
+                    check_run.conclusion.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[sqlx::test]
+    async fn ignore_pr_without_config_change(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .open_pr(default_repo_name(), |pr| {
+                    pr.changed_files = vec![("src/lib.rs".to_string(), Some(String::new()))];
+                })
+                .await?;
+            tester.push_to_pr(()).await?;
+            tester.post_comment("@bors ping").await?;
+            tester.expect_comments((), 1).await;
+            assert!(config_check_runs(tester).await.is_empty());
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn valid_config_change(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("timeout = 3600"))
+            .run_test(async |tester: &mut BorsTester| {
+                let pr = tester
+                    .open_pr(default_repo_name(), |pr| {
+                        pr.changed_files = vec![(
+                            CONFIG_FILE_PATH.to_string(),
+                            Some("timeout = 7200\nmin_ci_time = 60".to_string()),
+                        )];
+                    })
+                    .await?;
+                tester
+                    .post_comment(Comment::new(pr.id(), "@bors ping"))
+                    .await?;
+                tester.expect_comments(pr.id(), 1).await;
+                insta::assert_debug_snapshot!(config_check_runs(tester).await, @r#"
+                    [
+                        (
+                            "pr-2-sha",
+                            "`rust-bors.toml` is valid. The following settings are changed:\n\n- `min_ci_time`: `null` -> `60`\n- `timeout`: `3600` -> `7200`",
+                            Some(
+                                "success",
+                            ),
+                        ),
+                    ]
+                    "#);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn unchanged_effective_config(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("timeout = 3600"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .modify_pr_state((), |pr| {
+                        pr.changed_files =
+                            vec![(CONFIG_FILE_PATH.to_string(), Some(String::new()))];
+                    })
+                    .await;
+                tester.push_to_pr(()).await?;
+                tester.post_comment("@bors ping").await?;
+                tester.expect_comments((), 1).await;
+                insta::assert_debug_snapshot!(config_check_runs(tester).await, @r#"
+                    [
+                        (
+                            "pr-1-commit-1",
+                            "`rust-bors.toml` is valid. The effective settings are not changed.",
+                            Some(
+                                "success",
+                            ),
+                        ),
+                    ]
+                    "#);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn invalid_config_change(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .modify_pr_state((), |pr| {
+                    pr.changed_files = vec![(
+                        CONFIG_FILE_PATH.to_string(),
+                        Some(r#"timeout = "1h""#.to_string()),
+                    )];
+                })
+                .await;
+            tester.push_to_pr(()).await?;
+            tester.post_comment("@bors ping").await?;
+            tester.expect_comments((), 1).await;
+            insta::assert_debug_snapshot!(config_check_runs(tester).await, @r#"
+                [
+                    (
+                        "pr-1-commit-1",
+                        "`rust-bors.toml` cannot be loaded:\n\n```\nTOML parse error at line 1, column 11\n  |\n1 | timeout = \"1h\"\n  |           ^^^^\ninvalid type: string \"1h\", expected u64\n```",
+                        Some(
+                            "failure",
+                        ),
+                    ),
+                ]
+                "#);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn unknown_config_key(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .modify_pr_state((), |pr| {
+                    pr.changed_files = vec![(
+                        CONFIG_FILE_PATH.to_string(),
+                        Some("unknown_key = 5".to_string()),
+                    )];
+                })
+                .await;
+            tester.push_to_pr(()).await?;
+            tester.post_comment("@bors ping").await?;
+            tester.expect_comments((), 1).await;
+            let check_runs = config_check_runs(tester).await;
+            let (_, summary, conclusion) = &check_runs[0];
+            assert!(summary.contains("unknown field `unknown_key`"));
+            assert_eq!(conclusion.as_deref(), Some("failure"));
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn removed_config(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .modify_pr_state((), |pr| {
+                    pr.changed_files = vec![(CONFIG_FILE_PATH.to_string(), None)];
+                })
+                .await;
+            tester.push_to_pr(()).await?;
+            tester.post_comment("@bors ping").await?;
+            tester.expect_comments((), 1).await;
+            insta::assert_debug_snapshot!(config_check_runs(tester).await, @r#"
+                [
+                    (
+                        "pr-1-commit-1",
+                        "`rust-bors.toml` was removed, so bors would not be able to load the configuration of this repository.",
+                        Some(
+                            "failure",
+                        ),
+                    ),
+                ]
+                "#);
+            Ok(())
+        })
+        .await;
+    }
+}
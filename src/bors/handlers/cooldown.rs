@@ -0,0 +1,242 @@
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::PgDbClient;
+use crate::bors::command::{Approver, BorsCommand};
+use crate::bors::handlers::PullRequestData;
+use crate::bors::{Comment, RepositoryState};
+use crate::github::GithubUser;
+
+/// Outcome recorded in the audit log for commands that were ignored because of the cooldown.
+const IGNORED_REPETITION_OUTCOME: &str = "ignored: repeated command";
+
+/// How many of the latest audit log entries of the PR are searched for a previous occurrence of
+/// the command.
+const AUDIT_LOG_SEARCH_LENGTH: i64 = 20;
+
+/// What should happen with a command that is handled while the command cooldown is enabled.
+pub(super) enum RepeatedCommand {
+    /// The command should be executed normally.
+    Execute,
+    /// The command should be ignored, and the author should be told about it.
+    IgnoreWithNote(Comment),
+    /// The command should be ignored without a reply, because its author was already told that
+    /// its repetitions are ignored.
+    IgnoreSilently,
+}
+
+impl RepeatedCommand {
+    /// Outcome of the command recorded in the audit log, if it is ignored.
+    pub(super) fn audit_outcome(&self) -> Option<&'static str> {
+        match self {
+            RepeatedCommand::Execute => None,
+            RepeatedCommand::IgnoreWithNote(_) | RepeatedCommand::IgnoreSilently => {
+                Some(IGNORED_REPETITION_OUTCOME)
+            }
+        }
+    }
+}
+
+/// Decides whether a command should be ignored, because the same user has already issued the
+/// identical command (`command_text`) on the same PR within the cooldown of the repository, and
+/// executing it again would have no effect.
+pub(super) async fn check_repeated_command(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    pr: PullRequestData<'_>,
+    author: &GithubUser,
+    command: &BorsCommand,
+    command_text: &str,
+) -> anyhow::Result<RepeatedCommand> {
+    let Some(cooldown) = repo.config.load().command_cooldown else {
+        return Ok(RepeatedCommand::Execute);
+    };
+    if !has_no_effect(command, pr, author) {
+        return Ok(RepeatedCommand::Execute);
+    }
+
+    let since = Utc::now() - chrono::Duration::from_std(cooldown)?;
+    let log = db
+        .get_audit_log(
+            repo.repository(),
+            Some(pr.number()),
+            AUDIT_LOG_SEARCH_LENGTH,
+        )
+        .await?;
+    let Some(previous) = log.iter().rev().find(|entry| {
+        entry.created_at >= since
+            && entry.actor.as_deref() == Some(author.username.as_str())
+            && entry.action == command.name()
+            && entry.details.as_deref() == Some(command_text)
+    }) else {
+        return Ok(RepeatedCommand::Execute);
+    };
+
+    tracing::info!(
+        "Ignoring repeated command {command:?} of {}",
+        author.username
+    );
+    if previous.outcome == IGNORED_REPETITION_OUTCOME {
+        Ok(RepeatedCommand::IgnoreSilently)
+    } else {
+        Ok(RepeatedCommand::IgnoreWithNote(repeated_command_comment(
+            cooldown,
+        )))
+    }
+}
+
+fn repeated_command_comment(cooldown: Duration) -> Comment {
+    Comment::new(format!(
+        ":hourglass: This command was already issued recently and it would have no effect now, so it was ignored. Further repetitions within {} minutes will be ignored without a reply.",
+        cooldown.as_secs().div_ceil(60)
+    ))
+}
+
+/// Returns true if executing the command would not change the state of the PR.
+/// Commands that only print information or that start or cancel builds are never considered to
+/// have no effect.
+fn has_no_effect(command: &BorsCommand, pr: PullRequestData<'_>, author: &GithubUser) -> bool {
+    let db = pr.db;
+    match command {
+        BorsCommand::Approve {
+            approver,
+            priority,
+            rollup,
+            squash,
+            commit,
+        } => {
+            let approvers = match approver {
+                Approver::Myself => vec![author.username.clone()],
+                Approver::Specified(approvers) => approvers.clone(),
+            };
+            let current_approvers = db.approvers();
+            db.approved_sha() == Some(pr.github.head.sha.as_ref())
+                && commit.is_none()
+                && approvers
+                    .iter()
+                    .all(|approver| current_approvers.contains(&approver.as_str()))
+                && priority.is_none_or(|priority| db.priority == Some(priority as i32))
+                && rollup.is_none_or(|rollup| db.rollup == Some(rollup))
+                && *squash == db.squash
+        }
+        BorsCommand::Unapprove => !db.is_approved(),
+        BorsCommand::SetPriority(priority) => db.priority == Some(*priority as i32),
+        BorsCommand::SetRollupMode(rollup) => db.rollup == Some(*rollup),
+        BorsCommand::SetDelegate {
+            permission,
+            duration: None,
+        } => db.active_delegation() == Some(*permission) && db.delegation_expires_at.is_none(),
+        BorsCommand::Undelegate => db.active_delegation().is_none(),
+        BorsCommand::Hold => db.held,
+        BorsCommand::ReleaseHold => !db.held,
+        BorsCommand::Park { reason } => db.parked && db.park_reason == *reason,
+        BorsCommand::Unpark => !db.parked,
+        BorsCommand::SetBuildEnv { vars } => vars
+            .iter()
+            .all(|(key, value)| db.build_env.contains(&format!("{key}={value}"))),
+        BorsCommand::ClearBuildEnv => db.build_env.is_empty(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{BorsBuilder, BorsTester, Comment, GitHubState, User, default_repo_name};
+
+    fn cooldown_config() -> GitHubState {
+        GitHubState::default().with_default_config(
+            r#"
+command_cooldown = 3600
+
+[labels]
+approved = ["+approved"]
+"#,
+        )
+    }
+
+    #[sqlx::test]
+    async fn repeated_approval_is_ignored(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(cooldown_config())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.post_comment("@bors r+").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":hourglass: This command was already issued recently and it would have no effect now, so it was ignored. Further repetitions within 60 minutes will be ignored without a reply.");
+                tester.post_comment("@bors r+").await?;
+                tester.post_comment("@bors r+").await?;
+                // Only the reply to this command should be posted
+                tester.post_comment("@bors ping").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @"Pong 🏓!");
+
+                let outcomes: Vec<_> = tester
+                    .db()
+                    .get_audit_log(&default_repo_name(), None, 10)
+                    .await?
+                    .into_iter()
+                    .filter(|entry| entry.action == "approve")
+                    .map(|entry| entry.outcome)
+                    .collect();
+                insta::assert_debug_snapshot!(outcomes, @r#"
+                    [
+                        "success",
+                        "ignored: repeated command",
+                        "ignored: repeated command",
+                        "ignored: repeated command",
+                    ]
+                    "#);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn repeated_approval_after_push(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(cooldown_config())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.push_to_pr(()).await?;
+                tester.expect_comments((), 1).await;
+                tester.approve(()).await?;
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_approvers(&["default-user"]);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn repeated_approval_by_another_user(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(cooldown_config())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester
+                    .post_comment(Comment::from("@bors r+").with_author(User::reviewer()))
+                    .await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                    :pushpin: Commit pr-1-sha has been approved by `default-user,reviewer`
+
+                    It is now in the [queue](https://test.com/bors/queue/borstest) for this repository.
+                    ");
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn repeated_approval_without_cooldown(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.approve(()).await?;
+                tester.approve(()).await?;
+                Ok(())
+            })
+            .await;
+    }
+}
@@ -10,8 +10,7 @@ pub(super) async fn command_help(
 ) -> anyhow::Result<()> {
     let help = format_help();
 
-    repo.client
-        .post_comment(pr_number, Comment::new(help.to_string()))
+    repo.post_comment(pr_number, Comment::new(help.to_string()))
         .await?;
     Ok(())
 }
@@ -21,34 +20,56 @@ fn format_help() -> &'static str {
     // The help is generated manually to have a nicer structure.
     // We do a no-op destructuring of `BorsCommand` to make it harder to modify help in case new
     // commands are added though.
-    match BorsCommand::Ping {
+    match BorsCommand::Help {
         BorsCommand::Approve {
             approver: _,
             rollup: _,
             priority: _,
+            squash: _,
+            commit: _,
         } => {}
         BorsCommand::Unapprove => {}
         BorsCommand::Help => {}
-        BorsCommand::Ping => {}
-        BorsCommand::Try { parent: _, jobs: _ } => {}
-        BorsCommand::TryCancel => {}
+        BorsCommand::Ping { health: _ } => {}
+        BorsCommand::Try {
+            parent: _,
+            jobs: _,
+            commit: _,
+        } => {}
+        BorsCommand::TryCancel { .. } => {}
         BorsCommand::SetPriority(_) => {}
         BorsCommand::Info => {}
-        BorsCommand::SetDelegate(_) => {}
+        BorsCommand::SetDelegate { .. } => {}
         BorsCommand::Undelegate => {}
         BorsCommand::SetRollupMode(_) => {}
-        BorsCommand::OpenTree => {}
-        BorsCommand::TreeClosed(_) => {}
-        BorsCommand::Retry => {}
+        BorsCommand::OpenTree { .. } => {}
+        BorsCommand::TreeClosed { .. } => {}
+        BorsCommand::Retry { .. } => {}
+        BorsCommand::Notify => {}
+        BorsCommand::PauseQueue => {}
+        BorsCommand::ResumeQueue => {}
+        BorsCommand::Hold => {}
+        BorsCommand::ReleaseHold => {}
+        BorsCommand::Park { reason: _ } => {}
+        BorsCommand::Unpark => {}
+        BorsCommand::SetBuildEnv { vars: _ } => {}
+        BorsCommand::ClearBuildEnv => {}
+        BorsCommand::Sync => {}
+        BorsCommand::Reload => {}
+        BorsCommand::History => {}
+        BorsCommand::Revert { .. } => {}
+        BorsCommand::Backport { .. } => {}
     }
 
     r#"
 You can use the following commands:
 
 ## PR management
-- `r+ [p=<priority>] [rollup=<never|iffy|maybe|always>]`: Approve this PR on your behalf
+- `r+ [<sha>] [p=<priority>] [rollup=<never|iffy|maybe|always>] [squash]`: Approve this PR on your behalf
     - Optionally, you can specify the `<priority>` of the PR and if it is eligible for rollups (`<rollup>)`.
-- `r=<user> [p=<priority>] [rollup=<never|iffy|maybe|always>]`: Approve this PR on behalf of `<user>`
+    - If `<sha>` is specified, the PR is only approved if its head is still at the given commit. The SHA can be abbreviated to at least 7 characters.
+    - If `squash` is specified, the commits of the PR are squashed into a single commit when it is merged.
+- `r=<user> [<sha>] [p=<priority>] [rollup=<never|iffy|maybe|always>] [squash]`: Approve this PR on behalf of `<user>`
     - Optionally, you can specify the `<priority>` of the PR and if it is eligible for rollups (`<rollup>)`.
     - You can pass a comma-separated list of GitHub usernames.
 - `r-`: Unapprove this PR
@@ -56,25 +77,48 @@ You can use the following commands:
 - `rollup=<never|iffy|maybe|always>`: Set the rollup status of the PR
 - `rollup`: Short for `rollup=always`
 - `rollup-`: Short for `rollup=maybe`
-- `delegate=<try|review>`: Delegate permissions for running try builds or approving to the PR author
+- `delegate=<try|review> [for=<duration>]`: Delegate permissions for running try builds or approving to the PR author
     - `try` allows the PR author to start try builds.
     - `review` allows the PR author to both start try builds and approve the PR.
-- `delegate+`: Delegate approval permissions to the PR author
+    - Optionally, you can specify a `<duration>` (e.g. `12h` or `7d`) after which the delegation expires.
+- `delegate+ [for=<duration>]`: Delegate approval permissions to the PR author
     - Shortcut for `delegate=review`
 - `delegate-`: Remove any previously granted permission delegation
-- `try [parent=<parent>] [jobs=<jobs>]`: Start a try build.
-    - Optionally, you can specify a `<parent>` SHA with which will the PR be merged. You can specify `parent=last` to use the same parent SHA as the previous try build.
-    - Optionally, you can select a comma-separated list of CI `<jobs>` to run in the try build.
-- `try cancel`: Cancel a running try build
-- `retry`: Clear a failed auto build status from an approved PR. This will cause the merge queue to attempt to start a new auto build and retry merging the PR again.
+- `try [parent=<parent>] [jobs=<jobs>] [commit=<sha>]`: Start a try build.
+    - Optionally, you can specify a `<parent>` SHA with which will the PR be merged. You can specify `parent=last` to use the same parent SHA as the previous try build, or the name of a branch (e.g. `parent=beta`) to use its latest commit.
+    - Optionally, you can select a comma-separated list of CI `<jobs>` to run in the try build. Try builds of different jobs run concurrently, while a try build of the same jobs replaces the running one.
+    - Optionally, you can specify the `<sha>` of an older commit of the PR that should be tried instead of its head, e.g. to find out which commit of the PR introduced a regression.
+- `try cancel [<build id>]`: Cancel running try builds, or only the try build with the given ID
+- `env <key>=<value>...`: Set build parameters of this PR, which are passed to its try and auto builds
+    - Only the parameters and values allowed by the `build_env` option of the repository configuration can be set.
+- `env-`: Remove all build parameters of this PR
+- `retry [cancel-workflows] [reason=<reason>]`: Clear a failed auto build status from an approved PR. This will cause the merge queue to attempt to start a new auto build and retry merging the PR again. If the PR has no failed auto build, its failed try build is started again instead. The optional reason is recorded and shown in `info`.
+    - With `cancel-workflows`, workflows of the failed auto build that are still running are cancelled first.
 - `info`: Get information about the current PR
+- `history`: Get the commands used on the current PR and the actions that bors performed on it
+- `notify`: Get notified when this PR is among the first 3 PRs in the merge queue
+- `revert [p=<priority>]`: Open a PR that reverts this PR, after it was merged by bors
+    - If `<priority>` is specified, the revert PR is approved right away with the given priority.
+- `backport to=<branch>`: Cherry-pick this PR onto `<branch>` after it was merged by bors, and open a PR with the result
+    - If the PR is not merged yet, it is backported once it is merged.
 
 ## Repository management
-- `treeclosed=<priority>`: Close the tree for PRs with priority less than `<priority>`
-- `treeclosed-` or `treeopen`: Open the repository tree for merging
+- `treeclosed=<priority> [for=<duration>] [reason="<reason>"] [branch=<branch>]`: Close the tree for PRs with priority less than `<priority>`
+    - Optionally, you can specify a `<duration>` (e.g. `30m`, `2h` or `1d`) after which the tree is reopened automatically, and the `<reason>` why the tree is closed.
+    - Optionally, you can specify a `<branch>` with its own merge queue whose tree should be closed. By default, the tree that applies to this PR is closed.
+- `treeclosed-` or `treeopen` `[branch=<branch>]`: Open the repository tree for merging
+- `queue pause`: Pause the merge queue. No new auto builds are started, but running auto builds are still merged.
+- `queue resume`: Resume a paused merge queue
+- `hold`: Keep this approved PR at the top of the merge queue. Its auto build is started, but it is not merged until `release-hold` is used.
+- `release-hold`: Allow a held PR to be merged
+- `park [reason=<reason>]`: Keep this PR approved, but exclude it from the merge queue until `unpark` is used
+- `unpark` (or `unhold`): Put a parked PR back into the merge queue
+- `sync`: Synchronize the state of the PRs of this repository with GitHub, e.g. after webhooks were lost
+- `reload`: Reload the configuration and permissions of this repository right away, and report which settings were changed
 
 ## Meta commands
-- `ping`: Check if the bot is alive
+- `ping [health]`: Check if the bot is alive
+    - With `health`, also report the health of the database, the GitHub App tokens, webhooks and the merge queue
 - `help`: Print this help message
 "#
 }
@@ -87,13 +131,15 @@ mod tests {
     async fn help_command(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
             tester.post_comment("@bors help").await?;
-            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r#"
             You can use the following commands:
 
             ## PR management
-            - `r+ [p=<priority>] [rollup=<never|iffy|maybe|always>]`: Approve this PR on your behalf
+            - `r+ [<sha>] [p=<priority>] [rollup=<never|iffy|maybe|always>] [squash]`: Approve this PR on your behalf
                 - Optionally, you can specify the `<priority>` of the PR and if it is eligible for rollups (`<rollup>)`.
-            - `r=<user> [p=<priority>] [rollup=<never|iffy|maybe|always>]`: Approve this PR on behalf of `<user>`
+                - If `<sha>` is specified, the PR is only approved if its head is still at the given commit. The SHA can be abbreviated to at least 7 characters.
+                - If `squash` is specified, the commits of the PR are squashed into a single commit when it is merged.
+            - `r=<user> [<sha>] [p=<priority>] [rollup=<never|iffy|maybe|always>] [squash]`: Approve this PR on behalf of `<user>`
                 - Optionally, you can specify the `<priority>` of the PR and if it is eligible for rollups (`<rollup>)`.
                 - You can pass a comma-separated list of GitHub usernames.
             - `r-`: Unapprove this PR
@@ -101,27 +147,50 @@ mod tests {
             - `rollup=<never|iffy|maybe|always>`: Set the rollup status of the PR
             - `rollup`: Short for `rollup=always`
             - `rollup-`: Short for `rollup=maybe`
-            - `delegate=<try|review>`: Delegate permissions for running try builds or approving to the PR author
+            - `delegate=<try|review> [for=<duration>]`: Delegate permissions for running try builds or approving to the PR author
                 - `try` allows the PR author to start try builds.
                 - `review` allows the PR author to both start try builds and approve the PR.
-            - `delegate+`: Delegate approval permissions to the PR author
+                - Optionally, you can specify a `<duration>` (e.g. `12h` or `7d`) after which the delegation expires.
+            - `delegate+ [for=<duration>]`: Delegate approval permissions to the PR author
                 - Shortcut for `delegate=review`
             - `delegate-`: Remove any previously granted permission delegation
-            - `try [parent=<parent>] [jobs=<jobs>]`: Start a try build.
-                - Optionally, you can specify a `<parent>` SHA with which will the PR be merged. You can specify `parent=last` to use the same parent SHA as the previous try build.
-                - Optionally, you can select a comma-separated list of CI `<jobs>` to run in the try build.
-            - `try cancel`: Cancel a running try build
-            - `retry`: Clear a failed auto build status from an approved PR. This will cause the merge queue to attempt to start a new auto build and retry merging the PR again.
+            - `try [parent=<parent>] [jobs=<jobs>] [commit=<sha>]`: Start a try build.
+                - Optionally, you can specify a `<parent>` SHA with which will the PR be merged. You can specify `parent=last` to use the same parent SHA as the previous try build, or the name of a branch (e.g. `parent=beta`) to use its latest commit.
+                - Optionally, you can select a comma-separated list of CI `<jobs>` to run in the try build. Try builds of different jobs run concurrently, while a try build of the same jobs replaces the running one.
+                - Optionally, you can specify the `<sha>` of an older commit of the PR that should be tried instead of its head, e.g. to find out which commit of the PR introduced a regression.
+            - `try cancel [<build id>]`: Cancel running try builds, or only the try build with the given ID
+            - `env <key>=<value>...`: Set build parameters of this PR, which are passed to its try and auto builds
+                - Only the parameters and values allowed by the `build_env` option of the repository configuration can be set.
+            - `env-`: Remove all build parameters of this PR
+            - `retry [cancel-workflows] [reason=<reason>]`: Clear a failed auto build status from an approved PR. This will cause the merge queue to attempt to start a new auto build and retry merging the PR again. If the PR has no failed auto build, its failed try build is started again instead. The optional reason is recorded and shown in `info`.
+                - With `cancel-workflows`, workflows of the failed auto build that are still running are cancelled first.
             - `info`: Get information about the current PR
+            - `history`: Get the commands used on the current PR and the actions that bors performed on it
+            - `notify`: Get notified when this PR is among the first 3 PRs in the merge queue
+            - `revert [p=<priority>]`: Open a PR that reverts this PR, after it was merged by bors
+                - If `<priority>` is specified, the revert PR is approved right away with the given priority.
+            - `backport to=<branch>`: Cherry-pick this PR onto `<branch>` after it was merged by bors, and open a PR with the result
+                - If the PR is not merged yet, it is backported once it is merged.
 
             ## Repository management
-            - `treeclosed=<priority>`: Close the tree for PRs with priority less than `<priority>`
-            - `treeclosed-` or `treeopen`: Open the repository tree for merging
+            - `treeclosed=<priority> [for=<duration>] [reason="<reason>"] [branch=<branch>]`: Close the tree for PRs with priority less than `<priority>`
+                - Optionally, you can specify a `<duration>` (e.g. `30m`, `2h` or `1d`) after which the tree is reopened automatically, and the `<reason>` why the tree is closed.
+                - Optionally, you can specify a `<branch>` with its own merge queue whose tree should be closed. By default, the tree that applies to this PR is closed.
+            - `treeclosed-` or `treeopen` `[branch=<branch>]`: Open the repository tree for merging
+            - `queue pause`: Pause the merge queue. No new auto builds are started, but running auto builds are still merged.
+            - `queue resume`: Resume a paused merge queue
+            - `hold`: Keep this approved PR at the top of the merge queue. Its auto build is started, but it is not merged until `release-hold` is used.
+            - `release-hold`: Allow a held PR to be merged
+            - `park [reason=<reason>]`: Keep this PR approved, but exclude it from the merge queue until `unpark` is used
+            - `unpark` (or `unhold`): Put a parked PR back into the merge queue
+            - `sync`: Synchronize the state of the PRs of this repository with GitHub, e.g. after webhooks were lost
+            - `reload`: Reload the configuration and permissions of this repository right away, and report which settings were changed
 
             ## Meta commands
-            - `ping`: Check if the bot is alive
+            - `ping [health]`: Check if the bot is alive
+                - With `health`, also report the health of the database, the GitHub App tokens, webhooks and the merge queue
             - `help`: Print this help message
-            ");
+            "#);
             Ok(())
         })
         .await;
@@ -0,0 +1,112 @@
+use std::fmt::Write;
+use std::sync::Arc;
+
+use crate::bors::Comment;
+use crate::bors::RepositoryState;
+use crate::bors::handlers::{PullRequestData, reply_to_command};
+use crate::database::PgDbClient;
+
+/// How many of the latest audit log entries are printed by `@bors history`.
+const HISTORY_LENGTH: i64 = 30;
+
+pub(super) async fn command_history(
+    repo: Arc<RepositoryState>,
+    pr: PullRequestData<'_>,
+    db: Arc<PgDbClient>,
+) -> anyhow::Result<()> {
+    let entries = db
+        .get_audit_log(repo.repository(), Some(pr.number()), HISTORY_LENGTH)
+        .await?;
+
+    let mut message = format!("## History of PR `{}`\n", pr.number());
+    if entries.is_empty() {
+        writeln!(message, "No commands or actions have been recorded yet.")?;
+    } else {
+        writeln!(message, "| Time | Actor | Action | Details | Outcome |")?;
+        writeln!(message, "|------|-------|--------|---------|---------|")?;
+        for entry in entries {
+            writeln!(
+                message,
+                "| {} | {} | {} | {} | {} |",
+                entry.created_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                entry
+                    .actor
+                    .map(|actor| format!("`{actor}`"))
+                    .unwrap_or_else(|| "bors".to_string()),
+                entry.action,
+                entry
+                    .details
+                    .map(|details| format!("`{}`", table_cell(&details)))
+                    .unwrap_or_default(),
+                table_cell(&entry.outcome),
+            )?;
+        }
+    }
+
+    reply_to_command(&repo, pr, Comment::new(message)).await?;
+    Ok(())
+}
+
+/// Makes `text` safe to be put into a single cell of a Markdown table.
+fn table_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{BorsTester, Comment, User, run_test};
+
+    #[sqlx::test]
+    async fn history_without_entries(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors history").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @r"
+            ## History of PR `1`
+            No commands or actions have been recorded yet.
+            "
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn history_lists_commands_and_actions(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester.post_comment("@bors history").await?;
+
+            let history = tester.get_next_comment_text(()).await?;
+            assert!(history.contains("| `default-user` | approve | `@bors r+` | success |"));
+            assert!(history.contains("| bors | auto_build_started |"));
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn history_lists_rejected_commands(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .post_comment(Comment::from("@bors r+").with_author(User::unprivileged()))
+                .await?;
+            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors foo").await?;
+            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors history").await?;
+
+            let history = tester.get_next_comment_text(()).await?;
+            assert!(history.contains(
+                "| `unprivileged-user` | approve | `@bors r+` | rejected: insufficient privileges: not in review users |"
+            ));
+            assert!(history.contains(
+                r#"| `default-user` | invalid | `@bors foo` | rejected: Unknown command "foo". |"#
+            ));
+            Ok(())
+        })
+        .await;
+    }
+}
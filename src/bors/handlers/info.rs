@@ -1,10 +1,20 @@
 use crate::bors::Comment;
+use crate::bors::PullRequestStatus;
 use crate::bors::RepositoryState;
-use crate::bors::handlers::PullRequestData;
-use crate::database::{ApprovalStatus, MergeableState};
+use crate::bors::comment::describe_closed_tree;
+use crate::bors::handlers::review::pr_tree_state;
+use crate::bors::handlers::{PullRequestData, reply_to_command};
+use crate::bors::merge_queue::{has_required_approvals, is_auto_branch};
+use crate::database::MergeableState;
 use crate::database::{BuildStatus, PgDbClient};
+use crate::utils::sort_queue::{effective_priority, sort_queue_prs};
+use crate::utils::text::pluralize;
+use chrono::Utc;
 use std::sync::Arc;
 
+/// How many of the latest rejected commands are listed by `@bors info`.
+const REJECTED_COMMANDS_LENGTH: i64 = 5;
+
 pub(super) async fn command_info(
     repo: Arc<RepositoryState>,
     pr: PullRequestData<'_>,
@@ -15,8 +25,26 @@ pub(super) async fn command_info(
     let mut message = format!("## Status of PR `{}`\n", pr.number());
 
     // Approval info
-    if let ApprovalStatus::Approved(info) = &pr.db.approval_status {
-        writeln!(message, "- Approved by: `{}`", info.approver)?;
+    if pr.db.is_approved() {
+        let approvers = pr.db.approvers();
+        let required_approvals = repo.config.load().required_approvals;
+        write!(
+            message,
+            "- Approved by: {}",
+            approvers
+                .iter()
+                .map(|approver| format!("`{approver}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )?;
+        let approvals = pr.db.approval_authors.len();
+        if approvals < required_approvals {
+            write!(
+                message,
+                " ({approvals} of {required_approvals} required approvals)"
+            )?;
+        }
+        writeln!(message)?;
     } else {
         writeln!(message, "- Not Approved")?;
     }
@@ -27,6 +55,16 @@ pub(super) async fn command_info(
     } else {
         writeln!(message, "- Priority: unset")?;
     }
+    let aging_interval = repo.config.load().priority_aging_interval;
+    if aging_interval.is_some() {
+        let priority = effective_priority(pr.db, aging_interval, Utc::now());
+        if priority != i64::from(pr.db.priority.unwrap_or(0)) {
+            writeln!(
+                message,
+                "- Effective priority: {priority} (increased while waiting in the queue)"
+            )?;
+        }
+    }
 
     // Mergeability state
     writeln!(
@@ -39,6 +77,61 @@ pub(super) async fn command_info(
         }
     )?;
 
+    // Queue position
+    if pr.db.is_approved()
+        && let Some(position) = queue_position(&repo, &db, &pr).await?
+    {
+        writeln!(
+            message,
+            "- Queue position: {} ({position} {} ahead)",
+            position + 1,
+            pluralize("PR", position)
+        )?;
+    }
+
+    // Hold state
+    if pr.db.held {
+        writeln!(message, "- Held at the top of the queue")?;
+    }
+    if pr.db.parked {
+        match &pr.db.park_reason {
+            Some(reason) => writeln!(message, "- Parked (not in the merge queue): {reason}")?,
+            None => writeln!(message, "- Parked (not in the merge queue)")?,
+        }
+    }
+
+    // Build parameters
+    if !pr.db.build_env.is_empty() {
+        let vars: Vec<String> = pr
+            .db
+            .build_env
+            .iter()
+            .map(|var| format!("`{var}`"))
+            .collect();
+        writeln!(message, "- Build parameters: {}", vars.join(", "))?;
+    }
+
+    // Pending backports
+    if !pr.db.backports.is_empty() {
+        let branches: Vec<String> = pr
+            .db
+            .backports
+            .iter()
+            .map(|branch| format!("`{branch}`"))
+            .collect();
+        writeln!(
+            message,
+            "- Backport after merge to: {}",
+            branches.join(", ")
+        )?;
+    }
+
+    // Tree state
+    match describe_closed_tree(&pr_tree_state(&repo, &db, &pr).await?) {
+        Some(description) => writeln!(message, "- Tree: {description}")?,
+        None => writeln!(message, "- Tree: open")?,
+    }
+
     // Try build status
     if let Some(try_build) = &pr.db.try_build
         && try_build.status == BuildStatus::Pending
@@ -48,7 +141,7 @@ pub(super) async fn command_info(
         if let Ok(urls) = db.get_workflow_urls_for_build(try_build).await {
             message.extend(
                 urls.into_iter()
-                    .map(|url| format!("\t- Workflow URL: {url}")),
+                    .map(|url| format!("\t- Workflow URL: {url}\n")),
             );
         }
     }
@@ -69,24 +162,129 @@ pub(super) async fn command_info(
             }
         }
 
+        // Let the reader know if the base branch has moved since the build was started
+        write!(message, "\t- Based on `{}`", auto_build.parent)?;
+        match repo.client.get_branch_sha(&pr.db.base_branch).await {
+            Ok(base_sha) if base_sha.0 == auto_build.parent => {
+                writeln!(message, ", the current head of `{}`", pr.db.base_branch)?
+            }
+            Ok(base_sha) => writeln!(
+                message,
+                ", but `{}` is now at `{base_sha}`",
+                pr.db.base_branch
+            )?,
+            Err(_) => writeln!(message)?,
+        }
+
         if let Ok(urls) = db.get_workflow_urls_for_build(auto_build).await {
             message.extend(
                 urls.into_iter()
-                    .map(|url| format!("\t- Workflow URL: {url}")),
+                    .map(|url| format!("\t- Workflow URL: {url}\n")),
             );
         }
     }
 
-    repo.client
-        .post_comment(pr.number(), Comment::new(message))
-        .await?;
+    // Retries of failed builds
+    let retries = db.get_build_retries(pr.db).await?;
+    if !retries.is_empty() {
+        let auto_retries = retries
+            .iter()
+            .filter(|retry| is_auto_branch(&retry.build.branch))
+            .count();
+        let try_retries = retries.len() - auto_retries;
+        writeln!(
+            message,
+            "- Retries: {auto_retries} auto {}, {try_retries} try {}",
+            pluralize("build", auto_retries),
+            pluralize("build", try_retries)
+        )?;
+        if let Some(reason) = retries
+            .iter()
+            .rev()
+            .find_map(|retry| retry.reason.as_deref())
+        {
+            writeln!(message, "\t- Last reason: {reason}")?;
+        }
+    }
+
+    // Commands that were not executed
+    if repo.config.load().info_rejected_commands {
+        let rejected = db
+            .get_rejected_commands(repo.repository(), pr.number(), REJECTED_COMMANDS_LENGTH)
+            .await?;
+        if !rejected.is_empty() {
+            writeln!(message, "- Recently rejected commands:")?;
+            for entry in rejected {
+                writeln!(
+                    message,
+                    "\t- `{}` by `{}`: {}",
+                    entry
+                        .details
+                        .as_deref()
+                        .unwrap_or(&entry.action)
+                        .replace('\n', " "),
+                    entry.actor.as_deref().unwrap_or_default(),
+                    entry
+                        .outcome
+                        .strip_prefix("rejected: ")
+                        .unwrap_or(&entry.outcome)
+                )?;
+            }
+        }
+    }
 
-    Ok(())
+    reply_to_command(&repo, pr, Comment::new(message)).await
+}
+
+/// Returns how many PRs are ahead of the given PR in its merge queue, using the same ordering as
+/// the queue page.
+async fn queue_position(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    pr: &PullRequestData<'_>,
+) -> anyhow::Result<Option<usize>> {
+    let (queue_branches, aging_interval, required_approvals) = {
+        let config = repo.config.load();
+        (
+            config.merge_queue_branches.clone(),
+            config.priority_aging_interval,
+            config.required_approvals,
+        )
+    };
+    let same_queue = |base_branch: &String| {
+        if queue_branches.contains(&pr.db.base_branch) {
+            base_branch == &pr.db.base_branch
+        } else {
+            !queue_branches.contains(base_branch)
+        }
+    };
+    let prs = sort_queue_prs(
+        db.get_nonclosed_pull_requests(repo.repository()).await?,
+        aging_interval,
+    );
+    Ok(prs
+        .iter()
+        .filter(|queued| {
+            queued.is_approved()
+                && !queued.parked
+                && has_required_approvals(queued, required_approvals)
+                && queued.pr_status == PullRequestStatus::Open
+                && same_queue(&queued.base_branch)
+        })
+        .position(|queued| queued.number == pr.db.number))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tests::{BorsTester, WorkflowEvent, WorkflowRunData, run_test};
+    use chrono::Utc;
+
+    use crate::database::operations::set_pr_approved_at;
+    use crate::database::{MergeableState, OctocrabMergeableState};
+    use crate::github::PullRequestNumber;
+    use crate::tests::{
+        BorsBuilder, BorsTester, Comment, GitHubState, User, WorkflowEvent, WorkflowRunData,
+        default_branch_name, default_repo_name, run_test,
+    };
 
     #[sqlx::test]
     async fn info_for_unapproved_pr(pool: sqlx::PgPool) {
@@ -99,6 +297,7 @@ mod tests {
             - Not Approved
             - Priority: unset
             - Mergeable: yes
+            - Tree: open
             "
             );
             Ok(())
@@ -106,6 +305,113 @@ mod tests {
         .await;
     }
 
+    #[sqlx::test]
+    async fn info_polls_unknown_mergeability(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .modify_pr_state((), |pr| {
+                    pr.mergeable_state = OctocrabMergeableState::Unknown
+                })
+                .await;
+            tester.post_comment("@bors info").await?;
+            let info = tester.get_next_comment_text(()).await?;
+            assert!(info.contains("- Mergeable: unknown"));
+
+            // GitHub has computed the mergeability in the meantime
+            tester
+                .modify_pr_state((), |pr| pr.mergeable_state = OctocrabMergeableState::Clean)
+                .await;
+            tester
+                .wait_for_pr((), |pr| pr.mergeable_state == MergeableState::Mergeable)
+                .await?;
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn info_with_rejected_commands(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("info_rejected_commands = true"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(Comment::from("@bors r+").with_author(User::unprivileged()))
+                    .await?;
+                tester.expect_comments((), 1).await;
+                tester.post_comment("@bors foo").await?;
+                tester.expect_comments((), 1).await;
+
+                tester.post_comment("@bors info").await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @r#"
+                    ## Status of PR `1`
+                    - Not Approved
+                    - Priority: unset
+                    - Mergeable: yes
+                    - Tree: open
+                    - Recently rejected commands:
+                    	- `@bors r+` by `unprivileged-user`: insufficient privileges: not in review users
+                    	- `@bors foo` by `default-user`: Unknown command "foo".
+                    "#
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn info_hides_rejected_commands_by_default(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors foo").await?;
+            tester.expect_comments((), 1).await;
+
+            tester.post_comment("@bors info").await?;
+            let info = tester.get_next_comment_text(()).await?;
+            assert!(!info.contains("rejected"));
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn info_for_aged_pr(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool.clone())
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+priority_aging_interval = 3600
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors r+ p=1").await?;
+                tester.expect_comments((), 1).await;
+                set_pr_approved_at(
+                    &pool,
+                    &default_repo_name(),
+                    PullRequestNumber(1),
+                    Utc::now() - chrono::Duration::minutes(150),
+                )
+                .await?;
+
+                tester.post_comment("@bors info").await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @r"
+                ## Status of PR `1`
+                - Approved by: `default-user`
+                - Priority: 1
+                - Effective priority: 3 (increased while waiting in the queue)
+                - Mergeable: yes
+                - Queue position: 1 (0 PRs ahead)
+                - Tree: open
+                "
+                );
+                Ok(())
+            })
+            .await;
+    }
+
     #[sqlx::test]
     async fn info_for_approved_pr(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
@@ -119,6 +425,56 @@ mod tests {
             - Approved by: `default-user`
             - Priority: unset
             - Mergeable: yes
+            - Queue position: 1 (0 PRs ahead)
+            - Tree: open
+            "
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn info_for_parked_pr(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester
+                .post_comment(r#"@bors park reason="waiting for the release""#)
+                .await?;
+            tester.expect_comments((), 1).await;
+
+            tester.post_comment("@bors info").await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                ## Status of PR `1`
+                - Approved by: `default-user`
+                - Priority: unset
+                - Mergeable: yes
+                - Parked (not in the merge queue): waiting for the release
+                - Tree: open
+                ");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn info_for_co_reviewed_pr(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors r=user1").await?;
+            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors r=user2").await?;
+            tester.expect_comments((), 1).await;
+
+            tester.post_comment("@bors info").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @r"
+            ## Status of PR `1`
+            - Approved by: `user1`, `user2`
+            - Priority: unset
+            - Mergeable: yes
+            - Queue position: 1 (0 PRs ahead)
+            - Tree: open
             "
             );
             Ok(())
@@ -126,6 +482,27 @@ mod tests {
         .await;
     }
 
+    #[sqlx::test]
+    async fn info_for_pr_with_missing_approvals(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("required_approvals = 3"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors r=user1,user2").await?;
+                tester.expect_comments((), 1).await;
+
+                tester.post_comment("@bors info").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                    ## Status of PR `1`
+                    - Approved by: `user1`, `user2` (1 of 3 required approvals)
+                    - Priority: unset
+                    - Mergeable: yes
+                    - Tree: open
+                    ");
+                Ok(())
+            })
+            .await;
+    }
+
     #[sqlx::test]
     async fn info_for_pr_with_priority(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
@@ -140,6 +517,7 @@ mod tests {
             - Not Approved
             - Priority: 5
             - Mergeable: yes
+            - Tree: open
             "
             );
             Ok(())
@@ -147,6 +525,23 @@ mod tests {
         .await;
     }
 
+    #[sqlx::test]
+    async fn info_shows_queue_position(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+            tester.approve(()).await?;
+            tester.approve(pr2.id()).await?;
+
+            tester
+                .post_comment(Comment::new(pr2.id(), "@bors info"))
+                .await?;
+            let comment = tester.get_next_comment_text(pr2.id()).await?;
+            assert!(comment.contains("- Queue position: 2 (1 PR ahead)"));
+            Ok(())
+        })
+        .await;
+    }
+
     #[sqlx::test]
     async fn info_for_pr_with_try_build(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
@@ -161,6 +556,7 @@ mod tests {
             - Not Approved
             - Priority: unset
             - Mergeable: yes
+            - Tree: open
             - Try build is in progress
             "
             );
@@ -192,6 +588,8 @@ mod tests {
             - Approved by: `default-user`
             - Priority: 10
             - Mergeable: yes
+            - Queue position: 1 (0 PRs ahead)
+            - Tree: open
             - Try build is in progress
             	- Workflow URL: https://github.com/rust-lang/borstest/actions/runs/1
             "
@@ -200,4 +598,62 @@ mod tests {
         })
         .await;
     }
+
+    #[sqlx::test]
+    async fn info_for_pr_with_closed_tree(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .post_comment(r#"@bors treeclosed=100 reason="CI outage""#)
+                .await?;
+            tester.expect_comments((), 1).await;
+
+            tester.post_comment("@bors info").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @r"
+            ## Status of PR `1`
+            - Not Approved
+            - Priority: unset
+            - Mergeable: yes
+            - Tree: closed for PRs with priority less than 100 (reason: CI outage)
+            "
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn info_for_pr_with_auto_build(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+
+            tester.post_comment("@bors info").await?;
+            let comment = tester.get_next_comment_text(()).await?;
+            assert!(comment.contains("- Auto build is in progress"));
+            assert!(comment.contains("- Based on `main-sha1`, the current head of `main`"));
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn info_for_pr_with_outdated_auto_build(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester
+                .modify_branch(default_branch_name(), |branch| {
+                    branch.set_to_sha("main-sha2")
+                })
+                .await;
+
+            tester.post_comment("@bors info").await?;
+            let comment = tester.get_next_comment_text(()).await?;
+            assert!(comment.contains("- Based on `main-sha1`, but `main` is now at `main-sha2`"));
+            Ok(())
+        })
+        .await;
+    }
 }
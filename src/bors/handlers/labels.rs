@@ -1,16 +1,26 @@
 use itertools::Itertools;
 use tracing::log;
 
+use crate::PgDbClient;
 use crate::bors::RepositoryState;
+use crate::bors::outbox::perform_outbox_actions;
+use crate::database::OutboxAction;
 use crate::github::{LabelModification, LabelTrigger, PullRequestNumber};
 
 /// If there are any label modifications that should be performed on the given PR when `trigger`
 /// happens, this function will perform them.
 pub async fn handle_label_trigger(
     repo: &RepositoryState,
+    db: &PgDbClient,
     pr: PullRequestNumber,
     trigger: LabelTrigger,
 ) -> anyhow::Result<()> {
+    let actions = label_trigger_actions(repo, trigger);
+    perform_outbox_actions(repo, db, pr, &actions).await
+}
+
+/// Returns outbox actions that perform the label modifications configured for `trigger`.
+pub fn label_trigger_actions(repo: &RepositoryState, trigger: LabelTrigger) -> Vec<OutboxAction> {
     let mut add: Vec<String> = Vec::new();
     let mut remove: Vec<String> = Vec::new();
     if let Some(modifications) = repo.config.load().labels.get(&trigger) {
@@ -22,13 +32,13 @@ pub async fn handle_label_trigger(
                 LabelModification::Remove(label) => itertools::Either::Right(label.clone()),
             });
     }
+
+    let mut actions = vec![];
     if !add.is_empty() {
-        log::info!("Adding label(s) {add:?}");
-        repo.client.add_labels(pr, &add).await?;
+        actions.push(OutboxAction::AddLabels { labels: add });
     }
     if !remove.is_empty() {
-        log::info!("Removing label(s) {remove:?}");
-        repo.client.remove_labels(pr, &remove).await?;
+        actions.push(OutboxAction::RemoveLabels { labels: remove });
     }
-    Ok(())
+    actions
 }
@@ -1,54 +1,87 @@
 use std::sync::Arc;
 
 use super::mergeability_queue::MergeabilityQueueSender;
-use crate::bors::command::{BorsCommand, CommandParseError};
+use crate::bors::command::{Approver, BorsCommand, CommandParseError};
 use crate::bors::comment::CommentTag;
-use crate::bors::event::{BorsGlobalEvent, BorsRepositoryEvent, PullRequestComment};
+use crate::bors::dead_letter::replay_dead_letters;
+use crate::bors::event::{
+    BorsGlobalEvent, BorsRepositoryEvent, PullRequestComment, ReactionTarget, ReviewVerdict,
+};
+use crate::bors::handlers::backport::command_backport;
+use crate::bors::handlers::cooldown::{RepeatedCommand, check_repeated_command};
 use crate::bors::handlers::help::command_help;
+use crate::bors::handlers::history::command_history;
 use crate::bors::handlers::info::command_info;
-use crate::bors::handlers::labels::handle_label_trigger;
+pub(crate) use crate::bors::handlers::labels::handle_label_trigger;
+use crate::bors::handlers::notify::command_notify;
 use crate::bors::handlers::ping::command_ping;
+pub(crate) use crate::bors::handlers::pr_events::run_merged_pr_hooks;
 use crate::bors::handlers::pr_events::{
     handle_pull_request_assigned, handle_pull_request_unassigned,
 };
+pub(crate) use crate::bors::handlers::refresh::ZOMBIE_WORKFLOW_METRICS;
 use crate::bors::handlers::refresh::{
-    refresh_pending_builds, reload_mergeability_status, reload_repository_config,
-    reload_repository_permissions,
+    cancel_zombie_workflows, clean_up_try_artifacts, command_reload, command_sync,
+    reconcile_pending_builds, refresh_pending_builds, reload_mergeability_status,
+    reload_repository, reload_repository_config, reload_repository_permissions,
 };
+use crate::bors::handlers::repository::{deactivate_repository, reactivate_repository};
 use crate::bors::handlers::retry::command_retry;
+use crate::bors::handlers::revert::command_revert;
 use crate::bors::handlers::review::{
-    command_approve, command_close_tree, command_open_tree, command_unapprove,
+    command_approve, command_clear_build_env, command_close_tree, command_hold, command_open_tree,
+    command_park, command_pause_queue, command_release_hold, command_resume_queue,
+    command_set_build_env, command_unapprove, command_unpark,
 };
 use crate::bors::handlers::trybuild::{TRY_BRANCH_NAME, command_try_build, command_try_cancel};
-use crate::bors::handlers::workflow::{handle_workflow_completed, handle_workflow_started};
-use crate::bors::merge_queue::{AUTO_BRANCH_NAME, MergeQueueSender};
-use crate::bors::{BorsContext, CommandPrefix, Comment, RepositoryState};
-use crate::database::{DelegatedPermission, PullRequestModel};
+use crate::bors::handlers::workflow::{
+    handle_build_result_reported, handle_commit_status_changed, handle_workflow_completed,
+    handle_workflow_started,
+};
+use crate::bors::hooks::CommandHookContext;
+use crate::bors::merge_queue::{MergeQueueSender, is_auto_branch};
+use crate::bors::outbox::deliver_pending_actions;
+use crate::bors::{BorsContext, CommandPrefix, Comment, RefreshKind, RepositoryState};
+use crate::database::{
+    CoordinationLock, DeactivationReason, DelegatedPermission, OctocrabMergeableState, PrMilestone,
+    PullRequestModel,
+};
 use crate::github::api::client::HideCommentReason;
-use crate::github::{GithubUser, LabelTrigger, PullRequest, PullRequestNumber};
+use crate::github::{GithubRepoName, GithubUser, LabelTrigger, PullRequest, PullRequestNumber};
 use crate::permissions::PermissionType;
-use crate::{CommandParser, PgDbClient, TeamApiClient, load_repositories};
+use crate::{CommandParser, PgDbClient, load_repositories};
 use anyhow::Context;
+use futures::StreamExt;
 use octocrab::Octocrab;
+use octocrab::models::CommentId;
+use octocrab::models::reactions::ReactionContent;
 use pr_events::{
     handle_pull_request_closed, handle_pull_request_converted_to_draft, handle_pull_request_edited,
-    handle_pull_request_merged, handle_pull_request_opened, handle_pull_request_ready_for_review,
-    handle_pull_request_reopened, handle_push_to_branch, handle_push_to_pull_request,
+    handle_pull_request_labels_changed, handle_pull_request_merged, handle_pull_request_opened,
+    handle_pull_request_ready_for_review, handle_pull_request_reopened, handle_push_to_branch,
+    handle_push_to_pull_request,
 };
 use refresh::sync_pull_requests_state;
 use review::{command_delegate, command_set_priority, command_set_rollup, command_undelegate};
 use tracing::Instrument;
 
+mod backport;
+mod config;
+mod cooldown;
 mod help;
+mod history;
 mod info;
 mod labels;
+mod notify;
 mod ping;
 mod pr_events;
 mod refresh;
+mod repository;
 mod retry;
+mod revert;
 mod review;
 mod trybuild;
-mod workflow;
+pub(super) mod workflow;
 
 /// This function executes a single BORS repository event
 pub async fn handle_bors_repository_event(
@@ -85,20 +118,25 @@ pub async fn handle_bors_repository_event(
                 author = comment.author.username
             );
             let pr_number = comment.pr_number;
-            if let Err(error) =
-                handle_comment(Arc::clone(&repo), db, ctx, comment, merge_queue_tx.clone())
-                    .instrument(span.clone())
-                    .await
+            // The future handling all the commands is large, so it is boxed to avoid overflowing
+            // the stack
+            if let Err(error) = Box::pin(handle_comment(
+                Arc::clone(&repo),
+                db,
+                ctx,
+                comment,
+                mergeability_queue_tx,
+                merge_queue_tx.clone(),
+            ))
+            .instrument(span.clone())
+            .await
             {
-                repo.client
-                    .post_comment(
-                        pr_number,
-                        Comment::new(
-                            ":x: Encountered an error while executing command".to_string(),
-                        ),
-                    )
-                    .await
-                    .context("Cannot send comment reacting to an error")?;
+                repo.post_comment(
+                    pr_number,
+                    Comment::new(":x: Encountered an error while executing command".to_string()),
+                )
+                .await
+                .context("Cannot send comment reacting to an error")?;
                 return Err(error.context("Cannot perform command"));
             }
         }
@@ -128,11 +166,37 @@ pub async fn handle_bors_repository_event(
             #[cfg(test)]
             super::WAIT_FOR_WORKFLOW_COMPLETED.mark();
         }
+        BorsRepositoryEvent::BuildResultReported(payload) => {
+            let span = tracing::info_span!(
+                "Build result reported",
+                repo = payload.repository.to_string(),
+                build_id = payload.build_id
+            );
+            handle_build_result_reported(repo, db, payload, &merge_queue_tx)
+                .instrument(span.clone())
+                .await?;
+
+            #[cfg(test)]
+            super::WAIT_FOR_BUILD_RESULT_REPORTED.mark();
+        }
+        BorsRepositoryEvent::CommitStatusChanged(payload) => {
+            let span = tracing::info_span!(
+                "Commit status changed",
+                repo = payload.repository.to_string(),
+                context = payload.context
+            );
+            handle_commit_status_changed(repo, db, payload, &merge_queue_tx)
+                .instrument(span.clone())
+                .await?;
+
+            #[cfg(test)]
+            super::WAIT_FOR_COMMIT_STATUS.mark();
+        }
         BorsRepositoryEvent::PullRequestEdited(payload) => {
             let span =
                 tracing::info_span!("Pull request edited", repo = payload.repository.to_string());
 
-            handle_pull_request_edited(repo, db, mergeability_queue_tx, payload)
+            handle_pull_request_edited(repo, db, mergeability_queue_tx, merge_queue_tx, payload)
                 .instrument(span.clone())
                 .await?;
         }
@@ -163,7 +227,7 @@ pub async fn handle_bors_repository_event(
             let span =
                 tracing::info_span!("Pull request closed", repo = payload.repository.to_string());
 
-            handle_pull_request_closed(repo, db, payload)
+            handle_pull_request_closed(repo, db, merge_queue_tx, payload)
                 .instrument(span.clone())
                 .await?;
         }
@@ -191,7 +255,7 @@ pub async fn handle_bors_repository_event(
                 repo = payload.repository.to_string()
             );
 
-            handle_pull_request_converted_to_draft(repo, db, payload)
+            handle_pull_request_converted_to_draft(repo, db, merge_queue_tx, payload)
                 .instrument(span.clone())
                 .await?;
         }
@@ -215,13 +279,23 @@ pub async fn handle_bors_repository_event(
                 .instrument(span.clone())
                 .await?;
         }
+        BorsRepositoryEvent::PullRequestLabelsChanged(payload) => {
+            let span = tracing::info_span!(
+                "Pull request labels changed",
+                repo = payload.repository.to_string()
+            );
+
+            handle_pull_request_labels_changed(repo, db, merge_queue_tx, payload)
+                .instrument(span.clone())
+                .await?;
+        }
         BorsRepositoryEvent::PullRequestReadyForReview(payload) => {
             let span = tracing::info_span!(
                 "Pull request ready for review",
                 repo = payload.repository.to_string()
             );
 
-            handle_pull_request_ready_for_review(repo, db, payload)
+            handle_pull_request_ready_for_review(repo, db, merge_queue_tx, payload)
                 .instrument(span.clone())
                 .await?;
         }
@@ -233,6 +307,20 @@ pub async fn handle_bors_repository_event(
                 .instrument(span.clone())
                 .await?;
         }
+        BorsRepositoryEvent::RepositoryArchived(repository) => {
+            let span = tracing::info_span!("Repository archived", repo = repository.to_string());
+
+            deactivate_repository(&repo, &db, DeactivationReason::Archived)
+                .instrument(span.clone())
+                .await?;
+        }
+        BorsRepositoryEvent::RepositoryUnarchived(repository) => {
+            let span = tracing::info_span!("Repository unarchived", repo = repository.to_string());
+
+            reactivate_repository(&repo, &db, DeactivationReason::Archived, &merge_queue_tx)
+                .instrument(span.clone())
+                .await?;
+        }
     }
     Ok(())
 }
@@ -241,8 +329,7 @@ pub async fn handle_bors_repository_event(
 pub async fn handle_bors_global_event(
     event: BorsGlobalEvent,
     ctx: Arc<BorsContext>,
-    gh_client: &Octocrab,
-    team_api_client: &TeamApiClient,
+    gh_client: &mut Octocrab,
     mergeability_queue_tx: MergeabilityQueueSender,
     merge_queue_tx: MergeQueueSender,
 ) -> anyhow::Result<()> {
@@ -250,33 +337,150 @@ pub async fn handle_bors_global_event(
     match event {
         BorsGlobalEvent::InstallationsChanged => {
             let span = tracing::info_span!("Installations changed");
-            reload_repos(ctx, gh_client, team_api_client)
-                .instrument(span)
+            let ReloadedRepos { added, removed } = reload_repos(ctx, gh_client)
+                .instrument(span.clone())
                 .await?;
+
+            // The app was uninstalled from these repositories (or its installation was suspended)
+            for repo in removed {
+                let subspan = tracing::info_span!(
+                    parent: &span,
+                    "Deactivate",
+                    repo = repo.repository().to_string()
+                );
+                if let Err(error) =
+                    deactivate_repository(&repo, &db, DeactivationReason::Uninstalled)
+                        .instrument(subspan)
+                        .await
+                {
+                    tracing::error!(
+                        "Cannot deactivate repository {}: {error:?}",
+                        repo.repository()
+                    );
+                }
+            }
+
+            // Seed the PRs of newly added repositories, so that commands and the merge queue
+            // work right away, without waiting for a webhook from each PR. A failure in one
+            // repository does not prevent the others from being backfilled.
+            for repo in added {
+                let subspan = tracing::info_span!(
+                    parent: &span,
+                    "Backfill PRs",
+                    repo = repo.repository().to_string()
+                );
+                let name = repo.repository().clone();
+                let result = async {
+                    reactivate_repository(
+                        &repo,
+                        &db,
+                        DeactivationReason::Uninstalled,
+                        &merge_queue_tx,
+                    )
+                    .await?;
+                    sync_pull_requests_state(Arc::clone(&repo), Arc::clone(&db)).await?;
+                    reload_mergeability_status(repo, &db, mergeability_queue_tx.clone()).await
+                }
+                .instrument(subspan)
+                .await;
+                if let Err(error) = result {
+                    tracing::error!("Cannot backfill PRs of repository {name}: {error:?}");
+                }
+            }
         }
+        // Configs and permissions are refreshed in the background, so that a refresh of many
+        // repositories does not delay the handling of other events. If the previous refresh is
+        // still running, the new one is skipped.
         BorsGlobalEvent::RefreshConfig => {
-            let span = tracing::info_span!("Refresh config");
-            for_each_repo(&ctx, |repo| {
-                let span = tracing::info_span!("Repo", repo = repo.repository().to_string());
-                reload_repository_config(repo).instrument(span)
-            })
-            .instrument(span)
-            .await?;
+            if let Some(refresh) = ctx.start_refresh(RefreshKind::Config) {
+                let span = tracing::info_span!("Refresh config");
+                tokio::spawn(
+                    async move {
+                        let result = for_each_repo_with_jitter(&ctx, |repo| {
+                            let span =
+                                tracing::info_span!("Repo", repo = repo.repository().to_string());
+                            reload_repository_config(repo).instrument(span)
+                        })
+                        .await;
+                        if let Err(error) = result {
+                            tracing::error!("Cannot refresh config: {error:?}");
+                        }
+                        drop(refresh);
+
+                        #[cfg(test)]
+                        crate::bors::WAIT_FOR_CONFIG_REFRESH.mark();
+                    }
+                    .instrument(span),
+                );
+            }
         }
         BorsGlobalEvent::RefreshPermissions => {
-            let span = tracing::info_span!("Refresh permissions");
+            if let Some(refresh) = ctx.start_refresh(RefreshKind::Permissions) {
+                let span = tracing::info_span!("Refresh permissions");
+                tokio::spawn(
+                    async move {
+                        let result = for_each_repo_with_jitter(&ctx, |repo| {
+                            let span =
+                                tracing::info_span!("Repo", repo = repo.repository().to_string());
+                            reload_repository_permissions(repo, &ctx.team_api).instrument(span)
+                        })
+                        .await;
+                        if let Err(error) = result {
+                            tracing::error!("Cannot refresh permissions: {error:?}");
+                        }
+                        drop(refresh);
+
+                        #[cfg(test)]
+                        crate::bors::WAIT_FOR_PERMISSIONS_REFRESH.mark();
+                    }
+                    .instrument(span),
+                );
+            }
+        }
+        BorsGlobalEvent::AppClientChanged(client) => {
+            let span = tracing::info_span!("App client changed");
+            *gh_client = *client;
+            // The clients of the repositories were created from the previous app client, so
+            // they still use the old private key
+            reload_repos(ctx, gh_client).instrument(span).await?;
+            tracing::info!("Repositories were reloaded with the new private key");
+
+            #[cfg(test)]
+            crate::bors::WAIT_FOR_APP_CLIENT_CHANGE.mark();
+        }
+        BorsGlobalEvent::ReloadRepository(repo_name) => {
+            let span = tracing::info_span!("Reload repository", repo = repo_name.to_string());
+            let repo = ctx.repositories.read().unwrap().get(&repo_name).cloned();
+            match repo {
+                Some(repo) => {
+                    reload_repository(repo, &db, &ctx.team_api)
+                        .instrument(span)
+                        .await?;
+                }
+                None => tracing::warn!("Cannot reload unknown repository {repo_name}"),
+            }
+
+            #[cfg(test)]
+            crate::bors::WAIT_FOR_REPOSITORY_RELOAD.mark();
+        }
+        BorsGlobalEvent::ReconcilePendingBuilds => {
+            let span = tracing::info_span!("Reconcile pending builds");
             for_each_repo(&ctx, |repo| {
                 let span = tracing::info_span!("Repo", repo = repo.repository().to_string());
-                reload_repository_permissions(repo, team_api_client).instrument(span)
+                reconcile_pending_builds(repo, Arc::clone(&db), merge_queue_tx.clone())
+                    .instrument(span)
             })
             .instrument(span)
             .await?;
+
+            #[cfg(test)]
+            crate::bors::WAIT_FOR_PENDING_BUILDS_RECONCILE.mark();
         }
         BorsGlobalEvent::RefreshPendingBuilds => {
             let span = tracing::info_span!("Refresh pending builds");
             for_each_repo(&ctx, |repo| {
                 let span = tracing::info_span!("Repo", repo = repo.repository().to_string());
-                refresh_pending_builds(repo, &db).instrument(span)
+                refresh_pending_builds(repo, &db, merge_queue_tx.clone()).instrument(span)
             })
             .instrument(span)
             .await?;
@@ -309,9 +513,80 @@ pub async fn handle_bors_global_event(
             #[cfg(test)]
             crate::bors::WAIT_FOR_PR_STATUS_REFRESH.mark();
         }
+        BorsGlobalEvent::CancelZombieWorkflows => {
+            let span = tracing::info_span!("Cancel zombie workflows");
+            for_each_repo(&ctx, |repo| {
+                let subspan = tracing::info_span!("Repo", repo = repo.repository().to_string());
+                cancel_zombie_workflows(repo, &db).instrument(subspan)
+            })
+            .instrument(span)
+            .await?;
+
+            #[cfg(test)]
+            crate::bors::WAIT_FOR_ZOMBIE_WORKFLOWS_CANCEL.mark();
+        }
+        BorsGlobalEvent::CleanUpTryArtifacts => {
+            let span = tracing::info_span!("Clean up try artifacts");
+            for_each_repo(&ctx, |repo| {
+                let subspan = tracing::info_span!("Repo", repo = repo.repository().to_string());
+                clean_up_try_artifacts(repo, &db).instrument(subspan)
+            })
+            .instrument(span)
+            .await?;
+
+            #[cfg(test)]
+            crate::bors::WAIT_FOR_TRY_ARTIFACT_CLEANUP.mark();
+        }
         BorsGlobalEvent::ProcessMergeQueue => {
             merge_queue_tx.maybe_perform_tick().await?;
         }
+        BorsGlobalEvent::DeliverOutbox => {
+            let span = tracing::info_span!("Deliver outbox");
+            deliver_outbox(&ctx).instrument(span).await?;
+
+            #[cfg(test)]
+            crate::bors::WAIT_FOR_OUTBOX_DELIVERY.mark();
+        }
+        BorsGlobalEvent::ReplayDeadLetters => {
+            let span = tracing::info_span!("Replay dead letters");
+            // Another instance could otherwise handle the same webhooks again
+            if let Some(lock) = db.try_lock(CoordinationLock::DeadLetterReplay).await? {
+                let result = for_each_repo(&ctx, |repo| {
+                    let subspan = tracing::info_span!("Repo", repo = repo.repository().to_string());
+                    replay_dead_letters(
+                        repo,
+                        Arc::clone(&ctx),
+                        mergeability_queue_tx.clone(),
+                        merge_queue_tx.clone(),
+                    )
+                    .instrument(subspan)
+                })
+                .instrument(span)
+                .await;
+                lock.release().await;
+                result?;
+            }
+
+            #[cfg(test)]
+            crate::bors::WAIT_FOR_DEAD_LETTER_REPLAY.mark();
+        }
+    }
+    Ok(())
+}
+
+/// Performs the GitHub side effects that have not been performed yet, e.g. because of a GitHub
+/// outage or a restart of bors.
+pub async fn deliver_outbox(ctx: &BorsContext) -> anyhow::Result<()> {
+    let db = &ctx.db;
+    // Another instance could otherwise perform the same actions again
+    if let Some(lock) = db.try_lock(CoordinationLock::OutboxDelivery).await? {
+        let result = for_each_repo(ctx, |repo| {
+            let span = tracing::info_span!("Repo", repo = repo.repository().to_string());
+            deliver_pending_actions(repo, db).instrument(span)
+        })
+        .await;
+        lock.release().await;
+        result?;
     }
     Ok(())
 }
@@ -331,10 +606,62 @@ where
     Ok(())
 }
 
+/// Maximum number of repositories that are refreshed concurrently by periodic refreshes.
+const REFRESH_CONCURRENCY: usize = 16;
+
+/// Perform an asynchronous operation created by `make_fut` for each repository, with at most
+/// [`REFRESH_CONCURRENCY`] operations running at once. Each operation starts after a random delay
+/// of up to [`BorsContext::refresh_jitter`], so that the operations do not hit GitHub in a burst.
+async fn for_each_repo_with_jitter<MakeFut, Fut>(
+    ctx: &BorsContext,
+    make_fut: MakeFut,
+) -> anyhow::Result<()>
+where
+    MakeFut: Fn(Arc<RepositoryState>) -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let repos: Vec<Arc<RepositoryState>> =
+        ctx.repositories.read().unwrap().values().cloned().collect();
+    let max_jitter = ctx.refresh_jitter();
+    futures::stream::iter(repos)
+        .map(|repo| {
+            let delay = max_jitter.mul_f64(rand::random::<f64>());
+            let fut = make_fut(repo);
+            async move {
+                tokio::time::sleep(delay).await;
+                fut.await
+            }
+        })
+        .buffer_unordered(REFRESH_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(())
+}
+
+/// Returns the command that corresponds to the verdict of a submitted GitHub review.
+fn review_verdict_command(verdict: &ReviewVerdict) -> BorsCommand {
+    match verdict {
+        ReviewVerdict::Approved { commit } => BorsCommand::Approve {
+            approver: Approver::Myself,
+            priority: None,
+            rollup: None,
+            squash: false,
+            commit: Some(commit.clone()),
+        },
+        ReviewVerdict::ChangesRequested => BorsCommand::Unapprove,
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct PullRequestData<'a> {
     pub github: &'a PullRequest,
     pub db: &'a PullRequestModel,
+    /// Review thread in which the command that is being handled was posted, if any.
+    pub review_thread: Option<CommentId>,
+    /// Comment that contains the command that is being handled, if it can be reacted to.
+    pub command_comment: Option<ReactionTarget>,
 }
 
 impl PullRequestData<'_> {
@@ -348,12 +675,15 @@ async fn handle_comment(
     database: Arc<PgDbClient>,
     ctx: Arc<BorsContext>,
     comment: PullRequestComment,
+    mergeability_queue_tx: MergeabilityQueueSender,
     merge_queue_tx: MergeQueueSender,
 ) -> anyhow::Result<()> {
     use std::fmt::Write;
 
     let pr_number = comment.pr_number;
-    let mut commands = ctx.parser.parse_commands(&comment.text);
+    let mut commands = ctx
+        .parser
+        .parse_commands_with_aliases(&comment.text, &repo.config.load().command_aliases);
 
     // Temporary special case for migration from homu on rust-lang/rust.
     // Try to parse `@bors try` commands with a hardcoded prefix normally assigned to homu.
@@ -371,6 +701,14 @@ async fn handle_comment(
         commands.extend(homu_commands);
     }
 
+    // A review without explicit commands can act as an approval command, if enabled.
+    if commands.is_empty()
+        && repo.config.load().approve_on_review
+        && let Some(verdict) = &comment.review_verdict
+    {
+        commands.push(Ok(review_verdict_command(verdict)));
+    }
+
     // Bail if no commands
     if commands.is_empty() {
         return Ok(());
@@ -379,11 +717,61 @@ async fn handle_comment(
     tracing::debug!("Commands: {commands:?}");
     tracing::trace!("Text: {}", comment.text);
 
+    // `ping` and `help` are answered without reading anything from GitHub or the database, so
+    // that they work even when those are degraded. This allows users to distinguish a bot that
+    // is down from a merge queue that is stuck.
+    if commands
+        .iter()
+        .all(|command| matches!(command, Ok(BorsCommand::Ping { .. } | BorsCommand::Help)))
+    {
+        for command in commands {
+            match command {
+                Ok(BorsCommand::Ping { health }) => {
+                    let span = tracing::info_span!("Ping");
+                    command_ping(Arc::clone(&repo), &ctx, pr_number, health)
+                        .instrument(span)
+                        .await?;
+                }
+                _ => {
+                    let span = tracing::info_span!("Help");
+                    command_help(Arc::clone(&repo), pr_number)
+                        .instrument(span)
+                        .await?;
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    // Lines of the comment with commands, recorded in the audit log
+    let command_text = comment
+        .text
+        .lines()
+        .filter(|line| line.contains(ctx.parser.prefix().as_ref()))
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let repo_db = database.repo_db(repo.repository()).await?;
+    if let Some(reason) = repo_db.and_then(|repo| repo.deactivation_reason) {
+        // Deactivated repositories cannot be commented on (archived repositories are read-only
+        // and uninstalled ones are not accessible), so the command is only logged.
+        tracing::info!(
+            "Ignoring command `{command_text}` on PR {pr_number}, because the repository is deactivated: {reason}"
+        );
+        return Ok(());
+    }
+
     let pr_github = repo
         .client
         .get_pull_request(pr_number)
         .await
         .with_context(|| format!("Cannot get information about PR {pr_number}"))?;
+    // GitHub computes mergeability lazily, so make sure that the unknown state that is stored
+    // below does not stay unknown
+    if pr_github.mergeable_state == OctocrabMergeableState::Unknown {
+        mergeability_queue_tx.enqueue_pr(repo.repository().clone(), pr_number);
+    }
 
     for command in commands {
         match command {
@@ -393,25 +781,103 @@ async fn handle_comment(
                     .upsert_pull_request(repo.repository(), pr_github.clone().into())
                     .await
                     .with_context(|| format!("Cannot upsert PR {pr_number} into the database"))?;
+                database
+                    .record_pr_milestone(repo.repository(), pr_number, PrMilestone::FirstCommand)
+                    .await?;
 
                 let pr = PullRequestData {
                     github: &pr_github,
                     db: &pr_db,
+                    review_thread: comment.review_thread,
+                    command_comment: comment.reaction_target,
                 };
 
+                let hook_ctx = CommandHookContext {
+                    repository: repo.repository(),
+                    pr: &pr_github,
+                    author: &comment.author,
+                    command: &command,
+                };
+                if let Err(message) = run_before_command_hooks(&ctx, &hook_ctx).await {
+                    tracing::info!("Command rejected by a hook: {message}");
+                    database
+                        .record_audit_command(
+                            repo.repository(),
+                            pr_number,
+                            &comment.author.username,
+                            command.name(),
+                            &command_text,
+                            &format!("rejected: {message}"),
+                        )
+                        .await?;
+                    reply_to_command(
+                        &repo,
+                        pr,
+                        Comment::new(format!(":no_entry_sign: Command rejected: {message}")),
+                    )
+                    .await?;
+                    continue;
+                }
+
+                let repetition = check_repeated_command(
+                    &repo,
+                    &database,
+                    pr,
+                    &comment.author,
+                    &command,
+                    &command_text,
+                )
+                .await?;
+                if let Some(outcome) = repetition.audit_outcome() {
+                    database
+                        .record_audit_command(
+                            repo.repository(),
+                            pr_number,
+                            &comment.author.username,
+                            command.name(),
+                            &command_text,
+                            outcome,
+                        )
+                        .await?;
+                    if let RepeatedCommand::IgnoreWithNote(note) = repetition {
+                        reply_to_command(&repo, pr, note).await?;
+                    }
+                    continue;
+                }
+
+                // The repository and the database are moved into the command handler, keep a copy
+                // for the hooks and the audit log
+                let hook_repo = Arc::clone(&repo);
+                let hook_database = Arc::clone(&database);
                 let repo = Arc::clone(&repo);
                 let database = Arc::clone(&database);
-                let result = match command {
-                    BorsCommand::Retry => {
+                let result = match command.clone() {
+                    BorsCommand::Retry {
+                        cancel_workflows,
+                        reason,
+                    } => {
                         let span = tracing::info_span!("Retry");
-                        command_retry(repo, database, pr, &comment.author, &merge_queue_tx)
-                            .instrument(span)
-                            .await
+                        // Retried try builds mention the prefix in the same way as `Try` below
+                        let command_prefix: CommandPrefix = "@bors".to_string().into();
+                        command_retry(
+                            repo,
+                            database,
+                            pr,
+                            &comment.author,
+                            cancel_workflows,
+                            reason,
+                            &merge_queue_tx,
+                            &command_prefix,
+                        )
+                        .instrument(span)
+                        .await
                     }
                     BorsCommand::Approve {
                         approver,
                         priority,
                         rollup,
+                        squash,
+                        commit,
                     } => {
                         let span = tracing::info_span!("Approve");
                         command_approve(
@@ -423,18 +889,32 @@ async fn handle_comment(
                             &approver,
                             priority,
                             rollup,
+                            squash,
+                            commit.as_ref(),
                             &merge_queue_tx,
                         )
                         .instrument(span)
                         .await
                     }
-                    BorsCommand::OpenTree => {
+                    BorsCommand::OpenTree { branch } => {
                         let span = tracing::info_span!("TreeOpen");
-                        command_open_tree(repo, database, pr, &comment.author, &merge_queue_tx)
-                            .instrument(span)
-                            .await
+                        command_open_tree(
+                            repo,
+                            database,
+                            pr,
+                            &comment.author,
+                            branch,
+                            &merge_queue_tx,
+                        )
+                        .instrument(span)
+                        .await
                     }
-                    BorsCommand::TreeClosed(priority) => {
+                    BorsCommand::TreeClosed {
+                        priority,
+                        duration,
+                        reason,
+                        branch,
+                    } => {
                         let span = tracing::info_span!("TreeClosed");
                         command_close_tree(
                             repo,
@@ -442,15 +922,84 @@ async fn handle_comment(
                             pr,
                             &comment.author,
                             priority,
+                            duration,
+                            reason,
+                            branch,
                             &comment.html_url,
                             &merge_queue_tx,
                         )
                         .instrument(span)
                         .await
                     }
+                    BorsCommand::PauseQueue => {
+                        let span = tracing::info_span!("QueuePause");
+                        command_pause_queue(repo, database, pr, &comment.author)
+                            .instrument(span)
+                            .await
+                    }
+                    BorsCommand::ResumeQueue => {
+                        let span = tracing::info_span!("QueueResume");
+                        command_resume_queue(repo, database, pr, &comment.author, &merge_queue_tx)
+                            .instrument(span)
+                            .await
+                    }
+                    BorsCommand::Hold => {
+                        let span = tracing::info_span!("Hold");
+                        command_hold(repo, database, pr, &comment.author, &merge_queue_tx)
+                            .instrument(span)
+                            .await
+                    }
+                    BorsCommand::ReleaseHold => {
+                        let span = tracing::info_span!("ReleaseHold");
+                        command_release_hold(repo, database, pr, &comment.author, &merge_queue_tx)
+                            .instrument(span)
+                            .await
+                    }
+                    BorsCommand::Park { reason } => {
+                        let span = tracing::info_span!("Park");
+                        command_park(repo, database, pr, &comment.author, reason, &merge_queue_tx)
+                            .instrument(span)
+                            .await
+                    }
+                    BorsCommand::Unpark => {
+                        let span = tracing::info_span!("Unpark");
+                        command_unpark(repo, database, pr, &comment.author, &merge_queue_tx)
+                            .instrument(span)
+                            .await
+                    }
+                    BorsCommand::SetBuildEnv { vars } => {
+                        let span = tracing::info_span!("SetBuildEnv");
+                        command_set_build_env(repo, database, pr, &comment.author, vars)
+                            .instrument(span)
+                            .await
+                    }
+                    BorsCommand::ClearBuildEnv => {
+                        let span = tracing::info_span!("ClearBuildEnv");
+                        command_clear_build_env(repo, database, pr, &comment.author)
+                            .instrument(span)
+                            .await
+                    }
+                    BorsCommand::Sync => {
+                        let span = tracing::info_span!("Sync");
+                        command_sync(
+                            repo,
+                            database,
+                            pr,
+                            &comment.author,
+                            mergeability_queue_tx.clone(),
+                        )
+                        .instrument(span)
+                        .await
+                    }
+                    BorsCommand::Reload => {
+                        let span = tracing::info_span!("Reload");
+                        command_reload(repo, database, pr, &comment.author, &ctx.team_api)
+                            .instrument(span)
+                            .await
+                    }
                     BorsCommand::Unapprove => {
                         let span = tracing::info_span!("Unapprove");
-                        command_unapprove(repo, database, pr, &comment.author)
+                        command_unapprove(repo, database, pr, &comment.author, &merge_queue_tx)
                             .instrument(span)
                             .await
                     }
@@ -460,14 +1009,18 @@ async fn handle_comment(
                             .instrument(span)
                             .await
                     }
-                    BorsCommand::SetDelegate(delegate_type) => {
+                    BorsCommand::SetDelegate {
+                        permission,
+                        duration,
+                    } => {
                         let span = tracing::info_span!("Delegate");
                         command_delegate(
                             repo,
                             database,
                             pr,
                             &comment.author,
-                            delegate_type,
+                            permission,
+                            duration,
                             ctx.parser.prefix(),
                         )
                         .instrument(span)
@@ -483,11 +1036,17 @@ async fn handle_comment(
                         let span = tracing::info_span!("Help");
                         command_help(repo, pr.number()).instrument(span).await
                     }
-                    BorsCommand::Ping => {
+                    BorsCommand::Ping { health } => {
                         let span = tracing::info_span!("Ping");
-                        command_ping(repo, pr.number()).instrument(span).await
+                        command_ping(repo, &ctx, pr.number(), health)
+                            .instrument(span)
+                            .await
                     }
-                    BorsCommand::Try { parent, jobs } => {
+                    BorsCommand::Try {
+                        parent,
+                        jobs,
+                        commit,
+                    } => {
                         let span = tracing::info_span!("Try");
                         // we hard code the command prefix instead of using `ctx.parser.prefix()`
                         // because we are using the new bors for try builds, so we don't want to
@@ -500,14 +1059,15 @@ async fn handle_comment(
                             &comment.author,
                             parent,
                             jobs,
+                            commit,
                             &command_prefix,
                         )
                         .instrument(span)
                         .await
                     }
-                    BorsCommand::TryCancel => {
+                    BorsCommand::TryCancel { build_id } => {
                         let span = tracing::info_span!("Cancel try");
-                        command_try_cancel(repo, database, pr, &comment.author)
+                        command_try_cancel(repo, database, pr, &comment.author, build_id)
                             .instrument(span)
                             .await
                     }
@@ -515,6 +1075,35 @@ async fn handle_comment(
                         let span = tracing::info_span!("Info");
                         command_info(repo, pr, database).instrument(span).await
                     }
+                    BorsCommand::History => {
+                        let span = tracing::info_span!("History");
+                        command_history(repo, pr, database).instrument(span).await
+                    }
+                    BorsCommand::Revert { priority } => {
+                        let span = tracing::info_span!("Revert");
+                        command_revert(
+                            repo,
+                            database,
+                            pr,
+                            &comment.author,
+                            priority,
+                            &merge_queue_tx,
+                        )
+                        .instrument(span)
+                        .await
+                    }
+                    BorsCommand::Backport { to } => {
+                        let span = tracing::info_span!("Backport");
+                        command_backport(repo, database, pr, &comment.author, to)
+                            .instrument(span)
+                            .await
+                    }
+                    BorsCommand::Notify => {
+                        let span = tracing::info_span!("Notify");
+                        command_notify(repo, database, pr, &comment.author)
+                            .instrument(span)
+                            .await
+                    }
                     BorsCommand::SetRollupMode(rollup) => {
                         let span = tracing::info_span!("Rollup");
                         command_set_rollup(repo, database, pr, &comment.author, rollup)
@@ -522,8 +1111,39 @@ async fn handle_comment(
                             .await
                     }
                 };
-                if result.is_err() {
-                    return result.context("Cannot execute Bors command");
+                let outcome = match &result {
+                    Ok(()) => "success".to_string(),
+                    Err(error) => match error.downcast_ref::<PermissionDenied>() {
+                        Some(denied) => format!("rejected: {denied}"),
+                        None => format!("failure: {error}"),
+                    },
+                };
+                hook_database
+                    .record_audit_command(
+                        hook_repo.repository(),
+                        pr_number,
+                        &comment.author.username,
+                        command.name(),
+                        &command_text,
+                        &outcome,
+                    )
+                    .await?;
+                match result {
+                    Ok(()) => {}
+                    Err(error) if error.is::<PermissionDenied>() => continue,
+                    Err(error) => return Err(error).context("Cannot execute Bors command"),
+                }
+
+                if let Err(message) = run_after_command_hooks(&ctx, &hook_ctx).await {
+                    tracing::warn!("Command hook failed after executing the command: {message}");
+                    reply_to_command(
+                        &hook_repo,
+                        pr,
+                        Comment::new(format!(
+                            ":warning: The command was executed, but a follow-up check failed: {message}"
+                        )),
+                    )
+                    .await?;
                 }
             }
             Err(error) => {
@@ -545,14 +1165,23 @@ async fn handle_comment(
                         format!("Invalid command: {error}.")
                     }
                 };
+                database
+                    .record_audit_command(
+                        repo.repository(),
+                        pr_number,
+                        &comment.author.username,
+                        "invalid",
+                        &command_text,
+                        &format!("rejected: {message}"),
+                    )
+                    .await?;
                 writeln!(
                     message,
                     " Run `{} help` to see available commands.",
                     ctx.parser.prefix()
                 )?;
                 tracing::warn!("{}", message);
-                repo.client
-                    .post_comment(pr_github.number, Comment::new(message))
+                repo.post_comment(pr_github.number, Comment::new(message))
                     .await
                     .context("Could not reply to PR comment")?;
             }
@@ -561,18 +1190,57 @@ async fn handle_comment(
     Ok(())
 }
 
+/// Runs the `before_command` hooks of all registered command hooks, until one of them fails.
+async fn run_before_command_hooks(
+    ctx: &BorsContext,
+    hook_ctx: &CommandHookContext<'_>,
+) -> Result<(), String> {
+    for hook in ctx.command_hooks() {
+        hook.before_command(hook_ctx).await?;
+    }
+    Ok(())
+}
+
+/// Runs the `after_command` hooks of all registered command hooks, until one of them fails.
+async fn run_after_command_hooks(
+    ctx: &BorsContext,
+    hook_ctx: &CommandHookContext<'_>,
+) -> Result<(), String> {
+    for hook in ctx.command_hooks() {
+        hook.after_command(hook_ctx).await?;
+    }
+    Ok(())
+}
+
+/// Repositories whose presence changed after reloading the repositories of the bot.
+struct ReloadedRepos {
+    /// Repositories that the bot has newly gained access to.
+    added: Vec<Arc<RepositoryState>>,
+    /// Repositories that the bot no longer has access to.
+    removed: Vec<Arc<RepositoryState>>,
+}
+
+/// Reloads all repositories that the bot has access to.
+/// Repositories that the bot no longer has access to are removed from the bot state.
 async fn reload_repos(
     ctx: Arc<BorsContext>,
     gh_client: &Octocrab,
-    team_api_client: &TeamApiClient,
-) -> anyhow::Result<()> {
-    let reloaded_repos = load_repositories(gh_client, team_api_client).await?;
+) -> anyhow::Result<ReloadedRepos> {
+    let reloaded_repos = load_repositories(gh_client, ctx.github_urls(), &ctx.team_api).await?;
+    let mut added_repos = vec![];
     let mut repositories = ctx.repositories.write().unwrap();
-    for repo in repositories.values() {
-        if !reloaded_repos.contains_key(repo.repository()) {
-            tracing::warn!("Repository {} was not reloaded", repo.repository());
-        }
-    }
+    let removed_names: Vec<GithubRepoName> = repositories
+        .keys()
+        .filter(|name| !reloaded_repos.contains_key(*name))
+        .cloned()
+        .collect();
+    let removed_repos = removed_names
+        .into_iter()
+        .filter_map(|name| {
+            tracing::warn!("Repository {name} was removed");
+            repositories.remove(&name)
+        })
+        .collect();
     for (name, repo) in reloaded_repos {
         let repo = match repo {
             Ok(repo) => repo,
@@ -582,18 +1250,26 @@ async fn reload_repos(
             }
         };
 
-        if repositories.insert(name.clone(), Arc::new(repo)).is_some() {
+        let repo = Arc::new(repo);
+        if repositories
+            .insert(name.clone(), Arc::clone(&repo))
+            .is_some()
+        {
             tracing::info!("Repository {name} was reloaded");
         } else {
             tracing::info!("Repository {name} was added");
+            added_repos.push(repo);
         }
     }
-    Ok(())
+    Ok(ReloadedRepos {
+        added: added_repos,
+        removed: removed_repos,
+    })
 }
 
 /// Is this branch interesting for the bot?
 fn is_bors_observed_branch(branch: &str) -> bool {
-    branch == TRY_BRANCH_NAME || branch == AUTO_BRANCH_NAME
+    branch == TRY_BRANCH_NAME || is_auto_branch(branch)
 }
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
@@ -606,17 +1282,33 @@ pub enum BuildType {
 pub fn get_build_type(branch: &str) -> Option<BuildType> {
     if branch == TRY_BRANCH_NAME {
         Some(BuildType::Try)
-    } else if branch == AUTO_BRANCH_NAME {
+    } else if is_auto_branch(branch) {
         Some(BuildType::Auto)
     } else {
         None
     }
 }
 
+/// The author of a command did not have the permission to use it.
+///
+/// This is not a failure of bors: the author has already been told about it, and the command
+/// is only recorded as rejected.
+#[derive(Debug)]
+struct PermissionDenied(PermissionType);
+
+impl std::fmt::Display for PermissionDenied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "insufficient privileges: not in {} users", self.0)
+    }
+}
+
+impl std::error::Error for PermissionDenied {}
+
 /// Deny permission for a request.
+/// Always returns a [`PermissionDenied`] error, so that the command is recorded as rejected.
 async fn deny_request(
     repo: &RepositoryState,
-    pr_number: PullRequestNumber,
+    pr: PullRequestData<'_>,
     author: &GithubUser,
     permission_type: PermissionType,
 ) -> anyhow::Result<()> {
@@ -624,18 +1316,58 @@ async fn deny_request(
         "Permission denied for request command by {}",
         author.username
     );
-    repo.client
-        .post_comment(
-            pr_number,
-            Comment::new(format!(
-                "@{}: :key: Insufficient privileges: not in {} users",
-                author.username, permission_type
-            )),
-        )
-        .await?;
+    reply_to_command(
+        repo,
+        pr,
+        Comment::new(format!(
+            "@{}: :key: Insufficient privileges: not in {} users",
+            author.username, permission_type
+        )),
+    )
+    .await?;
+    Err(PermissionDenied(permission_type).into())
+}
+
+/// Reply to a command that was posted on the given PR.
+/// If the command was posted in a review thread, the reply is posted into the same thread.
+async fn reply_to_command(
+    repo: &RepositoryState,
+    pr: PullRequestData<'_>,
+    comment: Comment,
+) -> anyhow::Result<()> {
+    match pr.review_thread {
+        Some(thread) => {
+            let comment = repo.format_comment(comment);
+            repo.client
+                .reply_to_review_comment(pr.number(), thread, comment)
+                .await?;
+        }
+        None => {
+            repo.post_comment(pr.number(), comment).await?;
+        }
+    }
     Ok(())
 }
 
+/// Acknowledge a command that has no interesting output with a reaction on the comment that
+/// contains it, if the repository is configured to do so.
+/// Failing to add the reaction does not fail the command.
+async fn acknowledge_command(repo: &RepositoryState, pr: PullRequestData<'_>) {
+    if !repo.config.load().acknowledge_with_reaction {
+        return;
+    }
+    let Some(target) = pr.command_comment else {
+        return;
+    };
+    if let Err(error) = repo
+        .client
+        .add_reaction(target, ReactionContent::PlusOne)
+        .await
+    {
+        tracing::warn!("Cannot acknowledge command with a reaction: {error:?}");
+    }
+}
+
 /// Check if a user has specified permission or has been delegated.
 async fn has_permission(
     repo_state: &RepositoryState,
@@ -657,7 +1389,7 @@ async fn has_permission(
 
     let is_delegated = pr
         .db
-        .delegated_permission
+        .active_delegation()
         .is_some_and(|perm| match permission {
             PermissionType::Review => matches!(perm, DelegatedPermission::Review),
             PermissionType::Try => {
@@ -669,13 +1401,13 @@ async fn has_permission(
 }
 
 /// Unapprove a PR in the DB and apply the corresponding label trigger.
-async fn unapprove_pr(
+pub(super) async fn unapprove_pr(
     repo_state: &RepositoryState,
     db: &PgDbClient,
     pr: &PullRequestModel,
 ) -> anyhow::Result<()> {
     db.unapprove(pr).await?;
-    handle_label_trigger(repo_state, pr.number, LabelTrigger::Unapproved).await
+    handle_label_trigger(repo_state, db, pr.number, LabelTrigger::Unapproved).await
 }
 
 /// Hide all previous "Try build started" comments on the given PR.
@@ -684,21 +1416,52 @@ async fn hide_try_build_started_comments(
     db: &PgDbClient,
     pr: &PullRequestModel,
 ) -> anyhow::Result<()> {
-    let outdated = db
-        .get_tagged_bot_comments(repo.repository(), pr.number, CommentTag::TryBuildStarted)
-        .await?;
-    for comment in outdated {
-        repo.client
-            .hide_comment(&comment.node_id, HideCommentReason::Outdated)
+    hide_tagged_comments(repo, db, pr.number, &[CommentTag::TryBuildStarted]).await
+}
+
+/// Hide all comments with one of the given `tags` that bors has posted on the given PR, because
+/// they have become outdated.
+pub(super) async fn hide_tagged_comments(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    pr: PullRequestNumber,
+    tags: &[CommentTag],
+) -> anyhow::Result<()> {
+    for tag in tags {
+        let outdated = db
+            .get_tagged_bot_comments(repo.repository(), pr, *tag)
             .await?;
-        db.delete_tagged_bot_comment(&comment).await?;
+        for comment in outdated {
+            repo.client
+                .hide_comment(&comment.node_id, HideCommentReason::Outdated)
+                .await?;
+            db.delete_tagged_bot_comment(&comment).await?;
+        }
     }
     Ok(())
 }
 
+/// Tag a build status comment, so that it can be hidden once it becomes outdated, if the
+/// repository is configured to hide outdated comments.
+pub(super) fn tag_status_comment(
+    repo: &RepositoryState,
+    comment: Comment,
+    tag: CommentTag,
+) -> Comment {
+    if repo.config.load().hide_outdated_comments {
+        comment.with_tag(tag)
+    } else {
+        comment
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::tests::{BorsTester, Comment, User, default_repo_name, run_test};
+    use crate::github::{GithubRepoName, PullRequestNumber};
+    use crate::tests::{
+        BorsBuilder, BorsTester, Comment, GitHubState, Permissions, Repo, User, default_repo_name,
+        run_test,
+    };
 
     #[sqlx::test]
     async fn ignore_bot_comment(pool: sqlx::PgPool) {
@@ -733,4 +1496,61 @@ mod tests {
         })
         .await;
     }
+
+    #[sqlx::test]
+    async fn installation_backfills_prs_of_added_repositories(pool: sqlx::PgPool) {
+        let failing = GithubRepoName::new("rust-lang", "failing");
+        let github = GitHubState::default().with_repo(Repo::new(
+            failing.clone(),
+            Permissions::empty(),
+            "".to_string(),
+        ));
+        BorsBuilder::new(pool)
+            .github(github)
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .modify_repo(&failing, |repo| repo.installed = false)
+                    .await;
+                tester
+                    .set_repo_installed(&default_repo_name(), false)
+                    .await?;
+                tester
+                    .wait_for(|| async {
+                        let repo = tester.db().repo_db(&default_repo_name()).await?;
+                        Ok(repo.is_some_and(|repo| !repo.is_active()))
+                    })
+                    .await?;
+
+                // Bors does not see this PR, because it is not installed in the repository
+                tester
+                    .modify_repo(&default_repo_name(), |repo| {
+                        repo.add_pr();
+                    })
+                    .await;
+
+                // Fetching the PRs of one repository fails, but the other one is still backfilled
+                tester
+                    .modify_repo(&failing, |repo| {
+                        repo.installed = true;
+                        repo.pull_request_error = true;
+                    })
+                    .await;
+                tester
+                    .set_repo_installed(&default_repo_name(), true)
+                    .await?;
+                tester
+                    .wait_for(|| async {
+                        let pr = tester
+                            .db()
+                            .get_pull_request(&default_repo_name(), PullRequestNumber(2))
+                            .await?;
+                        Ok(pr.is_some())
+                    })
+                    .await?;
+                let repo = tester.db().repo_db(&default_repo_name()).await?.unwrap();
+                assert!(repo.is_active());
+                Ok(())
+            })
+            .await;
+    }
 }
@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use crate::PgDbClient;
+use crate::bors::RepositoryState;
+use crate::bors::comment::queue_notification_registered_comment;
+use crate::bors::handlers::{PullRequestData, reply_to_command};
+use crate::bors::merge_queue::QUEUE_NOTIFICATION_POSITION;
+use crate::github::GithubUser;
+
+/// Register the author of the command to be notified when the PR gets close to the front of the
+/// merge queue.
+pub(super) async fn command_notify(
+    repo: Arc<RepositoryState>,
+    db: Arc<PgDbClient>,
+    pr: PullRequestData<'_>,
+    author: &GithubUser,
+) -> anyhow::Result<()> {
+    db.add_queue_notification(repo.repository(), pr.number(), &author.username)
+        .await?;
+    reply_to_command(
+        &repo,
+        pr,
+        queue_notification_registered_comment(&author.username, QUEUE_NOTIFICATION_POSITION),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{BorsTester, run_test};
+
+    #[sqlx::test]
+    async fn notify_command(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors notify").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":bell: `default-user` will be notified when this PR is among the first 3 PRs in the queue."
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn notify_when_pr_reaches_front_of_queue(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors notify").await?;
+            tester.expect_comments((), 1).await;
+            tester.approve(()).await?;
+
+            tester.process_merge_queue().await;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @"@default-user: :bell: This PR is now at position 1 in the [queue](https://test.com/bors/queue/borstest). Please be around in case its build fails."
+            );
+            let comment = tester.get_next_comment_text(()).await?;
+            assert!(comment.contains("Testing commit"));
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn notify_only_once(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors notify").await?;
+            tester.expect_comments((), 1).await;
+            tester.approve(()).await?;
+
+            tester.process_merge_queue().await;
+            tester.expect_comments((), 2).await;
+            // The notification is not posted again
+            tester.finish_auto_build(()).await?;
+            Ok(())
+        })
+        .await;
+    }
+}
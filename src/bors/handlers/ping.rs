@@ -1,22 +1,33 @@
 use std::sync::Arc;
 
+use crate::BorsContext;
 use crate::bors::Comment;
 use crate::bors::RepositoryState;
+use crate::bors::comment::health_report_comment;
+use crate::bors::health::check_health;
 use crate::github::PullRequestNumber;
 
+/// Answers a ping, optionally with the health of the components that bors depends on.
 pub(super) async fn command_ping(
     repo: Arc<RepositoryState>,
+    ctx: &BorsContext,
     pr_number: PullRequestNumber,
+    health: bool,
 ) -> anyhow::Result<()> {
-    repo.client
-        .post_comment(pr_number, Comment::new("Pong 🏓!".to_string()))
-        .await?;
+    let comment = if health {
+        let repos: Vec<Arc<RepositoryState>> =
+            ctx.repositories.read().unwrap().values().cloned().collect();
+        health_report_comment(&check_health(&ctx.db, &repos).await)
+    } else {
+        Comment::new("Pong 🏓!".to_string())
+    };
+    repo.post_comment(pr_number, comment).await?;
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tests::{BorsTester, run_test};
+    use crate::tests::{BorsBuilder, BorsTester, GitHubState, default_repo_name, run_test};
 
     #[sqlx::test]
     async fn ping_command(pool: sqlx::PgPool) {
@@ -27,4 +38,62 @@ mod tests {
         })
         .await;
     }
+
+    #[sqlx::test]
+    async fn ping_without_loading_pr(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .modify_repo(&default_repo_name(), |repo| repo.pull_request_error = true)
+                .await;
+            tester.post_comment("@bors ping").await?;
+            assert_eq!(tester.get_next_comment_text(()).await?, "Pong 🏓!");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn ping_health(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors ping health").await?;
+            let comment = tester.get_next_comment_text(()).await?;
+            assert!(comment.starts_with("Pong 🏓!\n\n"));
+            assert!(comment.contains("- :white_check_mark: Database: healthy (connected)"));
+            assert!(comment.contains("- :white_check_mark: GitHub: healthy (1 app token valid)"));
+            // Webhook and merge queue activity is shared by all tests running in parallel
+            assert!(comment.contains("- :white_check_mark: Webhooks: healthy (last webhook"));
+            assert!(comment.contains("- :white_check_mark: Merge queue: healthy"));
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn ping_command_plain_style(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(r#"comment_style = "plain""#))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors ping").await?;
+                assert_eq!(tester.get_next_comment_text(()).await?, "Pong!");
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn ping_command_with_signature(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(
+                GitHubState::default().with_default_config(r#"comment_signature = "-- Foo bot""#),
+            )
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors ping").await?;
+                assert_eq!(
+                    tester.get_next_comment_text(()).await?,
+                    "Pong 🏓!\n\n-- Foo bot"
+                );
+                Ok(())
+            })
+            .await;
+    }
 }
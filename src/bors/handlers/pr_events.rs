@@ -1,19 +1,26 @@
 use crate::PgDbClient;
 use crate::bors::event::{
     PullRequestAssigned, PullRequestClosed, PullRequestComment, PullRequestConvertedToDraft,
-    PullRequestEdited, PullRequestMerged, PullRequestOpened, PullRequestPushed,
-    PullRequestReadyForReview, PullRequestReopened, PullRequestUnassigned, PushToBranch,
+    PullRequestEdited, PullRequestLabelsChanged, PullRequestMerged, PullRequestOpened,
+    PullRequestPushed, PullRequestReadyForReview, PullRequestReopened, PullRequestUnassigned,
+    PushToBranch,
 };
 
 use crate::bors::BorsContext;
-use crate::bors::handlers::handle_comment;
+use crate::bors::comment::{CommentTag, reopened_pr_unapproved_comment};
+use crate::bors::handlers::backport::backport_merged_pr;
+use crate::bors::handlers::config::validate_config_change;
 use crate::bors::handlers::unapprove_pr;
 use crate::bors::handlers::workflow::{AutoBuildCancelReason, maybe_cancel_auto_build};
+use crate::bors::handlers::{PullRequestData, handle_comment};
 use crate::bors::merge_queue::MergeQueueSender;
 use crate::bors::mergeability_queue::MergeabilityQueueSender;
-use crate::bors::{Comment, PullRequestStatus, RepositoryState};
-use crate::database::MergeableState;
-use crate::github::{CommitSha, PullRequestNumber};
+use crate::bors::{
+    Comment, MergeType, PullRequestStatus, RepositoryState, create_merge_commit_message,
+};
+use crate::config::{ReopenApprovalPolicy, StaleApprovalPolicy};
+use crate::database::{ApprovalStatus, MergeableState, PrMilestone, PullRequestModel, QueueStatus};
+use crate::github::{CommitSha, PullRequest, PullRequestNumber};
 use crate::utils::text::pluralize;
 use std::sync::Arc;
 
@@ -21,6 +28,7 @@ pub(super) async fn handle_pull_request_edited(
     repo_state: Arc<RepositoryState>,
     db: Arc<PgDbClient>,
     mergeability_queue: MergeabilityQueueSender,
+    merge_queue_tx: MergeQueueSender,
     payload: PullRequestEdited,
 ) -> anyhow::Result<()> {
     let pr = &payload.pull_request;
@@ -31,6 +39,25 @@ pub(super) async fn handle_pull_request_edited(
 
     // If the base branch has changed, unapprove the PR
     let Some(_) = payload.from_base_sha else {
+        // The merge commit of a running auto build contains the old title and description of the
+        // PR, so restart the build to merge the PR with an up-to-date commit message.
+        if repo_state.config.load().restart_auto_build_on_edit
+            && merge_message_changed(&pr_model, &payload)
+            && let Some(message) = maybe_cancel_auto_build(
+                &repo_state.client,
+                &db,
+                &pr_model,
+                AutoBuildCancelReason::PullRequestEdited,
+            )
+            .await?
+        {
+            db.clear_auto_build(&pr_model).await?;
+            let comment = Comment::new(format!(
+                "{message}\n\nA new auto build will be started with the updated merge commit message."
+            ));
+            replace_auto_build_status_comment(&repo_state, &db, pr_number, comment).await?;
+            merge_queue_tx.notify().await?;
+        }
         return Ok(());
     };
 
@@ -57,6 +84,7 @@ pub(super) async fn handle_push_to_pull_request(
         .await?;
 
     mergeability_queue.enqueue_pr(repo_state.repository().clone(), pr_number);
+    handle_modified_files(&repo_state, &db, pr).await?;
 
     let auto_build_cancel_message = maybe_cancel_auto_build(
         &repo_state.client,
@@ -70,6 +98,27 @@ pub(super) async fn handle_push_to_pull_request(
         return Ok(());
     }
 
+    if keeps_approval_on_push(&repo_state, &pr_model, &pr.head.sha).await? {
+        if pr_model.waiting_on_conflicts {
+            // The PR was approved knowing that its conflicts have to be resolved, so the approval
+            // also covers the new commits, regardless of the stale approval policy.
+            db.move_approval_and_head(&pr_model, pr.head.sha.as_ref())
+                .await?;
+        } else {
+            // Move the approval to the new head of the PR. Whether the PR can be merged with the
+            // new commits is decided by the stale approval policy when its auto build starts.
+            db.move_approval(&pr_model, pr.head.sha.as_ref()).await?;
+        }
+        return notify_of_pushed_approved_pr(
+            &repo_state,
+            pr_number,
+            pr_model.approved_sha().unwrap_or_default(),
+            &pr.head.sha,
+            auto_build_cancel_message,
+        )
+        .await;
+    }
+
     let had_failed_build = pr_model
         .auto_build
         .as_ref()
@@ -91,6 +140,32 @@ pub(super) async fn handle_push_to_pull_request(
     }
 }
 
+/// An approval must never cover changes that were not reviewed. It is only kept on push if the
+/// new head makes the same changes as the approved commit, e.g. because the PR was rebased.
+/// If the PR waits for its merge conflicts to be resolved, that is enough to keep the approval.
+/// Otherwise, unapproving on push also has to be disabled, and the stale approval policy has to
+/// allow merging new commits.
+async fn keeps_approval_on_push(
+    repo: &RepositoryState,
+    pr: &PullRequestModel,
+    head_sha: &CommitSha,
+) -> anyhow::Result<bool> {
+    let config = repo.config.load();
+    if !pr.waiting_on_conflicts
+        && (config.unapprove_on_push
+            || config.stale_approval == StaleApprovalPolicy::RequireReapproval)
+    {
+        return Ok(false);
+    }
+    let Some(approved_sha) = pr.approved_sha() else {
+        return Ok(false);
+    };
+    let base_sha = repo.client.get_branch_sha(&pr.base_branch).await?;
+    repo.client
+        .has_same_diff(&base_sha, &CommitSha(approved_sha.to_string()), head_sha)
+        .await
+}
+
 pub(super) async fn handle_pull_request_opened(
     repo_state: Arc<RepositoryState>,
     db: Arc<PgDbClient>,
@@ -120,25 +195,115 @@ pub(super) async fn handle_pull_request_opened(
         pr_status,
     )
     .await?;
+    db.record_pr_milestone(
+        repo_state.repository(),
+        payload.pull_request.number,
+        PrMilestone::Opened,
+    )
+    .await?;
 
-    process_pr_description_commands(&payload, repo_state.clone(), db, ctx, merge_queue_tx).await?;
+    handle_modified_files(&repo_state, &db, &payload.pull_request).await?;
+
+    process_pr_description_commands(
+        &payload,
+        repo_state.clone(),
+        db,
+        ctx,
+        mergeability_queue.clone(),
+        merge_queue_tx,
+    )
+    .await?;
 
     mergeability_queue.enqueue_pr(repo_state.repository().clone(), payload.pull_request.number);
 
     Ok(())
 }
 
+/// Validate the configuration file if the PR modifies it, and assign the PR to the path queue
+/// that its modified files belong to.
+/// Validating the configuration is only informative, so it does not fail the handling of the
+/// PR event. If the modified files cannot be loaded, the PR uses the default queue.
+async fn handle_modified_files(
+    repo_state: &RepositoryState,
+    db: &PgDbClient,
+    pr: &PullRequest,
+) -> anyhow::Result<()> {
+    let files = match repo_state.client.get_pull_request_files(pr.number).await {
+        Ok(files) => files,
+        Err(error) => {
+            tracing::warn!("Cannot load files modified by PR {}: {error:?}", pr.number);
+            vec![]
+        }
+    };
+    if let Err(error) = validate_config_change(repo_state, pr, &files).await {
+        tracing::warn!(
+            "Cannot validate configuration change of PR {}: {error:?}",
+            pr.number
+        );
+    }
+
+    let config = repo_state.config.load();
+    let path_queue = config.path_queue_for(&files);
+    db.set_path_queue(repo_state.repository(), pr.number, path_queue)
+        .await
+}
+
 pub(super) async fn handle_pull_request_closed(
     repo_state: Arc<RepositoryState>,
     db: Arc<PgDbClient>,
+    merge_queue_tx: MergeQueueSender,
     payload: PullRequestClosed,
 ) -> anyhow::Result<()> {
+    let pr_number = payload.pull_request.number;
+    let mut cancel_message = None;
+    let mut left_queue = false;
+    if let Some(pr) = db
+        .get_pull_request(repo_state.repository(), pr_number)
+        .await?
+    {
+        // bors closes squash-merged PRs itself, after it has already marked them as merged
+        if pr.pr_status == PullRequestStatus::Merged {
+            return Ok(());
+        }
+        cancel_message = maybe_cancel_auto_build(
+            &repo_state.client,
+            &db,
+            &pr,
+            AutoBuildCancelReason::PullRequestClosed,
+        )
+        .await?;
+
+        // Remove the PR from the merge queue, so that if it is reopened later, it will go through
+        // a fresh auto build instead of getting stuck on the cancelled (or unmerged) one.
+        if matches!(
+            pr.queue_status(),
+            QueueStatus::Pending(..) | QueueStatus::ReadyForMerge(..)
+        ) {
+            db.clear_auto_build(&pr).await?;
+            left_queue = true;
+        }
+        db.delete_queue_notifications(repo_state.repository(), pr_number)
+            .await?;
+    }
+
     db.set_pr_status(
         repo_state.repository(),
         payload.pull_request.number,
         PullRequestStatus::Closed,
     )
-    .await
+    .await?;
+
+    // The cancelled auto build no longer blocks the queue. The queue is only notified once the
+    // PR is closed in the database, so that its auto build is not started again.
+    if left_queue {
+        merge_queue_tx.notify().await?;
+    }
+    if let Some(message) = cancel_message {
+        repo_state
+            .post_comment(pr_number, Comment::new(message))
+            .await?;
+    }
+    Ok(())
 }
 
 pub(super) async fn handle_pull_request_merged(
@@ -151,9 +316,40 @@ pub(super) async fn handle_pull_request_merged(
         payload.pull_request.number,
         PullRequestStatus::Merged,
     )
+    .await?;
+    db.record_pr_milestone(
+        repo_state.repository(),
+        payload.pull_request.number,
+        PrMilestone::Merged,
+    )
+    .await?;
+
+    run_merged_pr_hooks(
+        &repo_state,
+        &db,
+        payload.pull_request.number,
+        &payload.pull_request.title,
+    )
     .await
 }
 
+/// Runs the actions that follow the merge of a PR. They are run both for PRs merged on GitHub and
+/// for PRs that bors closes after pushing their changes as a squashed commit.
+pub(crate) async fn run_merged_pr_hooks(
+    repo_state: &RepositoryState,
+    db: &PgDbClient,
+    pr_number: PullRequestNumber,
+    title: &str,
+) -> anyhow::Result<()> {
+    let Some(pr) = db
+        .get_pull_request(repo_state.repository(), pr_number)
+        .await?
+    else {
+        return Ok(());
+    };
+    backport_merged_pr(repo_state, db, &pr, title).await
+}
+
 pub(super) async fn handle_pull_request_reopened(
     repo_state: Arc<RepositoryState>,
     db: Arc<PgDbClient>,
@@ -162,19 +358,60 @@ pub(super) async fn handle_pull_request_reopened(
 ) -> anyhow::Result<()> {
     let pr = &payload.pull_request;
     let pr_number = pr.number;
-    db.upsert_pull_request(repo_state.repository(), pr.clone().into())
+    let pr_model = db
+        .upsert_pull_request(repo_state.repository(), pr.clone().into())
         .await?;
 
     mergeability_queue.enqueue_pr(repo_state.repository().clone(), pr_number);
 
+    let ApprovalStatus::Approved(approval_info) = &pr_model.approval_status else {
+        return Ok(());
+    };
+    let comment = match repo_state.config.load().approval_on_reopen {
+        ReopenApprovalPolicy::Restore if approval_info.sha == pr.head.sha.as_ref() => {
+            return Ok(());
+        }
+        ReopenApprovalPolicy::Restore => reopened_pr_unapproved_comment(Some(&pr.head.sha)),
+        ReopenApprovalPolicy::Unapprove => reopened_pr_unapproved_comment(None),
+    };
+    unapprove_pr(&repo_state, &db, &pr_model).await?;
+    repo_state.post_comment(pr_number, comment).await?;
+
     Ok(())
 }
 
 pub(super) async fn handle_pull_request_converted_to_draft(
     repo_state: Arc<RepositoryState>,
     db: Arc<PgDbClient>,
+    merge_queue_tx: MergeQueueSender,
     payload: PullRequestConvertedToDraft,
 ) -> anyhow::Result<()> {
+    let pr_number = payload.pull_request.number;
+    if let Some(pr) = db
+        .get_pull_request(repo_state.repository(), pr_number)
+        .await?
+    {
+        if let Some(message) = maybe_cancel_auto_build(
+            &repo_state.client,
+            &db,
+            &pr,
+            AutoBuildCancelReason::PullRequestConvertedToDraft,
+        )
+        .await?
+        {
+            repo_state
+                .post_comment(pr_number, Comment::new(message))
+                .await?;
+        }
+
+        // Drafts are skipped by the merge queue. Drop the cancelled build, so that the PR gets
+        // a fresh auto build once it is marked as ready for review again.
+        if matches!(pr.queue_status(), QueueStatus::Pending(..)) {
+            db.clear_auto_build(&pr).await?;
+            merge_queue_tx.notify().await?;
+        }
+    }
+
     db.set_pr_status(
         repo_state.repository(),
         payload.pull_request.number,
@@ -219,9 +456,27 @@ pub(super) async fn handle_pull_request_unassigned(
     .await
 }
 
+pub(super) async fn handle_pull_request_labels_changed(
+    repo_state: Arc<RepositoryState>,
+    db: Arc<PgDbClient>,
+    merge_queue_tx: MergeQueueSender,
+    payload: PullRequestLabelsChanged,
+) -> anyhow::Result<()> {
+    db.set_pr_labels(
+        repo_state.repository(),
+        payload.pull_request.number,
+        &payload.pull_request.labels,
+    )
+    .await?;
+    // A blocking label might have been removed, which could unblock the queue
+    merge_queue_tx.notify().await?;
+    Ok(())
+}
+
 pub(super) async fn handle_pull_request_ready_for_review(
     repo_state: Arc<RepositoryState>,
     db: Arc<PgDbClient>,
+    merge_queue_tx: MergeQueueSender,
     payload: PullRequestReadyForReview,
 ) -> anyhow::Result<()> {
     db.set_pr_status(
@@ -229,7 +484,10 @@ pub(super) async fn handle_pull_request_ready_for_review(
         payload.pull_request.number,
         PullRequestStatus::Open,
     )
-    .await
+    .await?;
+    // An approved PR can rejoin the merge queue
+    merge_queue_tx.notify().await?;
+    Ok(())
 }
 
 /// Handle a push to a branch that is directly in the repo that we're managing (not in a fork).
@@ -269,22 +527,93 @@ async fn process_pr_description_commands(
     repo: Arc<RepositoryState>,
     database: Arc<PgDbClient>,
     ctx: Arc<BorsContext>,
+    mergeability_queue_tx: MergeabilityQueueSender,
     merge_queue_tx: MergeQueueSender,
 ) -> anyhow::Result<()> {
-    let pr_description_comment = create_pr_description_comment(payload);
-    handle_comment(repo, database, ctx, pr_description_comment, merge_queue_tx).await
+    let pr_description_comment = create_pr_description_comment(&repo, payload);
+    handle_comment(
+        repo,
+        database,
+        ctx,
+        pr_description_comment,
+        mergeability_queue_tx,
+        merge_queue_tx,
+    )
+    .await
 }
 
-fn create_pr_description_comment(payload: &PullRequestOpened) -> PullRequestComment {
+fn create_pr_description_comment(
+    repo: &RepositoryState,
+    payload: &PullRequestOpened,
+) -> PullRequestComment {
     PullRequestComment {
         repository: payload.repository.clone(),
         author: payload.pull_request.author.clone(),
         pr_number: payload.pull_request.number,
         text: payload.pull_request.message.clone(),
         html_url: format!(
-            "https://github.com/{}/pull/{}",
-            payload.repository, payload.pull_request.number
+            "{}/pull/{}",
+            repo.client.repository_url(),
+            payload.pull_request.number
         ),
+        review_thread: None,
+        review_verdict: None,
+        reaction_target: None,
+    }
+}
+
+/// Did an edit of the title or the description of the PR change the merge commit message of its
+/// auto build? Edits that do not end up in the message (e.g. of trailing whitespace) do not.
+fn merge_message_changed(pr: &PullRequestModel, payload: &PullRequestEdited) -> bool {
+    let Some(build) = &pr.auto_build else {
+        return false;
+    };
+    if payload.from_title.is_none() && payload.from_body.is_none() {
+        return false;
+    }
+
+    let mut previous = payload.pull_request.clone();
+    if let Some(title) = &payload.from_title {
+        previous.title = title.clone();
+    }
+    if let Some(body) = &payload.from_body {
+        previous.message = body.clone();
+    }
+    let message = |github: &PullRequest| {
+        let pr_data = PullRequestData {
+            db: pr,
+            github,
+            review_thread: None,
+            command_comment: None,
+        };
+        create_merge_commit_message(pr_data, MergeType::Auto, build.id)
+    };
+    message(&previous) != message(&payload.pull_request)
+}
+
+/// Replaces the status comment of the cancelled auto build of the PR with `comment`, so that the
+/// PR does not keep announcing a build that is not running anymore. If the status comment is not
+/// tracked (see `hide_outdated_comments`), `comment` is posted as a new comment instead.
+async fn replace_auto_build_status_comment(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    pr_number: PullRequestNumber,
+    comment: Comment,
+) -> anyhow::Result<()> {
+    let status_comments = db
+        .get_tagged_bot_comments(repo.repository(), pr_number, CommentTag::AutoBuildStarted)
+        .await?;
+    match status_comments.last() {
+        Some(status_comment) => {
+            let comment = repo.format_comment(comment);
+            repo.client
+                .update_comment_content(&status_comment.node_id, &comment.render())
+                .await
+        }
+        None => {
+            repo.post_comment(pr_number, comment).await?;
+            Ok(())
+        }
     }
 }
 
@@ -293,15 +622,14 @@ async fn notify_of_edited_pr(
     pr_number: PullRequestNumber,
     base_name: &str,
 ) -> anyhow::Result<()> {
-    repo.client
-        .post_comment(
-            pr_number,
-            Comment::new(format!(
-                r#":warning: The base branch changed to `{base_name}`, and the
+    repo.post_comment(
+        pr_number,
+        Comment::new(format!(
+            r#":warning: The base branch changed to `{base_name}`, and the
 PR will need to be re-approved."#,
-            )),
-        )
-        .await?;
+        )),
+    )
+    .await?;
     Ok(())
 }
 
@@ -320,9 +648,27 @@ PR will need to be re-approved."#
         comment.push_str(&format!("\n\n{message}"));
     }
 
-    repo.client
-        .post_comment(pr_number, Comment::new(comment))
-        .await?;
+    repo.post_comment(pr_number, Comment::new(comment)).await?;
+    Ok(())
+}
+
+async fn notify_of_pushed_approved_pr(
+    repo: &RepositoryState,
+    pr_number: PullRequestNumber,
+    approved_sha: &str,
+    head_sha: &CommitSha,
+    cancel_message: Option<String>,
+) -> anyhow::Result<()> {
+    let mut comment = format!(
+        r#":information_source: A new commit `{head_sha}` was pushed to the branch. It makes the
+same changes as the approved commit `{approved_sha}`, so the approval was moved to it."#
+    );
+
+    if let Some(message) = cancel_message {
+        comment.push_str(&format!("\n\n{message}"));
+    }
+
+    repo.post_comment(pr_number, Comment::new(comment)).await?;
     Ok(())
 }
 
@@ -332,9 +678,9 @@ mod tests {
 
     use crate::bors::PullRequestStatus;
     use crate::bors::merge_queue::AUTO_BUILD_CHECK_RUN_NAME;
-    use crate::tests::{BorsTester, WorkflowRunData};
+    use crate::tests::{BorsBuilder, BorsTester, GitHubState, WorkflowRunData};
     use crate::{
-        database::{MergeableState, OctocrabMergeableState},
+        database::{BuildStatus, MergeableState, OctocrabMergeableState},
         tests::{User, default_branch_name, default_repo_name, run_test},
     };
 
@@ -362,6 +708,133 @@ mod tests {
         .await;
     }
 
+    #[sqlx::test]
+    async fn edit_pr_title_restarts_auto_build(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester
+                .workflow_start(WorkflowRunData::from(tester.auto_branch().await).with_run_id(123))
+                .await?;
+            tester
+                .edit_pr((), |pr| {
+                    pr.title = "Updated title".to_string();
+                })
+                .await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+            Auto build cancelled due to the PR title or description being edited. Cancelled workflows:
+
+            - https://github.com/rust-lang/borstest/actions/runs/123
+
+            A new auto build will be started with the updated merge commit message.
+            ");
+            tester.get_pr_copy(()).await.expect_no_auto_build();
+
+            tester.start_auto_build(()).await?;
+            insta::assert_snapshot!(tester.get_branch_commit_message(&tester.auto_branch().await).await, @r"
+            Auto merge of #1 - pr-1, r=default-user
+            Updated title
+
+            Description of PR 1
+
+            Bors-Build-Id: 2
+            ");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn edit_pr_keeps_auto_build_when_merge_message_unchanged(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester
+                .edit_pr((), |pr| {
+                    pr.description.push_str("\n\n");
+                })
+                .await?;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_auto_build(|build| build.status == BuildStatus::Pending);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn edit_pr_keeps_auto_build_when_restart_disabled(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+restart_auto_build_on_edit = false
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.start_auto_build(()).await?;
+                tester
+                    .edit_pr((), |pr| {
+                        pr.title = "Updated title".to_string();
+                    })
+                    .await?;
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_auto_build(|build| build.status == BuildStatus::Pending);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn edit_pr_replaces_auto_build_status_comment(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+hide_outdated_comments = true
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.process_merge_queue().await;
+                let status_comment = tester.get_next_comment(()).await?;
+                let node_id = status_comment.node_id.clone().unwrap();
+                tester
+                    .workflow_start(
+                        WorkflowRunData::from(tester.auto_branch().await).with_run_id(123),
+                    )
+                    .await?;
+                tester
+                    .edit_pr((), |pr| {
+                        pr.title = "Updated title".to_string();
+                    })
+                    .await?;
+                tester
+                    .wait_for(|| async {
+                        let comment = tester.get_comment_by_node_id(&node_id).await.unwrap();
+                        Ok(comment.content != status_comment.content)
+                    })
+                    .await?;
+                tester.get_pr_copy(()).await.expect_no_auto_build();
+                insta::assert_snapshot!(
+                    tester.get_comment_by_node_id(&node_id).await.unwrap().content,
+                    @r"
+                Auto build cancelled due to the PR title or description being edited. Cancelled workflows:
+
+                - https://github.com/rust-lang/borstest/actions/runs/123
+
+                A new auto build will be started with the updated merge commit message.
+                "
+                );
+                Ok(())
+            })
+            .await;
+    }
+
     #[sqlx::test]
     async fn edit_pr_do_nothing_when_base_not_edited(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
@@ -412,6 +885,210 @@ mod tests {
         .await;
     }
 
+    #[sqlx::test]
+    async fn keep_approval_on_push(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+unapprove_on_push = false
+stale_approval = "merge"
+
+[labels]
+approved = ["+approved"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.rebase_pr(()).await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                :information_source: A new commit `pr-1-commit-1` was pushed to the branch. It makes the
+                same changes as the approved commit `pr-1-sha`, so the approval was moved to it.
+                ");
+                tester
+                    .wait_for_pr((), |pr| pr.approved_sha() == Some("pr-1-commit-1"))
+                    .await?;
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_approved_by(&User::default_pr_author().name);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn keep_approval_on_push_unapprove_changed_diff(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("unapprove_on_push = false"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.push_to_pr(()).await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @r"
+                :warning: A new commit `pr-1-commit-1` was pushed to the branch, the
+                PR will need to be re-approved.
+                "
+                );
+                tester.get_pr_copy(()).await.expect_unapproved();
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn keep_approval_on_push_cancel_auto_build(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+unapprove_on_push = false
+stale_approval = "merge"
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.start_auto_build(()).await?;
+                tester
+                    .workflow_start(
+                        WorkflowRunData::from(tester.auto_branch().await).with_run_id(123),
+                    )
+                    .await?;
+                tester.rebase_pr(()).await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                :information_source: A new commit `pr-1-commit-1` was pushed to the branch. It makes the
+                same changes as the approved commit `pr-1-sha`, so the approval was moved to it.
+
+                Auto build cancelled due to push. Cancelled workflows:
+
+                - https://github.com/rust-lang/borstest/actions/runs/123
+                ");
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_approved_sha("pr-1-commit-1");
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn close_pr_cancels_running_auto_build(pool: sqlx::PgPool) {
+        let gh = run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester
+                .workflow_start(WorkflowRunData::from(tester.auto_branch().await).with_run_id(123))
+                .await?;
+            tester.set_pr_status_closed(()).await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+            Auto build cancelled due to the PR being closed. Cancelled workflows:
+
+            - https://github.com/rust-lang/borstest/actions/runs/123
+            ");
+            tester
+                .wait_for_pr((), |pr| pr.pr_status == PullRequestStatus::Closed)
+                .await?;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_status(PullRequestStatus::Closed)
+                .expect_no_auto_build();
+            tester
+                .expect_check_run(
+                    &tester.get_pr_copy(()).await.get_gh_pr().head_sha,
+                    AUTO_BUILD_CHECK_RUN_NAME,
+                    AUTO_BUILD_CHECK_RUN_NAME,
+                    CheckRunStatus::Completed,
+                    Some(CheckRunConclusion::Cancelled),
+                )
+                .await;
+            Ok(())
+        })
+        .await;
+        gh.check_cancelled_workflows(default_repo_name(), &[123]);
+    }
+
+    #[sqlx::test]
+    async fn close_pr_starts_next_auto_build(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+            tester.approve(()).await?;
+            tester.approve(pr2.id()).await?;
+            tester.start_auto_build(()).await?;
+            tester.workflow_start(tester.auto_branch().await).await?;
+            tester.set_pr_status_closed(()).await?;
+            tester.expect_comments((), 1).await;
+            tester.start_auto_build(pr2.id()).await?;
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn reopen_pr_restores_approval(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester.workflow_start(tester.auto_branch().await).await?;
+            tester.set_pr_status_closed(()).await?;
+            tester.expect_comments((), 1).await;
+            tester
+                .wait_for_pr((), |pr| pr.pr_status == PullRequestStatus::Closed)
+                .await?;
+            tester.reopen_pr(()).await?;
+            tester
+                .wait_for_pr((), |pr| pr.pr_status == PullRequestStatus::Open)
+                .await?;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_approved_by(&User::default_pr_author().name)
+                .expect_no_auto_build();
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn reopen_pr_with_new_commit_unapproves(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.set_pr_status_closed(()).await?;
+            tester
+                .modify_pr_state((), |pr| pr.head_sha = "pr-1-sha-new".to_string())
+                .await;
+            tester.reopen_pr(()).await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":warning: A new commit `pr-1-sha-new` was pushed to the branch while the PR was closed, the PR will need to be re-approved."
+            );
+            tester.get_pr_copy(()).await.expect_unapproved();
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn reopen_pr_unapproves_with_unapprove_policy(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(
+                GitHubState::default().with_default_config(r#"approval_on_reopen = "unapprove""#),
+            )
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.set_pr_status_closed(()).await?;
+                tester.reopen_pr(()).await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @":warning: The PR was reopened, it will need to be re-approved."
+                );
+                tester.get_pr_copy(()).await.expect_unapproved();
+                Ok(())
+            })
+            .await;
+    }
+
     #[sqlx::test]
     async fn push_to_pr_do_nothing_when_not_approved(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
@@ -545,6 +1222,55 @@ mod tests {
         .await;
     }
 
+    #[sqlx::test]
+    async fn convert_to_draft_cancels_running_auto_build(pool: sqlx::PgPool) {
+        let gh = run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester
+                .workflow_start(WorkflowRunData::from(tester.auto_branch().await).with_run_id(123))
+                .await?;
+            tester.set_pr_status_draft(()).await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+            Auto build cancelled due to the PR being converted to a draft. Cancelled workflows:
+
+            - https://github.com/rust-lang/borstest/actions/runs/123
+            ");
+            tester
+                .wait_for_pr((), |pr| pr.pr_status == PullRequestStatus::Draft)
+                .await?;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_status(PullRequestStatus::Draft)
+                .expect_no_auto_build();
+            Ok(())
+        })
+        .await;
+        gh.check_cancelled_workflows(default_repo_name(), &[123]);
+    }
+
+    #[sqlx::test]
+    async fn ready_for_review_rejoins_merge_queue(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.set_pr_status_draft(()).await?;
+            tester
+                .wait_for_pr((), |pr| pr.pr_status == PullRequestStatus::Draft)
+                .await?;
+            tester.process_merge_queue().await;
+            tester.get_pr_copy(()).await.expect_no_auto_build();
+
+            tester.set_pr_status_ready_for_review(()).await?;
+            tester
+                .wait_for_pr((), |pr| pr.pr_status == PullRequestStatus::Open)
+                .await?;
+            tester.start_auto_build(()).await?;
+            Ok(())
+        })
+        .await;
+    }
+
     #[sqlx::test]
     async fn assign_pr_updates_assignees(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
@@ -3,15 +3,29 @@ use std::time::Duration;
 
 use anyhow::Context;
 use chrono::{DateTime, Utc};
-use octocrab::params::checks::CheckRunConclusion;
-use std::collections::BTreeMap;
+use octocrab::models::RunId;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use crate::bors::PullRequestStatus;
-use crate::bors::RepositoryState;
 use crate::bors::comment::build_timed_out_comment;
-use crate::bors::handlers::workflow::{CancelBuildError, cancel_build};
+use crate::bors::event::{WorkflowRunCompleted, WorkflowRunStarted};
+use crate::bors::handlers::config::effective_config_changes;
+use crate::bors::handlers::trybuild::TRY_BRANCH_NAME;
+use crate::bors::handlers::workflow::{
+    CancelBuildError, handle_workflow_completed, handle_workflow_started, timeout_build,
+};
+use crate::bors::handlers::{
+    PullRequestData, deny_request, is_bors_observed_branch, reply_to_command,
+};
+use crate::bors::merge_queue::{MergeQueueSender, is_auto_branch};
 use crate::bors::mergeability_queue::MergeabilityQueueSender;
-use crate::database::{BuildModel, BuildStatus};
+use crate::bors::outbox::post_comment_batch;
+use crate::bors::{Comment, PullRequestStatus, RepositoryState};
+use crate::config::ArtifactRetentionConfig;
+use crate::database::{BuildModel, BuildStatus, WorkflowStatus, WorkflowType};
+use crate::github::{CommitSha, GithubUser, PullRequestNumber};
+use crate::permissions::PermissionType;
 use crate::{PgDbClient, TeamApiClient};
 
 /// Go through pending builds and figure out if we need to do something about them:
@@ -19,63 +33,359 @@ use crate::{PgDbClient, TeamApiClient};
 pub async fn refresh_pending_builds(
     repo: Arc<RepositoryState>,
     db: &PgDbClient,
+    merge_queue_tx: MergeQueueSender,
 ) -> anyhow::Result<()> {
     let running_builds = db.get_pending_builds(repo.repository()).await?;
     tracing::info!("Found {} pending build(s)", running_builds.len());
 
-    let timeout = repo.config.load().timeout;
+    let (timeout, auto_build_timeout) = {
+        let config = repo.config.load();
+        (
+            config.timeout,
+            config.auto_build_timeout.unwrap_or(config.timeout),
+        )
+    };
+    let mut auto_build_timed_out = false;
+    let mut comments = vec![];
     for build in running_builds {
-        if let Err(error) = refresh_build(&repo, db, &build, timeout).await {
-            tracing::error!("Could not refresh pending build {build:?}: {error:?}");
+        let is_auto_build = is_auto_branch(&build.branch);
+        let timeout = if is_auto_build {
+            auto_build_timeout
+        } else {
+            timeout
+        };
+        match refresh_build(&repo, db, &build, timeout, &mut comments).await {
+            Ok(timed_out) => auto_build_timed_out |= timed_out && is_auto_build,
+            Err(error) => {
+                tracing::error!("Could not refresh pending build {build:?}: {error:?}");
+            }
         }
     }
+    // Many builds can time out at once, e.g. after a CI outage
+    post_comment_batch(&repo, db, comments).await;
+
+    if auto_build_timed_out {
+        // The timed out auto build no longer blocks the queue
+        merge_queue_tx.notify().await?;
+    }
     Ok(())
 }
 
+/// Time out the build if it has been running for longer than `timeout`.
+/// Returns `true` if the build has timed out. The comment that should be posted about the timeout
+/// is added to `comments`.
 async fn refresh_build(
     repo: &RepositoryState,
     db: &PgDbClient,
     build: &BuildModel,
     timeout: Duration,
-) -> anyhow::Result<()> {
-    if elapsed_time(build.created_at) >= timeout {
-        if let Some(pr) = db.find_pr_by_build(build).await? {
-            tracing::info!("Cancelling build {build:?}");
-            match cancel_build(&repo.client, db, build, CheckRunConclusion::TimedOut).await {
-                Ok(_) => {}
-                Err(
-                    CancelBuildError::FailedToMarkBuildAsCancelled(error)
-                    | CancelBuildError::FailedToCancelWorkflows(error),
-                ) => {
-                    tracing::error!(
-                        "Could not cancel workflows for SHA {}: {error:?}",
-                        build.commit_sha
-                    );
-                }
-            }
+    comments: &mut Vec<(PullRequestNumber, Comment)>,
+) -> anyhow::Result<bool> {
+    if elapsed_time(build.created_at) < timeout {
+        return Ok(false);
+    }
 
-            if let Err(error) = repo
-                .client
-                .post_comment(pr.number, build_timed_out_comment(timeout))
-                .await
-            {
-                tracing::error!("Could not send comment to PR {}: {error:?}", pr.number);
+    if let Some(pr) = db.find_pr_by_build(build).await? {
+        tracing::info!("Timing out build {build:?}");
+        match timeout_build(&repo.client, db, build).await {
+            Ok(_) => {}
+            Err(
+                CancelBuildError::FailedToMarkBuildAsCancelled(error)
+                | CancelBuildError::FailedToCancelWorkflows(error),
+            ) => {
+                tracing::error!(
+                    "Could not cancel workflows for SHA {}: {error:?}",
+                    build.commit_sha
+                );
             }
-        } else {
-            // This is an orphaned build. It should never be created, unless we have some bug or
-            // unexpected race condition in bors.
-            // When we do encounter such a build, we can mark it as timeouted, as it is no longer
-            // relevant.
-            // Note that we could write an explicit query for finding these orphaned builds,
-            // but that could be quite expensive. Instead we piggyback on the existing logic
-            // for timed out builds; if a build is still pending and has no PR attached, then
-            // there likely won't be any additional event that could mark it as finished.
-            // So eventually all such builds will arrive here
+        }
+
+        comments.push((pr.number, build_timed_out_comment(timeout)));
+    } else {
+        // This is an orphaned build. It should never be created, unless we have some bug or
+        // unexpected race condition in bors.
+        // When we do encounter such a build, we can mark it as timeouted, as it is no longer
+        // relevant.
+        // Note that we could write an explicit query for finding these orphaned builds,
+        // but that could be quite expensive. Instead we piggyback on the existing logic
+        // for timed out builds; if a build is still pending and has no PR attached, then
+        // there likely won't be any additional event that could mark it as finished.
+        // So eventually all such builds will arrive here
+        tracing::warn!(
+            "Detected orphaned pending without a PR, marking it as time outed: {build:?}"
+        );
+        db.update_build_status(build, BuildStatus::Timeouted)
+            .await?;
+    }
+    Ok(true)
+}
+
+/// Reconcile pending builds with the state of their workflow runs on GitHub.
+///
+/// Webhooks that were sent while bors was not running are lost, so a restart during a long build
+/// could leave the build pending forever, which would block the merge queue until the build times
+/// out. Therefore, on startup, bors replays the workflow run events that it has missed: workflow
+/// runs that it does not know about yet are tracked, and runs that have completed in the meantime
+/// are completed, which finalizes their build.
+pub async fn reconcile_pending_builds(
+    repo: Arc<RepositoryState>,
+    db: Arc<PgDbClient>,
+    merge_queue_tx: MergeQueueSender,
+) -> anyhow::Result<()> {
+    let pending_builds = db.get_pending_builds(repo.repository()).await?;
+    tracing::info!("Reconciling {} pending build(s)", pending_builds.len());
+
+    for build in pending_builds {
+        if let Err(error) = reconcile_build(&repo, &db, &build, &merge_queue_tx).await {
+            tracing::error!("Could not reconcile pending build {build:?}: {error:?}");
+        }
+    }
+    Ok(())
+}
+
+async fn reconcile_build(
+    repo: &Arc<RepositoryState>,
+    db: &Arc<PgDbClient>,
+    build: &BuildModel,
+    merge_queue_tx: &MergeQueueSender,
+) -> anyhow::Result<()> {
+    let commit_sha = CommitSha(build.commit_sha.clone());
+    let gh_runs = repo
+        .client
+        .get_workflow_runs_for_commit(&build.branch, &commit_sha)
+        .await?;
+    let known_runs: HashMap<u64, WorkflowStatus> = db
+        .get_workflows_for_build(build)
+        .await?
+        .into_iter()
+        .map(|workflow| (workflow.run_id.0, workflow.status))
+        .collect();
+
+    let mut started = 0;
+    let mut completed = 0;
+    for run in gh_runs {
+        let known_status = known_runs.get(&run.id.0).copied();
+        if known_status.is_none() {
+            handle_workflow_started(
+                repo.clone(),
+                db.clone(),
+                WorkflowRunStarted {
+                    repository: repo.repository().clone(),
+                    name: run.name,
+                    branch: build.branch.clone(),
+                    commit_sha: commit_sha.clone(),
+                    run_id: run.id,
+                    workflow_type: WorkflowType::Github,
+                    url: run.url,
+                },
+            )
+            .await?;
+            started += 1;
+        }
+        if run.status != WorkflowStatus::Pending
+            && known_status.is_none_or(|status| status == WorkflowStatus::Pending)
+        {
+            handle_workflow_completed(
+                repo.clone(),
+                db.clone(),
+                WorkflowRunCompleted {
+                    repository: repo.repository().clone(),
+                    branch: build.branch.clone(),
+                    commit_sha: commit_sha.clone(),
+                    run_id: run.id,
+                    status: run.status,
+                    running_time: run.running_time,
+                    check_suite_id: run.check_suite_id,
+                },
+                merge_queue_tx,
+            )
+            .await?;
+            completed += 1;
+        }
+    }
+    if started > 0 || completed > 0 {
+        tracing::info!(
+            started,
+            completed,
+            "Replayed missed workflow events of build {}",
+            build.id
+        );
+    }
+    Ok(())
+}
+
+/// Prefix of all branches on which bors runs CI workflows.
+const BORS_BRANCH_PREFIX: &str = "automation/bors/";
+
+/// Zombie workflow runs cancelled by this bors instance, in all repositories.
+pub struct ZombieWorkflowMetrics {
+    cancelled: AtomicU64,
+}
+
+pub static ZOMBIE_WORKFLOW_METRICS: ZombieWorkflowMetrics = ZombieWorkflowMetrics {
+    cancelled: AtomicU64::new(0),
+};
+
+impl ZombieWorkflowMetrics {
+    fn record_cancelled(&self, count: usize) {
+        self.cancelled.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> ZombieWorkflowSnapshot {
+        ZombieWorkflowSnapshot {
+            cancelled: self.cancelled.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A point-in-time view of [`ZombieWorkflowMetrics`].
+#[derive(Debug, Serialize)]
+pub struct ZombieWorkflowSnapshot {
+    pub cancelled: u64,
+}
+
+/// Cancel "zombie" workflow runs, i.e. runs on bors branches that are still running on GitHub,
+/// but that do not belong to any pending build. These can be left behind e.g. when a build was
+/// cancelled, but cancelling its workflows has failed, or when bors missed a workflow webhook.
+pub async fn cancel_zombie_workflows(
+    repo: Arc<RepositoryState>,
+    db: &PgDbClient,
+) -> anyhow::Result<()> {
+    // Only the runs of the try and auto branches are listed, so that runs of other branches of a
+    // busy repository do not have to be paged through
+    let branches = repo
+        .client
+        .get_branches_with_prefix(BORS_BRANCH_PREFIX)
+        .await?;
+    let mut running_runs = vec![];
+    for branch in branches
+        .iter()
+        .filter(|branch| is_bors_observed_branch(branch))
+    {
+        running_runs.extend(repo.client.get_running_workflow_runs(branch).await?);
+    }
+    if running_runs.is_empty() {
+        return Ok(());
+    }
+
+    let mut active_run_ids = HashSet::new();
+    let mut active_commits = HashSet::new();
+    for build in db.get_pending_builds(repo.repository()).await? {
+        active_run_ids.extend(
+            db.get_workflows_for_build(&build)
+                .await?
+                .into_iter()
+                .map(|workflow| workflow.run_id.0),
+        );
+        // Workflows that were started for a pending build, but that bors does not know about yet
+        active_commits.insert((build.branch, build.commit_sha));
+    }
+
+    let zombie_run_ids: Vec<_> = running_runs
+        .into_iter()
+        .filter(|run| {
+            !active_run_ids.contains(&run.id.0)
+                && !active_commits.contains(&(run.branch.clone(), run.commit_sha.0.clone()))
+        })
+        .map(|run| run.id)
+        .collect();
+    if zombie_run_ids.is_empty() {
+        return Ok(());
+    }
+
+    tracing::info!(
+        reaped = zombie_run_ids.len(),
+        "Cancelling zombie workflow runs: {zombie_run_ids:?}"
+    );
+    repo.client.cancel_workflows(&zombie_run_ids).await?;
+    ZOMBIE_WORKFLOW_METRICS.record_cancelled(zombie_run_ids.len());
+    Ok(())
+}
+
+/// How many try builds are cleaned up at most during a single clean-up of try artifacts, to avoid
+/// sending too many requests to GitHub at once.
+const TRY_ARTIFACT_CLEANUP_BATCH_SIZE: i64 = 20;
+
+/// Clean up the artifacts of try builds that have finished longer than the configured retention
+/// period ago, either by deleting them or by dispatching the configured clean-up workflow.
+pub async fn clean_up_try_artifacts(
+    repo: Arc<RepositoryState>,
+    db: &PgDbClient,
+) -> anyhow::Result<()> {
+    let Some(retention) = repo.config.load().try_artifact_retention.clone() else {
+        return Ok(());
+    };
+    let finished_before = now() - chrono::Duration::from_std(retention.period)?;
+    let builds = db
+        .get_builds_for_artifact_cleanup(
+            repo.repository(),
+            TRY_BRANCH_NAME,
+            finished_before,
+            TRY_ARTIFACT_CLEANUP_BATCH_SIZE,
+        )
+        .await?;
+    for build in builds {
+        if let Err(error) = clean_up_build_artifacts(&repo, db, &build, &retention).await {
             tracing::warn!(
-                "Detected orphaned pending without a PR, marking it as time outed: {build:?}"
+                "Cannot clean up the artifacts of build {}: {error:?}",
+                build.id
             );
-            db.update_build_status(build, BuildStatus::Timeouted)
+        }
+    }
+    Ok(())
+}
+
+async fn clean_up_build_artifacts(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    build: &BuildModel,
+    retention: &ArtifactRetentionConfig,
+) -> anyhow::Result<()> {
+    let run_ids: Vec<RunId> = db
+        .get_workflows_for_build(build)
+        .await?
+        .into_iter()
+        .filter(|workflow| workflow.workflow_type == WorkflowType::Github)
+        .map(|workflow| workflow.run_id.into())
+        .collect();
+
+    match &retention.workflow {
+        Some(workflow) => {
+            let Some(pr) = db.find_pr_by_build(build).await? else {
+                return Err(anyhow::anyhow!("Build {} has no pull request", build.id));
+            };
+            let run_ids = run_ids
+                .iter()
+                .map(|run_id| run_id.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            repo.client
+                .dispatch_workflow(
+                    workflow,
+                    &pr.base_branch,
+                    serde_json::json!({
+                        "build_id": build.id.to_string(),
+                        "commit_sha": build.commit_sha,
+                        "run_ids": run_ids,
+                    }),
+                )
                 .await?;
+            tracing::info!(
+                "Dispatched workflow {workflow} to clean up the artifacts of build {}",
+                build.id
+            );
+            db.record_build_artifacts_cleaned(build, false).await?;
+        }
+        None => {
+            let mut deleted = 0;
+            for run_id in run_ids {
+                deleted += repo
+                    .client
+                    .delete_artifacts_for_workflow_run(run_id)
+                    .await?;
+            }
+            tracing::info!("Deleted {deleted} artifact(s) of build {}", build.id);
+            db.record_build_artifacts_cleaned(build, true).await?;
         }
     }
     Ok(())
@@ -87,7 +397,7 @@ pub async fn reload_repository_permissions(
     team_api_client: &TeamApiClient,
 ) -> anyhow::Result<()> {
     let permissions = team_api_client
-        .load_permissions(repo.repository())
+        .load_repository_permissions(&repo.client, &repo.config.load().permissions)
         .await
         .with_context(|| {
             format!(
@@ -95,12 +405,13 @@ pub async fn reload_repository_permissions(
                 repo.repository()
             )
         })?;
-    repo.permissions.store(Arc::new(permissions));
+    repo.permissions.store(permissions);
     Ok(())
 }
 
 /// Reloads the mergeability status from GitHub for PRs that have an unknown
-/// mergeability status in the DB.
+/// mergeability status in the DB, and for approved PRs that are waiting in the merge queue, so
+/// that conflicts of queued PRs are detected before they reach the head of the queue.
 pub async fn reload_mergeability_status(
     repo: Arc<RepositoryState>,
     db: &PgDbClient,
@@ -115,7 +426,20 @@ pub async fn reload_mergeability_status(
         prs.len()
     );
 
-    for pr in prs {
+    // PRs whose auto build is running are skipped, their conflicts will be found by the build
+    let queued_prs: Vec<_> = db
+        .get_merge_queue_prs(repo.repository(), None, &[])
+        .await?
+        .into_iter()
+        .filter(|pr| pr.auto_build.is_none())
+        .collect();
+
+    tracing::info!(
+        "Checking {} queued PR(s) for merge conflicts",
+        queued_prs.len()
+    );
+
+    for pr in prs.into_iter().chain(queued_prs) {
         mergeability_queue.enqueue_pr(repo.repository().clone(), pr.number);
     }
 
@@ -129,6 +453,50 @@ pub async fn reload_repository_config(repo: Arc<RepositoryState>) -> anyhow::Res
     Ok(())
 }
 
+/// Reloads the configuration and the permissions of the given repository right away, instead of
+/// waiting for the next periodic refresh.
+/// Returns the top-level settings that were changed by the reload, as (key, old value, new value)
+/// triples.
+pub async fn reload_repository(
+    repo: Arc<RepositoryState>,
+    db: &PgDbClient,
+    team_api_client: &TeamApiClient,
+) -> anyhow::Result<Vec<(String, String, String)>> {
+    let config = repo.client.load_config().await?;
+    let permissions = team_api_client
+        .load_repository_permissions(&repo.client, &config.permissions)
+        .await
+        .with_context(|| {
+            format!(
+                "Could not load permissions for repository {}",
+                repo.repository()
+            )
+        })?;
+    let changes = effective_config_changes(&repo.config.load(), &config)?;
+    repo.config.store(Arc::new(config));
+    repo.permissions.store(permissions);
+
+    let changed_keys = changes
+        .iter()
+        .map(|(key, _, _)| key.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    tracing::info!(
+        "Reloaded repository {}, changed settings: [{changed_keys}]",
+        repo.repository()
+    );
+    db.record_audit_action(
+        repo.repository(),
+        None,
+        "config_reloaded",
+        (!changed_keys.is_empty()).then_some(changed_keys.as_str()),
+    )
+    .await?;
+    Ok(changes)
+}
+
+/// Synchronize the state (status, base branch and labels) of non-closed PRs in the DB with
+/// GitHub, to heal any divergence caused by webhooks that bors did not receive.
 pub async fn sync_pull_requests_state(
     repo: Arc<RepositoryState>,
     db: Arc<PgDbClient>,
@@ -156,15 +524,37 @@ pub async fn sync_pull_requests_state(
     for (pr_num, gh_pr) in &nonclosed_gh_prs_num {
         let db_pr = nonclosed_db_prs_num.get(pr_num);
         if let Some(db_pr) = db_pr {
-            if db_pr.pr_status != gh_pr.status {
-                // PR status changed in GitHub
+            if db_pr.base_branch != gh_pr.base.name {
+                // PR was retargeted while we were not receiving webhooks. Upsert the whole PR,
+                // which also resets its mergeability state, so that it is checked again.
                 tracing::debug!(
-                    "PR {} status changed from {:?} to {:?}",
+                    "PR {} base branch changed from {} to {}",
                     pr_num,
-                    db_pr.pr_status,
-                    gh_pr.status
+                    db_pr.base_branch,
+                    gh_pr.base.name
                 );
-                db.set_pr_status(repo_name, *pr_num, gh_pr.status).await?;
+                db.upsert_pull_request(repo_name, gh_pr.clone().into())
+                    .await?;
+            } else {
+                if db_pr.pr_status != gh_pr.status {
+                    // PR status changed in GitHub
+                    tracing::debug!(
+                        "PR {} status changed from {:?} to {:?}",
+                        pr_num,
+                        db_pr.pr_status,
+                        gh_pr.status
+                    );
+                    db.set_pr_status(repo_name, *pr_num, gh_pr.status).await?;
+                }
+                if db_pr.labels != gh_pr.labels {
+                    tracing::debug!(
+                        "PR {} labels changed from {:?} to {:?}",
+                        pr_num,
+                        db_pr.labels,
+                        gh_pr.labels
+                    );
+                    db.set_pr_labels(repo_name, *pr_num, &gh_pr.labels).await?;
+                }
             }
         } else {
             // Nonclosed PRs in GitHub that are either not in the DB or marked as closed
@@ -173,22 +563,97 @@ pub async fn sync_pull_requests_state(
                 .await?;
         }
     }
-    // PRs that are closed in GitHub but not in the DB. In theory PR could also be merged
-    // but bors does the merging so it should not happen.
+    // PRs that are closed or merged in GitHub but not in the DB. PRs are usually merged by bors,
+    // but they can also be merged manually, so we load the PR to find out which one it was.
     for pr_num in nonclosed_db_prs_num.keys() {
         if !nonclosed_gh_prs_num.contains_key(pr_num) {
+            let status = match repo.client.get_pull_request(*pr_num).await {
+                Ok(pr) => pr.status,
+                Err(error) => {
+                    tracing::warn!("Cannot load PR {pr_num}, assuming it is closed: {error:?}");
+                    PullRequestStatus::Closed
+                }
+            };
             tracing::debug!(
-                "PR {} not found in open/draft prs in GitHub, closing it in DB",
-                pr_num
+                "PR {} not found in open/draft prs in GitHub, marking it as {} in DB",
+                pr_num,
+                status
             );
-            db.set_pr_status(repo_name, *pr_num, PullRequestStatus::Closed)
-                .await?;
+            db.set_pr_status(repo_name, *pr_num, status).await?;
         }
     }
 
     Ok(())
 }
 
+/// Synchronize the state of all PRs of the repository with GitHub on demand, e.g. after a GitHub
+/// outage during which webhooks were lost.
+pub(super) async fn command_sync(
+    repo: Arc<RepositoryState>,
+    db: Arc<PgDbClient>,
+    pr: PullRequestData<'_>,
+    author: &GithubUser,
+    mergeability_queue: MergeabilityQueueSender,
+) -> anyhow::Result<()> {
+    if !repo
+        .permissions
+        .load()
+        .has_permission(author.id, PermissionType::Review)
+    {
+        return deny_request(&repo, pr, author, PermissionType::Review).await;
+    }
+
+    sync_pull_requests_state(Arc::clone(&repo), Arc::clone(&db)).await?;
+    reload_mergeability_status(Arc::clone(&repo), &db, mergeability_queue).await?;
+    reply_to_command(
+        &repo,
+        pr,
+        Comment::new(
+            ":arrows_counterclockwise: The state of pull requests was synchronized with GitHub."
+                .to_string(),
+        ),
+    )
+    .await
+}
+
+/// Reload the configuration and permissions of the repository on demand, e.g. right after its
+/// configuration file was changed, and report which settings were changed.
+pub(super) async fn command_reload(
+    repo: Arc<RepositoryState>,
+    db: Arc<PgDbClient>,
+    pr: PullRequestData<'_>,
+    author: &GithubUser,
+    team_api_client: &TeamApiClient,
+) -> anyhow::Result<()> {
+    if !repo
+        .permissions
+        .load()
+        .has_permission(author.id, PermissionType::Review)
+    {
+        return deny_request(&repo, pr, author, PermissionType::Review).await;
+    }
+
+    let text = match reload_repository(Arc::clone(&repo), &db, team_api_client).await {
+        Ok(changes) if changes.is_empty() => {
+            ":arrows_counterclockwise: The configuration and permissions of this repository were reloaded. The effective settings were not changed.".to_string()
+        }
+        Ok(changes) => {
+            let mut text = ":arrows_counterclockwise: The configuration and permissions of this repository were reloaded. The following settings were changed:\n".to_string();
+            for (key, old, new) in changes {
+                text.push_str(&format!("\n- `{key}`: `{old}` -> `{new}`"));
+            }
+            text
+        }
+        Err(error) => {
+            tracing::warn!("Cannot reload repository {}: {error:?}", repo.repository());
+            format!(
+                ":x: The repository could not be reloaded, the previous configuration and permissions are kept:\n\n```\n{error}\n```"
+            )
+        }
+    };
+    reply_to_command(&repo, pr, Comment::new(text)).await
+}
+
 #[cfg(not(test))]
 fn now() -> DateTime<Utc> {
     Utc::now()
@@ -213,9 +678,14 @@ fn elapsed_time(date: DateTime<Utc>) -> Duration {
 mod tests {
     use crate::bors::PullRequestStatus;
     use crate::bors::handlers::refresh::MOCK_TIME;
-    use crate::bors::handlers::trybuild::TRY_BUILD_CHECK_RUN_NAME;
-    use crate::database::{MergeableState, OctocrabMergeableState};
-    use crate::tests::{BorsBuilder, BorsTester, GitHubState, default_repo_name, run_test};
+    use crate::bors::handlers::trybuild::{TRY_BRANCH_NAME, TRY_BUILD_CHECK_RUN_NAME};
+    use crate::bors::merge_queue::AUTO_BUILD_CHECK_RUN_NAME;
+    use crate::database::{BuildStatus, MergeableState, OctocrabMergeableState, WorkflowStatus};
+    use crate::github::PullRequestNumber;
+    use crate::tests::{
+        BorsBuilder, BorsTester, Branch, Comment, GitHubState, Permissions, User, WorkflowArtifact,
+        WorkflowEvent, WorkflowRunData, default_repo_name, run_test,
+    };
     use chrono::Utc;
     use octocrab::params::checks::{CheckRunConclusion, CheckRunStatus};
     use std::future::Future;
@@ -348,6 +818,313 @@ timeout = 3600
         gh.check_cancelled_workflows(default_repo_name(), &[1]);
     }
 
+    #[sqlx::test]
+    async fn refresh_timeout_comments_are_rate_limited(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+timeout = 3600
+notifications_per_minute = 1
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+                tester.post_comment("@bors try").await?;
+                tester.expect_comments((), 1).await;
+                tester
+                    .post_comment(Comment::new(pr2.id(), "@bors try"))
+                    .await?;
+                tester.expect_comments(pr2.id(), 1).await;
+
+                with_mocked_time(Duration::from_secs(4000), async {
+                    tester.cancel_timed_out_builds().await;
+                })
+                .await;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":boom: Test timed out after `3600`s");
+
+                // The comment for the second PR is postponed to the next minute
+                assert!(
+                    tester
+                        .db()
+                        .get_due_outbox_actions(&default_repo_name())
+                        .await?
+                        .is_empty()
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn cancel_zombie_workflows_ignore_pending_build(pool: sqlx::PgPool) {
+        let gh = run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+            tester.workflow_start(tester.try_branch().await).await?;
+
+            tester.cancel_zombie_workflows().await;
+            Ok(())
+        })
+        .await;
+        gh.check_cancelled_workflows(default_repo_name(), &[]);
+    }
+
+    #[sqlx::test]
+    async fn cancel_zombie_workflows_of_cancelled_build(pool: sqlx::PgPool) {
+        let gh = run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .modify_repo(&default_repo_name(), |repo| {
+                    repo.workflow_cancel_error = true
+                })
+                .await;
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+            tester.workflow_start(tester.try_branch().await).await?;
+            tester.post_comment("@bors try cancel").await?;
+            tester.expect_comments((), 1).await;
+
+            tester
+                .modify_repo(&default_repo_name(), |repo| {
+                    repo.workflow_cancel_error = false
+                })
+                .await;
+            tester.cancel_zombie_workflows().await;
+            Ok(())
+        })
+        .await;
+        gh.check_cancelled_workflows(default_repo_name(), &[1]);
+    }
+
+    #[sqlx::test]
+    async fn cancel_zombie_workflows_on_bors_branches(pool: sqlx::PgPool) {
+        let gh = run_test(pool, async |tester: &mut BorsTester| {
+            let try_branch = tester.create_branch(TRY_BRANCH_NAME).await;
+            tester
+                .modify_repo(&default_repo_name(), |repo| {
+                    // More runs than fit on a single page
+                    for run_id in 1..=150 {
+                        repo.update_workflow_run(
+                            WorkflowRunData::from(try_branch.clone()).with_run_id(run_id),
+                            WorkflowStatus::Pending,
+                        );
+                    }
+                    // Runs of other branches are not started by bors
+                    let revert = Branch::new("automation/bors/revert-1", "revert-sha");
+                    repo.branches.push(revert.clone());
+                    repo.update_workflow_run(
+                        WorkflowRunData::from(revert).with_run_id(200),
+                        WorkflowStatus::Pending,
+                    );
+                    repo.update_workflow_run(
+                        WorkflowRunData::from(Branch::new("feature", "feature-sha"))
+                            .with_run_id(201),
+                        WorkflowStatus::Pending,
+                    );
+                })
+                .await;
+            tester.cancel_zombie_workflows().await;
+
+            let (status, body) = tester
+                .web_request(http::Method::GET, "/api/v1/zombie-workflows", None)
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+            let metrics: serde_json::Value = serde_json::from_str(&body)?;
+            // Metrics are shared by all tests running in parallel
+            assert!(metrics["cancelled"].as_u64().unwrap() >= 150);
+            Ok(())
+        })
+        .await;
+        gh.check_cancelled_workflows(default_repo_name(), &(1..=150).collect::<Vec<_>>());
+    }
+
+    #[sqlx::test]
+    async fn reconcile_completes_build_with_missed_webhook(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester.workflow_start(tester.auto_branch().await).await?;
+
+            // The workflow finishes while bors is not running
+            let workflow = WorkflowRunData::from(tester.auto_branch().await);
+            tester
+                .modify_repo(&default_repo_name(), |repo| {
+                    repo.update_workflow_run(workflow, WorkflowStatus::Success)
+                })
+                .await;
+            tester.reconcile_pending_builds().await;
+            tester.process_merge_queue().await;
+            let comment = tester.get_next_comment_text(()).await?;
+            assert!(comment.contains("Test successful"));
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_status(PullRequestStatus::Merged);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn recover_from_crash_during_auto_build(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester.workflow_start(tester.auto_branch().await).await?;
+            let before_crash = tester.snapshot_database().await?;
+
+            // The workflow finishes, but bors crashes before the result is stored durably
+            tester
+                .workflow_event(WorkflowEvent::success(tester.auto_branch().await))
+                .await?;
+            tester.restore_database(&before_crash).await?;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_auto_build(|build| build.status == BuildStatus::Pending);
+
+            // After the restart, the missed result is loaded from GitHub
+            tester.reconcile_pending_builds().await;
+            tester.process_merge_queue().await;
+            let comment = tester.get_next_comment_text(()).await?;
+            assert!(comment.contains("Test successful"));
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_status(PullRequestStatus::Merged);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn reconcile_tracks_workflow_with_missed_webhook(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+
+            // The workflow starts while bors is not running
+            let workflow = WorkflowRunData::from(tester.try_branch().await);
+            tester
+                .modify_repo(&default_repo_name(), |repo| {
+                    repo.update_workflow_run(workflow, WorkflowStatus::Pending)
+                })
+                .await;
+            tester.reconcile_pending_builds().await;
+
+            let pr = tester
+                .db()
+                .get_pull_request(&default_repo_name(), PullRequestNumber(1))
+                .await?
+                .unwrap();
+            let build = pr.try_build.unwrap();
+            assert_eq!(build.status, BuildStatus::Pending);
+            let workflows = tester.db().get_workflows_for_build(&build).await?;
+            assert_eq!(workflows.len(), 1);
+            assert_eq!(workflows[0].status, WorkflowStatus::Pending);
+            Ok(())
+        })
+        .await;
+    }
+
+    fn gh_state_with_auto_build_timeout() -> GitHubState {
+        GitHubState::default().with_default_config(
+            r#"
+merge_queue_enabled = true
+timeout = 3600
+auto_build_timeout = 7200
+"#,
+        )
+    }
+
+    #[sqlx::test]
+    async fn refresh_auto_build_do_nothing_before_auto_build_timeout(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(gh_state_with_auto_build_timeout())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.start_auto_build(()).await?;
+                with_mocked_time(Duration::from_secs(4000), async {
+                    tester.cancel_timed_out_builds().await;
+                })
+                .await;
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_auto_build(|b| b.status == BuildStatus::Pending);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn refresh_auto_build_after_auto_build_timeout(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(gh_state_with_auto_build_timeout())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.start_auto_build(()).await?;
+                with_mocked_time(Duration::from_secs(8000), async {
+                    tester.cancel_timed_out_builds().await;
+                })
+                .await;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":boom: Test timed out after `7200`s");
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_auto_build(|b| b.status == BuildStatus::Timeouted);
+                tester
+                    .expect_check_run(
+                        &tester.get_pr_copy(()).await.get_gh_pr().head_sha,
+                        AUTO_BUILD_CHECK_RUN_NAME,
+                        AUTO_BUILD_CHECK_RUN_NAME,
+                        CheckRunStatus::Completed,
+                        Some(CheckRunConclusion::TimedOut),
+                    )
+                    .await;
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn refresh_auto_build_timeout_cancels_workflow(pool: sqlx::PgPool) {
+        let gh = BorsBuilder::new(pool)
+            .github(gh_state_with_auto_build_timeout())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.start_auto_build(()).await?;
+                tester.workflow_start(tester.auto_branch().await).await?;
+                with_mocked_time(Duration::from_secs(8000), async {
+                    tester.cancel_timed_out_builds().await;
+                })
+                .await;
+                tester.expect_comments((), 1).await;
+                Ok(())
+            })
+            .await;
+        gh.check_cancelled_workflows(default_repo_name(), &[1]);
+    }
+
+    #[sqlx::test]
+    async fn refresh_auto_build_timeout_starts_next_auto_build(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(gh_state_with_auto_build_timeout())
+            .run_test(async |tester: &mut BorsTester| {
+                let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+                tester.approve(()).await?;
+                tester.approve(pr2.id()).await?;
+                tester.start_auto_build(()).await?;
+                with_mocked_time(Duration::from_secs(8000), async {
+                    tester.cancel_timed_out_builds().await;
+                })
+                .await;
+                tester.expect_comments((), 1).await;
+                tester.start_auto_build(pr2.id()).await?;
+                Ok(())
+            })
+            .await;
+    }
+
     #[sqlx::test]
     async fn refresh_enqueues_unknown_mergeable_prs(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
@@ -371,6 +1148,44 @@ timeout = 3600
         .await;
     }
 
+    #[sqlx::test]
+    async fn refresh_detects_conflicts_of_queued_pr(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+[labels]
+conflicted = ["+S-conflicted"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                // GitHub has recomputed the mergeability, but no webhook was sent
+                tester
+                    .modify_pr_state((), |pr| pr.mergeable_state = OctocrabMergeableState::Dirty)
+                    .await;
+                tester.update_mergeability_status().await;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":lock: This PR has developed merge conflicts with the `main` branch, so it was removed from the queue. It will enter the queue again once they are resolved. If the resolution changes the diff of the PR, it will need to be re-approved.");
+                tester
+                    .wait_for_pr((), |pr| {
+                        pr.is_approved()
+                            && pr.waiting_on_conflicts
+                            && pr.mergeable_state == MergeableState::HasConflicts
+                    })
+                    .await?;
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_added_labels(&["S-conflicted"]);
+
+                // The conflict is only reported once
+                tester.update_mergeability_status().await;
+                tester.post_comment("@bors ping").await?;
+                tester.expect_comments((), 1).await;
+                Ok(())
+            })
+            .await;
+    }
+
     #[sqlx::test]
     async fn refresh_new_pr(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
@@ -429,6 +1244,354 @@ timeout = 3600
         .await;
     }
 
+    #[sqlx::test]
+    async fn refresh_pr_with_changed_base_branch(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let branch = tester.create_branch("beta").await;
+            tester
+                .with_blocked_webhooks(async |tester: &mut BorsTester| {
+                    tester.edit_pr((), |pr| pr.base_branch = branch).await
+                })
+                .await?;
+            tester.refresh_prs().await;
+            tester
+                .wait_for_pr((), |pr| pr.base_branch == "beta")
+                .await?;
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn refresh_pr_with_changed_labels(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .modify_pr_state((), |pr| pr.labels.push("T-compiler".to_string()))
+                .await;
+            tester.refresh_prs().await;
+            tester
+                .wait_for_pr((), |pr| pr.labels == ["T-compiler"])
+                .await?;
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn refresh_pr_with_status_merged(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.modify_pr_state((), |pr| pr.merge_pr()).await;
+            tester.refresh_prs().await;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_status(PullRequestStatus::Merged);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn sync_command(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let pr = tester
+                .with_blocked_webhooks(async |tester: &mut BorsTester| {
+                    tester.open_pr(default_repo_name(), |_| {}).await
+                })
+                .await?;
+            tester.post_comment("@bors sync").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":arrows_counterclockwise: The state of pull requests was synchronized with GitHub."
+            );
+            tester
+                .get_pr_copy(pr.number)
+                .await
+                .expect_status(PullRequestStatus::Open);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn sync_command_insufficient_permission(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .post_comment(Comment::from("@bors sync").with_author(User::unprivileged()))
+                .await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @"@unprivileged-user: :key: Insufficient privileges: not in review users"
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn reload_command(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("timeout = 3600"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .modify_repo(&default_repo_name(), |repo| {
+                        repo.config = "timeout = 7200\nmin_ci_time = 60".to_string();
+                    })
+                    .await;
+                tester.post_comment("@bors reload").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                    :arrows_counterclockwise: The configuration and permissions of this repository were reloaded. The following settings were changed:
+
+                    - `min_ci_time`: `null` -> `60`
+                    - `timeout`: `3600` -> `7200`
+                    ");
+                let log = tester
+                    .db()
+                    .get_audit_log(&default_repo_name(), None, 10)
+                    .await?;
+                let entry = log
+                    .iter()
+                    .find(|entry| entry.action == "config_reloaded")
+                    .unwrap();
+                assert_eq!(entry.details.as_deref(), Some("min_ci_time, timeout"));
+
+                // The new configuration is used right away
+                tester.post_comment("@bors reload").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":arrows_counterclockwise: The configuration and permissions of this repository were reloaded. The effective settings were not changed.");
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn reload_command_invalid_config(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("timeout = 3600"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .modify_repo(&default_repo_name(), |repo| {
+                        repo.config = r#"timeout = "1h""#.to_string();
+                    })
+                    .await;
+                tester.post_comment("@bors reload").await?;
+                let comment = tester.get_next_comment_text(()).await?;
+                assert!(comment.starts_with(
+                    ":x: The repository could not be reloaded, the previous configuration and permissions are kept"
+                ));
+                assert!(comment.contains("invalid type: string"));
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn reload_command_insufficient_permission(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .post_comment(Comment::from("@bors reload").with_author(User::unprivileged()))
+                .await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @"@unprivileged-user: :key: Insufficient privileges: not in review users"
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn refresh_config(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .modify_repo(&default_repo_name(), |repo| {
+                    repo.config = "try_enabled = false".to_string();
+                })
+                .await;
+            tester.refresh_config().await;
+            tester.post_comment("@bors try").await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":no_entry_sign: This repository has try builds disabled.");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn change_app_client_reloads_repositories(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .modify_repo(&default_repo_name(), |repo| {
+                    repo.config = "try_enabled = false".to_string();
+                })
+                .await;
+            tester.change_app_client().await;
+            tester.post_comment("@bors try").await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":no_entry_sign: This repository has try builds disabled.");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn refresh_permissions(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .modify_repo(&default_repo_name(), |repo| {
+                    repo.permissions = Permissions::empty();
+                })
+                .await;
+            tester.refresh_permissions().await;
+            tester.post_comment("@bors try").await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @"@default-user: :key: Insufficient privileges: not in try users");
+            Ok(())
+        })
+        .await;
+    }
+
+    fn gh_state_with_try_artifact_retention(workflow: Option<&str>) -> GitHubState {
+        let workflow = workflow
+            .map(|workflow| format!(r#"workflow = "{workflow}""#))
+            .unwrap_or_default();
+        GitHubState::default().with_default_config(&format!(
+            r#"
+[try_artifact_retention]
+period = 3600
+{workflow}
+"#
+        ))
+    }
+
+    async fn finish_try_build_with_artifacts(tester: &mut BorsTester) -> anyhow::Result<()> {
+        tester.post_comment("@bors try").await?;
+        tester.expect_comments((), 1).await;
+        tester
+            .workflow_full_success(
+                WorkflowRunData::from(tester.try_branch().await).with_artifacts(vec![
+                    WorkflowArtifact {
+                        id: 10,
+                        name: "dist".to_string(),
+                        size: 1024,
+                    },
+                ]),
+            )
+            .await?;
+        tester.expect_comments((), 1).await;
+        Ok(())
+    }
+
+    async fn try_build_artifact_count(tester: &BorsTester) -> usize {
+        let db = tester.db();
+        let pr = db
+            .get_pull_request(&default_repo_name(), PullRequestNumber(1))
+            .await
+            .unwrap()
+            .unwrap();
+        db.get_artifacts_for_build(&pr.try_build.unwrap())
+            .await
+            .unwrap()
+            .len()
+    }
+
+    #[sqlx::test]
+    async fn clean_up_try_artifacts_within_retention_period(pool: sqlx::PgPool) {
+        let gh = BorsBuilder::new(pool)
+            .github(gh_state_with_try_artifact_retention(None))
+            .run_test(async |tester: &mut BorsTester| {
+                finish_try_build_with_artifacts(tester).await?;
+                with_mocked_time(Duration::from_secs(10), async {
+                    tester.clean_up_try_artifacts().await;
+                })
+                .await;
+                assert_eq!(try_build_artifact_count(tester).await, 1);
+                Ok(())
+            })
+            .await;
+        assert!(
+            gh.get_repo(&default_repo_name())
+                .lock()
+                .deleted_artifacts
+                .is_empty()
+        );
+    }
+
+    #[sqlx::test]
+    async fn clean_up_try_artifacts_delete(pool: sqlx::PgPool) {
+        let gh = BorsBuilder::new(pool)
+            .github(gh_state_with_try_artifact_retention(None))
+            .run_test(async |tester: &mut BorsTester| {
+                finish_try_build_with_artifacts(tester).await?;
+                with_mocked_time(Duration::from_secs(4000), async {
+                    tester.clean_up_try_artifacts().await;
+                    // The artifacts are cleaned up only once
+                    tester.clean_up_try_artifacts().await;
+                })
+                .await;
+                assert_eq!(try_build_artifact_count(tester).await, 0);
+                Ok(())
+            })
+            .await;
+        assert_eq!(
+            gh.get_repo(&default_repo_name()).lock().deleted_artifacts,
+            vec![10]
+        );
+    }
+
+    #[sqlx::test]
+    async fn clean_up_try_artifacts_ignore_pending_build(pool: sqlx::PgPool) {
+        let gh = BorsBuilder::new(pool)
+            .github(gh_state_with_try_artifact_retention(None))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors try").await?;
+                tester.expect_comments((), 1).await;
+                with_mocked_time(Duration::from_secs(4000), async {
+                    tester.clean_up_try_artifacts().await;
+                })
+                .await;
+                Ok(())
+            })
+            .await;
+        assert!(
+            gh.get_repo(&default_repo_name())
+                .lock()
+                .deleted_artifacts
+                .is_empty()
+        );
+    }
+
+    #[sqlx::test]
+    async fn clean_up_try_artifacts_dispatch_workflow(pool: sqlx::PgPool) {
+        let gh = BorsBuilder::new(pool)
+            .github(gh_state_with_try_artifact_retention(Some("cleanup.yml")))
+            .run_test(async |tester: &mut BorsTester| {
+                finish_try_build_with_artifacts(tester).await?;
+                with_mocked_time(Duration::from_secs(4000), async {
+                    tester.clean_up_try_artifacts().await;
+                })
+                .await;
+                // The artifacts are not deleted by bors, so they are kept in the database
+                assert_eq!(try_build_artifact_count(tester).await, 1);
+                Ok(())
+            })
+            .await;
+        let repo = gh.get_repo(&default_repo_name());
+        let repo = repo.lock();
+        assert!(repo.deleted_artifacts.is_empty());
+        insta::assert_debug_snapshot!(repo.dispatched_workflows, @r#"
+            [
+                (
+                    "cleanup.yml",
+                    Object {
+                        "inputs": Object {
+                            "build_id": String("1"),
+                            "commit_sha": String("merge-0-pr-1"),
+                            "run_ids": String("1"),
+                        },
+                        "ref": String("main"),
+                    },
+                ),
+            ]
+            "#);
+    }
+
     async fn with_mocked_time<Fut: Future<Output = ()>>(in_future: Duration, future: Fut) {
         // It is important to use this function only with a single threaded runtime,
         // otherwise the `MOCK_TIME` variable might get mixed up between different threads.
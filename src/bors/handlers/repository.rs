@@ -0,0 +1,139 @@
+use octocrab::params::checks::CheckRunConclusion;
+
+use crate::PgDbClient;
+use crate::bors::RepositoryState;
+use crate::bors::handlers::workflow::{CancelBuildError, cancel_build};
+use crate::bors::merge_queue::MergeQueueSender;
+use crate::database::DeactivationReason;
+
+/// Stop operating in the repository, e.g. because it was archived.
+///
+/// Pending builds are cancelled, the merge queue of the repository is not processed anymore and
+/// commands are ignored, until the repository is reactivated.
+pub(super) async fn deactivate_repository(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    reason: DeactivationReason,
+) -> anyhow::Result<()> {
+    tracing::info!("Deactivating repository {}: {reason}", repo.repository());
+    db.deactivate_repository(repo.repository(), reason).await?;
+    db.record_audit_action(
+        repo.repository(),
+        None,
+        "repository_deactivated",
+        Some(&reason.to_string()),
+    )
+    .await?;
+
+    for build in db.get_pending_builds(repo.repository()).await? {
+        tracing::info!("Cancelling build {build:?}");
+        match cancel_build(&repo.client, db, &build, CheckRunConclusion::Cancelled).await {
+            Ok(_) => {}
+            Err(CancelBuildError::FailedToMarkBuildAsCancelled(error)) => {
+                tracing::error!("Could not cancel build {build:?}: {error:?}");
+            }
+            // GitHub refuses to cancel workflows once the app is uninstalled, there is nothing
+            // more that we can do about them.
+            Err(CancelBuildError::FailedToCancelWorkflows(error)) => {
+                tracing::warn!(
+                    "Could not cancel workflows for SHA {}: {error:?}",
+                    build.commit_sha
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resume operating in the repository, if it was deactivated for the given `reason`.
+pub(super) async fn reactivate_repository(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    reason: DeactivationReason,
+    merge_queue_tx: &MergeQueueSender,
+) -> anyhow::Result<()> {
+    if !db.reactivate_repository(repo.repository(), reason).await? {
+        return Ok(());
+    }
+
+    tracing::info!("Reactivated repository {}", repo.repository());
+    db.record_audit_action(repo.repository(), None, "repository_reactivated", None)
+        .await?;
+    merge_queue_tx.notify().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::DeactivationReason;
+    use crate::tests::{BorsTester, WorkflowEvent, WorkflowRunData, default_repo_name, run_test};
+
+    #[sqlx::test]
+    async fn archive_deactivates_repository(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.archive_repo().await?;
+            let repo = tester.db().repo_db(&default_repo_name()).await?.unwrap();
+            assert!(!repo.is_active());
+            assert_eq!(repo.deactivation_reason, Some(DeactivationReason::Archived));
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn archive_cancels_pending_builds(pool: sqlx::PgPool) {
+        let gh = run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+            tester
+                .workflow_event(WorkflowEvent::started(
+                    WorkflowRunData::from(tester.try_branch().await).with_run_id(123),
+                ))
+                .await?;
+
+            tester.archive_repo().await?;
+            tester.get_pr_copy(()).await.expect_try_build_cancelled();
+            Ok(())
+        })
+        .await;
+        gh.check_cancelled_workflows(default_repo_name(), &[123]);
+    }
+
+    #[sqlx::test]
+    async fn archived_repository_ignores_commands(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.archive_repo().await?;
+            tester.post_comment("@bors r+").await?;
+            // Returning here will make sure that no comments were received
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn archived_repository_does_not_process_merge_queue(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.archive_repo().await?;
+            tester.process_merge_queue().await;
+            tester.get_pr_copy(()).await.expect_no_auto_build();
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn unarchive_reactivates_repository(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.archive_repo().await?;
+            tester.unarchive_repo().await?;
+            tester.approve(()).await?;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_approved_by("default-user");
+            Ok(())
+        })
+        .await;
+    }
+}
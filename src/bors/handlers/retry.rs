@@ -1,30 +1,103 @@
 use std::sync::Arc;
 
 use crate::PgDbClient;
+use crate::bors::command::{CommandPrefix, Parent};
+use crate::bors::comment::{
+    build_retried_comment, retry_cancelled_workflows_comment, try_builds_disabled_comment,
+};
+use crate::bors::handlers::trybuild::command_try_build;
 use crate::bors::handlers::{PullRequestData, deny_request, has_permission};
 use crate::bors::merge_queue::MergeQueueSender;
 use crate::bors::{Comment, RepositoryState};
-use crate::database::QueueStatus;
-use crate::github::{GithubUser, PullRequestNumber};
+use crate::database::{BuildModel, BuildStatus, QueueStatus};
+use crate::github::{CommitSha, GithubUser, PullRequestNumber};
 use crate::permissions::PermissionType;
 
+/// Retries a failed auto build of an approved PR. If there is no such build, the failed try build
+/// of the PR is started again instead, with the same parent and jobs.
+#[allow(clippy::too_many_arguments)]
 pub(super) async fn command_retry(
     repo_state: Arc<RepositoryState>,
     db: Arc<PgDbClient>,
     pr: PullRequestData<'_>,
     author: &GithubUser,
+    cancel_workflows: bool,
+    reason: Option<String>,
     merge_queue_tx: &MergeQueueSender,
+    bot_prefix: &CommandPrefix,
 ) -> anyhow::Result<()> {
-    if !has_permission(&repo_state, author, pr, PermissionType::Review).await? {
-        deny_request(&repo_state, pr.number(), author, PermissionType::Review).await?;
-        return Ok(());
-    }
-
     let pr_model = pr.db;
+    let failed_try_build = pr_model.try_build.as_ref().filter(|build| {
+        matches!(
+            build.status,
+            BuildStatus::Failure | BuildStatus::Cancelled | BuildStatus::Timeouted
+        )
+    });
 
-    if matches!(pr_model.queue_status(), QueueStatus::Stalled(_, _)) {
+    if let QueueStatus::Stalled(_, build) = pr_model.queue_status() {
+        if !has_permission(&repo_state, author, pr, PermissionType::Review).await? {
+            return deny_request(&repo_state, pr, author, PermissionType::Review).await;
+        }
+
+        // Workflows of the failed build that are still running would compete for runners with
+        // the new auto build
+        if cancel_workflows {
+            cancel_running_workflows(&repo_state, &db, pr.number(), &build, "auto").await?;
+        }
+        record_retry(
+            &repo_state,
+            &db,
+            pr,
+            &build,
+            author,
+            reason.as_deref(),
+            "auto",
+        )
+        .await?;
         db.clear_auto_build(pr_model).await?;
         merge_queue_tx.notify().await?;
+    } else if let Some(build) = failed_try_build {
+        if !has_permission(&repo_state, author, pr, PermissionType::Try).await? {
+            return deny_request(&repo_state, pr, author, PermissionType::Try).await;
+        }
+        if !repo_state.config.load().try_enabled {
+            repo_state
+                .post_comment(
+                    pr.number(),
+                    try_builds_disabled_comment(repo_state.config.load().language),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        if cancel_workflows {
+            cancel_running_workflows(&repo_state, &db, pr.number(), build, "try").await?;
+        }
+        record_retry(
+            &repo_state,
+            &db,
+            pr,
+            build,
+            author,
+            reason.as_deref(),
+            "try",
+        )
+        .await?;
+        let jobs = db.get_try_build_jobs(build).await?;
+        let parent = Parent::CommitSha(CommitSha(build.parent.clone()));
+        command_try_build(
+            repo_state,
+            db,
+            pr,
+            author,
+            Some(parent),
+            jobs,
+            None,
+            bot_prefix,
+        )
+        .await?;
+    } else if !has_permission(&repo_state, author, pr, PermissionType::Review).await? {
+        deny_request(&repo_state, pr, author, PermissionType::Review).await?;
     } else {
         notify_of_invalid_retry_state(&repo_state, pr.number()).await?;
     }
@@ -32,14 +105,64 @@ pub(super) async fn command_retry(
     Ok(())
 }
 
+/// Stores the retry in the DB, so that spurious failures can be analyzed later, and echoes its
+/// reason in a comment, if it was given.
+async fn record_retry(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    pr: PullRequestData<'_>,
+    build: &BuildModel,
+    author: &GithubUser,
+    reason: Option<&str>,
+    build_kind: &str,
+) -> anyhow::Result<()> {
+    db.record_build_retry(pr.db, build, Some(&author.username), reason)
+        .await?;
+    if let Some(reason) = reason {
+        repo.post_comment(pr.number(), build_retried_comment(build_kind, reason))
+            .await?;
+    }
+    Ok(())
+}
+
+async fn cancel_running_workflows(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    pr_number: PullRequestNumber,
+    build: &BuildModel,
+    build_kind: &str,
+) -> anyhow::Result<()> {
+    let workflows = db.get_pending_workflows_for_build(build).await?;
+    if workflows.is_empty() {
+        return Ok(());
+    }
+    let run_ids: Vec<octocrab::models::RunId> = workflows
+        .iter()
+        .map(|workflow| octocrab::models::RunId(workflow.run_id.0))
+        .collect();
+    tracing::info!(
+        "Cancelling workflows {run_ids:?} of failed build {}",
+        build.id
+    );
+    repo.client.cancel_workflows(&run_ids).await?;
+    repo.post_comment(
+        pr_number,
+        retry_cancelled_workflows_comment(
+            build_kind,
+            workflows.into_iter().map(|workflow| workflow.url),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
 async fn notify_of_invalid_retry_state(
     repo: &RepositoryState,
     pr_number: PullRequestNumber,
 ) -> anyhow::Result<()> {
-    repo.client
-        .post_comment(
+    repo.post_comment(
             pr_number,
-            Comment::new(":exclamation: You can only retry pull requests that are approved and have a previously failed auto build".to_string())
+            Comment::new(":exclamation: You can only retry pull requests that are approved and have a previously failed auto build, or that have a failed try build".to_string())
         )
         .await?;
     Ok(())
@@ -47,7 +170,9 @@ async fn notify_of_invalid_retry_state(
 
 #[cfg(test)]
 mod tests {
-    use crate::tests::{BorsTester, Comment, User, run_test};
+    use crate::database::{BuildStatus, WorkflowStatus};
+    use crate::github::PullRequestNumber;
+    use crate::tests::{BorsTester, Comment, User, WorkflowRunData, default_repo_name, run_test};
 
     #[sqlx::test]
     async fn retry_command_insufficient_privileges(pool: sqlx::PgPool) {
@@ -70,7 +195,7 @@ mod tests {
             tester.post_comment(Comment::from("@bors retry")).await?;
             insta::assert_snapshot!(
                 tester.get_next_comment_text(()).await?,
-                @":exclamation: You can only retry pull requests that are approved and have a previously failed auto build"
+                @":exclamation: You can only retry pull requests that are approved and have a previously failed auto build, or that have a failed try build"
             );
             Ok(())
         })
@@ -91,7 +216,142 @@ mod tests {
             tester.process_merge_queue().await;
             insta::assert_snapshot!(
                 tester.get_next_comment_text(()).await?,
-                @":hourglass: Testing commit pr-1-sha with merge merge-1-pr-1..."
+                @r"
+            :hourglass: Testing commit pr-1-sha with merge merge-1-pr-1...
+
+            No PRs are waiting behind it in the [queue](https://test.com/bors/queue/borstest).
+            "
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn retry_cancel_workflows(pool: sqlx::PgPool) {
+        let gh = run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            let branch = tester.auto_branch().await;
+            let w1 = WorkflowRunData::from(branch.clone()).with_run_id(1);
+            let w2 = WorkflowRunData::from(branch).with_run_id(2);
+            for workflow in [&w1, &w2] {
+                tester
+                    .modify_repo(&default_repo_name(), |repo| {
+                        repo.update_workflow_run(workflow.clone(), WorkflowStatus::Pending)
+                    })
+                    .await;
+            }
+            tester.workflow_start(w1).await?;
+            tester.workflow_full_failure(w2).await?;
+            tester.expect_comments((), 1).await;
+
+            tester
+                .post_comment(Comment::from("@bors retry cancel-workflows"))
+                .await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @r"
+            Cancelled running workflows of the failed auto build:
+            - https://github.com/rust-lang/borstest/actions/runs/1
+            "
+            );
+            tester.wait_for_pr((), |pr| pr.auto_build.is_none()).await?;
+            Ok(())
+        })
+        .await;
+        gh.check_cancelled_workflows(default_repo_name(), &[1]);
+    }
+
+    #[sqlx::test]
+    async fn retry_with_reason(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester
+                .workflow_full_failure(tester.auto_branch().await)
+                .await?;
+            tester.expect_comments((), 1).await;
+            tester
+                .post_comment(Comment::from(r#"@bors retry reason="network timeout""#))
+                .await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":repeat: Retrying the failed auto build. Reason: network timeout"
+            );
+            tester.wait_for_pr((), |pr| pr.auto_build.is_none()).await?;
+
+            tester.post_comment("@bors info").await?;
+            let comment = tester.get_next_comment_text(()).await?;
+            assert!(comment.contains("- Retries: 1 auto build, 0 try builds"));
+            assert!(comment.contains("\t- Last reason: network timeout"));
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn retry_try_build(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try jobs=ci").await?;
+            tester.expect_comments((), 1).await;
+            tester
+                .workflow_full_failure(tester.try_branch().await)
+                .await?;
+            tester.expect_comments((), 1).await;
+            let failed_build = tester
+                .db()
+                .get_pull_request(&default_repo_name(), PullRequestNumber(1))
+                .await?
+                .unwrap()
+                .try_build
+                .unwrap();
+
+            tester.post_comment(Comment::from("@bors retry")).await?;
+            let comment = tester.get_next_comment_text(()).await?;
+            assert!(comment.contains(":hourglass: Trying commit pr-1-sha"));
+            tester
+                .wait_for_pr((), |pr| {
+                    pr.try_build
+                        .as_ref()
+                        .is_some_and(|build| build.status == BuildStatus::Pending)
+                })
+                .await?;
+
+            let pr = tester
+                .db()
+                .get_pull_request(&default_repo_name(), PullRequestNumber(1))
+                .await?
+                .unwrap();
+            let try_builds = tester.db().get_pending_try_builds(&pr).await?;
+            assert_eq!(try_builds.len(), 1);
+            assert_eq!(try_builds[0].build.parent, failed_build.parent);
+            assert_eq!(try_builds[0].jobs, vec!["ci".to_string()]);
+
+            tester.post_comment("@bors info").await?;
+            let comment = tester.get_next_comment_text(()).await?;
+            assert!(comment.contains("- Retries: 0 auto builds, 1 try build"));
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn retry_try_build_requires_try_permission(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+            tester
+                .workflow_full_failure(tester.try_branch().await)
+                .await?;
+            tester.expect_comments((), 1).await;
+
+            tester
+                .post_comment(Comment::from("@bors retry").with_author(User::unprivileged()))
+                .await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @"@unprivileged-user: :key: Insufficient privileges: not in try users"
             );
             Ok(())
         })
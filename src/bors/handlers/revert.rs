@@ -0,0 +1,297 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+
+use crate::PgDbClient;
+use crate::bors::command::Priority;
+use crate::bors::comment::dry_run_comment;
+use crate::bors::handlers::backport::merged_build;
+use crate::bors::handlers::{PullRequestData, deny_request, has_permission, reply_to_command};
+use crate::bors::merge_queue::MergeQueueSender;
+use crate::bors::{Comment, PullRequestStatus, RepositoryState, RollupMode};
+use crate::database::ApprovalInfo;
+use crate::github::api::operations::{ForcePush, MergeError};
+use crate::github::{CommitSha, GithubUser};
+use crate::permissions::PermissionType;
+
+/// Prefix of the branches from which revert PRs are opened. The branch of a revert PR is
+/// suffixed with the number of the reverted PR.
+pub(super) const REVERT_BRANCH_PREFIX: &str = "automation/bors/revert-";
+
+/// Opens a PR that reverts the given PR, which was merged by bors. The revert is applied on top
+/// of the current head of the base branch of the PR. If `priority` is set, the revert PR is
+/// approved right away with that priority.
+pub(super) async fn command_revert(
+    repo: Arc<RepositoryState>,
+    db: Arc<PgDbClient>,
+    pr: PullRequestData<'_>,
+    author: &GithubUser,
+    priority: Option<Priority>,
+    merge_queue_tx: &MergeQueueSender,
+) -> anyhow::Result<()> {
+    if !has_permission(&repo, author, pr, PermissionType::Review).await? {
+        return deny_request(&repo, pr, author, PermissionType::Review).await;
+    }
+
+    // Squash-merged PRs are closed on GitHub, but bors has marked them as merged
+    let merged = pr.github.status == PullRequestStatus::Merged
+        || pr.db.pr_status == PullRequestStatus::Merged;
+    let Some(build) = merged_build(pr.db).filter(|_| merged) else {
+        return reply_to_command(
+            &repo,
+            pr,
+            Comment::new(
+                ":exclamation: Only PRs that were merged by bors can be reverted.".to_string(),
+            ),
+        )
+        .await;
+    };
+
+    let pr_number = pr.number();
+    if repo.config.load().dry_run {
+        let comment = dry_run_comment(&format!("open a PR that reverts #{pr_number}"));
+        return reply_to_command(&repo, pr, comment).await;
+    }
+
+    // Opening a second PR from the same branch would fail
+    let branch = format!("{REVERT_BRANCH_PREFIX}{pr_number}");
+    if let Some(revert_pr) = repo.client.find_open_pull_request(&branch).await? {
+        return reply_to_command(
+            &repo,
+            pr,
+            Comment::new(format!(
+                ":information_source: This PR is already being reverted in [#{0}]({1}/pull/{0}).",
+                revert_pr.number,
+                repo.client.repository_url()
+            )),
+        )
+        .await;
+    }
+
+    let base_branch = &pr.github.base.name;
+    let merged_sha = CommitSha(build.commit_sha.clone());
+    let message = format!(
+        "Revert #{pr_number} - {}\n\nThis reverts commit {merged_sha}, which merged #{pr_number}.",
+        pr.github.title
+    );
+    let revert_sha = repo
+        .client
+        .create_revert_commit(&merged_sha, &CommitSha(build.parent.clone()), &message)
+        .await?;
+
+    // If the base branch has moved since the merge, the revert has to be merged on top of it.
+    // A leftover revert branch without an open PR is simply overwritten.
+    let base_sha = repo.client.get_branch_sha(base_branch).await?;
+    let start_sha = if base_sha == merged_sha {
+        &revert_sha
+    } else {
+        &base_sha
+    };
+    repo.client
+        .set_branch_to_sha(&branch, start_sha, ForcePush::Yes)
+        .await
+        .map_err(|error| anyhow!("Cannot set revert branch to {start_sha}: {error:?}"))?;
+    if base_sha != merged_sha {
+        match repo
+            .client
+            .merge_branches(&branch, &revert_sha, &message)
+            .await
+        {
+            Ok(_) => {}
+            Err(MergeError::Conflict) => {
+                return reply_to_command(
+                    &repo,
+                    pr,
+                    Comment::new(format!(
+                        ":lock: This PR cannot be reverted automatically, because the revert conflicts with later changes of the `{base_branch}` branch."
+                    )),
+                )
+                .await;
+            }
+            Err(error) => return Err(anyhow!("Cannot merge revert of PR {pr_number}: {error:?}")),
+        }
+    }
+
+    let revert_pr = repo
+        .client
+        .create_pull_request(
+            &format!("Revert #{pr_number}: {}", pr.github.title),
+            &branch,
+            base_branch,
+            &format!(
+                "Reverts #{pr_number}.\n\nRequested by `{}`.",
+                author.username
+            ),
+        )
+        .await?;
+    tracing::info!("Opened PR {} to revert PR {pr_number}", revert_pr.number);
+
+    let mut text = format!(
+        ":leftwards_arrow_with_hook: Opened #{} to revert this PR.",
+        revert_pr.number
+    );
+    if let Some(priority) = priority {
+        let revert_pr_db = db
+            .upsert_pull_request(repo.repository(), revert_pr.clone().into())
+            .await?;
+        let approval_info = ApprovalInfo {
+            approver: author.username.clone(),
+            sha: revert_pr.head.sha.to_string(),
+        };
+        db.approve(
+            &revert_pr_db,
+            approval_info,
+            &author.username,
+            Some(priority),
+            Some(RollupMode::Never),
+            None,
+        )
+        .await?;
+        merge_queue_tx.notify().await?;
+        text.push_str(&format!(
+            " It was approved by `{}` with priority {priority}.",
+            author.username
+        ));
+    }
+    reply_to_command(&repo, pr, Comment::new(text)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::PullRequestNumber;
+    use crate::tests::{BorsTester, default_repo_name, run_test};
+
+    #[sqlx::test]
+    async fn revert_unmerged_pr(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors revert").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":exclamation: Only PRs that were merged by bors can be reverted."
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn revert_merged_pr(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_and_finish_auto_build(()).await?;
+            tester.set_pr_status_merged(()).await?;
+
+            tester.post_comment("@bors revert").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":leftwards_arrow_with_hook: Opened #2 to revert this PR."
+            );
+
+            let repo = tester.get_repo(&default_repo_name()).await;
+            let repo = repo.lock();
+            let revert_pr = &repo.pull_requests[&2];
+            assert_eq!(revert_pr.title, "Revert #1: Title of PR 1");
+            assert_eq!(revert_pr.base_branch.get_name(), "main");
+            insta::assert_snapshot!(
+                repo.commit_messages[&revert_pr.head_sha],
+                @r"
+            Revert #1 - Title of PR 1
+
+            This reverts commit merge-0-pr-1, which merged #1.
+            "
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn revert_already_reverted_pr(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_and_finish_auto_build(()).await?;
+            tester.set_pr_status_merged(()).await?;
+
+            tester.post_comment("@bors revert").await?;
+            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors revert").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":information_source: This PR is already being reverted in [#2](https://github.com/rust-lang/borstest/pull/2)."
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn revert_conflict_after_base_moved(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_and_finish_auto_build(()).await?;
+            tester.set_pr_status_merged(()).await?;
+            tester
+                .modify_branch("main", |branch| branch.set_to_sha("main-sha2"))
+                .await;
+            tester
+                .modify_branch("automation/bors/revert-1", |branch| {
+                    branch.merge_conflict = true
+                })
+                .await;
+
+            tester.post_comment("@bors revert").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":lock: This PR cannot be reverted automatically, because the revert conflicts with later changes of the `main` branch."
+            );
+            let repo = tester.get_repo(&default_repo_name()).await;
+            assert!(!repo.lock().pull_requests.contains_key(&2));
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn revert_squash_merged_pr(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors r+ squash").await?;
+            tester.expect_comments((), 1).await;
+            tester.start_and_finish_auto_build(()).await?;
+            tester.expect_comments((), 1).await;
+            tester.set_pr_status_closed(()).await?;
+
+            tester.post_comment("@bors revert").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":leftwards_arrow_with_hook: Opened #2 to revert this PR."
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn revert_and_approve(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_and_finish_auto_build(()).await?;
+            tester.set_pr_status_merged(()).await?;
+
+            tester.post_comment("@bors revert p=100").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":leftwards_arrow_with_hook: Opened #2 to revert this PR. It was approved by `default-user` with priority 100."
+            );
+
+            let revert_pr = tester
+                .db()
+                .get_pull_request(&default_repo_name(), PullRequestNumber(2))
+                .await?
+                .unwrap();
+            assert_eq!(revert_pr.approver(), Some("default-user"));
+            assert_eq!(revert_pr.priority, Some(100));
+            Ok(())
+        })
+        .await;
+    }
+}
@@ -1,28 +1,42 @@
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
 
 use crate::bors::RepositoryState;
 use crate::bors::command::RollupMode;
 use crate::bors::command::{Approver, CommandPrefix};
 use crate::bors::comment::{
-    approve_blocking_labels_present, approve_non_open_pr_comment, approve_wip_title,
-    approved_comment, delegate_comment, delegate_try_builds_comment, unapprove_non_open_pr_comment,
+    approve_blocking_labels_present, approve_lint_violations, approve_non_open_pr_comment,
+    approve_outdated_commit_comment, approve_unchecked_items, approve_wip_title, approved_comment,
+    build_env_cleared_comment, build_env_disabled_comment, build_env_not_allowed_comment,
+    build_env_set_comment, delegate_comment, delegate_try_builds_comment, describe_closed_tree,
+    merge_queue_disabled_comment, no_build_env_comment, tree_closed_blocked_pr_comment,
+    tree_reopened_unblocked_pr_comment, unapprove_non_open_pr_comment,
 };
 use crate::bors::handlers::labels::handle_label_trigger;
 use crate::bors::handlers::workflow::{AutoBuildCancelReason, maybe_cancel_auto_build};
-use crate::bors::handlers::{PullRequestData, deny_request};
+use crate::bors::handlers::{PullRequestData, acknowledge_command, deny_request, reply_to_command};
 use crate::bors::handlers::{has_permission, unapprove_pr};
-use crate::bors::merge_queue::MergeQueueSender;
+use crate::bors::merge_queue::{MergeQueueSender, QueueId, queue_overflow};
+use crate::bors::outbox::post_comment_batch;
 use crate::bors::{Comment, PullRequestStatus};
 use crate::database::ApprovalInfo;
 use crate::database::DelegatedPermission;
+use crate::database::MergeableState;
+use crate::database::PrMilestone;
+use crate::database::PullRequestModel;
 use crate::database::TreeState;
 use crate::github::LabelTrigger;
-use crate::github::{GithubUser, PullRequestNumber};
+use crate::github::{CommitSha, GithubUser, PullRequestNumber};
 use crate::permissions::PermissionType;
+use crate::utils::text::unchecked_task_items;
 use crate::{BorsContext, PgDbClient};
 
 /// Approve a pull request.
 /// A pull request can only be approved by a user of sufficient authority.
+/// If `commit` is specified, the approval is only performed if it matches the head of the PR.
 #[allow(clippy::too_many_arguments)]
 pub(super) async fn command_approve(
     ctx: Arc<BorsContext>,
@@ -33,36 +47,162 @@ pub(super) async fn command_approve(
     approver: &Approver,
     priority: Option<u32>,
     rollup: Option<RollupMode>,
+    squash: bool,
+    commit: Option<&CommitSha>,
     merge_queue_tx: &MergeQueueSender,
 ) -> anyhow::Result<()> {
     tracing::info!("Approving PR {}", pr.number());
     if !has_permission(&repo_state, author, pr, PermissionType::Review).await? {
-        deny_request(&repo_state, pr.number(), author, PermissionType::Review).await?;
-        return Ok(());
+        return deny_request(&repo_state, pr, author, PermissionType::Review).await;
     };
 
+    // The commit can be abbreviated, so it only has to be a prefix of the head SHA
+    if let Some(commit) = commit.filter(|commit| !pr.github.head.sha.0.starts_with(&commit.0)) {
+        reply_to_command(
+            &repo_state,
+            pr,
+            approve_outdated_commit_comment(commit, &pr.github.head.sha),
+        )
+        .await?;
+        return Ok(());
+    }
+
     if let Some(error_comment) = check_pr_approval_validity(pr, &repo_state).await? {
-        repo_state
-            .client
-            .post_comment(pr.number(), error_comment)
-            .await?;
+        reply_to_command(&repo_state, pr, error_comment).await?;
         return Ok(());
     }
 
     let approver = match approver {
         Approver::Myself => author.username.clone(),
-        Approver::Specified(approver) => approver.clone(),
+        Approver::Specified(approvers) => approvers.join(","),
     };
     let approval_info = ApprovalInfo {
-        approver: approver.clone(),
+        approver,
         sha: pr.github.head.sha.to_string(),
     };
 
-    db.approve(pr.db, approval_info, priority, rollup).await?;
-    handle_label_trigger(&repo_state, pr.number(), LabelTrigger::Approved).await?;
+    // Reviewers who approved the same commit before are kept as co-reviewers
+    let (approver, approval_authors) = db
+        .approve(
+            pr.db,
+            approval_info,
+            &author.username,
+            priority,
+            rollup,
+            Some(squash),
+        )
+        .await?;
+    db.record_pr_milestone(repo_state.repository(), pr.number(), PrMilestone::Approved)
+        .await?;
+    // A PR with merge conflicts cannot enter the queue yet, but there is no need to approve it
+    // again once its author resolves the conflicts
+    let waiting_on_conflicts = pr.db.mergeable_state == MergeableState::HasConflicts;
+    db.set_waiting_on_conflicts(pr.db, waiting_on_conflicts)
+        .await?;
+    handle_label_trigger(&repo_state, &db, pr.number(), LabelTrigger::Approved).await?;
+
+    // The PR only enters the merge queue once enough distinct reviewers have approved it
+    let required_approvals = repo_state.config.load().required_approvals;
+    let missing_approvals = Some(required_approvals.saturating_sub(approval_authors.len()))
+        .filter(|missing| *missing > 0);
+
+    if repo_state.config.load().preempt_on_priority && missing_approvals.is_none() {
+        let priority = priority
+            .map(|priority| priority as i32)
+            .or(pr.db.priority)
+            .unwrap_or(0);
+        preempt_lower_priority_builds(&repo_state, &db, pr.db, priority).await?;
+    }
 
     merge_queue_tx.notify().await?;
-    notify_of_approval(ctx, &repo_state, pr, approver.as_str()).await
+    notify_of_approval(
+        ctx,
+        &repo_state,
+        pr,
+        approver.as_str(),
+        priority,
+        missing_approvals,
+        waiting_on_conflicts,
+    )
+    .await
+}
+
+/// Cancels running auto builds of PRs with a lower priority than the just approved `pr` in the
+/// same merge queue, so that `pr` can be tested right away. The preempted PRs stay approved, so
+/// they are tested again once it is their turn.
+async fn preempt_lower_priority_builds(
+    repo_state: &RepositoryState,
+    db: &PgDbClient,
+    pr: &PullRequestModel,
+    priority: i32,
+) -> anyhow::Result<()> {
+    let repo_name = repo_state.repository();
+    let config = repo_state.config.load_full();
+    let queue = QueueId::of(&config, pr);
+    let queue_branch = match queue {
+        QueueId::Branch(branch) => Some(branch),
+        QueueId::Default | QueueId::Path(_) => None,
+    };
+
+    // Do not cancel anything if the approved PR could not be tested right away anyway
+    if pr.mergeable_state != MergeableState::Mergeable
+        || db.get_emergency_stop().await?.is_some()
+        || db.get_merge_queue_pause(repo_name).await?.is_some()
+    {
+        return Ok(());
+    }
+    let tree_state = match queue_branch {
+        Some(branch) => db.get_branch_tree_state(repo_name, branch).await?,
+        None => db
+            .repo_db(repo_name)
+            .await?
+            .map(|repo| repo.tree_state)
+            .unwrap_or(TreeState::Open),
+    };
+    if !tree_state.is_expired()
+        && tree_state
+            .priority()
+            .is_some_and(|threshold| i64::from(priority) < i64::from(threshold))
+    {
+        return Ok(());
+    }
+
+    for other in db.get_nonclosed_pull_requests(repo_name).await? {
+        // Builds of other queues (including other path queues targeting the same base branch)
+        // do not block the approved PR
+        if other.number == pr.number
+            || other.held
+            || QueueId::of(&config, &other) != queue
+            || other.priority.unwrap_or(0) >= priority
+        {
+            continue;
+        }
+        let Some(message) = maybe_cancel_auto_build(
+            &repo_state.client,
+            db,
+            &other,
+            AutoBuildCancelReason::Preempted(pr.number),
+        )
+        .await?
+        else {
+            continue;
+        };
+        tracing::info!(
+            "Auto build of PR {} was preempted by PR {}",
+            other.number,
+            pr.number
+        );
+        db.clear_auto_build(&other).await?;
+        repo_state
+            .post_comment(
+                other.number,
+                Comment::new(format!(
+                    "{message}\n\nThis PR stays approved and will be tested again."
+                )),
+            )
+            .await?;
+    }
+    Ok(())
 }
 
 /// Keywords that will prevent an approval if they appear in the PR's title.
@@ -78,7 +218,9 @@ async fn check_pr_approval_validity(
 ) -> anyhow::Result<Option<Comment>> {
     // Check PR status
     if !matches!(pr.github.status, PullRequestStatus::Open) {
-        return Ok(Some(approve_non_open_pr_comment()));
+        return Ok(Some(approve_non_open_pr_comment(
+            repo.config.load().language,
+        )));
     }
 
     // Check WIP title
@@ -98,6 +240,7 @@ async fn check_pr_approval_validity(
             config
                 .labels_blocking_approval
                 .iter()
+                .chain(&config.block_labels)
                 .any(|blocking_label| blocking_label == label)
         })
         .collect();
@@ -105,6 +248,19 @@ async fn check_pr_approval_validity(
         return Ok(Some(approve_blocking_labels_present(&blocking_labels)));
     }
 
+    // Check required items of the PR template
+    let unchecked_items =
+        unchecked_task_items(&pr.github.message, &config.required_checklist_items);
+    if !unchecked_items.is_empty() {
+        return Ok(Some(approve_unchecked_items(&unchecked_items)));
+    }
+
+    // Check the title and description, from which the merge commit message is created
+    let violations = config.lint.violations(&pr.github.title, &pr.github.message);
+    if !violations.is_empty() {
+        return Ok(Some(approve_lint_violations(&violations)));
+    }
+
     Ok(None)
 }
 
@@ -115,23 +271,21 @@ pub(super) async fn command_unapprove(
     db: Arc<PgDbClient>,
     pr: PullRequestData<'_>,
     author: &GithubUser,
+    merge_queue_tx: &MergeQueueSender,
 ) -> anyhow::Result<()> {
     let pr_num = pr.number();
 
     tracing::info!("Unapproving PR {}", pr_num);
     if !has_permission(&repo_state, author, pr, PermissionType::Review).await? {
-        deny_request(&repo_state, pr_num, author, PermissionType::Review).await?;
-        return Ok(());
+        return deny_request(&repo_state, pr, author, PermissionType::Review).await;
     };
 
     if !matches!(
         pr.github.status,
         PullRequestStatus::Open | PullRequestStatus::Draft
     ) {
-        repo_state
-            .client
-            .post_comment(pr_num, unapprove_non_open_pr_comment())
-            .await?;
+        let language = repo_state.config.load().language;
+        reply_to_command(&repo_state, pr, unapprove_non_open_pr_comment(language)).await?;
         return Ok(());
     }
 
@@ -143,6 +297,10 @@ pub(super) async fn command_unapprove(
     )
     .await?;
     unapprove_pr(&repo_state, &db, pr.db).await?;
+    if auto_build_cancel_message.is_some() {
+        // The cancelled auto build no longer blocks the queue
+        merge_queue_tx.notify().await?;
+    }
     notify_of_unapproval(&repo_state, pr, auto_build_cancel_message).await?;
 
     Ok(())
@@ -158,10 +316,11 @@ pub(super) async fn command_set_priority(
     priority: u32,
 ) -> anyhow::Result<()> {
     if !has_permission(&repo_state, author, pr, PermissionType::Review).await? {
-        deny_request(&repo_state, pr.number(), author, PermissionType::Review).await?;
-        return Ok(());
+        return deny_request(&repo_state, pr, author, PermissionType::Review).await;
     };
-    db.set_priority(pr.db, priority).await
+    db.set_priority(pr.db, priority).await?;
+    acknowledge_command(&repo_state, pr).await;
+    Ok(())
 }
 
 /// Delegate permissions of a pull request to its author.
@@ -171,6 +330,7 @@ pub(super) async fn command_delegate(
     pr: PullRequestData<'_>,
     author: &GithubUser,
     delegated_permission: DelegatedPermission,
+    duration: Option<Duration>,
     bot_prefix: &CommandPrefix,
 ) -> anyhow::Result<()> {
     tracing::info!(
@@ -179,17 +339,21 @@ pub(super) async fn command_delegate(
         delegated_permission
     );
     if !sufficient_delegate_permission(repo_state.clone(), author) {
-        deny_request(&repo_state, pr.number(), author, PermissionType::Review).await?;
-        return Ok(());
+        return deny_request(&repo_state, pr, author, PermissionType::Review).await;
     }
 
-    db.delegate(pr.db, delegated_permission).await?;
+    let expires_at = match duration {
+        Some(duration) => Some(Utc::now() + chrono::Duration::from_std(duration)?),
+        None => None,
+    };
+    db.delegate(pr.db, delegated_permission, expires_at).await?;
     notify_of_delegation(
         &repo_state,
-        pr.number(),
+        pr,
         &pr.github.author.username,
         &author.username,
         delegated_permission,
+        expires_at,
         bot_prefix,
     )
     .await
@@ -204,10 +368,11 @@ pub(super) async fn command_undelegate(
 ) -> anyhow::Result<()> {
     tracing::info!("Undelegating PR {} approval", pr.number());
     if !has_permission(&repo_state, author, pr, PermissionType::Review).await? {
-        deny_request(&repo_state, pr.number(), author, PermissionType::Review).await?;
-        return Ok(());
+        return deny_request(&repo_state, pr, author, PermissionType::Review).await;
     }
-    db.undelegate(pr.db).await
+    db.undelegate(pr.db).await?;
+    acknowledge_command(&repo_state, pr).await;
+    Ok(())
 }
 
 /// Set the rollup of a pull request.
@@ -220,36 +385,84 @@ pub(super) async fn command_set_rollup(
     rollup: RollupMode,
 ) -> anyhow::Result<()> {
     if !has_permission(&repo_state, author, pr, PermissionType::Review).await? {
-        deny_request(&repo_state, pr.number(), author, PermissionType::Review).await?;
-        return Ok(());
+        return deny_request(&repo_state, pr, author, PermissionType::Review).await;
     }
-    db.set_rollup(pr.db, rollup).await
+    db.set_rollup(pr.db, rollup).await?;
+    acknowledge_command(&repo_state, pr).await;
+    Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(super) async fn command_close_tree(
     repo_state: Arc<RepositoryState>,
     db: Arc<PgDbClient>,
     pr: PullRequestData<'_>,
     author: &GithubUser,
     priority: u32,
+    duration: Option<Duration>,
+    reason: Option<String>,
+    branch: Option<String>,
     comment_url: &str,
     merge_queue_tx: &MergeQueueSender,
 ) -> anyhow::Result<()> {
     if !sufficient_approve_permission(repo_state.clone(), author) {
-        deny_request(&repo_state, pr.number(), author, PermissionType::Review).await?;
-        return Ok(());
+        return deny_request(&repo_state, pr, author, PermissionType::Review).await;
+    };
+    let queue_branch = match tree_branch(&repo_state, &pr, branch) {
+        Ok(queue_branch) => queue_branch,
+        Err(branch) => return notify_of_unmanaged_tree_branch(&repo_state, pr, &branch).await,
     };
-    db.upsert_repository(
+    let until = match duration {
+        Some(duration) => Some(Utc::now() + chrono::Duration::from_std(duration)?),
+        None => None,
+    };
+    let tree_state = TreeState::Closed {
+        priority,
+        source: comment_url.to_string(),
+        reason,
+        until,
+    };
+    let previous_tree_state = self::tree_state(&repo_state, &db, queue_branch.as_deref()).await?;
+    match &queue_branch {
+        Some(branch) => {
+            db.upsert_branch_tree_state(repo_state.repository(), branch, tree_state.clone())
+                .await?
+        }
+        None => {
+            db.upsert_repository(repo_state.repository(), tree_state.clone())
+                .await?
+        }
+    }
+    db.record_audit_action(
         repo_state.repository(),
-        TreeState::Closed {
-            priority,
-            source: comment_url.to_string(),
-        },
+        None,
+        "tree_closed",
+        Some(&tree_audit_details(
+            queue_branch.as_deref(),
+            &format!("closed by `{}` below priority {priority}", author.username),
+        )),
     )
     .await?;
 
     merge_queue_tx.notify().await?;
-    notify_of_tree_closed(&repo_state, pr.number(), priority).await
+    if repo_state.config.load().tree_closure_notifications {
+        // PRs that were already blocked by the previous closure have already been notified
+        let previously_blocked = previous_tree_state.priority().unwrap_or(0);
+        let comments = waiting_queue_prs(&repo_state, &db, queue_branch.as_deref(), pr.number())
+            .await?
+            .into_iter()
+            .filter(|waiting| {
+                let pr_priority = i64::from(waiting.priority.unwrap_or(0));
+                pr_priority >= i64::from(previously_blocked) && pr_priority < i64::from(priority)
+            })
+            .map(|waiting| {
+                let comment = tree_closed_blocked_pr_comment(&tree_state, queue_branch.as_deref());
+                (waiting.number, comment)
+            })
+            .collect();
+        post_comment_batch(&repo_state, &db, comments).await;
+    }
+    notify_of_tree_closed(&repo_state, pr, &tree_state, queue_branch.as_deref()).await
 }
 
 pub(super) async fn command_open_tree(
@@ -257,18 +470,390 @@ pub(super) async fn command_open_tree(
     db: Arc<PgDbClient>,
     pr: PullRequestData<'_>,
     author: &GithubUser,
+    branch: Option<String>,
     merge_queue_tx: &MergeQueueSender,
 ) -> anyhow::Result<()> {
     if !sufficient_delegate_permission(repo_state.clone(), author) {
-        deny_request(&repo_state, pr.number(), author, PermissionType::Review).await?;
-        return Ok(());
+        return deny_request(&repo_state, pr, author, PermissionType::Review).await;
+    }
+
+    let queue_branch = match tree_branch(&repo_state, &pr, branch) {
+        Ok(queue_branch) => queue_branch,
+        Err(branch) => return notify_of_unmanaged_tree_branch(&repo_state, pr, &branch).await,
+    };
+    let previous_tree_state = tree_state(&repo_state, &db, queue_branch.as_deref()).await?;
+    match &queue_branch {
+        Some(branch) => {
+            db.upsert_branch_tree_state(repo_state.repository(), branch, TreeState::Open)
+                .await?
+        }
+        None => {
+            db.upsert_repository(repo_state.repository(), TreeState::Open)
+                .await?
+        }
+    }
+    db.record_audit_action(
+        repo_state.repository(),
+        None,
+        "tree_opened",
+        Some(&tree_audit_details(
+            queue_branch.as_deref(),
+            &format!("opened by `{}`", author.username),
+        )),
+    )
+    .await?;
+
+    merge_queue_tx.notify().await?;
+    if repo_state.config.load().tree_closure_notifications
+        && let Some(previous_priority) = previous_tree_state.priority()
+    {
+        let comments = waiting_queue_prs(&repo_state, &db, queue_branch.as_deref(), pr.number())
+            .await?
+            .into_iter()
+            .filter(|waiting| {
+                i64::from(waiting.priority.unwrap_or(0)) < i64::from(previous_priority)
+            })
+            .map(|waiting| {
+                let comment = tree_reopened_unblocked_pr_comment(queue_branch.as_deref());
+                (waiting.number, comment)
+            })
+            .collect();
+        post_comment_batch(&repo_state, &db, comments).await;
+    }
+    notify_of_tree_open(&repo_state, pr, queue_branch.as_deref()).await
+}
+
+/// Returns the approved PRs that wait in the queue of the given branch with its own merge queue
+/// (or in the queues of the repository) for an auto build, except for the given PR.
+async fn waiting_queue_prs(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    queue_branch: Option<&str>,
+    except: PullRequestNumber,
+) -> anyhow::Result<Vec<PullRequestModel>> {
+    let queue_branches = repo.config.load().merge_queue_branches.clone();
+    Ok(db
+        .get_merge_queue_prs(repo.repository(), None, &[])
+        .await?
+        .into_iter()
+        .filter(|pr| pr.number != except && pr.auto_build.is_none())
+        .filter(|pr| match queue_branch {
+            Some(branch) => pr.base_branch == branch,
+            None => !queue_branches.contains(&pr.base_branch),
+        })
+        .collect())
+}
+
+/// Describes a change of the tree state of the repository, or of a branch with its own queue,
+/// for the audit log.
+fn tree_audit_details(queue_branch: Option<&str>, change: &str) -> String {
+    match queue_branch {
+        Some(branch) => format!("tree of branch `{branch}` {change}"),
+        None => format!("tree {change}"),
+    }
+}
+
+/// Pause the merge queue of the repository. Unlike closing the tree, this does not depend on
+/// the priority of PRs: no new auto builds are started at all, but auto builds that are already
+/// running are still merged.
+pub(super) async fn command_pause_queue(
+    repo_state: Arc<RepositoryState>,
+    db: Arc<PgDbClient>,
+    pr: PullRequestData<'_>,
+    author: &GithubUser,
+) -> anyhow::Result<()> {
+    if !sufficient_approve_permission(repo_state.clone(), author) {
+        return deny_request(&repo_state, pr, author, PermissionType::Review).await;
+    }
+    if !repo_state.config.load().merge_queue_enabled {
+        return notify_of_disabled_merge_queue(&repo_state, pr).await;
     }
 
-    db.upsert_repository(repo_state.repository(), TreeState::Open)
+    db.pause_merge_queue(repo_state.repository(), &author.username)
         .await?;
+    reply_to_command(
+        &repo_state,
+        pr,
+        Comment::new(
+            ":pause_button: Merge queue paused. No new auto builds will be started until it is resumed with `queue resume`, but running auto builds will still be merged."
+                .to_string(),
+        ),
+    )
+    .await
+}
+
+/// Resume a paused merge queue of the repository.
+pub(super) async fn command_resume_queue(
+    repo_state: Arc<RepositoryState>,
+    db: Arc<PgDbClient>,
+    pr: PullRequestData<'_>,
+    author: &GithubUser,
+    merge_queue_tx: &MergeQueueSender,
+) -> anyhow::Result<()> {
+    if !sufficient_approve_permission(repo_state.clone(), author) {
+        return deny_request(&repo_state, pr, author, PermissionType::Review).await;
+    }
+    if !repo_state.config.load().merge_queue_enabled {
+        return notify_of_disabled_merge_queue(&repo_state, pr).await;
+    }
+
+    let text = if db.resume_merge_queue(repo_state.repository()).await? {
+        merge_queue_tx.notify().await?;
+        ":arrow_forward: Merge queue resumed."
+    } else {
+        "Merge queue is not paused."
+    };
+    reply_to_command(&repo_state, pr, Comment::new(text.to_string())).await
+}
+
+/// Hold an approved PR at the top of the merge queue. Its auto build is started as usual, but the
+/// PR is not merged (and the queue behind it is blocked) until the hold is released.
+pub(super) async fn command_hold(
+    repo_state: Arc<RepositoryState>,
+    db: Arc<PgDbClient>,
+    pr: PullRequestData<'_>,
+    author: &GithubUser,
+    merge_queue_tx: &MergeQueueSender,
+) -> anyhow::Result<()> {
+    if !sufficient_approve_permission(repo_state.clone(), author) {
+        return deny_request(&repo_state, pr, author, PermissionType::Review).await;
+    }
+    if !repo_state.config.load().merge_queue_enabled {
+        return notify_of_disabled_merge_queue(&repo_state, pr).await;
+    }
+
+    let text = if !pr.db.is_approved() {
+        "Only approved PRs can be held."
+    } else if pr.db.held {
+        "PR is already held."
+    } else {
+        db.set_held(pr.db, true).await?;
+        merge_queue_tx.notify().await?;
+        ":pushpin: PR is held at the top of the merge queue. It will be built, but not merged until the hold is released with `release-hold`."
+    };
+    reply_to_command(&repo_state, pr, Comment::new(text.to_string())).await
+}
+
+/// Release a held PR, so that the merge queue can merge it.
+pub(super) async fn command_release_hold(
+    repo_state: Arc<RepositoryState>,
+    db: Arc<PgDbClient>,
+    pr: PullRequestData<'_>,
+    author: &GithubUser,
+    merge_queue_tx: &MergeQueueSender,
+) -> anyhow::Result<()> {
+    if !sufficient_approve_permission(repo_state.clone(), author) {
+        return deny_request(&repo_state, pr, author, PermissionType::Review).await;
+    }
+    if !repo_state.config.load().merge_queue_enabled {
+        return notify_of_disabled_merge_queue(&repo_state, pr).await;
+    }
+
+    let text = if pr.db.held {
+        db.set_held(pr.db, false).await?;
+        merge_queue_tx.notify().await?;
+        ":arrow_forward: Hold released. The PR can now be merged."
+    } else {
+        "PR is not held."
+    };
+    reply_to_command(&repo_state, pr, Comment::new(text.to_string())).await
+}
+
+/// Park an approved PR. It stays approved, but it is excluded from the merge queue (and its
+/// running auto build is cancelled) until it is unparked.
+pub(super) async fn command_park(
+    repo_state: Arc<RepositoryState>,
+    db: Arc<PgDbClient>,
+    pr: PullRequestData<'_>,
+    author: &GithubUser,
+    reason: Option<String>,
+    merge_queue_tx: &MergeQueueSender,
+) -> anyhow::Result<()> {
+    if !sufficient_approve_permission(repo_state.clone(), author) {
+        return deny_request(&repo_state, pr, author, PermissionType::Review).await;
+    }
+    if !repo_state.config.load().merge_queue_enabled {
+        return notify_of_disabled_merge_queue(&repo_state, pr).await;
+    }
+
+    if !pr.db.is_approved() {
+        let comment = Comment::new("Only approved PRs can be parked.".to_string());
+        return reply_to_command(&repo_state, pr, comment).await;
+    }
+    if pr.db.parked && pr.db.park_reason == reason {
+        return reply_to_command(
+            &repo_state,
+            pr,
+            Comment::new("PR is already parked.".to_string()),
+        )
+        .await;
+    }
 
+    let auto_build_cancel_message =
+        maybe_cancel_auto_build(&repo_state.client, &db, pr.db, AutoBuildCancelReason::Park)
+            .await?;
+    if pr.db.auto_build.is_some() {
+        // The PR is built again once it is unparked
+        db.clear_auto_build(pr.db).await?;
+    }
+    db.set_parked(pr.db, true, reason.as_deref()).await?;
     merge_queue_tx.notify().await?;
-    notify_of_tree_open(&repo_state, pr.number()).await
+
+    let mut text = ":parking: PR is parked. It stays approved, but it will not be tested or merged until it is unparked with `unpark`.".to_string();
+    if let Some(reason) = &reason {
+        text.push_str(&format!("\n\nReason: {reason}"));
+    }
+    if let Some(message) = auto_build_cancel_message {
+        text.push_str(&format!("\n\n{message}"));
+    }
+    reply_to_command(&repo_state, pr, Comment::new(text)).await
+}
+
+/// Unpark a parked PR, so that it enters the merge queue again.
+pub(super) async fn command_unpark(
+    repo_state: Arc<RepositoryState>,
+    db: Arc<PgDbClient>,
+    pr: PullRequestData<'_>,
+    author: &GithubUser,
+    merge_queue_tx: &MergeQueueSender,
+) -> anyhow::Result<()> {
+    if !sufficient_approve_permission(repo_state.clone(), author) {
+        return deny_request(&repo_state, pr, author, PermissionType::Review).await;
+    }
+    if !repo_state.config.load().merge_queue_enabled {
+        return notify_of_disabled_merge_queue(&repo_state, pr).await;
+    }
+
+    let text = if pr.db.parked {
+        db.set_parked(pr.db, false, None).await?;
+        merge_queue_tx.notify().await?;
+        ":arrow_forward: PR unparked. It is back in the merge queue."
+    } else {
+        "PR is not parked."
+    };
+    reply_to_command(&repo_state, pr, Comment::new(text.to_string())).await
+}
+
+/// Set build parameters of a PR, which are passed to its try and auto builds.
+/// Only the parameters and values allowed by the `build_env` config option can be set.
+pub(super) async fn command_set_build_env(
+    repo_state: Arc<RepositoryState>,
+    db: Arc<PgDbClient>,
+    pr: PullRequestData<'_>,
+    author: &GithubUser,
+    vars: Vec<(String, String)>,
+) -> anyhow::Result<()> {
+    if !sufficient_approve_permission(repo_state.clone(), author) {
+        return deny_request(&repo_state, pr, author, PermissionType::Review).await;
+    }
+    let config = repo_state.config.load();
+    let language = config.language;
+    let allowed = config.build_env.clone();
+    if allowed.is_empty() {
+        return reply_to_command(&repo_state, pr, build_env_disabled_comment(language)).await;
+    }
+    if let Some((key, value)) = vars.iter().find(|(key, value)| {
+        !allowed
+            .get(key)
+            .is_some_and(|values| values.contains(value))
+    }) {
+        let comment = build_env_not_allowed_comment(key, value, &allowed, language);
+        return reply_to_command(&repo_state, pr, comment).await;
+    }
+
+    let mut build_env: BTreeMap<String, String> = pr
+        .db
+        .build_env
+        .iter()
+        .filter_map(|var| var.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    build_env.extend(vars);
+    let build_env: Vec<String> = build_env
+        .into_iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect();
+    db.set_build_env(pr.db, &build_env).await?;
+    reply_to_command(&repo_state, pr, build_env_set_comment(&build_env, language)).await
+}
+
+/// Remove all build parameters of a PR.
+pub(super) async fn command_clear_build_env(
+    repo_state: Arc<RepositoryState>,
+    db: Arc<PgDbClient>,
+    pr: PullRequestData<'_>,
+    author: &GithubUser,
+) -> anyhow::Result<()> {
+    if !sufficient_approve_permission(repo_state.clone(), author) {
+        return deny_request(&repo_state, pr, author, PermissionType::Review).await;
+    }
+    let language = repo_state.config.load().language;
+    if pr.db.build_env.is_empty() {
+        return reply_to_command(&repo_state, pr, no_build_env_comment(language)).await;
+    }
+    db.set_build_env(pr.db, &[]).await?;
+    reply_to_command(&repo_state, pr, build_env_cleared_comment(language)).await
+}
+
+/// Returns the tree state that applies to the given PR, i.e. the tree state of its base branch if
+/// it has its own merge queue, or the tree state of the repository otherwise.
+/// Trees whose closure has already expired are considered to be open.
+pub(super) async fn pr_tree_state(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    pr: &PullRequestData<'_>,
+) -> anyhow::Result<TreeState> {
+    tree_state(repo, db, own_queue_branch(repo, pr).as_deref()).await
+}
+
+/// Returns the tree state of the given branch with its own merge queue, or of the repository.
+/// Trees whose closure has already expired are considered to be open.
+async fn tree_state(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    queue_branch: Option<&str>,
+) -> anyhow::Result<TreeState> {
+    let tree_state = match queue_branch {
+        Some(branch) => db.get_branch_tree_state(repo.repository(), branch).await?,
+        None => db
+            .repo_db(repo.repository())
+            .await?
+            .map(|repo| repo.tree_state)
+            .unwrap_or(TreeState::Open),
+    };
+    if tree_state.is_expired() {
+        return Ok(TreeState::Open);
+    }
+    Ok(tree_state)
+}
+
+/// Returns the base branch of the PR if it has its own merge queue, and thus also its own tree
+/// state.
+fn own_queue_branch(repo: &RepositoryState, pr: &PullRequestData<'_>) -> Option<String> {
+    let base_branch = &pr.github.base.name;
+    repo.config
+        .load()
+        .merge_queue_branches
+        .contains(base_branch)
+        .then(|| base_branch.clone())
+}
+
+/// Returns the branch whose tree should be modified by a tree command, or `None` if the tree of
+/// the repository should be modified.
+/// A `branch` requested explicitly has to have its own merge queue, otherwise it is returned as
+/// an error.
+fn tree_branch(
+    repo: &RepositoryState,
+    pr: &PullRequestData<'_>,
+    branch: Option<String>,
+) -> Result<Option<String>, String> {
+    match branch {
+        Some(branch) if repo.config.load().merge_queue_branches.contains(&branch) => {
+            Ok(Some(branch))
+        }
+        Some(branch) => Err(branch),
+        None => Ok(own_queue_branch(repo, pr)),
+    }
 }
 
 fn sufficient_approve_permission(repo: Arc<RepositoryState>, author: &GithubUser) -> bool {
@@ -285,31 +870,55 @@ fn sufficient_delegate_permission(repo: Arc<RepositoryState>, author: &GithubUse
 
 async fn notify_of_tree_closed(
     repo: &RepositoryState,
-    pr_number: PullRequestNumber,
-    priority: u32,
+    pr: PullRequestData<'_>,
+    tree_state: &TreeState,
+    queue_branch: Option<&str>,
 ) -> anyhow::Result<()> {
-    repo.client
-        .post_comment(
-            pr_number,
-            Comment::new(format!(
-                "Tree closed for PRs with priority less than {priority}"
-            )),
-        )
-        .await?;
-    Ok(())
+    let description = describe_closed_tree(tree_state).unwrap_or_default();
+    let text = match queue_branch {
+        Some(branch) => format!("Tree of the `{branch}` branch {description}"),
+        None => format!("Tree {description}"),
+    };
+    reply_to_command(repo, pr, Comment::new(text)).await
 }
 
 async fn notify_of_tree_open(
     repo: &RepositoryState,
-    pr_number: PullRequestNumber,
+    pr: PullRequestData<'_>,
+    queue_branch: Option<&str>,
 ) -> anyhow::Result<()> {
-    repo.client
-        .post_comment(
-            pr_number,
-            Comment::new("Tree is now open for merging".to_string()),
-        )
-        .await?;
-    Ok(())
+    let text = match queue_branch {
+        Some(branch) => format!("Tree of the `{branch}` branch is now open for merging"),
+        None => "Tree is now open for merging".to_string(),
+    };
+    reply_to_command(repo, pr, Comment::new(text)).await
+}
+
+async fn notify_of_unmanaged_tree_branch(
+    repo: &RepositoryState,
+    pr: PullRequestData<'_>,
+    branch: &str,
+) -> anyhow::Result<()> {
+    reply_to_command(
+        repo,
+        pr,
+        Comment::new(format!(
+            ":exclamation: The `{branch}` branch does not have its own merge queue, so its tree cannot be opened or closed separately. Add it to `merge_queue_branches` in the repository configuration first."
+        )),
+    )
+    .await
+}
+
+async fn notify_of_disabled_merge_queue(
+    repo: &RepositoryState,
+    pr: PullRequestData<'_>,
+) -> anyhow::Result<()> {
+    reply_to_command(
+        repo,
+        pr,
+        merge_queue_disabled_comment(repo.config.load().language),
+    )
+    .await
 }
 
 async fn notify_of_unapproval(
@@ -323,10 +932,7 @@ async fn notify_of_unapproval(
         comment.push_str(&format!("\n\n{message}"));
     }
 
-    repo.client
-        .post_comment(pr.number(), Comment::new(comment))
-        .await?;
-    Ok(())
+    reply_to_command(repo, pr, Comment::new(comment)).await
 }
 
 async fn notify_of_approval(
@@ -334,44 +940,75 @@ async fn notify_of_approval(
     repo: &RepositoryState,
     pr: PullRequestData<'_>,
     approver: &str,
+    priority: Option<u32>,
+    missing_approvals: Option<usize>,
+    waiting_on_conflicts: bool,
 ) -> anyhow::Result<()> {
-    repo.client
-        .post_comment(
-            pr.db.number,
-            approved_comment(
-                ctx.get_web_url(),
-                repo.repository(),
-                &pr.github.head.sha,
-                approver,
-            ),
-        )
-        .await?;
-    Ok(())
+    // Let the reviewer know if the PR cannot be merged because of a closed tree
+    let tree_state = pr_tree_state(repo, &ctx.db, &pr).await?;
+    let pr_priority = priority.or(pr.db.priority.map(|p| p as u32));
+    let blocking_tree_state = tree_state
+        .priority()
+        .filter(|threshold| pr_priority.is_none_or(|p| p < *threshold))
+        .map(|_| &tree_state);
+
+    let full_queue_size = match repo.config.load().max_queue_size {
+        Some(max_queue_size) => {
+            let prs = ctx
+                .db
+                .get_nonclosed_pull_requests(repo.repository())
+                .await?;
+            queue_overflow(&prs, Some(max_queue_size))
+                .contains(&pr.number())
+                .then_some(max_queue_size)
+        }
+        None => None,
+    };
+
+    reply_to_command(
+        repo,
+        pr,
+        approved_comment(
+            ctx.get_web_url(),
+            repo.repository(),
+            &pr.github.head.sha,
+            approver,
+            missing_approvals,
+            full_queue_size,
+            blocking_tree_state,
+            waiting_on_conflicts,
+        ),
+    )
+    .await
 }
 
 async fn notify_of_delegation(
     repo: &RepositoryState,
-    pr_number: PullRequestNumber,
+    pr: PullRequestData<'_>,
     delegatee: &str,
     delegator: &str,
     delegated_permission: DelegatedPermission,
+    expires_at: Option<DateTime<Utc>>,
     bot_prefix: &CommandPrefix,
 ) -> anyhow::Result<()> {
     let comment = match delegated_permission {
-        DelegatedPermission::Try => delegate_try_builds_comment(delegatee, bot_prefix),
-        DelegatedPermission::Review => delegate_comment(delegatee, delegator, bot_prefix),
+        DelegatedPermission::Try => delegate_try_builds_comment(delegatee, bot_prefix, expires_at),
+        DelegatedPermission::Review => {
+            delegate_comment(delegatee, delegator, bot_prefix, expires_at)
+        }
     };
 
-    repo.client.post_comment(pr_number, comment).await?;
-    Ok(())
+    reply_to_command(repo, pr, comment).await
 }
 
 #[cfg(test)]
 mod tests {
     use octocrab::params::checks::{CheckRunConclusion, CheckRunStatus};
 
-    use crate::bors::merge_queue::AUTO_BUILD_CHECK_RUN_NAME;
-    use crate::database::{DelegatedPermission, TreeState};
+    use crate::bors::PullRequestStatus;
+    use crate::bors::merge_queue::{AUTO_BRANCH_NAME, AUTO_BUILD_CHECK_RUN_NAME};
+    use crate::database::{DelegatedPermission, MergeableState, OctocrabMergeableState, TreeState};
+    use crate::github::PullRequestNumber;
     use crate::tests::BorsTester;
     use crate::{
         bors::{
@@ -379,9 +1016,11 @@ mod tests {
             handlers::{TRY_BRANCH_NAME, trybuild::TRY_MERGE_BRANCH_NAME},
         },
         tests::{
-            BorsBuilder, Comment, GitHubState, Permissions, User, default_repo_name, run_test,
+            BorsBuilder, Comment, CommentReaction, GitHubState, Permissions, User,
+            default_repo_name, run_test,
         },
     };
+    use chrono::Utc;
 
     #[sqlx::test]
     async fn default_approve(pool: sqlx::PgPool) {
@@ -407,520 +1046,560 @@ mod tests {
     }
 
     #[sqlx::test]
-    async fn approve_on_behalf(pool: sqlx::PgPool) {
+    async fn approve_in_review_thread(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            let approve_user = "user1";
             tester
-                .post_comment(format!(r#"@bors r={approve_user}"#).as_str())
+                .post_comment(Comment::from("@bors r+").in_review_thread(42))
                 .await?;
+            let comment = tester.get_next_comment(()).await?;
+            assert_eq!(comment.review_thread, Some(42));
             insta::assert_snapshot!(
-                tester.get_next_comment_text(()).await?,
+                comment.content,
                 @r"
-            :pushpin: Commit pr-1-sha has been approved by `user1`
+            :pushpin: Commit pr-1-sha has been approved by `default-user`
 
             It is now in the [queue](https://test.com/bors/queue/borstest) for this repository.
             "
             );
-
             tester
                 .get_pr_copy(())
                 .await
-                .expect_approved_by(approve_user);
+                .expect_approved_by(&User::default_pr_author().name);
             Ok(())
         })
         .await;
     }
 
     #[sqlx::test]
-    async fn insufficient_permission_approve(pool: sqlx::PgPool) {
-        run_test(pool, async |tester: &mut BorsTester| {
-            tester
-                .post_comment(Comment::from("@bors try").with_author(User::unprivileged()))
-                .await?;
-            insta::assert_snapshot!(
-                tester.get_next_comment_text(()).await?,
-                @"@unprivileged-user: :key: Insufficient privileges: not in try users"
-            );
-            Ok(())
-        })
-        .await;
-    }
-
-    #[sqlx::test]
-    async fn insufficient_permission_set_priority(pool: sqlx::PgPool) {
+    async fn approve_in_review_body(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
             tester
-                .post_comment(Comment::from("@bors p=2").with_author(User::unprivileged()))
+                .submit_review((), User::reviewer(), "commented", Some("@bors r+"))
                 .await?;
-            tester.post_comment("@bors p=2").await?;
-            insta::assert_snapshot!(
-                tester.get_next_comment_text(()).await?,
-                @"@unprivileged-user: :key: Insufficient privileges: not in review users"
-            );
-            Ok(())
-        })
-        .await;
-    }
-
-    #[sqlx::test]
-    async fn unapprove(pool: sqlx::PgPool) {
-        run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors r+").await?;
             insta::assert_snapshot!(
                 tester.get_next_comment_text(()).await?,
                 @r"
-            :pushpin: Commit pr-1-sha has been approved by `default-user`
+            :pushpin: Commit pr-1-sha has been approved by `reviewer`
 
             It is now in the [queue](https://test.com/bors/queue/borstest) for this repository.
-            ",
+            "
             );
             tester
                 .get_pr_copy(())
                 .await
-                .expect_approved_by(&User::default_pr_author().name);
-            tester.post_comment("@bors r-").await?;
-            insta::assert_snapshot!(
-                tester.get_next_comment_text(()).await?,
-                @"Commit pr-1-sha has been unapproved."
-            );
-
-            tester.get_pr_copy(()).await.expect_unapproved();
+                .expect_approved_by(&User::reviewer().name);
             Ok(())
         })
         .await;
     }
 
     #[sqlx::test]
-    async fn unapprove_lacking_permissions(pool: sqlx::PgPool) {
+    async fn unapprove_in_review_thread(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
             tester.approve(()).await?;
             tester
-                .post_comment(Comment::from("@bors r-").with_author(User::unprivileged()))
+                .post_comment(Comment::from("@bors r-").in_review_thread(42))
                 .await?;
-            insta::assert_snapshot!(
-                tester.get_next_comment_text(()).await?,
-                @"@unprivileged-user: :key: Insufficient privileges: not in review users"
-            );
-
-            tester
-                .get_pr_copy(())
-                .await
-                .expect_approved_by(&User::default_pr_author().name);
+            let comment = tester.get_next_comment(()).await?;
+            assert_eq!(comment.review_thread, Some(42));
             Ok(())
         })
         .await;
     }
 
     #[sqlx::test]
-    async fn unapprove_merged_pr(pool: sqlx::PgPool) {
+    async fn approve_specific_commit(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester.approve(()).await?;
-            tester.set_pr_status_closed(()).await?;
-            tester.post_comment("@bors r-").await?;
+            let sha = "ea9c1b050cc8b420c2c211d2177811e564a4dc60";
+            tester
+                .modify_pr_state((), |pr| pr.head_sha = sha.to_string())
+                .await;
+            tester.post_comment(format!("@bors r+ {sha}").as_str()).await?;
             insta::assert_snapshot!(
                 tester.get_next_comment_text(()).await?,
-                @":clipboard: Only unclosed PRs can be unapproved."
-            );
+                @r"
+            :pushpin: Commit ea9c1b050cc8b420c2c211d2177811e564a4dc60 has been approved by `default-user`
 
-            tester
-                .get_pr_copy(())
-                .await
-                .expect_approved_by(&User::default_pr_author().name);
+            It is now in the [queue](https://test.com/bors/queue/borstest) for this repository.
+            "
+            );
+            tester.get_pr_copy(()).await.expect_approved_sha(sha);
             Ok(())
         })
         .await;
     }
 
     #[sqlx::test]
-    async fn approve_with_priority(pool: sqlx::PgPool) {
+    async fn approve_abbreviated_commit(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors r+ p=10").await?;
-            tester.expect_comments((), 1).await;
-
+            let sha = "ea9c1b050cc8b420c2c211d2177811e564a4dc60";
             tester
-                .get_pr_copy(())
-                .await
-                .expect_priority(Some(10))
-                .expect_approved_by(&User::default_pr_author().name);
-            Ok(())
-        })
-        .await;
-    }
-
-    #[sqlx::test]
-    async fn approve_on_behalf_with_priority(pool: sqlx::PgPool) {
-        run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors r=user1 p=10").await?;
+                .modify_pr_state((), |pr| pr.head_sha = sha.to_string())
+                .await;
+            tester.post_comment("@bors r+ ea9c1b0").await?;
             tester.expect_comments((), 1).await;
-
-            tester
-                .get_pr_copy(())
-                .await
-                .expect_priority(Some(10))
-                .expect_approved_by("user1");
-            Ok(())
-        })
-        .await;
-    }
-
-    #[sqlx::test]
-    async fn set_priority(pool: sqlx::PgPool) {
-        run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors p=5").await?;
-            tester.wait_for_pr((), |pr| pr.priority == Some(5)).await?;
-            Ok(())
-        })
-        .await;
-    }
-
-    #[sqlx::test]
-    async fn priority_preserved_after_approve(pool: sqlx::PgPool) {
-        run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors p=5").await?;
-            tester.wait_for_pr((), |pr| pr.priority == Some(5)).await?;
-
-            tester.approve(()).await?;
-
-            tester.get_pr_copy(()).await.expect_priority(Some(5));
-
+            tester.get_pr_copy(()).await.expect_approved_sha(sha);
             Ok(())
         })
         .await;
     }
 
     #[sqlx::test]
-    async fn priority_overridden_on_approve_with_priority(pool: sqlx::PgPool) {
+    async fn approve_outdated_abbreviated_commit(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors p=5").await?;
-            tester.wait_for_pr((), |pr| pr.priority == Some(5)).await?;
-
-            tester.post_comment("@bors r+ p=10").await?;
-            tester.expect_comments((), 1).await;
-
-            tester.get_pr_copy(()).await.expect_priority(Some(10));
-
+            tester
+                .modify_pr_state((), |pr| {
+                    pr.head_sha = "ea9c1b050cc8b420c2c211d2177811e564a4dc60".to_string()
+                })
+                .await;
+            tester.post_comment("@bors r+ ea9c1b1").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":clipboard: Commit ea9c1b1 is not the head of this PR (the head is ea9c1b050cc8b420c2c211d2177811e564a4dc60), ignoring approval."
+            );
+            tester.get_pr_copy(()).await.expect_unapproved();
             Ok(())
         })
         .await;
     }
 
     #[sqlx::test]
-    async fn tree_closed_with_priority(pool: sqlx::PgPool) {
+    async fn approve_outdated_commit(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors treeclosed=5").await?;
+            tester
+                .post_comment("@bors r+ ea9c1b050cc8b420c2c211d2177811e564a4dc60")
+                .await?;
             insta::assert_snapshot!(
                 tester.get_next_comment_text(()).await?,
-                @"Tree closed for PRs with priority less than 5"
-            );
-
-            let repo = tester.db().repo_db(&default_repo_name()).await?;
-            assert_eq!(
-                repo.unwrap().tree_state,
-                TreeState::Closed {
-                    priority: 5,
-                    source: format!(
-                        "https://github.com/{}/pull/1#issuecomment-1",
-                        default_repo_name()
-                    ),
-                }
+                @":clipboard: Commit ea9c1b050cc8b420c2c211d2177811e564a4dc60 is not the head of this PR (the head is pr-1-sha), ignoring approval."
             );
-
+            tester.get_pr_copy(()).await.expect_unapproved();
             Ok(())
         })
         .await;
     }
 
     #[sqlx::test]
-    async fn insufficient_permission_tree_closed(pool: sqlx::PgPool) {
-        let gh = GitHubState::default();
-        gh.default_repo().lock().permissions = Permissions::empty();
-
+    async fn approve_minimal_markdown_style(pool: sqlx::PgPool) {
         BorsBuilder::new(pool)
-            .github(gh)
+            .github(
+                GitHubState::default().with_default_config(r#"comment_style = "minimal_markdown""#),
+            )
             .run_test(async |tester: &mut BorsTester| {
-                tester.post_comment("@bors treeclosed=5").await?;
+                tester.post_comment("@bors r+").await?;
                 insta::assert_snapshot!(
                     tester.get_next_comment_text(()).await?,
-                    @"@default-user: :key: Insufficient privileges: not in review users"
+                    @r"
+                Commit pr-1-sha has been approved by `default-user`
+
+                It is now in the [queue](https://test.com/bors/queue/borstest) for this repository.
+                "
                 );
                 Ok(())
             })
             .await;
     }
 
-    fn review_comment(text: &str) -> Comment {
-        Comment::from(text).with_author(User::reviewer())
-    }
-
     #[sqlx::test]
-    async fn cannot_approve_without_delegation(pool: sqlx::PgPool) {
+    async fn approve_with_command_alias(pool: sqlx::PgPool) {
         BorsBuilder::new(pool)
-            .github(GitHubState::unauthorized_pr_author())
+            .github(GitHubState::default().with_default_config(
+                r#"
+[command_aliases]
+lgtm = "r+ rollup=never"
+
+[labels]
+approved = ["+approved"]
+"#,
+            ))
             .run_test(async |tester: &mut BorsTester| {
-                tester.post_comment("@bors r+").await?;
-                insta::assert_snapshot!(
-                    tester.get_next_comment_text(()).await?,
-                    @"@default-user: :key: Insufficient privileges: not in review users"
-                );
+                tester.post_comment("@bors lgtm").await?;
+                tester.expect_comments((), 1).await;
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_rollup(Some(RollupMode::Never))
+                    .expect_approved_by(&User::default_pr_author().name);
                 Ok(())
             })
             .await;
     }
 
     #[sqlx::test]
-    async fn delegate_author(pool: sqlx::PgPool) {
+    async fn approve_accessible_comments(pool: sqlx::PgPool) {
         BorsBuilder::new(pool)
-            .github(GitHubState::unauthorized_pr_author())
+            .github(GitHubState::default().with_default_config("accessible_comments = true"))
             .run_test(async |tester: &mut BorsTester| {
-                tester
-                    .post_comment(review_comment("@bors delegate+"))
-                    .await?;
+                tester.post_comment("@bors r+").await?;
                 insta::assert_snapshot!(
                     tester.get_next_comment_text(()).await?,
-                    @r#"
-                :v: @default-user, you can now approve this pull request!
+                    @r"
+                APPROVED: Commit pr-1-sha has been approved by `default-user`
 
-                If @reviewer told you to "`r=me`" after making some further change, then please make that change and post `@bors r=reviewer`.
-                "#
+                It is now in the [queue](https://test.com/bors/queue/borstest) for this repository.
+                "
                 );
-
-                tester
-                    .get_pr_copy(())
-                    .await
-                    .expect_delegated(DelegatedPermission::Review);
                 Ok(())
             })
             .await;
     }
 
     #[sqlx::test]
-    async fn delegatee_can_approve(pool: sqlx::PgPool) {
+    async fn approve_with_review(pool: sqlx::PgPool) {
         BorsBuilder::new(pool)
-            .github(GitHubState::unauthorized_pr_author())
+            .github(GitHubState::default().with_default_config(
+                r#"
+approve_on_review = true
+
+[labels]
+approved = ["+approved"]
+"#,
+            ))
             .run_test(async |tester: &mut BorsTester| {
                 tester
-                    .post_comment(review_comment("@bors delegate+"))
+                    .submit_review((), User::reviewer(), "approved", None)
                     .await?;
-                tester.expect_comments((), 1).await;
-
-                tester.approve(()).await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @r"
+                :pushpin: Commit pr-1-sha has been approved by `reviewer`
 
+                It is now in the [queue](https://test.com/bors/queue/borstest) for this repository.
+                "
+                );
                 tester
                     .get_pr_copy(())
                     .await
-                    .expect_approved_by(&User::default_pr_author().name);
+                    .expect_approved_by(&User::reviewer().name);
                 Ok(())
             })
             .await;
     }
 
     #[sqlx::test]
-    async fn delegatee_can_try(pool: sqlx::PgPool) {
-        let gh = BorsBuilder::new(pool)
-            .github(GitHubState::unauthorized_pr_author())
+    async fn unapprove_with_review(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("approve_on_review = true"))
             .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
                 tester
-                    .post_comment(review_comment("@bors delegate+"))
+                    .submit_review((), User::reviewer(), "changes_requested", None)
                     .await?;
-                tester.expect_comments((), 1).await;
-                tester.post_comment("@bors try").await?;
-                tester.expect_comments((), 1).await;
-
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @"Commit pr-1-sha has been unapproved."
+                );
+                tester.get_pr_copy(()).await.expect_unapproved();
                 Ok(())
             })
             .await;
-        gh.check_sha_history(
-            default_repo_name(),
-            TRY_MERGE_BRANCH_NAME,
-            &["main-sha1", "merge-0-pr-1"],
-        );
-        gh.check_sha_history(default_repo_name(), TRY_BRANCH_NAME, &["merge-0-pr-1"]);
     }
 
     #[sqlx::test]
-    async fn delegatee_can_set_priority(pool: sqlx::PgPool) {
+    async fn approve_with_review_insufficient_permission(pool: sqlx::PgPool) {
         BorsBuilder::new(pool)
-            .github(GitHubState::unauthorized_pr_author())
+            .github(GitHubState::default().with_default_config("approve_on_review = true"))
             .run_test(async |tester: &mut BorsTester| {
                 tester
-                    .post_comment(review_comment("@bors delegate+"))
+                    .submit_review((), User::unprivileged(), "approved", None)
                     .await?;
-                tester.expect_comments((), 1).await;
-
-                tester.post_comment("@bors p=7").await?;
-                tester.wait_for_pr((), |pr| pr.priority == Some(7)).await?;
-
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @"@unprivileged-user: :key: Insufficient privileges: not in review users"
+                );
+                tester.get_pr_copy(()).await.expect_unapproved();
                 Ok(())
             })
             .await;
     }
 
     #[sqlx::test]
-    async fn delegate_insufficient_permission(pool: sqlx::PgPool) {
-        BorsBuilder::new(pool)
-            .github(GitHubState::unauthorized_pr_author())
-            .run_test(async |tester: &mut BorsTester| {
-                tester.post_comment("@bors delegate+").await?;
-                insta::assert_snapshot!(
-                    tester.get_next_comment_text(()).await?,
-                    @"@default-user: :key: Insufficient privileges: not in review users"
-                );
-                Ok(())
-            })
-            .await;
+    async fn approve_with_review_disabled(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .submit_review((), User::reviewer(), "approved", None)
+                .await?;
+            tester.post_comment("@bors ping").await?;
+            assert_eq!(tester.get_next_comment_text(()).await?, "Pong 🏓!");
+            // The review did not act as an approval
+            assert!(
+                tester
+                    .db()
+                    .get_pull_request(&default_repo_name(), PullRequestNumber(1))
+                    .await?
+                    .is_none_or(|pr| !pr.is_approved())
+            );
+            Ok(())
+        })
+        .await;
     }
 
     #[sqlx::test]
-    async fn undelegate_by_reviewer(pool: sqlx::PgPool) {
-        BorsBuilder::new(pool)
-            .github(GitHubState::unauthorized_pr_author())
-            .run_test(async |tester: &mut BorsTester| {
-                tester
-                    .post_comment(review_comment("@bors delegate+"))
-                    .await?;
-                tester.expect_comments((), 1).await;
-                tester
-                    .get_pr_copy(())
-                    .await
-                    .expect_delegated(DelegatedPermission::Review);
+    async fn approve_on_behalf(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let approve_user = "user1";
+            tester
+                .post_comment(format!(r#"@bors r={approve_user}"#).as_str())
+                .await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @r"
+            :pushpin: Commit pr-1-sha has been approved by `user1`
 
-                tester
-                    .post_comment(review_comment("@bors delegate-"))
-                    .await?;
-                tester
-                    .wait_for_pr((), |pr| pr.delegated_permission.is_none())
-                    .await?;
+            It is now in the [queue](https://test.com/bors/queue/borstest) for this repository.
+            "
+            );
 
-                Ok(())
-            })
-            .await;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_approved_by(approve_user);
+            Ok(())
+        })
+        .await;
     }
 
     #[sqlx::test]
-    async fn undelegate_by_delegatee(pool: sqlx::PgPool) {
+    async fn approve_by_multiple_reviewers(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors r=user1").await?;
+            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors r=user2,user1").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @r"
+            :pushpin: Commit pr-1-sha has been approved by `user1,user2`
+
+            It is now in the [queue](https://test.com/bors/queue/borstest) for this repository.
+            "
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_after_unapproval_replaces_approvers(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors r=user1").await?;
+            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors r-").await?;
+            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors r=user2").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @r"
+            :pushpin: Commit pr-1-sha has been approved by `user2`
+
+            It is now in the [queue](https://test.com/bors/queue/borstest) for this repository.
+            "
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_with_full_queue(pool: sqlx::PgPool) {
         BorsBuilder::new(pool)
-            .github(GitHubState::unauthorized_pr_author())
+            .github(GitHubState::default().with_default_config(
+                r#"
+max_queue_size = 1
+
+[labels]
+approved = ["+approved"]
+"#,
+            ))
             .run_test(async |tester: &mut BorsTester| {
-                tester
-                    .post_comment(review_comment("@bors delegate+"))
-                    .await?;
-                tester.expect_comments((), 1).await;
+                tester.approve(()).await?;
 
-                tester.post_comment("@bors delegate-").await?;
+                let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
                 tester
-                    .wait_for_pr((), |pr| pr.delegated_permission.is_none())
+                    .post_comment(Comment::new(pr2.id(), "@bors r+"))
                     .await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(pr2.id()).await?,
+                    @r"
+                :pushpin: Commit pr-2-sha has been approved by `default-user`
 
+                :hourglass: The [queue](https://test.com/bors/queue/borstest) for this repository is full (it can contain at most 1 PR), so this PR is waiting for queue space.
+                "
+                );
+                tester
+                    .get_pr_copy(pr2.id())
+                    .await
+                    .expect_approved_by(&User::default_pr_author().name);
                 Ok(())
             })
             .await;
     }
 
     #[sqlx::test]
-    async fn reviewer_unapprove_delegated_approval(pool: sqlx::PgPool) {
+    async fn approve_with_missing_approvals(pool: sqlx::PgPool) {
         BorsBuilder::new(pool)
-            .github(GitHubState::unauthorized_pr_author())
+            .github(GitHubState::default().with_default_config(
+                "merge_queue_enabled = true\nrequired_approvals = 2",
+            ))
             .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors r+").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                    :pushpin: Commit pr-1-sha has been approved by `default-user`
+
+                    :busts_in_silhouette: It needs 1 more approval from other reviewers before it enters the [queue](https://test.com/bors/queue/borstest) for this repository.
+                    ");
+                tester.process_merge_queue().await;
+                tester.get_pr_copy(()).await.expect_no_auto_build();
+
+                // Approving again does not add another approval
+                tester.post_comment("@bors r+").await?;
+                tester.expect_comments((), 1).await;
+                tester.process_merge_queue().await;
+                tester.get_pr_copy(()).await.expect_no_auto_build();
+
                 tester
-                    .post_comment(review_comment("@bors delegate+"))
+                    .post_comment(Comment::from("@bors r+").with_author(User::reviewer()))
                     .await?;
-                tester.expect_comments((), 1).await;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                    :pushpin: Commit pr-1-sha has been approved by `default-user,reviewer`
 
-                tester.approve(()).await?;
+                    It is now in the [queue](https://test.com/bors/queue/borstest) for this repository.
+                    ");
+                tester.process_merge_queue().await;
                 tester
                     .get_pr_copy(())
                     .await
-                    .expect_approved_by(&User::default_pr_author().name);
-
-                tester.post_comment(review_comment("@bors r-")).await?;
+                    .expect_approvers(&["default-user", "reviewer"])
+                    .expect_auto_build(|_| true);
                 tester.expect_comments((), 1).await;
-
-                tester.get_pr_copy(()).await.expect_unapproved();
-
                 Ok(())
             })
             .await;
     }
 
     #[sqlx::test]
-    async fn non_author_cannot_use_delegation(pool: sqlx::PgPool) {
+    async fn approve_multiple_approvers_at_once(pool: sqlx::PgPool) {
         BorsBuilder::new(pool)
-            .github(GitHubState::unauthorized_pr_author())
+            .github(GitHubState::default().with_default_config(
+                "merge_queue_enabled = true\nrequired_approvals = 2",
+            ))
             .run_test(async |tester: &mut BorsTester| {
-                tester
-                    .post_comment(review_comment("@bors delegate+"))
-                    .await?;
-                tester.expect_comments((), 1).await;
+                // Only the author of the comment approved the PR, the named users did not
+                tester.post_comment("@bors r=user1,user2").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                    :pushpin: Commit pr-1-sha has been approved by `user1,user2`
 
+                    :busts_in_silhouette: It needs 1 more approval from other reviewers before it enters the [queue](https://test.com/bors/queue/borstest) for this repository.
+                    ");
+                tester.process_merge_queue().await;
                 tester
-                    .post_comment(Comment::from("@bors r+").with_author(User::unprivileged()))
-                    .await?;
-                tester.expect_comments((), 1).await;
-
-                tester.get_pr_copy(()).await.expect_unapproved();
-
+                    .get_pr_copy(())
+                    .await
+                    .expect_approvers(&["user1", "user2"])
+                    .expect_no_auto_build();
                 Ok(())
             })
             .await;
     }
 
     #[sqlx::test]
-    async fn delegate_insufficient_permission_try_user(pool: sqlx::PgPool) {
+    async fn unapprove_clears_partial_approvals(pool: sqlx::PgPool) {
         BorsBuilder::new(pool)
+            .github(
+                GitHubState::default()
+                    .with_default_config("merge_queue_enabled = true\nrequired_approvals = 2"),
+            )
             .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors r=user1").await?;
+                tester.expect_comments((), 1).await;
+                tester.post_comment("@bors r-").await?;
+                tester.expect_comments((), 1).await;
+                tester.post_comment("@bors r=user2").await?;
+                tester.expect_comments((), 1).await;
+                tester.process_merge_queue().await;
                 tester
-                    .post_comment(Comment::from("@bors delegate+").with_author(User::try_user()))
-                    .await?;
-                insta::assert_snapshot!(
-                    tester.get_next_comment_text(()).await?,
-                    @"@user-with-try-privileges: :key: Insufficient privileges: not in review users"
-                );
+                    .get_pr_copy(())
+                    .await
+                    .expect_approvers(&["user2"])
+                    .expect_no_auto_build();
                 Ok(())
             })
             .await;
     }
 
     #[sqlx::test]
-    async fn approve_with_rollup_value(pool: sqlx::PgPool) {
+    async fn insufficient_permission_approve(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors r+ rollup=never").await?;
-            tester.expect_comments((), 1).await;
+            tester
+                .post_comment(Comment::from("@bors try").with_author(User::unprivileged()))
+                .await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @"@unprivileged-user: :key: Insufficient privileges: not in try users"
+            );
+            Ok(())
+        })
+        .await;
+    }
 
+    #[sqlx::test]
+    async fn insufficient_permission_set_priority(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
             tester
-                .get_pr_copy(())
-                .await
-                .expect_rollup(Some(RollupMode::Never))
-                .expect_approved_by(&User::default_pr_author().name);
+                .post_comment(Comment::from("@bors p=2").with_author(User::unprivileged()))
+                .await?;
+            tester.post_comment("@bors p=2").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @"@unprivileged-user: :key: Insufficient privileges: not in review users"
+            );
             Ok(())
         })
         .await;
     }
 
     #[sqlx::test]
-    async fn approve_with_rollup_bare(pool: sqlx::PgPool) {
+    async fn unapprove(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors r+ rollup").await?;
-            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors r+").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @r"
+            :pushpin: Commit pr-1-sha has been approved by `default-user`
 
+            It is now in the [queue](https://test.com/bors/queue/borstest) for this repository.
+            ",
+            );
             tester
                 .get_pr_copy(())
                 .await
-                .expect_rollup(Some(RollupMode::Always))
                 .expect_approved_by(&User::default_pr_author().name);
+            tester.post_comment("@bors r-").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @"Commit pr-1-sha has been unapproved."
+            );
+
+            tester.get_pr_copy(()).await.expect_unapproved();
             Ok(())
         })
         .await;
     }
 
     #[sqlx::test]
-    async fn approve_with_rollup_bare_maybe(pool: sqlx::PgPool) {
+    async fn unapprove_lacking_permissions(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors r+ rollup-").await?;
-            tester.expect_comments((), 1).await;
+            tester.approve(()).await?;
+            tester
+                .post_comment(Comment::from("@bors r-").with_author(User::unprivileged()))
+                .await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @"@unprivileged-user: :key: Insufficient privileges: not in review users"
+            );
+
             tester
                 .get_pr_copy(())
                 .await
-                .expect_rollup(Some(RollupMode::Maybe))
                 .expect_approved_by(&User::default_pr_author().name);
             Ok(())
         })
@@ -928,16 +1607,19 @@ mod tests {
     }
 
     #[sqlx::test]
-    async fn approve_with_priority_rollup(pool: sqlx::PgPool) {
+    async fn unapprove_merged_pr(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors r+ p=10 rollup=never").await?;
-            tester.expect_comments((), 1).await;
+            tester.approve(()).await?;
+            tester.set_pr_status_closed(()).await?;
+            tester.post_comment("@bors r-").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":clipboard: Only unclosed PRs can be unapproved."
+            );
 
             tester
                 .get_pr_copy(())
                 .await
-                .expect_priority(Some(10))
-                .expect_rollup(Some(RollupMode::Never))
                 .expect_approved_by(&User::default_pr_author().name);
             Ok(())
         })
@@ -945,29 +1627,31 @@ mod tests {
     }
 
     #[sqlx::test]
-    async fn approve_on_behalf_with_rollup_bare(pool: sqlx::PgPool) {
+    async fn approve_with_priority(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors r=user1 rollup").await?;
+            tester.post_comment("@bors r+ p=10").await?;
             tester.expect_comments((), 1).await;
+
             tester
                 .get_pr_copy(())
                 .await
-                .expect_rollup(Some(RollupMode::Always))
-                .expect_approved_by("user1");
+                .expect_priority(Some(10))
+                .expect_approved_by(&User::default_pr_author().name);
             Ok(())
         })
         .await;
     }
 
     #[sqlx::test]
-    async fn approve_on_behalf_with_rollup_value(pool: sqlx::PgPool) {
+    async fn approve_on_behalf_with_priority(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors r=user1 rollup=always").await?;
+            tester.post_comment("@bors r=user1 p=10").await?;
             tester.expect_comments((), 1).await;
+
             tester
                 .get_pr_copy(())
                 .await
-                .expect_rollup(Some(RollupMode::Always))
+                .expect_priority(Some(10))
                 .expect_approved_by("user1");
             Ok(())
         })
@@ -975,62 +1659,68 @@ mod tests {
     }
 
     #[sqlx::test]
-    async fn approve_on_behalf_with_priority_rollup_value(pool: sqlx::PgPool) {
+    async fn set_priority(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester
-                .post_comment("@bors r=user1 rollup=always priority=10")
-                .await?;
-            tester.expect_comments((), 1).await;
-            tester
-                .get_pr_copy(())
-                .await
-                .expect_priority(Some(10))
-                .expect_rollup(Some(RollupMode::Always))
-                .expect_approved_by("user1");
+            tester.post_comment("@bors p=5").await?;
+            tester.wait_for_pr((), |pr| pr.priority == Some(5)).await?;
             Ok(())
         })
         .await;
     }
 
     #[sqlx::test]
-    async fn set_rollup_default(pool: sqlx::PgPool) {
+    async fn priority_preserved_after_approve(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors rollup").await?;
-            tester
-                .wait_for_pr((), |pr| pr.rollup == Some(RollupMode::Always))
-                .await?;
+            tester.post_comment("@bors p=5").await?;
+            tester.wait_for_pr((), |pr| pr.priority == Some(5)).await?;
+
+            tester.approve(()).await?;
+
+            tester.get_pr_copy(()).await.expect_priority(Some(5));
+
             Ok(())
         })
         .await;
     }
 
     #[sqlx::test]
-    async fn set_rollup_with_value(pool: sqlx::PgPool) {
+    async fn priority_overridden_on_approve_with_priority(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors rollup=maybe").await?;
-            tester
-                .wait_for_pr((), |pr| pr.rollup == Some(RollupMode::Maybe))
-                .await?;
+            tester.post_comment("@bors p=5").await?;
+            tester.wait_for_pr((), |pr| pr.priority == Some(5)).await?;
+
+            tester.post_comment("@bors r+ p=10").await?;
+            tester.expect_comments((), 1).await;
+
+            tester.get_pr_copy(()).await.expect_priority(Some(10));
+
             Ok(())
         })
         .await;
     }
 
     #[sqlx::test]
-    async fn rollup_preserved_after_approve(pool: sqlx::PgPool) {
+    async fn tree_closed_with_priority(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors rollup").await?;
-            tester
-                .wait_for_pr((), |pr| pr.rollup == Some(RollupMode::Always))
-                .await?;
-
-            tester.approve(()).await?;
+            tester.post_comment("@bors treeclosed=5").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @"Tree closed for PRs with priority less than 5"
+            );
 
-            tester
-                .get_pr_copy(())
-                .await
-                .expect_rollup(Some(RollupMode::Always))
-                .expect_approved_by(&User::default_pr_author().name);
+            let repo = tester.db().repo_db(&default_repo_name()).await?;
+            assert_eq!(
+                repo.unwrap().tree_state,
+                TreeState::Closed {
+                    priority: 5,
+                    source: format!(
+                        "https://github.com/{}/pull/1#issuecomment-1",
+                        default_repo_name()
+                    ),
+                    reason: None,
+                    until: None,
+                }
+            );
 
             Ok(())
         })
@@ -1038,21 +1728,25 @@ mod tests {
     }
 
     #[sqlx::test]
-    async fn rollup_overridden_on_approve_with_rollup(pool: sqlx::PgPool) {
+    async fn tree_closed_with_reason_and_duration(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors rollup=never").await?;
             tester
-                .wait_for_pr((), |pr| pr.rollup == Some(RollupMode::Never))
+                .post_comment(r#"@bors treeclosed=100 for=2h reason="CI outage""#)
                 .await?;
-
-            tester.post_comment("@bors r+ rollup").await?;
-            tester.expect_comments((), 1).await;
-
-            tester
-                .get_pr_copy(())
-                .await
-                .expect_rollup(Some(RollupMode::Always))
-                .expect_approved_by(&User::default_pr_author().name);
+            let comment = tester.get_next_comment_text(()).await?;
+            assert!(comment.starts_with("Tree closed for PRs with priority less than 100 until "));
+            assert!(comment.ends_with(" UTC (reason: CI outage)"));
+
+            let tree_state = tester
+                .db()
+                .repo_db(&default_repo_name())
+                .await?
+                .unwrap()
+                .tree_state;
+            assert_eq!(tree_state.reason(), Some("CI outage"));
+            let until = tree_state.closed_until().unwrap();
+            assert!(until > Utc::now() + chrono::Duration::minutes(119));
+            assert!(until <= Utc::now() + chrono::Duration::hours(2));
 
             Ok(())
         })
@@ -1060,148 +1754,1205 @@ mod tests {
     }
 
     #[sqlx::test]
-    async fn approve_store_sha(pool: sqlx::PgPool) {
+    async fn approve_with_closed_tree(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            let pr = tester.get_pr_copy(()).await.get_gh_pr();
-            tester.approve(()).await?;
-
             tester
-                .get_pr_copy(())
-                .await
-                .expect_approved_sha(&pr.head_sha);
+                .post_comment(r#"@bors treeclosed=100 reason="CI outage""#)
+                .await?;
+            tester.expect_comments((), 1).await;
+
+            tester.post_comment("@bors r+").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @r"
+            :pushpin: Commit pr-1-sha has been approved by `default-user`
+
+            It is now in the [queue](https://test.com/bors/queue/borstest) for this repository.
+
+            :no_entry: The tree is currently closed for PRs with priority less than 100 (reason: CI outage), so this PR will not be merged until the tree is reopened.
+            "
+            );
+
+            // A PR with a high enough priority is not blocked
+            tester.post_comment("@bors r+ p=100").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @r"
+            :pushpin: Commit pr-1-sha has been approved by `default-user`
 
+            It is now in the [queue](https://test.com/bors/queue/borstest) for this repository.
+            "
+            );
             Ok(())
         })
         .await;
     }
 
     #[sqlx::test]
-    async fn reapproved_pr_uses_latest_sha(pool: sqlx::PgPool) {
-        run_test(pool, async |tester: &mut BorsTester| {
-            let pr = tester.get_pr_copy(()).await.get_gh_pr();
-            tester.approve(()).await?;
+    async fn tree_closed_branch_queue(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+merge_queue_branches = ["beta"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                let beta = tester.create_branch("beta").await;
+                let pr = tester
+                    .open_pr(default_repo_name(), |pr| pr.base_branch = beta)
+                    .await?;
+                tester
+                    .post_comment(Comment::new(pr.id(), "@bors treeclosed=5"))
+                    .await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(pr.id()).await?,
+                    @"Tree of the `beta` branch closed for PRs with priority less than 5"
+                );
 
-            tester
-                .get_pr_copy(())
-                .await
-                .expect_approved_sha(&pr.head_sha);
+                let tree_state = tester
+                    .db()
+                    .get_branch_tree_state(&default_repo_name(), "beta")
+                    .await?;
+                assert!(tree_state.is_closed());
+                let repo = tester.db().repo_db(&default_repo_name()).await?;
+                assert_eq!(repo.unwrap().tree_state, TreeState::Open);
 
-            tester.push_to_pr(()).await?;
-            let pr2 = tester.get_pr_copy(()).await.get_gh_pr();
-            assert_ne!(pr.head_sha, pr2.head_sha);
+                tester
+                    .post_comment(Comment::new(pr.id(), "@bors treeopen"))
+                    .await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(pr.id()).await?,
+                    @"Tree of the `beta` branch is now open for merging"
+                );
+                let tree_state = tester
+                    .db()
+                    .get_branch_tree_state(&default_repo_name(), "beta")
+                    .await?;
+                assert_eq!(tree_state, TreeState::Open);
+                Ok(())
+            })
+            .await;
+    }
 
-            tester.expect_comments((), 1).await;
+    #[sqlx::test]
+    async fn tree_closed_explicit_branch(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+merge_queue_branches = ["beta"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.create_branch("beta").await;
+                tester
+                    .post_comment("@bors treeclosed=100 branch=beta")
+                    .await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @"Tree of the `beta` branch closed for PRs with priority less than 100"
+                );
+                let tree_state = tester
+                    .db()
+                    .get_branch_tree_state(&default_repo_name(), "beta")
+                    .await?;
+                assert!(tree_state.is_closed());
+                let repo = tester.db().repo_db(&default_repo_name()).await?;
+                assert_eq!(repo.unwrap().tree_state, TreeState::Open);
 
-            tester.approve(()).await?;
+                tester.post_comment("@bors treeopen branch=beta").await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @"Tree of the `beta` branch is now open for merging"
+                );
+                let tree_state = tester
+                    .db()
+                    .get_branch_tree_state(&default_repo_name(), "beta")
+                    .await?;
+                assert_eq!(tree_state, TreeState::Open);
+                Ok(())
+            })
+            .await;
+    }
 
+    #[sqlx::test]
+    async fn tree_closed_unmanaged_branch(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
             tester
-                .get_pr_copy(())
-                .await
-                .expect_approved_sha(&pr2.head_sha);
-
+                .post_comment("@bors treeclosed=100 branch=beta")
+                .await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":exclamation: The `beta` branch does not have its own merge queue, so its tree cannot be opened or closed separately. Add it to `merge_queue_branches` in the repository configuration first."
+            );
+            let repo = tester.db().repo_db(&default_repo_name()).await?;
+            assert_eq!(repo.unwrap().tree_state, TreeState::Open);
             Ok(())
         })
         .await;
     }
 
     #[sqlx::test]
-    async fn delegate_try(pool: sqlx::PgPool) {
+    async fn insufficient_permission_tree_closed(pool: sqlx::PgPool) {
+        let gh = GitHubState::default();
+        gh.default_repo().lock().permissions = Permissions::empty();
+
         BorsBuilder::new(pool)
-            .github(GitHubState::unauthorized_pr_author())
+            .github(gh)
             .run_test(async |tester: &mut BorsTester| {
-                tester
-                    .post_comment(review_comment("@bors delegate=try"))
-                    .await?;
+                tester.post_comment("@bors treeclosed=5").await?;
                 insta::assert_snapshot!(
                     tester.get_next_comment_text(()).await?,
-                    @r"
-                :v: @default-user, you can now perform try builds on this pull request!
-
-                You can now post `@bors try` to start a try build.
-                "
+                    @"@default-user: :key: Insufficient privileges: not in review users"
                 );
-                tester
-                    .get_pr_copy(())
-                    .await
-                    .expect_delegated(DelegatedPermission::Try);
                 Ok(())
             })
             .await;
     }
 
     #[sqlx::test]
-    async fn delegated_try_can_build(pool: sqlx::PgPool) {
+    async fn tree_closure_notifies_blocked_prs(pool: sqlx::PgPool) {
         BorsBuilder::new(pool)
-            .github(GitHubState::unauthorized_pr_author())
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+tree_closure_notifications = true
+"#,
+            ))
             .run_test(async |tester: &mut BorsTester| {
+                let low = tester.open_pr(default_repo_name(), |_| {}).await?;
+                let high = tester.open_pr(default_repo_name(), |_| {}).await?;
                 tester
-                    .post_comment(review_comment("@bors delegate=try"))
+                    .post_comment(Comment::new(low.id(), "@bors r+ p=5"))
                     .await?;
-                tester.expect_comments((), 1).await;
+                tester.expect_comments(low.id(), 1).await;
+                tester
+                    .post_comment(Comment::new(high.id(), "@bors r+ p=50"))
+                    .await?;
+                tester.expect_comments(high.id(), 1).await;
 
-                tester.post_comment("@bors try").await?;
-                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
-                :hourglass: Trying commit pr-1-sha with merge merge-0-pr-1…
+                tester.post_comment("@bors treeclosed=10").await?;
+                tester.expect_comments((), 1).await;
+                insta::assert_snapshot!(tester.get_next_comment_text(low.id()).await?, @r":lock: This PR stays approved, but it will not be tested or merged for now, because the tree is closed for PRs with priority less than 10. The tree was closed in https://github.com/rust-lang/borstest/pull/1#issuecomment-1.");
 
-                To cancel the try build, run the command `@bors try cancel`.
-                ");
+                // PRs that are already blocked are not notified again
+                tester.post_comment("@bors treeclosed=20").await?;
+                tester.expect_comments((), 1).await;
 
+                tester.post_comment("@bors treeopen").await?;
+                tester.expect_comments((), 1).await;
+                insta::assert_snapshot!(tester.get_next_comment_text(low.id()).await?, @r":unlock: The tree is open again, so this PR is back in the merge queue.");
                 Ok(())
             })
             .await;
     }
 
     #[sqlx::test]
-    async fn delegated_try_can_not_approve(pool: sqlx::PgPool) {
+    async fn tree_closure_notifications_are_rate_limited_across_batches(pool: sqlx::PgPool) {
         BorsBuilder::new(pool)
-            .github(GitHubState::unauthorized_pr_author())
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+tree_closure_notifications = true
+notifications_per_minute = 1
+"#,
+            ))
             .run_test(async |tester: &mut BorsTester| {
+                let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
                 tester
-                    .post_comment(review_comment("@bors delegate=try"))
+                    .post_comment(Comment::new(pr2.id(), "@bors r+"))
                     .await?;
+                tester.expect_comments(pr2.id(), 1).await;
+
+                tester.post_comment("@bors treeclosed=10").await?;
                 tester.expect_comments((), 1).await;
-                tester
-                    .get_pr_copy(())
-                    .await
-                    .expect_delegated(DelegatedPermission::Try);
+                tester.expect_comments(pr2.id(), 1).await;
 
-                tester.post_comment("@bors r+").await?;
-                insta::assert_snapshot!(
-                    tester.get_next_comment_text(()).await?,
-                    @"@default-user: :key: Insufficient privileges: not in review users"
+                // The notification slot of this minute was used by the previous batch
+                tester.post_comment("@bors treeopen").await?;
+                tester.expect_comments((), 1).await;
+                assert!(
+                    tester
+                        .db()
+                        .get_due_outbox_actions(&default_repo_name())
+                        .await?
+                        .is_empty()
                 );
-                tester.get_pr_copy(()).await.expect_unapproved();
-
                 Ok(())
             })
             .await;
     }
 
     #[sqlx::test]
-    async fn multiple_commands_in_one_comment(pool: sqlx::PgPool) {
+    async fn queue_pause_stops_new_auto_builds(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester
-                .post_comment(
-                    r#"
-@bors r+ rollup=never
-@bors p=10
-"#,
-                )
-                .await?;
-            tester.expect_comments((), 1).await;
+            tester.approve(()).await?;
+            tester.post_comment("@bors queue pause").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":pause_button: Merge queue paused. No new auto builds will be started until it is resumed with `queue resume`, but running auto builds will still be merged."
+            );
+            tester.process_merge_queue().await;
+            tester.get_pr_copy(()).await.expect_no_auto_build();
 
-            tester
-                .wait_for_pr((), |pr| {
-                    pr.rollup == Some(RollupMode::Never) && pr.priority == Some(10)
-                })
-                .await?;
+            tester.post_comment("@bors queue resume").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":arrow_forward: Merge queue resumed."
+            );
+            tester.start_auto_build(()).await?;
             Ok(())
         })
         .await;
     }
 
     #[sqlx::test]
-    async fn approve_draft_pr(pool: sqlx::PgPool) {
+    async fn queue_pause_merges_running_auto_build(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester.post_comment("@bors queue pause").await?;
+            tester.expect_comments((), 1).await;
+            tester
+                .workflow_full_success(tester.auto_branch().await)
+                .await?;
+            tester.process_merge_queue().await;
+            tester.expect_comments((), 1).await;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_status(PullRequestStatus::Merged);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn queue_resume_not_paused(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors queue resume").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @"Merge queue is not paused."
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn insufficient_permission_queue_pause(pool: sqlx::PgPool) {
+        let gh = GitHubState::default();
+        gh.default_repo().lock().permissions = Permissions::empty();
+
+        BorsBuilder::new(pool)
+            .github(gh)
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors queue pause").await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @"@default-user: :key: Insufficient privileges: not in review users"
+                );
+                assert!(
+                    tester
+                        .db()
+                        .get_merge_queue_pause(&default_repo_name())
+                        .await?
+                        .is_none()
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn hold_builds_but_does_not_merge(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.post_comment("@bors hold").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":pushpin: PR is held at the top of the merge queue. It will be built, but not merged until the hold is released with `release-hold`."
+            );
+            tester.start_auto_build(()).await?;
+            tester
+                .workflow_full_success(tester.auto_branch().await)
+                .await?;
+            tester.process_merge_queue().await;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_status(PullRequestStatus::Open);
+
+            tester.post_comment("@bors release-hold").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":arrow_forward: Hold released. The PR can now be merged."
+            );
+            tester.process_merge_queue().await;
+            tester.expect_comments((), 1).await;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_status(PullRequestStatus::Merged);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn hold_already_held(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.post_comment("@bors hold").await?;
+            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors hold").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @"PR is already held."
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn park_excludes_pr_from_queue(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester
+                .post_comment(r#"@bors park reason="waiting for the release""#)
+                .await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                :parking: PR is parked. It stays approved, but it will not be tested or merged until it is unparked with `unpark`.
+
+                Reason: waiting for the release
+                ");
+            tester.process_merge_queue().await;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_approvers(&["default-user"])
+                .expect_no_auto_build();
+
+            tester.post_comment("@bors unpark").await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":arrow_forward: PR unparked. It is back in the merge queue.");
+            tester.process_merge_queue().await;
+            tester.expect_comments((), 1).await;
+            tester.get_pr_copy(()).await.expect_auto_build(|_| true);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn park_cancels_running_auto_build(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester.post_comment("@bors park").await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                :parking: PR is parked. It stays approved, but it will not be tested or merged until it is unparked with `unpark`.
+
+                Auto build cancelled due to the PR being parked. Cancelled workflows:
+                ");
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_approvers(&["default-user"])
+                .expect_no_auto_build();
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn park_already_parked(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.post_comment("@bors park").await?;
+            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors park").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @"PR is already parked."
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn park_unapproved_pr(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors park").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @"Only approved PRs can be parked."
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn unpark_not_parked(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors unpark").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @"PR is not parked."
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn hold_with_merge_queue_disabled(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("merge_queue_enabled = false"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors hold").await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @":no_entry_sign: This repository has the merge queue disabled."
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn hold_unapproved_pr(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors hold").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @"Only approved PRs can be held."
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn release_hold_not_held(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors release-hold").await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @"PR is not held."
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn insufficient_permission_hold(pool: sqlx::PgPool) {
+        let gh = GitHubState::default();
+        gh.default_repo().lock().permissions = Permissions::empty();
+
+        BorsBuilder::new(pool)
+            .github(gh)
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors hold").await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @"@default-user: :key: Insufficient privileges: not in review users"
+                );
+                assert!(
+                    !tester
+                        .db()
+                        .get_pull_request(&default_repo_name(), PullRequestNumber(1))
+                        .await?
+                        .unwrap()
+                        .held
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    fn review_comment(text: &str) -> Comment {
+        Comment::from(text).with_author(User::reviewer())
+    }
+
+    #[sqlx::test]
+    async fn cannot_approve_without_delegation(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::unauthorized_pr_author())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors r+").await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @"@default-user: :key: Insufficient privileges: not in review users"
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_as_reviewer_from_config(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::unauthorized_pr_author().with_default_config(
+                r#"
+[permissions]
+reviewers = ["Default-User"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_approvers(&[&User::default_pr_author().name]);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_as_team_member_from_config(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(
+                GitHubState::unauthorized_pr_author()
+                    .with_team("rust-lang", "reviewers", &[User::default_pr_author()])
+                    .with_default_config(
+                        r#"
+[permissions]
+reviewers = ["@rust-lang/reviewers", "@rust-lang/missing-team", "missing-user"]
+"#,
+                    ),
+            )
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_approvers(&[&User::default_pr_author().name]);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn cannot_approve_as_try_user_from_config(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::unauthorized_pr_author().with_default_config(
+                r#"
+[permissions]
+try_users = ["default-user"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors r+").await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @"@default-user: :key: Insufficient privileges: not in review users"
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn delegate_author(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::unauthorized_pr_author())
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(review_comment("@bors delegate+"))
+                    .await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @r#"
+                :v: @default-user, you can now approve this pull request!
+
+                If @reviewer told you to "`r=me`" after making some further change, then please make that change and post `@bors r=reviewer`.
+                "#
+                );
+
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_delegated(DelegatedPermission::Review);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn delegatee_can_approve(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::unauthorized_pr_author())
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(review_comment("@bors delegate+"))
+                    .await?;
+                tester.expect_comments((), 1).await;
+
+                tester.approve(()).await?;
+
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_approved_by(&User::default_pr_author().name);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn delegatee_can_try(pool: sqlx::PgPool) {
+        let gh = BorsBuilder::new(pool)
+            .github(GitHubState::unauthorized_pr_author())
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(review_comment("@bors delegate+"))
+                    .await?;
+                tester.expect_comments((), 1).await;
+                tester.post_comment("@bors try").await?;
+                tester.expect_comments((), 1).await;
+
+                Ok(())
+            })
+            .await;
+        gh.check_sha_history(
+            default_repo_name(),
+            TRY_MERGE_BRANCH_NAME,
+            &["main-sha1", "merge-0-pr-1"],
+        );
+        gh.check_sha_history(default_repo_name(), TRY_BRANCH_NAME, &["merge-0-pr-1"]);
+    }
+
+    #[sqlx::test]
+    async fn delegatee_can_set_priority(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::unauthorized_pr_author())
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(review_comment("@bors delegate+"))
+                    .await?;
+                tester.expect_comments((), 1).await;
+
+                tester.post_comment("@bors p=7").await?;
+                tester.wait_for_pr((), |pr| pr.priority == Some(7)).await?;
+
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn delegate_insufficient_permission(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::unauthorized_pr_author())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors delegate+").await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @"@default-user: :key: Insufficient privileges: not in review users"
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn delegate_with_expiry(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::unauthorized_pr_author())
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(review_comment("@bors delegate+ for=7d"))
+                    .await?;
+                let comment = tester.get_next_comment_text(()).await?;
+                assert!(comment.contains(":hourglass: The delegation expires at "));
+
+                let pr = tester
+                    .db()
+                    .get_pull_request(&default_repo_name(), PullRequestNumber(1))
+                    .await?
+                    .unwrap();
+                assert_eq!(pr.active_delegation(), Some(DelegatedPermission::Review));
+                assert!(pr.delegation_expires_at.unwrap() > Utc::now() + chrono::Duration::days(6));
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn expired_delegation_is_revoked(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::unauthorized_pr_author())
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(review_comment("@bors delegate+ for=1h"))
+                    .await?;
+                tester.expect_comments((), 1).await;
+
+                // Let the delegation expire
+                let pr = tester
+                    .db()
+                    .get_pull_request(&default_repo_name(), PullRequestNumber(1))
+                    .await?
+                    .unwrap();
+                tester
+                    .db()
+                    .delegate(
+                        &pr,
+                        DelegatedPermission::Review,
+                        Some(Utc::now() - chrono::Duration::minutes(1)),
+                    )
+                    .await?;
+
+                tester.post_comment("@bors r+").await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @"@default-user: :key: Insufficient privileges: not in review users"
+                );
+                tester.get_pr_copy(()).await.expect_unapproved();
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn undelegate_by_reviewer(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::unauthorized_pr_author())
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(review_comment("@bors delegate+"))
+                    .await?;
+                tester.expect_comments((), 1).await;
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_delegated(DelegatedPermission::Review);
+
+                tester
+                    .post_comment(review_comment("@bors delegate-"))
+                    .await?;
+                tester
+                    .wait_for_pr((), |pr| pr.delegated_permission.is_none())
+                    .await?;
+
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn undelegate_by_delegatee(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::unauthorized_pr_author())
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(review_comment("@bors delegate+"))
+                    .await?;
+                tester.expect_comments((), 1).await;
+
+                tester.post_comment("@bors delegate-").await?;
+                tester
+                    .wait_for_pr((), |pr| pr.delegated_permission.is_none())
+                    .await?;
+
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn reviewer_unapprove_delegated_approval(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::unauthorized_pr_author())
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(review_comment("@bors delegate+"))
+                    .await?;
+                tester.expect_comments((), 1).await;
+
+                tester.approve(()).await?;
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_approved_by(&User::default_pr_author().name);
+
+                tester.post_comment(review_comment("@bors r-")).await?;
+                tester.expect_comments((), 1).await;
+
+                tester.get_pr_copy(()).await.expect_unapproved();
+
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn non_author_cannot_use_delegation(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::unauthorized_pr_author())
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(review_comment("@bors delegate+"))
+                    .await?;
+                tester.expect_comments((), 1).await;
+
+                tester
+                    .post_comment(Comment::from("@bors r+").with_author(User::unprivileged()))
+                    .await?;
+                tester.expect_comments((), 1).await;
+
+                tester.get_pr_copy(()).await.expect_unapproved();
+
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn delegate_insufficient_permission_try_user(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(Comment::from("@bors delegate+").with_author(User::try_user()))
+                    .await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @"@user-with-try-privileges: :key: Insufficient privileges: not in review users"
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_with_rollup_value(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors r+ rollup=never").await?;
+            tester.expect_comments((), 1).await;
+
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_rollup(Some(RollupMode::Never))
+                .expect_approved_by(&User::default_pr_author().name);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_with_rollup_bare(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors r+ rollup").await?;
+            tester.expect_comments((), 1).await;
+
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_rollup(Some(RollupMode::Always))
+                .expect_approved_by(&User::default_pr_author().name);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_with_rollup_bare_maybe(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors r+ rollup-").await?;
+            tester.expect_comments((), 1).await;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_rollup(Some(RollupMode::Maybe))
+                .expect_approved_by(&User::default_pr_author().name);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_with_priority_rollup(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors r+ p=10 rollup=never").await?;
+            tester.expect_comments((), 1).await;
+
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_priority(Some(10))
+                .expect_rollup(Some(RollupMode::Never))
+                .expect_approved_by(&User::default_pr_author().name);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_on_behalf_with_rollup_bare(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors r=user1 rollup").await?;
+            tester.expect_comments((), 1).await;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_rollup(Some(RollupMode::Always))
+                .expect_approved_by("user1");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_on_behalf_with_rollup_value(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors r=user1 rollup=always").await?;
+            tester.expect_comments((), 1).await;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_rollup(Some(RollupMode::Always))
+                .expect_approved_by("user1");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_on_behalf_with_priority_rollup_value(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .post_comment("@bors r=user1 rollup=always priority=10")
+                .await?;
+            tester.expect_comments((), 1).await;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_priority(Some(10))
+                .expect_rollup(Some(RollupMode::Always))
+                .expect_approved_by("user1");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn set_rollup_default(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors rollup").await?;
+            tester
+                .wait_for_pr((), |pr| pr.rollup == Some(RollupMode::Always))
+                .await?;
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn set_rollup_with_value(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors rollup=maybe").await?;
+            tester
+                .wait_for_pr((), |pr| pr.rollup == Some(RollupMode::Maybe))
+                .await?;
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn rollup_preserved_after_approve(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors rollup").await?;
+            tester
+                .wait_for_pr((), |pr| pr.rollup == Some(RollupMode::Always))
+                .await?;
+
+            tester.approve(()).await?;
+
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_rollup(Some(RollupMode::Always))
+                .expect_approved_by(&User::default_pr_author().name);
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn rollup_overridden_on_approve_with_rollup(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors rollup=never").await?;
+            tester
+                .wait_for_pr((), |pr| pr.rollup == Some(RollupMode::Never))
+                .await?;
+
+            tester.post_comment("@bors r+ rollup").await?;
+            tester.expect_comments((), 1).await;
+
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_rollup(Some(RollupMode::Always))
+                .expect_approved_by(&User::default_pr_author().name);
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_store_sha(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let pr = tester.get_pr_copy(()).await.get_gh_pr();
+            tester.approve(()).await?;
+
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_approved_sha(&pr.head_sha);
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn reapproved_pr_uses_latest_sha(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let pr = tester.get_pr_copy(()).await.get_gh_pr();
+            tester.approve(()).await?;
+
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_approved_sha(&pr.head_sha);
+
+            tester.push_to_pr(()).await?;
+            let pr2 = tester.get_pr_copy(()).await.get_gh_pr();
+            assert_ne!(pr.head_sha, pr2.head_sha);
+
+            tester.expect_comments((), 1).await;
+
+            tester.approve(()).await?;
+
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_approved_sha(&pr2.head_sha);
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn delegate_try(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::unauthorized_pr_author())
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(review_comment("@bors delegate=try"))
+                    .await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @r"
+                :v: @default-user, you can now perform try builds on this pull request!
+
+                You can now post `@bors try` to start a try build.
+                "
+                );
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_delegated(DelegatedPermission::Try);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn delegated_try_can_build(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::unauthorized_pr_author())
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(review_comment("@bors delegate=try"))
+                    .await?;
+                tester.expect_comments((), 1).await;
+
+                tester.post_comment("@bors try").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                :hourglass: Trying commit pr-1-sha with merge merge-0-pr-1…
+
+                To cancel the try build, run the command `@bors try cancel`.
+                ");
+
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn delegated_try_can_not_approve(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::unauthorized_pr_author())
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(review_comment("@bors delegate=try"))
+                    .await?;
+                tester.expect_comments((), 1).await;
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_delegated(DelegatedPermission::Try);
+
+                tester.post_comment("@bors r+").await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @"@default-user: :key: Insufficient privileges: not in review users"
+                );
+                tester.get_pr_copy(()).await.expect_unapproved();
+
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn multiple_commands_in_one_comment(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .post_comment(
+                    r#"
+@bors r+ rollup=never
+@bors p=10
+"#,
+                )
+                .await?;
+            tester.expect_comments((), 1).await;
+
+            tester
+                .wait_for_pr((), |pr| {
+                    pr.rollup == Some(RollupMode::Never) && pr.priority == Some(10)
+                })
+                .await?;
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_draft_pr(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
             tester
                 .set_pr_status_draft(())
@@ -1263,51 +3014,144 @@ mod tests {
     }
 
     #[sqlx::test]
-    async fn approve_pr_with_blocked_label(pool: sqlx::PgPool) {
+    async fn approve_pr_with_blocked_label(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+labels_blocking_approval = ["proposed-final-comment-period"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .edit_pr((), |pr| {
+                        pr.labels = vec![
+                            "S-waiting-on-review".to_string(),
+                            "proposed-final-comment-period".to_string(),
+                        ];
+                    })
+                    .await?;
+                tester.post_comment("@bors r+").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":clipboard: This PR cannot be approved because it currently has the following label: `proposed-final-comment-period`.");
+                tester.get_pr_copy(()).await.expect_unapproved();
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_pr_with_blocked_labels(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+labels_blocking_approval = ["proposed-final-comment-period", "final-comment-period"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .edit_pr((), |pr| {
+                        pr.labels = vec![
+                            "S-waiting-on-review".to_string(),
+                            "proposed-final-comment-period".to_string(),
+                            "final-comment-period".to_string(),
+                            "S-blocked".to_string(),
+                        ];
+                    })
+                    .await?;
+                tester.post_comment("@bors r+").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":clipboard: This PR cannot be approved because it currently has the following labels: `proposed-final-comment-period`, `final-comment-period`.");
+                tester.get_pr_copy(()).await.expect_unapproved();
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_pr_with_unchecked_template_items(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"required_checklist_items = ["Tests were added", "Docs were updated"]"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .edit_pr((), |pr| {
+                        pr.description = "- [x] Tests were added\n- [ ] Docs were updated".to_string();
+                    })
+                    .await?;
+                tester.post_comment("@bors r+").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                :clipboard: This PR cannot be approved because the following items of the PR template are not checked:
+                - [ ] Docs were updated
+
+                Hint: Tick them in the PR description, or explain why they do not apply.
+                ");
+                tester.get_pr_copy(()).await.expect_unapproved();
+
+                tester
+                    .edit_pr((), |pr| {
+                        pr.description = "- [x] Tests were added\n- [x] Docs were updated".to_string();
+                    })
+                    .await?;
+                tester.post_comment("@bors r+").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                :pushpin: Commit pr-1-sha has been approved by `default-user`
+
+                It is now in the [queue](https://test.com/bors/queue/borstest) for this repository.
+                ");
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_pr_violating_lint_rules(pool: sqlx::PgPool) {
         BorsBuilder::new(pool)
             .github(GitHubState::default().with_default_config(
                 r#"
-labels_blocking_approval = ["proposed-final-comment-period"]
+[lint]
+title_pattern = "^(feat|fix): "
+require_issue_reference = true
+max_subject_length = 10
+forbidden_markers = ["fixup!"]
 "#,
             ))
             .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors r+").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                :clipboard: This PR cannot be approved because its title or description does not follow the rules of this repository:
+                - The title has to match `^(feat|fix): `.
+                - The title or the description has to reference an issue (e.g. `#123`).
+                - The title has 13 characters, but it can have at most 10 characters.
+
+                Hint: Edit the PR title or description and approve it again.
+                ");
+                tester.get_pr_copy(()).await.expect_unapproved();
+
                 tester
                     .edit_pr((), |pr| {
-                        pr.labels = vec![
-                            "S-waiting-on-review".to_string(),
-                            "proposed-final-comment-period".to_string(),
-                        ];
+                        pr.title = "fix: foo".to_string();
+                        pr.description = "Fixes #1".to_string();
                     })
                     .await?;
                 tester.post_comment("@bors r+").await?;
-                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":clipboard: This PR cannot be approved because it currently has the following label: `proposed-final-comment-period`.");
-                tester.get_pr_copy(()).await.expect_unapproved();
+                tester.expect_comments((), 1).await;
+                tester.get_pr_copy(()).await.expect_approvers(&["default-user"]);
                 Ok(())
             })
             .await;
     }
 
     #[sqlx::test]
-    async fn approve_pr_with_blocked_labels(pool: sqlx::PgPool) {
+    async fn approve_pr_with_block_label(pool: sqlx::PgPool) {
         BorsBuilder::new(pool)
-            .github(GitHubState::default().with_default_config(
-                r#"
-labels_blocking_approval = ["proposed-final-comment-period", "final-comment-period"]
-"#,
-            ))
+            .github(GitHubState::default().with_default_config(r#"block_labels = ["S-blocked"]"#))
             .run_test(async |tester: &mut BorsTester| {
                 tester
                     .edit_pr((), |pr| {
-                        pr.labels = vec![
-                            "S-waiting-on-review".to_string(),
-                            "proposed-final-comment-period".to_string(),
-                            "final-comment-period".to_string(),
-                            "S-blocked".to_string(),
-                        ];
+                        pr.labels = vec!["S-blocked".to_string()];
                     })
                     .await?;
                 tester.post_comment("@bors r+").await?;
-                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":clipboard: This PR cannot be approved because it currently has the following labels: `proposed-final-comment-period`, `final-comment-period`.");
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":clipboard: This PR cannot be approved because it currently has the following label: `S-blocked`.");
                 tester.get_pr_copy(()).await.expect_unapproved();
                 Ok(())
             })
@@ -1334,6 +3178,29 @@ labels_blocking_approval = ["proposed-final-comment-period", "final-comment-peri
         .await;
     }
 
+    #[sqlx::test]
+    async fn unapprove_running_auto_build_resets_auto_branch(pool: sqlx::PgPool) {
+        let gh = run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester.workflow_start(tester.auto_branch().await).await?;
+            tester.post_comment("@bors r-").await?;
+            tester.expect_comments((), 1).await;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_unapproved()
+                .expect_no_auto_build();
+            Ok(())
+        })
+        .await;
+        gh.check_sha_history(
+            default_repo_name(),
+            AUTO_BRANCH_NAME,
+            &["merge-0-pr-1", "main-sha1"],
+        );
+    }
+
     #[sqlx::test]
     async fn unapprove_running_auto_build_pr_failed_comment(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
@@ -1379,4 +3246,361 @@ labels_blocking_approval = ["proposed-final-comment-period", "final-comment-peri
         })
         .await;
     }
+
+    #[sqlx::test]
+    async fn unapprove_running_auto_build_starts_next_pr(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+            tester.approve(()).await?;
+            tester.approve(pr2.id()).await?;
+            tester.start_auto_build(()).await?;
+            tester.workflow_start(tester.auto_branch().await).await?;
+            tester.post_comment("@bors r-").await?;
+            tester.expect_comments((), 1).await;
+            tester.start_auto_build(pr2.id()).await?;
+            tester
+                .get_pr_copy(pr2.id())
+                .await
+                .expect_auto_build(|_| true);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_pr_with_conflicts(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .edit_pr((), |pr| {
+                    pr.mergeable_state = OctocrabMergeableState::Dirty;
+                })
+                .await?;
+            tester
+                .wait_for_pr((), |pr| pr.mergeable_state == MergeableState::HasConflicts)
+                .await?;
+            tester.post_comment("@bors r+").await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+            :pushpin: Commit pr-1-sha has been approved by `default-user`
+
+            :warning: This PR has merge conflicts, so it is waiting for them to be resolved. It will enter the [queue](https://test.com/bors/queue/borstest) once they are resolved. If the resolution changes the diff of the PR, it will need to be re-approved.
+            ");
+            tester
+                .wait_for_pr((), |pr| pr.is_approved() && pr.waiting_on_conflicts)
+                .await?;
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_pr_with_conflicts_keep_approval_on_resolution(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+stale_approval = "merge"
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .edit_pr((), |pr| {
+                        pr.mergeable_state = OctocrabMergeableState::Dirty;
+                    })
+                    .await?;
+                tester
+                    .wait_for_pr((), |pr| pr.mergeable_state == MergeableState::HasConflicts)
+                    .await?;
+                tester.approve(()).await?;
+
+                tester.rebase_pr(()).await?;
+                tester.expect_comments((), 1).await;
+                tester
+                    .modify_pr_state((), |pr| pr.mergeable_state = OctocrabMergeableState::Clean)
+                    .await;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":arrow_forward: Merge conflicts were resolved, so this PR has entered the [queue](https://test.com/bors/queue/borstest).");
+                tester
+                    .wait_for_pr((), |pr| {
+                        pr.is_approved()
+                            && !pr.waiting_on_conflicts
+                            && pr.mergeable_state == MergeableState::Mergeable
+                    })
+                    .await?;
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_pr_with_conflicts_keep_approval_on_rebase_by_default(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("merge_queue_enabled = true"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .edit_pr((), |pr| {
+                        pr.mergeable_state = OctocrabMergeableState::Dirty;
+                    })
+                    .await?;
+                tester
+                    .wait_for_pr((), |pr| pr.mergeable_state == MergeableState::HasConflicts)
+                    .await?;
+                tester.approve(()).await?;
+
+                tester.rebase_pr(()).await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                :information_source: A new commit `pr-1-commit-1` was pushed to the branch. It makes the
+                same changes as the approved commit `pr-1-sha`, so the approval was moved to it.
+                ");
+                tester
+                    .modify_pr_state((), |pr| pr.mergeable_state = OctocrabMergeableState::Clean)
+                    .await;
+                tester.expect_comments((), 1).await;
+                tester.start_auto_build(()).await?;
+                tester.get_pr_copy(()).await.expect_auto_build(|_| true);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn approve_pr_with_conflicts_unapprove_on_changed_resolution(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .edit_pr((), |pr| {
+                    pr.mergeable_state = OctocrabMergeableState::Dirty;
+                })
+                .await?;
+            tester
+                .wait_for_pr((), |pr| pr.mergeable_state == MergeableState::HasConflicts)
+                .await?;
+            tester.approve(()).await?;
+
+            tester.push_to_pr(()).await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+            :warning: A new commit `pr-1-commit-1` was pushed to the branch, the
+            PR will need to be re-approved.
+            ");
+            tester.get_pr_copy(()).await.expect_unapproved();
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn acknowledge_command_with_reaction(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("acknowledge_with_reaction = true"))
+            .run_test(async |tester: &mut BorsTester| {
+                let comment = tester.post_comment("@bors p=5").await?;
+                assert_eq!(
+                    tester.wait_for_reactions(1).await?,
+                    vec![CommentReaction {
+                        review_comment: false,
+                        comment_id: comment.id.unwrap(),
+                        content: "+1".to_string(),
+                    }]
+                );
+                tester.get_pr_copy(()).await.expect_priority(Some(5));
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn acknowledge_review_comment_command_with_reaction(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("acknowledge_with_reaction = true"))
+            .run_test(async |tester: &mut BorsTester| {
+                let comment = tester
+                    .post_comment(Comment::from("@bors rollup=never").in_review_thread(42))
+                    .await?;
+                assert_eq!(
+                    tester.wait_for_reactions(1).await?,
+                    vec![CommentReaction {
+                        review_comment: true,
+                        comment_id: comment.id.unwrap(),
+                        content: "+1".to_string(),
+                    }]
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn do_not_acknowledge_command_with_reaction_by_default(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors p=5").await?;
+            tester.post_comment("@bors ping").await?;
+            tester.expect_comments((), 1).await;
+            assert!(
+                tester
+                    .default_repo()
+                    .await
+                    .lock()
+                    .comment_reactions
+                    .is_empty()
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn approval_is_not_acknowledged_with_reaction(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("acknowledge_with_reaction = true"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                assert!(
+                    tester
+                        .default_repo()
+                        .await
+                        .lock()
+                        .comment_reactions
+                        .is_empty()
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    fn gh_state_with_build_env() -> GitHubState {
+        GitHubState::default().with_default_config(
+            r#"
+merge_queue_enabled = true
+
+[build_env]
+profile = ["debug-assertions", "release"]
+"#,
+        )
+    }
+
+    #[sqlx::test]
+    async fn build_env_passed_to_builds(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(gh_state_with_build_env())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors env profile=debug-assertions").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r":gear: Build parameters of this PR: `profile=debug-assertions`. They will be passed to its next try and auto builds.");
+
+                tester.post_comment("@bors try").await?;
+                tester.expect_comments((), 1).await;
+                insta::assert_snapshot!(tester.get_branch_commit_message(&tester.try_branch().await).await, @r"
+                Auto merge of #1 - pr-1, r=<try>
+                Title of PR 1
+
+                Bors-Build-Id: 1
+                Bors-Env: profile=debug-assertions
+                ");
+
+                tester.approve(()).await?;
+                tester.start_auto_build(()).await?;
+                insta::assert_snapshot!(tester.get_branch_commit_message(&tester.auto_branch().await).await, @r"
+                Auto merge of #1 - pr-1, r=default-user
+                Title of PR 1
+
+                Description of PR 1
+
+                Bors-Build-Id: 2
+                Bors-Env: profile=debug-assertions
+                ");
+                tester.finish_auto_build(()).await?;
+                tester.wait_for_pr((), |pr| pr.build_env.is_empty()).await?;
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn build_env_value_not_allowed(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(gh_state_with_build_env())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors env profile=fast").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r":exclamation: Build parameter `profile` cannot be set to `fast`. Allowed values: `debug-assertions`, `release`.");
+                tester.post_comment("@bors env opt-level=3").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r":exclamation: Unknown build parameter `opt-level`. Available parameters: `profile`.");
+                tester.wait_for_pr((), |pr| pr.build_env.is_empty()).await?;
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn build_env_localized(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+language = "de"
+
+[build_env]
+profile = ["debug-assertions", "release"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors env profile=fast").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":exclamation: Der Build-Parameter `profile` kann nicht auf `fast` gesetzt werden. Erlaubte Werte: `debug-assertions`, `release`.");
+                tester.post_comment("@bors env opt-level=3").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":exclamation: Unbekannter Build-Parameter `opt-level`. Verfügbare Parameter: `profile`.");
+                tester.post_comment("@bors env profile=release").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":gear: Build-Parameter dieses PRs: `profile=release`. Sie werden an die nächsten Try- und Auto-Builds übergeben.");
+                tester.post_comment("@bors env-").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":gear: Die Build-Parameter dieses PRs wurden entfernt.");
+                tester.post_comment("@bors env-").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @"Der PR hat keine Build-Parameter.");
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn build_env_disabled(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors env profile=release").await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r":exclamation: Build parameters are not enabled in this repository. They can be allowed with the `build_env` configuration option.");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn build_env_unauthorized(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(gh_state_with_build_env())
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(
+                        Comment::from("@bors env profile=release")
+                            .with_author(User::unprivileged()),
+                    )
+                    .await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"@unprivileged-user: :key: Insufficient privileges: not in review users");
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn build_env_clear(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(gh_state_with_build_env())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors env profile=release").await?;
+                tester.expect_comments((), 1).await;
+                tester.post_comment("@bors info").await?;
+                let info = tester.get_next_comment_text(()).await?;
+                assert!(
+                    info.contains("- Build parameters: `profile=release`"),
+                    "{info}"
+                );
+
+                tester.post_comment("@bors env-").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r":gear: Build parameters of this PR were removed.");
+                tester.wait_for_pr((), |pr| pr.build_env.is_empty()).await?;
+                tester.post_comment("@bors env-").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"PR has no build parameters.");
+                Ok(())
+            })
+            .await;
+    }
 }
@@ -1,23 +1,32 @@
+use std::collections::HashSet;
 use std::sync::Arc;
 
 use super::{PullRequestData, deny_request};
-use super::{has_permission, hide_try_build_started_comments};
+use super::{has_permission, hide_tagged_comments, hide_try_build_started_comments};
 use crate::PgDbClient;
 use crate::bors::command::{CommandPrefix, Parent};
 use crate::bors::comment::try_build_cancelled_comment;
 use crate::bors::comment::try_build_cancelled_with_failed_workflow_cancel_comment;
 use crate::bors::comment::{CommentTag, no_try_build_in_progress_comment};
 use crate::bors::comment::{
-    cant_find_last_parent_comment, merge_conflict_comment, try_build_started_comment,
+    cant_find_last_parent_comment, draft_try_build_denied_comment, dry_run_comment,
+    fork_try_build_denied_comment, fork_try_build_unrestricted_comment, merge_conflict_comment,
+    try_build_started_comment, try_builds_disabled_comment, try_commit_not_in_pr_comment,
+    try_parent_branch_not_found_comment, try_parent_unreachable_comment, unknown_try_jobs_comment,
 };
 use crate::bors::handlers::workflow::{CancelBuildError, cancel_build};
-use crate::bors::{MergeType, RepositoryState, create_merge_commit_message};
-use crate::database::{BuildModel, BuildStatus, PullRequestModel};
+use crate::bors::{
+    Comment, MergeType, PullRequestStatus, RepositoryState, create_merge_commit_message,
+};
+use crate::config::{ForkTryPolicy, UnreachableParentPolicy};
+use crate::database::{BuildModel, BuildStatus, PullRequestModel, WorkflowStatus};
 use crate::github::api::client::{CheckRunOutput, GithubRepositoryClient};
 use crate::github::api::operations::ForcePush;
 use crate::github::{CommitSha, GithubUser, PullRequestNumber};
 use crate::github::{MergeResult, attempt_merge};
+use crate::github::{is_triggered_by_push, jobs_outside_environment};
 use crate::permissions::PermissionType;
+use crate::utils::text::pluralize;
 use anyhow::{Context, anyhow};
 use octocrab::params::checks::CheckRunConclusion;
 use octocrab::params::checks::CheckRunStatus;
@@ -40,6 +49,9 @@ pub(super) const TRY_BUILD_CHECK_RUN_NAME: &str = "Bors try build";
 ///
 /// If `parent` is set, it will use it as a base commit for the merge.
 /// Otherwise, it will use the latest commit on the main repository branch.
+///
+/// If `commit` is set, it will try the given commit of the PR instead of its head.
+#[allow(clippy::too_many_arguments)]
 pub(super) async fn command_try_build(
     repo: Arc<RepositoryState>,
     db: Arc<PgDbClient>,
@@ -47,66 +59,205 @@ pub(super) async fn command_try_build(
     author: &GithubUser,
     parent: Option<Parent>,
     jobs: Vec<String>,
+    commit: Option<CommitSha>,
     bot_prefix: &CommandPrefix,
 ) -> anyhow::Result<()> {
     let repo = repo.as_ref();
+    if !repo.config.load().try_enabled {
+        tracing::info!("Try build was denied, because try builds are disabled");
+        repo.post_comment(
+            pr.number(),
+            try_builds_disabled_comment(repo.config.load().language),
+        )
+        .await?;
+        return Ok(());
+    }
+
     if !has_permission(repo, author, pr, PermissionType::Try).await? {
-        deny_request(repo, pr.number(), author, PermissionType::Try).await?;
+        return deny_request(repo, pr, author, PermissionType::Try).await;
+    }
+
+    if pr.db.pr_status == PullRequestStatus::Draft {
+        tracing::info!("Try build of a draft PR was denied");
+        repo.post_comment(
+            pr.number(),
+            draft_try_build_denied_comment(repo.config.load().language),
+        )
+        .await?;
         return Ok(());
     }
 
     if Some(Parent::Last) == parent && pr.db.try_build.is_none() {
         tracing::warn!("try build was requested with parent=last but no previous build was found");
-        repo.client
-            .post_comment(pr.number(), cant_find_last_parent_comment())
-            .await?;
+        repo.post_comment(
+            pr.number(),
+            cant_find_last_parent_comment(repo.config.load().language),
+        )
+        .await?;
         return Ok(());
     };
 
-    let base_sha = match get_base_sha(pr.db, parent) {
-        Some(base_sha) => base_sha,
-        None => repo
-            .client
-            .get_branch_sha(&pr.github.base.name)
-            .await
-            .context(format!("Cannot get SHA for branch {}", pr.github.base.name))?,
+    // An older commit of the PR can be tried, e.g. to find out which commit introduced a regression
+    let head_sha = match commit {
+        Some(commit) if commit != pr.github.head.sha => {
+            let commits = repo.client.get_pull_request_commits(pr.number()).await?;
+            if !commits.contains(&commit) {
+                tracing::info!("Try build commit {commit} is not a commit of the PR");
+                repo.post_comment(pr.number(), try_commit_not_in_pr_comment(&commit))
+                    .await?;
+                return Ok(());
+            }
+            commit
+        }
+        _ => pr.github.head.sha.clone(),
     };
 
-    // Try to cancel any previously running try build workflows
-    let cancelled_workflow_urls = if let Some(build) = get_pending_build(pr.db) {
-        let res = cancel_previous_try_build(repo, &db, build).await?;
-        // Also try to hide previous "Try build started" comments that weren't hidden yet
-        if let Err(error) = hide_try_build_started_comments(repo, &db, pr.db).await {
-            tracing::error!("Failed to hide previous try build started comment(s): {error:?}");
-        }
+    if !jobs.is_empty()
+        && repo.config.load().validate_try_jobs
+        && let Some(comment) = validate_try_jobs(repo, pr, &jobs).await
+    {
+        repo.post_comment(pr.number(), comment).await?;
+        return Ok(());
+    }
 
-        res
+    // Try builds have access to repository secrets, so code coming from forks must either
+    // be refused or only run in an environment that restricts what the build can access.
+    let environment = if pr.github.from_fork {
+        match &repo.config.load().fork_try_builds {
+            ForkTryPolicy::Allow => None,
+            ForkTryPolicy::Deny => {
+                tracing::info!("Try build of a fork PR was denied by the repository config");
+                repo.post_comment(
+                    pr.number(),
+                    fork_try_build_denied_comment(repo.config.load().language),
+                )
+                .await?;
+                return Ok(());
+            }
+            ForkTryPolicy::Restricted { environment } => {
+                // The check is boxed, because it would otherwise make the try build future large
+                // enough to overflow the stack
+                if !Box::pin(check_fork_try_environment(repo, pr, &head_sha, environment)).await? {
+                    return Ok(());
+                }
+                Some(environment.clone())
+            }
+        }
     } else {
-        vec![]
+        None
+    };
+
+    let explicit_parent = matches!(parent, Some(Parent::CommitSha(_)));
+    let base_sha = match parent {
+        Some(Parent::Branch(branch)) => match repo.client.get_branch_sha(&branch).await {
+            Ok(base_sha) => base_sha,
+            Err(error) => {
+                tracing::warn!("Cannot resolve try build parent branch {branch}: {error:?}");
+                repo.post_comment(pr.number(), try_parent_branch_not_found_comment(&branch))
+                    .await?;
+                return Ok(());
+            }
+        },
+        parent => match get_base_sha(pr.db, parent) {
+            Some(base_sha) => base_sha,
+            None => repo
+                .client
+                .get_branch_sha(&pr.github.base.name)
+                .await
+                .context(format!("Cannot get SHA for branch {}", pr.github.base.name))?,
+        },
     };
 
+    // Merging the PR into a commit that is not part of the base branch produces confusing results
+    if explicit_parent {
+        let base_branch = &pr.github.base.name;
+        let base_branch_sha = repo
+            .client
+            .get_branch_sha(base_branch)
+            .await
+            .context(format!("Cannot get SHA for branch {base_branch}"))?;
+        if !repo.client.is_ancestor(&base_sha, &base_branch_sha).await? {
+            let policy = repo.config.load().unreachable_try_parent;
+            tracing::info!("Try build parent {base_sha} is not reachable from {base_branch}");
+            repo.post_comment(
+                pr.number(),
+                try_parent_unreachable_comment(&base_sha, base_branch, policy),
+            )
+            .await?;
+            if policy == UnreachableParentPolicy::Refuse {
+                return Ok(());
+            }
+        }
+    }
+
+    if repo.config.load().dry_run {
+        tracing::info!("Not starting try build, because of dry-run mode");
+        let comment = dry_run_comment(&format!("try commit {head_sha} merged into {base_sha}"));
+        repo.post_comment(pr.number(), comment).await?;
+        return Ok(());
+    }
+
+    // Try to cancel any previously running try build workflows of the same jobs. Try builds of
+    // other jobs keep running concurrently.
+    let (replaced_builds, concurrent_builds): (Vec<_>, Vec<_>) = db
+        .get_pending_try_builds(pr.db)
+        .await?
+        .into_iter()
+        .partition(|try_build| same_jobs(&try_build.jobs, &jobs));
+    let mut cancelled_workflow_urls = vec![];
+    for try_build in &replaced_builds {
+        cancelled_workflow_urls
+            .extend(cancel_previous_try_build(repo, &db, &try_build.build).await?);
+    }
+    // Also try to hide previous "Try build started" comments that weren't hidden yet
+    if !replaced_builds.is_empty()
+        && concurrent_builds.is_empty()
+        && let Err(error) = hide_try_build_started_comments(repo, &db, pr.db).await
+    {
+        tracing::error!("Failed to hide previous try build started comment(s): {error:?}");
+    }
+
+    // Reserve the build ID upfront, so that it can be included in the merge commit message
+    let build_id = db.reserve_build_id().await?;
+
     match attempt_merge(
         &repo.client,
         TRY_MERGE_BRANCH_NAME,
-        &pr.github.head.sha,
+        &head_sha,
         &base_sha,
-        &create_merge_commit_message(pr, MergeType::Try { try_jobs: jobs }),
+        &create_merge_commit_message(
+            pr,
+            MergeType::Try {
+                try_jobs: jobs.clone(),
+                environment: environment.clone(),
+            },
+            build_id,
+        ),
     )
     .await?
     {
         MergeResult::Success(merge_sha) => {
             // If the merge was succesful, run CI with merged commit
-            let build_id =
-                run_try_build(&repo.client, &db, pr.db, merge_sha.clone(), base_sha).await?;
+            run_try_build(
+                &repo.client,
+                &db,
+                pr.db,
+                build_id,
+                merge_sha.clone(),
+                base_sha,
+                &jobs,
+                &head_sha,
+            )
+            .await?;
 
             // Create a check run to track the try build status in GitHub's UI.
-            // This gets added to the PR's head SHA so GitHub shows UI in the checks tab and
-            // the bottom of the PR.
+            // This gets added to the tried commit of the PR (usually its head), so GitHub shows UI
+            // in the checks tab and the bottom of the PR.
             match repo
                 .client
                 .create_check_run(
                     TRY_BUILD_CHECK_RUN_NAME,
-                    &pr.github.head.sha,
+                    &head_sha,
                     CheckRunStatus::InProgress,
                     CheckRunOutput {
                         title: "Bors try build".to_string(),
@@ -126,15 +277,26 @@ pub(super) async fn command_try_build(
                 }
             }
 
+            // The results of the previous try builds are replaced by the result of this one
+            if repo.config.load().hide_outdated_comments
+                && let Err(error) =
+                    hide_tagged_comments(repo, &db, pr.number(), &[CommentTag::TryBuildCompleted])
+                        .await
+            {
+                tracing::error!("Failed to hide previous try build result comment(s): {error:?}");
+            }
+
             let comment = repo
-                .client
                 .post_comment(
                     pr.number(),
                     try_build_started_comment(
+                        &head_sha,
                         &pr.github.head.sha,
                         &merge_sha,
                         bot_prefix,
                         cancelled_workflow_urls,
+                        environment.as_deref(),
+                        (!concurrent_builds.is_empty()).then_some(build_id),
                     ),
                 )
                 .await?;
@@ -147,8 +309,7 @@ pub(super) async fn command_try_build(
             .await?;
         }
         MergeResult::Conflict => {
-            repo.client
-                .post_comment(pr.number(), merge_conflict_comment(&pr.github.head.name))
+            repo.post_comment(pr.number(), merge_conflict_comment(&pr.github.head.name))
                 .await?;
         }
     }
@@ -179,26 +340,38 @@ async fn cancel_previous_try_build(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run_try_build(
     client: &GithubRepositoryClient,
     db: &PgDbClient,
     pr: &PullRequestModel,
+    build_id: i32,
     commit_sha: CommitSha,
     parent_sha: CommitSha,
-) -> anyhow::Result<i32> {
+    jobs: &[String],
+    head_sha: &CommitSha,
+) -> anyhow::Result<()> {
     client
         .set_branch_to_sha(TRY_BRANCH_NAME, &commit_sha, ForcePush::Yes)
         .await
         .map_err(|error| anyhow!("Cannot set try branch to main branch: {error:?}"))?;
 
-    let build_id = db
-        .attach_try_build(pr, TRY_BRANCH_NAME.to_string(), commit_sha, parent_sha)
-        .await?;
+    db.attach_try_build(
+        pr,
+        build_id,
+        TRY_BRANCH_NAME.to_string(),
+        commit_sha,
+        parent_sha,
+        jobs,
+        head_sha,
+    )
+    .await?;
 
     tracing::info!("Try build started");
-    Ok(build_id)
+    Ok(())
 }
 
+/// Returns the base commit of a try build if it is known without asking GitHub.
 fn get_base_sha(pr_model: &PullRequestModel, parent: Option<Parent>) -> Option<CommitSha> {
     let last_parent = pr_model
         .try_build
@@ -209,6 +382,7 @@ fn get_base_sha(pr_model: &PullRequestModel, parent: Option<Parent>) -> Option<C
         Some(parent) => match parent {
             Parent::Last => last_parent,
             Parent::CommitSha(parent) => Some(parent),
+            Parent::Branch(_) => None,
         },
         None => None,
     }
@@ -219,64 +393,229 @@ pub(super) async fn command_try_cancel(
     db: Arc<PgDbClient>,
     pr: PullRequestData<'_>,
     author: &GithubUser,
+    build_id: Option<i32>,
 ) -> anyhow::Result<()> {
     let repo = repo.as_ref();
     if !has_permission(repo, author, pr, PermissionType::Try).await? {
-        deny_request(repo, pr.number(), author, PermissionType::Try).await?;
-        return Ok(());
+        return deny_request(repo, pr, author, PermissionType::Try).await;
     }
 
     let pr_number: PullRequestNumber = pr.number();
-    let Some(build) = get_pending_build(pr.db) else {
+    let builds: Vec<BuildModel> = db
+        .get_pending_try_builds(pr.db)
+        .await?
+        .into_iter()
+        .map(|try_build| try_build.build)
+        .filter(|build| build_id.is_none_or(|id| build.id == id))
+        .collect();
+    if builds.is_empty() {
         tracing::info!("No try build found when trying to cancel a try build");
-        repo.client
-            .post_comment(pr_number, no_try_build_in_progress_comment())
-            .await?;
+        repo.post_comment(
+            pr_number,
+            no_try_build_in_progress_comment(repo.config.load().language),
+        )
+        .await?;
         return Ok(());
-    };
+    }
 
-    match cancel_build(
-        &repo.client,
-        db.as_ref(),
-        build,
-        CheckRunConclusion::Cancelled,
-    )
-    .await
-    {
-        Ok(workflows) => {
-            tracing::info!("Try build cancelled");
+    let mut cancelled_workflow_urls = vec![];
+    let mut workflow_cancel_failed = false;
+    for build in &builds {
+        let mut build_workflow_urls = vec![];
+        match cancel_build(
+            &repo.client,
+            db.as_ref(),
+            build,
+            CheckRunConclusion::Cancelled,
+        )
+        .await
+        {
+            Ok(workflows) => {
+                tracing::info!("Try build {} cancelled", build.id);
+                build_workflow_urls.extend(workflows.into_iter().map(|w| w.url));
+            }
+            Err(CancelBuildError::FailedToMarkBuildAsCancelled(error)) => {
+                return Err(error);
+            }
+            Err(CancelBuildError::FailedToCancelWorkflows(error)) => {
+                tracing::error!(
+                    "Could not cancel workflows for try build with SHA {}: {error:?}",
+                    build.commit_sha
+                );
+                workflow_cancel_failed = true;
+            }
+        }
 
-            repo.client
-                .post_comment(
-                    pr_number,
-                    try_build_cancelled_comment(workflows.into_iter().map(|w| w.url)),
-                )
-                .await?
+        match cancel_unknown_try_workflows(&repo.client, db.as_ref(), build).await {
+            Ok(urls) => build_workflow_urls.extend(urls),
+            Err(error) => {
+                tracing::error!(
+                    "Could not cancel unknown workflows for try build with SHA {}: {error:?}",
+                    build.commit_sha
+                );
+                workflow_cancel_failed = true;
+            }
         }
-        Err(CancelBuildError::FailedToMarkBuildAsCancelled(error)) => {
-            return Err(error);
+        reset_try_merge_branch(&repo.client, build).await;
+
+        db.record_audit_action(
+            repo.repository(),
+            Some(pr_number),
+            "try_build_cancelled",
+            Some(&format!(
+                "build {} ({}), {} {} cancelled",
+                build.id,
+                build.commit_sha,
+                build_workflow_urls.len(),
+                pluralize("workflow", build_workflow_urls.len())
+            )),
+        )
+        .await?;
+        cancelled_workflow_urls.extend(build_workflow_urls);
+    }
+
+    let comment = if workflow_cancel_failed {
+        try_build_cancelled_with_failed_workflow_cancel_comment(repo.config.load().language)
+    } else {
+        try_build_cancelled_comment(cancelled_workflow_urls.into_iter())
+    };
+    repo.post_comment(pr_number, comment).await?;
+
+    Ok(())
+}
+
+/// Cancels the workflow runs of a cancelled try build that bors does not know about yet, e.g.
+/// because they were queued on GitHub, but their webhooks have not arrived yet.
+/// Returns the URLs of the cancelled workflow runs.
+async fn cancel_unknown_try_workflows(
+    client: &GithubRepositoryClient,
+    db: &PgDbClient,
+    build: &BuildModel,
+) -> anyhow::Result<Vec<String>> {
+    let known_run_ids: HashSet<u64> = db
+        .get_workflows_for_build(build)
+        .await?
+        .into_iter()
+        .map(|workflow| workflow.run_id.0)
+        .collect();
+    let unknown_runs: Vec<_> = client
+        .get_workflow_runs_for_commit(&build.branch, &CommitSha(build.commit_sha.clone()))
+        .await?
+        .into_iter()
+        .filter(|run| run.status == WorkflowStatus::Pending && !known_run_ids.contains(&run.id.0))
+        .collect();
+    if unknown_runs.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let run_ids: Vec<_> = unknown_runs.iter().map(|run| run.id).collect();
+    tracing::info!("Cancelling unknown workflows {run_ids:?}");
+    client.cancel_workflows(&run_ids).await?;
+    Ok(unknown_runs.into_iter().map(|run| run.url).collect())
+}
+
+/// Reset the try merge branch back to the parent of a cancelled try build, so that the merge
+/// commit of the cancelled build does not stay on the branch. The branch is kept if it was already
+/// moved by another try build.
+/// The try branch itself is left as it is, because pushing to it would start CI again.
+async fn reset_try_merge_branch(client: &GithubRepositoryClient, build: &BuildModel) {
+    let result = async {
+        let branch_sha = client.get_branch_sha(TRY_MERGE_BRANCH_NAME).await?;
+        if branch_sha.as_ref() != build.commit_sha {
+            return Ok(());
         }
-        Err(CancelBuildError::FailedToCancelWorkflows(error)) => {
-            tracing::error!(
-                "Could not cancel workflows for try build with SHA {}: {error:?}",
-                build.commit_sha
-            );
-            repo.client
-                .post_comment(
-                    pr_number,
-                    try_build_cancelled_with_failed_workflow_cancel_comment(),
-                )
-                .await?
+        client
+            .set_branch_to_sha(
+                TRY_MERGE_BRANCH_NAME,
+                &CommitSha(build.parent.clone()),
+                ForcePush::Yes,
+            )
+            .await?;
+        anyhow::Ok(())
+    }
+    .await;
+    if let Err(error) = result {
+        tracing::error!(
+            "Could not reset branch {TRY_MERGE_BRANCH_NAME} of cancelled try build: {error:?}"
+        );
+    }
+}
+
+/// Checks that the requested try jobs are defined in the workflows of the PR, so that a typo does
+/// not start a try build of the default jobs. Returns a comment listing the available jobs if
+/// some job is unknown.
+async fn validate_try_jobs(
+    repo: &RepositoryState,
+    pr: PullRequestData<'_>,
+    jobs: &[String],
+) -> Option<Comment> {
+    let mut available_jobs = match repo
+        .client
+        .get_workflow_job_names(&pr.github.head.sha)
+        .await
+    {
+        Ok(available_jobs) => available_jobs,
+        Err(error) => {
+            tracing::warn!("Cannot load workflow jobs, try jobs will not be validated: {error:?}");
+            return None;
         }
     };
+    // Without any workflows, there is nothing to validate against
+    if available_jobs.is_empty() {
+        return None;
+    }
 
-    Ok(())
+    let unknown_jobs: Vec<&String> = jobs
+        .iter()
+        .filter(|job| !available_jobs.contains(job))
+        .collect();
+    if unknown_jobs.is_empty() {
+        return None;
+    }
+    available_jobs.sort();
+    available_jobs.dedup();
+    Some(unknown_try_jobs_comment(&unknown_jobs, &available_jobs))
 }
 
-fn get_pending_build(pr: &PullRequestModel) -> Option<&BuildModel> {
-    pr.try_build
-        .as_ref()
-        .and_then(|b| (b.status == BuildStatus::Pending).then_some(b))
+/// Checks that all jobs of the workflows that would run for a try build of the given commit run in
+/// the given environment. The workflows are loaded from the tested commit, because the PR could
+/// also modify them. If some job does not use the environment, the try build is refused with a
+/// comment, and false is returned.
+async fn check_fork_try_environment(
+    repo: &RepositoryState,
+    pr: PullRequestData<'_>,
+    sha: &CommitSha,
+    environment: &str,
+) -> anyhow::Result<bool> {
+    let files = repo
+        .client
+        .get_workflow_files(sha)
+        .await
+        .context("Cannot load workflows to check the environment of a fork try build")?;
+    let jobs: Vec<String> = files
+        .iter()
+        .filter(|(_, content)| is_triggered_by_push(content))
+        .flat_map(|(_, content)| jobs_outside_environment(content, environment))
+        .collect();
+    if jobs.is_empty() {
+        return Ok(true);
+    }
+    tracing::info!(
+        "Try build of a fork PR was denied, because jobs {jobs:?} do not run in the {environment} environment"
+    );
+    let comment =
+        fork_try_build_unrestricted_comment(environment, &jobs, repo.config.load().language);
+    repo.post_comment(pr.number(), comment).await?;
+    Ok(false)
+}
+
+/// Returns true if both lists contain the same CI jobs, regardless of their order.
+fn same_jobs(a: &[String], b: &[String]) -> bool {
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort();
+    b.sort();
+    a == b
 }
 
 #[cfg(test)]
@@ -286,8 +625,8 @@ mod tests {
     };
     use crate::database::operations::get_all_workflows;
     use crate::database::{BuildStatus, WorkflowStatus};
-    use crate::github::CommitSha;
     use crate::github::api::client::HideCommentReason;
+    use crate::github::{CommitSha, GithubRepoName, PullRequestNumber};
     use crate::tests::BorsTester;
     use crate::tests::{
         BorsBuilder, Comment, GitHubState, User, WorkflowEvent, WorkflowJob, WorkflowRunData,
@@ -401,6 +740,32 @@ mod tests {
         .await;
     }
 
+    #[sqlx::test]
+    async fn try_as_try_user_from_config(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+[permissions]
+try_users = ["unprivileged-user"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(Comment::from("@bors try").with_author(User::unprivileged()))
+                    .await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @r"
+                    :hourglass: Trying commit pr-1-sha with merge merge-0-pr-1…
+
+                    To cancel the try build, run the command `@bors try cancel`.
+                    "
+                );
+                Ok(())
+            })
+            .await;
+    }
+
     #[sqlx::test]
     async fn try_merge_comment(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
@@ -436,6 +801,8 @@ It fixes so many issues, sir."
             insta::assert_snapshot!(tester.get_branch_commit_message(&tester.try_branch().await).await, @r###"
             Auto merge of #1 - pr-1, r=<try>
             Title of PR 1
+
+            Bors-Build-Id: 1
             "###);
             Ok(())
         })
@@ -468,6 +835,8 @@ try-job: Bar
 
             try-job: Foo
             try-job: Bar
+
+            Bors-Build-Id: 1
             "###);
             Ok(())
         })
@@ -498,6 +867,8 @@ try-job: Bar
 
             try-job: Baz
             try-job: Baz2
+
+            Bors-Build-Id: 1
             "###);
             Ok(())
         })
@@ -523,6 +894,7 @@ try-job: Bar
     #[sqlx::test]
     async fn try_merge_explicit_parent(pool: sqlx::PgPool) {
         let gh = run_test(pool, async |tester: &mut BorsTester| {
+            set_old_main_commit(tester, "ea9c1b050cc8b420c2c211d2177811e564a4dc60").await;
             tester
                 .post_comment("@bors try parent=ea9c1b050cc8b420c2c211d2177811e564a4dc60")
                 .await?;
@@ -540,6 +912,7 @@ try-job: Bar
     #[sqlx::test]
     async fn try_merge_last_parent(pool: sqlx::PgPool) {
         let gh = run_test(pool, async |tester: &mut BorsTester| {
+            set_old_main_commit(tester, "ea9c1b050cc8b420c2c211d2177811e564a4dc60").await;
             tester
                 .post_comment("@bors try parent=ea9c1b050cc8b420c2c211d2177811e564a4dc60")
                 .await?;
@@ -570,74 +943,90 @@ try-job: Bar
     }
 
     #[sqlx::test]
-    async fn try_merge_last_parent_unknown(pool: sqlx::PgPool) {
-        run_test(pool, async |tester: &mut BorsTester| {
-            tester
-                .post_comment("@bors try parent=last")
-                .await?;
-            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":exclamation: There was no previous build. Please set an explicit parent or remove the `parent=last` argument to use the default parent.");
+    async fn try_merge_unreachable_parent_warn(pool: sqlx::PgPool) {
+        let gh = run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try parent=c0ffee0000000000000000000000000000000000").await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":warning: The parent commit `c0ffee0000000000000000000000000000000000` is not reachable from the `main` branch. The try build will be started, but its merge commit may contain changes that are not part of `main`, or miss some of its changes.");
+            tester.expect_comments((), 1).await;
             Ok(())
         })
+        .await;
+        gh.check_sha_history(
+            default_repo_name(),
+            TRY_MERGE_BRANCH_NAME,
+            &["c0ffee0000000000000000000000000000000000", "merge-0-pr-1"],
+        );
+    }
+
+    #[sqlx::test]
+    async fn try_merge_unreachable_parent_refuse(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"unreachable_try_parent = "refuse""#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors try parent=c0ffee0000000000000000000000000000000000").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":exclamation: The parent commit `c0ffee0000000000000000000000000000000000` is not reachable from the `main` branch, so the try build was not started. Please use a commit of the `main` branch as the parent.");
+                assert!(
+                    tester
+                        .db()
+                        .get_pending_builds(&default_repo_name())
+                        .await?
+                        .is_empty()
+                );
+                Ok(())
+            })
             .await;
     }
 
     #[sqlx::test]
-    async fn try_merge_conflict(pool: sqlx::PgPool) {
+    async fn try_older_commit(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester.modify_branch(TRY_MERGE_BRANCH_NAME, |branch| branch.merge_conflict = true).await;
             tester
-                .post_comment("@bors try")
+                .modify_pr_state((), |pr| {
+                    pr.previous_commits = vec!["a1b2c3d4e5f60718293a4b5c6d7e8f9012345678".to_string()]
+                })
+                .await;
+            tester
+                .post_comment("@bors try commit=a1b2c3d4e5f60718293a4b5c6d7e8f9012345678")
                 .await?;
-            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r###"
-            :lock: Merge conflict
-
-            This pull request and the base branch diverged in a way that cannot
-             be automatically merged. Please rebase on top of the latest base
-             branch, and let the reviewer approve again.
-
-            <details><summary>How do I rebase?</summary>
-
-            Assuming `self` is your fork and `upstream` is this repository,
-             you can resolve the conflict following these steps:
-
-            1. `git checkout pr-1` *(switch to your branch)*
-            2. `git fetch upstream HEAD` *(retrieve the latest base branch)*
-            3. `git rebase upstream/HEAD -p` *(rebase on top of it)*
-            4. Follow the on-screen instruction to resolve conflicts (check `git status` if you got lost).
-            5. `git push self pr-1 --force-with-lease` *(update this PR)*
-
-            You may also read
-             [*Git Rebasing to Resolve Conflicts* by Drew Blessing](http://blessing.io/git/git-rebase/open-source/2015/08/23/git-rebasing-to-resolve-conflicts.html)
-             for a short tutorial.
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+            :hourglass: Trying commit a1b2c3d4e5f60718293a4b5c6d7e8f9012345678 with merge merge-0-a1b2c3d4e5f60718293a4b5c6d7e8f9012345678…
 
-            Please avoid the ["**Resolve conflicts**" button](https://help.github.com/articles/resolving-a-merge-conflict-on-github/) on GitHub.
-             It uses `git merge` instead of `git rebase` which makes the PR commit history more difficult to read.
+            :information_source: This is an older commit of the PR, not its current head (pr-1-sha).
 
-            Sometimes step 4 will complete without asking for resolution. This is usually due to difference between how `Cargo.lock` conflict is
-            handled during merge and rebase. This is normal, and you should still perform step 5 to update this PR.
+            To cancel the try build, run the command `@bors try cancel`.
+            ");
 
-            </details>
-            "###);
+            let pr = tester
+                .db()
+                .get_pull_request(&default_repo_name(), PullRequestNumber(1))
+                .await?
+                .unwrap();
+            let try_builds = tester.db().get_pending_try_builds(&pr).await?;
+            assert_eq!(try_builds.len(), 1);
+            assert_eq!(
+                try_builds[0].head_sha.as_deref(),
+                Some("a1b2c3d4e5f60718293a4b5c6d7e8f9012345678")
+            );
             Ok(())
         })
-            .await;
+        .await;
     }
 
     #[sqlx::test]
-    async fn try_merge_insert_into_db(pool: sqlx::PgPool) {
+    async fn try_commit_not_in_pr(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors try").await?;
-            tester.expect_comments((), 1).await;
+            tester
+                .post_comment("@bors try commit=a1b2c3d4e5f60718293a4b5c6d7e8f9012345678")
+                .await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":exclamation: Commit a1b2c3d4e5f60718293a4b5c6d7e8f9012345678 is not a commit of this PR, so the try build was not started.");
             assert!(
                 tester
                     .db()
-                    .find_build(
-                        &default_repo_name(),
-                        TRY_BRANCH_NAME.to_string(),
-                        CommitSha(tester.try_branch().await.get_sha().to_string()),
-                    )
+                    .get_pending_builds(&default_repo_name())
                     .await?
-                    .is_some()
+                    .is_empty()
             );
             Ok(())
         })
@@ -645,13 +1034,18 @@ try-job: Bar
     }
 
     #[sqlx::test]
-    async fn try_cancel_previous_build_no_workflows(pool: sqlx::PgPool) {
+    async fn try_head_commit_explicitly(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors try").await?;
-            tester.expect_comments((), 1).await;
-            tester.post_comment("@bors try").await?;
+            tester
+                .modify_pr_state((), |pr| {
+                    pr.head_sha = "a1b2c3d4e5f60718293a4b5c6d7e8f9012345678".to_string()
+                })
+                .await;
+            tester
+                .post_comment("@bors try commit=a1b2c3d4e5f60718293a4b5c6d7e8f9012345678")
+                .await?;
             insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
-            :hourglass: Trying commit pr-1-sha with merge merge-1-pr-1…
+            :hourglass: Trying commit a1b2c3d4e5f60718293a4b5c6d7e8f9012345678 with merge merge-0-a1b2c3d4e5f60718293a4b5c6d7e8f9012345678…
 
             To cancel the try build, run the command `@bors try cancel`.
             ");
@@ -660,42 +1054,250 @@ try-job: Bar
         .await;
     }
 
-    // Make sure that the second try command knows about the result of the first one.
     #[sqlx::test]
-    async fn multiple_commands_in_one_comment(pool: sqlx::PgPool) {
+    async fn try_merge_last_parent_unknown(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
             tester
-                .post_comment(
-                    r#"
-@bors try
-@bors try cancel
-"#,
-                )
+                .post_comment("@bors try parent=last")
                 .await?;
-            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
-            :hourglass: Trying commit pr-1-sha with merge merge-0-pr-1…
-
-            To cancel the try build, run the command `@bors try cancel`.
-            ");
-            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @"Try build cancelled. Cancelled workflows:");
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":exclamation: There was no previous build. Please set an explicit parent or remove the `parent=last` argument to use the default parent.");
             Ok(())
         })
-        .await;
+            .await;
     }
 
     #[sqlx::test]
-    async fn try_cancel_previous_build_running_workflows(pool: sqlx::PgPool) {
+    async fn try_merge_branch_parent(pool: sqlx::PgPool) {
         let gh = run_test(pool, async |tester: &mut BorsTester| {
-            tester.post_comment("@bors try").await?;
+            tester.create_branch("beta").await;
+            tester.post_comment("@bors try parent=beta").await?;
             tester.expect_comments((), 1).await;
-            tester
-                .workflow_start(WorkflowRunData::from(tester.try_branch().await).with_run_id(123))
-                .await?;
-
-            tester.post_comment("@bors try").await?;
-            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
-            :hourglass: Trying commit pr-1-sha with merge merge-1-pr-1…
-
+            Ok(())
+        })
+        .await;
+        gh.check_sha_history(
+            default_repo_name(),
+            TRY_MERGE_BRANCH_NAME,
+            &["beta-initial", "merge-0-pr-1"],
+        );
+    }
+
+    #[sqlx::test]
+    async fn try_merge_branch_parent_unknown(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try parent=beta").await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":exclamation: Cannot find the parent branch `beta`. Please check that it exists in this repository.");
+            assert!(tester.db().get_pending_builds(&default_repo_name()).await?.is_empty());
+            Ok(())
+        })
+        .await;
+    }
+
+    const WORKFLOW: &str = r#"
+name: CI
+on: push
+jobs:
+  build:
+    runs-on: ubuntu-latest
+  lint:
+    name: Lint
+    runs-on: ubuntu-latest
+"#;
+
+    #[sqlx::test]
+    async fn try_disabled(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("try_enabled = false"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors try").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":no_entry_sign: This repository has try builds disabled.");
+                assert!(tester.db().get_pending_builds(&default_repo_name()).await?.is_empty());
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn try_jobs_unknown(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("validate_try_jobs = true"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .modify_repo(&default_repo_name(), |repo| {
+                        repo.workflow_files = vec![("ci.yml".to_string(), WORKFLOW.to_string())]
+                    })
+                    .await;
+                tester.post_comment("@bors try jobs=build,buidl").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":exclamation: Unknown try jobs: `buidl`. Available jobs: `Lint`, `build`, `lint`.");
+                assert!(tester.db().get_pending_builds(&default_repo_name()).await?.is_empty());
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn try_jobs_known(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("validate_try_jobs = true"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .modify_repo(&default_repo_name(), |repo| {
+                        repo.workflow_files = vec![("ci.yml".to_string(), WORKFLOW.to_string())]
+                    })
+                    .await;
+                tester.post_comment("@bors try jobs=build,Lint").await?;
+                tester.expect_comments((), 1).await;
+                assert_eq!(
+                    tester
+                        .db()
+                        .get_pending_builds(&default_repo_name())
+                        .await?
+                        .len(),
+                    1
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn try_jobs_not_validated_by_default(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .modify_repo(&default_repo_name(), |repo| {
+                    repo.workflow_files = vec![("ci.yml".to_string(), WORKFLOW.to_string())]
+                })
+                .await;
+            tester.post_comment("@bors try jobs=foo").await?;
+            tester.expect_comments((), 1).await;
+            assert_eq!(
+                tester
+                    .db()
+                    .get_pending_builds(&default_repo_name())
+                    .await?
+                    .len(),
+                1
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn try_merge_conflict(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.modify_branch(TRY_MERGE_BRANCH_NAME, |branch| branch.merge_conflict = true).await;
+            tester
+                .post_comment("@bors try")
+                .await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r###"
+            :lock: Merge conflict
+
+            This pull request and the base branch diverged in a way that cannot
+             be automatically merged. Please rebase on top of the latest base
+             branch, and let the reviewer approve again.
+
+            <details><summary>How do I rebase?</summary>
+
+            Assuming `self` is your fork and `upstream` is this repository,
+             you can resolve the conflict following these steps:
+
+            1. `git checkout pr-1` *(switch to your branch)*
+            2. `git fetch upstream HEAD` *(retrieve the latest base branch)*
+            3. `git rebase upstream/HEAD -p` *(rebase on top of it)*
+            4. Follow the on-screen instruction to resolve conflicts (check `git status` if you got lost).
+            5. `git push self pr-1 --force-with-lease` *(update this PR)*
+
+            You may also read
+             [*Git Rebasing to Resolve Conflicts* by Drew Blessing](http://blessing.io/git/git-rebase/open-source/2015/08/23/git-rebasing-to-resolve-conflicts.html)
+             for a short tutorial.
+
+            Please avoid the ["**Resolve conflicts**" button](https://help.github.com/articles/resolving-a-merge-conflict-on-github/) on GitHub.
+             It uses `git merge` instead of `git rebase` which makes the PR commit history more difficult to read.
+
+            Sometimes step 4 will complete without asking for resolution. This is usually due to difference between how `Cargo.lock` conflict is
+            handled during merge and rebase. This is normal, and you should still perform step 5 to update this PR.
+
+            </details>
+            "###);
+            Ok(())
+        })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn try_merge_insert_into_db(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+            assert!(
+                tester
+                    .db()
+                    .find_build(
+                        &default_repo_name(),
+                        TRY_BRANCH_NAME.to_string(),
+                        CommitSha(tester.try_branch().await.get_sha().to_string()),
+                    )
+                    .await?
+                    .is_some()
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn try_cancel_previous_build_no_workflows(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors try").await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+            :hourglass: Trying commit pr-1-sha with merge merge-1-pr-1…
+
+            To cancel the try build, run the command `@bors try cancel`.
+            ");
+            Ok(())
+        })
+        .await;
+    }
+
+    // Make sure that the second try command knows about the result of the first one.
+    #[sqlx::test]
+    async fn multiple_commands_in_one_comment(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .post_comment(
+                    r#"
+@bors try
+@bors try cancel
+"#,
+                )
+                .await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+            :hourglass: Trying commit pr-1-sha with merge merge-0-pr-1…
+
+            To cancel the try build, run the command `@bors try cancel`.
+            ");
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @"Try build cancelled. Cancelled workflows:");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn try_cancel_previous_build_running_workflows(pool: sqlx::PgPool) {
+        let gh = run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+            tester
+                .workflow_start(WorkflowRunData::from(tester.try_branch().await).with_run_id(123))
+                .await?;
+
+            tester.post_comment("@bors try").await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+            :hourglass: Trying commit pr-1-sha with merge merge-1-pr-1…
+
             (The previously running try build was automatically cancelled.)
 
             To cancel the try build, run the command `@bors try cancel`.
@@ -740,6 +1342,86 @@ try-job: Bar
         .await;
     }
 
+    #[sqlx::test]
+    async fn try_concurrent_builds_of_different_jobs(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try jobs=Foo").await?;
+            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors try jobs=Bar").await?;
+            let comment = tester.get_next_comment_text(()).await?;
+
+            let builds = tester.db().get_pending_builds(&default_repo_name()).await?;
+            assert_eq!(builds.len(), 2);
+            let build_id = builds.iter().map(|build| build.id).max().unwrap();
+            assert!(comment.contains(&format!(
+                "Other try builds of this PR are still running. To cancel only this try build, run the command `@bors try cancel {build_id}`."
+            )));
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn try_same_jobs_replace_running_build(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try jobs=Foo,Bar").await?;
+            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors try jobs=Bar,Foo").await?;
+            tester.expect_comments((), 1).await;
+            assert_eq!(
+                tester
+                    .db()
+                    .get_pending_builds(&default_repo_name())
+                    .await?
+                    .len(),
+                1
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn try_cancel_concurrent_build_by_id(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try jobs=Foo").await?;
+            tester.expect_comments((), 1).await;
+            let first_build = tester.db().get_pending_builds(&default_repo_name()).await?[0].id;
+            tester.post_comment("@bors try jobs=Bar").await?;
+            tester.expect_comments((), 1).await;
+
+            tester
+                .post_comment(format!("@bors try cancel {first_build}").as_str())
+                .await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @"Try build cancelled. Cancelled workflows:");
+            let builds = tester.db().get_pending_builds(&default_repo_name()).await?;
+            assert_eq!(builds.len(), 1);
+            assert_ne!(builds[0].id, first_build);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn try_cancel_unknown_build_id(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors try cancel 1000").await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":exclamation: There is currently no try build in progress.");
+            assert_eq!(
+                tester
+                    .db()
+                    .get_pending_builds(&default_repo_name())
+                    .await?
+                    .len(),
+                1
+            );
+            Ok(())
+        })
+        .await;
+    }
+
     #[sqlx::test]
     async fn try_cancel_no_running_build(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
@@ -779,21 +1461,83 @@ try-job: Bar
         gh.check_cancelled_workflows(default_repo_name(), &[123, 124]);
     }
 
+    #[sqlx::test]
+    async fn try_cancel_cancels_unknown_workflows(pool: sqlx::PgPool) {
+        let gh = run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+
+            let branch = tester.try_branch().await;
+            tester
+                .workflow_event(WorkflowEvent::started(
+                    WorkflowRunData::from(branch.clone()).with_run_id(123),
+                ))
+                .await?;
+            // The webhook of this workflow has not arrived yet
+            tester
+                .modify_repo(&default_repo_name(), |repo| {
+                    repo.update_workflow_run(
+                        WorkflowRunData::from(branch.clone()).with_run_id(124),
+                        WorkflowStatus::Pending,
+                    )
+                })
+                .await;
+            tester.post_comment("@bors try cancel").await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+            Try build cancelled. Cancelled workflows:
+            - https://github.com/rust-lang/borstest/actions/runs/123
+            - https://github.com/rust-lang/borstest/actions/runs/124
+            ");
+            let audit_log = tester
+                .db()
+                .get_audit_log(&default_repo_name(), None, 10)
+                .await?;
+            assert!(audit_log.iter().any(|entry| {
+                entry.action == "try_build_cancelled"
+                    && entry.details.as_deref()
+                        == Some("build 1 (merge-0-pr-1), 2 workflows cancelled")
+            }));
+            Ok(())
+        })
+        .await;
+        gh.check_cancelled_workflows(default_repo_name(), &[123, 124]);
+    }
+
+    #[sqlx::test]
+    async fn try_cancel_resets_try_merge_branch(pool: sqlx::PgPool) {
+        let gh = run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors try cancel").await?;
+            tester.expect_comments((), 1).await;
+            Ok(())
+        })
+        .await;
+        gh.check_sha_history(
+            default_repo_name(),
+            TRY_MERGE_BRANCH_NAME,
+            &["main-sha1", "merge-0-pr-1", "main-sha1"],
+        );
+        // Pushing to the try branch would start its CI again
+        gh.check_sha_history(default_repo_name(), TRY_BRANCH_NAME, &["merge-0-pr-1"]);
+    }
+
     #[sqlx::test]
     async fn try_cancel_error(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
             tester.modify_repo(&default_repo_name(), |repo| repo.workflow_cancel_error = true).await;
             tester.post_comment("@bors try").await?;
             tester.expect_comments((), 1).await;
+            let branch = tester.try_branch().await;
             tester
-                .workflow_event(WorkflowEvent::started(tester.try_branch().await))
+                .workflow_event(WorkflowEvent::started(branch.clone()))
                 .await?;
             tester.post_comment("@bors try cancel").await?;
             insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @"Try build was cancelled. It was not possible to cancel some workflows.");
             let build = tester.db().find_build(
                 &default_repo_name(),
-                tester.try_branch().await.get_name().to_string(),
-                tester.try_branch().await.get_sha().to_string().into()
+                branch.get_name().to_string(),
+                branch.get_sha().to_string().into()
             ).await?.expect("build not found");
             assert_eq!(build.status, BuildStatus::Cancelled);
 
@@ -1086,6 +1830,51 @@ try_failed = ["+foo", "+bar", "-baz"]
         .await;
     }
 
+    #[sqlx::test]
+    async fn hide_outdated_try_build_result_comment(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("hide_outdated_comments = true"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors try").await?;
+                tester.expect_comments((), 1).await;
+                tester
+                    .workflow_full_failure(tester.try_branch().await)
+                    .await?;
+                let result = tester.get_next_comment(()).await?;
+
+                tester.post_comment("@bors try").await?;
+                tester.expect_comments((), 1).await;
+                tester
+                    .expect_hidden_comment(&result, HideCommentReason::Outdated)
+                    .await;
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn keep_try_build_result_comment_by_default(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+            tester
+                .workflow_full_success(tester.try_branch().await)
+                .await?;
+            let result = tester.get_next_comment(()).await?;
+
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+            assert!(
+                tester
+                    .get_comment_by_node_id(&result.node_id.unwrap())
+                    .await
+                    .is_none_or(|comment| comment.hide_reason.is_none())
+            );
+            Ok(())
+        })
+        .await;
+    }
+
     #[sqlx::test]
     async fn update_try_build_started_comment_after_workflow_starts(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
@@ -1111,4 +1900,268 @@ try_failed = ["+foo", "+bar", "-baz"]
         })
         .await;
     }
+
+    #[sqlx::test]
+    async fn try_fork_pr_allowed_by_default(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .modify_pr_state((), |pr| {
+                    pr.fork = Some(GithubRepoName::new("contributor", "borstest"))
+                })
+                .await;
+            tester.post_comment("@bors try").await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+            :hourglass: Trying commit pr-1-sha with merge merge-0-pr-1…
+
+            To cancel the try build, run the command `@bors try cancel`.
+            ");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn try_draft_pr_denied(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.set_pr_status_draft(()).await?;
+            tester.post_comment("@bors try").await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":clipboard: Try builds cannot be started for draft PRs. Mark the PR as ready for review first.");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn try_fork_pr_denied(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+fork_try_builds = "deny"
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .modify_pr_state((), |pr| {
+                        pr.fork = Some(GithubRepoName::new("contributor", "borstest"))
+                    })
+                    .await;
+                tester.post_comment("@bors try").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":lock: Try builds are not allowed for pull requests opened from forks in this repository.");
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn try_fork_pr_denied_localized(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+fork_try_builds = "deny"
+language = "de"
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .modify_pr_state((), |pr| {
+                        pr.fork = Some(GithubRepoName::new("contributor", "borstest"))
+                    })
+                    .await;
+                tester.post_comment("@bors try").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":lock: Try-Builds sind in diesem Repository für Pull Requests aus Forks nicht erlaubt.");
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn try_fork_pr_restricted_environment(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+fork_try_builds = { restricted = { environment = "untrusted" } }
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .modify_pr_state((), |pr| {
+                        pr.fork = Some(GithubRepoName::new("contributor", "borstest"))
+                    })
+                    .await;
+                tester.post_comment("@bors try").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                :hourglass: Trying commit pr-1-sha with merge merge-0-pr-1…
+
+                :lock: This PR comes from a fork, so its try build only runs workflow jobs that use the `untrusted` environment, and its try merge commit is marked with it.
+
+                To cancel the try build, run the command `@bors try cancel`.
+                ");
+                insta::assert_snapshot!(tester.get_branch_commit_message(&tester.try_branch().await).await, @r"
+                Auto merge of #1 - pr-1, r=<try>
+                Title of PR 1
+
+
+                try-environment: untrusted
+
+                Bors-Build-Id: 1
+                ");
+                Ok(())
+            })
+            .await;
+    }
+
+    const RESTRICTED_WORKFLOW: &str = r#"
+on:
+  push:
+    branches:
+      - automation/bors/try
+jobs:
+  build:
+    runs-on: ubuntu-latest
+    environment: untrusted
+  test:
+    runs-on: ubuntu-latest
+"#;
+
+    const PULL_REQUEST_WORKFLOW: &str = r#"
+on: pull_request
+jobs:
+  lint:
+    runs-on: ubuntu-latest
+"#;
+
+    #[sqlx::test]
+    async fn try_fork_pr_restricted_environment_unrestricted_jobs(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+fork_try_builds = { restricted = { environment = "untrusted" } }
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .modify_repo(&default_repo_name(), |repo| {
+                        repo.workflow_files = vec![
+                            ("ci.yml".to_string(), RESTRICTED_WORKFLOW.to_string()),
+                            ("lint.yml".to_string(), PULL_REQUEST_WORKFLOW.to_string()),
+                        ]
+                    })
+                    .await;
+                tester
+                    .modify_pr_state((), |pr| {
+                        pr.fork = Some(GithubRepoName::new("contributor", "borstest"))
+                    })
+                    .await;
+                tester.post_comment("@bors try").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @":lock: Try builds of pull requests opened from forks have to run in the `untrusted` environment, but the following workflow jobs do not use it: `test`.");
+                let pr = tester
+                    .db()
+                    .get_pull_request(&default_repo_name(), PullRequestNumber(1))
+                    .await?
+                    .unwrap();
+                assert!(pr.try_build.is_none());
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn try_fork_pr_restricted_environment_restricted_jobs(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+fork_try_builds = { restricted = { environment = "untrusted" } }
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                let workflow = RESTRICTED_WORKFLOW.replace(
+                    "  test:\n",
+                    "  test:\n    environment:\n      name: untrusted\n",
+                );
+                tester
+                    .modify_repo(&default_repo_name(), |repo| {
+                        repo.workflow_files = vec![
+                            ("ci.yml".to_string(), workflow),
+                            ("lint.yml".to_string(), PULL_REQUEST_WORKFLOW.to_string()),
+                        ]
+                    })
+                    .await;
+                tester
+                    .modify_pr_state((), |pr| {
+                        pr.fork = Some(GithubRepoName::new("contributor", "borstest"))
+                    })
+                    .await;
+                tester.post_comment("@bors try").await?;
+                tester.expect_comments((), 1).await;
+                insta::assert_snapshot!(tester.get_branch_commit_message(&tester.try_branch().await).await, @r"
+                Auto merge of #1 - pr-1, r=<try>
+                Title of PR 1
+
+
+                try-environment: untrusted
+
+                Bors-Build-Id: 1
+                ");
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn try_non_fork_pr_ignores_restricted_environment(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+fork_try_builds = { restricted = { environment = "untrusted" } }
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors try").await?;
+                tester.expect_comments((), 1).await;
+                insta::assert_snapshot!(tester.get_branch_commit_message(&tester.try_branch().await).await, @r"
+                Auto merge of #1 - pr-1, r=<try>
+                Title of PR 1
+
+                Bors-Build-Id: 1
+                ");
+                Ok(())
+            })
+            .await;
+    }
+
+    /// Makes `sha` an older commit of the `main` branch, so that it is reachable from it.
+    async fn set_old_main_commit(tester: &mut BorsTester, sha: &str) {
+        tester
+            .modify_branch("main", |branch| {
+                let current = branch.get_sha().to_string();
+                branch.set_to_sha(sha);
+                branch.set_to_sha(&current);
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn try_build_dry_run(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config("dry_run = true"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors try").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r":clipboard: Dry run: bors would now try commit pr-1-sha merged into main-sha1. Nothing was pushed.");
+                let pr = tester
+                    .db()
+                    .get_pull_request(&default_repo_name(), PullRequestNumber(1))
+                    .await?
+                    .unwrap();
+                assert!(pr.try_build.is_none());
+                let repo = tester.get_repo(&default_repo_name()).await;
+                assert!(repo.lock().get_branch_by_name(TRY_BRANCH_NAME).is_none());
+                assert!(
+                    repo.lock()
+                        .get_branch_by_name(TRY_MERGE_BRANCH_NAME)
+                        .is_none()
+                );
+                Ok(())
+            })
+            .await;
+    }
 }
@@ -1,19 +1,28 @@
 use super::trybuild::TRY_BRANCH_NAME;
 use crate::PgDbClient;
 use crate::bors::comment::{
-    CommentTag, append_workflow_links_to_comment, build_failed_comment, try_build_succeeded_comment,
+    CommentTag, append_workflow_links_to_comment, build_failed_comment, rollup_failed_comment,
+    spurious_failure_retry_comment, try_build_succeeded_comment,
 };
-use crate::bors::event::{WorkflowRunCompleted, WorkflowRunStarted};
-use crate::bors::handlers::labels::handle_label_trigger;
+use crate::bors::event::{
+    BuildResultReported, CommitStatusChanged, WorkflowRunCompleted, WorkflowRunStarted,
+};
+use crate::bors::handlers::labels::label_trigger_actions;
 use crate::bors::handlers::{BuildType, is_bors_observed_branch};
-use crate::bors::handlers::{get_build_type, hide_try_build_started_comments};
+use crate::bors::handlers::{get_build_type, hide_try_build_started_comments, tag_status_comment};
 use crate::bors::merge_queue::MergeQueueSender;
-use crate::bors::{FailedWorkflowRun, RepositoryState, WorkflowRun};
+use crate::bors::outbox::{comment_action, deliver_outbox_actions, post_comment_batch};
+use crate::bors::workers::run_in_background;
+use crate::bors::{FailedWorkflowRun, RepositoryState, RollupMode, WorkflowRun};
 use crate::database::{
-    BuildModel, BuildStatus, PullRequestModel, QueueStatus, WorkflowModel, WorkflowStatus,
+    BuildModel, BuildStatus, OutboxAction, PullRequestModel, QueueStatus, RunId, WorkflowModel,
+    WorkflowStatus, WorkflowType,
 };
 use crate::github::api::client::GithubRepositoryClient;
-use crate::github::{CommitSha, LabelTrigger};
+use crate::github::api::operations::ForcePush;
+use crate::github::{CommitSha, LabelTrigger, PullRequestNumber};
+use crate::utils::text::rollup_constituents;
+use itertools::Itertools;
 use octocrab::models::CheckRunId;
 use octocrab::models::workflows::{Conclusion, Job, Status};
 use octocrab::params::checks::CheckRunConclusion;
@@ -56,6 +65,14 @@ pub(super) async fn handle_workflow_started(
         return Ok(());
     }
 
+    // The build is tracked using commit statuses instead
+    if !get_required_status_contexts(&repo, &db, &build)
+        .await?
+        .is_empty()
+    {
+        return Ok(());
+    }
+
     tracing::info!("Storing workflow started into DB");
     db.create_workflow(
         &build,
@@ -74,6 +91,57 @@ pub(super) async fn handle_workflow_started(
     Ok(())
 }
 
+/// Returns the commit status contexts that have to succeed for the given build to pass.
+/// These are the configured `required_status_contexts`, and for auto builds also the contexts
+/// required by the path queue of the PR, and the status checks required by the branch protection
+/// of the base branch, if `branch_protection_checks` is enabled.
+///
+/// If there are no such contexts, the build is tracked using GitHub Actions workflows instead.
+async fn get_required_status_contexts(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    build: &BuildModel,
+) -> anyhow::Result<Vec<String>> {
+    let (mut contexts, branch_protection_checks, path_queues) = {
+        let config = repo.config.load();
+        (
+            config.required_status_contexts.clone(),
+            config.branch_protection_checks,
+            config.path_queues.clone(),
+        )
+    };
+    let needs_pr = branch_protection_checks
+        || path_queues
+            .values()
+            .any(|queue| !queue.required_status_contexts.is_empty());
+    if needs_pr
+        && get_build_type(&build.branch) == Some(BuildType::Auto)
+        && let Some(pr) = db.find_pr_by_build(build).await?
+    {
+        let mut required = vec![];
+        if let Some(queue) = pr
+            .path_queue
+            .as_ref()
+            .and_then(|queue| path_queues.get(queue))
+        {
+            required.extend(queue.required_status_contexts.iter().cloned());
+        }
+        if branch_protection_checks {
+            required.extend(
+                repo.client
+                    .get_required_status_checks(&pr.base_branch)
+                    .await?,
+            );
+        }
+        for context in required {
+            if !contexts.contains(&context) {
+                contexts.push(context);
+            }
+        }
+    }
+    Ok(contexts)
+}
+
 async fn add_workflow_links_to_try_build_start_comment(
     repo: Arc<RepositoryState>,
     db: Arc<PgDbClient>,
@@ -197,9 +265,17 @@ async fn maybe_complete_build(
         return Ok(());
     };
 
+    // The build is tracked using commit statuses instead
+    if !get_required_status_contexts(repo, db, &build)
+        .await?
+        .is_empty()
+    {
+        return Ok(());
+    }
+
     // Load the workflow runs that we know about from the DB. We know about workflow runs for
     // which we have received a started or a completed event.
-    let mut db_workflow_runs = db.get_workflows_for_build(&build).await?;
+    let db_workflow_runs = db.get_workflows_for_build(&build).await?;
     tracing::debug!("Workflow runs from DB: {db_workflow_runs:?}");
 
     // If the workflow run was a success, check if we're still waiting for some other workflow run.
@@ -241,19 +317,214 @@ async fn maybe_complete_build(
     // Below this point, we assume that the build has completed.
     // Either all workflow runs attached to the corresponding check suite are completed or there
     // was at least one failure.
-
     let has_failure = db_workflow_runs
         .iter()
         .any(|check| matches!(check.status, WorkflowStatus::Failure));
-    let build_succeeded = !has_failure;
+
+    complete_build(
+        repo,
+        db,
+        &build,
+        &pr,
+        build_type,
+        db_workflow_runs,
+        !has_failure,
+        merge_queue_tx,
+        error_context,
+    )
+    .await
+}
+
+/// Handle a build result reported by an external CI system through the build result API.
+/// The reported result is stored as an external workflow of the build, and it decides the
+/// outcome of the whole build.
+pub(super) async fn handle_build_result_reported(
+    repo: Arc<RepositoryState>,
+    db: Arc<PgDbClient>,
+    payload: BuildResultReported,
+    merge_queue_tx: &MergeQueueSender,
+) -> anyhow::Result<()> {
+    let Some(build) = db.get_build(payload.build_id).await? else {
+        tracing::warn!(
+            "Received a result for an unknown build {}",
+            payload.build_id
+        );
+        return Ok(());
+    };
+
+    // The build might have been completed or cancelled since the result was accepted
+    if build.status != BuildStatus::Pending {
+        tracing::warn!(
+            "Received a result for an already completed build {}",
+            build.id
+        );
+        return Ok(());
+    }
+
+    let Some(build_type) = get_build_type(&build.branch) else {
+        return Ok(());
+    };
+    let Some(pr) = db.find_pr_by_build(&build).await? else {
+        tracing::warn!("Cannot find PR for build {}", build.commit_sha);
+        return Ok(());
+    };
+
+    tracing::info!(
+        "Storing reported result of build {} ({:?}) into DB",
+        build.id,
+        payload.status
+    );
+    // Reported results do not correspond to any GitHub workflow run, so they have no run ID
+    db.create_workflow(
+        &build,
+        payload.name,
+        payload.url,
+        RunId(0),
+        WorkflowType::External,
+        payload.status,
+    )
+    .await?;
+    db.record_build_artifacts(&build, &payload.artifacts)
+        .await?;
+
+    let workflow_runs = db.get_workflows_for_build(&build).await?;
+    complete_build(
+        repo.as_ref(),
+        db.as_ref(),
+        &build,
+        &pr,
+        build_type,
+        workflow_runs,
+        payload.status == WorkflowStatus::Success,
+        merge_queue_tx,
+        None,
+    )
+    .await
+}
+
+/// Handle a change of a commit status (or check run) reported by an external CI system.
+/// This is only used for builds with required status contexts (see
+/// [`get_required_status_contexts`]): they succeed once all the required contexts have
+/// succeeded, and fail as soon as one of them fails.
+pub(super) async fn handle_commit_status_changed(
+    repo: Arc<RepositoryState>,
+    db: Arc<PgDbClient>,
+    payload: CommitStatusChanged,
+    merge_queue_tx: &MergeQueueSender,
+) -> anyhow::Result<()> {
+    if !repo.config.load().uses_status_contexts() {
+        return Ok(());
+    }
+    let Some(branch) = payload
+        .branches
+        .iter()
+        .find(|branch| is_bors_observed_branch(branch))
+    else {
+        return Ok(());
+    };
+    let Some(build_type) = get_build_type(branch) else {
+        return Ok(());
+    };
+
+    let Some(build) = db
+        .find_build(
+            &payload.repository,
+            branch.clone(),
+            payload.commit_sha.clone(),
+        )
+        .await?
+    else {
+        tracing::warn!(
+            "Received commit status `{}` for an unknown build: {}",
+            payload.context,
+            payload.commit_sha
+        );
+        return Ok(());
+    };
+    if build.status != BuildStatus::Pending {
+        return Ok(());
+    }
+    let Some(pr) = db.find_pr_by_build(&build).await? else {
+        tracing::warn!("Cannot find PR for build {}", build.commit_sha);
+        return Ok(());
+    };
+    let required_contexts = get_required_status_contexts(&repo, &db, &build).await?;
+    if !required_contexts.contains(&payload.context) {
+        return Ok(());
+    }
+
+    tracing::info!(
+        "Storing status {:?} of context `{}` into DB",
+        payload.status,
+        payload.context
+    );
+    // Workflow URLs have to be unique within a build
+    let url = payload.url.unwrap_or_else(|| {
+        format!(
+            "{}/commit/{}#{}",
+            repo.client.repository_url(),
+            payload.commit_sha,
+            payload.context
+        )
+    });
+    db.upsert_external_workflow(&build, &payload.context, &url, payload.status)
+        .await?;
+
+    let workflow_runs = db.get_workflows_for_build(&build).await?;
+    let context_status = |context: &str| {
+        workflow_runs
+            .iter()
+            .find(|workflow| workflow.name == context)
+            .map(|workflow| workflow.status)
+    };
+    let has_failure = required_contexts
+        .iter()
+        .any(|context| context_status(context) == Some(WorkflowStatus::Failure));
+    let all_succeeded = required_contexts
+        .iter()
+        .all(|context| context_status(context) == Some(WorkflowStatus::Success));
+    if !has_failure && !all_succeeded {
+        tracing::info!("Some required contexts are not finished yet, waiting for the next status");
+        return Ok(());
+    }
+
+    complete_build(
+        repo.as_ref(),
+        db.as_ref(),
+        &build,
+        &pr,
+        build_type,
+        workflow_runs,
+        !has_failure,
+        merge_queue_tx,
+        None,
+    )
+    .await
+}
+
+/// Mark a pending build as completed, update its check run and notify the PR about the result.
+#[allow(clippy::too_many_arguments)]
+async fn complete_build(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    build: &BuildModel,
+    pr: &PullRequestModel,
+    build_type: BuildType,
+    mut db_workflow_runs: Vec<WorkflowModel>,
+    build_succeeded: bool,
+    merge_queue_tx: &MergeQueueSender,
+    error_context: Option<String>,
+) -> anyhow::Result<()> {
     let pr_num = pr.number;
+    let commit_sha = CommitSha(build.commit_sha.clone());
 
     let status = if build_succeeded {
         BuildStatus::Success
     } else {
         BuildStatus::Failure
     };
-    let trigger = match build_type {
+    let mut spurious_retry = None;
+    let mut trigger = match build_type {
         BuildType::Try => {
             if !build_succeeded {
                 Some(LabelTrigger::TryBuildFailed)
@@ -268,30 +539,12 @@ async fn maybe_complete_build(
         }),
     };
 
-    db.update_build_status(&build, status).await?;
-    if let Some(trigger) = trigger {
-        handle_label_trigger(repo, pr_num, trigger).await?;
-    }
-
-    if let Some(check_run_id) = build.check_run_id {
-        let (status, conclusion) = if build_succeeded {
-            (CheckRunStatus::Completed, Some(CheckRunConclusion::Success))
-        } else {
-            (CheckRunStatus::Completed, Some(CheckRunConclusion::Failure))
-        };
-
-        if let Err(error) = repo
-            .client
-            .update_check_run(CheckRunId(check_run_id as u64), status, conclusion)
-            .await
-        {
-            tracing::error!("Could not update check run {check_run_id}: {error:?}");
-        }
-    }
-
-    // Trigger merge queue when an auto build completes
-    if build_type == BuildType::Auto {
-        merge_queue_tx.notify().await?;
+    // Artifacts have to be stored before the build is marked as completed, because the merge
+    // queue mentions them once it notices the successful auto build.
+    if build_succeeded
+        && let Err(error) = record_workflow_artifacts(repo, db, build, &db_workflow_runs).await
+    {
+        tracing::error!("Cannot store artifacts of build {}: {error:?}", build.id);
     }
 
     db_workflow_runs.sort_by(|a, b| a.name.cmp(&b.name));
@@ -300,10 +553,20 @@ async fn maybe_complete_build(
         tracing::info!("Build succeeded for PR {pr_num}");
 
         if build_type == BuildType::Try {
-            Some(try_build_succeeded_comment(
+            let artifacts = db.get_artifacts_for_build(build).await?;
+            let comment = try_build_succeeded_comment(
                 &db_workflow_runs,
-                payload.commit_sha,
+                &artifacts,
+                commit_sha,
                 CommitSha(build.parent.clone()),
+                repo.repository(),
+                pr_num,
+                repo.config.load().try_build_comment_template.as_deref(),
+            );
+            Some(tag_status_comment(
+                repo,
+                comment,
+                CommentTag::TryBuildCompleted,
             ))
         } else {
             // Merge queue will post the build succeeded comment
@@ -315,14 +578,19 @@ async fn maybe_complete_build(
         // Download failed jobs
         let mut workflow_runs: Vec<FailedWorkflowRun> = vec![];
         for workflow_run in db_workflow_runs {
-            let failed_jobs = match get_failed_jobs(repo, &workflow_run).await {
-                Ok(jobs) => jobs,
-                Err(error) => {
-                    tracing::error!(
-                        "Cannot download jobs for workflow run {}: {error:?}",
-                        workflow_run.run_id
-                    );
-                    vec![]
+            // Only GitHub Actions workflow runs have jobs that we can download
+            let failed_jobs = if workflow_run.workflow_type != WorkflowType::Github {
+                vec![]
+            } else {
+                match get_failed_jobs(repo, &workflow_run).await {
+                    Ok(jobs) => jobs,
+                    Err(error) => {
+                        tracing::error!(
+                            "Cannot download jobs for workflow run {}: {error:?}",
+                            workflow_run.run_id
+                        );
+                        vec![]
+                    }
                 }
             };
             workflow_runs.push(FailedWorkflowRun {
@@ -331,22 +599,238 @@ async fn maybe_complete_build(
             })
         }
 
-        Some(build_failed_comment(
-            repo.repository(),
-            payload.commit_sha,
-            workflow_runs,
-            error_context,
-        ))
+        if build_type == BuildType::Auto
+            && error_context.is_none()
+            && let Some(retry) = find_spurious_failure_retry(repo, db, pr, &workflow_runs).await
+        {
+            tracing::info!("Auto build of PR {pr_num} failed spuriously, retrying it");
+            // The PR will be tested again, so its failure is not announced
+            trigger = None;
+            let comment =
+                spurious_failure_retry_comment(&retry.patterns, retry.attempt, retry.max_attempts);
+            spurious_retry = Some(retry);
+            Some(comment)
+        } else {
+            let comment =
+                build_failed_comment(repo.repository(), commit_sha, workflow_runs, error_context);
+            let tag = match build_type {
+                BuildType::Try => CommentTag::TryBuildCompleted,
+                BuildType::Auto => CommentTag::AutoBuildFailed,
+            };
+            Some(tag_status_comment(repo, comment, tag))
+        }
     };
 
+    // The build status is updated in the same transaction in which its GitHub side effects are
+    // recorded, so that they are performed even if bors crashes in the meantime.
+    let mut actions = trigger
+        .map(|trigger| label_trigger_actions(repo, trigger))
+        .unwrap_or_default();
+    if let Some(check_run_id) = build.check_run_id {
+        actions.push(OutboxAction::CompleteCheckRun {
+            check_run_id: check_run_id as u64,
+            success: build_succeeded,
+        });
+    }
+    if let Some(comment) = comment_opt {
+        actions.push(comment_action(repo, comment));
+    }
+    let entries = db
+        .update_build_status_with_outbox(build, status, pr, &actions)
+        .await?;
+
+    let rollup_failed =
+        build_type == BuildType::Auto && !build_succeeded && spurious_retry.is_none();
+    if let Some(retry) = spurious_retry {
+        let reason = format!(
+            "Spurious failure matching {}",
+            retry.patterns.iter().map(|p| format!("`{p}`")).join(", ")
+        );
+        db.record_build_retry(pr, build, None, Some(&reason))
+            .await?;
+        // The merge queue will start a new auto build of the PR
+        db.clear_auto_build(pr).await?;
+    }
+
+    // Trigger merge queue when an auto build completes
+    if build_type == BuildType::Auto {
+        merge_queue_tx.notify().await?;
+    }
+
     if build_type == BuildType::Try {
-        hide_try_build_started_comments(repo, db, &pr).await?;
+        hide_try_build_started_comments(repo, db, pr).await?;
     }
 
-    if let Some(comment) = comment_opt {
-        repo.client.post_comment(pr_num, comment).await?;
+    deliver_outbox_actions(repo, db, entries).await?;
+
+    if rollup_failed && let Err(error) = notify_rollup_constituents(repo, db, pr, build).await {
+        tracing::error!("Cannot notify PRs merged into rollup {pr_num}: {error:?}");
+    }
+    Ok(())
+}
+
+/// If the given PR is a rollup, notifies the PRs merged into it that its auto build has failed,
+/// so that their authors can check whether they have caused the failure.
+async fn notify_rollup_constituents(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    rollup: &PullRequestModel,
+    build: &BuildModel,
+) -> anyhow::Result<()> {
+    let description = repo.client.get_pull_request(rollup.number).await?.message;
+    let constituents = rollup_constituents(&description);
+    if constituents.is_empty() {
+        return Ok(());
+    }
+    tracing::info!(
+        "Auto build of rollup {} failed, notifying PRs {constituents:?}",
+        rollup.number
+    );
+
+    let rollup_failure_iffy = repo.config.load().rollup_failure_iffy;
+    let commit_sha = CommitSha(build.commit_sha.clone());
+    let mut comments = vec![];
+    for number in constituents.into_iter().map(PullRequestNumber) {
+        if number == rollup.number {
+            continue;
+        }
+        let pr = db.get_pull_request(repo.repository(), number).await?;
+        // PRs that are already `iffy` or `never` are not included in rollups by default
+        let set_iffy = rollup_failure_iffy
+            && pr.as_ref().is_some_and(|pr| {
+                matches!(
+                    pr.rollup,
+                    None | Some(RollupMode::Maybe | RollupMode::Always)
+                )
+            });
+        if set_iffy && let Some(pr) = &pr {
+            db.set_rollup(pr, RollupMode::Iffy).await?;
+        }
+        comments.push((
+            number,
+            rollup_failed_comment(rollup.number, &commit_sha, set_iffy),
+        ));
+    }
+    post_comment_batch(repo, db, comments).await;
+    Ok(())
+}
+
+/// An automatic retry of an auto build that has failed spuriously.
+struct SpuriousFailureRetry {
+    /// Spurious failure patterns matched by the logs of the failed jobs.
+    patterns: Vec<String>,
+    /// Which automatic retry of the PR is this (starting from 1).
+    attempt: u32,
+    max_attempts: u32,
+}
+
+/// Checks whether all failed jobs of a failed auto build have failed spuriously, i.e. their logs
+/// match one of the spurious failure patterns configured for the repository, and whether the PR
+/// can still be retried automatically.
+async fn find_spurious_failure_retry(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    pr: &PullRequestModel,
+    workflow_runs: &[FailedWorkflowRun],
+) -> Option<SpuriousFailureRetry> {
+    let (patterns, max_attempts) = {
+        let config = repo.config.load();
+        (
+            config.spurious_failure_patterns.clone(),
+            config.spurious_failure_retries,
+        )
+    };
+    if patterns.is_empty() {
+        return None;
     }
 
+    let failed_runs: Vec<&FailedWorkflowRun> = workflow_runs
+        .iter()
+        .filter(|run| run.workflow_run.status == WorkflowStatus::Failure)
+        .collect();
+    // If we do not know why some workflow has failed, the failure cannot be considered spurious
+    if failed_runs.is_empty() || failed_runs.iter().any(|run| run.failed_jobs.is_empty()) {
+        return None;
+    }
+
+    let mut matched_patterns = vec![];
+    for job in failed_runs.iter().flat_map(|run| &run.failed_jobs) {
+        let client = repo.client.clone();
+        let job_id = job.id;
+        let logs =
+            match run_in_background(
+                "get_job_logs",
+                async move { client.get_job_logs(job_id).await },
+            )
+            .await
+            {
+                Ok(logs) => logs,
+                Err(error) => {
+                    tracing::error!("Cannot download logs of job {}: {error:?}", job.id);
+                    return None;
+                }
+            };
+        let pattern = patterns.iter().find(|pattern| pattern.is_match(&logs))?;
+        matched_patterns.push(pattern.as_str().to_string());
+    }
+
+    let retries = match db.get_build_retries(pr).await {
+        Ok(retries) => retries,
+        Err(error) => {
+            tracing::error!("Cannot load retries of PR {}: {error:?}", pr.number);
+            return None;
+        }
+    };
+    // Retries are counted per approval, so that a PR gets new retries once a new commit is pushed
+    // to it, or once it is approved again
+    let automatic_retries = retries
+        .iter()
+        .filter(|retry| {
+            retry.retried_by.is_none()
+                && retry.head_sha.as_deref() == pr.approved_sha()
+                && pr
+                    .approved_at
+                    .is_none_or(|approved_at| retry.created_at >= approved_at)
+        })
+        .count() as u32;
+    if automatic_retries >= max_attempts {
+        tracing::info!(
+            "Auto build of PR {} failed spuriously, but it was already retried {automatic_retries} time(s)",
+            pr.number
+        );
+        return None;
+    }
+
+    Some(SpuriousFailureRetry {
+        patterns: matched_patterns.into_iter().unique().collect(),
+        attempt: automatic_retries + 1,
+        max_attempts,
+    })
+}
+
+/// Store artifacts uploaded by the GitHub Actions workflow runs of a build.
+async fn record_workflow_artifacts(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    build: &BuildModel,
+    workflow_runs: &[WorkflowModel],
+) -> anyhow::Result<()> {
+    for workflow_run in workflow_runs {
+        if workflow_run.workflow_type != WorkflowType::Github {
+            continue;
+        }
+        // If listing the artifacts takes too long, they are stored once they are listed, but
+        // they will not be mentioned in the build result comment.
+        let client = repo.client.clone();
+        let db = db.clone();
+        let build = build.clone();
+        let run_id = workflow_run.run_id;
+        run_in_background("record_workflow_artifacts", async move {
+            let artifacts = client.get_artifacts_for_workflow_run(run_id.into()).await?;
+            db.record_build_artifacts(&build, &artifacts).await
+        })
+        .await?;
+    }
     Ok(())
 }
 
@@ -355,10 +839,12 @@ async fn get_failed_jobs(
     repo: &RepositoryState,
     workflow_run: &WorkflowModel,
 ) -> anyhow::Result<Vec<Job>> {
-    let jobs = repo
-        .client
-        .get_jobs_for_workflow_run(workflow_run.run_id.into())
-        .await?;
+    let client = repo.client.clone();
+    let run_id = workflow_run.run_id;
+    let jobs = run_in_background("get_jobs_for_workflow_run", async move {
+        client.get_jobs_for_workflow_run(run_id.into()).await
+    })
+    .await?;
     Ok(jobs
         .into_iter()
         .filter(|j| {
@@ -393,16 +879,50 @@ pub async fn cancel_build(
     db: &PgDbClient,
     build: &BuildModel,
     check_run_conclusion: CheckRunConclusion,
+) -> Result<Vec<WorkflowModel>, CancelBuildError> {
+    stop_build(
+        client,
+        db,
+        build,
+        BuildStatus::Cancelled,
+        check_run_conclusion,
+    )
+    .await
+}
+
+/// Attempt to mark a pending build that has been running for too long as timed out.
+/// Its pending workflows are cancelled in the same way as in [`cancel_build`].
+pub async fn timeout_build(
+    client: &GithubRepositoryClient,
+    db: &PgDbClient,
+    build: &BuildModel,
+) -> Result<Vec<WorkflowModel>, CancelBuildError> {
+    stop_build(
+        client,
+        db,
+        build,
+        BuildStatus::Timeouted,
+        CheckRunConclusion::TimedOut,
+    )
+    .await
+}
+
+async fn stop_build(
+    client: &GithubRepositoryClient,
+    db: &PgDbClient,
+    build: &BuildModel,
+    status: BuildStatus,
+    check_run_conclusion: CheckRunConclusion,
 ) -> Result<Vec<WorkflowModel>, CancelBuildError> {
     assert_eq!(
         build.status,
         BuildStatus::Pending,
-        "Passed a non-pending build to `cancel_build`"
+        "Passed a non-pending build to `stop_build`"
     );
 
     // This is the most important part: we need to ensure that the status of the build is switched
-    // to cancelled.
-    db.update_build_status(build, BuildStatus::Cancelled)
+    // to a finished state.
+    db.update_build_status(build, status)
         .await
         .map_err(CancelBuildError::FailedToMarkBuildAsCancelled)?;
 
@@ -442,6 +962,16 @@ pub enum AutoBuildCancelReason {
     PushToPR,
     /// A PR was unapproved while it was being tested in an auto build.
     Unapproval,
+    /// A PR was closed while it was being tested in an auto build.
+    PullRequestClosed,
+    /// The title or description of a PR was edited while it was being tested in an auto build.
+    PullRequestEdited,
+    /// A PR was converted to a draft while it was being tested in an auto build.
+    PullRequestConvertedToDraft,
+    /// A PR with a higher priority was approved while a PR was being tested in an auto build.
+    Preempted(PullRequestNumber),
+    /// A PR was parked while it was being tested in an auto build.
+    Park,
 }
 
 /// Cancel an auto build attached to the PR, if there is any.
@@ -460,7 +990,9 @@ pub async fn maybe_cancel_auto_build(
 
     tracing::info!("Cancelling auto build {auto_build:?}");
 
-    match cancel_build(client, db, &auto_build, CheckRunConclusion::Cancelled).await {
+    let result = cancel_build(client, db, &auto_build, CheckRunConclusion::Cancelled).await;
+    reset_auto_branch(client, &auto_build).await;
+    match result {
         Ok(workflows) => {
             tracing::info!("Auto build cancelled");
             let workflow_urls = workflows.into_iter().map(|w| w.url).collect();
@@ -477,6 +1009,24 @@ pub async fn maybe_cancel_auto_build(
     }
 }
 
+/// Reset the branch of a cancelled auto build back to the parent of the build, so that the merge
+/// commit of the cancelled build does not stay on the branch while the queue moves on.
+async fn reset_auto_branch(client: &GithubRepositoryClient, build: &BuildModel) {
+    if let Err(error) = client
+        .set_branch_to_sha(
+            &build.branch,
+            &CommitSha(build.parent.clone()),
+            ForcePush::Yes,
+        )
+        .await
+    {
+        tracing::error!(
+            "Could not reset branch {} of cancelled auto build: {error:?}",
+            build.branch
+        );
+    }
+}
+
 /// If `workflow_urls` is `None`, it was not possible to cancel workflows.
 fn auto_build_cancelled_msg(
     reason: AutoBuildCancelReason,
@@ -485,8 +1035,19 @@ fn auto_build_cancelled_msg(
     use std::fmt::Write;
 
     let reason = match reason {
-        AutoBuildCancelReason::PushToPR => "push",
-        AutoBuildCancelReason::Unapproval => "unapproval",
+        AutoBuildCancelReason::PushToPR => "push".to_string(),
+        AutoBuildCancelReason::Unapproval => "unapproval".to_string(),
+        AutoBuildCancelReason::PullRequestClosed => "the PR being closed".to_string(),
+        AutoBuildCancelReason::PullRequestEdited => {
+            "the PR title or description being edited".to_string()
+        }
+        AutoBuildCancelReason::PullRequestConvertedToDraft => {
+            "the PR being converted to a draft".to_string()
+        }
+        AutoBuildCancelReason::Preempted(pr) => {
+            format!("the approval of #{pr}, which has a higher priority")
+        }
+        AutoBuildCancelReason::Park => "the PR being parked".to_string(),
     };
     let mut comment = format!("Auto build cancelled due to {reason}.");
     match cancelled_workflow_urls {
@@ -506,10 +1067,14 @@ fn auto_build_cancelled_msg(
 mod tests {
     use std::time::Duration;
 
-    use crate::database::WorkflowStatus;
+    use crate::bors::RollupMode;
     use crate::database::operations::get_all_workflows;
-    use crate::tests::{BorsBuilder, BorsTester, GitHubState, default_repo_name};
-    use crate::tests::{Branch, WorkflowEvent, WorkflowRunData, run_test};
+    use crate::database::{BuildStatus, MergeableState, OctocrabMergeableState, WorkflowStatus};
+    use crate::tests::{BorsBuilder, BorsTester, Comment, GitHubState, default_repo_name};
+    use crate::tests::{
+        Branch, WorkflowArtifact, WorkflowEvent, WorkflowJob, WorkflowRunData, run_test,
+    };
+    use octocrab::models::JobId;
 
     #[sqlx::test]
     async fn workflow_started_unknown_build(pool: sqlx::PgPool) {
@@ -576,6 +1141,65 @@ mod tests {
     }
 
     // First start both workflows, then finish both of them.
+    #[sqlx::test]
+    async fn try_success_with_artifacts(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+
+            let workflow =
+                WorkflowRunData::from(tester.try_branch().await).with_artifacts(vec![
+                    WorkflowArtifact {
+                        id: 10,
+                        name: "dist".to_string(),
+                        size: 3 * 1024 * 1024,
+                    },
+                ]);
+            tester.workflow_full_success(workflow).await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @r#"
+            :sunny: Try build successful ([Workflow1](https://github.com/rust-lang/borstest/actions/runs/1))
+            Build commit: merge-0-pr-1 (`merge-0-pr-1`, parent: `main-sha1`)
+            Artifacts: [dist](https://github.com/rust-lang/borstest/actions/runs/1/artifacts/10) (3.0 MiB)
+
+            <!-- homu: {"type":"TryBuildCompleted","merge_sha":"merge-0-pr-1"} -->
+            "#
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn try_success_with_comment_template(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+try_build_comment_template = "[Compare](https://perf.example/{repo}/{pr}?start={parent}&end={sha})"
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors try").await?;
+                tester.expect_comments((), 1).await;
+                tester
+                    .workflow_full_success(tester.try_branch().await)
+                    .await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @r#"
+                :sunny: Try build successful ([Workflow1](https://github.com/rust-lang/borstest/actions/runs/1))
+                Build commit: merge-0-pr-1 (`merge-0-pr-1`, parent: `main-sha1`)
+                [Compare](https://perf.example/rust-lang/borstest/1?start=main-sha1&end=merge-0-pr-1)
+
+                <!-- homu: {"type":"TryBuildCompleted","merge_sha":"merge-0-pr-1"} -->
+                "#
+                );
+                Ok(())
+            })
+            .await;
+    }
+
     #[sqlx::test]
     async fn try_success_multiple_workflows_per_suite_1(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
@@ -720,4 +1344,379 @@ min_ci_time = 20
             })
             .await;
     }
+
+    fn external_ci_config() -> GitHubState {
+        GitHubState::default().with_default_config(
+            r#"
+merge_queue_enabled = true
+required_status_contexts = ["ci/jenkins", "ci/lint"]
+"#,
+        )
+    }
+
+    #[sqlx::test]
+    async fn external_ci_try_build_success(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(external_ci_config())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors try").await?;
+                tester.expect_comments((), 1).await;
+                tester
+                    .commit_status(tester.try_branch().await, "ci/jenkins", "success")
+                    .await?;
+                assert_eq!(
+                    tester
+                        .db()
+                        .get_pending_builds(&default_repo_name())
+                        .await?
+                        .len(),
+                    1
+                );
+                tester
+                    .commit_status(tester.try_branch().await, "ci/lint", "success")
+                    .await?;
+                let comment = tester.get_next_comment_text(()).await?;
+                assert!(comment.contains(":sunny: Try build successful"));
+                assert!(comment.contains("https://ci.example.com/ci/lint/merge-0-pr-1"));
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn external_ci_failure_fails_build(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(external_ci_config())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors try").await?;
+                tester.expect_comments((), 1).await;
+                tester
+                    .commit_status(tester.try_branch().await, "ci/lint", "failure")
+                    .await?;
+                assert!(
+                    tester
+                        .get_next_comment_text(())
+                        .await?
+                        .contains(":broken_heart: Test for merge-0-pr-1 failed")
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn external_ci_ignores_other_contexts(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool.clone())
+            .github(external_ci_config())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors try").await?;
+                tester.expect_comments((), 1).await;
+                tester
+                    .commit_status(tester.try_branch().await, "ci/optional", "failure")
+                    .await?;
+                tester
+                    .workflow_full_failure(tester.try_branch().await)
+                    .await?;
+                assert_eq!(
+                    tester
+                        .db()
+                        .get_pending_builds(&default_repo_name())
+                        .await?
+                        .len(),
+                    1
+                );
+                Ok(())
+            })
+            .await;
+        assert_eq!(get_all_workflows(&pool).await.unwrap().len(), 0);
+    }
+
+    #[sqlx::test]
+    async fn branch_protection_checks_auto_build(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+branch_protection_checks = true
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .modify_repo(&default_repo_name(), |repo| {
+                        repo.required_status_checks = vec!["ci/build".to_string()]
+                    })
+                    .await;
+                tester.approve(()).await?;
+                tester.start_auto_build(()).await?;
+                // Workflows are not enough, the required check has to succeed
+                tester
+                    .workflow_full_success(tester.auto_branch().await)
+                    .await?;
+                tester.process_merge_queue().await;
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_auto_build(|build| build.status == BuildStatus::Pending);
+                tester
+                    .commit_status(tester.auto_branch().await, "ci/build", "success")
+                    .await?;
+                tester.process_merge_queue().await;
+                assert!(
+                    tester
+                        .get_next_comment_text(())
+                        .await?
+                        .contains("Test successful")
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn branch_protection_checks_unprotected_branch(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+branch_protection_checks = true
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.start_auto_build(()).await?;
+                tester.finish_auto_build(()).await?;
+                Ok(())
+            })
+            .await;
+    }
+
+    fn spurious_failure_config() -> GitHubState {
+        GitHubState::default().with_default_config(
+            r#"
+merge_queue_enabled = true
+spurious_failure_patterns = ["runner .* lost", "connection reset"]
+spurious_failure_retries = 1
+
+[labels]
+approved = ["+approved"]
+"#,
+        )
+    }
+
+    /// Fails the auto build with a workflow that has a single failed job with the given logs.
+    async fn fail_auto_build_with_logs(
+        tester: &mut BorsTester,
+        run_id: u64,
+        logs: &str,
+    ) -> anyhow::Result<()> {
+        let mut workflow = WorkflowRunData::from(tester.auto_branch().await).with_run_id(run_id);
+        let job_id = run_id * 10;
+        workflow.jobs.push(WorkflowJob {
+            id: JobId(job_id),
+            status: WorkflowStatus::Failure,
+        });
+        tester
+            .modify_repo(&default_repo_name(), |repo| {
+                repo.job_logs.insert(job_id, logs.to_string());
+                repo.update_workflow_run(workflow.clone(), WorkflowStatus::Pending);
+            })
+            .await;
+        tester.workflow_full_failure(workflow).await
+    }
+
+    #[sqlx::test]
+    async fn spurious_failure_retries_auto_build(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(spurious_failure_config())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.start_auto_build(()).await?;
+                fail_auto_build_with_logs(tester, 1, "error: the runner has been lost").await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @":repeat: The auto build failed spuriously (matched `runner .* lost`), retrying it (attempt 1/1)."
+                );
+                tester.wait_for_pr((), |pr| pr.auto_build.is_none()).await?;
+                tester.get_pr_copy(()).await.expect_approved_by("default-user");
+                tester.start_auto_build(()).await?;
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn spurious_failure_retries_are_limited(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(spurious_failure_config())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.start_auto_build(()).await?;
+                fail_auto_build_with_logs(tester, 1, "connection reset").await?;
+                tester.expect_comments((), 1).await;
+                tester.wait_for_pr((), |pr| pr.auto_build.is_none()).await?;
+                tester.start_auto_build(()).await?;
+
+                fail_auto_build_with_logs(tester, 2, "connection reset").await?;
+                assert!(
+                    tester
+                        .get_next_comment_text(())
+                        .await?
+                        .contains(":broken_heart: Test for")
+                );
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_auto_build(|build| build.status == BuildStatus::Failure);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn spurious_failure_retries_are_counted_per_approval(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(spurious_failure_config())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.start_auto_build(()).await?;
+                fail_auto_build_with_logs(tester, 1, "connection reset").await?;
+                tester.expect_comments((), 1).await;
+                tester.wait_for_pr((), |pr| pr.auto_build.is_none()).await?;
+                tester.start_auto_build(()).await?;
+                fail_auto_build_with_logs(tester, 2, "connection reset").await?;
+                tester.expect_comments((), 1).await;
+
+                tester.push_to_pr(()).await?;
+                tester
+                    .wait_for_pr((), |pr| !pr.is_approved() && pr.auto_build.is_none())
+                    .await?;
+                tester
+                    .modify_pr_state((), |pr| pr.mergeable_state = OctocrabMergeableState::Clean)
+                    .await;
+                tester
+                    .wait_for_pr((), |pr| pr.mergeable_state == MergeableState::Mergeable)
+                    .await?;
+                tester.approve(()).await?;
+                tester.start_auto_build(()).await?;
+                fail_auto_build_with_logs(tester, 3, "connection reset").await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @":repeat: The auto build failed spuriously (matched `connection reset`), retrying it (attempt 1/1)."
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn spurious_failure_unknown_failure_not_retried(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(spurious_failure_config())
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.start_auto_build(()).await?;
+                fail_auto_build_with_logs(tester, 1, "error[E0308]: mismatched types").await?;
+                assert!(
+                    tester
+                        .get_next_comment_text(())
+                        .await?
+                        .contains(":broken_heart: Test for")
+                );
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_auto_build(|build| build.status == BuildStatus::Failure);
+                Ok(())
+            })
+            .await;
+    }
+
+    const ROLLUP_DESCRIPTION: &str = r"Successful merges:
+
+ - #1 (Title of PR 1)
+ - #2 (Title of PR 2)
+
+r? @ghost";
+
+    #[sqlx::test]
+    async fn rollup_failure_notifies_constituents(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+            let rollup = tester
+                .open_pr(default_repo_name(), |pr| {
+                    pr.description = ROLLUP_DESCRIPTION.to_string();
+                })
+                .await?;
+            tester.approve(rollup.id()).await?;
+            tester.start_auto_build(rollup.id()).await?;
+            tester
+                .workflow_full_failure(tester.auto_branch().await)
+                .await?;
+            tester.expect_comments(rollup.id(), 1).await;
+
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":broken_heart: This PR was merged into the rollup #3, whose auto build (merge-0-pr-3) has failed. Please check whether the failure was caused by this PR."
+            );
+            assert!(
+                tester
+                    .get_next_comment_text(pr2.id())
+                    .await?
+                    .contains("merged into the rollup #3")
+            );
+            tester.get_pr_copy(pr2.id()).await.expect_rollup(None);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn rollup_failure_sets_constituents_iffy(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+rollup_failure_iffy = true
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+                let rollup = tester
+                    .open_pr(default_repo_name(), |pr| {
+                        pr.description = ROLLUP_DESCRIPTION.to_string();
+                    })
+                    .await?;
+                tester.post_comment("@bors rollup").await?;
+                tester
+                    .post_comment(Comment::new(pr2.id(), "@bors rollup=never"))
+                    .await?;
+                tester.approve(rollup.id()).await?;
+                tester.start_auto_build(rollup.id()).await?;
+                tester
+                    .workflow_full_failure(tester.auto_branch().await)
+                    .await?;
+                tester.expect_comments(rollup.id(), 1).await;
+
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @":broken_heart: This PR was merged into the rollup #3, whose auto build (merge-0-pr-3) has failed. Please check whether the failure was caused by this PR. The rollup status of this PR was set to `iffy`."
+                );
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_rollup(Some(RollupMode::Iffy));
+                assert!(
+                    !tester
+                        .get_next_comment_text(pr2.id())
+                        .await?
+                        .contains("iffy")
+                );
+                tester
+                    .get_pr_copy(pr2.id())
+                    .await
+                    .expect_rollup(Some(RollupMode::Never));
+                Ok(())
+            })
+            .await;
+    }
 }
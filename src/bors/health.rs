@@ -0,0 +1,205 @@
+//! Health of the components that bors depends on, reported by `@bors ping health` and by the
+//! `/health` endpoint, so that users, load balancers and uptime monitors can find out which
+//! component is degraded.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::PgDbClient;
+use crate::bors::RepositoryState;
+use crate::utils::text::pluralize;
+
+/// How long a single health check can take before the component is considered to be unhealthy.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The merge queue is considered to be stuck if it has not been processed for this long.
+/// It is normally processed at least every 30 seconds.
+const MERGE_QUEUE_STALENESS: chrono::Duration = chrono::Duration::minutes(5);
+
+/// Times of recent activity of this bors instance.
+pub struct HealthMetrics {
+    last_webhook_at: Mutex<Option<DateTime<Utc>>>,
+    last_merge_queue_tick_at: Mutex<Option<DateTime<Utc>>>,
+}
+
+/// Activity of this bors instance.
+pub static HEALTH_METRICS: HealthMetrics = HealthMetrics {
+    last_webhook_at: Mutex::new(None),
+    last_merge_queue_tick_at: Mutex::new(None),
+};
+
+impl HealthMetrics {
+    /// Records that a webhook has been received from GitHub.
+    pub(crate) fn record_webhook(&self, received_at: DateTime<Utc>) {
+        *self.last_webhook_at.lock().unwrap() = Some(received_at);
+    }
+
+    /// Records that the merge queue has been processed.
+    pub(crate) fn record_merge_queue_tick(&self, processed_at: DateTime<Utc>) {
+        *self.last_merge_queue_tick_at.lock().unwrap() = Some(processed_at);
+    }
+}
+
+/// Health of the components that bors depends on.
+#[derive(Debug, Serialize)]
+pub struct HealthReport {
+    /// True if all components are healthy.
+    pub healthy: bool,
+    /// Connectivity to the database.
+    pub database: ComponentHealth,
+    /// Validity of the GitHub App installation tokens.
+    pub github: ComponentHealth,
+    /// Time since the last webhook was received. Repositories can be quiet for a long time, so
+    /// this is informational only and never unhealthy.
+    pub webhooks: ComponentHealth,
+    /// Time since the merge queue was last processed.
+    pub merge_queue: ComponentHealth,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ComponentHealth {
+    pub healthy: bool,
+    /// Human-readable description of the state of the component.
+    pub details: String,
+}
+
+impl ComponentHealth {
+    fn healthy(details: impl Into<String>) -> Self {
+        Self {
+            healthy: true,
+            details: details.into(),
+        }
+    }
+
+    fn unhealthy(details: impl Into<String>) -> Self {
+        Self {
+            healthy: false,
+            details: details.into(),
+        }
+    }
+}
+
+/// Checks the health of the database, of the GitHub App tokens of the given repositories, and of
+/// the activity of this bors instance.
+/// Repositories of the same owner share a GitHub App installation, so only the token of one
+/// repository per owner is checked.
+pub async fn check_health(db: &PgDbClient, repos: &[Arc<RepositoryState>]) -> HealthReport {
+    let now = Utc::now();
+    let (database, github) = tokio::join!(check_database(db), check_github(repos));
+    let webhooks = match *HEALTH_METRICS.last_webhook_at.lock().unwrap() {
+        Some(received_at) => ComponentHealth::healthy(format!(
+            "last webhook was received {} ago",
+            format_elapsed(now - received_at)
+        )),
+        None => ComponentHealth::healthy("no webhook was received since startup"),
+    };
+    let merge_queue = match *HEALTH_METRICS.last_merge_queue_tick_at.lock().unwrap() {
+        Some(processed_at) => {
+            let details = format!("last processed {} ago", format_elapsed(now - processed_at));
+            if now - processed_at > MERGE_QUEUE_STALENESS {
+                ComponentHealth::unhealthy(details)
+            } else {
+                ComponentHealth::healthy(details)
+            }
+        }
+        None => ComponentHealth::healthy("not processed since startup yet"),
+    };
+
+    HealthReport {
+        healthy: database.healthy && github.healthy && webhooks.healthy && merge_queue.healthy,
+        database,
+        github,
+        webhooks,
+        merge_queue,
+    }
+}
+
+async fn check_database(db: &PgDbClient) -> ComponentHealth {
+    match tokio::time::timeout(CHECK_TIMEOUT, db.check_connection()).await {
+        Ok(Ok(())) => ComponentHealth::healthy("connected"),
+        Ok(Err(error)) => {
+            tracing::warn!("Database health check failed: {error:?}");
+            ComponentHealth::unhealthy("cannot query the database")
+        }
+        Err(_) => ComponentHealth::unhealthy("the database did not respond in time"),
+    }
+}
+
+async fn check_github(repos: &[Arc<RepositoryState>]) -> ComponentHealth {
+    let mut checked_repos: Vec<&Arc<RepositoryState>> = vec![];
+    for repo in repos {
+        if !checked_repos
+            .iter()
+            .any(|checked| checked.repository().owner() == repo.repository().owner())
+        {
+            checked_repos.push(repo);
+        }
+    }
+
+    let results = futures::future::join_all(checked_repos.iter().map(|repo| async move {
+        let result = tokio::time::timeout(CHECK_TIMEOUT, repo.client.check_token()).await;
+        (repo.repository(), result)
+    }))
+    .await;
+
+    let mut failed_owners = vec![];
+    for (repo, result) in results {
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => {
+                tracing::warn!("GitHub health check of {repo} failed: {error:?}");
+                failed_owners.push(repo.owner().to_string());
+            }
+            Err(_) => {
+                tracing::warn!("GitHub health check of {repo} timed out");
+                failed_owners.push(repo.owner().to_string());
+            }
+        }
+    }
+
+    let installations = checked_repos.len();
+    if failed_owners.is_empty() {
+        ComponentHealth::healthy(format!(
+            "{installations} app {} valid",
+            pluralize("token", installations)
+        ))
+    } else {
+        ComponentHealth::unhealthy(format!(
+            "cannot use the app token of {}",
+            failed_owners.join(", ")
+        ))
+    }
+}
+
+/// Formats an elapsed time with a coarse granularity, e.g. `5 minutes`.
+fn format_elapsed(elapsed: chrono::Duration) -> String {
+    let seconds = elapsed.num_seconds().max(0) as usize;
+    let (value, unit) = if seconds < 60 {
+        (seconds, "second")
+    } else if seconds < 60 * 60 {
+        (seconds / 60, "minute")
+    } else if seconds < 24 * 60 * 60 {
+        (seconds / (60 * 60), "hour")
+    } else {
+        (seconds / (24 * 60 * 60), "day")
+    };
+    format!("{value} {}", pluralize(unit, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_elapsed;
+
+    #[test]
+    fn format_elapsed_time() {
+        assert_eq!(format_elapsed(chrono::Duration::seconds(1)), "1 second");
+        assert_eq!(format_elapsed(chrono::Duration::seconds(59)), "59 seconds");
+        assert_eq!(format_elapsed(chrono::Duration::seconds(150)), "2 minutes");
+        assert_eq!(format_elapsed(chrono::Duration::hours(5)), "5 hours");
+        assert_eq!(format_elapsed(chrono::Duration::days(1)), "1 day");
+        assert_eq!(format_elapsed(chrono::Duration::seconds(-3)), "0 seconds");
+    }
+}
@@ -0,0 +1,128 @@
+//! Hooks that allow deployments of bors to customize the handling of commands, without having to
+//! modify the command parser or the command handlers.
+
+use futures::future::BoxFuture;
+
+use crate::bors::command::BorsCommand;
+use crate::github::{GithubRepoName, GithubUser, PullRequest};
+
+/// A command that is being executed, passed to command hooks.
+pub struct CommandHookContext<'a> {
+    pub repository: &'a GithubRepoName,
+    pub pr: &'a PullRequest,
+    /// Author of the comment that contained the command.
+    pub author: &'a GithubUser,
+    pub command: &'a BorsCommand,
+}
+
+/// Custom logic executed before and after each bors command, e.g. to enforce that approved PRs
+/// reference an internal ticket.
+///
+/// A hook returns `Err` with a human-readable explanation when it fails. The explanation is posted
+/// as a comment on the PR.
+///
+/// Comments that only contain `ping` and `help` commands are answered without running the hooks.
+pub trait CommandHook: Send + Sync {
+    /// Called before the command is executed. If the hook fails, the command is not executed.
+    fn before_command<'a>(
+        &'a self,
+        _ctx: &'a CommandHookContext<'a>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Called after the command was executed successfully.
+    fn after_command<'a>(
+        &'a self,
+        _ctx: &'a CommandHookContext<'a>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use futures::future::BoxFuture;
+
+    use crate::bors::command::BorsCommand;
+    use crate::bors::hooks::{CommandHook, CommandHookContext};
+    use crate::tests::{BorsBuilder, BorsTester};
+
+    /// Requires a ticket reference in the description of approved PRs.
+    struct RequireTicket;
+
+    impl CommandHook for RequireTicket {
+        fn before_command<'a>(
+            &'a self,
+            ctx: &'a CommandHookContext<'a>,
+        ) -> BoxFuture<'a, Result<(), String>> {
+            Box::pin(async move {
+                match ctx.command {
+                    BorsCommand::Approve { .. } if !ctx.pr.message.contains("TICKET-") => {
+                        Err("the PR description does not reference a ticket".to_string())
+                    }
+                    _ => Ok(()),
+                }
+            })
+        }
+    }
+
+    struct FailAfter;
+
+    impl CommandHook for FailAfter {
+        fn after_command<'a>(
+            &'a self,
+            _ctx: &'a CommandHookContext<'a>,
+        ) -> BoxFuture<'a, Result<(), String>> {
+            Box::pin(async { Err("could not notify the release tracker".to_string()) })
+        }
+    }
+
+    #[sqlx::test]
+    async fn before_hook_rejects_command(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .command_hook(Arc::new(RequireTicket))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors r+").await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @":no_entry_sign: Command rejected: the PR description does not reference a ticket"
+                );
+                tester.get_pr_copy(()).await.expect_unapproved();
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn before_hook_allows_command(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .command_hook(Arc::new(RequireTicket))
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .edit_pr((), |pr| pr.description = "Fixes TICKET-123".to_string())
+                    .await?;
+                tester.approve(()).await?;
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn after_hook_failure_is_reported(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .command_hook(Arc::new(FailAfter))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors p=5").await?;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(()).await?,
+                    @":warning: The command was executed, but a follow-up check failed: could not notify the release tracker"
+                );
+                tester.get_pr_copy(()).await.expect_priority(Some(5));
+                Ok(())
+            })
+            .await;
+    }
+}
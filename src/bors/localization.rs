@@ -0,0 +1,179 @@
+//! Translation catalogs for comments posted by bors.
+
+/// Language in which bors posts its comments.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    /// English.
+    #[default]
+    En,
+    /// German.
+    De,
+}
+
+/// Comment text that can be translated using a simple catalog.
+///
+/// Dynamic parts of a message are written as named placeholders (e.g. `{key}`) in its
+/// translations, and are filled in by [`Message::format`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    NoPreviousBuild,
+    ForkTryBuildDenied,
+    ForkTryBuildUnrestricted,
+    DraftTryBuildDenied,
+    NoTryBuildInProgress,
+    TryBuildCancelledWithFailedWorkflowCancel,
+    ApproveNonOpenPr,
+    UnapproveNonOpenPr,
+    TryBuildsDisabled,
+    MergeQueueDisabled,
+    BuildEnvDisabled,
+    BuildEnvValueNotAllowed,
+    UnknownBuildEnvParameter,
+    BuildEnvSet,
+    BuildEnvCleared,
+    NoBuildEnv,
+    MaintenanceWindowEnded,
+    SpeculativeBuildDiscarded,
+}
+
+impl Message {
+    /// Returns the text of the message in the given `language`.
+    pub fn translate(self, language: Language) -> &'static str {
+        match language {
+            Language::En => english(self),
+            Language::De => german(self),
+        }
+    }
+
+    /// Returns the text of the message in the given `language`, with its placeholders replaced
+    /// by the given `(name, value)` arguments.
+    /// Placeholders without a matching argument are kept as they are.
+    pub fn format(self, language: Language, args: &[(&str, &str)]) -> String {
+        let mut text = String::new();
+        let mut rest = self.translate(language);
+        while let Some(start) = rest.find('{') {
+            text.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let arg = rest.find('}').and_then(|end| {
+                args.iter()
+                    .find(|(name, _)| *name == &rest[1..end])
+                    .map(|(_, value)| (end, value))
+            });
+            match arg {
+                Some((end, value)) => {
+                    text.push_str(value);
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    text.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        text.push_str(rest);
+        text
+    }
+}
+
+fn english(message: Message) -> &'static str {
+    match message {
+        Message::NoPreviousBuild => {
+            ":exclamation: There was no previous build. Please set an explicit parent or remove the `parent=last` argument to use the default parent."
+        }
+        Message::ForkTryBuildDenied => {
+            ":lock: Try builds are not allowed for pull requests opened from forks in this repository."
+        }
+        Message::ForkTryBuildUnrestricted => {
+            ":lock: Try builds of pull requests opened from forks have to run in the `{environment}` environment, but the following workflow jobs do not use it: {jobs}."
+        }
+        Message::DraftTryBuildDenied => {
+            ":clipboard: Try builds cannot be started for draft PRs. Mark the PR as ready for review first."
+        }
+        Message::NoTryBuildInProgress => {
+            ":exclamation: There is currently no try build in progress."
+        }
+        Message::TryBuildCancelledWithFailedWorkflowCancel => {
+            "Try build was cancelled. It was not possible to cancel some workflows."
+        }
+        Message::ApproveNonOpenPr => ":clipboard: Only open, non-draft PRs can be approved.",
+        Message::UnapproveNonOpenPr => ":clipboard: Only unclosed PRs can be unapproved.",
+        Message::TryBuildsDisabled => ":no_entry_sign: This repository has try builds disabled.",
+        Message::MergeQueueDisabled => {
+            ":no_entry_sign: This repository has the merge queue disabled."
+        }
+        Message::BuildEnvDisabled => {
+            ":exclamation: Build parameters are not enabled in this repository. They can be allowed with the `build_env` configuration option."
+        }
+        Message::BuildEnvValueNotAllowed => {
+            ":exclamation: Build parameter `{key}` cannot be set to `{value}`. Allowed values: {allowed}."
+        }
+        Message::UnknownBuildEnvParameter => {
+            ":exclamation: Unknown build parameter `{key}`. Available parameters: {allowed}."
+        }
+        Message::BuildEnvSet => {
+            ":gear: Build parameters of this PR: {build_env}. They will be passed to its next try and auto builds."
+        }
+        Message::BuildEnvCleared => ":gear: Build parameters of this PR were removed.",
+        Message::NoBuildEnv => "PR has no build parameters.",
+        Message::MaintenanceWindowEnded => {
+            ":sunny: The scheduled maintenance window has ended, the tree is now open for merging."
+        }
+        Message::SpeculativeBuildDiscarded => {
+            ":recycle: The auto build of this PR was discarded, because it was based on the auto build of another PR that did not succeed. A new auto build will be started."
+        }
+    }
+}
+
+fn german(message: Message) -> &'static str {
+    match message {
+        Message::NoPreviousBuild => {
+            ":exclamation: Es gab keinen vorherigen Build. Bitte gib einen expliziten Parent an oder entferne das Argument `parent=last`, um den Standard-Parent zu verwenden."
+        }
+        Message::ForkTryBuildDenied => {
+            ":lock: Try-Builds sind in diesem Repository für Pull Requests aus Forks nicht erlaubt."
+        }
+        Message::ForkTryBuildUnrestricted => {
+            ":lock: Try-Builds von Pull Requests aus Forks müssen in der Umgebung `{environment}` laufen, aber die folgenden Workflow-Jobs verwenden sie nicht: {jobs}."
+        }
+        Message::DraftTryBuildDenied => {
+            ":clipboard: Für Entwürfe können keine Try-Builds gestartet werden. Markiere den PR zuerst als bereit zur Überprüfung."
+        }
+        Message::NoTryBuildInProgress => ":exclamation: Derzeit läuft kein Try-Build.",
+        Message::TryBuildCancelledWithFailedWorkflowCancel => {
+            "Der Try-Build wurde abgebrochen. Einige Workflows konnten nicht abgebrochen werden."
+        }
+        Message::ApproveNonOpenPr => {
+            ":clipboard: Nur offene PRs, die keine Entwürfe sind, können genehmigt werden."
+        }
+        Message::UnapproveNonOpenPr => {
+            ":clipboard: Nur nicht geschlossene PRs können wieder abgelehnt werden."
+        }
+        Message::TryBuildsDisabled => {
+            ":no_entry_sign: In diesem Repository sind Try-Builds deaktiviert."
+        }
+        Message::MergeQueueDisabled => {
+            ":no_entry_sign: In diesem Repository ist die Merge-Queue deaktiviert."
+        }
+        Message::BuildEnvDisabled => {
+            ":exclamation: Build-Parameter sind in diesem Repository nicht aktiviert. Sie können mit der Konfigurationsoption `build_env` erlaubt werden."
+        }
+        Message::BuildEnvValueNotAllowed => {
+            ":exclamation: Der Build-Parameter `{key}` kann nicht auf `{value}` gesetzt werden. Erlaubte Werte: {allowed}."
+        }
+        Message::UnknownBuildEnvParameter => {
+            ":exclamation: Unbekannter Build-Parameter `{key}`. Verfügbare Parameter: {allowed}."
+        }
+        Message::BuildEnvSet => {
+            ":gear: Build-Parameter dieses PRs: {build_env}. Sie werden an die nächsten Try- und Auto-Builds übergeben."
+        }
+        Message::BuildEnvCleared => ":gear: Die Build-Parameter dieses PRs wurden entfernt.",
+        Message::NoBuildEnv => "Der PR hat keine Build-Parameter.",
+        Message::MaintenanceWindowEnded => {
+            ":sunny: Das geplante Wartungsfenster ist beendet, der Tree ist jetzt wieder für Merges geöffnet."
+        }
+        Message::SpeculativeBuildDiscarded => {
+            ":recycle: Der Auto-Build dieses PRs wurde verworfen, weil er auf dem Auto-Build eines anderen PRs basierte, der nicht erfolgreich war. Ein neuer Auto-Build wird gestartet."
+        }
+    }
+}
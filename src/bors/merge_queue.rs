@@ -1,27 +1,43 @@
 use chrono::{DateTime, Utc};
+use octocrab::models::CheckRunId;
 use octocrab::models::checks::CheckRun;
-use octocrab::params::checks::CheckRunStatus;
+use octocrab::params::checks::{CheckRunConclusion, CheckRunStatus};
+use std::collections::{HashMap, HashSet};
 use std::future::Future;
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use tracing::Instrument;
 
 use crate::BorsContext;
+use crate::bors::Comment;
 use crate::bors::comment::{
-    auto_build_push_failed_comment, auto_build_started_comment, auto_build_succeeded_comment,
-    merge_conflict_comment,
+    CommentTag, already_merged_comment, auto_build_base_moved_comment,
+    auto_build_lint_violations_comment, auto_build_push_failed_comment, auto_build_started_comment,
+    auto_build_succeeded_comment, dry_run_auto_build_comment, maintenance_window_ended_comment,
+    maintenance_window_started_comment, merge_conflict_comment,
+    queue_position_notification_comment, speculative_build_discarded_comment,
+    squash_merged_comment, stale_approval_comment,
 };
+use crate::bors::handlers::workflow::{CancelBuildError, cancel_build};
+use crate::bors::handlers::{
+    hide_tagged_comments, run_merged_pr_hooks, tag_status_comment, unapprove_pr,
+};
+use crate::bors::health::HEALTH_METRICS;
+use crate::bors::queue_skips::{QUEUE_SKIPS, SkipReason, TickSkips};
 use crate::bors::{PullRequestStatus, RepositoryState};
+use crate::config::{RepositoryConfig, StaleApprovalPolicy};
 use crate::database::{
-    ApprovalInfo, BuildModel, BuildStatus, MergeableState, OctocrabMergeableState,
-    PullRequestModel, QueueStatus,
+    ApprovalInfo, BuildModel, BuildStatus, CoordinationLock, MergeableState,
+    OctocrabMergeableState, PrMilestone, PullRequestModel, QueueStatus, TreeState,
 };
 use crate::github::api::client::CheckRunOutput;
 use crate::github::api::operations::{BranchUpdateError, ForcePush};
-use crate::github::{CommitSha, PullRequest, PullRequestNumber};
+use crate::github::{CommitSha, GithubRepoName, PullRequest, PullRequestNumber};
 use crate::github::{MergeResult, attempt_merge};
 use crate::utils::sort_queue::sort_queue_prs;
+use crate::utils::text::pluralize;
 
+use super::outbox::{comment_action, deliver_outbox_actions, post_comment_or_enqueue};
 use super::{MergeType, create_merge_commit_message};
 
 #[derive(Debug)]
@@ -88,22 +104,121 @@ pub(super) const AUTO_MERGE_BRANCH_NAME: &str = "automation/bors/auto-merge";
 /// This branch should run CI checks.
 pub(super) const AUTO_BRANCH_NAME: &str = "automation/bors/auto";
 
+/// Returns the name of the branch where the auto build in the given `slot` of a queue runs.
+/// When several auto builds run in parallel, each of them runs on a separate branch.
+/// Queues other than the default one (base branches with their own queue and path queues) use
+/// separate auto branches, named after the queue.
+pub(super) fn auto_branch_name(queue: Option<&str>, slot: usize) -> String {
+    let name = match queue {
+        Some(branch) => format!("{AUTO_BRANCH_NAME}-{branch}"),
+        None => AUTO_BRANCH_NAME.to_string(),
+    };
+    if slot == 0 {
+        name
+    } else {
+        format!("{name}-{slot}")
+    }
+}
+
+/// Identifies the merge queue that a PR belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum QueueId<'a> {
+    /// The default queue of the repository.
+    Default,
+    /// The queue of a base branch listed in `merge_queue_branches`.
+    Branch(&'a str),
+    /// A path queue configured in `path_queues`.
+    Path(&'a str),
+}
+
+impl<'a> QueueId<'a> {
+    /// Returns the queue of the given PR. PRs targeting base branches with their own queue
+    /// always belong to that queue, other PRs belong to their path queue, if it exists.
+    pub(super) fn of(config: &RepositoryConfig, pr: &'a PullRequestModel) -> Self {
+        if config.merge_queue_branches.contains(&pr.base_branch) {
+            QueueId::Branch(&pr.base_branch)
+        } else if let Some(queue) = pr
+            .path_queue
+            .as_deref()
+            .filter(|queue| config.path_queues.contains_key(*queue))
+        {
+            QueueId::Path(queue)
+        } else {
+            QueueId::Default
+        }
+    }
+}
+
+/// Is this a branch where auto builds run?
+pub(super) fn is_auto_branch(branch: &str) -> bool {
+    match branch.strip_prefix(AUTO_BRANCH_NAME) {
+        Some("") => true,
+        Some(suffix) => {
+            branch != AUTO_MERGE_BRANCH_NAME
+                && suffix
+                    .strip_prefix('-')
+                    .is_some_and(|name| !name.is_empty())
+        }
+        None => false,
+    }
+}
+
 // The name of the check run seen in the GitHub UI.
 pub(super) const AUTO_BUILD_CHECK_RUN_NAME: &str = "Bors auto build";
 
+// The name of the check run that shows the position of an approved PR in the merge queue.
+pub(super) const QUEUE_CHECK_RUN_NAME: &str = "Bors queue";
+
+/// Users that asked to be notified about a PR are mentioned once the PR gets among this many
+/// PRs at the front of the queue.
+pub(super) const QUEUE_NOTIFICATION_POSITION: usize = 3;
+
 /// Process the merge queue.
 /// Try to finish and merge a successful auto build, if any.
 /// If there is a PR ready to be merged, starts an auto build for it.
+///
+/// Repositories are processed concurrently and independently of each other, so that a slow
+/// GitHub API call or an error in one repository does not delay merges in other repositories.
+///
+/// Nothing is done while the emergency stop is active. Running auto builds are not cancelled,
+/// but they are not merged until the stop is cleared.
+///
+/// Only a single bors instance processes the merge queues at a time, so that e.g. an instance
+/// that is being replaced by a new deployment does not start the same auto builds again.
 pub async fn merge_queue_tick(ctx: Arc<BorsContext>) -> anyhow::Result<()> {
+    if let Some(stop) = ctx.db.get_emergency_stop().await? {
+        tracing::info!(
+            "Merge queue is paused by an emergency stop since {} (reason: {})",
+            stop.created_at,
+            stop.reason.as_deref().unwrap_or("not specified")
+        );
+        return Ok(());
+    }
+
+    // The lock is held until the whole tick is finished
+    let Some(lock) = ctx.db.try_lock(CoordinationLock::MergeQueue).await? else {
+        tracing::info!("Merge queue is being processed by another bors instance");
+        return Ok(());
+    };
+
     let repos: Vec<Arc<RepositoryState>> =
         ctx.repositories.read().unwrap().values().cloned().collect();
 
-    for repo in repos {
-        let repo_name = repo.repository().to_string();
-        if let Err(error) = process_repository(&repo, &ctx).await {
-            tracing::error!("Error running merge queue for {repo_name}: {error:?}");
+    futures::future::join_all(repos.into_iter().map(|repo| {
+        let ctx = &ctx;
+        let span = tracing::info_span!("Repo", repo = repo.repository().to_string());
+        async move {
+            if let Err(error) = process_repository(&repo, ctx).await {
+                tracing::error!(
+                    "Error running merge queue for {}: {error:?}",
+                    repo.repository()
+                );
+            }
         }
-    }
+        .instrument(span)
+    }))
+    .await;
+    lock.release().await;
 
     Ok(())
 }
@@ -121,16 +236,364 @@ async fn process_repository(repo: &RepositoryState, ctx: &BorsContext) -> anyhow
             return Ok(());
         }
     };
+    if let Some(reason) = repo_db.deactivation_reason {
+        tracing::info!("Repository {repo_name} is deactivated: {reason}");
+        return Ok(());
+    }
+
+    // PRs targeting base branches with their own queue are processed independently of the
+    // others, so that e.g. a closed tree or a failing build of one queue does not block the others.
+    // PRs of path queues are also processed independently of the others, but they share the
+    // tree state of the repository.
+    let (queue_branches, path_queues, block_labels, queue_check_runs, aging_interval, max_parallel) = {
+        let config = repo.config.load();
+        (
+            config.merge_queue_branches.clone(),
+            config.path_queues.clone(),
+            config.block_labels.clone(),
+            config.queue_check_runs,
+            config.priority_aging_interval,
+            config.max_parallel_auto_builds,
+        )
+    };
+    let required_approvals = repo.config.load().required_approvals;
+    // PRs that do not fit into the queue wait for queue space, whichever queue they belong to
+    let overflow_prs = queue_overflow(
+        &ctx.db.get_nonclosed_pull_requests(repo_name).await?,
+        repo.config.load().max_queue_size,
+    );
+    // Sorted PRs of each queue, used for the queue check runs
+    let mut queues = vec![];
+    let mut skips = TickSkips::default();
+    // PRs of all queues, and the tree priorities of the queues, used to find out why the other
+    // approved PRs were not included in any queue
+    let mut queued_prs: HashSet<PullRequestNumber> = HashSet::new();
+    let mut branch_tree_priorities: HashMap<&str, Option<u32>> = HashMap::new();
+
+    let paused = match ctx.db.get_merge_queue_pause(repo_name).await? {
+        Some(pause) => {
+            tracing::info!(
+                "Merge queue of {repo_name} is paused by {} since {}",
+                pause.paused_by,
+                pause.created_at
+            );
+            true
+        }
+        None => false,
+    };
+
+    let tree_state = apply_maintenance_windows(repo, ctx, repo_db.tree_state).await?;
+    let tree_state = open_tree_if_expired(ctx, repo_name, None, tree_state).await?;
+    let (path_queue_prs, prs): (Vec<PullRequestModel>, Vec<PullRequestModel>) = ctx
+        .db
+        .get_merge_queue_prs(repo_name, tree_state.priority(), &block_labels)
+        .await?
+        .into_iter()
+        .filter(|pr| !queue_branches.contains(&pr.base_branch))
+        .filter(|pr| has_required_approvals(pr, required_approvals))
+        .filter(|pr| !overflow_prs.contains(&pr.number))
+        .partition(|pr| {
+            pr.path_queue
+                .as_ref()
+                .is_some_and(|queue| path_queues.contains_key(queue))
+        });
+    if queue_check_runs {
+        queues.push(sort_queue_prs(prs.clone(), aging_interval));
+    }
+    queued_prs.extend(prs.iter().chain(&path_queue_prs).map(|pr| pr.number));
+    let queue = MergeQueue {
+        name: None,
+        max_parallel_builds: max_parallel,
+    };
+    if let Err(error) = process_queue(repo, ctx, &queue, prs, paused, &mut skips).await {
+        tracing::error!("Could not process the merge queue of {repo_name}: {error:?}");
+    }
+
+    for (name, path_queue) in &path_queues {
+        let prs: Vec<PullRequestModel> = path_queue_prs
+            .iter()
+            .filter(|pr| pr.path_queue.as_ref() == Some(name))
+            .cloned()
+            .collect();
+        if queue_check_runs {
+            queues.push(sort_queue_prs(prs.clone(), aging_interval));
+        }
+        let queue = MergeQueue {
+            name: Some(name.as_str()),
+            max_parallel_builds: path_queue.max_parallel_auto_builds.unwrap_or(max_parallel),
+        };
+        if let Err(error) = process_queue(repo, ctx, &queue, prs, paused, &mut skips).await {
+            tracing::error!("Could not process the path queue `{name}` of {repo_name}: {error:?}");
+        }
+    }
+
+    for branch in &queue_branches {
+        let tree_state = ctx.db.get_branch_tree_state(repo_name, branch).await?;
+        let tree_state = open_tree_if_expired(ctx, repo_name, Some(branch), tree_state).await?;
+        let prs: Vec<PullRequestModel> = ctx
+            .db
+            .get_merge_queue_prs(repo_name, tree_state.priority(), &block_labels)
+            .await?
+            .into_iter()
+            .filter(|pr| &pr.base_branch == branch)
+            .filter(|pr| has_required_approvals(pr, required_approvals))
+            .filter(|pr| !overflow_prs.contains(&pr.number))
+            .collect();
+        if queue_check_runs {
+            queues.push(sort_queue_prs(prs.clone(), aging_interval));
+        }
+        queued_prs.extend(prs.iter().map(|pr| pr.number));
+        branch_tree_priorities.insert(branch, tree_state.priority());
+        let queue = MergeQueue {
+            name: Some(branch.as_str()),
+            max_parallel_builds: max_parallel,
+        };
+        if let Err(error) = process_queue(repo, ctx, &queue, prs, paused, &mut skips).await {
+            tracing::error!(
+                "Could not process the merge queue of branch `{branch}` of {repo_name}: {error:?}"
+            );
+        }
+    }
+
+    if queue_check_runs
+        && !repo.config.load().dry_run
+        && let Err(error) = update_queue_check_runs(repo, ctx, &queues).await
+    {
+        tracing::error!("Could not update queue check runs of {repo_name}: {error:?}");
+    }
+
+    for pr in ctx.db.get_nonclosed_pull_requests(repo_name).await? {
+        if pr.pr_status != PullRequestStatus::Open
+            || !matches!(pr.queue_status(), QueueStatus::Approved(..))
+            || queued_prs.contains(&pr.number)
+        {
+            continue;
+        }
+        let tree_priority = match branch_tree_priorities.get(pr.base_branch.as_str()) {
+            Some(priority) => *priority,
+            None => tree_state.priority(),
+        };
+        if overflow_prs.contains(&pr.number) {
+            skips.skip(pr.number, SkipReason::QueueFull);
+        } else if let Some(reason) =
+            excluded_pr_skip_reason(&pr, &block_labels, required_approvals, tree_priority)
+        {
+            skips.skip(pr.number, reason);
+        }
+    }
+    QUEUE_SKIPS.record(repo_name, skips, Utc::now());
+
+    Ok(())
+}
+
+/// Returns the PRs that do not fit into a merge queue limited to `max_queue_size` PRs.
+/// PRs with a running or successful auto build always fit, the others fit in the order in which
+/// they were approved.
+pub(crate) fn queue_overflow(
+    prs: &[PullRequestModel],
+    max_queue_size: Option<usize>,
+) -> HashSet<PullRequestNumber> {
+    let Some(max_queue_size) = max_queue_size else {
+        return HashSet::new();
+    };
+    let mut queued: Vec<&PullRequestModel> = prs
+        .iter()
+        .filter(|pr| pr.queue_status().is_in_queue() && !pr.parked)
+        .collect();
+    queued.sort_by_key(|pr| {
+        (
+            matches!(pr.queue_status(), QueueStatus::Approved(..)),
+            pr.approved_at,
+            pr.number,
+        )
+    });
+    queued
+        .into_iter()
+        .skip(max_queue_size)
+        .map(|pr| pr.number)
+        .collect()
+}
+
+/// Returns why an approved PR was not included in its merge queue.
+fn excluded_pr_skip_reason(
+    pr: &PullRequestModel,
+    block_labels: &[String],
+    required_approvals: usize,
+    tree_priority: Option<u32>,
+) -> Option<SkipReason> {
+    if pr.parked {
+        Some(SkipReason::Parked)
+    } else if pr.mergeable_state == MergeableState::HasConflicts {
+        Some(SkipReason::MergeConflict)
+    } else if pr.mergeable_state == MergeableState::Unknown {
+        Some(SkipReason::UnknownMergeability)
+    } else if pr.labels.iter().any(|label| block_labels.contains(label)) {
+        Some(SkipReason::BlockingLabel)
+    } else if !has_required_approvals(pr, required_approvals) {
+        Some(SkipReason::MissingApprovals)
+    } else if tree_priority
+        .is_some_and(|priority| i64::from(pr.priority.unwrap_or(0)) < i64::from(priority))
+    {
+        Some(SkipReason::BelowTreePriority)
+    } else {
+        None
+    }
+}
+
+/// Closes the tree of the repository when one of its maintenance windows starts, and announces
+/// the end of the windows that closed it on the tracking issue. The tree is reopened by
+/// [`open_tree_if_expired`], because the closure expires at the end of the window.
+async fn apply_maintenance_windows(
+    repo: &RepositoryState,
+    ctx: &BorsContext,
+    tree_state: TreeState,
+) -> anyhow::Result<TreeState> {
+    let repo_name = repo.repository();
+    let config = repo.config.load();
+    let tracking_issue = config.tracking_issue.map(PullRequestNumber);
+
+    for ended_at in ctx
+        .db
+        .finish_expired_scheduled_tree_closures(repo_name)
+        .await?
+    {
+        // The tree could have been reopened or closed again manually during the window
+        if tree_state.closed_until() == Some(ended_at)
+            && let Some(issue) = tracking_issue
+        {
+            let comment = maintenance_window_ended_comment(config.language);
+            post_comment_or_enqueue(repo, &ctx.db, issue, comment).await;
+        }
+    }
+
+    let Some((window, starts_at, ends_at)) = config.active_maintenance_window(Utc::now()) else {
+        return Ok(tree_state);
+    };
+    if !ctx
+        .db
+        .record_scheduled_tree_closure(repo_name, starts_at, ends_at)
+        .await?
+    {
+        return Ok(tree_state);
+    }
+    if tree_state.is_closed() && !tree_state.is_expired() {
+        tracing::info!(
+            "Maintenance window of {repo_name} started, but the tree is already closed: {tree_state:?}"
+        );
+        return Ok(tree_state);
+    }
+
+    let source = match tracking_issue {
+        Some(issue) => format!("{}/issues/{issue}", repo.client.repository_url()),
+        None => repo.client.repository_url(),
+    };
+    let tree_state = TreeState::Closed {
+        priority: window.priority,
+        source,
+        reason: window.reason.clone(),
+        until: Some(ends_at),
+    };
+    tracing::info!("Maintenance window of {repo_name} started, closing the tree until {ends_at}");
+    ctx.db
+        .upsert_repository(repo_name, tree_state.clone())
+        .await?;
+    ctx.db
+        .record_audit_action(
+            repo_name,
+            None,
+            "tree_closed",
+            Some(&format!(
+                "closed for a maintenance window below priority {} until {ends_at}",
+                window.priority
+            )),
+        )
+        .await?;
+    if let Some(issue) = tracking_issue {
+        post_comment_or_enqueue(
+            repo,
+            &ctx.db,
+            issue,
+            maintenance_window_started_comment(&tree_state),
+        )
+        .await;
+    }
+    Ok(tree_state)
+}
+
+/// Reopens the tree of the repository (or of the given base branch with its own queue) if it
+/// was closed only for a limited time, which has already elapsed.
+async fn open_tree_if_expired(
+    ctx: &BorsContext,
+    repo_name: &GithubRepoName,
+    queue_branch: Option<&str>,
+    tree_state: TreeState,
+) -> anyhow::Result<TreeState> {
+    if !tree_state.is_expired() {
+        return Ok(tree_state);
+    }
+    match queue_branch {
+        Some(branch) => {
+            tracing::info!("Tree closure of branch `{branch}` of {repo_name} expired");
+            ctx.db
+                .upsert_branch_tree_state(repo_name, branch, TreeState::Open)
+                .await?;
+            ctx.db
+                .record_audit_action(
+                    repo_name,
+                    None,
+                    "tree_opened",
+                    Some(&format!("closure of branch `{branch}` expired")),
+                )
+                .await?;
+        }
+        None => {
+            tracing::info!("Tree closure of {repo_name} expired");
+            ctx.db.upsert_repository(repo_name, TreeState::Open).await?;
+            ctx.db
+                .record_audit_action(repo_name, None, "tree_opened", Some("closure expired"))
+                .await?;
+        }
+    }
+    Ok(TreeState::Open)
+}
+
+/// A single merge queue of a repository.
+struct MergeQueue<'a> {
+    /// Name of the queue, which is the base branch of its PRs for base branches with their own
+    /// queue, or the name of a path queue. `None` for the default queue, which contains PRs
+    /// targeting branches that do not have their own queue.
+    name: Option<&'a str>,
+    /// Maximum number of auto builds of the queue that can run at the same time.
+    max_parallel_builds: usize,
+}
 
-    let priority = repo_db.tree_state.priority();
-    let prs = ctx.db.get_merge_queue_prs(repo_name, priority).await?;
+/// Process a single merge queue of a repository.
+/// If the queue is `paused`, successful auto builds are merged, but no new ones are started.
+async fn process_queue(
+    repo: &RepositoryState,
+    ctx: &BorsContext,
+    queue: &MergeQueue<'_>,
+    prs: Vec<PullRequestModel>,
+    paused: bool,
+    skips: &mut TickSkips,
+) -> anyhow::Result<()> {
+    let repo_name = repo.repository();
 
     // Sort PRs according to merge queue priority rules.
     // Successful builds come first so they can be merged immediately,
     // then pending builds (which block the queue to prevent starting simultaneous auto-builds).
-    let prs = sort_queue_prs(prs);
+    let prs = sort_queue_prs(prs, repo.config.load().priority_aging_interval);
+
+    // Failing to deliver notifications should not block merges.
+    if let Err(error) = notify_queue_subscribers(repo, ctx, &prs).await {
+        tracing::error!("Could not send queue notifications for {repo_name}: {error:?}");
+    }
+
+    if queue.max_parallel_builds > 1 {
+        return process_speculative_queue(repo, ctx, queue, prs, paused, skips).await;
+    }
 
-    for pr in prs {
+    for (index, pr) in prs.iter().enumerate() {
         let pr_num = pr.number;
 
         match pr.queue_status() {
@@ -144,21 +607,345 @@ async fn process_repository(repo: &RepositoryState, ctx: &BorsContext) -> anyhow
                 // Build in progress - stop queue. We can only have one PR being built
                 // at a time.
                 tracing::info!("PR {pr_num} has a pending build - blocking queue");
+                skips.skip_waiting(&prs[index + 1..], SkipReason::PendingBuild);
                 break;
             }
             QueueStatus::ReadyForMerge(approval_info, auto_build) => {
-                handle_successful_build(repo, ctx, &pr, &auto_build, &approval_info, pr_num)
-                    .await?;
+                if pr.held {
+                    // The held PR stays at the top of the queue and blocks it
+                    tracing::info!("PR {pr_num} is held - not merging it");
+                    skips.skip_waiting(&prs[index + 1..], SkipReason::BehindHeldPr);
+                } else {
+                    handle_successful_build(repo, ctx, pr, &auto_build, &approval_info).await?;
+                    skips.skip_waiting(&prs[index + 1..], SkipReason::MergingOtherPr);
+                }
                 break;
             }
             QueueStatus::Approved(..) => {
-                if handle_start_auto_build(repo, ctx, &pr, pr_num).await? {
+                if paused {
+                    skips.skip_waiting(&prs[index..], SkipReason::QueuePaused);
                     break;
                 }
+                let branch = auto_branch_name(queue.name, 0);
+                let waiting_behind = count_waiting_prs(&prs[index + 1..]);
+                match handle_start_auto_build(repo, ctx, pr, pr_num, &branch, None, waiting_behind)
+                    .await?
+                {
+                    AutoBuildStart::Started(_) => {
+                        skips.skip_waiting(&prs[index + 1..], SkipReason::PendingBuild);
+                        break;
+                    }
+                    AutoBuildStart::Skipped(reason) => skips.skip(pr_num, reason),
+                    AutoBuildStart::DryRun => {
+                        skips.skip(pr_num, SkipReason::DryRun);
+                        skips.skip_waiting(&prs[index + 1..], SkipReason::BlockedByEarlierPr);
+                        break;
+                    }
+                    AutoBuildStart::Blocked => {
+                        skips.skip(pr_num, SkipReason::StartFailed);
+                        skips.skip_waiting(&prs[index + 1..], SkipReason::BlockedByEarlierPr);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Process the merge queue while allowing up to `max_parallel_builds` auto builds to run at the
+/// same time.
+///
+/// Each new auto build is started speculatively on top of the merge commit of the previously
+/// started auto build, so that the PRs can be merged one after another as soon as their builds
+/// succeed. If a build fails, the builds that were started on top of it are discarded and
+/// rebuilt.
+async fn process_speculative_queue(
+    repo: &RepositoryState,
+    ctx: &BorsContext,
+    queue: &MergeQueue<'_>,
+    prs: Vec<PullRequestModel>,
+    paused: bool,
+    skips: &mut TickSkips,
+) -> anyhow::Result<()> {
+    let max_parallel_builds = queue.max_parallel_builds;
+    // Auto builds that are running or waiting to be merged, in the order in which they were started
+    let mut builds: Vec<(&PullRequestModel, &BuildModel)> = prs
+        .iter()
+        .filter_map(|pr| pr.auto_build.as_ref().map(|build| (pr, build)))
+        .collect();
+    builds.sort_by_key(|(_, build)| build.id);
+
+    let mut base_shas: HashMap<&str, CommitSha> = HashMap::new();
+    for (pr, _) in &builds {
+        if !base_shas.contains_key(pr.base_branch.as_str()) {
+            let sha = repo.client.get_branch_sha(&pr.base_branch).await?;
+            base_shas.insert(&pr.base_branch, sha);
+        }
+    }
+
+    // A build is only valid if it was started on top of the base branch, or on top of another
+    // valid build. Other builds depend on a build that has failed or was cancelled, so they have
+    // to be discarded.
+    let mut valid_shas: HashSet<&str> = base_shas.values().map(|sha| sha.0.as_str()).collect();
+    let mut running = vec![];
+    for (pr, build) in builds {
+        if valid_shas.contains(build.parent.as_str()) {
+            valid_shas.insert(&build.commit_sha);
+            running.push((pr, build));
+        } else {
+            let comment = speculative_build_discarded_comment(repo.config.load().language);
+            discard_auto_build(repo, ctx, pr, build, comment).await?;
+        }
+    }
+
+    // Merge the first build, if it has succeeded and its PR is not held. The following builds can
+    // only be merged after it.
+    let running_shas: HashSet<&str> = running
+        .iter()
+        .map(|(_, build)| build.commit_sha.as_str())
+        .collect();
+    if let Some((pr, build)) = running
+        .iter()
+        .find(|(_, build)| !running_shas.contains(build.parent.as_str()))
+        && !pr.held
+        && let QueueStatus::ReadyForMerge(approval_info, auto_build) = pr.queue_status()
+    {
+        debug_assert_eq!(auto_build.id, build.id);
+        skips.skip_waiting(&prs, SkipReason::MergingOtherPr);
+        return handle_successful_build(repo, ctx, pr, &auto_build, &approval_info).await;
+    }
+
+    if paused {
+        skips.skip_waiting(&prs, SkipReason::QueuePaused);
+        return Ok(());
+    }
+
+    // Start new builds on top of the last running build
+    let mut used_branches: HashSet<String> = running
+        .iter()
+        .map(|(_, build)| build.branch.clone())
+        .collect();
+    let mut tip: Option<(CommitSha, String)> = running
+        .last()
+        .map(|(pr, build)| (CommitSha(build.commit_sha.clone()), pr.base_branch.clone()));
+    let mut running_count = running.len();
+
+    for (index, pr) in prs.iter().enumerate() {
+        if running_count >= max_parallel_builds {
+            skips.skip_waiting(&prs[index..], SkipReason::ParallelLimit);
+            break;
+        }
+        // Discarded builds will be restarted in the next tick
+        if !matches!(pr.queue_status(), QueueStatus::Approved(..)) {
+            continue;
+        }
+        // We cannot build PRs with different base branches on top of each other
+        if let Some((_, base_branch)) = &tip
+            && base_branch != &pr.base_branch
+        {
+            skips.skip_waiting(&prs[index..], SkipReason::DifferentBaseBranch);
+            break;
+        }
+
+        let Some(branch) = (0..max_parallel_builds)
+            .map(|slot| auto_branch_name(queue.name, slot))
+            .find(|branch| !used_branches.contains(branch))
+        else {
+            skips.skip_waiting(&prs[index..], SkipReason::ParallelLimit);
+            break;
+        };
+        let parent = tip.as_ref().map(|(sha, _)| sha);
+        let waiting_behind = count_waiting_prs(&prs[index + 1..]);
+        match handle_start_auto_build(repo, ctx, pr, pr.number, &branch, parent, waiting_behind)
+            .await?
+        {
+            AutoBuildStart::Started(merge_sha) => {
+                used_branches.insert(branch);
+                tip = Some((merge_sha, pr.base_branch.clone()));
+                running_count += 1;
+            }
+            AutoBuildStart::Skipped(reason) => skips.skip(pr.number, reason),
+            AutoBuildStart::DryRun => {
+                skips.skip(pr.number, SkipReason::DryRun);
+                skips.skip_waiting(&prs[index + 1..], SkipReason::BlockedByEarlierPr);
+                break;
+            }
+            AutoBuildStart::Blocked => {
+                skips.skip(pr.number, SkipReason::StartFailed);
+                skips.skip_waiting(&prs[index + 1..], SkipReason::BlockedByEarlierPr);
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Discard an auto build whose result cannot be merged, e.g. because it was started on top of a
+/// build that did not succeed. The PR stays approved, so that a new auto build is started for it.
+async fn discard_auto_build(
+    repo: &RepositoryState,
+    ctx: &BorsContext,
+    pr: &PullRequestModel,
+    build: &BuildModel,
+    comment: Comment,
+) -> anyhow::Result<()> {
+    tracing::info!("Discarding auto build {build:?} of PR {}", pr.number);
+    if build.status == BuildStatus::Pending {
+        match cancel_build(&repo.client, &ctx.db, build, CheckRunConclusion::Cancelled).await {
+            Ok(_) => {}
+            Err(CancelBuildError::FailedToMarkBuildAsCancelled(error)) => return Err(error),
+            Err(CancelBuildError::FailedToCancelWorkflows(error)) => {
+                tracing::error!(
+                    "Could not cancel workflows of discarded build {}: {error:?}",
+                    build.commit_sha
+                );
             }
         }
     }
+    let actions = [comment_action(repo, comment)];
+    let entries = ctx.db.clear_auto_build_with_outbox(pr, &actions).await?;
+    deliver_outbox_actions(repo, &ctx.db, entries).await
+}
+
+/// Create or update the check runs that show the position of approved PRs in their merge queue.
+/// The check runs of PRs that have left the queue are updated to say so, and they are no longer
+/// tracked.
+async fn update_queue_check_runs(
+    repo: &RepositoryState,
+    ctx: &BorsContext,
+    queues: &[Vec<PullRequestModel>],
+) -> anyhow::Result<()> {
+    let repo_name = repo.repository();
+    let check_runs = ctx.db.get_queue_check_runs(repo_name).await?;
+    let queue_url = format!("{}/queue/{}", ctx.get_web_url(), repo_name.name());
+
+    let mut queued_prs = HashSet::new();
+    for prs in queues {
+        for (position, pr) in prs.iter().enumerate() {
+            let Some(head_sha) = pr.approved_sha() else {
+                continue;
+            };
+            queued_prs.insert(pr.number);
+
+            let output = queue_check_run_output(position, &queue_url);
+            let check_run_id = match check_runs
+                .iter()
+                .find(|check_run| check_run.pr_number == pr.number)
+            {
+                Some(check_run) if check_run.head_sha == head_sha => {
+                    // Only talk to GitHub when the position has changed
+                    if check_run.position == position as i32 {
+                        continue;
+                    }
+                    repo.client
+                        .update_check_run_output(CheckRunId(check_run.check_run_id as u64), output)
+                        .await?;
+                    check_run.check_run_id
+                }
+                _ => {
+                    let check_run = repo
+                        .client
+                        .create_completed_check_run(
+                            QUEUE_CHECK_RUN_NAME,
+                            &CommitSha(head_sha.to_string()),
+                            CheckRunConclusion::Neutral,
+                            output,
+                            &pr.number.to_string(),
+                        )
+                        .await?;
+                    check_run.id.into_inner() as i64
+                }
+            };
+            ctx.db
+                .upsert_queue_check_run(
+                    repo_name,
+                    pr.number,
+                    check_run_id,
+                    head_sha,
+                    position as i32,
+                )
+                .await?;
+        }
+    }
+
+    for check_run in check_runs
+        .iter()
+        .filter(|check_run| !queued_prs.contains(&check_run.pr_number))
+    {
+        if let Err(error) = repo
+            .client
+            .update_check_run_output(
+                CheckRunId(check_run.check_run_id as u64),
+                CheckRunOutput {
+                    title: "Not in the merge queue".to_string(),
+                    summary: "This PR is no longer in the merge queue.".to_string(),
+                },
+            )
+            .await
+        {
+            tracing::warn!(
+                "Could not update queue check run of PR {}: {error:?}",
+                check_run.pr_number
+            );
+        }
+        ctx.db.delete_queue_check_run(check_run).await?;
+    }
+    Ok(())
+}
+
+/// `position` is the number of PRs ahead of the PR in the queue.
+fn queue_check_run_output(position: usize, queue_url: &str) -> CheckRunOutput {
+    CheckRunOutput {
+        title: format!("Position {} in the merge queue", position + 1),
+        summary: format!(
+            "There {} {position} {} ahead of this PR in the merge queue. See the [queue]({queue_url}) for details.",
+            if position == 1 { "is" } else { "are" },
+            pluralize("PR", position)
+        ),
+    }
+}
+
+/// Mention users that asked to be notified once their PR gets among the first
+/// [`QUEUE_NOTIFICATION_POSITION`] PRs of the queue.
+async fn notify_queue_subscribers(
+    repo: &RepositoryState,
+    ctx: &BorsContext,
+    prs: &[PullRequestModel],
+) -> anyhow::Result<()> {
+    let notifications = ctx.db.get_queue_notifications(repo.repository()).await?;
+    if notifications.is_empty() {
+        return Ok(());
+    }
+
+    for (index, pr) in prs.iter().take(QUEUE_NOTIFICATION_POSITION).enumerate() {
+        let usernames: Vec<String> = notifications
+            .iter()
+            .filter(|notification| notification.pr_number == pr.number)
+            .map(|notification| notification.username.clone())
+            .collect();
+        if usernames.is_empty() {
+            continue;
+        }
 
+        post_comment_or_enqueue(
+            repo,
+            &ctx.db,
+            pr.number,
+            queue_position_notification_comment(
+                &usernames,
+                index + 1,
+                ctx.get_web_url(),
+                repo.repository(),
+            ),
+        )
+        .await;
+        ctx.db
+            .delete_queue_notifications(repo.repository(), pr.number)
+            .await?;
+    }
     Ok(())
 }
 
@@ -169,16 +956,30 @@ async fn handle_successful_build(
     pr: &PullRequestModel,
     auto_build: &BuildModel,
     approval_info: &ApprovalInfo,
-    pr_num: PullRequestNumber,
 ) -> anyhow::Result<()> {
+    let pr_num = pr.number;
     let commit_sha = CommitSha(auto_build.commit_sha.clone());
-    let workflows = ctx.db.get_workflows_for_build(auto_build).await?;
-    let comment = auto_build_succeeded_comment(
-        &workflows,
-        &approval_info.approver,
-        &commit_sha,
-        &pr.base_branch,
-    );
+
+    // The base branch might have been pushed to (or even force-pushed to) since the auto build was
+    // started, e.g. by a merge of another queue. Only fast-forward the base branch if it still
+    // points to the commit on top of which the PR was tested, otherwise the PR has to be tested
+    // again, because its merge with the new base branch was never tested.
+    let base_sha = repo.client.get_branch_sha(&pr.base_branch).await?;
+    if base_sha.0 != auto_build.parent {
+        tracing::warn!(
+            "Base branch `{}` of PR {pr_num} moved from {} to {base_sha} since its auto build was started",
+            pr.base_branch,
+            auto_build.parent
+        );
+        return discard_auto_build(
+            repo,
+            ctx,
+            pr,
+            auto_build,
+            auto_build_base_moved_comment(&pr.base_branch, &auto_build.parent, &base_sha),
+        )
+        .await;
+    }
 
     if let Err(error) = repo
         .client
@@ -197,32 +998,104 @@ async fn handle_successful_build(
             error => auto_build_push_failed_comment(&error.to_string()),
         };
 
-        ctx.db
-            .update_build_status(auto_build, BuildStatus::Failure)
+        let actions = [comment_action(repo, error_comment)];
+        let entries = ctx
+            .db
+            .update_build_status_with_outbox(auto_build, BuildStatus::Failure, pr, &actions)
             .await?;
-        repo.client.post_comment(pr_num, error_comment).await?;
+        deliver_outbox_actions(repo, &ctx.db, entries).await
     } else {
         tracing::info!("Auto build succeeded and merged for PR {pr_num}");
+        let workflows = ctx.db.get_workflows_for_build(auto_build).await?;
+        let artifacts = ctx.db.get_artifacts_for_build(auto_build).await?;
+        let comment = auto_build_succeeded_comment(
+            &workflows,
+            &artifacts,
+            &approval_info.approver,
+            &commit_sha,
+            &pr.base_branch,
+        );
+        let mut actions = vec![comment_action(repo, comment)];
+        // GitHub only marks a PR as merged if its head is contained in the base branch, which is
+        // not the case for a squashed commit, so bors has to close the PR itself
+        let squashed = squash_merge(repo, pr);
+        if squashed {
+            let commit_url = format!("{}/commit/{commit_sha}", repo.client.repository_url());
+            actions.push(comment_action(
+                repo,
+                squash_merged_comment(&commit_url, &commit_sha, &pr.base_branch),
+            ));
+        }
+        let entries = ctx
+            .db
+            .set_pr_status_with_outbox(pr, PullRequestStatus::Merged, &actions)
+            .await?;
+        ctx.db.record_build_merged(auto_build).await?;
+        ctx.db
+            .record_pr_milestone(repo.repository(), pr_num, PrMilestone::Merged)
+            .await?;
         ctx.db
-            .set_pr_status(&pr.repository, pr.number, PullRequestStatus::Merged)
+            .record_audit_action(repo.repository(), Some(pr_num), "merged", None)
             .await?;
-        repo.client.post_comment(pr.number, comment).await?;
+        deliver_outbox_actions(repo, &ctx.db, entries).await?;
+
+        if squashed {
+            if let Err(error) = repo.client.close_pull_request(pr_num).await {
+                tracing::error!("Cannot close squash-merged PR {pr_num}: {error:?}");
+            }
+            run_merged_pr_hooks(repo, &ctx.db, pr_num, &pr.title).await?;
+        }
+        Ok(())
     }
+}
 
-    Ok(())
+/// Whether the changes of the PR are merged as a single squashed commit, instead of a merge
+/// commit.
+fn squash_merge(repo: &RepositoryState, pr: &PullRequestModel) -> bool {
+    pr.squash || repo.config.load().squash_merge
+}
+
+/// Outcome of an attempt to start an auto build.
+enum AutoBuildStart {
+    /// The auto build was started, with the given merge commit.
+    Started(CommitSha),
+    /// The PR cannot be built for the given reason, the queue should continue with the next PR.
+    Skipped(SkipReason),
+    /// The auto build would have been started, but the repository is in dry-run mode. The queue
+    /// should not continue, as if the build was pending.
+    DryRun,
+    /// The queue should not continue.
+    Blocked,
 }
 
-/// Handle starting a new auto build for an approved PR.
-/// Returns true if the queue should break, false to continue.
+/// Handle starting a new auto build for an approved PR on the given `branch`.
+/// If `parent` is set, the PR is merged on top of it instead of the base branch.
 async fn handle_start_auto_build(
     repo: &RepositoryState,
     ctx: &BorsContext,
     pr: &PullRequestModel,
     pr_num: PullRequestNumber,
-) -> anyhow::Result<bool> {
-    let Err(error) = start_auto_build(repo, ctx, pr).await else {
-        tracing::info!("Starting auto build for PR {pr_num}");
-        return Ok(true);
+    branch: &str,
+    parent: Option<&CommitSha>,
+    waiting_behind: usize,
+) -> anyhow::Result<AutoBuildStart> {
+    let error = match start_auto_build(repo, ctx, pr, branch, parent, waiting_behind).await {
+        Ok(merge_sha) => {
+            tracing::info!("Starting auto build for PR {pr_num}");
+            ctx.db
+                .record_pr_milestone(repo.repository(), pr_num, PrMilestone::BuildStarted)
+                .await?;
+            ctx.db
+                .record_audit_action(
+                    repo.repository(),
+                    Some(pr_num),
+                    "auto_build_started",
+                    Some(&format!("merge commit {merge_sha}")),
+                )
+                .await?;
+            return Ok(AutoBuildStart::Started(merge_sha));
+        }
+        Err(error) => error,
     };
 
     match error {
@@ -233,26 +1106,98 @@ async fn handle_start_auto_build(
             ctx.db
                 .update_pr_mergeable_state(pr, MergeableState::Unknown)
                 .await?;
-            repo.client
-                .post_comment(pr.number, merge_conflict_comment(&gh_pr.head.name))
-                .await?;
-            Ok(false)
+            post_comment_or_enqueue(
+                repo,
+                &ctx.db,
+                pr.number,
+                merge_conflict_comment(&gh_pr.head.name),
+            )
+            .await;
+            Ok(AutoBuildStart::Skipped(SkipReason::MergeConflict))
         }
         StartAutoBuildError::SanityCheckFailed(error) => {
             tracing::info!("Sanity check failed for PR {pr_num}: {error:?}");
-            Ok(true)
+            Ok(AutoBuildStart::Blocked)
         }
-        StartAutoBuildError::GitHubError(error) => {
-            tracing::debug!(
+        StartAutoBuildError::LintFailed(violations) => {
+            tracing::info!("PR {pr_num} violates lint rules: {violations:?}");
+            unapprove_pr(repo, &ctx.db, pr).await?;
+            post_comment_or_enqueue(
+                repo,
+                &ctx.db,
+                pr.number,
+                auto_build_lint_violations_comment(&violations),
+            )
+            .await;
+            Ok(AutoBuildStart::Skipped(SkipReason::LintViolation))
+        }
+        StartAutoBuildError::StaleApproval(approved_head_sha) => {
+            tracing::info!(
+                "PR {pr_num} has new commits since it was approved at {approved_head_sha}"
+            );
+            unapprove_pr(repo, &ctx.db, pr).await?;
+            post_comment_or_enqueue(
+                repo,
+                &ctx.db,
+                pr.number,
+                stale_approval_comment(&approved_head_sha),
+            )
+            .await;
+            Ok(AutoBuildStart::Skipped(SkipReason::StaleApproval))
+        }
+        StartAutoBuildError::AlreadyMerged => {
+            tracing::info!("Changes of PR {pr_num} are already contained in its base branch");
+            post_comment_or_enqueue(
+                repo,
+                &ctx.db,
+                pr.number,
+                already_merged_comment(&pr.base_branch),
+            )
+            .await;
+            repo.client.close_pull_request(pr.number).await?;
+            ctx.db
+                .set_pr_status(repo.repository(), pr.number, PullRequestStatus::Closed)
+                .await?;
+            ctx.db
+                .record_audit_action(
+                    repo.repository(),
+                    Some(pr_num),
+                    "closed",
+                    Some(&format!(
+                        "changes are already contained in `{}`",
+                        pr.base_branch
+                    )),
+                )
+                .await?;
+            Ok(AutoBuildStart::Skipped(SkipReason::AlreadyMerged))
+        }
+        StartAutoBuildError::DryRun { head_sha, base_sha } => {
+            tracing::info!("Not starting auto build for PR {pr_num}, because of dry-run mode");
+            // The queue is processed repeatedly, but each head is only described once
+            if pr.dry_run_head_sha.as_deref() != Some(head_sha.as_ref()) {
+                ctx.db.set_dry_run_head_sha(pr, &head_sha).await?;
+                let queue_url = format!("{}/queue/{}", ctx.get_web_url(), repo.repository().name());
+                post_comment_or_enqueue(
+                    repo,
+                    &ctx.db,
+                    pr.number,
+                    dry_run_auto_build_comment(&head_sha, &base_sha, waiting_behind, &queue_url),
+                )
+                .await;
+            }
+            Ok(AutoBuildStart::DryRun)
+        }
+        StartAutoBuildError::GitHubError(error) => {
+            tracing::debug!(
                 "Failed to start auto build for PR {pr_num} due to a GitHub error: {error:?}"
             );
-            Ok(true)
+            Ok(AutoBuildStart::Blocked)
         }
         StartAutoBuildError::DatabaseError(error) => {
             tracing::debug!(
                 "Failed to start auto build for PR {pr_num} due to database error: {error:?}"
             );
-            Ok(true)
+            Ok(AutoBuildStart::Blocked)
         }
     }
 }
@@ -267,6 +1212,20 @@ pub enum StartAutoBuildError {
     GitHubError(anyhow::Error),
     /// Sanity checks failed - PR state doesn't match requirements.
     SanityCheckFailed(anyhow::Error),
+    /// The PR title or description violates the lint rules of the repository, e.g. because it
+    /// was edited after the PR was approved.
+    LintFailed(Vec<String>),
+    /// The changes of the PR are already contained in its base branch.
+    AlreadyMerged,
+    /// New commits were pushed to the PR after it was approved at the given commit, and the
+    /// stale approval policy of the repository does not allow merging them.
+    StaleApproval(String),
+    /// The repository is in dry-run mode, so the auto build of the given head merged into the
+    /// given base was not started.
+    DryRun {
+        head_sha: CommitSha,
+        base_sha: CommitSha,
+    },
 }
 
 async fn verify_pr_state(gh_pr: &PullRequest, pr: &PullRequestModel) -> anyhow::Result<()> {
@@ -292,34 +1251,149 @@ async fn verify_pr_state(gh_pr: &PullRequest, pr: &PullRequestModel) -> anyhow::
     Ok(())
 }
 
-/// Starts a new auto build for a pull request.
+/// Decides, based on the stale approval policy of the repository, whether a PR can be merged
+/// with the commits that were pushed to it after it was approved at `approved_head_sha`.
+async fn can_merge_after_approval(
+    repo: &RepositoryState,
+    pr: &PullRequestModel,
+    approved_head_sha: &str,
+    head_sha: &CommitSha,
+) -> anyhow::Result<bool> {
+    match repo.config.load().stale_approval {
+        StaleApprovalPolicy::Merge => Ok(true),
+        StaleApprovalPolicy::RequireReapproval => Ok(false),
+        StaleApprovalPolicy::MergeBaseOnly => {
+            let client = &repo.client;
+            let new_commits = client
+                .get_commit_parents_between(&CommitSha(approved_head_sha.to_string()), head_sha)
+                .await?;
+            let branch_sha = client.get_branch_sha(&pr.base_branch).await?;
+            // Each new commit has to be a merge commit, whose other parent comes from the base
+            // branch
+            for parents in new_commits {
+                let mut merged_from_base = false;
+                for parent in parents.iter().skip(1) {
+                    if client.is_ancestor(parent, &branch_sha).await? {
+                        merged_from_base = true;
+                        break;
+                    }
+                }
+                if !merged_from_base {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+    }
+}
+
+/// PRs only enter the merge queue once enough distinct reviewers have commented an approval.
+/// Users that are only named in `r=<user>` do not count.
+/// PRs whose auto build was already started are kept, e.g. if the config was changed meanwhile.
+pub fn has_required_approvals(pr: &PullRequestModel, required_approvals: usize) -> bool {
+    pr.auto_build.is_some() || pr.approval_authors.len() >= required_approvals
+}
+
+/// Returns the number of PRs that wait for an auto build to be started.
+fn count_waiting_prs(prs: &[PullRequestModel]) -> usize {
+    prs.iter()
+        .filter(|pr| matches!(pr.queue_status(), QueueStatus::Approved(..)))
+        .count()
+}
+
+/// Starts a new auto build for a pull request on the given `branch`.
+/// The PR is merged on top of `parent`, or on top of its base branch if `parent` is not set.
+/// `waiting_behind` is the number of approved PRs that wait behind this PR in the queue.
+/// Returns the SHA of the merge commit that is being built.
 async fn start_auto_build(
     repo: &RepositoryState,
     ctx: &BorsContext,
     pr: &PullRequestModel,
-) -> anyhow::Result<(), StartAutoBuildError> {
+    branch: &str,
+    parent: Option<&CommitSha>,
+    waiting_behind: usize,
+) -> anyhow::Result<CommitSha, StartAutoBuildError> {
     let client = &repo.client;
 
     let gh_pr = client
         .get_pull_request(pr.number)
         .await
         .map_err(StartAutoBuildError::GitHubError)?;
-    let base_sha = client
-        .get_branch_sha(&pr.base_branch)
-        .await
-        .map_err(StartAutoBuildError::GitHubError)?;
+    let base_sha = match parent {
+        Some(parent) => parent.clone(),
+        None => client
+            .get_branch_sha(&pr.base_branch)
+            .await
+            .map_err(StartAutoBuildError::GitHubError)?,
+    };
     let head_sha = gh_pr.head.sha.clone();
 
     verify_pr_state(&gh_pr, pr)
         .await
         .map_err(StartAutoBuildError::SanityCheckFailed)?;
 
+    let violations = repo
+        .config
+        .load()
+        .lint
+        .violations(&gh_pr.title, &gh_pr.message);
+    if !violations.is_empty() {
+        return Err(StartAutoBuildError::LintFailed(violations));
+    }
+
+    if let Some(approved_head_sha) = &pr.approved_head_sha
+        && approved_head_sha != head_sha.as_ref()
+        && !can_merge_after_approval(repo, pr, approved_head_sha, &head_sha)
+            .await
+            .map_err(StartAutoBuildError::GitHubError)?
+    {
+        return Err(StartAutoBuildError::StaleApproval(
+            approved_head_sha.clone(),
+        ));
+    }
+
+    // A speculative build starts on top of the builds of other PRs, which are not merged yet, so
+    // their changes cannot be considered to be merged
+    // In dry-run mode, PRs are not closed
+    let check_already_merged = {
+        let config = repo.config.load();
+        config.close_already_merged && !config.dry_run
+    };
+    if check_already_merged {
+        let branch_sha = match parent {
+            Some(_) => client
+                .get_branch_sha(&pr.base_branch)
+                .await
+                .map_err(StartAutoBuildError::GitHubError)?,
+            None => base_sha.clone(),
+        };
+        if client
+            .is_ancestor(&head_sha, &branch_sha)
+            .await
+            .map_err(StartAutoBuildError::GitHubError)?
+        {
+            return Err(StartAutoBuildError::AlreadyMerged);
+        }
+    }
+
+    if repo.config.load().dry_run {
+        return Err(StartAutoBuildError::DryRun { head_sha, base_sha });
+    }
+
     let pr_data = super::handlers::PullRequestData {
         db: pr,
         github: &gh_pr,
+        review_thread: None,
+        command_comment: None,
     };
 
-    let auto_merge_commit_message = create_merge_commit_message(pr_data, MergeType::Auto);
+    // Reserve the build ID upfront, so that it can be included in the merge commit message
+    let build_id = ctx
+        .db
+        .reserve_build_id()
+        .await
+        .map_err(StartAutoBuildError::DatabaseError)?;
+    let auto_merge_commit_message = create_merge_commit_message(pr_data, MergeType::Auto, build_id);
 
     // 1. Merge PR head with base branch on `AUTO_MERGE_BRANCH_NAME`
     let merge_sha = match attempt_merge(
@@ -336,18 +1410,46 @@ async fn start_auto_build(
         Err(error) => return Err(StartAutoBuildError::GitHubError(error)),
     };
 
-    // 2. Push merge commit to `AUTO_BRANCH_NAME` where CI runs
+    // The head of a cherry-picked PR is not contained in the base branch, but merging it does
+    // not change anything
+    if check_already_merged
+        && parent.is_none()
+        && !client
+            .has_changes(&base_sha, &merge_sha)
+            .await
+            .map_err(StartAutoBuildError::GitHubError)?
+    {
+        return Err(StartAutoBuildError::AlreadyMerged);
+    }
+
+    // If requested, replace the merge commit with a single commit on top of the base, which
+    // contains all the changes of the PR
+    let merge_sha = if squash_merge(repo, pr) {
+        client
+            .create_squashed_commit(
+                &merge_sha,
+                &base_sha,
+                &auto_merge_commit_message,
+                &gh_pr.author,
+            )
+            .await
+            .map_err(StartAutoBuildError::GitHubError)?
+    } else {
+        merge_sha
+    };
+
+    // 2. Push merge commit to the auto branch where CI runs
     client
-        .set_branch_to_sha(AUTO_BRANCH_NAME, &merge_sha, ForcePush::Yes)
+        .set_branch_to_sha(branch, &merge_sha, ForcePush::Yes)
         .await
         .map_err(|e| StartAutoBuildError::GitHubError(e.into()))?;
 
     // 3. Record the build in the database
-    let build_id = ctx
-        .db
+    ctx.db
         .attach_auto_build(
             pr,
-            AUTO_BRANCH_NAME.to_string(),
+            build_id,
+            branch.to_string(),
             merge_sha.clone(),
             base_sha,
         )
@@ -387,16 +1489,24 @@ async fn start_auto_build(
         }
     }
 
-    // 5. Post status comment
-    let comment = auto_build_started_comment(&head_sha, &merge_sha);
-    if let Err(error) = client.post_comment(pr.number, comment).await {
-        tracing::error!(
-            "Failed to post auto build started comment on PR {}: {error:?}",
-            pr.number
-        );
-    };
+    // 5. Post status comment, which replaces the comments of the previous auto builds
+    if repo.config.load().hide_outdated_comments
+        && let Err(error) = hide_tagged_comments(
+            repo,
+            &ctx.db,
+            pr.number,
+            &[CommentTag::AutoBuildStarted, CommentTag::AutoBuildFailed],
+        )
+        .await
+    {
+        tracing::error!("Failed to hide outdated auto build comment(s): {error:?}");
+    }
+    let queue_url = format!("{}/queue/{}", ctx.get_web_url(), repo.repository().name());
+    let comment = auto_build_started_comment(&head_sha, &merge_sha, waiting_behind, &queue_url);
+    let comment = tag_status_comment(repo, comment, CommentTag::AutoBuildStarted);
+    post_comment_or_enqueue(repo, &ctx.db, pr.number, comment).await;
 
-    Ok(())
+    Ok(merge_sha)
 }
 
 /// Starts the background merge queue loop.
@@ -432,6 +1542,7 @@ pub fn start_merge_queue(
         ) {
             *notified = false;
             *last_executed_at = Utc::now();
+            HEALTH_METRICS.record_merge_queue_tick(*last_executed_at);
 
             let span = tracing::info_span!("MergeQueue");
             tracing::debug!("Processing merge queue");
@@ -477,17 +1588,27 @@ pub fn start_merge_queue(
 
 #[cfg(test)]
 mod tests {
+    use chrono::{DateTime, SubsecRound, Utc};
     use octocrab::params::checks::{CheckRunConclusion, CheckRunStatus};
 
     use crate::tests::{BorsBuilder, GitHubState, run_test};
     use crate::{
         bors::{
             PullRequestStatus,
-            merge_queue::{AUTO_BRANCH_NAME, AUTO_BUILD_CHECK_RUN_NAME, AUTO_MERGE_BRANCH_NAME},
+            merge_queue::{
+                AUTO_BRANCH_NAME, AUTO_BUILD_CHECK_RUN_NAME, AUTO_MERGE_BRANCH_NAME,
+                QUEUE_CHECK_RUN_NAME, auto_branch_name, is_auto_branch,
+            },
+        },
+        database::{
+            BuildStatus, CoordinationLock, MergeableState, OctocrabMergeableState, TreeState,
+            operations::set_pr_approved_at,
+        },
+        github::{CommitSha, PullRequestNumber, api::client::HideCommentReason},
+        tests::{
+            BorsTester, BranchPushBehaviour, BranchPushError, Comment, default_branch_name,
+            default_repo_name,
         },
-        database::{BuildStatus, MergeableState, OctocrabMergeableState},
-        github::CommitSha,
-        tests::{BorsTester, BranchPushBehaviour, BranchPushError, Comment, default_repo_name},
     };
 
     #[sqlx::test]
@@ -514,6 +1635,126 @@ merge_queue_enabled = false
             .await;
     }
 
+    async fn queue_check_runs(tester: &BorsTester) -> Vec<(String, String, Option<String>)> {
+        let repo = tester.default_repo().await;
+        repo.lock()
+            .check_runs
+            .iter()
+            .filter(|check_run| check_run.name == QUEUE_CHECK_RUN_NAME)
+            .map(|check_run| {
+                (
+                    check_run.head_sha.clone(),
+                    check_run.title.clone(),
+                    check_run.conclusion.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[sqlx::test]
+    async fn queue_check_runs_show_position(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+queue_check_runs = true
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+                tester.approve(()).await?;
+                tester.approve(pr2.id()).await?;
+                tester.start_auto_build(()).await?;
+                insta::assert_debug_snapshot!(queue_check_runs(tester).await, @r#"
+                [
+                    (
+                        "pr-1-sha",
+                        "Position 1 in the merge queue",
+                        Some(
+                            "neutral",
+                        ),
+                    ),
+                    (
+                        "pr-2-sha",
+                        "Position 2 in the merge queue",
+                        Some(
+                            "neutral",
+                        ),
+                    ),
+                ]
+                "#);
+
+                tester.post_comment("@bors r-").await?;
+                tester.expect_comments((), 1).await;
+                tester.start_auto_build(pr2.id()).await?;
+                insta::assert_debug_snapshot!(queue_check_runs(tester).await, @r#"
+                [
+                    (
+                        "pr-1-sha",
+                        "Not in the merge queue",
+                        Some(
+                            "neutral",
+                        ),
+                    ),
+                    (
+                        "pr-2-sha",
+                        "Position 1 in the merge queue",
+                        Some(
+                            "neutral",
+                        ),
+                    ),
+                ]
+                "#);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn merge_queue_locked_by_other_instance(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            let lock = tester
+                .db()
+                .try_lock(CoordinationLock::MergeQueue)
+                .await?
+                .expect("lock should be free");
+            tester.process_merge_queue().await;
+            tester.get_pr_copy(()).await.expect_no_auto_build();
+
+            lock.release().await;
+            tester.start_auto_build(()).await?;
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn blocking_label_skips_pr(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+block_labels = ["S-blocked"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.add_label((), "S-blocked").await?;
+                tester
+                    .wait_for_pr((), |pr| pr.labels.contains(&"S-blocked".to_string()))
+                    .await?;
+                tester.process_merge_queue().await;
+                tester.get_pr_copy(()).await.expect_no_auto_build();
+
+                tester.remove_label((), "S-blocked").await?;
+                tester.wait_for_pr((), |pr| pr.labels.is_empty()).await?;
+                tester.start_auto_build(()).await?;
+                Ok(())
+            })
+            .await;
+    }
+
     #[sqlx::test]
     async fn auto_build_check_run_created(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
@@ -540,7 +1781,65 @@ merge_queue_enabled = false
             tester.process_merge_queue().await;
             insta::assert_snapshot!(
                 tester.get_next_comment_text(()).await?,
-                @":hourglass: Testing commit pr-1-sha with merge merge-0-pr-1..."
+                @r"
+            :hourglass: Testing commit pr-1-sha with merge merge-0-pr-1...
+
+            No PRs are waiting behind it in the [queue](https://test.com/bors/queue/borstest).
+            "
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn hide_outdated_auto_build_comments(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+hide_outdated_comments = true
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.process_merge_queue().await;
+                let started = tester.get_next_comment(()).await?;
+                tester
+                    .workflow_full_failure(tester.auto_branch().await)
+                    .await?;
+                let failed = tester.get_next_comment(()).await?;
+
+                tester.post_comment("@bors retry").await?;
+                tester.wait_for_pr((), |pr| pr.auto_build.is_none()).await?;
+                tester.start_auto_build(()).await?;
+                tester
+                    .expect_hidden_comment(&started, HideCommentReason::Outdated)
+                    .await;
+                tester
+                    .expect_hidden_comment(&failed, HideCommentReason::Outdated)
+                    .await;
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn auto_build_started_comment_waiting_prs(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+            let pr3 = tester.open_pr(default_repo_name(), |_| {}).await?;
+            tester.approve(()).await?;
+            tester.approve(pr2.id()).await?;
+            tester.approve(pr3.id()).await?;
+            tester.process_merge_queue().await;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @r"
+            :hourglass: Testing commit pr-1-sha with merge merge-0-pr-1...
+
+            2 PRs are waiting behind it in the [queue](https://test.com/bors/queue/borstest).
+            "
             );
             Ok(())
         })
@@ -639,6 +1938,112 @@ merge_queue_enabled = false
         gh.check_sha_history(default_repo_name(), AUTO_BRANCH_NAME, &["merge-0-pr-1"]);
     }
 
+    #[sqlx::test]
+    async fn squash_merge_pr(pool: sqlx::PgPool) {
+        let gh = run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors r+ squash").await?;
+            tester.expect_comments((), 1).await;
+            tester.start_auto_build(()).await?;
+            tester.finish_auto_build(()).await?;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":twisted_rightwards_arrows: The changes of this PR were squashed into [merge-0-pr-1-squashed](https://github.com/rust-lang/borstest/commit/merge-0-pr-1-squashed), which was pushed to `main`. Closing the PR, since GitHub does not recognize it as merged."
+            );
+            assert_eq!(
+                tester.get_pr_copy(()).await.get_gh_pr().status,
+                PullRequestStatus::Closed
+            );
+
+            // The webhook of the closed PR does not revert the merged status
+            tester.set_pr_status_closed(()).await?;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_status(PullRequestStatus::Merged);
+
+            let repo = tester.get_repo(&default_repo_name()).await;
+            insta::assert_snapshot!(
+                repo.lock().get_commit_author("merge-0-pr-1-squashed").unwrap(),
+                @"default-user <101+default-user@users.noreply.github.com>"
+            );
+            Ok(())
+        })
+        .await;
+        gh.check_sha_history(
+            default_repo_name(),
+            "main",
+            &["main-sha1", "merge-0-pr-1-squashed"],
+        );
+        gh.check_sha_history(
+            default_repo_name(),
+            AUTO_BRANCH_NAME,
+            &["merge-0-pr-1-squashed"],
+        );
+    }
+
+    #[sqlx::test]
+    async fn squash_merge_config(pool: sqlx::PgPool) {
+        let gh = BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+squash_merge = true
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.start_auto_build(()).await?;
+                tester.finish_auto_build(()).await?;
+                tester.expect_comments((), 1).await;
+                Ok(())
+            })
+            .await;
+        gh.check_sha_history(
+            default_repo_name(),
+            "main",
+            &["main-sha1", "merge-0-pr-1-squashed"],
+        );
+    }
+
+    #[sqlx::test]
+    async fn base_branch_moved_before_merge(pool: sqlx::PgPool) {
+        let gh = run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester
+                .workflow_full_success(tester.auto_branch().await)
+                .await?;
+
+            // The base branch is updated before bors manages to merge the PR
+            tester
+                .modify_branch(default_branch_name(), |branch| {
+                    branch.set_to_sha("main-sha2")
+                })
+                .await;
+            tester.process_merge_queue().await;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @":recycle: The `main` branch was updated from `main-sha1` to `main-sha2` after the auto build of this PR was started, so the tested commit cannot be merged. A new auto build will be started."
+            );
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_status(PullRequestStatus::Open)
+                .expect_no_auto_build();
+
+            // The PR is tested again on top of the updated base branch
+            tester.process_merge_queue().await;
+            tester.expect_comments((), 1).await;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_auto_build(|b| b.parent == "main-sha2");
+            Ok(())
+        })
+        .await;
+        gh.check_sha_history(default_repo_name(), "main", &["main-sha1", "main-sha2"]);
+    }
+
     #[sqlx::test]
     async fn merge_queue_sequential_order(pool: sqlx::PgPool) {
         let gh = run_test(pool, async |tester: &mut BorsTester| {
@@ -677,10 +2082,41 @@ merge_queue_enabled = false
     }
 
     #[sqlx::test]
-    async fn merge_queue_priority_order(pool: sqlx::PgPool) {
-        let gh = run_test(pool, async |tester: &mut BorsTester| {
-            let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
-            let pr3 = tester.open_pr(default_repo_name(), |_| {}).await?;
+    async fn full_queue_defers_later_approvals(pool: sqlx::PgPool) {
+        let gh = BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+max_queue_size = 1
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+                tester.approve(()).await?;
+                tester
+                    .post_comment(Comment::new(pr2.id(), "@bors r+ p=10"))
+                    .await?;
+                tester.expect_comments(pr2.id(), 1).await;
+
+                // The PR with the higher priority waits for queue space, since it was approved
+                // after the queue became full
+                tester.start_and_finish_auto_build(()).await?;
+                tester.start_and_finish_auto_build(pr2.id()).await?;
+                Ok(())
+            })
+            .await;
+        gh.check_sha_history(
+            default_repo_name(),
+            "main",
+            &["main-sha1", "merge-0-pr-1", "merge-1-pr-2"],
+        );
+    }
+
+    #[sqlx::test]
+    async fn merge_queue_priority_order(pool: sqlx::PgPool) {
+        let gh = run_test(pool, async |tester: &mut BorsTester| {
+            let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+            let pr3 = tester.open_pr(default_repo_name(), |_| {}).await?;
             let pr4 = tester.open_pr(default_repo_name(), |_| {}).await?;
 
             tester.approve(pr2.id()).await?;
@@ -705,6 +2141,124 @@ merge_queue_enabled = false
         );
     }
 
+    #[sqlx::test]
+    async fn approval_preempts_lower_priority_auto_build(pool: sqlx::PgPool) {
+        let gh = BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+preempt_on_priority = true
+
+[labels]
+approved = ["+approved"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+                tester.approve(()).await?;
+                tester.start_auto_build(()).await?;
+                tester.workflow_start(tester.auto_branch().await).await?;
+
+                tester
+                    .post_comment(Comment::new(pr2.id(), "@bors r+ p=10"))
+                    .await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                Auto build cancelled due to the approval of #2, which has a higher priority. Cancelled workflows:
+
+                - https://github.com/rust-lang/borstest/actions/runs/1
+
+                This PR stays approved and will be tested again.
+                ");
+                tester.expect_comments(pr2.id(), 1).await;
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_approved_by("default-user")
+                    .expect_no_auto_build();
+
+                tester.start_and_finish_auto_build(pr2.id()).await?;
+                tester.start_and_finish_auto_build(()).await?;
+                Ok(())
+            })
+            .await;
+
+        gh.check_sha_history(
+            default_repo_name(),
+            "main",
+            &["main-sha1", "merge-1-pr-2", "merge-2-pr-1"],
+        );
+    }
+
+    #[sqlx::test]
+    async fn approval_does_not_preempt_same_priority_auto_build(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+preempt_on_priority = true
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+                tester.post_comment("@bors r+ p=10").await?;
+                tester.expect_comments((), 1).await;
+                tester.start_auto_build(()).await?;
+
+                tester
+                    .post_comment(Comment::new(pr2.id(), "@bors r+ p=10"))
+                    .await?;
+                tester.expect_comments(pr2.id(), 1).await;
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_auto_build(|build| build.status == BuildStatus::Pending);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn priority_aging_order(pool: sqlx::PgPool) {
+        let gh = BorsBuilder::new(pool.clone())
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+priority_aging_interval = 3600
+
+[labels]
+approved = ["+approved"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+                tester.approve(()).await?;
+                tester
+                    .post_comment(Comment::new(pr2.id(), "@bors r+ p=2"))
+                    .await?;
+                tester.expect_comments(pr2.id(), 1).await;
+
+                // PR 1 has been waiting for three hours, so it overtakes PR 2
+                set_pr_approved_at(
+                    &pool,
+                    &default_repo_name(),
+                    PullRequestNumber(1),
+                    Utc::now() - chrono::Duration::hours(3),
+                )
+                .await?;
+
+                tester.start_and_finish_auto_build(()).await?;
+                tester.start_and_finish_auto_build(pr2.id()).await?;
+                Ok(())
+            })
+            .await;
+
+        gh.check_sha_history(
+            default_repo_name(),
+            "main",
+            &["main-sha1", "merge-0-pr-1", "merge-1-pr-2"],
+        );
+    }
+
     #[sqlx::test]
     async fn auto_build_push_conflict(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
@@ -939,6 +2493,262 @@ merge_queue_enabled = false
         .await;
     }
 
+    #[sqlx::test]
+    async fn auto_build_unapproves_pr_violating_lint_rules(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+
+[lint]
+forbidden_markers = ["DNM"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester
+                    .modify_pr_state((), |pr| pr.title = "[dnm] Title of PR 1".to_string())
+                    .await;
+                tester.process_merge_queue().await;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                :clipboard: The title or description of this PR does not follow the rules of this repository anymore, so it was unapproved:
+                - The title must not contain `DNM`.
+
+                Hint: Edit the PR title or description and approve it again.
+                ");
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_unapproved()
+                    .expect_no_auto_build();
+                Ok(())
+            })
+            .await;
+    }
+
+    /// Pushes a commit to the approved PR and waits until GitHub reports it as mergeable again.
+    async fn push_to_approved_pr(tester: &mut BorsTester, merge_base: bool) -> anyhow::Result<()> {
+        if merge_base {
+            tester.merge_base_into_pr(()).await?;
+        } else {
+            tester.rebase_pr(()).await?;
+        }
+        tester.expect_comments((), 1).await;
+        let head_sha = tester.get_pr_copy(()).await.get_gh_pr().head_sha;
+        tester
+            .wait_for_pr((), |pr| pr.approved_sha() == Some(head_sha.as_str()))
+            .await?;
+        tester
+            .modify_pr_state((), |pr| pr.mergeable_state = OctocrabMergeableState::Clean)
+            .await;
+        tester
+            .wait_for_pr((), |pr| pr.mergeable_state == MergeableState::Mergeable)
+            .await?;
+        Ok(())
+    }
+
+    fn stale_approval_config(policy: &str) -> GitHubState {
+        GitHubState::default().with_default_config(&format!(
+            r#"
+merge_queue_enabled = true
+unapprove_on_push = false
+stale_approval = "{policy}"
+"#
+        ))
+    }
+
+    #[sqlx::test]
+    async fn stale_approval_merge(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(stale_approval_config("merge"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                push_to_approved_pr(tester, false).await?;
+                tester.process_merge_queue().await;
+                tester.expect_comments((), 1).await;
+                tester.get_pr_copy(()).await.expect_auto_build(|_| true);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn stale_approval_require_reapproval(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(stale_approval_config("require_reapproval"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                tester.rebase_pr(()).await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                :warning: A new commit `pr-1-commit-1` was pushed to the branch, the
+                PR will need to be re-approved.
+                ");
+                tester.get_pr_copy(()).await.expect_unapproved();
+                tester
+                    .modify_pr_state((), |pr| pr.mergeable_state = OctocrabMergeableState::Clean)
+                    .await;
+                tester
+                    .wait_for_pr((), |pr| pr.mergeable_state == MergeableState::Mergeable)
+                    .await?;
+                tester.process_merge_queue().await;
+                tester.get_pr_copy(()).await.expect_no_auto_build();
+
+                // A new approval covers the new commits
+                tester.approve(()).await?;
+                tester.process_merge_queue().await;
+                tester.expect_comments((), 1).await;
+                tester.get_pr_copy(()).await.expect_auto_build(|_| true);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn push_after_approval_with_default_config(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.push_to_pr(()).await?;
+            tester.expect_comments((), 1).await;
+            tester
+                .modify_pr_state((), |pr| pr.mergeable_state = OctocrabMergeableState::Clean)
+                .await;
+            tester
+                .wait_for_pr((), |pr| pr.mergeable_state == MergeableState::Mergeable)
+                .await?;
+            tester.process_merge_queue().await;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_unapproved()
+                .expect_no_auto_build();
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn stale_approval_merge_base_only_allows_base_merge(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(stale_approval_config("merge_base_only"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                push_to_approved_pr(tester, true).await?;
+                tester.process_merge_queue().await;
+                tester.expect_comments((), 1).await;
+                tester.get_pr_copy(()).await.expect_auto_build(|_| true);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn stale_approval_merge_base_only_rejects_other_commits(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(stale_approval_config("merge_base_only"))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                push_to_approved_pr(tester, true).await?;
+                push_to_approved_pr(tester, false).await?;
+                tester.process_merge_queue().await;
+                tester.expect_comments((), 1).await;
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_unapproved()
+                    .expect_no_auto_build();
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn auto_build_closes_pr_contained_in_base_branch(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+close_already_merged = true
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                let head_sha = tester.get_pr_copy(()).await.get_gh_pr().head_sha;
+                tester
+                    .modify_repo(&default_repo_name(), |repo| {
+                        repo.get_branch_by_name(default_branch_name())
+                            .unwrap()
+                            .set_to_sha(&head_sha);
+                    })
+                    .await;
+                tester.process_merge_queue().await;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                    :broom: The changes of this PR are already contained in the `main` branch, e.g. because they were merged in a rollup or cherry-picked. Closing it instead of testing it again.
+
+                    Hint: If something is missing, reopen the PR, push the missing changes and approve it again.
+                    ");
+                let pr = tester.get_pr_copy(()).await;
+                pr.expect_status(PullRequestStatus::Closed)
+                    .expect_no_auto_build();
+                assert_eq!(pr.get_gh_pr().status, PullRequestStatus::Closed);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn auto_build_closes_cherry_picked_pr(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+close_already_merged = true
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.approve(()).await?;
+                let head_sha = tester.get_pr_copy(()).await.get_gh_pr().head_sha;
+                tester
+                    .modify_repo(&default_repo_name(), |repo| {
+                        repo.cherry_picked_commits.insert(head_sha);
+                    })
+                    .await;
+                tester.process_merge_queue().await;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                    :broom: The changes of this PR are already contained in the `main` branch, e.g. because they were merged in a rollup or cherry-picked. Closing it instead of testing it again.
+
+                    Hint: If something is missing, reopen the PR, push the missing changes and approve it again.
+                    ");
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_status(PullRequestStatus::Closed)
+                    .expect_no_auto_build();
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn auto_build_cherry_picked_pr_without_check(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            let head_sha = tester.get_pr_copy(()).await.get_gh_pr().head_sha;
+            tester
+                .modify_repo(&default_repo_name(), |repo| {
+                    repo.cherry_picked_commits.insert(head_sha);
+                })
+                .await;
+            tester.start_auto_build(()).await?;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_status(PullRequestStatus::Open)
+                .expect_auto_build(|_| true);
+            Ok(())
+        })
+        .await;
+    }
+
     #[sqlx::test]
     async fn auto_build_sanity_check_recovers(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
@@ -1121,9 +2931,178 @@ auto_build_failed = ["+foo", "+bar", "-baz"]
     }
 
     #[sqlx::test]
-    async fn run_empty_queue(pool: sqlx::PgPool) {
+    async fn expired_tree_closure_reopens_tree(pool: sqlx::PgPool) {
         run_test(pool, async |tester: &mut BorsTester| {
-            // This PR should not be in the queue
+            tester
+                .db()
+                .upsert_repository(
+                    &default_repo_name(),
+                    TreeState::Closed {
+                        priority: 100,
+                        source: "https://github.com/rust-lang/borstest/pull/1#issuecomment-1"
+                            .to_string(),
+                        reason: Some("CI outage".to_string()),
+                        until: Some(Utc::now() - chrono::Duration::minutes(1)),
+                    },
+                )
+                .await?;
+            tester.approve(()).await?;
+
+            tester.process_merge_queue().await;
+            tester.expect_comments((), 1).await;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_auto_build(|b| b.status == BuildStatus::Pending);
+            let repo = tester.db().repo_db(&default_repo_name()).await?;
+            assert_eq!(repo.unwrap().tree_state, TreeState::Open);
+            Ok(())
+        })
+        .await;
+    }
+
+    fn gh_state_with_maintenance_window(start: DateTime<Utc>) -> GitHubState {
+        GitHubState::default().with_default_config(&maintenance_window_config(start))
+    }
+
+    fn maintenance_window_config(start: DateTime<Utc>) -> String {
+        format!(
+            r#"
+merge_queue_enabled = true
+tracking_issue = 2
+
+[[maintenance_windows]]
+start = "{}"
+duration = 3600
+priority = 100
+reason = "Release"
+"#,
+            start.to_rfc3339()
+        )
+    }
+
+    #[sqlx::test]
+    async fn maintenance_window_closes_tree(pool: sqlx::PgPool) {
+        let start = Utc::now().trunc_subsecs(0) - chrono::Duration::minutes(5);
+        BorsBuilder::new(pool)
+            .github(gh_state_with_maintenance_window(start))
+            .run_test(async |tester: &mut BorsTester| {
+                let issue = tester.open_pr(default_repo_name(), |_| {}).await?;
+                tester.approve(()).await?;
+
+                tester.process_merge_queue().await;
+                let comment = tester.get_next_comment_text(issue.id()).await?;
+                assert!(
+                    comment.starts_with(
+                        ":construction: Tree closed for PRs with priority less than 100 until"
+                    ),
+                    "{comment}"
+                );
+                assert!(
+                    comment.ends_with("(reason: Release) for a scheduled maintenance window."),
+                    "{comment}"
+                );
+                let repo = tester.db().repo_db(&default_repo_name()).await?.unwrap();
+                assert_eq!(
+                    repo.tree_state,
+                    TreeState::Closed {
+                        priority: 100,
+                        source: "https://github.com/rust-lang/borstest/issues/2".to_string(),
+                        reason: Some("Release".to_string()),
+                        until: Some(start + chrono::Duration::hours(1)),
+                    }
+                );
+                tester.get_pr_copy(()).await.expect_no_auto_build();
+
+                // The window is only applied once
+                tester.post_comment("@bors treeopen").await?;
+                tester.expect_comments((), 1).await;
+                tester.process_merge_queue().await;
+                tester.expect_comments((), 1).await;
+                let repo = tester.db().repo_db(&default_repo_name()).await?.unwrap();
+                assert_eq!(repo.tree_state, TreeState::Open);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn maintenance_window_reopens_tree(pool: sqlx::PgPool) {
+        let start = Utc::now().trunc_subsecs(0) - chrono::Duration::hours(2);
+        BorsBuilder::new(pool)
+            .github(gh_state_with_maintenance_window(start))
+            .run_test(async |tester: &mut BorsTester| {
+                let issue = tester.open_pr(default_repo_name(), |_| {}).await?;
+                let end = start + chrono::Duration::hours(1);
+                tester
+                    .db()
+                    .record_scheduled_tree_closure(&default_repo_name(), start, end)
+                    .await?;
+                tester
+                    .db()
+                    .upsert_repository(
+                        &default_repo_name(),
+                        TreeState::Closed {
+                            priority: 100,
+                            source: "https://github.com/rust-lang/borstest/issues/2".to_string(),
+                            reason: Some("Release".to_string()),
+                            until: Some(end),
+                        },
+                    )
+                    .await?;
+
+                tester.process_merge_queue().await;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(issue.id()).await?,
+                    @":sunny: The scheduled maintenance window has ended, the tree is now open for merging."
+                );
+                let repo = tester.db().repo_db(&default_repo_name()).await?.unwrap();
+                assert_eq!(repo.tree_state, TreeState::Open);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn maintenance_window_ended_localized(pool: sqlx::PgPool) {
+        let start = Utc::now().trunc_subsecs(0) - chrono::Duration::hours(2);
+        let config = format!("language = \"de\"\n{}", maintenance_window_config(start));
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(&config))
+            .run_test(async |tester: &mut BorsTester| {
+                let issue = tester.open_pr(default_repo_name(), |_| {}).await?;
+                let end = start + chrono::Duration::hours(1);
+                tester
+                    .db()
+                    .record_scheduled_tree_closure(&default_repo_name(), start, end)
+                    .await?;
+                tester
+                    .db()
+                    .upsert_repository(
+                        &default_repo_name(),
+                        TreeState::Closed {
+                            priority: 100,
+                            source: "https://github.com/rust-lang/borstest/issues/2".to_string(),
+                            reason: Some("Release".to_string()),
+                            until: Some(end),
+                        },
+                    )
+                    .await?;
+
+                tester.process_merge_queue().await;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(issue.id()).await?,
+                    @":sunny: Das geplante Wartungsfenster ist beendet, der Tree ist jetzt wieder für Merges geöffnet."
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn run_empty_queue(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            // This PR should not be in the queue
             let pr = tester.open_pr(default_repo_name(), |_| {}).await?;
             // Make sure that bors knows about the DB
             tester
@@ -1136,4 +3115,543 @@ auto_build_failed = ["+foo", "+bar", "-baz"]
         })
         .await;
     }
+
+    #[test]
+    fn auto_branch_names() {
+        assert!(is_auto_branch(&auto_branch_name(None, 0)));
+        assert!(is_auto_branch(&auto_branch_name(None, 2)));
+        assert!(!is_auto_branch(AUTO_MERGE_BRANCH_NAME));
+        assert!(is_auto_branch(&auto_branch_name(Some("beta"), 0)));
+        assert!(is_auto_branch(&auto_branch_name(Some("beta"), 1)));
+        assert!(!is_auto_branch("automation/bors/auto-"));
+        assert!(!is_auto_branch("automation/bors/try"));
+    }
+
+    fn gh_state_with_branch_queue() -> GitHubState {
+        GitHubState::default().with_default_config(
+            r#"
+merge_queue_enabled = true
+merge_queue_branches = ["beta"]
+"#,
+        )
+    }
+
+    #[sqlx::test]
+    async fn branch_queue_builds_independently(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(gh_state_with_branch_queue())
+            .run_test(async |tester: &mut BorsTester| {
+                let beta = tester.create_branch("beta").await;
+                let pr2 = tester
+                    .open_pr(default_repo_name(), |pr| pr.base_branch = beta)
+                    .await?;
+                tester.approve(()).await?;
+                tester.approve(pr2.id()).await?;
+
+                tester.process_merge_queue().await;
+                tester.expect_comments((), 1).await;
+                tester.expect_comments(pr2.id(), 1).await;
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_auto_build(|b| b.branch == AUTO_BRANCH_NAME);
+                tester
+                    .get_pr_copy(pr2.id())
+                    .await
+                    .expect_auto_build(|b| b.branch == auto_branch_name(Some("beta"), 0));
+
+                tester
+                    .workflow_full_success(
+                        tester
+                            .get_branch_copy(&auto_branch_name(Some("beta"), 0))
+                            .await,
+                    )
+                    .await?;
+                tester.process_merge_queue().await;
+                tester.expect_comments(pr2.id(), 1).await;
+                tester
+                    .get_pr_copy(pr2.id())
+                    .await
+                    .expect_status(PullRequestStatus::Merged);
+                assert_eq!(
+                    tester.get_branch_copy("beta").await.get_sha(),
+                    "merge-1-pr-2"
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn branch_queue_ignores_closed_main_tree(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(gh_state_with_branch_queue())
+            .run_test(async |tester: &mut BorsTester| {
+                let beta = tester.create_branch("beta").await;
+                let pr2 = tester
+                    .open_pr(default_repo_name(), |pr| pr.base_branch = beta)
+                    .await?;
+                tester.post_comment("@bors treeclosed=5").await?;
+                tester.expect_comments((), 1).await;
+                tester.approve(()).await?;
+                tester.approve(pr2.id()).await?;
+
+                tester.process_merge_queue().await;
+                tester.expect_comments(pr2.id(), 1).await;
+                tester.get_pr_copy(()).await.expect_no_auto_build();
+                tester
+                    .get_pr_copy(pr2.id())
+                    .await
+                    .expect_auto_build(|b| b.status == BuildStatus::Pending);
+                Ok(())
+            })
+            .await;
+    }
+
+    fn gh_state_with_parallel_auto_builds() -> GitHubState {
+        GitHubState::default().with_default_config(
+            r#"
+merge_queue_enabled = true
+max_parallel_auto_builds = 2
+"#,
+        )
+    }
+
+    #[sqlx::test]
+    async fn speculative_auto_builds_start_in_parallel(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(gh_state_with_parallel_auto_builds())
+            .run_test(async |tester: &mut BorsTester| {
+                let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+                let pr3 = tester.open_pr(default_repo_name(), |_| {}).await?;
+                tester.approve(()).await?;
+                tester.approve(pr2.id()).await?;
+                tester.approve(pr3.id()).await?;
+
+                tester.process_merge_queue().await;
+                assert!(
+                    tester
+                        .get_next_comment_text(())
+                        .await?
+                        .contains("Testing commit")
+                );
+                assert!(
+                    tester
+                        .get_next_comment_text(pr2.id())
+                        .await?
+                        .contains("Testing commit")
+                );
+                tester.get_pr_copy(pr2.id()).await.expect_auto_build(|b| {
+                    b.branch == auto_branch_name(None, 1) && b.parent == "merge-0-pr-1"
+                });
+                // Only two builds can run at the same time
+                tester.get_pr_copy(pr3.id()).await.expect_no_auto_build();
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn speculative_auto_builds_merge_in_order(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(gh_state_with_parallel_auto_builds())
+            .run_test(async |tester: &mut BorsTester| {
+                let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+                tester.approve(()).await?;
+                tester.approve(pr2.id()).await?;
+                tester.process_merge_queue().await;
+                tester.expect_comments((), 1).await;
+                tester.expect_comments(pr2.id(), 1).await;
+
+                // The second PR cannot be merged before the first one
+                tester
+                    .workflow_full_success(tester.get_branch_copy(&auto_branch_name(None, 1)).await)
+                    .await?;
+                tester.process_merge_queue().await;
+                tester
+                    .get_pr_copy(pr2.id())
+                    .await
+                    .expect_status(PullRequestStatus::Open);
+
+                tester.finish_auto_build(()).await?;
+                tester.process_merge_queue().await;
+                assert!(
+                    tester
+                        .get_next_comment_text(pr2.id())
+                        .await?
+                        .contains("Test successful")
+                );
+                tester
+                    .get_pr_copy(pr2.id())
+                    .await
+                    .expect_status(PullRequestStatus::Merged);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn speculative_auto_build_rebuilt_after_failure(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(gh_state_with_parallel_auto_builds())
+            .run_test(async |tester: &mut BorsTester| {
+                let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+                tester.approve(()).await?;
+                tester.approve(pr2.id()).await?;
+                tester.process_merge_queue().await;
+                tester.expect_comments((), 1).await;
+                tester.expect_comments(pr2.id(), 1).await;
+
+                tester
+                    .workflow_full_failure(tester.auto_branch().await)
+                    .await?;
+                tester.expect_comments((), 1).await;
+
+                tester.process_merge_queue().await;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(pr2.id()).await?,
+                    @":recycle: The auto build of this PR was discarded, because it was based on the auto build of another PR that did not succeed. A new auto build will be started."
+                );
+                tester.start_auto_build(pr2.id()).await?;
+                tester
+                    .get_pr_copy(pr2.id())
+                    .await
+                    .expect_auto_build(|b| b.status == BuildStatus::Pending);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn speculative_auto_build_discarded_localized(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+max_parallel_auto_builds = 2
+language = "de"
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+                tester.approve(()).await?;
+                tester.approve(pr2.id()).await?;
+                tester.process_merge_queue().await;
+                tester.expect_comments((), 1).await;
+                tester.expect_comments(pr2.id(), 1).await;
+
+                tester
+                    .workflow_full_failure(tester.auto_branch().await)
+                    .await?;
+                tester.expect_comments((), 1).await;
+
+                tester.process_merge_queue().await;
+                insta::assert_snapshot!(
+                    tester.get_next_comment_text(pr2.id()).await?,
+                    @":recycle: Der Auto-Build dieses PRs wurde verworfen, weil er auf dem Auto-Build eines anderen PRs basierte, der nicht erfolgreich war. Ein neuer Auto-Build wird gestartet."
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    fn gh_state_with_path_queues() -> GitHubState {
+        GitHubState::default().with_default_config(
+            r#"
+merge_queue_enabled = true
+
+[path_queues.frontend]
+paths = ["frontend/"]
+
+[path_queues.backend]
+paths = ["backend/"]
+"#,
+        )
+    }
+
+    async fn open_pr_modifying(
+        tester: &mut BorsTester,
+        files: &[&str],
+    ) -> anyhow::Result<PullRequestNumber> {
+        let pr = tester
+            .open_pr(default_repo_name(), |pr| {
+                pr.changed_files = files
+                    .iter()
+                    .map(|file| (file.to_string(), Some(String::new())))
+                    .collect();
+            })
+            .await?;
+        Ok(pr.number)
+    }
+
+    #[sqlx::test]
+    async fn path_queues_build_in_parallel(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(gh_state_with_path_queues())
+            .run_test(async |tester: &mut BorsTester| {
+                let frontend = open_pr_modifying(tester, &["frontend/app.js"]).await?;
+                let backend = open_pr_modifying(tester, &["backend/main.rs"]).await?;
+                tester.approve(()).await?;
+                tester.approve(frontend).await?;
+                tester.approve(backend).await?;
+
+                tester.process_merge_queue().await;
+                tester.expect_comments((), 1).await;
+                tester.expect_comments(frontend, 1).await;
+                tester.expect_comments(backend, 1).await;
+                tester
+                    .get_pr_copy(())
+                    .await
+                    .expect_auto_build(|b| b.branch == AUTO_BRANCH_NAME);
+                tester
+                    .get_pr_copy(frontend)
+                    .await
+                    .expect_auto_build(|b| b.branch == auto_branch_name(Some("frontend"), 0));
+                tester
+                    .get_pr_copy(backend)
+                    .await
+                    .expect_auto_build(|b| b.branch == auto_branch_name(Some("backend"), 0));
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn pr_modifying_several_path_queues_uses_default_queue(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(gh_state_with_path_queues())
+            .run_test(async |tester: &mut BorsTester| {
+                let pr = open_pr_modifying(tester, &["frontend/app.js", "backend/main.rs"]).await?;
+                tester.approve(pr).await?;
+                tester.process_merge_queue().await;
+                tester.expect_comments(pr, 1).await;
+                tester
+                    .get_pr_copy(pr)
+                    .await
+                    .expect_auto_build(|b| b.branch == AUTO_BRANCH_NAME);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn path_queue_follows_push(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(gh_state_with_path_queues())
+            .run_test(async |tester: &mut BorsTester| {
+                let pr = open_pr_modifying(tester, &["frontend/app.js"]).await?;
+                tester
+                    .wait_for_pr(pr, |pr| pr.path_queue.as_deref() == Some("frontend"))
+                    .await?;
+                tester
+                    .modify_pr_state(pr, |pr| {
+                        pr.changed_files
+                            .push(("README.md".to_string(), Some(String::new())));
+                    })
+                    .await;
+                tester.push_to_pr(pr).await?;
+                tester.wait_for_pr(pr, |pr| pr.path_queue.is_none()).await?;
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn path_queue_rebuilds_on_moved_base_branch(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(gh_state_with_path_queues())
+            .run_test(async |tester: &mut BorsTester| {
+                let frontend = open_pr_modifying(tester, &["frontend/app.js"]).await?;
+                let backend = open_pr_modifying(tester, &["backend/main.rs"]).await?;
+                tester.approve(frontend).await?;
+                tester.approve(backend).await?;
+                tester.process_merge_queue().await;
+                tester.expect_comments(frontend, 1).await;
+                tester.expect_comments(backend, 1).await;
+
+                tester
+                    .workflow_full_success(
+                        tester
+                            .get_branch_copy(&auto_branch_name(Some("frontend"), 0))
+                            .await,
+                    )
+                    .await?;
+                tester.process_merge_queue().await;
+                tester.expect_comments(frontend, 1).await;
+
+                // The merge of the backend PR with the moved base branch was not tested
+                tester
+                    .workflow_full_success(
+                        tester
+                            .get_branch_copy(&auto_branch_name(Some("backend"), 0))
+                            .await,
+                    )
+                    .await?;
+                tester.process_merge_queue().await;
+                insta::assert_snapshot!(tester.get_next_comment_text(backend).await?, @":recycle: The `main` branch was updated from `main-sha1` to `merge-1-pr-2` after the auto build of this PR was started, so the tested commit cannot be merged. A new auto build will be started.");
+
+                tester.process_merge_queue().await;
+                tester.expect_comments(backend, 1).await;
+                tester
+                    .workflow_full_success(
+                        tester
+                            .get_branch_copy(&auto_branch_name(Some("backend"), 0))
+                            .await,
+                    )
+                    .await?;
+                tester.process_merge_queue().await;
+                let comment = tester.get_next_comment_text(backend).await?;
+                assert!(comment.contains("Test successful - "));
+                assert!(comment.contains("Pushing merge-2-pr-3 to `main`"));
+                tester
+                    .get_pr_copy(backend)
+                    .await
+                    .expect_status(PullRequestStatus::Merged);
+                insta::assert_debug_snapshot!(
+                    tester.get_branch_copy(default_branch_name()).await.get_sha_history(),
+                    @r#"
+                    [
+                        "main-sha1",
+                        "merge-1-pr-2",
+                        "merge-2-pr-3",
+                    ]
+                    "#
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn approval_does_not_preempt_other_path_queue(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+preempt_on_priority = true
+
+[path_queues.frontend]
+paths = ["frontend/"]
+
+[path_queues.backend]
+paths = ["backend/"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                let frontend = open_pr_modifying(tester, &["frontend/app.js"]).await?;
+                let backend = open_pr_modifying(tester, &["backend/main.rs"]).await?;
+                tester
+                    .wait_for_pr(backend, |pr| pr.path_queue.as_deref() == Some("backend"))
+                    .await?;
+                tester.approve(frontend).await?;
+                tester.process_merge_queue().await;
+                tester.expect_comments(frontend, 1).await;
+
+                tester
+                    .post_comment(Comment::new(backend, "@bors r+ p=10"))
+                    .await?;
+                tester.expect_comments(backend, 1).await;
+                tester
+                    .get_pr_copy(frontend)
+                    .await
+                    .expect_auto_build(|build| build.status == BuildStatus::Pending);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn path_queue_required_status_contexts(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+
+[path_queues.frontend]
+paths = ["frontend/"]
+required_status_contexts = ["frontend-tests"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                let frontend = open_pr_modifying(tester, &["frontend/app.js"]).await?;
+                tester.approve(frontend).await?;
+                tester.process_merge_queue().await;
+                tester.expect_comments(frontend, 1).await;
+
+                let branch = tester
+                    .get_branch_copy(&auto_branch_name(Some("frontend"), 0))
+                    .await;
+                // Workflows are not enough, the required context of the queue has to succeed
+                tester.workflow_full_success(branch.clone()).await?;
+                tester.process_merge_queue().await;
+                tester
+                    .get_pr_copy(frontend)
+                    .await
+                    .expect_auto_build(|build| build.status == BuildStatus::Pending);
+                tester
+                    .commit_status(branch, "frontend-tests", "success")
+                    .await?;
+                tester.process_merge_queue().await;
+                assert!(
+                    tester
+                        .get_next_comment_text(frontend)
+                        .await?
+                        .contains("Test successful")
+                );
+
+                // The default queue does not require the context
+                tester.approve(()).await?;
+                tester.process_merge_queue().await;
+                tester.expect_comments((), 1).await;
+                tester
+                    .workflow_full_success(tester.auto_branch().await)
+                    .await?;
+                tester.process_merge_queue().await;
+                assert!(
+                    tester
+                        .get_next_comment_text(())
+                        .await?
+                        .contains("Test successful")
+                );
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn dry_run_does_not_start_auto_build(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+merge_queue_enabled = true
+dry_run = true
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                tester.post_comment("@bors r+").await?;
+                tester.expect_comments((), 1).await;
+                tester.process_merge_queue().await;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+                    :clipboard: Dry run: bors would now test commit pr-1-sha merged into main-sha1, and merge it if the tests pass. Nothing was pushed.
+
+                    No PRs are waiting behind it in the [queue](https://test.com/bors/queue/borstest).
+                    ");
+
+                // The same build is only described once
+                tester.process_merge_queue().await;
+                tester.post_comment("@bors ping").await?;
+                insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @"Pong 🏓!");
+
+                tester.get_pr_copy(()).await.expect_no_auto_build();
+                let repo = tester.get_repo(&default_repo_name()).await;
+                assert!(repo.lock().get_branch_by_name(AUTO_BRANCH_NAME).is_none());
+                assert!(
+                    repo.lock()
+                        .get_branch_by_name(AUTO_MERGE_BRANCH_NAME)
+                        .is_none()
+                );
+                Ok(())
+            })
+            .await;
+    }
 }
@@ -6,12 +6,20 @@
 //!
 //! You can add a PR to the mergeability queue. The queue will then immediately contact the GH API
 //! (by GETing the PR from GitHub), which should start the background job. After that, the PR will
-//! be checked in increasing intervals (after 5s, then after 10s, then after 15s, etc.), until we
-//! either get a known mergeability status from GH or until we run out of retries.
+//! be checked in exponentially increasing intervals (after 5s, then after 10s, then after 20s,
+//! etc., up to one minute), until we either get a known mergeability status from GH or until we
+//! run out of retries.
+//!
+//! Approved PRs waiting in the merge queue are also re-checked periodically, so that a PR which
+//! has developed merge conflicts is taken out of the queue (and its author is notified) before
+//! it reaches the head of the queue.
 
 use super::BorsContext;
-use crate::database::OctocrabMergeableState;
-use crate::github::{GithubRepoName, PullRequestNumber};
+use crate::bors::comment::{approved_pr_conflicted_comment, merge_conflicts_resolved_comment};
+use crate::bors::handlers::handle_label_trigger;
+use crate::bors::outbox::post_comment_or_enqueue;
+use crate::database::{MergeableState, OctocrabMergeableState};
+use crate::github::{GithubRepoName, LabelTrigger, PullRequestNumber};
 use std::cmp::Reverse;
 use std::collections::BinaryHeap;
 use std::sync::{Arc, Mutex};
@@ -26,8 +34,11 @@ const BASE_DELAY: Duration = Duration::from_secs(5);
 #[cfg(test)]
 const BASE_DELAY: Duration = Duration::from_millis(500);
 
+/// Max delay before two mergeability check attempts.
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
 /// Max number of mergeable check retries before giving up.
-const MAX_RETRIES: u32 = 5;
+const MAX_RETRIES: u32 = 8;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct QueuedPullRequest {
@@ -132,12 +143,7 @@ impl MergeabilityQueueSender {
     }
 
     fn enqueue_retry_later(&self, queue_item: MergeabilityQueueItem) {
-        // First attempt = BASE_DELAY
-        // Second attempt = BASE_DELAY * 2
-        // Third attempt = BASE_DELAY * 3
-        // etc.
-        let delay = BASE_DELAY * queue_item.attempt;
-        let expiration = Some(Instant::now() + delay);
+        let expiration = Some(Instant::now() + retry_delay(queue_item.attempt));
         let next_attempt = queue_item.attempt + 1;
 
         self.insert_item(
@@ -175,6 +181,17 @@ impl MergeabilityQueueSender {
     }
 }
 
+/// Delay before the next check of a PR after the given attempt.
+/// First attempt = BASE_DELAY
+/// Second attempt = BASE_DELAY * 2
+/// Third attempt = BASE_DELAY * 4
+/// etc., up to MAX_DELAY.
+fn retry_delay(attempt: u32) -> Duration {
+    BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(MAX_DELAY)
+}
+
 impl MergeabilityQueueReceiver {
     /// Get the next item from the queue.
     pub async fn dequeue(&self) -> Option<(MergeabilityQueueItem, MergeabilityQueueSender)> {
@@ -308,17 +325,56 @@ pub async fn check_mergeability(
         }
     };
 
+    let mergeable_state: MergeableState = new_mergeable_state.clone().into();
     ctx.db
-        .update_pr_mergeable_state(&pr_model, new_mergeable_state.clone().into())
+        .update_pr_mergeable_state(&pr_model, mergeable_state.clone())
         .await?;
 
+    // A PR approved with merge conflicts can now enter the queue
+    if pr_model.waiting_on_conflicts
+        && pr_model.is_approved()
+        && mergeable_state == MergeableState::Mergeable
+    {
+        ctx.db.set_waiting_on_conflicts(&pr_model, false).await?;
+        post_comment_or_enqueue(
+            &repo_state,
+            &ctx.db,
+            pr_model.number,
+            merge_conflicts_resolved_comment(ctx.get_web_url(), &pull_request.repo),
+        )
+        .await;
+    } else if pr_model.is_approved()
+        && !pr_model.waiting_on_conflicts
+        && pr_model.mergeable_state != MergeableState::HasConflicts
+        && mergeable_state == MergeableState::HasConflicts
+    {
+        // An approved PR has become conflicted while waiting in the queue. Park it until the
+        // conflicts are resolved, so that it does not waste a merge queue tick.
+        ctx.db.set_waiting_on_conflicts(&pr_model, true).await?;
+        handle_label_trigger(
+            &repo_state,
+            &ctx.db,
+            pr_model.number,
+            LabelTrigger::Conflicted,
+        )
+        .await?;
+        post_comment_or_enqueue(
+            &repo_state,
+            &ctx.db,
+            pr_model.number,
+            approved_pr_conflicted_comment(&pr_model.base_branch),
+        )
+        .await;
+    }
+
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use crate::bors::mergeability_queue::{
-        MergeabilityQueueItem, QueuedPullRequest, create_mergeability_queue,
+        BASE_DELAY, MAX_DELAY, MergeabilityQueueItem, QueuedPullRequest, create_mergeability_queue,
+        retry_delay,
     };
     use crate::github::PullRequestNumber;
     use crate::tests::default_repo_name;
@@ -369,6 +425,15 @@ mod tests {
         }
     }
 
+    #[test]
+    fn exponential_retry_delay() {
+        assert_eq!(retry_delay(1), BASE_DELAY);
+        assert_eq!(retry_delay(2), BASE_DELAY * 2);
+        assert_eq!(retry_delay(3), BASE_DELAY * 4);
+        assert_eq!(retry_delay(4), BASE_DELAY * 8);
+        assert_eq!(retry_delay(100), MAX_DELAY);
+    }
+
     fn item(pr_number: u64, attempt: u32) -> MergeabilityQueueItem {
         MergeabilityQueueItem {
             pull_request: QueuedPullRequest {
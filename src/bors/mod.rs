@@ -2,19 +2,21 @@ use std::fmt;
 use std::str::FromStr;
 
 use arc_swap::ArcSwap;
+pub use command::BorsCommand;
 pub use command::CommandParser;
 pub use command::RollupMode;
 pub use comment::Comment;
-pub use context::BorsContext;
-pub use handlers::{handle_bors_global_event, handle_bors_repository_event};
+pub use context::{BorsContext, RefreshKind};
+pub(crate) use handlers::ZOMBIE_WORKFLOW_METRICS;
+pub use handlers::{deliver_outbox, handle_bors_global_event, handle_bors_repository_event};
 use itertools::Itertools;
-use octocrab::models::RunId;
 use octocrab::models::workflows::Job;
+use octocrab::models::{CheckSuiteId, RunId};
 use serde::Serialize;
 
 use crate::config::RepositoryConfig;
-use crate::github::GithubRepoName;
 use crate::github::api::client::GithubRepositoryClient;
+use crate::github::{CommitSha, GithubRepoName, PullRequestNumber};
 use crate::permissions::UserPermissions;
 #[cfg(test)]
 use crate::tests::TestSyncMarker;
@@ -23,10 +25,18 @@ use crate::utils::text::suppress_github_mentions;
 mod command;
 pub mod comment;
 mod context;
+pub(crate) mod dead_letter;
 pub mod event;
 mod handlers;
+pub mod health;
+pub mod hooks;
+pub mod localization;
 pub mod merge_queue;
 pub mod mergeability_queue;
+mod outbox;
+pub mod queue_skips;
+pub mod status;
+pub mod workers;
 
 use crate::database::{WorkflowModel, WorkflowStatus};
 pub use command::CommandPrefix;
@@ -40,15 +50,48 @@ pub static WAIT_FOR_MERGEABILITY_STATUS_REFRESH: TestSyncMarker = TestSyncMarker
 #[cfg(test)]
 pub static WAIT_FOR_PR_STATUS_REFRESH: TestSyncMarker = TestSyncMarker::new();
 
+#[cfg(test)]
+pub static WAIT_FOR_ZOMBIE_WORKFLOWS_CANCEL: TestSyncMarker = TestSyncMarker::new();
+
+#[cfg(test)]
+pub static WAIT_FOR_TRY_ARTIFACT_CLEANUP: TestSyncMarker = TestSyncMarker::new();
+
+#[cfg(test)]
+pub static WAIT_FOR_PENDING_BUILDS_RECONCILE: TestSyncMarker = TestSyncMarker::new();
+
 #[cfg(test)]
 pub static WAIT_FOR_WORKFLOW_STARTED: TestSyncMarker = TestSyncMarker::new();
 
 #[cfg(test)]
 pub static WAIT_FOR_WORKFLOW_COMPLETED: TestSyncMarker = TestSyncMarker::new();
 
+#[cfg(test)]
+pub static WAIT_FOR_BUILD_RESULT_REPORTED: TestSyncMarker = TestSyncMarker::new();
+
+#[cfg(test)]
+pub static WAIT_FOR_COMMIT_STATUS: TestSyncMarker = TestSyncMarker::new();
+
 #[cfg(test)]
 pub static WAIT_FOR_MERGE_QUEUE: TestSyncMarker = TestSyncMarker::new();
 
+#[cfg(test)]
+pub static WAIT_FOR_OUTBOX_DELIVERY: TestSyncMarker = TestSyncMarker::new();
+
+#[cfg(test)]
+pub static WAIT_FOR_DEAD_LETTER_REPLAY: TestSyncMarker = TestSyncMarker::new();
+
+#[cfg(test)]
+pub static WAIT_FOR_REPOSITORY_RELOAD: TestSyncMarker = TestSyncMarker::new();
+
+#[cfg(test)]
+pub static WAIT_FOR_APP_CLIENT_CHANGE: TestSyncMarker = TestSyncMarker::new();
+
+#[cfg(test)]
+pub static WAIT_FOR_CONFIG_REFRESH: TestSyncMarker = TestSyncMarker::new();
+
+#[cfg(test)]
+pub static WAIT_FOR_PERMISSIONS_REFRESH: TestSyncMarker = TestSyncMarker::new();
+
 /// Corresponds to a single execution of a workflow.
 #[derive(Clone, Debug)]
 pub struct WorkflowRun {
@@ -56,6 +99,35 @@ pub struct WorkflowRun {
     pub status: WorkflowStatus,
 }
 
+/// A workflow run that has not finished yet, together with the branch and commit it runs on.
+#[derive(Clone, Debug)]
+pub struct RunningWorkflowRun {
+    pub id: RunId,
+    pub branch: String,
+    pub commit_sha: CommitSha,
+}
+
+/// A workflow run started for the commit of a build, as currently known by GitHub.
+#[derive(Clone, Debug)]
+pub struct BuildWorkflowRun {
+    pub id: RunId,
+    pub name: String,
+    pub url: String,
+    pub check_suite_id: CheckSuiteId,
+    pub status: WorkflowStatus,
+    /// How long the run took, if it has already completed.
+    pub running_time: Option<chrono::Duration>,
+}
+
+/// An artifact produced by a build, as reported by CI.
+#[derive(Clone, Debug)]
+pub struct BuildArtifact {
+    pub name: String,
+    pub url: String,
+    /// Size of the artifact in bytes, if it is known.
+    pub size: Option<u64>,
+}
+
 pub struct FailedWorkflowRun {
     pub workflow_run: WorkflowModel,
     pub failed_jobs: Vec<Job>,
@@ -74,6 +146,32 @@ impl RepositoryState {
     pub fn repository(&self) -> &GithubRepoName {
         self.client.repository()
     }
+
+    /// Post a comment to the given PR, formatted according to the comment settings of this
+    /// repository.
+    pub async fn post_comment(
+        &self,
+        pr: PullRequestNumber,
+        comment: Comment,
+    ) -> anyhow::Result<octocrab::models::issues::Comment> {
+        let comment = self.format_comment(comment);
+        self.client.post_comment(pr, comment).await
+    }
+
+    /// Format the comment according to the comment settings of this repository.
+    pub fn format_comment(&self, comment: Comment) -> Comment {
+        let config = self.config.load();
+        let comment = comment.with_style(config.comment_style);
+        let comment = if config.accessible_comments {
+            comment.with_status_words()
+        } else {
+            comment
+        };
+        match &config.comment_signature {
+            Some(signature) => comment.with_signature(signature),
+            None => comment,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
@@ -114,13 +212,32 @@ impl FromStr for PullRequestStatus {
 /// Prefix used to specify custom try jobs in PR descriptions.
 pub const CUSTOM_TRY_JOB_PREFIX: &str = "try-job:";
 
+/// Git trailer that contains the ID of the bors build that tests the merge commit.
+/// CI jobs can use it to refer to the build when reporting results back to bors.
+pub const BUILD_ID_TRAILER: &str = "Bors-Build-Id:";
+
+/// Git trailer that contains a build parameter set with `@bors env`, as `<key>=<value>`.
+/// The merge commit has one such trailer for each parameter of the PR.
+pub const BUILD_ENV_TRAILER: &str = "Bors-Env:";
+
+/// Prefix used to specify the restricted environment of a try build in the merge commit message.
+pub const TRY_ENVIRONMENT_PREFIX: &str = "try-environment:";
+
 #[derive(Debug, Clone)]
 pub enum MergeType {
-    Try { try_jobs: Vec<String> },
+    Try {
+        try_jobs: Vec<String>,
+        /// Restricted environment in which CI should run the try build.
+        environment: Option<String>,
+    },
     Auto,
 }
 
-pub fn create_merge_commit_message(pr: handlers::PullRequestData, merge_type: MergeType) -> String {
+pub fn create_merge_commit_message(
+    pr: handlers::PullRequestData,
+    merge_type: MergeType,
+    build_id: i32,
+) -> String {
     let pr_number = pr.number();
 
     let reviewer = match &merge_type {
@@ -132,7 +249,7 @@ pub fn create_merge_commit_message(pr: handlers::PullRequestData, merge_type: Me
     match &merge_type {
         // Strip all PR text for try builds, to avoid useless issue pings on the repository.
         // Only keep any lines starting with `CUSTOM_TRY_JOB_PREFIX`.
-        MergeType::Try { try_jobs } => {
+        MergeType::Try { try_jobs, .. } => {
             // If we do not have any custom try jobs, keep the ones that might be in the PR
             // description.
             pr_description = if try_jobs.is_empty() {
@@ -159,12 +276,24 @@ pub fn create_merge_commit_message(pr: handlers::PullRequestData, merge_type: Me
     );
 
     match merge_type {
-        MergeType::Try { try_jobs } => {
+        MergeType::Try {
+            try_jobs,
+            environment,
+        } => {
             for job in try_jobs {
                 message.push_str(&format!("\n{CUSTOM_TRY_JOB_PREFIX} {job}"));
             }
+            if let Some(environment) = environment {
+                message.push_str(&format!("\n{TRY_ENVIRONMENT_PREFIX} {environment}"));
+            }
         }
         MergeType::Auto => {}
     }
+    // Separate the trailer from the rest of the message by exactly one empty line
+    let mut message = message.trim_end().to_string();
+    message.push_str(&format!("\n\n{BUILD_ID_TRAILER} {build_id}"));
+    for var in &pr.db.build_env {
+        message.push_str(&format!("\n{BUILD_ENV_TRAILER} {var}"));
+    }
     message
 }
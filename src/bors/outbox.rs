@@ -0,0 +1,381 @@
+//! Reliable execution of GitHub side effects.
+//!
+//! Side effects (comments, label changes, check run updates) are first recorded in the outbox,
+//! ideally in the same transaction as the state change that caused them. They are then performed
+//! right away, and if that fails, they are periodically retried with an exponential backoff, until
+//! they succeed. This guarantees that each side effect is performed at least once, even if bors
+//! crashes in the meantime, and that a failing side effect never blocks other operations, such as
+//! merging PRs. Each delivery claims the entries that it performs, so that the immediate and the
+//! periodic delivery never perform the same entry twice.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use octocrab::models::CheckRunId;
+use octocrab::params::checks::{CheckRunConclusion, CheckRunStatus};
+
+use crate::PgDbClient;
+use crate::bors::workers::run_in_background;
+use crate::bors::{Comment, RepositoryState};
+use crate::database::{OutboxAction, OutboxModel};
+use crate::github::PullRequestNumber;
+use crate::github::api::rate_limit::RateLimitExceeded;
+
+/// Returns an outbox action that posts the given comment, formatted according to the comment
+/// settings of the repository.
+pub(super) fn comment_action(repo: &RepositoryState, comment: Comment) -> OutboxAction {
+    OutboxAction::Comment {
+        tag: comment.tag(),
+        text: repo.format_comment(comment).render(),
+    }
+}
+
+/// Record the given side effects in the outbox and try to perform them.
+/// Side effects that cannot be performed are retried later.
+pub(super) async fn perform_outbox_actions(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    pr: PullRequestNumber,
+    actions: &[OutboxAction],
+) -> anyhow::Result<()> {
+    if actions.is_empty() {
+        return Ok(());
+    }
+    let entries = db
+        .enqueue_outbox_actions(repo.repository(), pr, actions)
+        .await?;
+    deliver_outbox_actions(repo, db, entries).await
+}
+
+/// Post a comment to the given PR through the outbox.
+/// This function never fails, so that comment delivery never blocks other operations.
+pub(super) async fn post_comment_or_enqueue(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    pr: PullRequestNumber,
+    comment: Comment,
+) {
+    let action = comment_action(repo, comment);
+    if let Err(error) = perform_outbox_actions(repo, db, pr, &[action]).await {
+        tracing::error!("Failed to post comment to PR {pr}: {error:?}");
+    }
+}
+
+/// Post comments to several PRs that were affected by a single event, e.g. when several builds
+/// time out at once.
+///
+/// At most `notifications_per_minute` (see the repository config) batched comments are posted
+/// per minute, also across several batches, because the slots of each minute are reserved in the
+/// database. Comments without a free slot in the current minute are spread over the following
+/// minutes, with a random jitter, and delivered from the outbox, so that bors does not trip the
+/// abuse detection of GitHub.
+/// This function never fails, so that comment delivery never blocks other operations.
+pub(super) async fn post_comment_batch(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    comments: Vec<(PullRequestNumber, Comment)>,
+) {
+    if comments.is_empty() {
+        return;
+    }
+    let per_minute = repo.config.load().notifications_per_minute.max(1);
+    let window = match db
+        .reserve_notification_slots(repo.repository(), comments.len(), per_minute)
+        .await
+    {
+        Ok(window) => window,
+        Err(error) => {
+            tracing::error!("Failed to reserve notification slots: {error:?}");
+            None
+        }
+    };
+    let mut due_entries = vec![];
+    for (index, (pr, comment)) in comments.into_iter().enumerate() {
+        // Without a reserved window, only this batch is rate limited
+        let delay_secs = match &window {
+            Some(window) => window.delay_secs(index, per_minute),
+            None => (index / per_minute) as i32 * 60,
+        };
+        let action = comment_action(repo, comment);
+        match db
+            .enqueue_delayed_outbox_action(repo.repository(), pr, &action, delay_secs)
+            .await
+        {
+            Ok(entry) if delay_secs == 0 => due_entries.push(entry),
+            Ok(_) => {}
+            Err(error) => tracing::error!("Failed to record comment for PR {pr}: {error:?}"),
+        }
+    }
+    if let Err(error) = deliver_outbox_actions(repo, db, due_entries).await {
+        tracing::error!("Failed to post batched comments: {error:?}");
+    }
+}
+
+/// Perform outbox actions that were recorded (and claimed) in the database.
+/// Successfully performed actions are removed from the outbox, failed actions are scheduled to
+/// be retried later.
+///
+/// If an action of some PR fails, the following actions of the same PR are not performed, so
+/// that e.g. comments are not posted out of order. If an action fails because of a GitHub API
+/// rate limit, no further actions are performed. Actions that were not attempted are released,
+/// so that they are performed by the next delivery.
+pub(super) async fn deliver_outbox_actions(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    entries: Vec<OutboxModel>,
+) -> anyhow::Result<()> {
+    let mut failed_prs = HashSet::new();
+    let mut skipped = vec![];
+    let mut entries = entries.into_iter();
+    for entry in entries.by_ref() {
+        if failed_prs.contains(&entry.pr_number) {
+            skipped.push(entry);
+            continue;
+        }
+        match perform_action(repo, db, entry.pr_number, &entry.action).await {
+            Ok(()) => db.delete_outbox_action(&entry).await?,
+            Err(error) => {
+                tracing::warn!(
+                    "Failed to perform outbox action {:?} on PR {} (attempt {}): {error:?}",
+                    entry.action,
+                    entry.pr_number,
+                    entry.attempts + 1
+                );
+                failed_prs.insert(entry.pr_number);
+                db.record_outbox_action_failure(&entry).await?;
+                if RateLimitExceeded::find(&error).is_some() {
+                    break;
+                }
+            }
+        }
+    }
+    skipped.extend(entries);
+    db.release_outbox_actions(&skipped).await
+}
+
+/// Retry outbox actions of the given repository whose next attempt is due.
+/// The actions are claimed first, so that they are not performed by a concurrent delivery (e.g.
+/// the inline delivery of a just recorded action) at the same time.
+pub(super) async fn deliver_pending_actions(
+    repo: Arc<RepositoryState>,
+    db: &PgDbClient,
+) -> anyhow::Result<()> {
+    let entries = db.claim_due_outbox_actions(repo.repository()).await?;
+    deliver_outbox_actions(&repo, db, entries).await
+}
+
+async fn perform_action(
+    repo: &RepositoryState,
+    db: &PgDbClient,
+    pr: PullRequestNumber,
+    action: &OutboxAction,
+) -> anyhow::Result<()> {
+    match action {
+        OutboxAction::Comment { text, tag } => {
+            let comment = repo
+                .client
+                .post_comment(pr, Comment::new(text.clone()))
+                .await?;
+            // The comment was already posted, so failing to record it must not lead to a retry
+            if let Some(tag) = tag
+                && let Err(error) = db
+                    .record_tagged_bot_comment(repo.repository(), pr, *tag, &comment.node_id)
+                    .await
+            {
+                tracing::error!("Cannot record tagged comment on PR {pr}: {error:?}");
+            }
+        }
+        // Label updates are idempotent, so they can keep running in the background even if
+        // they take so long that they are retried later
+        OutboxAction::AddLabels { labels } => {
+            tracing::info!("Adding label(s) {labels:?}");
+            let client = repo.client.clone();
+            let labels = labels.clone();
+            run_in_background(
+                "add_labels",
+                async move { client.add_labels(pr, &labels).await },
+            )
+            .await?;
+        }
+        OutboxAction::RemoveLabels { labels } => {
+            tracing::info!("Removing label(s) {labels:?}");
+            let client = repo.client.clone();
+            let labels = labels.clone();
+            run_in_background("remove_labels", async move {
+                client.remove_labels(pr, &labels).await
+            })
+            .await?;
+        }
+        OutboxAction::CompleteCheckRun {
+            check_run_id,
+            success,
+        } => {
+            let conclusion = if *success {
+                CheckRunConclusion::Success
+            } else {
+                CheckRunConclusion::Failure
+            };
+            repo.client
+                .update_check_run(
+                    CheckRunId(*check_run_id),
+                    CheckRunStatus::Completed,
+                    Some(conclusion),
+                )
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bors::PullRequestStatus;
+    use crate::tests::{BorsTester, default_repo_name, run_test};
+
+    #[sqlx::test]
+    async fn comment_failure_does_not_block_merge(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester
+                .workflow_full_success(tester.auto_branch().await)
+                .await?;
+            tester
+                .modify_repo(&default_repo_name(), |repo| repo.comment_error = true)
+                .await;
+            tester.process_merge_queue().await;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_status(PullRequestStatus::Merged);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn failed_comment_delivered_later(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester.workflow_full_success(tester.auto_branch().await).await?;
+            tester
+                .modify_repo(&default_repo_name(), |repo| repo.comment_error = true)
+                .await;
+            tester.process_merge_queue().await;
+            tester
+                .modify_repo(&default_repo_name(), |repo| repo.comment_error = false)
+                .await;
+            tester.deliver_outbox().await;
+            insta::assert_snapshot!(
+                tester.get_next_comment_text(()).await?,
+                @r"
+            :sunny: Test successful - [Workflow1](https://github.com/rust-lang/borstest/actions/runs/1)
+            Approved by: `default-user`
+            Pushing merge-0-pr-1 to `main`...
+            "
+            );
+            assert!(
+                tester
+                    .db()
+                    .get_due_outbox_actions(&default_repo_name())
+                    .await?
+                    .is_empty()
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn failed_delivery_is_backed_off(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester
+                .workflow_full_success(tester.auto_branch().await)
+                .await?;
+            tester
+                .modify_repo(&default_repo_name(), |repo| repo.comment_error = true)
+                .await;
+            tester.process_merge_queue().await;
+
+            // The first retry is performed immediately
+            let entries = tester
+                .db()
+                .get_due_outbox_actions(&default_repo_name())
+                .await?;
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].attempts, 1);
+
+            // Further retries are postponed
+            tester.deliver_outbox().await;
+            assert!(
+                tester
+                    .db()
+                    .get_due_outbox_actions(&default_repo_name())
+                    .await?
+                    .is_empty()
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn due_actions_are_claimed_once(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester
+                .workflow_full_success(tester.auto_branch().await)
+                .await?;
+            tester
+                .modify_repo(&default_repo_name(), |repo| repo.comment_error = true)
+                .await;
+            tester.process_merge_queue().await;
+
+            let db = tester.db();
+            assert_eq!(
+                db.claim_due_outbox_actions(&default_repo_name())
+                    .await?
+                    .len(),
+                1
+            );
+            // The entry is leased by the first claim, so a concurrent delivery skips it
+            assert!(
+                db.claim_due_outbox_actions(&default_repo_name())
+                    .await?
+                    .is_empty()
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn build_failure_comment_retried(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+            tester
+                .modify_repo(&default_repo_name(), |repo| repo.comment_error = true)
+                .await;
+            tester
+                .workflow_full_failure(tester.try_branch().await)
+                .await?;
+            tester
+                .modify_repo(&default_repo_name(), |repo| repo.comment_error = false)
+                .await;
+            tester.deliver_outbox().await;
+            assert!(
+                tester
+                    .get_next_comment_text(())
+                    .await?
+                    .contains("Test for merge-0-pr-1 failed")
+            );
+            Ok(())
+        })
+        .await;
+    }
+}
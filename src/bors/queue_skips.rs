@@ -0,0 +1,237 @@
+//! Reasons why approved PRs were not selected by the merge queue, recorded during every tick.
+//! They are shown on the queue page and exposed by the API, so that e.g. a drop of the merge
+//! throughput can be explained afterwards. They are kept in memory only.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::database::{PullRequestModel, QueueStatus};
+use crate::github::{GithubRepoName, PullRequestNumber};
+
+/// Why an approved PR was not selected by the merge queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The merge queue is paused.
+    QueuePaused,
+    /// An auto build of another PR of the queue is pending.
+    PendingBuild,
+    /// Another PR of the queue is being merged.
+    MergingOtherPr,
+    /// The maximum number of parallel auto builds of the queue is running.
+    ParallelLimit,
+    /// The running auto builds target a different base branch, so the PR cannot be built
+    /// speculatively on top of them.
+    DifferentBaseBranch,
+    /// The auto build of an earlier PR of the queue could not be started, e.g. because of a
+    /// GitHub error.
+    BlockedByEarlierPr,
+    /// The auto build of the PR could not be started, e.g. because of a GitHub error.
+    StartFailed,
+    /// The tree is closed for PRs with the priority of the PR.
+    BelowTreePriority,
+    /// The PR has a label that blocks merging.
+    BlockingLabel,
+    /// The PR has merge conflicts.
+    MergeConflict,
+    /// The mergeability of the PR is not known yet.
+    UnknownMergeability,
+    /// The PR is parked with `@bors park`.
+    Parked,
+    /// The merge queue already contains the maximum number of PRs, and the PR was approved after
+    /// them.
+    QueueFull,
+    /// A successful auto build is waiting to be merged, but its PR is held with `@bors hold`.
+    BehindHeldPr,
+    /// The PR was not approved by enough reviewers yet.
+    MissingApprovals,
+    /// The PR title or description violates the lint rules of the repository.
+    LintViolation,
+    /// The changes of the PR are already contained in its base branch, so it was closed.
+    AlreadyMerged,
+    /// New commits were pushed to the PR after its approval, so it was unapproved.
+    StaleApproval,
+    /// The auto build was not started, because the repository is in dry-run mode.
+    DryRun,
+}
+
+impl SkipReason {
+    /// Human-readable description of the reason, shown on the queue page.
+    pub fn description(&self) -> &'static str {
+        match self {
+            SkipReason::QueuePaused => "the queue is paused",
+            SkipReason::PendingBuild => "waiting for a pending build",
+            SkipReason::MergingOtherPr => "waiting for another PR to be merged",
+            SkipReason::ParallelLimit => "all parallel builds are in use",
+            SkipReason::DifferentBaseBranch => "waiting for builds of another base branch",
+            SkipReason::BlockedByEarlierPr => "blocked by an earlier PR",
+            SkipReason::StartFailed => "the auto build could not be started",
+            SkipReason::BelowTreePriority => "below the tree priority",
+            SkipReason::BlockingLabel => "has a blocking label",
+            SkipReason::MergeConflict => "has merge conflicts",
+            SkipReason::UnknownMergeability => "mergeability is unknown",
+            SkipReason::Parked => "parked",
+            SkipReason::QueueFull => "waiting for queue space",
+            SkipReason::BehindHeldPr => "waiting for a held PR to be released",
+            SkipReason::MissingApprovals => "missing approvals",
+            SkipReason::LintViolation => "violates lint rules",
+            SkipReason::AlreadyMerged => "already merged",
+            SkipReason::StaleApproval => "new commits since approval",
+            SkipReason::DryRun => "dry run, the auto build was not started",
+        }
+    }
+}
+
+/// Reasons why approved PRs were not selected during a single tick of the merge queue of a
+/// repository.
+#[derive(Default)]
+pub struct TickSkips {
+    reasons: BTreeMap<PullRequestNumber, SkipReason>,
+}
+
+impl TickSkips {
+    /// Records why the given PR was skipped. Only the first reason of each PR is kept.
+    pub fn skip(&mut self, pr: PullRequestNumber, reason: SkipReason) {
+        self.reasons.entry(pr).or_insert(reason);
+    }
+
+    /// Records why the PRs of the given queue that wait for an auto build were skipped.
+    pub fn skip_waiting(&mut self, prs: &[PullRequestModel], reason: SkipReason) {
+        for pr in prs {
+            if matches!(pr.queue_status(), QueueStatus::Approved(..)) {
+                self.skip(pr.number, reason);
+            }
+        }
+    }
+}
+
+/// Skip reasons of the latest tick of each repository, and how often each reason was recorded
+/// since startup.
+pub struct QueueSkips {
+    repositories: Mutex<BTreeMap<GithubRepoName, RepositorySkips>>,
+}
+
+/// Skip reasons of the merge queues of this bors instance.
+pub static QUEUE_SKIPS: QueueSkips = QueueSkips {
+    repositories: Mutex::new(BTreeMap::new()),
+};
+
+#[derive(Default)]
+struct RepositorySkips {
+    recorded_at: Option<DateTime<Utc>>,
+    latest: BTreeMap<PullRequestNumber, SkipReason>,
+    totals: BTreeMap<SkipReason, u64>,
+}
+
+impl QueueSkips {
+    /// Replaces the skip reasons of the repository with the ones recorded during a tick that has
+    /// finished at `recorded_at`.
+    pub(crate) fn record(
+        &self,
+        repo: &GithubRepoName,
+        skips: TickSkips,
+        recorded_at: DateTime<Utc>,
+    ) {
+        let mut repositories = self.repositories.lock().unwrap();
+        let entry = repositories.entry(repo.clone()).or_default();
+        for reason in skips.reasons.values() {
+            *entry.totals.entry(*reason).or_default() += 1;
+        }
+        entry.recorded_at = Some(recorded_at);
+        entry.latest = skips.reasons;
+    }
+
+    /// Returns the reasons why PRs of the repository were skipped during the latest tick.
+    pub fn latest(&self, repo: &GithubRepoName) -> BTreeMap<PullRequestNumber, SkipReason> {
+        self.repositories
+            .lock()
+            .unwrap()
+            .get(repo)
+            .map(|skips| skips.latest.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn snapshot(&self, repo: &GithubRepoName) -> QueueSkipsSnapshot {
+        let repositories = self.repositories.lock().unwrap();
+        let Some(skips) = repositories.get(repo) else {
+            return QueueSkipsSnapshot::default();
+        };
+        QueueSkipsSnapshot {
+            recorded_at: skips.recorded_at,
+            prs: skips
+                .latest
+                .iter()
+                .map(|(pr, reason)| SkippedPr {
+                    number: pr.0,
+                    reason: *reason,
+                })
+                .collect(),
+            totals: skips.totals.clone(),
+        }
+    }
+}
+
+/// A point-in-time view of the skip reasons of a repository.
+#[derive(Debug, Default)]
+pub struct QueueSkipsSnapshot {
+    /// When the latest tick has finished, or `None` if the queue was not processed since
+    /// startup.
+    pub recorded_at: Option<DateTime<Utc>>,
+    /// PRs skipped during the latest tick.
+    pub prs: Vec<SkippedPr>,
+    /// How many times each reason was recorded since startup, counted once per PR and tick.
+    pub totals: BTreeMap<SkipReason, u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SkippedPr {
+    pub number: u64,
+    pub reason: SkipReason,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::{QueueSkips, SkipReason, TickSkips};
+    use crate::github::{GithubRepoName, PullRequestNumber};
+
+    #[test]
+    fn record_tick_skips() {
+        let skips = QueueSkips {
+            repositories: Default::default(),
+        };
+        let repo = GithubRepoName::new("rust-lang", "borstest");
+
+        let mut tick = TickSkips::default();
+        tick.skip(PullRequestNumber(1), SkipReason::PendingBuild);
+        // Only the first reason is kept
+        tick.skip(PullRequestNumber(1), SkipReason::QueuePaused);
+        tick.skip(PullRequestNumber(2), SkipReason::PendingBuild);
+        skips.record(&repo, tick, Utc::now());
+
+        let mut tick = TickSkips::default();
+        tick.skip(PullRequestNumber(2), SkipReason::Parked);
+        skips.record(&repo, tick, Utc::now());
+
+        let latest = skips.latest(&repo);
+        assert_eq!(
+            latest.into_iter().collect::<Vec<_>>(),
+            vec![(PullRequestNumber(2), SkipReason::Parked)]
+        );
+        let snapshot = skips.snapshot(&repo);
+        assert!(snapshot.recorded_at.is_some());
+        assert_eq!(
+            snapshot.totals.into_iter().collect::<Vec<_>>(),
+            vec![(SkipReason::PendingBuild, 2), (SkipReason::Parked, 1)]
+        );
+        assert!(
+            skips
+                .latest(&GithubRepoName::new("rust-lang", "other"))
+                .is_empty()
+        );
+    }
+}
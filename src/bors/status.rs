@@ -0,0 +1,173 @@
+//! Overall status of this bors instance, shown by the public `/status` page, so that users can
+//! find out what is going on during an incident without access to the logs or to the API.
+
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::PgDbClient;
+use crate::bors::RepositoryState;
+use crate::bors::health::{HealthReport, check_health};
+use crate::github::webhook_lag::WEBHOOK_LAG_METRICS;
+
+/// Version of this bors instance.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Overall status of this bors instance.
+#[derive(Debug, Serialize)]
+pub struct StatusReport {
+    pub version: &'static str,
+    /// True if all components that bors depends on are healthy.
+    pub healthy: bool,
+    pub health: HealthReport,
+    /// Why the merge queues of all repositories were stopped, if they were.
+    pub emergency_stop: Option<EmergencyStopStatus>,
+    pub webhook_lag: WebhookLagStatus,
+    pub repositories: Vec<RepositoryStatus>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmergencyStopStatus {
+    pub reason: Option<String>,
+}
+
+/// Lag of the webhooks handled since startup, across all event types.
+#[derive(Debug, Serialize)]
+pub struct WebhookLagStatus {
+    /// Number of handled webhooks.
+    pub count: u64,
+    /// Average lag, or `None` if no webhook was handled yet.
+    pub average_seconds: Option<f64>,
+    /// The largest observed lag.
+    pub max_seconds: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RepositoryStatus {
+    pub name: String,
+    pub merge_queue: MergeQueueStatus,
+    /// Whether the tree is closed, or `None` if it could not be loaded from the database.
+    pub tree_closed: Option<bool>,
+}
+
+/// State of the merge queue of a repository.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeQueueStatus {
+    Running,
+    /// The merge queue was paused with `@bors queue pause`.
+    Paused,
+    /// All merge queues were stopped with an emergency stop.
+    Stopped,
+    /// The merge queue is disabled in the repository configuration.
+    Disabled,
+    /// The repository was deactivated, e.g. because it was archived.
+    Deactivated,
+    /// The state could not be loaded from the database.
+    Unknown,
+}
+
+impl MergeQueueStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MergeQueueStatus::Running => "running",
+            MergeQueueStatus::Paused => "paused",
+            MergeQueueStatus::Stopped => "stopped",
+            MergeQueueStatus::Disabled => "disabled",
+            MergeQueueStatus::Deactivated => "deactivated",
+            MergeQueueStatus::Unknown => "unknown",
+        }
+    }
+}
+
+/// Checks the health of this bors instance and collects the state of the merge queues of the
+/// given repositories.
+/// The state of the repositories is not loaded if the database is unhealthy, so that the status
+/// can still be reported quickly during a database outage.
+pub async fn check_status(db: &PgDbClient, repos: &[Arc<RepositoryState>]) -> StatusReport {
+    let health = check_health(db, repos).await;
+    let database_available = health.database.healthy;
+
+    let emergency_stop = if database_available {
+        db.get_emergency_stop()
+            .await
+            .inspect_err(|error| tracing::warn!("Cannot load the emergency stop: {error:?}"))
+            .ok()
+            .flatten()
+            .map(|stop| EmergencyStopStatus {
+                reason: stop.reason,
+            })
+    } else {
+        None
+    };
+
+    let mut repositories = Vec::with_capacity(repos.len());
+    for repo in repos {
+        let (merge_queue, tree_closed) = if database_available {
+            repository_status(db, repo, emergency_stop.is_some()).await
+        } else {
+            (MergeQueueStatus::Unknown, None)
+        };
+        repositories.push(RepositoryStatus {
+            name: repo.repository().to_string(),
+            merge_queue,
+            tree_closed,
+        });
+    }
+    repositories.sort_by(|a, b| a.name.cmp(&b.name));
+
+    StatusReport {
+        version: VERSION,
+        healthy: health.healthy,
+        health,
+        emergency_stop,
+        webhook_lag: webhook_lag_status(),
+        repositories,
+    }
+}
+
+async fn repository_status(
+    db: &PgDbClient,
+    repo: &RepositoryState,
+    emergency_stop: bool,
+) -> (MergeQueueStatus, Option<bool>) {
+    let repo_name = repo.repository();
+    let (repo_db, pause) =
+        match tokio::try_join!(db.repo_db(repo_name), db.get_merge_queue_pause(repo_name)) {
+            Ok((Some(repo_db), pause)) => (repo_db, pause),
+            Ok((None, _)) => return (MergeQueueStatus::Unknown, None),
+            Err(error) => {
+                tracing::warn!("Cannot load the status of {repo_name}: {error:?}");
+                return (MergeQueueStatus::Unknown, None);
+            }
+        };
+
+    let merge_queue = if !repo.config.load().merge_queue_enabled {
+        MergeQueueStatus::Disabled
+    } else if repo_db.deactivation_reason.is_some() {
+        MergeQueueStatus::Deactivated
+    } else if emergency_stop {
+        MergeQueueStatus::Stopped
+    } else if pause.is_some() {
+        MergeQueueStatus::Paused
+    } else {
+        MergeQueueStatus::Running
+    };
+    (merge_queue, Some(repo_db.tree_state.is_closed()))
+}
+
+fn webhook_lag_status() -> WebhookLagStatus {
+    let snapshot = WEBHOOK_LAG_METRICS.snapshot();
+    let count: u64 = snapshot.event_types.values().map(|h| h.count).sum();
+    let sum: f64 = snapshot.event_types.values().map(|h| h.sum_seconds).sum();
+    let max_seconds = snapshot
+        .event_types
+        .values()
+        .map(|h| h.max_seconds)
+        .fold(0.0, f64::max);
+    WebhookLagStatus {
+        count,
+        average_seconds: (count > 0).then(|| sum / count as f64),
+        max_seconds,
+    }
+}
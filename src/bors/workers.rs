@@ -0,0 +1,176 @@
+//! Bounded pool of background workers for slow GitHub operations.
+//!
+//! Some GitHub operations, such as downloading job logs, listing workflow artifacts or updating
+//! labels, can take a long time. They are performed by a small pool of background workers, so
+//! that they cannot pile up, and webhook handlers and the merge queue only wait for them for a
+//! limited time. An operation that does not finish in time keeps running in the background, while
+//! its caller continues as if the operation has failed. Usage of the pool is collected in
+//! [`WORKER_POOL_METRICS`].
+
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use serde::Serialize;
+use tokio::sync::Semaphore;
+
+/// How many background operations can run at the same time.
+pub(crate) const BACKGROUND_WORKERS: usize = 4;
+
+/// How long does a caller wait for a background operation before continuing without its result.
+pub(crate) const BACKGROUND_OPERATION_BUDGET: Duration = Duration::from_secs(30);
+
+static WORKER_PERMITS: Semaphore = Semaphore::const_new(BACKGROUND_WORKERS);
+
+/// Runs a slow `operation` on the background worker pool and waits for its result for at most
+/// [`BACKGROUND_OPERATION_BUDGET`]. If the operation takes longer, an error is returned, but the
+/// operation keeps running in the background.
+pub(crate) async fn run_in_background<T, F>(operation: &'static str, future: F) -> anyhow::Result<T>
+where
+    T: Send + 'static,
+    F: Future<Output = anyhow::Result<T>> + Send + 'static,
+{
+    run_with_budget(operation, BACKGROUND_OPERATION_BUDGET, future).await
+}
+
+async fn run_with_budget<T, F>(
+    operation: &'static str,
+    budget: Duration,
+    future: F,
+) -> anyhow::Result<T>
+where
+    T: Send + 'static,
+    F: Future<Output = anyhow::Result<T>> + Send + 'static,
+{
+    let metrics = &WORKER_POOL_METRICS;
+    metrics.submitted.fetch_add(1, Ordering::Relaxed);
+    metrics.queued.fetch_add(1, Ordering::Relaxed);
+    let handle = tokio::spawn(async move {
+        let _permit = WORKER_PERMITS
+            .acquire()
+            .await
+            .expect("The worker pool is never closed");
+        metrics.queued.fetch_sub(1, Ordering::Relaxed);
+        metrics.running.fetch_add(1, Ordering::Relaxed);
+
+        let start = Instant::now();
+        let result = future.await;
+        metrics
+            .busy_time_ms
+            .fetch_add(start.elapsed().as_millis() as u64, Ordering::Relaxed);
+        metrics.running.fetch_sub(1, Ordering::Relaxed);
+        match &result {
+            Ok(_) => metrics.completed.fetch_add(1, Ordering::Relaxed),
+            Err(_) => metrics.failed.fetch_add(1, Ordering::Relaxed),
+        };
+        result
+    });
+
+    match tokio::time::timeout(budget, handle).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(error)) => Err(anyhow!(
+            "Background operation `{operation}` panicked: {error}"
+        )),
+        Err(_) => {
+            tracing::warn!(
+                "Background operation `{operation}` did not finish within {budget:?}, leaving it running in the background"
+            );
+            metrics.detached.fetch_add(1, Ordering::Relaxed);
+            Err(anyhow!(
+                "Background operation `{operation}` did not finish within {budget:?}"
+            ))
+        }
+    }
+}
+
+/// Usage of the background worker pool.
+pub struct WorkerPoolMetrics {
+    /// Operations submitted to the pool.
+    submitted: AtomicU64,
+    /// Operations waiting for a free worker.
+    queued: AtomicU64,
+    /// Operations being performed right now.
+    running: AtomicU64,
+    /// Operations that have finished successfully.
+    completed: AtomicU64,
+    /// Operations that have failed.
+    failed: AtomicU64,
+    /// Operations whose caller stopped waiting for them, because they took too long.
+    detached: AtomicU64,
+    /// Total time spent performing operations, in milliseconds.
+    busy_time_ms: AtomicU64,
+}
+
+pub static WORKER_POOL_METRICS: WorkerPoolMetrics = WorkerPoolMetrics {
+    submitted: AtomicU64::new(0),
+    queued: AtomicU64::new(0),
+    running: AtomicU64::new(0),
+    completed: AtomicU64::new(0),
+    failed: AtomicU64::new(0),
+    detached: AtomicU64::new(0),
+    busy_time_ms: AtomicU64::new(0),
+};
+
+impl WorkerPoolMetrics {
+    pub fn snapshot(&self) -> WorkerPoolSnapshot {
+        WorkerPoolSnapshot {
+            workers: BACKGROUND_WORKERS,
+            submitted: self.submitted.load(Ordering::Relaxed),
+            queued: self.queued.load(Ordering::Relaxed),
+            running: self.running.load(Ordering::Relaxed),
+            completed: self.completed.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            detached: self.detached.load(Ordering::Relaxed),
+            busy_time_seconds: self.busy_time_ms.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+}
+
+/// A point-in-time view of [`WorkerPoolMetrics`].
+#[derive(Debug, Serialize)]
+pub struct WorkerPoolSnapshot {
+    /// Size of the pool.
+    pub workers: usize,
+    pub submitted: u64,
+    pub queued: u64,
+    pub running: u64,
+    pub completed: u64,
+    pub failed: u64,
+    pub detached: u64,
+    pub busy_time_seconds: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    use super::{run_in_background, run_with_budget};
+
+    #[tokio::test]
+    async fn returns_result_of_operation() {
+        let result = run_in_background("test", async { Ok(1) }).await.unwrap();
+        assert_eq!(result, 1);
+    }
+
+    #[tokio::test]
+    async fn slow_operation_continues_in_background() {
+        let finished = Arc::new(AtomicBool::new(false));
+        let result = run_with_budget("test", Duration::from_millis(10), {
+            let finished = finished.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                finished.store(true, Ordering::SeqCst);
+                Ok(())
+            }
+        })
+        .await;
+        assert!(result.is_err());
+        assert!(!finished.load(Ordering::SeqCst));
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(finished.load(Ordering::SeqCst));
+    }
+}
@@ -1,47 +1,785 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
 use std::time::Duration;
 
+use chrono::{DateTime, Utc};
+use regex::Regex;
 use serde::de::Error;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serializer};
 
+use crate::bors::comment::CommentStyle;
+use crate::bors::localization::Language;
 use crate::github::{LabelModification, LabelTrigger};
+use crate::utils::cron::CronSchedule;
+use crate::utils::text::contains_issue_reference;
 
 pub const CONFIG_FILE_PATH: &str = "rust-bors.toml";
 
 /// Configuration of a repository loaded from a `rust-bors.toml`
 /// file located in the root of the repository file tree.
-#[derive(serde::Deserialize, Debug)]
+/// It is serialized in the same format, with durations in seconds.
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct RepositoryConfig {
     /// Maximum duration (in seconds) to wait for CI checks to complete before timing out.
     /// Defaults to 3600 seconds (1 hour).
     #[serde(
         default = "default_timeout",
-        deserialize_with = "deserialize_duration_from_secs"
+        deserialize_with = "deserialize_duration_from_secs",
+        serialize_with = "serialize_duration_as_secs"
     )]
     pub timeout: Duration,
+    /// Maximum duration (in seconds) to wait for an auto build to complete before timing out.
+    /// Defaults to `None`, in which case `timeout` is used.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_duration_from_secs_opt",
+        serialize_with = "serialize_duration_as_secs_opt"
+    )]
+    pub auto_build_timeout: Option<Duration>,
     /// Label modifications to apply when specific events occur.
     /// Maps trigger events (approve, try, etc.) to label additions/removals.
     /// Format: `trigger = ["+label_to_add", "-label_to_remove"]`
-    #[serde(default, deserialize_with = "deserialize_labels")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_labels",
+        serialize_with = "serialize_labels"
+    )]
     pub labels: HashMap<LabelTrigger, Vec<LabelModification>>,
     /// Labels that will block a PR from being approved when present on the PR.
     #[serde(default)]
     pub labels_blocking_approval: Vec<String>,
+    /// Labels that will block a PR from being approved and merged when present on the PR.
+    /// An approved PR that acquires such a label is skipped by the merge queue until the label
+    /// is removed.
+    /// Defaults to no labels.
+    #[serde(default)]
+    pub block_labels: Vec<String>,
+    /// Items of the PR template checklist that have to be ticked (`- [x] <item>`) in the
+    /// description of a PR before it can be approved.
+    /// Defaults to no items.
+    #[serde(default)]
+    pub required_checklist_items: Vec<String>,
+    /// Commit status (or check run) contexts reported by external CI systems, such as Buildkite
+    /// or Jenkins, that have to succeed on the try/auto branches for a build to pass.
+    /// If set, builds are tracked using these contexts instead of GitHub Actions workflows.
+    /// Defaults to no contexts (builds are tracked using GitHub Actions workflows).
+    #[serde(default)]
+    pub required_status_contexts: Vec<String>,
+    /// If enabled, the status checks required by the branch protection of the base branch are
+    /// also required to succeed on the merge commit of an auto build, in addition to
+    /// `required_status_contexts`. Auto builds are then tracked using these contexts.
+    /// Defaults to false.
+    #[serde(default)]
+    pub branch_protection_checks: bool,
+    /// If enabled, the commits of all PRs are squashed into a single commit when they are merged,
+    /// instead of creating a merge commit. Individual PRs can also be squashed with `r+ squash`.
+    /// Defaults to false.
+    #[serde(default)]
+    pub squash_merge: bool,
+    /// If enabled, the merge queue checks whether the changes of a PR are already contained in
+    /// its base branch (e.g. because they were merged in a rollup or cherry-picked) before
+    /// starting its auto build. Such PRs are closed with a comment instead of being built.
+    /// Defaults to false.
+    #[serde(default)]
+    pub close_already_merged: bool,
+    /// If enabled, jobs requested with `try jobs=...` have to be defined in the GitHub Actions
+    /// workflows of the PR, otherwise the try build is refused. This should stay disabled if the
+    /// try jobs refer to something else, e.g. to entries of a job matrix.
+    /// Defaults to false.
+    #[serde(default)]
+    pub validate_try_jobs: bool,
+    /// Regular expressions matched against the logs of the failed jobs of auto builds (e.g.
+    /// network timeouts or lost runners). If the log of every failed job matches one of them,
+    /// the failure is considered to be spurious and the auto build is retried automatically.
+    /// Defaults to no patterns.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_regexes",
+        serialize_with = "serialize_regexes"
+    )]
+    pub spurious_failure_patterns: Vec<Regex>,
+    /// How many times can the auto builds of a single approval of a PR be retried automatically
+    /// because of spurious failures, before the failure is reported.
+    /// Defaults to 3.
+    #[serde(default = "default_spurious_failure_retries")]
+    pub spurious_failure_retries: u32,
+    /// If enabled, the PRs merged into a rollup whose auto build has failed are set back to
+    /// `rollup=iffy`, so that they are not included in another rollup before they are checked.
+    /// The PRs are notified about the failure regardless of this option.
+    /// Defaults to false.
+    #[serde(default)]
+    pub rollup_failure_iffy: bool,
     /// Minimum time (in seconds) to wait for CI checks to complete before proceeding.
     /// Defaults to `None` (no minimum wait time).
-    #[serde(default, deserialize_with = "deserialize_duration_from_secs_opt")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_duration_from_secs_opt",
+        serialize_with = "serialize_duration_as_secs_opt"
+    )]
     pub min_ci_time: Option<Duration>,
-    /// Whether auto merging is enabled.
+    /// Whether try builds are enabled. If disabled, `try` commands are refused.
+    /// Defaults to true.
+    #[serde(default = "default_try_enabled")]
+    pub try_enabled: bool,
+    /// Whether auto merging is enabled. If disabled, commands that only affect the merge queue
+    /// (e.g. `hold` or `queue pause`) are refused.
     /// Defaults to false.
     #[serde(default)]
     pub merge_queue_enabled: bool,
+    /// If enabled, bors handles commands and keeps its database up to date as usual, but instead
+    /// of starting try and auto builds (or opening revert and backport PRs) it only describes
+    /// what it would do in a comment. It never pushes branches, creates check runs or merges PRs,
+    /// which allows trying out bors on a repository that still uses other merge tooling.
+    /// Defaults to false.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Maximum number of auto builds that can run at the same time.
+    /// If larger than 1, auto builds of PRs are started speculatively on top of the merge
+    /// commits of the PRs before them in the queue.
+    /// Defaults to 1.
+    #[serde(default = "default_max_parallel_auto_builds")]
+    pub max_parallel_auto_builds: usize,
+    /// Whether approving a PR should cancel running auto builds of PRs with a lower priority in
+    /// the same merge queue, so that the approved PR can be tested right away. The preempted PRs
+    /// stay approved and are tested again later.
+    /// Defaults to false.
+    #[serde(default)]
+    pub preempt_on_priority: bool,
+    /// If set, the priority of an approved PR used to order the merge queue increases by one
+    /// for each elapsed interval (in seconds) since the PR was approved, so that PRs with a low
+    /// priority are not starved by a steady stream of PRs with a higher priority.
+    /// Defaults to `None` (no aging).
+    #[serde(
+        default,
+        deserialize_with = "deserialize_duration_from_secs_opt",
+        serialize_with = "serialize_duration_as_secs_opt"
+    )]
+    pub priority_aging_interval: Option<Duration>,
+    /// Whether approved PRs should have a neutral check run that shows their position in the
+    /// merge queue. The check run is updated by the merge queue whenever the position changes.
+    /// Defaults to false.
+    #[serde(default)]
+    pub queue_check_runs: bool,
+    /// Base branches that have their own merge queue, independent of the queue of the other
+    /// branches. PRs targeting these branches are built on the `automation/bors/auto-<branch>`
+    /// branch, and they can be merged even if the tree of the other branches is closed.
+    /// Defaults to no branches.
+    #[serde(default)]
+    pub merge_queue_branches: Vec<String>,
+    /// Merge queues for PRs that only modify files under specific paths of the repository, keyed
+    /// by the name of the queue. PRs of different path queues are considered to be independent,
+    /// so they are built and merged in parallel. PRs that modify files of several path queues, or
+    /// files outside of them, use the default queue.
+    /// Defaults to no path queues.
+    #[serde(default)]
+    pub path_queues: BTreeMap<String, PathQueueConfig>,
+    /// How should try builds of PRs opened from forks be handled.
+    /// Defaults to `allow`.
+    #[serde(default)]
+    pub fork_try_builds: ForkTryPolicy,
+    /// How should try builds be handled if their explicit `parent=<sha>` is not reachable from
+    /// the base branch of the PR.
+    /// Defaults to `warn`.
+    #[serde(default)]
+    pub unreachable_try_parent: UnreachableParentPolicy,
+    /// Maximum number of approved PRs that can be in the merge queue at the same time.
+    /// Approvals above this limit are still accepted, but the PR waits for queue space.
+    /// Defaults to `None` (no limit).
+    #[serde(default)]
+    pub max_queue_size: Option<usize>,
+    /// Number of distinct reviewers that have to comment an approval of a PR (e.g. with `r+`
+    /// each) before it enters the merge queue. Users that are only named in `r=<user>` are not
+    /// counted.
+    /// Defaults to 1.
+    #[serde(default = "default_required_approvals")]
+    pub required_approvals: usize,
+    /// If set, a command that is repeated by the same user on the same PR within this duration
+    /// (in seconds) and that would have no effect (e.g. `r+` on an already approved PR) is
+    /// ignored. Only the first repetition is answered with a note, further ones are ignored
+    /// silently.
+    /// Defaults to `None` (repeated commands are always answered).
+    #[serde(
+        default,
+        deserialize_with = "deserialize_duration_from_secs_opt",
+        serialize_with = "serialize_duration_as_secs_opt"
+    )]
+    pub command_cooldown: Option<Duration>,
+    /// Whether an approved PR should be unapproved when a new commit is pushed to it.
+    /// If disabled, the approval is moved to the new head commit of the PR only if it makes the
+    /// same changes as the approved commit (e.g. if the PR was rebased). Otherwise, the PR is
+    /// still unapproved.
+    /// Defaults to true.
+    #[serde(default = "default_unapprove_on_push")]
+    pub unapprove_on_push: bool,
+    /// Whether a PR can be merged if new commits were pushed to it after it was approved. Only
+    /// relevant if the approval is kept on push (see `unapprove_on_push`).
+    /// Defaults to `require_reapproval`.
+    #[serde(default)]
+    pub stale_approval: StaleApprovalPolicy,
+    /// Whether a running auto build should be restarted when an edit of the title or the
+    /// description of the PR changes its merge commit message. If disabled, the PR is merged with
+    /// the message that it had when its auto build started.
+    /// Defaults to true.
+    #[serde(default = "default_restart_auto_build_on_edit")]
+    pub restart_auto_build_on_edit: bool,
+    /// Language of the comments posted by bors.
+    /// Defaults to `en` (English).
+    #[serde(default)]
+    pub language: Language,
+    /// Formatting profile of the comments posted by bors.
+    /// Defaults to `emoji`.
+    #[serde(default)]
+    pub comment_style: CommentStyle,
+    /// Whether comments should start with explicit status words (e.g. `SUCCESS` or `FAILURE`)
+    /// instead of conveying information through emoji.
+    /// Defaults to false.
+    #[serde(default)]
+    pub accessible_comments: bool,
+    /// Signature line appended to every comment posted by bors, e.g. to identify the
+    /// organization or the bot instance that posted it.
+    /// Defaults to `None` (no signature).
+    #[serde(default)]
+    pub comment_signature: Option<String>,
+    /// Text appended to the comments of successful try builds, e.g. with links to performance
+    /// comparison or bisection tools. `{sha}`, `{parent}`, `{pr}` and `{repo}` are replaced with
+    /// the merge commit of the try build, its parent, the number of the PR and the name of the
+    /// repository (`<owner>/<name>`).
+    /// Defaults to `None` (no text).
+    #[serde(default)]
+    pub try_build_comment_template: Option<String>,
+    /// Clean-up of the artifacts of finished try builds, which are often large and not needed
+    /// anymore after some time.
+    /// Defaults to `None` (artifacts are kept until they expire on GitHub).
+    #[serde(default)]
+    pub try_artifact_retention: Option<ArtifactRetentionConfig>,
+    /// Whether bors should hide its earlier build status comments on a PR once they become
+    /// outdated: the results of try builds when a new try build starts, and the "Testing commit"
+    /// and failure comments of auto builds when a new auto build starts. Comments of started try
+    /// builds are hidden regardless of this option.
+    /// Defaults to false.
+    #[serde(default)]
+    pub hide_outdated_comments: bool,
+    /// Whether the output of the `info` command should list the recent commands on the PR that
+    /// bors has rejected, because they could not be parsed or because their author did not have
+    /// the permission to use them.
+    /// Defaults to false.
+    #[serde(default)]
+    pub info_rejected_commands: bool,
+    /// Whether commands that do not produce any interesting output (e.g. setting the priority or
+    /// the rollup mode of a PR) should be acknowledged by a :+1: reaction on the comment that
+    /// contained them. Commands that change the state of a PR substantially (e.g. an approval)
+    /// are still answered with a comment.
+    /// Defaults to false.
+    #[serde(default)]
+    pub acknowledge_with_reaction: bool,
+    /// Maximum number of comments that bors posts per minute when events affect many PRs at
+    /// once (e.g. when several builds time out), counted across all such events. The remaining
+    /// comments are spread over the following minutes, to avoid tripping the abuse detection of
+    /// GitHub.
+    /// Defaults to 10.
+    #[serde(default = "default_notifications_per_minute")]
+    pub notifications_per_minute: usize,
+    /// Whether approved PRs waiting in the merge queue should be notified when a `treeclosed`
+    /// command blocks them because of their priority, and again when `treeopen` unblocks them.
+    /// Defaults to false.
+    #[serde(default)]
+    pub tree_closure_notifications: bool,
+    /// Whether submitting an "Approve" GitHub review should be treated as `r+`, and submitting a
+    /// "Request changes" review as `r-`. The usual permission checks still apply.
+    /// Defaults to false.
+    #[serde(default)]
+    pub approve_on_review: bool,
+    /// What happens with the approval of a PR when it is reopened after being closed.
+    /// Defaults to `restore`.
+    #[serde(default)]
+    pub approval_on_reopen: ReopenApprovalPolicy,
+    /// Custom names for commands, e.g. `lgtm = "r+"`. A command that starts with an alias is
+    /// parsed as if the alias was replaced with its expansion, so `@bors lgtm p=1` is parsed as
+    /// `@bors r+ p=1`. Aliases are not expanded recursively.
+    /// Defaults to no aliases.
+    #[serde(default, deserialize_with = "deserialize_command_aliases")]
+    pub command_aliases: HashMap<String, String>,
+    /// Build parameters that reviewers can set on a PR with `@bors env <key>=<value>`, with their
+    /// allowed values, e.g. `profile = ["debug-assertions", "release"]`. The parameters of a PR
+    /// are passed to its try and auto builds as `Bors-Env: <key>=<value>` trailers of the merge
+    /// commit message.
+    /// Defaults to no parameters (the `env` command is disabled).
+    #[serde(default)]
+    pub build_env: BTreeMap<String, Vec<String>>,
+    /// Number of an issue of the repository where bors posts notifications that do not belong
+    /// to any PR, e.g. when the tree is closed for a maintenance window.
+    /// Defaults to `None` (such notifications are not posted).
+    #[serde(default)]
+    pub tracking_issue: Option<u64>,
+    /// Time windows during which the tree is closed automatically, e.g. for a release or an
+    /// infrastructure upgrade.
+    /// Defaults to no maintenance windows.
+    #[serde(default, deserialize_with = "deserialize_maintenance_windows")]
+    pub maintenance_windows: Vec<MaintenanceWindow>,
+    /// Rules that the title and the description of a PR, from which the merge commit message is
+    /// created, have to follow. They are checked when the PR is approved and again before its
+    /// auto build is started.
+    /// Defaults to no rules.
+    #[serde(default)]
+    pub lint: LintConfig,
+    /// Users and teams that have review or try permissions in the repository, in addition to
+    /// the ones loaded from the team API.
+    /// Defaults to no additional users.
+    #[serde(default)]
+    pub permissions: PermissionsConfig,
+}
+
+impl RepositoryConfig {
+    /// Return the name of the path queue that a PR modifying the given files belongs to, if all
+    /// the files are under the paths of a single path queue.
+    pub fn path_queue_for(&self, files: &[String]) -> Option<&str> {
+        let mut queues = files.iter().map(|file| {
+            self.path_queues
+                .iter()
+                .find(|(_, queue)| queue.contains(file))
+                .map(|(name, _)| name.as_str())
+        });
+        let first = queues.next()??;
+        queues.all(|queue| queue == Some(first)).then_some(first)
+    }
+
+    /// Whether some builds are tracked using commit status contexts instead of GitHub Actions
+    /// workflows.
+    pub fn uses_status_contexts(&self) -> bool {
+        !self.required_status_contexts.is_empty()
+            || self.branch_protection_checks
+            || self
+                .path_queues
+                .values()
+                .any(|queue| !queue.required_status_contexts.is_empty())
+    }
+
+    /// Returns the maintenance window that is in progress at the given time, together with its
+    /// start and end.
+    pub fn active_maintenance_window(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Option<(&MaintenanceWindow, DateTime<Utc>, DateTime<Utc>)> {
+        self.maintenance_windows.iter().find_map(|window| {
+            let start = window.latest_start(now)?;
+            Some((window, start, start + window.duration))
+        })
+    }
+}
+
+/// A time window during which the tree is closed automatically. It either recurs according to
+/// a cron `schedule`, or happens once at `start`.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct MaintenanceWindow {
+    /// Cron expression (`<minute> <hour> <day of month> <month> <day of week>`, in UTC) of the
+    /// starts of a recurring window.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<CronSchedule>,
+    /// Start of a one-off window, as an RFC 3339 timestamp.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_timestamp_opt",
+        serialize_with = "serialize_timestamp_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub start: Option<DateTime<Utc>>,
+    /// How long (in seconds) is the tree closed.
+    #[serde(
+        deserialize_with = "deserialize_chrono_duration_from_secs",
+        serialize_with = "serialize_chrono_duration_as_secs"
+    )]
+    pub duration: chrono::Duration,
+    /// PRs with a lower priority than this cannot be merged during the window.
+    pub priority: u32,
+    /// Why is the tree closed.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+impl MaintenanceWindow {
+    /// Returns the start of the occurrence of the window that is in progress at the given time.
+    fn latest_start(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match (&self.schedule, self.start) {
+            (Some(schedule), _) => schedule.latest_before(now, self.duration),
+            (None, Some(start)) => (start <= now && now < start + self.duration).then_some(start),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Rules for the title and the description of PRs, configured in the `[lint]` section.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct LintConfig {
+    /// Regular expression that the PR title has to match, e.g. `^(feat|fix|docs): `.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_regex_opt",
+        serialize_with = "serialize_regex_opt"
+    )]
+    pub title_pattern: Option<Regex>,
+    /// If enabled, the PR title or description has to reference an issue (e.g. `#123`).
+    #[serde(default)]
+    pub require_issue_reference: bool,
+    /// Maximum length of the PR title (in characters).
+    #[serde(default)]
+    pub max_subject_length: Option<usize>,
+    /// Markers that must not appear in the PR title (ignoring case), e.g. `fixup!` or `DNM`.
+    #[serde(default)]
+    pub forbidden_markers: Vec<String>,
+}
+
+impl LintConfig {
+    /// Returns descriptions of the rules that a PR with the given title and description violates.
+    pub fn violations(&self, title: &str, description: &str) -> Vec<String> {
+        let mut violations = vec![];
+        if let Some(pattern) = &self.title_pattern
+            && !pattern.is_match(title)
+        {
+            violations.push(format!("The title has to match `{pattern}`."));
+        }
+        if self.require_issue_reference
+            && !contains_issue_reference(title)
+            && !contains_issue_reference(description)
+        {
+            violations.push(
+                "The title or the description has to reference an issue (e.g. `#123`).".to_string(),
+            );
+        }
+        let length = title.chars().count();
+        if let Some(max_length) = self.max_subject_length
+            && length > max_length
+        {
+            violations.push(format!(
+                "The title has {length} characters, but it can have at most {max_length} characters."
+            ));
+        }
+        let lowercase_title = title.to_lowercase();
+        for marker in &self.forbidden_markers {
+            if lowercase_title.contains(&marker.to_lowercase()) {
+                violations.push(format!("The title must not contain `{marker}`."));
+            }
+        }
+        violations
+    }
+}
+
+/// Users and teams with permissions in the repository, configured in the `[permissions]`
+/// section. This allows using bors without an external permission service.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PermissionsConfig {
+    /// Users and teams that can approve PRs and start try builds.
+    #[serde(default)]
+    pub reviewers: Vec<PermissionEntry>,
+    /// Users and teams that can start try builds.
+    #[serde(default)]
+    pub try_users: Vec<PermissionEntry>,
+}
+
+impl PermissionsConfig {
+    pub fn is_empty(&self) -> bool {
+        self.reviewers.is_empty() && self.try_users.is_empty()
+    }
+}
+
+/// A GitHub user (`login`) or a GitHub team (`@org/team-slug`) listed in the permissions
+/// configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PermissionEntry {
+    User(String),
+    Team { org: String, slug: String },
+}
+
+impl FromStr for PermissionEntry {
+    type Err = String;
+
+    fn from_str(entry: &str) -> Result<Self, Self::Err> {
+        let is_valid_name =
+            |name: &str| !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-');
+        match entry.strip_prefix('@') {
+            Some(team) => match team.split_once('/') {
+                Some((org, slug)) if is_valid_name(org) && is_valid_name(slug) => Ok(Self::Team {
+                    org: org.to_string(),
+                    slug: slug.to_string(),
+                }),
+                _ => Err(format!(
+                    "Invalid team `{entry}`: teams have to be written as `@org/team-slug`"
+                )),
+            },
+            None if is_valid_name(entry) => Ok(Self::User(entry.to_string())),
+            None => Err(format!("Invalid GitHub user `{entry}`")),
+        }
+    }
+}
+
+impl fmt::Display for PermissionEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PermissionEntry::User(login) => f.write_str(login),
+            PermissionEntry::Team { org, slug } => write!(f, "@{org}/{slug}"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PermissionEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entry = String::deserialize(deserializer)?;
+        entry.parse().map_err(Error::custom)
+    }
+}
+
+impl serde::Serialize for PermissionEntry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Clean-up of the artifacts of try builds, configured in the `[try_artifact_retention]` section.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ArtifactRetentionConfig {
+    /// How long (in seconds) are the artifacts of a try build kept after the build has finished.
+    #[serde(
+        deserialize_with = "deserialize_duration_from_secs",
+        serialize_with = "serialize_duration_as_secs"
+    )]
+    pub period: Duration,
+    /// GitHub Actions workflow (e.g. `cleanup-artifacts.yml`) that is dispatched to clean up the
+    /// artifacts, e.g. when they are uploaded to external storage. It receives the `build_id`,
+    /// `commit_sha` and `run_ids` (comma-separated) inputs.
+    /// If it is not set, bors deletes the GitHub Actions artifacts of the build itself.
+    #[serde(default)]
+    pub workflow: Option<String>,
+}
+
+/// A merge queue for PRs that only modify files under some paths of the repository.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PathQueueConfig {
+    /// Path prefixes of the files that belong to the queue, e.g. `frontend/`.
+    pub paths: Vec<String>,
+    /// Maximum number of auto builds of the queue that can run at the same time.
+    /// Defaults to `max_parallel_auto_builds` of the repository.
+    #[serde(default)]
+    pub max_parallel_auto_builds: Option<usize>,
+    /// Commit status (or check run) contexts, e.g. the jobs of the workflows of the component,
+    /// that have to succeed for auto builds of the queue to pass, in addition to the
+    /// `required_status_contexts` of the repository. If set, the auto builds of the queue are
+    /// tracked using these contexts instead of GitHub Actions workflows.
+    /// Defaults to no contexts.
+    #[serde(default)]
+    pub required_status_contexts: Vec<String>,
+}
+
+impl PathQueueConfig {
+    fn contains(&self, file: &str) -> bool {
+        self.paths
+            .iter()
+            .any(|path| file.starts_with(path.as_str()))
+    }
+}
+
+/// Policy for try builds of pull requests opened from forks.
+///
+/// Try builds run on a branch of the main repository, so their workflows have access to
+/// repository secrets, even if the tested code comes from a fork.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ForkTryPolicy {
+    /// Fork PRs are tested in the same way as any other PR.
+    #[default]
+    Allow,
+    /// Try builds are refused for fork PRs.
+    Deny,
+    /// Try builds of fork PRs are only started if every job of the workflows triggered by the
+    /// push of the try merge commit runs in the given deployment environment, which is also
+    /// annotated in the merge commit. The workflows are checked at the tested commit, because the
+    /// PR can modify them. What the environment can access (e.g. no repository secrets) is
+    /// configured in GitHub, bors only verifies that the workflows use it.
+    Restricted { environment: String },
+}
+
+/// Policy for try builds whose explicit parent commit is not an ancestor of the base branch.
+///
+/// Merging a PR into such a commit produces a merge commit that can contain unrelated changes
+/// (or miss changes of the base branch), which makes the try build results confusing.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnreachableParentPolicy {
+    /// The try build is started, but bors posts a warning about the parent.
+    #[default]
+    Warn,
+    /// The try build is refused.
+    Refuse,
+}
+
+/// Policy for the approval of pull requests that are reopened after being closed.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReopenApprovalPolicy {
+    /// The PR keeps its approval, unless its head commit has changed while it was closed.
+    #[default]
+    Restore,
+    /// The PR is unapproved, so it has to be approved again after being reopened.
+    Unapprove,
+}
+
+/// Policy for PRs with new commits that were pushed after the PR was approved.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StaleApprovalPolicy {
+    /// The PR is merged with the new commits.
+    Merge,
+    /// The PR is unapproved when new commits are pushed to it, so it has to be approved again.
+    #[default]
+    RequireReapproval,
+    /// The PR is merged only if all new commits merge its base branch into the PR, e.g. to
+    /// resolve merge conflicts. Otherwise, it has to be approved again.
+    MergeBaseOnly,
 }
 
 fn default_timeout() -> Duration {
     Duration::from_secs(3600)
 }
 
+fn default_max_parallel_auto_builds() -> usize {
+    1
+}
+
+fn default_notifications_per_minute() -> usize {
+    10
+}
+
+fn default_try_enabled() -> bool {
+    true
+}
+
+fn default_required_approvals() -> usize {
+    1
+}
+
+fn default_unapprove_on_push() -> bool {
+    true
+}
+
+fn default_restart_auto_build_on_edit() -> bool {
+    true
+}
+
+fn default_spurious_failure_retries() -> u32 {
+    3
+}
+
+fn deserialize_regexes<'de, D>(deserializer: D) -> Result<Vec<Regex>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern)
+                .map_err(|error| Error::custom(format!("Invalid pattern `{pattern}`: {error}")))
+        })
+        .collect()
+}
+
+fn deserialize_regex_opt<'de, D>(deserializer: D) -> Result<Option<Regex>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|pattern| {
+            Regex::new(&pattern)
+                .map_err(|error| Error::custom(format!("Invalid pattern `{pattern}`: {error}")))
+        })
+        .transpose()
+}
+
+fn deserialize_command_aliases<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let aliases = HashMap::<String, String>::deserialize(deserializer)?;
+    for (alias, expansion) in &aliases {
+        if alias.is_empty() || alias.contains(|c: char| c.is_whitespace() || c == '=') {
+            return Err(Error::custom(format!(
+                "Invalid command alias `{alias}`: it must be a single word without `=`"
+            )));
+        }
+        if expansion.trim().is_empty() {
+            return Err(Error::custom(format!(
+                "Invalid command alias `{alias}`: its expansion must not be empty"
+            )));
+        }
+    }
+    Ok(aliases)
+}
+
+fn deserialize_maintenance_windows<'de, D>(
+    deserializer: D,
+) -> Result<Vec<MaintenanceWindow>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let windows = Vec::<MaintenanceWindow>::deserialize(deserializer)?;
+    for window in &windows {
+        if window.schedule.is_some() == window.start.is_some() {
+            return Err(Error::custom(
+                "Invalid maintenance window: exactly one of `schedule` and `start` has to be set",
+            ));
+        }
+        if window.duration <= chrono::Duration::zero() {
+            return Err(Error::custom(
+                "Invalid maintenance window: `duration` must be positive",
+            ));
+        }
+    }
+    Ok(windows)
+}
+
+fn deserialize_timestamp_opt<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(timestamp) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    DateTime::parse_from_rfc3339(&timestamp)
+        .map(|timestamp| Some(timestamp.to_utc()))
+        .map_err(|error| Error::custom(format!("Invalid timestamp `{timestamp}`: {error}")))
+}
+
+fn serialize_timestamp_opt<S: Serializer>(
+    timestamp: &Option<DateTime<Utc>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match timestamp {
+        Some(timestamp) => serializer.serialize_some(&timestamp.to_rfc3339()),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_chrono_duration_from_secs<'de, D>(
+    deserializer: D,
+) -> Result<chrono::Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let seconds = u32::deserialize(deserializer)?;
+    Ok(chrono::Duration::seconds(seconds.into()))
+}
+
+fn serialize_chrono_duration_as_secs<S: Serializer>(
+    duration: &chrono::Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_i64(duration.num_seconds())
+}
+
 fn deserialize_duration_from_secs_opt<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
 where
     D: Deserializer<'de>,
@@ -59,6 +797,67 @@ where
     Ok(Duration::from_secs(seconds))
 }
 
+fn serialize_duration_as_secs<S: Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_secs())
+}
+
+fn serialize_duration_as_secs_opt<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serializer.serialize_some(&duration.as_secs()),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn serialize_regexes<S: Serializer>(regexes: &[Regex], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(regexes.iter().map(Regex::as_str))
+}
+
+fn serialize_regex_opt<S: Serializer>(
+    regex: &Option<Regex>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match regex {
+        Some(regex) => serializer.serialize_some(regex.as_str()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Serializes labels in the format of the `[labels]` section of the config file.
+fn serialize_labels<S: Serializer>(
+    labels: &HashMap<LabelTrigger, Vec<LabelModification>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let labels: BTreeMap<&str, Vec<String>> = labels
+        .iter()
+        .map(|(trigger, modifications)| {
+            let trigger = match trigger {
+                LabelTrigger::Approved => "approved",
+                LabelTrigger::Unapproved => "unapproved",
+                LabelTrigger::TryBuildFailed => "try_failed",
+                LabelTrigger::AutoBuildSucceeded => "auto_build_succeeded",
+                LabelTrigger::AutoBuildFailed => "auto_build_failed",
+                LabelTrigger::Conflicted => "conflicted",
+                LabelTrigger::Backport => "backport",
+            };
+            let modifications = modifications
+                .iter()
+                .map(|modification| match modification {
+                    LabelModification::Add(label) => format!("+{label}"),
+                    LabelModification::Remove(label) => format!("-{label}"),
+                })
+                .collect();
+            (trigger, modifications)
+        })
+        .collect();
+    serializer.collect_map(labels)
+}
+
 fn deserialize_labels<'de, D>(
     deserializer: D,
 ) -> Result<HashMap<LabelTrigger, Vec<LabelModification>>, D::Error>
@@ -73,6 +872,8 @@ where
         TryFailed,
         AutoBuildSucceeded,
         AutoBuildFailed,
+        Conflicted,
+        Backport,
     }
 
     impl From<Trigger> for LabelTrigger {
@@ -83,6 +884,8 @@ where
                 Trigger::TryFailed => LabelTrigger::TryBuildFailed,
                 Trigger::AutoBuildSucceeded => LabelTrigger::AutoBuildSucceeded,
                 Trigger::AutoBuildFailed => LabelTrigger::AutoBuildFailed,
+                Trigger::Conflicted => LabelTrigger::Conflicted,
+                Trigger::Backport => LabelTrigger::Backport,
             }
         }
     }
@@ -152,7 +955,14 @@ where
 mod tests {
     use std::{collections::BTreeMap, time::Duration};
 
-    use crate::config::{RepositoryConfig, default_timeout};
+    use chrono::{DateTime, Utc};
+
+    use crate::bors::comment::CommentStyle;
+    use crate::bors::localization::Language;
+    use crate::config::{
+        ForkTryPolicy, MaintenanceWindow, PathQueueConfig, PermissionEntry, ReopenApprovalPolicy,
+        RepositoryConfig, StaleApprovalPolicy, UnreachableParentPolicy, default_timeout,
+    };
 
     #[test]
     fn deserialize_empty() {
@@ -168,6 +978,100 @@ mod tests {
         assert_eq!(config.timeout.as_secs(), 3600);
     }
 
+    #[test]
+    fn deserialize_max_parallel_auto_builds_default() {
+        let config = load_config("");
+        assert_eq!(config.max_parallel_auto_builds, 1);
+    }
+
+    #[test]
+    fn deserialize_max_parallel_auto_builds() {
+        let content = "max_parallel_auto_builds = 3";
+        let config = load_config(content);
+        assert_eq!(config.max_parallel_auto_builds, 3);
+    }
+
+    #[test]
+    fn deserialize_required_status_contexts() {
+        let content = r#"required_status_contexts = ["buildkite/pipeline", "ci/jenkins"]"#;
+        let config = load_config(content);
+        assert_eq!(
+            config.required_status_contexts,
+            vec!["buildkite/pipeline".to_string(), "ci/jenkins".to_string()]
+        );
+    }
+
+    #[test]
+    fn deserialize_squash_merge() {
+        assert!(!load_config("").squash_merge);
+        assert!(load_config("squash_merge = true").squash_merge);
+    }
+
+    #[test]
+    fn deserialize_validate_try_jobs() {
+        assert!(!load_config("").validate_try_jobs);
+        assert!(load_config("validate_try_jobs = true").validate_try_jobs);
+    }
+
+    #[test]
+    fn deserialize_spurious_failure_patterns() {
+        let config = load_config("");
+        assert!(config.spurious_failure_patterns.is_empty());
+        assert_eq!(config.spurious_failure_retries, 3);
+
+        let config = load_config(
+            r#"
+spurious_failure_patterns = ["connection reset by peer", "runner .* lost"]
+spurious_failure_retries = 1
+"#,
+        );
+        let patterns: Vec<&str> = config
+            .spurious_failure_patterns
+            .iter()
+            .map(|pattern| pattern.as_str())
+            .collect();
+        assert_eq!(patterns, vec!["connection reset by peer", "runner .* lost"]);
+        assert_eq!(config.spurious_failure_retries, 1);
+    }
+
+    #[test]
+    fn deserialize_rollup_failure_iffy() {
+        assert!(!load_config("").rollup_failure_iffy);
+        assert!(load_config("rollup_failure_iffy = true").rollup_failure_iffy);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid pattern `(`")]
+    fn deserialize_spurious_failure_patterns_invalid() {
+        load_config(r#"spurious_failure_patterns = ["("]"#);
+    }
+
+    #[test]
+    fn deserialize_branch_protection_checks() {
+        assert!(!load_config("").branch_protection_checks);
+        assert!(load_config("branch_protection_checks = true").branch_protection_checks);
+    }
+
+    #[test]
+    fn deserialize_queue_check_runs() {
+        let content = "queue_check_runs = true";
+        let config = load_config(content);
+        assert!(config.queue_check_runs);
+    }
+
+    #[test]
+    fn deserialize_auto_build_timeout_empty() {
+        let config = load_config("");
+        assert_eq!(config.auto_build_timeout, None);
+    }
+
+    #[test]
+    fn deserialize_auto_build_timeout() {
+        let content = "auto_build_timeout = 7200";
+        let config = load_config(content);
+        assert_eq!(config.auto_build_timeout, Some(Duration::from_secs(7200)));
+    }
+
     #[test]
     fn deserialize_min_ci_time_empty() {
         let content = "";
@@ -182,6 +1086,87 @@ mod tests {
         assert_eq!(config.min_ci_time, Some(Duration::from_secs(3600)));
     }
 
+    #[test]
+    fn deserialize_merge_queue_branches_default() {
+        let config = load_config("");
+        assert!(config.merge_queue_branches.is_empty());
+    }
+
+    #[test]
+    fn deserialize_merge_queue_branches() {
+        let content = r#"merge_queue_branches = ["beta", "stable"]"#;
+        let config = load_config(content);
+        assert_eq!(config.merge_queue_branches, vec!["beta", "stable"]);
+    }
+
+    #[test]
+    fn deserialize_path_queues() {
+        let content = r#"
+[path_queues.frontend]
+paths = ["frontend/", "assets/"]
+max_parallel_auto_builds = 2
+required_status_contexts = ["frontend-tests"]
+
+[path_queues.backend]
+paths = ["backend/"]
+"#;
+        let config = load_config(content);
+        assert_eq!(
+            config.path_queues["frontend"],
+            PathQueueConfig {
+                paths: vec!["frontend/".to_string(), "assets/".to_string()],
+                max_parallel_auto_builds: Some(2),
+                required_status_contexts: vec!["frontend-tests".to_string()],
+            }
+        );
+        assert_eq!(config.path_queues["backend"].max_parallel_auto_builds, None);
+    }
+
+    #[test]
+    fn path_queue_for_files() {
+        let config = load_config(
+            r#"
+[path_queues.frontend]
+paths = ["frontend/", "assets/"]
+
+[path_queues.backend]
+paths = ["backend/"]
+"#,
+        );
+        let files = |files: &[&str]| files.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            config.path_queue_for(&files(&["frontend/a.js", "assets/logo.png"])),
+            Some("frontend")
+        );
+        assert_eq!(
+            config.path_queue_for(&files(&["backend/src/main.rs"])),
+            Some("backend")
+        );
+        assert_eq!(
+            config.path_queue_for(&files(&["frontend/a.js", "backend/src/main.rs"])),
+            None
+        );
+        assert_eq!(
+            config.path_queue_for(&files(&["frontend/a.js", "README.md"])),
+            None
+        );
+        assert_eq!(config.path_queue_for(&[]), None);
+    }
+
+    #[test]
+    fn deserialize_try_enabled_default() {
+        let content = "";
+        let config = load_config(content);
+        assert!(config.try_enabled);
+    }
+
+    #[test]
+    fn deserialize_try_enabled_false() {
+        let content = "try_enabled = false";
+        let config = load_config(content);
+        assert!(!config.try_enabled);
+    }
+
     #[test]
     fn deserialize_merge_queue_enabled_default() {
         let content = "";
@@ -211,6 +1196,7 @@ unapproved = ["-approved"]
 try_failed = []
 auto_build_succeeded = ["+foobar", "-foo"]
 auto_build_failed = ["+bar", "+baz"]
+conflicted = ["+conflicts"]
 "#;
         let config = load_config(content);
         insta::assert_debug_snapshot!(config.labels.into_iter().collect::<BTreeMap<_, _>>(), @r#"
@@ -242,6 +1228,11 @@ auto_build_failed = ["+bar", "+baz"]
                     "baz",
                 ),
             ],
+            Conflicted: [
+                Add(
+                    "conflicts",
+                ),
+            ],
         }
         "#);
     }
@@ -255,6 +1246,13 @@ approved = ["foo"]
         load_config(content);
     }
 
+    #[test]
+    fn deserialize_required_checklist_items() {
+        let content = r#"required_checklist_items = ["Tests were added"]"#;
+        let config = load_config(content);
+        assert_eq!(config.required_checklist_items, vec!["Tests were added"]);
+    }
+
     #[test]
     fn deserialize_labels_blocking_approval() {
         let content = r#"labels_blocking_approval = ["foo", "bar"]"#;
@@ -267,6 +1265,74 @@ approved = ["foo"]
         "#);
     }
 
+    #[test]
+    fn deserialize_fork_try_builds_default() {
+        let content = "";
+        let config = load_config(content);
+        assert_eq!(config.fork_try_builds, ForkTryPolicy::Allow);
+    }
+
+    #[test]
+    fn deserialize_fork_try_builds_deny() {
+        let content = r#"fork_try_builds = "deny""#;
+        let config = load_config(content);
+        assert_eq!(config.fork_try_builds, ForkTryPolicy::Deny);
+    }
+
+    #[test]
+    fn deserialize_fork_try_builds_restricted() {
+        let content = r#"fork_try_builds = { restricted = { environment = "untrusted" } }"#;
+        let config = load_config(content);
+        assert_eq!(
+            config.fork_try_builds,
+            ForkTryPolicy::Restricted {
+                environment: "untrusted".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_max_queue_size_default() {
+        let content = "";
+        let config = load_config(content);
+        assert_eq!(config.max_queue_size, None);
+    }
+
+    #[test]
+    fn deserialize_max_queue_size() {
+        let content = "max_queue_size = 20";
+        let config = load_config(content);
+        assert_eq!(config.max_queue_size, Some(20));
+    }
+
+    #[test]
+    fn deserialize_required_approvals_default() {
+        let content = "";
+        let config = load_config(content);
+        assert_eq!(config.required_approvals, 1);
+    }
+
+    #[test]
+    fn deserialize_required_approvals() {
+        let content = "required_approvals = 2";
+        let config = load_config(content);
+        assert_eq!(config.required_approvals, 2);
+    }
+
+    #[test]
+    fn deserialize_command_cooldown_default() {
+        let content = "";
+        let config = load_config(content);
+        assert_eq!(config.command_cooldown, None);
+    }
+
+    #[test]
+    fn deserialize_command_cooldown() {
+        let content = "command_cooldown = 600";
+        let config = load_config(content);
+        assert_eq!(config.command_cooldown, Some(Duration::from_secs(600)));
+    }
+
     #[test]
     #[should_panic(expected = "unknown field `labels-blocking-approval`")]
     fn deserialize_unknown_key_fail() {
@@ -274,6 +1340,385 @@ approved = ["foo"]
         load_config(content);
     }
 
+    #[test]
+    fn deserialize_unapprove_on_push_default() {
+        let config = load_config("");
+        assert!(config.unapprove_on_push);
+    }
+
+    #[test]
+    fn deserialize_unapprove_on_push() {
+        let content = "unapprove_on_push = false";
+        let config = load_config(content);
+        assert!(!config.unapprove_on_push);
+    }
+
+    #[test]
+    fn deserialize_restart_auto_build_on_edit() {
+        assert!(load_config("").restart_auto_build_on_edit);
+        assert!(!load_config("restart_auto_build_on_edit = false").restart_auto_build_on_edit);
+    }
+
+    #[test]
+    fn deserialize_stale_approval() {
+        assert_eq!(
+            load_config("").stale_approval,
+            StaleApprovalPolicy::RequireReapproval
+        );
+        let config = load_config(r#"stale_approval = "merge_base_only""#);
+        assert_eq!(config.stale_approval, StaleApprovalPolicy::MergeBaseOnly);
+    }
+
+    #[test]
+    fn deserialize_dry_run() {
+        assert!(!load_config("").dry_run);
+        assert!(load_config("dry_run = true").dry_run);
+    }
+
+    #[test]
+    fn deserialize_comment_style_default() {
+        let config = load_config("");
+        assert_eq!(config.comment_style, CommentStyle::Emoji);
+    }
+
+    #[test]
+    fn deserialize_comment_style() {
+        let content = r#"comment_style = "minimal_markdown""#;
+        let config = load_config(content);
+        assert_eq!(config.comment_style, CommentStyle::MinimalMarkdown);
+    }
+
+    #[test]
+    fn deserialize_accessible_comments_default() {
+        let config = load_config("");
+        assert!(!config.accessible_comments);
+    }
+
+    #[test]
+    fn deserialize_accessible_comments() {
+        let content = "accessible_comments = true";
+        let config = load_config(content);
+        assert!(config.accessible_comments);
+    }
+
+    #[test]
+    fn deserialize_notifications_per_minute_default() {
+        let config = load_config("");
+        assert_eq!(config.notifications_per_minute, 10);
+    }
+
+    #[test]
+    fn deserialize_notifications_per_minute() {
+        let content = "notifications_per_minute = 3";
+        let config = load_config(content);
+        assert_eq!(config.notifications_per_minute, 3);
+    }
+
+    #[test]
+    fn deserialize_try_build_comment_template() {
+        assert!(load_config("").try_build_comment_template.is_none());
+        let content =
+            r#"try_build_comment_template = "[Compare](https://perf.example/{parent}...{sha})""#;
+        let config = load_config(content);
+        assert_eq!(
+            config.try_build_comment_template.as_deref(),
+            Some("[Compare](https://perf.example/{parent}...{sha})")
+        );
+    }
+
+    #[test]
+    fn deserialize_hide_outdated_comments() {
+        assert!(!load_config("").hide_outdated_comments);
+        assert!(load_config("hide_outdated_comments = true").hide_outdated_comments);
+    }
+
+    #[test]
+    fn deserialize_acknowledge_with_reaction() {
+        assert!(!load_config("").acknowledge_with_reaction);
+        assert!(load_config("acknowledge_with_reaction = true").acknowledge_with_reaction);
+    }
+
+    #[test]
+    fn deserialize_info_rejected_commands() {
+        assert!(!load_config("").info_rejected_commands);
+        assert!(load_config("info_rejected_commands = true").info_rejected_commands);
+    }
+
+    #[test]
+    fn deserialize_comment_signature() {
+        let content = r#"comment_signature = "Sent by the merge bot of Foo""#;
+        let config = load_config(content);
+        assert_eq!(
+            config.comment_signature.as_deref(),
+            Some("Sent by the merge bot of Foo")
+        );
+    }
+
+    #[test]
+    fn deserialize_block_labels() {
+        let content = r#"block_labels = ["S-blocked", "do-not-merge"]"#;
+        let config = load_config(content);
+        assert_eq!(config.block_labels, vec!["S-blocked", "do-not-merge"]);
+    }
+
+    #[test]
+    fn deserialize_approve_on_review_default() {
+        let config = load_config("");
+        assert!(!config.approve_on_review);
+    }
+
+    #[test]
+    fn deserialize_approve_on_review() {
+        let content = "approve_on_review = true";
+        let config = load_config(content);
+        assert!(config.approve_on_review);
+    }
+
+    #[test]
+    fn deserialize_unreachable_try_parent_default() {
+        let config = load_config("");
+        assert_eq!(config.unreachable_try_parent, UnreachableParentPolicy::Warn);
+    }
+
+    #[test]
+    fn deserialize_unreachable_try_parent() {
+        let content = r#"unreachable_try_parent = "refuse""#;
+        let config = load_config(content);
+        assert_eq!(
+            config.unreachable_try_parent,
+            UnreachableParentPolicy::Refuse
+        );
+    }
+
+    #[test]
+    fn deserialize_approval_on_reopen_default() {
+        let config = load_config("");
+        assert_eq!(config.approval_on_reopen, ReopenApprovalPolicy::Restore);
+    }
+
+    #[test]
+    fn deserialize_approval_on_reopen() {
+        let content = r#"approval_on_reopen = "unapprove""#;
+        let config = load_config(content);
+        assert_eq!(config.approval_on_reopen, ReopenApprovalPolicy::Unapprove);
+    }
+
+    #[test]
+    fn deserialize_command_aliases() {
+        assert!(load_config("").command_aliases.is_empty());
+
+        let config = load_config(
+            r#"
+[command_aliases]
+lgtm = "r+"
+merge = "r+ rollup=never"
+"#,
+        );
+        assert_eq!(config.command_aliases.len(), 2);
+        assert_eq!(config.command_aliases["lgtm"], "r+");
+        assert_eq!(config.command_aliases["merge"], "r+ rollup=never");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid command alias `r=foo`")]
+    fn deserialize_command_aliases_invalid() {
+        load_config(
+            r#"
+[command_aliases]
+"r=foo" = "r+"
+"#,
+        );
+    }
+
+    #[test]
+    fn deserialize_build_env() {
+        assert!(load_config("").build_env.is_empty());
+
+        let config = load_config(
+            r#"
+[build_env]
+profile = ["debug-assertions", "release"]
+"#,
+        );
+        assert_eq!(
+            config.build_env["profile"],
+            vec!["debug-assertions".to_string(), "release".to_string()]
+        );
+    }
+
+    #[test]
+    fn deserialize_maintenance_windows() {
+        let config = load_config(
+            r#"
+tracking_issue = 10
+
+[[maintenance_windows]]
+schedule = "0 22 * * 5"
+duration = 7200
+priority = 1000
+reason = "Weekly infrastructure upgrade"
+
+[[maintenance_windows]]
+start = "2025-10-01T08:00:00Z"
+duration = 3600
+priority = 10
+"#,
+        );
+        assert_eq!(config.tracking_issue, Some(10));
+        assert_eq!(
+            config.maintenance_windows[1],
+            MaintenanceWindow {
+                schedule: None,
+                start: Some(timestamp("2025-10-01T08:00:00Z")),
+                duration: chrono::Duration::hours(1),
+                priority: 10,
+                reason: None,
+            }
+        );
+
+        // Friday
+        let (window, start, end) = config
+            .active_maintenance_window(timestamp("2025-09-19T23:00:00Z"))
+            .unwrap();
+        assert_eq!(window.priority, 1000);
+        assert_eq!(start, timestamp("2025-09-19T22:00:00Z"));
+        assert_eq!(end, timestamp("2025-09-20T00:00:00Z"));
+        let (window, _, _) = config
+            .active_maintenance_window(timestamp("2025-10-01T08:30:00Z"))
+            .unwrap();
+        assert_eq!(window.priority, 10);
+        assert!(
+            config
+                .active_maintenance_window(timestamp("2025-10-01T09:00:00Z"))
+                .is_none()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "exactly one of `schedule` and `start` has to be set")]
+    fn deserialize_maintenance_window_without_start() {
+        load_config(
+            r#"
+[[maintenance_windows]]
+duration = 3600
+priority = 10
+"#,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid cron expression")]
+    fn deserialize_maintenance_window_invalid_schedule() {
+        load_config(
+            r#"
+[[maintenance_windows]]
+schedule = "0 25 * * *"
+duration = 3600
+priority = 10
+"#,
+        );
+    }
+
+    #[test]
+    fn deserialize_lint() {
+        let config = load_config(
+            r#"
+[lint]
+title_pattern = "^(feat|fix): "
+require_issue_reference = true
+max_subject_length = 20
+forbidden_markers = ["WIP", "fixup!"]
+"#,
+        );
+        assert!(config.lint.violations("fix: foo", "Fixes #1").is_empty());
+        assert_eq!(
+            config
+                .lint
+                .violations("Update the fixup! code", "See the issue"),
+            vec![
+                "The title has to match `^(feat|fix): `.",
+                "The title or the description has to reference an issue (e.g. `#123`).",
+                "The title has 22 characters, but it can have at most 20 characters.",
+                "The title must not contain `fixup!`.",
+            ]
+        );
+        assert_eq!(
+            config.lint.violations("feat: wip #2", ""),
+            vec!["The title must not contain `WIP`."]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid pattern")]
+    fn deserialize_lint_invalid_pattern() {
+        load_config(
+            r#"
+[lint]
+title_pattern = "("
+"#,
+        );
+    }
+
+    #[test]
+    fn deserialize_permissions() {
+        let config = load_config(
+            r#"
+[permissions]
+reviewers = ["alice", "@rust-lang/compiler"]
+try_users = ["bob-2"]
+"#,
+        );
+        assert_eq!(
+            config.permissions.reviewers,
+            vec![
+                PermissionEntry::User("alice".to_string()),
+                PermissionEntry::Team {
+                    org: "rust-lang".to_string(),
+                    slug: "compiler".to_string()
+                }
+            ]
+        );
+        assert_eq!(
+            config.permissions.try_users,
+            vec![PermissionEntry::User("bob-2".to_string())]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "teams have to be written as `@org/team-slug`")]
+    fn deserialize_permissions_team_without_org() {
+        load_config(
+            r#"
+[permissions]
+reviewers = ["@compiler"]
+"#,
+        );
+    }
+
+    #[test]
+    fn deserialize_language_default() {
+        let config = load_config("");
+        assert_eq!(config.language, Language::En);
+    }
+
+    #[test]
+    fn deserialize_language() {
+        let content = r#"language = "de""#;
+        let config = load_config(content);
+        assert_eq!(config.language, Language::De);
+    }
+
+    #[test]
+    #[should_panic]
+    fn deserialize_unknown_language() {
+        let content = r#"language = "xx""#;
+        load_config(content);
+    }
+
+    fn timestamp(timestamp: &str) -> DateTime<Utc> {
+        timestamp.parse().unwrap()
+    }
+
     fn load_config(config: &str) -> RepositoryConfig {
         toml::from_str(config).unwrap()
     }
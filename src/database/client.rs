@@ -1,25 +1,49 @@
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use sqlx::{Connection, PgConnection, PgPool};
 
 use crate::bors::comment::CommentTag;
-use crate::bors::{PullRequestStatus, RollupMode};
+use crate::bors::{BuildArtifact, PullRequestStatus, RollupMode};
 use crate::database::operations::{get_merge_queue_prs, update_pr_auto_build_id};
 use crate::database::{
-    BuildModel, BuildStatus, CommentModel, PullRequestModel, RepoModel, TreeState, WorkflowModel,
-    WorkflowStatus, WorkflowType,
+    AuditLogEntryModel, BuildArtifactModel, BuildModel, BuildRetryModel, BuildStatsModel,
+    BuildStatus, BuildTimingModel, CommentModel, CoordinationGuard, CoordinationLock,
+    DeactivationReason, DeadLetterModel, EmergencyStopModel, MergeQueuePauseModel,
+    NotificationWindow, OutboxAction, OutboxModel, PrMilestone, PullRequestModel,
+    QueueCheckRunModel, QueueNotificationModel, RepoModel, TreeState, TryBuildModel,
+    WeeklyLatencyModel, WorkflowModel, WorkflowStatus, WorkflowType,
 };
 use crate::github::PullRequestNumber;
 use crate::github::{CommitSha, GithubRepoName};
 
 use super::operations::{
-    approve_pull_request, clear_auto_build, create_build, create_pull_request, create_workflow,
-    delegate_pull_request, delete_tagged_bot_comment, find_build, find_pr_by_build,
-    get_nonclosed_pull_requests, get_pending_builds, get_prs_with_unknown_mergeability_state,
-    get_pull_request, get_repository, get_repository_by_name, get_tagged_bot_comments,
-    get_workflow_urls_for_build, get_workflows_for_build, insert_repo_if_not_exists,
-    record_tagged_bot_comment, set_pr_assignees, set_pr_priority, set_pr_rollup, set_pr_status,
+    add_queue_notification, approve_pull_request, check_connection, claim_due_outbox_actions,
+    clear_auto_build, clear_emergency_stop, create_build, create_build_retry, create_pr_try_build,
+    create_pull_request, create_workflow, deactivate_repository, delegate_pull_request,
+    delete_build_artifacts, delete_dead_letter, delete_outbox_action, delete_queue_check_run,
+    delete_queue_notifications, delete_tagged_bot_comment, enqueue_delayed_outbox_action,
+    enqueue_outbox_action, find_build, find_pr_by_build, finish_expired_scheduled_tree_closures,
+    get_artifacts_for_build, get_artifacts_for_builds, get_audit_log, get_branch_tree_state,
+    get_build, get_build_retries, get_build_retries_for_prs, get_build_stats, get_build_timing,
+    get_builds, get_builds_for_artifact_cleanup, get_dead_letters, get_dead_letters_to_replay,
+    get_due_outbox_actions, get_emergency_stop, get_merge_queue_pause, get_nonclosed_pull_requests,
+    get_pending_builds, get_pending_try_builds, get_prs_with_unknown_mergeability_state,
+    get_pull_request, get_queue_check_runs, get_queue_notifications, get_rejected_commands,
+    get_repository, get_repository_by_name, get_tagged_bot_comments, get_try_build_jobs,
+    get_weekly_latencies, get_workflow_urls_for_build, get_workflows_for_build,
+    get_workflows_for_builds, insert_dead_letter, insert_repo_if_not_exists,
+    move_pull_request_approval, move_pull_request_approval_and_head, pause_merge_queue,
+    reactivate_repository, record_audit_entry, record_build_artifacts_cleaned, record_build_merged,
+    record_dead_letter_failure, record_outbox_action_failure, record_pr_milestone,
+    record_scheduled_tree_closure, record_tagged_bot_comment, release_outbox_actions,
+    request_dead_letter_replay, reserve_build_id, reserve_notification_slots, resume_merge_queue,
+    set_emergency_stop, set_pr_assignees, set_pr_backports, set_pr_build_env,
+    set_pr_dry_run_head_sha, set_pr_held, set_pr_labels, set_pr_parked, set_pr_path_queue,
+    set_pr_priority, set_pr_rollup, set_pr_status, set_pr_waiting_on_conflicts, try_advisory_lock,
     unapprove_pull_request, undelegate_pull_request, update_build_check_run_id,
-    update_build_status, update_mergeable_states_by_base_branch, update_pr_mergeability_state,
-    update_pr_try_build_id, update_workflow_status, upsert_pull_request, upsert_repository,
+    update_build_status, update_external_workflow, update_mergeable_states_by_base_branch,
+    update_pr_mergeability_state, update_pr_try_build_id, update_workflow_status,
+    upsert_branch_tree_state, upsert_build_artifact, upsert_pull_request, upsert_queue_check_run,
+    upsert_repository,
 };
 use super::{ApprovalInfo, DelegatedPermission, MergeableState, RunId, UpsertPullRequestParams};
 
@@ -34,14 +58,46 @@ impl PgDbClient {
         Self { pool }
     }
 
+    /// Approves the PR on behalf of the approvers in `approval_info`, as requested by `author`.
+    /// Returns all approvers of the PR and the distinct users who commented its approvals. If the
+    /// same commit was already approved, the new approvers are added to the existing ones.
     pub async fn approve(
         &self,
         pr: &PullRequestModel,
         approval_info: ApprovalInfo,
+        author: &str,
         priority: Option<u32>,
         rollup: Option<RollupMode>,
+        squash: Option<bool>,
+    ) -> anyhow::Result<(String, Vec<String>)> {
+        approve_pull_request(
+            &self.pool,
+            pr.id,
+            approval_info,
+            author,
+            priority,
+            rollup,
+            squash,
+        )
+        .await
+    }
+
+    /// Moves the approval of an approved PR to the given commit, without changing its approvers.
+    /// The head SHA of the PR at approval time is kept, so that it is known which commits were
+    /// pushed after the approval.
+    pub async fn move_approval(&self, pr: &PullRequestModel, sha: &str) -> anyhow::Result<()> {
+        move_pull_request_approval(&self.pool, pr.id, sha).await
+    }
+
+    /// Moves the approval of an approved PR to the given commit, and treats that commit as the
+    /// head SHA of the PR at approval time, so that the stale approval policy does not apply to
+    /// the commits that were pushed before it.
+    pub async fn move_approval_and_head(
+        &self,
+        pr: &PullRequestModel,
+        sha: &str,
     ) -> anyhow::Result<()> {
-        approve_pull_request(&self.pool, pr.id, approval_info, priority, rollup).await
+        move_pull_request_approval_and_head(&self.pool, pr.id, sha).await
     }
 
     /// Unapprove a pull request and remove its auto build status, if there is any attached.
@@ -61,8 +117,9 @@ impl PgDbClient {
         &self,
         pr: &PullRequestModel,
         delegated_permission: DelegatedPermission,
+        expires_at: Option<DateTime<Utc>>,
     ) -> anyhow::Result<()> {
-        delegate_pull_request(&self.pool, pr.id, delegated_permission).await
+        delegate_pull_request(&self.pool, pr.id, delegated_permission, expires_at).await
     }
 
     pub async fn undelegate(&self, pr: &PullRequestModel) -> anyhow::Result<()> {
@@ -99,6 +156,75 @@ impl PgDbClient {
             .await
     }
 
+    pub async fn set_pr_labels(
+        &self,
+        repo: &GithubRepoName,
+        pr_number: PullRequestNumber,
+        labels: &[String],
+    ) -> anyhow::Result<()> {
+        set_pr_labels(&self.pool, repo, pr_number, labels).await
+    }
+
+    pub async fn set_waiting_on_conflicts(
+        &self,
+        pr: &PullRequestModel,
+        waiting_on_conflicts: bool,
+    ) -> anyhow::Result<()> {
+        set_pr_waiting_on_conflicts(&self.pool, pr.id, waiting_on_conflicts).await
+    }
+
+    pub async fn set_held(&self, pr: &PullRequestModel, held: bool) -> anyhow::Result<()> {
+        set_pr_held(&self.pool, pr.id, held).await
+    }
+
+    /// Set whether the PR is parked, together with the reason of parking it.
+    pub async fn set_parked(
+        &self,
+        pr: &PullRequestModel,
+        parked: bool,
+        reason: Option<&str>,
+    ) -> anyhow::Result<()> {
+        set_pr_parked(&self.pool, pr.id, parked, reason).await
+    }
+
+    /// Set the path queue of the PR, or move it to the default queue if `path_queue` is `None`.
+    pub async fn set_path_queue(
+        &self,
+        repo: &GithubRepoName,
+        pr_number: PullRequestNumber,
+        path_queue: Option<&str>,
+    ) -> anyhow::Result<()> {
+        set_pr_path_queue(&self.pool, repo, pr_number, path_queue).await
+    }
+
+    /// Sets the build parameters of the PR, as `<key>=<value>` pairs.
+    pub async fn set_build_env(
+        &self,
+        pr: &PullRequestModel,
+        build_env: &[String],
+    ) -> anyhow::Result<()> {
+        set_pr_build_env(&self.pool, pr.id, build_env).await
+    }
+
+    /// Sets the branches onto which the PR should be backported once it is merged.
+    pub async fn set_backports(
+        &self,
+        pr: &PullRequestModel,
+        backports: &[String],
+    ) -> anyhow::Result<()> {
+        set_pr_backports(&self.pool, pr.id, backports).await
+    }
+
+    /// Records that the merge queue has described the auto build of the given head of the PR in
+    /// dry-run mode.
+    pub async fn set_dry_run_head_sha(
+        &self,
+        pr: &PullRequestModel,
+        head_sha: &CommitSha,
+    ) -> anyhow::Result<()> {
+        set_pr_dry_run_head_sha(&self.pool, pr.id, head_sha.as_ref()).await
+    }
+
     pub async fn set_rollup(
         &self,
         pr: &PullRequestModel,
@@ -178,34 +304,114 @@ impl PgDbClient {
         find_pr_by_build(&self.pool, build.id).await
     }
 
+    /// Reserves an ID for a build that will be later created using [`Self::attach_try_build`]
+    /// or [`Self::attach_auto_build`].
+    pub async fn reserve_build_id(&self) -> anyhow::Result<i32> {
+        reserve_build_id(&self.pool).await
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub async fn attach_try_build(
         &self,
         pr: &PullRequestModel,
+        build_id: i32,
         branch: String,
         commit_sha: CommitSha,
         parent: CommitSha,
-    ) -> anyhow::Result<i32> {
+        jobs: &[String],
+        head_sha: &CommitSha,
+    ) -> anyhow::Result<()> {
         let mut tx = self.pool.begin().await?;
-        let build_id =
-            create_build(&mut *tx, &pr.repository, &branch, &commit_sha, &parent).await?;
+        create_build(
+            &mut *tx,
+            build_id,
+            &pr.repository,
+            &branch,
+            &commit_sha,
+            &parent,
+            None,
+        )
+        .await?;
+        create_pr_try_build(&mut *tx, pr.id, build_id, jobs, head_sha).await?;
         update_pr_try_build_id(&mut *tx, pr.id, build_id).await?;
         tx.commit().await?;
-        Ok(build_id)
+        Ok(())
+    }
+
+    pub async fn get_pending_try_builds(
+        &self,
+        pr: &PullRequestModel,
+    ) -> anyhow::Result<Vec<TryBuildModel>> {
+        get_pending_try_builds(&self.pool, pr.id).await
+    }
+
+    /// Returns the jobs selected for the given try build.
+    pub async fn get_try_build_jobs(&self, build: &BuildModel) -> anyhow::Result<Vec<String>> {
+        get_try_build_jobs(&self.pool, build.id).await
+    }
+
+    /// Records that the given failed build of a PR was retried, along with the approved commit of
+    /// the PR. `retried_by` is `None` for automatic retries.
+    pub async fn record_build_retry(
+        &self,
+        pr: &PullRequestModel,
+        build: &BuildModel,
+        retried_by: Option<&str>,
+        reason: Option<&str>,
+    ) -> anyhow::Result<()> {
+        create_build_retry(
+            &self.pool,
+            pr.id,
+            build.id,
+            retried_by,
+            reason,
+            pr.approved_sha(),
+        )
+        .await
+    }
+
+    pub async fn get_build_retries(
+        &self,
+        pr: &PullRequestModel,
+    ) -> anyhow::Result<Vec<BuildRetryModel>> {
+        get_build_retries(&self.pool, pr.id).await
+    }
+
+    /// Returns the build retries of all the given PRs.
+    pub async fn get_build_retries_for_prs(
+        &self,
+        prs: &[&PullRequestModel],
+    ) -> anyhow::Result<Vec<BuildRetryModel>> {
+        let ids: Vec<i32> = prs.iter().map(|pr| pr.id).collect();
+        get_build_retries_for_prs(&self.pool, &ids).await
     }
 
     pub async fn attach_auto_build(
         &self,
         pr: &PullRequestModel,
+        build_id: i32,
         branch: String,
         commit_sha: CommitSha,
         parent: CommitSha,
-    ) -> anyhow::Result<i32> {
+    ) -> anyhow::Result<()> {
         let mut tx = self.pool.begin().await?;
-        let build_id =
-            create_build(&mut *tx, &pr.repository, &branch, &commit_sha, &parent).await?;
+        create_build(
+            &mut *tx,
+            build_id,
+            &pr.repository,
+            &branch,
+            &commit_sha,
+            &parent,
+            pr.approved_at,
+        )
+        .await?;
         update_pr_auto_build_id(&mut *tx, pr.id, build_id).await?;
         tx.commit().await?;
-        Ok(build_id)
+        Ok(())
+    }
+
+    pub async fn get_build(&self, build_id: i32) -> anyhow::Result<Option<BuildModel>> {
+        get_build(&self.pool, build_id).await
     }
 
     pub async fn find_build(
@@ -224,6 +430,17 @@ impl PgDbClient {
         get_pending_builds(&self.pool, repo).await
     }
 
+    /// Returns at most `limit` builds of the repository, newest first.
+    pub async fn get_builds(
+        &self,
+        repo: &GithubRepoName,
+        status: Option<BuildStatus>,
+        before_id: Option<i32>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<BuildModel>> {
+        get_builds(&self.pool, repo, status, before_id, limit).await
+    }
+
     pub async fn update_build_status(
         &self,
         build: &BuildModel,
@@ -232,6 +449,18 @@ impl PgDbClient {
         update_build_status(&self.pool, build.id, status).await
     }
 
+    /// Records that the merge commit of the given auto build has been pushed to the base branch.
+    pub async fn record_build_merged(&self, build: &BuildModel) -> anyhow::Result<()> {
+        record_build_merged(&self.pool, build.id).await
+    }
+
+    pub async fn get_build_timing(
+        &self,
+        build: &BuildModel,
+    ) -> anyhow::Result<Option<BuildTimingModel>> {
+        get_build_timing(&self.pool, build.id).await
+    }
+
     pub async fn update_build_check_run_id(
         &self,
         build_id: i32,
@@ -261,6 +490,30 @@ impl PgDbClient {
         .await
     }
 
+    /// Records the status of an external CI context (e.g. a commit status) as a workflow of
+    /// the given build.
+    pub async fn upsert_external_workflow(
+        &self,
+        build: &BuildModel,
+        name: &str,
+        url: &str,
+        status: WorkflowStatus,
+    ) -> anyhow::Result<()> {
+        if update_external_workflow(&self.pool, build.id, name, url, status).await? {
+            return Ok(());
+        }
+        create_workflow(
+            &self.pool,
+            build.id,
+            name,
+            url,
+            RunId(0),
+            WorkflowType::External,
+            status,
+        )
+        .await
+    }
+
     pub async fn update_workflow_status(
         &self,
         run_id: u64,
@@ -276,6 +529,15 @@ impl PgDbClient {
         get_workflows_for_build(&self.pool, build.id).await
     }
 
+    /// Returns the workflows of all the given builds.
+    pub async fn get_workflows_for_builds(
+        &self,
+        builds: &[&BuildModel],
+    ) -> anyhow::Result<Vec<WorkflowModel>> {
+        let ids: Vec<i32> = builds.iter().map(|build| build.id).collect();
+        get_workflows_for_builds(&self.pool, &ids).await
+    }
+
     pub async fn get_workflow_urls_for_build(
         &self,
         build: &BuildModel,
@@ -298,6 +560,73 @@ impl PgDbClient {
         Ok(workflows)
     }
 
+    /// Stores artifacts produced by a build.
+    /// Artifacts are identified by their name, so storing an artifact with the same name again
+    /// replaces its previous URL and size.
+    pub async fn record_build_artifacts(
+        &self,
+        build: &BuildModel,
+        artifacts: &[BuildArtifact],
+    ) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for artifact in artifacts {
+            upsert_build_artifact(
+                &mut *tx,
+                build.id,
+                &artifact.name,
+                &artifact.url,
+                artifact.size.map(|size| size as i64),
+            )
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_artifacts_for_build(
+        &self,
+        build: &BuildModel,
+    ) -> anyhow::Result<Vec<BuildArtifactModel>> {
+        get_artifacts_for_build(&self.pool, build.id).await
+    }
+
+    /// Returns the artifacts of all the given builds.
+    pub async fn get_artifacts_for_builds(
+        &self,
+        builds: &[&BuildModel],
+    ) -> anyhow::Result<Vec<BuildArtifactModel>> {
+        let ids: Vec<i32> = builds.iter().map(|build| build.id).collect();
+        get_artifacts_for_builds(&self.pool, &ids).await
+    }
+
+    /// Returns at most `limit` builds on the given branch whose artifacts should be cleaned up,
+    /// because they have finished before `finished_before`.
+    pub async fn get_builds_for_artifact_cleanup(
+        &self,
+        repo: &GithubRepoName,
+        branch: &str,
+        finished_before: DateTime<Utc>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<BuildModel>> {
+        get_builds_for_artifact_cleanup(&self.pool, repo, branch, finished_before, limit).await
+    }
+
+    /// Records that the artifacts of the build have been cleaned up. If `deleted` is true, the
+    /// artifacts were deleted, so they are also removed from the database.
+    pub async fn record_build_artifacts_cleaned(
+        &self,
+        build: &BuildModel,
+        deleted: bool,
+    ) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        if deleted {
+            delete_build_artifacts(&mut *tx, build.id).await?;
+        }
+        record_build_artifacts_cleaned(&mut *tx, build.id).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
     pub async fn repo_db(&self, repo: &GithubRepoName) -> anyhow::Result<Option<RepoModel>> {
         get_repository(&self.pool, repo).await
     }
@@ -322,12 +651,76 @@ impl PgDbClient {
         upsert_repository(&self.pool, repo, tree_state).await
     }
 
+    /// Records that the tree has been closed for a scheduled maintenance window.
+    /// Returns false if the window starting at `starts_at` has already been recorded.
+    pub async fn record_scheduled_tree_closure(
+        &self,
+        repo: &GithubRepoName,
+        starts_at: DateTime<Utc>,
+        ends_at: DateTime<Utc>,
+    ) -> anyhow::Result<bool> {
+        record_scheduled_tree_closure(&self.pool, repo, starts_at, ends_at).await
+    }
+
+    /// Marks the scheduled tree closures that have already ended as finished, and returns
+    /// their ends.
+    pub async fn finish_expired_scheduled_tree_closures(
+        &self,
+        repo: &GithubRepoName,
+    ) -> anyhow::Result<Vec<DateTime<Utc>>> {
+        finish_expired_scheduled_tree_closures(&self.pool, repo).await
+    }
+
+    /// Marks the repository as deactivated, so that bors stops operating in it.
+    pub async fn deactivate_repository(
+        &self,
+        repo: &GithubRepoName,
+        reason: DeactivationReason,
+    ) -> anyhow::Result<()> {
+        deactivate_repository(&self.pool, repo, reason).await
+    }
+
+    /// Reactivates the repository if it was deactivated for the given reason.
+    /// Returns true if the repository was reactivated.
+    pub async fn reactivate_repository(
+        &self,
+        repo: &GithubRepoName,
+        reason: DeactivationReason,
+    ) -> anyhow::Result<bool> {
+        reactivate_repository(&self.pool, repo, reason).await
+    }
+
+    /// Returns the tree state of a base branch that has its own merge queue.
+    pub async fn get_branch_tree_state(
+        &self,
+        repo: &GithubRepoName,
+        branch: &str,
+    ) -> anyhow::Result<TreeState> {
+        get_branch_tree_state(&self.pool, repo, branch).await
+    }
+
+    pub async fn upsert_branch_tree_state(
+        &self,
+        repo: &GithubRepoName,
+        branch: &str,
+        tree_state: TreeState,
+    ) -> anyhow::Result<()> {
+        upsert_branch_tree_state(&self.pool, repo, branch, tree_state).await
+    }
+
     pub async fn get_merge_queue_prs(
         &self,
         repo: &GithubRepoName,
         tree_priority: Option<u32>,
+        blocking_labels: &[String],
     ) -> anyhow::Result<Vec<PullRequestModel>> {
-        get_merge_queue_prs(&self.pool, repo, tree_priority.map(|p| p as i32)).await
+        get_merge_queue_prs(
+            &self.pool,
+            repo,
+            tree_priority.map(|p| p as i32),
+            blocking_labels,
+        )
+        .await
     }
 
     pub async fn get_tagged_bot_comments(
@@ -352,4 +745,393 @@ impl PgDbClient {
     pub async fn delete_tagged_bot_comment(&self, comment: &CommentModel) -> anyhow::Result<()> {
         delete_tagged_bot_comment(&self.pool, comment.id).await
     }
+
+    /// Register `username` to be notified when the given PR gets close to the front of the
+    /// merge queue.
+    pub async fn add_queue_notification(
+        &self,
+        repo: &GithubRepoName,
+        pr_number: PullRequestNumber,
+        username: &str,
+    ) -> anyhow::Result<()> {
+        add_queue_notification(&self.pool, repo, pr_number, username).await
+    }
+
+    pub async fn get_queue_notifications(
+        &self,
+        repo: &GithubRepoName,
+    ) -> anyhow::Result<Vec<QueueNotificationModel>> {
+        get_queue_notifications(&self.pool, repo).await
+    }
+
+    pub async fn delete_queue_notifications(
+        &self,
+        repo: &GithubRepoName,
+        pr_number: PullRequestNumber,
+    ) -> anyhow::Result<()> {
+        delete_queue_notifications(&self.pool, repo, pr_number).await
+    }
+
+    /// Record GitHub side effects in the outbox, so that they can be performed later.
+    pub async fn enqueue_outbox_actions(
+        &self,
+        repo: &GithubRepoName,
+        pr_number: PullRequestNumber,
+        actions: &[OutboxAction],
+    ) -> anyhow::Result<Vec<OutboxModel>> {
+        let mut tx = self.pool.begin().await?;
+        let entries = enqueue_outbox_actions(&mut tx, repo, pr_number, actions).await?;
+        tx.commit().await?;
+        Ok(entries)
+    }
+
+    /// Update the status of a build and record the GitHub side effects caused by the update in the
+    /// outbox, in a single transaction.
+    pub async fn update_build_status_with_outbox(
+        &self,
+        build: &BuildModel,
+        status: BuildStatus,
+        pr: &PullRequestModel,
+        actions: &[OutboxAction],
+    ) -> anyhow::Result<Vec<OutboxModel>> {
+        let mut tx = self.pool.begin().await?;
+        update_build_status(&mut *tx, build.id, status).await?;
+        let entries = enqueue_outbox_actions(&mut tx, &pr.repository, pr.number, actions).await?;
+        tx.commit().await?;
+        Ok(entries)
+    }
+
+    /// Update the status of a PR and record the GitHub side effects caused by the update in the
+    /// outbox, in a single transaction.
+    pub async fn set_pr_status_with_outbox(
+        &self,
+        pr: &PullRequestModel,
+        pr_status: PullRequestStatus,
+        actions: &[OutboxAction],
+    ) -> anyhow::Result<Vec<OutboxModel>> {
+        let mut tx = self.pool.begin().await?;
+        set_pr_status(&mut *tx, &pr.repository, pr.number, pr_status).await?;
+        let entries = enqueue_outbox_actions(&mut tx, &pr.repository, pr.number, actions).await?;
+        tx.commit().await?;
+        Ok(entries)
+    }
+
+    /// Detach the auto build from a PR and record the GitHub side effects caused by it in the
+    /// outbox, in a single transaction.
+    pub async fn clear_auto_build_with_outbox(
+        &self,
+        pr: &PullRequestModel,
+        actions: &[OutboxAction],
+    ) -> anyhow::Result<Vec<OutboxModel>> {
+        let mut tx = self.pool.begin().await?;
+        clear_auto_build(&mut *tx, pr.id).await?;
+        let entries = enqueue_outbox_actions(&mut tx, &pr.repository, pr.number, actions).await?;
+        tx.commit().await?;
+        Ok(entries)
+    }
+
+    pub async fn enqueue_delayed_outbox_action(
+        &self,
+        repo: &GithubRepoName,
+        pr_number: PullRequestNumber,
+        action: &OutboxAction,
+        delay_secs: i32,
+    ) -> anyhow::Result<OutboxModel> {
+        enqueue_delayed_outbox_action(&self.pool, repo, pr_number, action, delay_secs).await
+    }
+
+    /// Reserve slots for `count` notifications in the notification windows of the repository, so
+    /// that at most `per_minute` notifications are sent per minute, even across several batches.
+    pub async fn reserve_notification_slots(
+        &self,
+        repo: &GithubRepoName,
+        count: usize,
+        per_minute: usize,
+    ) -> anyhow::Result<Option<NotificationWindow>> {
+        reserve_notification_slots(&self.pool, repo, count as i32, per_minute as i32).await
+    }
+
+    pub async fn get_due_outbox_actions(
+        &self,
+        repo: &GithubRepoName,
+    ) -> anyhow::Result<Vec<OutboxModel>> {
+        get_due_outbox_actions(&self.pool, repo).await
+    }
+
+    /// Claim the outbox entries of the repository whose next attempt is due. Claimed entries are
+    /// not returned to other deliveries until they are performed, fail, or their lease expires.
+    pub async fn claim_due_outbox_actions(
+        &self,
+        repo: &GithubRepoName,
+    ) -> anyhow::Result<Vec<OutboxModel>> {
+        claim_due_outbox_actions(&self.pool, repo).await
+    }
+
+    /// Make claimed outbox entries that were not attempted due again.
+    pub async fn release_outbox_actions(&self, entries: &[OutboxModel]) -> anyhow::Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let ids: Vec<i32> = entries.iter().map(|entry| entry.id).collect();
+        release_outbox_actions(&self.pool, &ids).await
+    }
+
+    pub async fn delete_outbox_action(&self, entry: &OutboxModel) -> anyhow::Result<()> {
+        delete_outbox_action(&self.pool, entry.id).await
+    }
+
+    pub async fn record_outbox_action_failure(&self, entry: &OutboxModel) -> anyhow::Result<()> {
+        record_outbox_action_failure(&self.pool, entry.id).await
+    }
+
+    /// Stores a webhook whose handling has failed in the dead-letter queue.
+    pub async fn insert_dead_letter(
+        &self,
+        repo: &GithubRepoName,
+        event_type: &str,
+        payload: &str,
+        error: &str,
+    ) -> anyhow::Result<DeadLetterModel> {
+        insert_dead_letter(&self.pool, repo, event_type, payload, error).await
+    }
+
+    pub async fn get_dead_letters(&self) -> anyhow::Result<Vec<DeadLetterModel>> {
+        get_dead_letters(&self.pool).await
+    }
+
+    pub async fn get_dead_letters_to_replay(
+        &self,
+        repo: &GithubRepoName,
+    ) -> anyhow::Result<Vec<DeadLetterModel>> {
+        get_dead_letters_to_replay(&self.pool, repo).await
+    }
+
+    /// Requests a replay of the dead letter with the given `id`, or of all dead letters if `id`
+    /// is `None`. Returns the number of dead letters that will be replayed.
+    pub async fn request_dead_letter_replay(&self, id: Option<i32>) -> anyhow::Result<u64> {
+        request_dead_letter_replay(&self.pool, id).await
+    }
+
+    pub async fn delete_dead_letter(&self, dead_letter: &DeadLetterModel) -> anyhow::Result<()> {
+        delete_dead_letter(&self.pool, dead_letter.id).await
+    }
+
+    pub async fn record_dead_letter_failure(
+        &self,
+        dead_letter: &DeadLetterModel,
+        error: &str,
+    ) -> anyhow::Result<()> {
+        record_dead_letter_failure(&self.pool, dead_letter.id, error).await
+    }
+
+    /// Records that the given PR has reached `milestone`, for review latency metrics.
+    pub async fn record_pr_milestone(
+        &self,
+        repo: &GithubRepoName,
+        pr_number: PullRequestNumber,
+        milestone: PrMilestone,
+    ) -> anyhow::Result<()> {
+        record_pr_milestone(&self.pool, repo, pr_number, milestone).await
+    }
+
+    /// Returns review latency metrics of PRs merged in the last `weeks` weeks, per week.
+    pub async fn get_weekly_latencies(
+        &self,
+        repo: &GithubRepoName,
+        weeks: i32,
+    ) -> anyhow::Result<Vec<WeeklyLatencyModel>> {
+        get_weekly_latencies(&self.pool, repo, weeks).await
+    }
+
+    /// Returns statistics of the try and auto builds started in the last `days` days.
+    pub async fn get_build_stats(
+        &self,
+        repo: &GithubRepoName,
+        days: i32,
+    ) -> anyhow::Result<Vec<BuildStatsModel>> {
+        get_build_stats(&self.pool, repo, days).await
+    }
+
+    /// Records that `actor` has used a bors command, for the audit log.
+    pub async fn record_audit_command(
+        &self,
+        repo: &GithubRepoName,
+        pr_number: PullRequestNumber,
+        actor: &str,
+        command: &str,
+        text: &str,
+        outcome: &str,
+    ) -> anyhow::Result<()> {
+        record_audit_entry(
+            &self.pool,
+            Some(repo),
+            Some(pr_number),
+            Some(actor),
+            command,
+            Some(text).filter(|text| !text.is_empty()),
+            outcome,
+        )
+        .await
+    }
+
+    /// Records an action that bors has performed on its own, for the audit log.
+    pub async fn record_audit_action(
+        &self,
+        repo: &GithubRepoName,
+        pr_number: Option<PullRequestNumber>,
+        action: &str,
+        details: Option<&str>,
+    ) -> anyhow::Result<()> {
+        record_audit_entry(
+            &self.pool,
+            Some(repo),
+            pr_number,
+            None,
+            action,
+            details,
+            "success",
+        )
+        .await
+    }
+
+    /// Records whether a request to the administrative API was allowed, for the audit log.
+    /// Requests that do not concern a single repository are recorded without a repository.
+    pub async fn record_api_decision(
+        &self,
+        repo: Option<&GithubRepoName>,
+        action: &str,
+        details: &str,
+        outcome: &str,
+    ) -> anyhow::Result<()> {
+        record_audit_entry(
+            &self.pool,
+            repo,
+            None,
+            None,
+            &format!("api: {action}"),
+            Some(details),
+            outcome,
+        )
+        .await
+    }
+
+    /// Returns the last `limit` audit log entries of a repository, or of one of its PRs.
+    pub async fn get_audit_log(
+        &self,
+        repo: &GithubRepoName,
+        pr_number: Option<PullRequestNumber>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<AuditLogEntryModel>> {
+        get_audit_log(&self.pool, repo, pr_number, limit).await
+    }
+
+    /// Returns the last `limit` rejected commands of a PR.
+    pub async fn get_rejected_commands(
+        &self,
+        repo: &GithubRepoName,
+        pr_number: PullRequestNumber,
+        limit: i64,
+    ) -> anyhow::Result<Vec<AuditLogEntryModel>> {
+        get_rejected_commands(&self.pool, repo, pr_number, limit).await
+    }
+
+    pub async fn get_queue_check_runs(
+        &self,
+        repo: &GithubRepoName,
+    ) -> anyhow::Result<Vec<QueueCheckRunModel>> {
+        get_queue_check_runs(&self.pool, repo).await
+    }
+
+    pub async fn upsert_queue_check_run(
+        &self,
+        repo: &GithubRepoName,
+        pr_number: PullRequestNumber,
+        check_run_id: i64,
+        head_sha: &str,
+        position: i32,
+    ) -> anyhow::Result<()> {
+        upsert_queue_check_run(
+            &self.pool,
+            repo,
+            pr_number,
+            check_run_id,
+            head_sha,
+            position,
+        )
+        .await
+    }
+
+    pub async fn delete_queue_check_run(
+        &self,
+        check_run: &QueueCheckRunModel,
+    ) -> anyhow::Result<()> {
+        delete_queue_check_run(&self.pool, check_run.id).await
+    }
+
+    pub async fn get_emergency_stop(&self) -> anyhow::Result<Option<EmergencyStopModel>> {
+        get_emergency_stop(&self.pool).await
+    }
+
+    pub async fn set_emergency_stop(&self, reason: Option<&str>) -> anyhow::Result<()> {
+        set_emergency_stop(&self.pool, reason).await
+    }
+
+    pub async fn clear_emergency_stop(&self) -> anyhow::Result<bool> {
+        clear_emergency_stop(&self.pool).await
+    }
+
+    /// Checks that the database can be reached.
+    pub async fn check_connection(&self) -> anyhow::Result<()> {
+        check_connection(&self.pool).await
+    }
+
+    /// Tries to acquire the given coordination lock, so that the guarded work is not performed
+    /// by several bors instances at once. Returns `None` if another instance holds the lock.
+    ///
+    /// The lock is held by a dedicated connection outside of the pool, so that the guarded work
+    /// neither keeps a transaction open nor takes a connection away from the pool.
+    pub async fn try_lock(
+        &self,
+        lock: CoordinationLock,
+    ) -> anyhow::Result<Option<CoordinationGuard>> {
+        let mut connection = PgConnection::connect_with(&self.pool.connect_options()).await?;
+        if try_advisory_lock(&mut connection, lock.key()).await? {
+            Ok(Some(CoordinationGuard::new(lock, connection)))
+        } else {
+            connection.close().await?;
+            Ok(None)
+        }
+    }
+
+    pub async fn get_merge_queue_pause(
+        &self,
+        repo: &GithubRepoName,
+    ) -> anyhow::Result<Option<MergeQueuePauseModel>> {
+        get_merge_queue_pause(&self.pool, repo).await
+    }
+
+    pub async fn pause_merge_queue(
+        &self,
+        repo: &GithubRepoName,
+        paused_by: &str,
+    ) -> anyhow::Result<()> {
+        pause_merge_queue(&self.pool, repo, paused_by).await
+    }
+
+    pub async fn resume_merge_queue(&self, repo: &GithubRepoName) -> anyhow::Result<bool> {
+        resume_merge_queue(&self.pool, repo).await
+    }
+}
+
+async fn enqueue_outbox_actions(
+    tx: &mut PgConnection,
+    repo: &GithubRepoName,
+    pr_number: PullRequestNumber,
+    actions: &[OutboxAction],
+) -> anyhow::Result<Vec<OutboxModel>> {
+    let mut entries = Vec::with_capacity(actions.len());
+    for action in actions {
+        entries.push(enqueue_outbox_action(&mut *tx, repo, pr_number, action).await?);
+    }
+    Ok(entries)
 }
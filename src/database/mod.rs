@@ -5,6 +5,7 @@ use std::{
 };
 
 use crate::bors::comment::CommentTag;
+use crate::database::operations::advisory_unlock;
 use crate::{
     bors::{PullRequestStatus, RollupMode},
     github::{GithubRepoName, PullRequest, PullRequestNumber},
@@ -12,11 +13,17 @@ use crate::{
 use chrono::{DateTime, Utc};
 pub use client::PgDbClient;
 pub use octocrab::models::pulls::MergeableState as OctocrabMergeableState;
+use serde::{Deserialize, Serialize};
 use sqlx::error::BoxDynError;
-use sqlx::{Database, Postgres};
+use sqlx::{Connection, Database, PgConnection, Postgres};
 
 mod client;
 pub(crate) mod operations;
+mod schema;
+
+pub use schema::{
+    PendingMigration, SchemaStatus, expected_schema_version, get_schema_status, run_migrations,
+};
 
 type PrimaryKey = i32;
 
@@ -195,6 +202,16 @@ pub enum QueueStatus {
     NotApproved,
 }
 
+impl QueueStatus {
+    /// Does this status occupy a slot in the merge queue?
+    pub fn is_in_queue(&self) -> bool {
+        matches!(
+            self,
+            QueueStatus::Approved(..) | QueueStatus::ReadyForMerge(..) | QueueStatus::Pending(..)
+        )
+    }
+}
+
 /// Represents the approval status of a pull request.
 #[derive(Debug, Clone, PartialEq)]
 pub enum ApprovalStatus {
@@ -343,7 +360,7 @@ pub struct BuildModel {
 }
 
 /// Represents a pull request.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PullRequestModel {
     pub id: PrimaryKey,
     /// The GitHub repository this PR belongs to.
@@ -356,6 +373,8 @@ pub struct PullRequestModel {
     pub author: String,
     /// List of GitHub usernames assigned to this PR.
     pub assignees: Vec<String>,
+    /// Names of the labels of this PR.
+    pub labels: Vec<String>,
     /// The GitHub PR state: open, closed, draft, or merged.
     pub pr_status: PullRequestStatus,
     /// The target branch this PR will be merged into.
@@ -366,10 +385,45 @@ pub struct PullRequestModel {
     pub approval_status: ApprovalStatus,
     /// Temporary permissions granted to the PR author by a reviewer (try or review).
     pub delegated_permission: Option<DelegatedPermission>,
+    /// When the delegated permissions expire, if they were granted only for a limited time.
+    pub delegation_expires_at: Option<DateTime<Utc>>,
     /// Priority for merge queue ordering. Higher priority PRs are merged first.
     pub priority: Option<i32>,
     /// Rollup mode determining if this PR can be included in rollup builds.
     pub rollup: Option<RollupMode>,
+    /// Whether the commits of this PR should be squashed into a single commit when it is merged.
+    pub squash: bool,
+    /// Whether the PR was approved while it had merge conflicts. Such a PR stays approved when its
+    /// author pushes to it, so that it enters the queue once the conflicts are resolved.
+    pub waiting_on_conflicts: bool,
+    /// Whether the PR is held at the top of the merge queue with `@bors hold`. A held PR is
+    /// tested, but it is not merged until the hold is released.
+    pub held: bool,
+    /// Whether the PR is parked with `@bors park`. A parked PR stays approved, but it is excluded
+    /// from the merge queue until it is unparked.
+    pub parked: bool,
+    /// Why the PR is parked, if a reason was given to `@bors park`.
+    pub park_reason: Option<String>,
+    /// Name of the path queue of the PR, if all files modified by the PR belong to the paths of
+    /// a single path queue. Determined when the PR is opened or pushed to.
+    pub path_queue: Option<String>,
+    /// Build parameters set with `@bors env`, as `<key>=<value>` pairs sorted by key. They are
+    /// passed to the try and auto builds of the PR, and cleared when the PR is merged.
+    pub build_env: Vec<String>,
+    /// When the PR was approved, if it is approved. Re-approving an already approved PR does not
+    /// change this time.
+    pub approved_at: Option<DateTime<Utc>>,
+    /// The head SHA of the PR when it was last approved by a reviewer. Unlike the approved SHA,
+    /// it is not moved to new commits that are pushed to an approved PR.
+    pub approved_head_sha: Option<String>,
+    /// Distinct users who commented an approval of the approved commit. Unlike the approvers,
+    /// users named in `r=<user>` are not included, only the author of the comment is.
+    pub approval_authors: Vec<String>,
+    /// Branches onto which the PR should be backported with `@bors backport` once it is merged.
+    pub backports: Vec<String>,
+    /// The head SHA of the PR whose auto build was described by the merge queue in dry-run mode,
+    /// so that each head is only described once.
+    pub dry_run_head_sha: Option<String>,
     /// The (latest) try build associated with this PR, if any.
     pub try_build: Option<BuildModel>,
     /// The (latest) auto merge build associated with this PR, if any.
@@ -378,6 +432,14 @@ pub struct PullRequestModel {
 }
 
 impl PullRequestModel {
+    /// Returns the permissions delegated to the PR author, unless they have already expired.
+    pub fn active_delegation(&self) -> Option<DelegatedPermission> {
+        self.delegated_permission.filter(|_| {
+            self.delegation_expires_at
+                .is_none_or(|expires_at| expires_at > Utc::now())
+        })
+    }
+
     pub fn is_approved(&self) -> bool {
         matches!(self.approval_status, ApprovalStatus::Approved(_))
     }
@@ -389,6 +451,13 @@ impl PullRequestModel {
         }
     }
 
+    /// Returns the distinct reviewers that have approved the PR.
+    pub fn approvers(&self) -> Vec<&str> {
+        self.approver()
+            .map(|approver| approver.split(',').collect())
+            .unwrap_or_default()
+    }
+
     pub fn approved_sha(&self) -> Option<&str> {
         match &self.approval_status {
             ApprovalStatus::Approved(info) => Some(info.sha.as_str()),
@@ -462,6 +531,53 @@ pub struct WorkflowModel {
     pub created_at: DateTime<Utc>,
 }
 
+/// A try build of a pull request. A PR can have several try builds running concurrently, as long
+/// as they run different CI jobs.
+#[derive(Debug)]
+pub struct TryBuildModel {
+    pub build: BuildModel,
+    /// CI jobs selected with `try jobs=...`. Empty if the try build runs the default jobs.
+    pub jobs: Vec<String>,
+    /// The commit of the PR that was tried. This is usually the head of the PR, unless an older
+    /// commit was selected with `try commit=...`. `None` for try builds started before the commit
+    /// was recorded.
+    pub head_sha: Option<String>,
+}
+
+/// A retry of a failed build, either requested with `@bors retry` or performed automatically.
+#[derive(Debug)]
+pub struct BuildRetryModel {
+    pub id: PrimaryKey,
+    /// The PR whose build was retried.
+    pub pull_request_id: PrimaryKey,
+    /// The failed build that was retried.
+    pub build: BuildModel,
+    /// GitHub username of the user who requested the retry, or `None` if the build was retried
+    /// automatically because of a spurious failure.
+    pub retried_by: Option<String>,
+    /// Why the build was retried, e.g. the spurious failure that was encountered.
+    pub reason: Option<String>,
+    /// The approved commit of the PR when the build was retried. `None` for retries recorded
+    /// before the commit was recorded.
+    pub head_sha: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents an artifact produced by a build, e.g. an archive uploaded by a CI workflow.
+#[derive(Debug)]
+pub struct BuildArtifactModel {
+    pub id: PrimaryKey,
+    /// The build that produced this artifact.
+    pub build_id: PrimaryKey,
+    /// The name of the artifact.
+    pub name: String,
+    /// URL where the artifact can be downloaded.
+    pub url: String,
+    /// Size of the artifact in bytes, if it is known.
+    pub size: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
 /// Represents the state of a repository's tree.
 #[derive(Debug, PartialEq, Clone)]
 pub enum TreeState {
@@ -473,6 +589,10 @@ pub enum TreeState {
         priority: u32,
         /// URL to a PR comment that closed the tree.
         source: String,
+        /// Why the tree was closed.
+        reason: Option<String>,
+        /// When the tree should be reopened automatically.
+        until: Option<DateTime<Utc>>,
     },
 }
 
@@ -494,23 +614,51 @@ impl TreeState {
             TreeState::Open => None,
         }
     }
+
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            TreeState::Closed { reason, .. } => reason.as_deref(),
+            TreeState::Open => None,
+        }
+    }
+
+    pub fn closed_until(&self) -> Option<DateTime<Utc>> {
+        match self {
+            TreeState::Closed { until, .. } => *until,
+            TreeState::Open => None,
+        }
+    }
+
+    /// Returns true if the tree was closed only for a limited time, which has already elapsed.
+    pub fn is_expired(&self) -> bool {
+        self.closed_until().is_some_and(|until| until <= Utc::now())
+    }
 }
 
+type TreeStateColumns = (
+    Option<i32>,
+    Option<String>,
+    Option<String>,
+    Option<DateTime<Utc>>,
+);
+
 impl sqlx::Type<sqlx::Postgres> for TreeState {
     fn type_info() -> sqlx::postgres::PgTypeInfo {
-        <(Option<i32>, Option<String>) as sqlx::Type<sqlx::Postgres>>::type_info()
+        <TreeStateColumns as sqlx::Type<sqlx::Postgres>>::type_info()
     }
 }
 
 impl sqlx::Decode<'_, sqlx::Postgres> for TreeState {
     fn decode(value: <Postgres as Database>::ValueRef<'_>) -> Result<Self, BoxDynError> {
-        let data = <(Option<i32>, Option<String>) as sqlx::Decode<sqlx::Postgres>>::decode(value)?;
+        let data = <TreeStateColumns as sqlx::Decode<sqlx::Postgres>>::decode(value)?;
         match data {
-            (Some(priority), Some(source)) => Ok(TreeState::Closed {
+            (Some(priority), Some(source), reason, until) => Ok(TreeState::Closed {
                 priority: priority as u32,
                 source,
+                reason,
+                until,
             }),
-            (None, None) => Ok(TreeState::Open),
+            (None, None, _, _) => Ok(TreeState::Open),
             _ => Err(
                 "Cannot deserialize TreeState, priority is non-NULL, but source is NULL"
                     .to_string()
@@ -529,6 +677,7 @@ pub struct UpsertPullRequestParams {
     pub base_branch: String,
     pub mergeable_state: MergeableState,
     pub pr_status: PullRequestStatus,
+    pub labels: Vec<String>,
 }
 
 impl From<PullRequest> for UpsertPullRequestParams {
@@ -541,6 +690,7 @@ impl From<PullRequest> for UpsertPullRequestParams {
             base_branch: pr.base.name,
             mergeable_state: pr.mergeable_state.into(),
             pr_status: pr.status,
+            labels: pr.labels,
         }
     }
 }
@@ -553,6 +703,137 @@ pub struct RepoModel {
     /// State of the repository tree (open or closed with priority threshold).
     pub tree_state: TreeState,
     pub created_at: DateTime<Utc>,
+    /// When bors stopped operating in the repository, if it did.
+    pub deactivated_at: Option<DateTime<Utc>>,
+    pub deactivation_reason: Option<DeactivationReason>,
+}
+
+impl RepoModel {
+    /// Returns true if bors should operate in the repository.
+    pub fn is_active(&self) -> bool {
+        self.deactivated_at.is_none()
+    }
+}
+
+/// Why did bors stop operating in a repository?
+#[derive(Debug, Clone, Copy, PartialEq, sqlx::Type)]
+#[sqlx(type_name = "TEXT")]
+#[sqlx(rename_all = "lowercase")]
+pub enum DeactivationReason {
+    /// The repository was archived on GitHub.
+    Archived,
+    /// The GitHub App was uninstalled from the repository, or its installation was suspended.
+    Uninstalled,
+}
+
+impl Display for DeactivationReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DeactivationReason::Archived => write!(f, "the repository was archived"),
+            DeactivationReason::Uninstalled => write!(f, "the bors app was uninstalled"),
+        }
+    }
+}
+
+/// Represents the check run that shows the position of an approved PR in the merge queue.
+pub struct QueueCheckRunModel {
+    pub id: PrimaryKey,
+    /// The GitHub repository of the PR.
+    pub repository: GithubRepoName,
+    pub pr_number: PullRequestNumber,
+    pub check_run_id: i64,
+    /// The commit to which the check run is attached.
+    pub head_sha: String,
+    /// How many PRs were ahead of the PR in the queue when the check run was last updated.
+    pub position: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents an instance-wide emergency stop, which pauses the merge queues of all repositories
+/// until it is cleared.
+pub struct EmergencyStopModel {
+    /// Why the merge queues were stopped.
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents a paused merge queue of a repository. While the queue is paused, no new auto builds
+/// are started, but auto builds that are already running are still merged.
+pub struct MergeQueuePauseModel {
+    /// The GitHub repository whose merge queue is paused.
+    pub repository: GithubRepoName,
+    /// GitHub username of the user who paused the queue.
+    pub paused_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Work that must only be performed by a single bors instance at a time, e.g. while a new
+/// instance is being deployed and the old one is still running.
+#[derive(Clone, Copy, Debug)]
+pub enum CoordinationLock {
+    /// Processing of the merge queues.
+    MergeQueue,
+    /// Retrying outbox actions whose delivery has failed.
+    OutboxDelivery,
+    /// Replaying webhooks from the dead-letter queue.
+    DeadLetterReplay,
+}
+
+impl CoordinationLock {
+    /// Key of the Postgres advisory lock.
+    pub(crate) fn key(self) -> i64 {
+        match self {
+            CoordinationLock::MergeQueue => 1,
+            CoordinationLock::OutboxDelivery => 2,
+            CoordinationLock::DeadLetterReplay => 3,
+        }
+    }
+}
+
+/// A held [`CoordinationLock`]. The lock is released by [`CoordinationGuard::release`], when the
+/// guard is dropped, or when the database connection of the instance holding it is lost.
+pub struct CoordinationGuard {
+    lock: CoordinationLock,
+    connection: Option<PgConnection>,
+}
+
+impl CoordinationGuard {
+    pub(crate) fn new(lock: CoordinationLock, connection: PgConnection) -> Self {
+        Self {
+            lock,
+            connection: Some(connection),
+        }
+    }
+
+    /// Releases the lock right away, so that it can be acquired again as soon as this returns.
+    pub async fn release(mut self) {
+        let Some(mut connection) = self.connection.take() else {
+            return;
+        };
+        // Closing the connection releases the lock as well, but only once Postgres notices it
+        if let Err(error) = advisory_unlock(&mut connection, self.lock.key()).await {
+            tracing::warn!(
+                "Cannot release coordination lock {:?}: {error:?}",
+                self.lock
+            );
+        }
+        if let Err(error) = connection.close().await {
+            tracing::warn!("Cannot close the connection of a coordination lock: {error:?}");
+        }
+    }
+}
+
+impl Drop for CoordinationGuard {
+    fn drop(&mut self) {
+        // Dropping the connection closes it, which also releases the lock
+        if let Some(connection) = self.connection.take()
+            && let Ok(runtime) = tokio::runtime::Handle::try_current()
+        {
+            runtime.spawn(async move {
+                let _ = connection.close().await;
+            });
+        }
+    }
 }
 
 /// Represents a tagged comment made by the bors GitHub app that can be later hidden.
@@ -569,6 +850,216 @@ pub struct CommentModel {
     pub created_at: DateTime<Utc>,
 }
 
+/// Represents a request of a user to be notified when a PR gets close to the front of the
+/// merge queue.
+pub struct QueueNotificationModel {
+    pub id: PrimaryKey,
+    /// The GitHub repository of the PR.
+    pub repository: GithubRepoName,
+    /// The number of the PR whose queue position should be watched.
+    pub pr_number: PullRequestNumber,
+    /// GitHub username of the user who should be notified.
+    pub username: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A GitHub side effect (e.g. posting a comment) that bors wants to perform.
+/// Side effects are first recorded in the outbox, in the same transaction as the state change
+/// that caused them, and then performed, with retries, until they succeed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutboxAction {
+    /// Post a comment with the given (already rendered) text.
+    /// If the comment has a tag, it is recorded once it is posted, so that it can be hidden later.
+    Comment {
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tag: Option<CommentTag>,
+    },
+    /// Add labels to the PR.
+    AddLabels { labels: Vec<String> },
+    /// Remove labels from the PR.
+    RemoveLabels { labels: Vec<String> },
+    /// Mark a check run as completed.
+    CompleteCheckRun { check_run_id: u64, success: bool },
+}
+
+impl sqlx::Type<sqlx::Postgres> for OutboxAction {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        <String as sqlx::Type<sqlx::Postgres>>::type_info()
+    }
+}
+
+impl sqlx::Decode<'_, sqlx::Postgres> for OutboxAction {
+    fn decode(value: <Postgres as Database>::ValueRef<'_>) -> Result<Self, BoxDynError> {
+        let payload = <&str as sqlx::Decode<sqlx::Postgres>>::decode(value)?;
+        Ok(serde_json::from_str(payload)?)
+    }
+}
+
+/// Represents a GitHub side effect recorded in the outbox, which has not been performed yet.
+pub struct OutboxModel {
+    pub id: PrimaryKey,
+    /// The GitHub repository of the PR.
+    pub repository: GithubRepoName,
+    /// The number of the PR that the side effect concerns.
+    pub pr_number: PullRequestNumber,
+    /// The side effect that should be performed.
+    pub action: OutboxAction,
+    /// How many attempts to perform the side effect have failed so far.
+    pub attempts: i32,
+    /// When should the next attempt be performed.
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Notification slots that were reserved in the notification windows of a repository.
+pub struct NotificationWindow {
+    /// Start of the first window in which slots were reserved.
+    pub start: DateTime<Utc>,
+    /// How many slots of the first window were already reserved before.
+    pub reserved: i32,
+    /// Time at which the slots were reserved.
+    pub now: DateTime<Utc>,
+}
+
+impl NotificationWindow {
+    /// Returns after how many seconds the notification with the given index (among the reserved
+    /// slots) can be sent, or zero if it can be sent right away.
+    pub fn delay_secs(&self, index: usize, per_minute: usize) -> i32 {
+        let window = (self.reserved as usize + index) / per_minute;
+        if window == 0 {
+            return 0;
+        }
+        let window_start = self.start + chrono::Duration::minutes(window as i64);
+        let delay_ms = (window_start - self.now).num_milliseconds().max(1);
+        ((delay_ms + 999) / 1000) as i32
+    }
+}
+
+/// Represents a webhook whose handling has failed. The raw webhook is kept in the dead-letter
+/// queue, so that it can be replayed once the cause of the failure is fixed.
+pub struct DeadLetterModel {
+    pub id: PrimaryKey,
+    /// The GitHub repository that the webhook concerns.
+    pub repository: GithubRepoName,
+    /// Type of the webhook event (the value of the `X-GitHub-Event` header).
+    pub event_type: String,
+    /// The raw body of the webhook.
+    pub payload: String,
+    /// The error of the last failed attempt to handle the webhook.
+    pub error: String,
+    /// How many attempts to handle the webhook have failed so far.
+    pub attempts: i32,
+    /// Whether an administrator has asked for the webhook to be replayed.
+    pub replay_requested: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A point in the lifecycle of a pull request, recorded in its timeline to derive review latency
+/// metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrMilestone {
+    /// The PR was opened.
+    Opened,
+    /// A bors command was used on the PR. Only the first command is recorded.
+    FirstCommand,
+    /// The PR was approved. The first approval is recorded as the approval time, the last one
+    /// as the time when the PR entered the merge queue.
+    Approved,
+    /// An auto build of the PR was started. Only the last build is recorded.
+    BuildStarted,
+    /// The PR was merged.
+    Merged,
+}
+
+impl PrMilestone {
+    fn as_str(self) -> &'static str {
+        match self {
+            PrMilestone::Opened => "opened",
+            PrMilestone::FirstCommand => "first_command",
+            PrMilestone::Approved => "approved",
+            PrMilestone::BuildStarted => "build_started",
+            PrMilestone::Merged => "merged",
+        }
+    }
+}
+
+/// Review latency metrics of the PRs of a repository that were merged in a given week.
+/// Latencies are medians, in seconds, over the PRs for which both milestones are known.
+#[derive(Debug, Serialize)]
+pub struct WeeklyLatencyModel {
+    /// Start of the week (Monday).
+    pub week: DateTime<Utc>,
+    /// Number of PRs merged in the week.
+    pub merged: i64,
+    /// From opening the PR to its first bors command.
+    pub open_to_first_command: Option<f64>,
+    /// From opening the PR to its approval.
+    pub open_to_approval: Option<f64>,
+    /// From entering the merge queue to the start of the auto build.
+    pub queue_to_build_start: Option<f64>,
+    /// From the start of the auto build to the merge.
+    pub build_start_to_merge: Option<f64>,
+    /// From approval to the merge.
+    pub approval_to_merge: Option<f64>,
+}
+
+/// Points in time in the lifecycle of a build.
+#[derive(Debug)]
+pub struct BuildTimingModel {
+    /// When the PR of an auto build entered the merge queue. Unknown for try builds.
+    pub queued_at: Option<DateTime<Utc>>,
+    pub started_at: DateTime<Utc>,
+    /// When the build stopped being pending.
+    pub finished_at: Option<DateTime<Utc>>,
+    /// When the merge commit of an auto build was pushed to the base branch.
+    pub merged_at: Option<DateTime<Utc>>,
+}
+
+/// Statistics of the builds of one kind (`auto` or `try`) of a repository.
+/// Durations are medians, in seconds, over the builds for which both points in time are known.
+#[derive(Debug, Serialize)]
+pub struct BuildStatsModel {
+    pub kind: String,
+    /// Number of started builds.
+    pub builds: i64,
+    pub succeeded: i64,
+    /// Number of builds that failed or timed out. Cancelled builds are not counted as failures.
+    pub failed: i64,
+    /// Number of auto builds that were merged.
+    pub merged: i64,
+    /// Number of times that a failed build was retried.
+    pub retries: i64,
+    /// Ratio of failed builds to builds that succeeded or failed.
+    pub failure_rate: Option<f64>,
+    pub retries_per_merge: Option<f64>,
+    /// From entering the merge queue to the start of the build.
+    pub queue_wait: Option<f64>,
+    /// From the start of the build to its success or failure.
+    pub ci_duration: Option<f64>,
+    /// From the success of the build to the merge.
+    pub finish_to_merge: Option<f64>,
+}
+
+/// An entry of the audit log, which records bors commands and the actions that bors performed
+/// on its own.
+pub struct AuditLogEntryModel {
+    pub id: PrimaryKey,
+    pub repository: GithubRepoName,
+    /// The PR that the entry concerns, if any.
+    pub pr_number: Option<PullRequestNumber>,
+    /// GitHub username of the author of the command, or `None` for actions performed by bors.
+    pub actor: Option<String>,
+    /// Name of the command (e.g. `approve`) or of the action (e.g. `auto_build_started`).
+    pub action: String,
+    /// The raw text of the command, or additional details about the action.
+    pub details: Option<String>,
+    /// How did the command or action end up (e.g. `success` or `failure: <error>`).
+    pub outcome: String,
+    pub created_at: DateTime<Utc>,
+}
+
 impl sqlx::Type<sqlx::Postgres> for CommentTag {
     fn type_info() -> sqlx::postgres::PgTypeInfo {
         <String as sqlx::Type<sqlx::Postgres>>::type_info()
@@ -582,6 +1073,9 @@ impl sqlx::Encode<'_, sqlx::Postgres> for CommentTag {
     ) -> Result<sqlx::encode::IsNull, BoxDynError> {
         let tag = match self {
             CommentTag::TryBuildStarted => "TryBuildStarted",
+            CommentTag::TryBuildCompleted => "TryBuildCompleted",
+            CommentTag::AutoBuildStarted => "AutoBuildStarted",
+            CommentTag::AutoBuildFailed => "AutoBuildFailed",
         };
         <&str as sqlx::Encode<sqlx::Postgres>>::encode(tag, buf)
     }
@@ -591,6 +1085,9 @@ impl sqlx::Decode<'_, sqlx::Postgres> for CommentTag {
     fn decode(value: sqlx::postgres::PgValueRef<'_>) -> Result<Self, BoxDynError> {
         match <&str as sqlx::Decode<sqlx::Postgres>>::decode(value)? {
             "TryBuildStarted" => Ok(CommentTag::TryBuildStarted),
+            "TryBuildCompleted" => Ok(CommentTag::TryBuildCompleted),
+            "AutoBuildStarted" => Ok(CommentTag::AutoBuildStarted),
+            "AutoBuildFailed" => Ok(CommentTag::AutoBuildFailed),
             tag => Err(format!("Unknown comment tag: {tag}").into()),
         }
     }
@@ -16,14 +16,31 @@ use crate::utils::timing::measure_db_query;
 use super::ApprovalInfo;
 use super::ApprovalStatus;
 use super::Assignees;
+use super::AuditLogEntryModel;
+use super::BuildArtifactModel;
 use super::BuildModel;
+use super::BuildRetryModel;
+use super::BuildStatsModel;
+use super::BuildTimingModel;
 use super::CommentModel;
+use super::DeactivationReason;
+use super::DeadLetterModel;
 use super::DelegatedPermission;
+use super::EmergencyStopModel;
+use super::MergeQueuePauseModel;
 use super::MergeableState;
+use super::NotificationWindow;
+use super::OutboxAction;
+use super::OutboxModel;
+use super::PrMilestone;
 use super::PullRequestModel;
+use super::QueueCheckRunModel;
+use super::QueueNotificationModel;
 use super::RunId;
 use super::TreeState;
+use super::TryBuildModel;
 use super::UpsertPullRequestParams;
+use super::WeeklyLatencyModel;
 use super::WorkflowStatus;
 use super::WorkflowType;
 use futures::TryStreamExt;
@@ -44,6 +61,7 @@ pub(crate) async fn get_pull_request(
         pr.title,
         pr.author,
         pr.assignees as "assignees: Assignees",
+        pr.labels,
         (
             pr.approved_by,
             pr.approved_sha
@@ -51,7 +69,20 @@ pub(crate) async fn get_pull_request(
         pr.status as "pr_status: PullRequestStatus",
         pr.priority,
         pr.rollup as "rollup: RollupMode",
+        pr.squash,
+        pr.waiting_on_conflicts,
+        pr.held,
+        pr.parked,
+        pr.park_reason,
+        pr.path_queue,
+        pr.build_env,
+        pr.approved_at as "approved_at: DateTime<Utc>",
+        pr.approved_head_sha,
+        pr.approval_authors,
+        pr.backports,
+        pr.dry_run_head_sha,
         pr.delegated_permission as "delegated_permission: DelegatedPermission",
+        pr.delegation_expires_at as "delegation_expires_at?: DateTime<Utc>",
         pr.base_branch,
         pr.mergeable_state as "mergeable_state: MergeableState",
         pr.created_at as "created_at: DateTime<Utc>",
@@ -114,7 +145,13 @@ pub(crate) async fn set_pr_status(
 ) -> anyhow::Result<()> {
     measure_db_query("set_pr_status", || async {
         sqlx::query!(
-            "UPDATE pull_request SET status = $3 WHERE repository = $1 AND number = $2",
+            r#"
+            UPDATE pull_request
+            SET
+                status = $3,
+                build_env = CASE WHEN $3 = 'merged' THEN '{}' ELSE build_env END
+            WHERE repository = $1 AND number = $2
+            "#,
             repo as &GithubRepoName,
             pr_number.0 as i32,
             pr_status as PullRequestStatus,
@@ -137,8 +174,8 @@ pub(crate) async fn upsert_pull_request(
             PullRequestModel,
             r#"
             WITH upserted_pr AS (
-                INSERT INTO pull_request (repository, number, title, author, assignees, base_branch, mergeable_state, status)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                INSERT INTO pull_request (repository, number, title, author, assignees, base_branch, mergeable_state, status, labels)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
                 ON CONFLICT (repository, number)
                 DO UPDATE SET
                     title = $3,
@@ -146,7 +183,12 @@ pub(crate) async fn upsert_pull_request(
                     assignees = $5,
                     base_branch = $6,
                     mergeable_state = $7,
-                    status = $8
+                    -- A PR that was squash-merged by bors stays merged when it is closed
+                    status = CASE
+                        WHEN pull_request.status = 'merged' AND $8 = 'closed' THEN pull_request.status
+                        ELSE $8
+                    END,
+                    labels = $9
                 RETURNING *
             )
             SELECT
@@ -156,6 +198,7 @@ pub(crate) async fn upsert_pull_request(
                 pr.title,
                 pr.author,
                 pr.assignees as "assignees: Assignees",
+                pr.labels,
                 (
                     pr.approved_by,
                     pr.approved_sha
@@ -163,7 +206,20 @@ pub(crate) async fn upsert_pull_request(
                 pr.status as "pr_status: PullRequestStatus",
                 pr.priority,
                 pr.rollup as "rollup: RollupMode",
+                pr.squash,
+                pr.waiting_on_conflicts,
+                pr.held,
+                pr.parked,
+                pr.park_reason,
+                pr.path_queue,
+                pr.build_env,
+                pr.approved_at as "approved_at: DateTime<Utc>",
+                pr.approved_head_sha,
+                pr.approval_authors,
+                pr.backports,
+                pr.dry_run_head_sha,
                 pr.delegated_permission as "delegated_permission: DelegatedPermission",
+                pr.delegation_expires_at as "delegation_expires_at?: DateTime<Utc>",
                 pr.base_branch,
                 pr.mergeable_state as "mergeable_state: MergeableState",
                 pr.created_at as "created_at: DateTime<Utc>",
@@ -181,6 +237,7 @@ pub(crate) async fn upsert_pull_request(
             &params.base_branch,
             params.mergeable_state as _,
             params.pr_status as _,
+            &params.labels,
         )
         .fetch_one(executor)
         .await?;
@@ -204,6 +261,7 @@ pub(crate) async fn get_nonclosed_pull_requests(
                 pr.title,
                 pr.author,
                 pr.assignees as "assignees: Assignees",
+                pr.labels,
                 (
                     pr.approved_by,
                     pr.approved_sha
@@ -211,7 +269,20 @@ pub(crate) async fn get_nonclosed_pull_requests(
                 pr.status as "pr_status: PullRequestStatus",
                 pr.priority,
                 pr.rollup as "rollup: RollupMode",
+                pr.squash,
+                pr.waiting_on_conflicts,
+                pr.held,
+                pr.parked,
+                pr.park_reason,
+                pr.path_queue,
+                pr.build_env,
+                pr.approved_at as "approved_at: DateTime<Utc>",
+                pr.approved_head_sha,
+                pr.approval_authors,
+                pr.backports,
+                pr.dry_run_head_sha,
                 pr.delegated_permission as "delegated_permission: DelegatedPermission",
+                pr.delegation_expires_at as "delegation_expires_at?: DateTime<Utc>",
                 pr.base_branch,
                 pr.mergeable_state as "mergeable_state: MergeableState",
                 pr.created_at as "created_at: DateTime<Utc>",
@@ -268,6 +339,7 @@ pub(crate) async fn get_prs_with_unknown_mergeability_state(
                 pr.title,
                 pr.author,
                 pr.assignees as "assignees: Assignees",
+                pr.labels,
                 (
                     pr.approved_by,
                     pr.approved_sha
@@ -275,7 +347,20 @@ pub(crate) async fn get_prs_with_unknown_mergeability_state(
                 pr.status as "pr_status: PullRequestStatus",
                 pr.priority,
                 pr.rollup as "rollup: RollupMode",
+                pr.squash,
+                pr.waiting_on_conflicts,
+                pr.held,
+                pr.parked,
+                pr.park_reason,
+                pr.path_queue,
+                pr.build_env,
+                pr.approved_at as "approved_at: DateTime<Utc>",
+                pr.approved_head_sha,
+                pr.approval_authors,
+                pr.backports,
+                pr.dry_run_head_sha,
                 pr.delegated_permission as "delegated_permission: DelegatedPermission",
+                pr.delegation_expires_at as "delegation_expires_at?: DateTime<Utc>",
                 pr.base_branch,
                 pr.mergeable_state as "mergeable_state: MergeableState",
                 pr.created_at as "created_at: DateTime<Utc>",
@@ -323,6 +408,7 @@ pub(crate) async fn update_mergeable_states_by_base_branch(
                 pr.title,
                 pr.author,
                 pr.assignees as "assignees: Assignees",
+                pr.labels,
                 (
                     pr.approved_by,
                     pr.approved_sha
@@ -330,7 +416,20 @@ pub(crate) async fn update_mergeable_states_by_base_branch(
                 pr.status as "pr_status: PullRequestStatus",
                 pr.priority,
                 pr.rollup as "rollup: RollupMode",
+                pr.squash,
+                pr.waiting_on_conflicts,
+                pr.held,
+                pr.parked,
+                pr.park_reason,
+                pr.path_queue,
+                pr.build_env,
+                pr.approved_at as "approved_at: DateTime<Utc>",
+                pr.approved_head_sha,
+                pr.approval_authors,
+                pr.backports,
+                pr.dry_run_head_sha,
                 pr.delegated_permission as "delegated_permission: DelegatedPermission",
+                pr.delegation_expires_at as "delegation_expires_at?: DateTime<Utc>",
                 pr.base_branch,
                 pr.mergeable_state as "mergeable_state: MergeableState",
                 pr.created_at as "created_at: DateTime<Utc>",
@@ -356,26 +455,99 @@ pub(crate) async fn approve_pull_request(
     executor: impl PgExecutor<'_>,
     pr_id: i32,
     approval_info: ApprovalInfo,
+    author: &str,
     priority: Option<u32>,
     rollup: Option<RollupMode>,
-) -> anyhow::Result<()> {
+    squash: Option<bool>,
+) -> anyhow::Result<(String, Vec<String>)> {
     let priority_i32 = priority.map(|p| p as i32);
 
     measure_db_query("approve_pull_request", || async {
-        sqlx::query!(
+        // If the same commit is already approved, the approvers (and the users who commented the
+        // approvals) are merged, so that concurrent approvals of several reviewers are all kept.
+        // The update takes a row lock, so the approvers of a concurrent approval are always seen.
+        let record = sqlx::query!(
             r#"
 UPDATE pull_request
-SET approved_by = $1,
+SET approved_by = CASE
+        WHEN approved_by IS NOT NULL AND approved_sha = $2 THEN (
+            SELECT string_agg(approver, ',' ORDER BY position)
+            FROM (
+                SELECT approver, MIN(position) AS position
+                FROM unnest(string_to_array(approved_by || ',' || $1, ','))
+                    WITH ORDINALITY AS approvers(approver, position)
+                GROUP BY approver
+            ) merged
+        )
+        ELSE $1
+    END,
+    approval_authors = CASE
+        WHEN approved_by IS NOT NULL AND approved_sha = $2 THEN (
+            CASE
+                WHEN $7 = ANY(approval_authors) THEN approval_authors
+                ELSE array_append(approval_authors, $7)
+            END
+        )
+        ELSE ARRAY[$7]
+    END,
     approved_sha = $2,
+    approved_head_sha = $2,
+    approved_at = COALESCE(approved_at, NOW()),
     priority = COALESCE($3, priority),
-    rollup = COALESCE($4, rollup)
-WHERE id = $5
+    rollup = COALESCE($4, rollup),
+    squash = COALESCE($5, squash)
+WHERE id = $6
+RETURNING approved_by as "approved_by!", approval_authors
 "#,
             approval_info.approver,
             approval_info.sha,
             priority_i32,
             rollup as Option<RollupMode>,
+            squash,
             pr_id,
+            author,
+        )
+        .fetch_one(executor)
+        .await?;
+        Ok((record.approved_by, record.approval_authors))
+    })
+    .await
+}
+
+pub(crate) async fn move_pull_request_approval(
+    executor: impl PgExecutor<'_>,
+    pr_id: i32,
+    sha: &str,
+) -> anyhow::Result<()> {
+    measure_db_query("move_pull_request_approval", || async {
+        sqlx::query!(
+            r#"
+                UPDATE pull_request
+                SET approved_sha = $1
+                WHERE id = $2 AND approved_by IS NOT NULL"#,
+            sha,
+            pr_id
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn move_pull_request_approval_and_head(
+    executor: impl PgExecutor<'_>,
+    pr_id: i32,
+    sha: &str,
+) -> anyhow::Result<()> {
+    measure_db_query("move_pull_request_approval_and_head", || async {
+        sqlx::query!(
+            r#"
+                UPDATE pull_request
+                SET approved_sha = $1, approved_head_sha = $1
+                WHERE id = $2 AND approved_by IS NOT NULL"#,
+            sha,
+            pr_id
         )
         .execute(executor)
         .await?;
@@ -393,8 +565,12 @@ pub(crate) async fn unapprove_pull_request(
             r#"
                 UPDATE pull_request
                 SET approved_by = NULL,
+                    approval_authors = '{}',
                     approved_sha = NULL,
-                    auto_build_id = NULL
+                    approved_head_sha = NULL,
+                    approved_at = NULL,
+                    auto_build_id = NULL,
+                    waiting_on_conflicts = FALSE
                 WHERE id = $1"#,
             pr_id
         )
@@ -409,11 +585,13 @@ pub(crate) async fn delegate_pull_request(
     executor: impl PgExecutor<'_>,
     pr_id: i32,
     delegated_permission: DelegatedPermission,
+    expires_at: Option<DateTime<Utc>>,
 ) -> anyhow::Result<()> {
     measure_db_query("delegate_pull_request", || async {
         sqlx::query!(
-            "UPDATE pull_request SET delegated_permission = $1 WHERE id = $2",
+            "UPDATE pull_request SET delegated_permission = $1, delegation_expires_at = $2 WHERE id = $3",
             delegated_permission as _,
+            expires_at,
             pr_id
         )
         .execute(executor)
@@ -429,7 +607,7 @@ pub(crate) async fn undelegate_pull_request(
 ) -> anyhow::Result<()> {
     measure_db_query("undelegate_pull_request", || async {
         sqlx::query!(
-            "UPDATE pull_request SET delegated_permission = NULL WHERE id = $1",
+            "UPDATE pull_request SET delegated_permission = NULL, delegation_expires_at = NULL WHERE id = $1",
             pr_id
         )
         .execute(executor)
@@ -454,23 +632,39 @@ SELECT
     pr.title,
     pr.author,
     pr.assignees as "assignees: Assignees",
+    pr.labels,
     (
         pr.approved_by,
         pr.approved_sha
     ) AS "approval_status!: ApprovalStatus",
     pr.status as "pr_status: PullRequestStatus",
     pr.delegated_permission as "delegated_permission: DelegatedPermission",
+    pr.delegation_expires_at as "delegation_expires_at?: DateTime<Utc>",
     pr.priority,
     pr.base_branch,
     pr.mergeable_state as "mergeable_state: MergeableState",
     pr.rollup as "rollup: RollupMode",
+    pr.squash,
+    pr.waiting_on_conflicts,
+    pr.held,
+    pr.parked,
+    pr.park_reason,
+    pr.path_queue,
+    pr.build_env,
+    pr.approved_at as "approved_at: DateTime<Utc>",
+    pr.approved_head_sha,
+    pr.approval_authors,
+    pr.backports,
+    pr.dry_run_head_sha,
     pr.created_at as "created_at: DateTime<Utc>",
     try_build AS "try_build: BuildModel",
     auto_build AS "auto_build: BuildModel"
 FROM pull_request as pr
 LEFT JOIN build AS try_build ON pr.try_build_id = try_build.id
 LEFT JOIN build AS auto_build ON pr.auto_build_id = auto_build.id
-WHERE try_build.id = $1 OR auto_build.id = $1
+WHERE try_build.id = $1
+    OR auto_build.id = $1
+    OR pr.id = (SELECT pull_request_id FROM pr_try_build WHERE build_id = $1)
 "#,
             build_id
         )
@@ -518,29 +712,75 @@ pub(crate) async fn update_pr_auto_build_id(
     .await
 }
 
+/// Reserves a primary key for a build that will be created later.
+/// This allows using the ID of the build before it is created (e.g. in a merge commit message).
+pub(crate) async fn reserve_build_id(executor: impl PgExecutor<'_>) -> anyhow::Result<i32> {
+    measure_db_query("reserve_build_id", || async {
+        let build_id = sqlx::query_scalar!(
+            r#"SELECT nextval(pg_get_serial_sequence('build', 'id'))::INT AS "id!""#
+        )
+        .fetch_one(executor)
+        .await?;
+        Ok(build_id)
+    })
+    .await
+}
+
 pub(crate) async fn create_build(
     executor: impl PgExecutor<'_>,
+    build_id: i32,
     repo: &GithubRepoName,
     branch: &str,
     commit_sha: &CommitSha,
     parent: &CommitSha,
-) -> anyhow::Result<i32> {
+    queued_at: Option<DateTime<Utc>>,
+) -> anyhow::Result<()> {
     measure_db_query("create_build", || async {
-        let build_id = sqlx::query_scalar!(
+        sqlx::query!(
             r#"
-INSERT INTO build (repository, branch, commit_sha, parent, status)
-VALUES ($1, $2, $3, $4, $5)
-RETURNING id
+INSERT INTO build (id, repository, branch, commit_sha, parent, status, queued_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7)
 "#,
+            build_id,
             repo as &GithubRepoName,
             branch,
             commit_sha.0,
             parent.0,
-            BuildStatus::Pending as BuildStatus
+            BuildStatus::Pending as BuildStatus,
+            queued_at
         )
-        .fetch_one(executor)
+        .execute(executor)
         .await?;
-        Ok(build_id)
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn get_build(
+    executor: impl PgExecutor<'_>,
+    build_id: i32,
+) -> anyhow::Result<Option<BuildModel>> {
+    measure_db_query("get_build", || async {
+        let build = sqlx::query_as!(
+            BuildModel,
+            r#"
+SELECT
+    id,
+    repository as "repository: GithubRepoName",
+    branch,
+    commit_sha,
+    status as "status: BuildStatus",
+    parent,
+    created_at as "created_at: DateTime<Utc>",
+    check_run_id
+FROM build
+WHERE id = $1
+"#,
+            build_id
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(build)
     })
     .await
 }
@@ -611,14 +851,145 @@ WHERE repository = $1
     .await
 }
 
+/// Returns at most `limit` builds of the repository, newest first.
+/// Only builds with an ID lower than `before_id` are returned, if it is set.
+pub(crate) async fn get_builds(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    status: Option<BuildStatus>,
+    before_id: Option<i32>,
+    limit: i64,
+) -> anyhow::Result<Vec<BuildModel>> {
+    measure_db_query("get_builds", || async {
+        let builds = sqlx::query_as!(
+            BuildModel,
+            r#"
+SELECT
+    id,
+    repository as "repository: GithubRepoName",
+    branch,
+    commit_sha,
+    status as "status: BuildStatus",
+    parent,
+    created_at as "created_at: DateTime<Utc>",
+    check_run_id
+FROM build
+WHERE repository = $1
+    AND ($2::TEXT IS NULL OR status = $2)
+    AND ($3::INT IS NULL OR id < $3)
+ORDER BY id DESC
+LIMIT $4
+"#,
+            repo as &GithubRepoName,
+            status as Option<BuildStatus>,
+            before_id,
+            limit
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(builds)
+    })
+    .await
+}
+
+pub(crate) async fn create_pr_try_build(
+    executor: impl PgExecutor<'_>,
+    pr_id: i32,
+    build_id: i32,
+    jobs: &[String],
+    head_sha: &CommitSha,
+) -> anyhow::Result<()> {
+    measure_db_query("create_pr_try_build", || async {
+        sqlx::query!(
+            r#"
+INSERT INTO pr_try_build (build_id, pull_request_id, jobs, head_sha)
+VALUES ($1, $2, $3, $4)
+"#,
+            build_id,
+            pr_id,
+            jobs,
+            head_sha.0
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Returns the jobs selected for the given try build.
+pub(crate) async fn get_try_build_jobs(
+    executor: impl PgExecutor<'_>,
+    build_id: i32,
+) -> anyhow::Result<Vec<String>> {
+    measure_db_query("get_try_build_jobs", || async {
+        let jobs = sqlx::query_scalar!(
+            "SELECT jobs FROM pr_try_build WHERE build_id = $1",
+            build_id
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(jobs.unwrap_or_default())
+    })
+    .await
+}
+
+/// Returns the pending try builds of a PR, ordered from the oldest one.
+pub(crate) async fn get_pending_try_builds(
+    executor: impl PgExecutor<'_>,
+    pr_id: i32,
+) -> anyhow::Result<Vec<TryBuildModel>> {
+    measure_db_query("get_pending_try_builds", || async {
+        let builds = sqlx::query_as!(
+            TryBuildModel,
+            r#"
+SELECT
+    (
+        build.id,
+        build.repository,
+        build.branch,
+        build.commit_sha,
+        build.status,
+        build.parent,
+        build.created_at,
+        build.check_run_id
+    ) AS "build!: BuildModel",
+    pr_try_build.jobs,
+    pr_try_build.head_sha
+FROM pr_try_build
+    JOIN build ON pr_try_build.build_id = build.id
+WHERE pr_try_build.pull_request_id = $1
+    AND build.status = $2
+ORDER BY build.id
+"#,
+            pr_id,
+            BuildStatus::Pending as BuildStatus
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(builds)
+    })
+    .await
+}
+
 pub(crate) async fn update_build_status(
     executor: impl PgExecutor<'_>,
     build_id: i32,
     status: BuildStatus,
 ) -> anyhow::Result<()> {
     measure_db_query("update_build_status", || async {
+        // The build is finished when it first leaves the pending state
         sqlx::query!(
-            "UPDATE build SET status = $1 WHERE id = $2",
+            r#"
+UPDATE build
+SET
+    status = $1,
+    finished_at = CASE
+        WHEN $1 = 'pending' THEN NULL
+        ELSE COALESCE(finished_at, NOW())
+    END
+WHERE id = $2
+"#,
             status as _,
             build_id
         )
@@ -629,6 +1000,48 @@ pub(crate) async fn update_build_status(
     .await
 }
 
+/// Records that the merge commit of the given build has been pushed to the base branch.
+pub(crate) async fn record_build_merged(
+    executor: impl PgExecutor<'_>,
+    build_id: i32,
+) -> anyhow::Result<()> {
+    measure_db_query("record_build_merged", || async {
+        sqlx::query!(
+            "UPDATE build SET merged_at = COALESCE(merged_at, NOW()) WHERE id = $1",
+            build_id
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn get_build_timing(
+    executor: impl PgExecutor<'_>,
+    build_id: i32,
+) -> anyhow::Result<Option<BuildTimingModel>> {
+    measure_db_query("get_build_timing", || async {
+        let timing = sqlx::query_as!(
+            BuildTimingModel,
+            r#"
+SELECT
+    queued_at as "queued_at: DateTime<Utc>",
+    created_at as "started_at: DateTime<Utc>",
+    finished_at as "finished_at: DateTime<Utc>",
+    merged_at as "merged_at: DateTime<Utc>"
+FROM build
+WHERE id = $1
+"#,
+            build_id
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(timing)
+    })
+    .await
+}
+
 pub(crate) async fn create_workflow(
     executor: impl PgExecutor<'_>,
     build_id: i32,
@@ -658,6 +1071,34 @@ VALUES ($1, $2, $3, $4, $5, $6)
     .await
 }
 
+/// Updates the status and URL of the external workflow with the given `name` of a build.
+/// Returns `false` if the build has no such workflow.
+pub(crate) async fn update_external_workflow(
+    executor: impl PgExecutor<'_>,
+    build_id: i32,
+    name: &str,
+    url: &str,
+    status: WorkflowStatus,
+) -> anyhow::Result<bool> {
+    measure_db_query("update_external_workflow", || async {
+        let result = sqlx::query!(
+            r#"
+UPDATE workflow SET status = $4, url = $3
+WHERE build_id = $1 AND name = $2 AND type = $5
+"#,
+            build_id,
+            name,
+            url,
+            status as _,
+            WorkflowType::External as _
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    })
+    .await
+}
+
 pub(crate) async fn update_workflow_status(
     executor: impl PgExecutor<'_>,
     run_id: u64,
@@ -694,15 +1135,15 @@ pub(crate) async fn set_pr_priority(
     .await
 }
 
-pub(crate) async fn set_pr_rollup(
+pub(crate) async fn set_pr_waiting_on_conflicts(
     executor: impl PgExecutor<'_>,
     pr_id: i32,
-    rollup: RollupMode,
+    waiting_on_conflicts: bool,
 ) -> anyhow::Result<()> {
-    measure_db_query("set_pr_rollup", || async {
+    measure_db_query("set_pr_waiting_on_conflicts", || async {
         sqlx::query!(
-            "UPDATE pull_request SET rollup = $1 WHERE id = $2",
-            rollup as RollupMode,
+            "UPDATE pull_request SET waiting_on_conflicts = $1 WHERE id = $2",
+            waiting_on_conflicts,
             pr_id,
         )
         .execute(executor)
@@ -712,18 +1153,56 @@ pub(crate) async fn set_pr_rollup(
     .await
 }
 
-pub(crate) async fn set_pr_assignees(
+pub(crate) async fn set_pr_held(
+    executor: impl PgExecutor<'_>,
+    pr_id: i32,
+    held: bool,
+) -> anyhow::Result<()> {
+    measure_db_query("set_pr_held", || async {
+        sqlx::query!(
+            "UPDATE pull_request SET held = $1 WHERE id = $2",
+            held,
+            pr_id,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn set_pr_parked(
+    executor: impl PgExecutor<'_>,
+    pr_id: i32,
+    parked: bool,
+    reason: Option<&str>,
+) -> anyhow::Result<()> {
+    measure_db_query("set_pr_parked", || async {
+        sqlx::query!(
+            "UPDATE pull_request SET parked = $1, park_reason = $2 WHERE id = $3",
+            parked,
+            reason,
+            pr_id,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn set_pr_path_queue(
     executor: impl PgExecutor<'_>,
     repo: &GithubRepoName,
     pr_number: PullRequestNumber,
-    assignees: &[String],
+    path_queue: Option<&str>,
 ) -> anyhow::Result<()> {
-    measure_db_query("set_pr_assignees", || async {
+    measure_db_query("set_pr_path_queue", || async {
         sqlx::query!(
-            "UPDATE pull_request SET assignees = $1 WHERE repository = $2 AND number = $3",
-            assignees.join(","),
+            "UPDATE pull_request SET path_queue = $3 WHERE repository = $1 AND number = $2",
             repo as &GithubRepoName,
             pr_number.0 as i32,
+            path_queue,
         )
         .execute(executor)
         .await?;
@@ -732,17 +1211,129 @@ pub(crate) async fn set_pr_assignees(
     .await
 }
 
-pub(crate) async fn get_workflows_for_build(
+pub(crate) async fn set_pr_build_env(
     executor: impl PgExecutor<'_>,
-    build_id: i32,
-) -> anyhow::Result<Vec<WorkflowModel>> {
-    measure_db_query("get_workflows_for_build", || async {
-        let workflows = sqlx::query_as!(
-            WorkflowModel,
-            r#"
-SELECT
-    workflow.id,
-    workflow.name,
+    pr_id: i32,
+    build_env: &[String],
+) -> anyhow::Result<()> {
+    measure_db_query("set_pr_build_env", || async {
+        sqlx::query!(
+            "UPDATE pull_request SET build_env = $2 WHERE id = $1",
+            pr_id,
+            build_env
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn set_pr_backports(
+    executor: impl PgExecutor<'_>,
+    pr_id: i32,
+    backports: &[String],
+) -> anyhow::Result<()> {
+    measure_db_query("set_pr_backports", || async {
+        sqlx::query!(
+            "UPDATE pull_request SET backports = $2 WHERE id = $1",
+            pr_id,
+            backports
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn set_pr_dry_run_head_sha(
+    executor: impl PgExecutor<'_>,
+    pr_id: i32,
+    head_sha: &str,
+) -> anyhow::Result<()> {
+    measure_db_query("set_pr_dry_run_head_sha", || async {
+        sqlx::query!(
+            "UPDATE pull_request SET dry_run_head_sha = $2 WHERE id = $1",
+            pr_id,
+            head_sha
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn set_pr_rollup(
+    executor: impl PgExecutor<'_>,
+    pr_id: i32,
+    rollup: RollupMode,
+) -> anyhow::Result<()> {
+    measure_db_query("set_pr_rollup", || async {
+        sqlx::query!(
+            "UPDATE pull_request SET rollup = $1 WHERE id = $2",
+            rollup as RollupMode,
+            pr_id,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn set_pr_assignees(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    pr_number: PullRequestNumber,
+    assignees: &[String],
+) -> anyhow::Result<()> {
+    measure_db_query("set_pr_assignees", || async {
+        sqlx::query!(
+            "UPDATE pull_request SET assignees = $1 WHERE repository = $2 AND number = $3",
+            assignees.join(","),
+            repo as &GithubRepoName,
+            pr_number.0 as i32,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn set_pr_labels(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    pr_number: PullRequestNumber,
+    labels: &[String],
+) -> anyhow::Result<()> {
+    measure_db_query("set_pr_labels", || async {
+        sqlx::query!(
+            "UPDATE pull_request SET labels = $1 WHERE repository = $2 AND number = $3",
+            labels,
+            repo as &GithubRepoName,
+            pr_number.0 as i32,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn get_workflows_for_build(
+    executor: impl PgExecutor<'_>,
+    build_id: i32,
+) -> anyhow::Result<Vec<WorkflowModel>> {
+    measure_db_query("get_workflows_for_build", || async {
+        let workflows = sqlx::query_as!(
+            WorkflowModel,
+            r#"
+SELECT
+    workflow.id,
+    workflow.name,
     workflow.url,
     workflow.run_id,
     workflow.type as "workflow_type: WorkflowType",
@@ -771,6 +1362,204 @@ WHERE build.id = $1
     .await
 }
 
+/// Returns the workflows of all the given builds.
+pub(crate) async fn get_workflows_for_builds(
+    executor: impl PgExecutor<'_>,
+    build_ids: &[i32],
+) -> anyhow::Result<Vec<WorkflowModel>> {
+    measure_db_query("get_workflows_for_builds", || async {
+        let workflows = sqlx::query_as!(
+            WorkflowModel,
+            r#"
+SELECT
+    workflow.id,
+    workflow.name,
+    workflow.url,
+    workflow.run_id,
+    workflow.type as "workflow_type: WorkflowType",
+    workflow.status as "status: WorkflowStatus",
+    workflow.created_at as "created_at: DateTime<Utc>",
+    (
+        build.id,
+        build.repository,
+        build.branch,
+        build.commit_sha,
+        build.status,
+        build.parent,
+        build.created_at,
+        build.check_run_id
+    ) AS "build!: BuildModel"
+FROM workflow
+    JOIN build ON workflow.build_id = build.id
+WHERE build.id = ANY($1)
+ORDER BY workflow.id
+"#,
+            build_ids
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(workflows)
+    })
+    .await
+}
+
+pub(crate) async fn upsert_build_artifact(
+    executor: impl PgExecutor<'_>,
+    build_id: i32,
+    name: &str,
+    url: &str,
+    size: Option<i64>,
+) -> anyhow::Result<()> {
+    measure_db_query("upsert_build_artifact", || async {
+        sqlx::query!(
+            r#"
+INSERT INTO build_artifact (build_id, name, url, size)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT (build_id, name)
+DO UPDATE SET url = EXCLUDED.url, size = EXCLUDED.size
+"#,
+            build_id,
+            name,
+            url,
+            size
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn get_artifacts_for_build(
+    executor: impl PgExecutor<'_>,
+    build_id: i32,
+) -> anyhow::Result<Vec<BuildArtifactModel>> {
+    measure_db_query("get_artifacts_for_build", || async {
+        let artifacts = sqlx::query_as!(
+            BuildArtifactModel,
+            r#"
+SELECT
+    id,
+    build_id,
+    name,
+    url,
+    size,
+    created_at as "created_at: DateTime<Utc>"
+FROM build_artifact
+WHERE build_id = $1
+ORDER BY name
+"#,
+            build_id
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(artifacts)
+    })
+    .await
+}
+
+/// Returns the artifacts of all the given builds, ordered by name.
+pub(crate) async fn get_artifacts_for_builds(
+    executor: impl PgExecutor<'_>,
+    build_ids: &[i32],
+) -> anyhow::Result<Vec<BuildArtifactModel>> {
+    measure_db_query("get_artifacts_for_builds", || async {
+        let artifacts = sqlx::query_as!(
+            BuildArtifactModel,
+            r#"
+SELECT
+    id,
+    build_id,
+    name,
+    url,
+    size,
+    created_at as "created_at: DateTime<Utc>"
+FROM build_artifact
+WHERE build_id = ANY($1)
+ORDER BY name
+"#,
+            build_ids
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(artifacts)
+    })
+    .await
+}
+
+pub(crate) async fn delete_build_artifacts(
+    executor: impl PgExecutor<'_>,
+    build_id: i32,
+) -> anyhow::Result<()> {
+    measure_db_query("delete_build_artifacts", || async {
+        sqlx::query!("DELETE FROM build_artifact WHERE build_id = $1", build_id)
+            .execute(executor)
+            .await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Returns at most `limit` builds on the given branch that have finished before `finished_before`
+/// and whose artifacts have not been cleaned up yet, the oldest ones first.
+pub(crate) async fn get_builds_for_artifact_cleanup(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    branch: &str,
+    finished_before: DateTime<Utc>,
+    limit: i64,
+) -> anyhow::Result<Vec<BuildModel>> {
+    measure_db_query("get_builds_for_artifact_cleanup", || async {
+        let builds = sqlx::query_as!(
+            BuildModel,
+            r#"
+SELECT
+    id,
+    repository as "repository: GithubRepoName",
+    branch,
+    commit_sha,
+    status as "status: BuildStatus",
+    parent,
+    created_at as "created_at: DateTime<Utc>",
+    check_run_id
+FROM build
+WHERE repository = $1
+    AND branch = $2
+    AND status != $3
+    AND finished_at < $4
+    AND artifacts_cleaned_at IS NULL
+ORDER BY finished_at
+LIMIT $5
+"#,
+            repo as &GithubRepoName,
+            branch,
+            BuildStatus::Pending as BuildStatus,
+            finished_before,
+            limit
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(builds)
+    })
+    .await
+}
+
+pub(crate) async fn record_build_artifacts_cleaned(
+    executor: impl PgExecutor<'_>,
+    build_id: i32,
+) -> anyhow::Result<()> {
+    measure_db_query("record_build_artifacts_cleaned", || async {
+        sqlx::query!(
+            "UPDATE build SET artifacts_cleaned_at = NOW() WHERE id = $1",
+            build_id
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
 pub(crate) async fn get_workflow_urls_for_build(
     executor: impl PgExecutor<'_>,
     build_id: i32,
@@ -843,9 +1632,13 @@ pub(crate) async fn get_repository(
             name as "name: GithubRepoName",
             (
                 tree_state,
-                treeclosed_src
+                treeclosed_src,
+                treeclosed_reason,
+                treeclosed_until
             ) AS "tree_state!: TreeState",
-            created_at
+            created_at,
+            deactivated_at,
+            deactivation_reason as "deactivation_reason: DeactivationReason"
         FROM repository
         WHERE name = $1
         "#,
@@ -864,20 +1657,19 @@ pub(crate) async fn insert_repo_if_not_exists(
     repo: &GithubRepoName,
     tree_state: TreeState,
 ) -> anyhow::Result<()> {
-    let (priority, src) = match tree_state {
-        TreeState::Open => (None, None),
-        TreeState::Closed { priority, source } => (Some(priority as i32), Some(source)),
-    };
+    let (priority, src, reason, until) = tree_state_columns(tree_state);
     measure_db_query("insert_repository_if_not_exists", || async {
         sqlx::query!(
             r#"
-        INSERT INTO repository (name, tree_state, treeclosed_src)
-        VALUES ($1, $2, $3)
+        INSERT INTO repository (name, tree_state, treeclosed_src, treeclosed_reason, treeclosed_until)
+        VALUES ($1, $2, $3, $4, $5)
         ON CONFLICT (name) DO NOTHING
         "#,
             repo as &GithubRepoName,
             priority,
-            src
+            src,
+            reason,
+            until
         )
         .execute(executor)
         .await?;
@@ -902,9 +1694,13 @@ pub(crate) async fn get_repository_by_name(
             name as "name: GithubRepoName",
             (
                 tree_state,
-                treeclosed_src
+                treeclosed_src,
+                treeclosed_reason,
+                treeclosed_until
             ) AS "tree_state!: TreeState",
-            created_at
+            created_at,
+            deactivated_at,
+            deactivation_reason as "deactivation_reason: DeactivationReason"
         FROM repository
         WHERE name LIKE $1
         LIMIT 1
@@ -925,21 +1721,24 @@ pub(crate) async fn upsert_repository(
     repo: &GithubRepoName,
     tree_state: TreeState,
 ) -> anyhow::Result<()> {
-    let (priority, src) = match tree_state {
-        TreeState::Open => (None, None),
-        TreeState::Closed { priority, source } => (Some(priority as i32), Some(source)),
-    };
+    let (priority, src, reason, until) = tree_state_columns(tree_state);
     measure_db_query("upsert_repository", || async {
         sqlx::query!(
             r#"
-        INSERT INTO repository (name, tree_state, treeclosed_src)
-        VALUES ($1, $2, $3)
+        INSERT INTO repository (name, tree_state, treeclosed_src, treeclosed_reason, treeclosed_until)
+        VALUES ($1, $2, $3, $4, $5)
         ON CONFLICT (name)
-        DO UPDATE SET tree_state = EXCLUDED.tree_state, treeclosed_src = EXCLUDED.treeclosed_src
+        DO UPDATE SET
+            tree_state = EXCLUDED.tree_state,
+            treeclosed_src = EXCLUDED.treeclosed_src,
+            treeclosed_reason = EXCLUDED.treeclosed_reason,
+            treeclosed_until = EXCLUDED.treeclosed_until
         "#,
             repo as &GithubRepoName,
             priority,
-            src
+            src,
+            reason,
+            until
         )
         .execute(executor)
         .await?;
@@ -949,32 +1748,221 @@ pub(crate) async fn upsert_repository(
     .await
 }
 
-pub(crate) async fn update_build_check_run_id(
+/// Records that the tree of the repository has been closed for a scheduled maintenance window.
+/// Returns false if the window starting at `starts_at` has already been recorded.
+pub(crate) async fn record_scheduled_tree_closure(
     executor: impl PgExecutor<'_>,
-    build_id: i32,
-    check_run_id: i64,
-) -> anyhow::Result<()> {
-    measure_db_query("update_build_check_run_id", || async {
-        sqlx::query!(
-            "UPDATE build SET check_run_id = $1 WHERE id = $2",
-            check_run_id,
-            build_id
+    repo: &GithubRepoName,
+    starts_at: DateTime<Utc>,
+    ends_at: DateTime<Utc>,
+) -> anyhow::Result<bool> {
+    measure_db_query("record_scheduled_tree_closure", || async {
+        let result = sqlx::query!(
+            r#"
+        INSERT INTO scheduled_tree_closure (repository, starts_at, ends_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (repository, starts_at) DO NOTHING
+        "#,
+            repo as &GithubRepoName,
+            starts_at,
+            ends_at
         )
         .execute(executor)
         .await?;
-        Ok(())
+        Ok(result.rows_affected() > 0)
     })
     .await
 }
 
-/// Fetches pull requests eligible for merge:
-/// - Only approved PRs that are open and mergeable
-/// - Includes only PRs with pending or successful auto builds
-/// - Excludes non-pending PRs that do not meet the tree closure priority threshold (if tree closed)
-pub(crate) async fn get_merge_queue_prs(
+/// Marks the scheduled tree closures of the repository that have already ended as finished,
+/// and returns their ends.
+pub(crate) async fn finish_expired_scheduled_tree_closures(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+) -> anyhow::Result<Vec<DateTime<Utc>>> {
+    measure_db_query("finish_expired_scheduled_tree_closures", || async {
+        let ends = sqlx::query_scalar!(
+            r#"
+        UPDATE scheduled_tree_closure
+        SET finished = TRUE
+        WHERE repository = $1 AND NOT finished AND ends_at <= NOW()
+        RETURNING ends_at
+        "#,
+            repo as &GithubRepoName
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(ends)
+    })
+    .await
+}
+
+/// Marks the repository as deactivated, so that bors stops operating in it.
+/// The original deactivation time is kept if the repository was already deactivated.
+pub(crate) async fn deactivate_repository(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    reason: DeactivationReason,
+) -> anyhow::Result<()> {
+    measure_db_query("deactivate_repository", || async {
+        sqlx::query!(
+            r#"
+        INSERT INTO repository (name, deactivated_at, deactivation_reason)
+        VALUES ($1, NOW(), $2)
+        ON CONFLICT (name)
+        DO UPDATE SET
+            deactivated_at = COALESCE(repository.deactivated_at, EXCLUDED.deactivated_at),
+            deactivation_reason = EXCLUDED.deactivation_reason
+        "#,
+            repo as &GithubRepoName,
+            reason as DeactivationReason
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    })
+    .await
+}
+
+/// Reactivates the repository if it was deactivated for the given reason.
+/// Returns true if the repository was reactivated.
+pub(crate) async fn reactivate_repository(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    reason: DeactivationReason,
+) -> anyhow::Result<bool> {
+    measure_db_query("reactivate_repository", || async {
+        let result = sqlx::query!(
+            r#"
+        UPDATE repository
+        SET deactivated_at = NULL, deactivation_reason = NULL
+        WHERE name = $1 AND deactivation_reason = $2
+        "#,
+            repo as &GithubRepoName,
+            reason as DeactivationReason
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    })
+    .await
+}
+
+/// Returns the tree state of a base branch that has its own merge queue.
+/// If the tree state of the branch was never changed, it is open.
+pub(crate) async fn get_branch_tree_state(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    branch: &str,
+) -> anyhow::Result<TreeState> {
+    measure_db_query("get_branch_tree_state", || async {
+        let tree_state = sqlx::query_scalar!(
+            r#"
+        SELECT
+            (
+                tree_state,
+                treeclosed_src,
+                treeclosed_reason,
+                treeclosed_until
+            ) AS "tree_state!: TreeState"
+        FROM branch_tree_state
+        WHERE repository = $1 AND branch = $2
+        "#,
+            repo as &GithubRepoName,
+            branch
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(tree_state.unwrap_or(TreeState::Open))
+    })
+    .await
+}
+
+pub(crate) async fn upsert_branch_tree_state(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    branch: &str,
+    tree_state: TreeState,
+) -> anyhow::Result<()> {
+    let (priority, src, reason, until) = tree_state_columns(tree_state);
+    measure_db_query("upsert_branch_tree_state", || async {
+        sqlx::query!(
+            r#"
+        INSERT INTO branch_tree_state (repository, branch, tree_state, treeclosed_src, treeclosed_reason, treeclosed_until)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (repository, branch)
+        DO UPDATE SET
+            tree_state = EXCLUDED.tree_state,
+            treeclosed_src = EXCLUDED.treeclosed_src,
+            treeclosed_reason = EXCLUDED.treeclosed_reason,
+            treeclosed_until = EXCLUDED.treeclosed_until
+        "#,
+            repo as &GithubRepoName,
+            branch,
+            priority,
+            src,
+            reason,
+            until
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    })
+    .await
+}
+
+/// Splits a tree state into the values of the database columns that store it.
+fn tree_state_columns(
+    tree_state: TreeState,
+) -> (
+    Option<i32>,
+    Option<String>,
+    Option<String>,
+    Option<DateTime<Utc>>,
+) {
+    match tree_state {
+        TreeState::Open => (None, None, None, None),
+        TreeState::Closed {
+            priority,
+            source,
+            reason,
+            until,
+        } => (Some(priority as i32), Some(source), reason, until),
+    }
+}
+
+pub(crate) async fn update_build_check_run_id(
+    executor: impl PgExecutor<'_>,
+    build_id: i32,
+    check_run_id: i64,
+) -> anyhow::Result<()> {
+    measure_db_query("update_build_check_run_id", || async {
+        sqlx::query!(
+            "UPDATE build SET check_run_id = $1 WHERE id = $2",
+            check_run_id,
+            build_id
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Fetches pull requests eligible for merge:
+/// - Only approved PRs that are open and mergeable
+/// - Includes only PRs with pending or successful auto builds
+/// - Excludes non-pending PRs that do not meet the tree closure priority threshold (if tree closed)
+/// - Excludes non-pending PRs that have any of the `blocking_labels`
+pub(crate) async fn get_merge_queue_prs(
     executor: impl PgExecutor<'_>,
     repo: &GithubRepoName,
     tree_priority: Option<i32>,
+    blocking_labels: &[String],
 ) -> anyhow::Result<Vec<PullRequestModel>> {
     measure_db_query("get_merge_queue_prs", || async {
         let records = sqlx::query_as!(
@@ -987,6 +1975,7 @@ pub(crate) async fn get_merge_queue_prs(
                 pr.title,
                 pr.author,
                 pr.assignees as "assignees: Assignees",
+                pr.labels,
                 (
                     pr.approved_by,
                     pr.approved_sha
@@ -994,7 +1983,20 @@ pub(crate) async fn get_merge_queue_prs(
                 pr.status as "pr_status: PullRequestStatus",
                 pr.priority,
                 pr.rollup as "rollup: RollupMode",
+                pr.squash,
+                pr.waiting_on_conflicts,
+                pr.held,
+                pr.parked,
+                pr.park_reason,
+                pr.path_queue,
+                pr.build_env,
+                pr.approved_at as "approved_at: DateTime<Utc>",
+                pr.approved_head_sha,
+                pr.approval_authors,
+                pr.backports,
+                pr.dry_run_head_sha,
                 pr.delegated_permission as "delegated_permission: DelegatedPermission",
+                pr.delegation_expires_at as "delegation_expires_at?: DateTime<Utc>",
                 pr.base_branch,
                 pr.mergeable_state as "mergeable_state: MergeableState",
                 pr.created_at as "created_at: DateTime<Utc>",
@@ -1007,6 +2009,8 @@ pub(crate) async fn get_merge_queue_prs(
               AND pr.status = 'open'
               AND pr.approved_by IS NOT NULL
               AND pr.mergeable_state = 'mergeable'
+              -- Parked PRs stay approved, but they are kept outside of the queue
+              AND NOT pr.parked
               AND (
                 -- We ALWAYS need to return pending and successful PRs, regardless of tree state
                 auto_build.status IN ('pending', 'success') OR (
@@ -1015,9 +2019,13 @@ pub(crate) async fn get_merge_queue_prs(
                     auto_build.status IS NULL AND ($2::int IS NULL OR pr.priority >= $2)
                 )
               )
+              -- Running builds are left to finish, but their PRs are not merged while they have
+              -- a blocking label
+              AND (auto_build.status = 'pending' OR NOT (pr.labels && $3::text[]))
             "#,
             repo as &GithubRepoName,
-            tree_priority
+            tree_priority,
+            blocking_labels
         )
         .fetch_all(executor)
         .await?;
@@ -1098,6 +2106,75 @@ pub(crate) async fn delete_tagged_bot_comment(
     .await
 }
 
+pub(crate) async fn add_queue_notification(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    pr_number: PullRequestNumber,
+    username: &str,
+) -> anyhow::Result<()> {
+    measure_db_query("add_queue_notification", || async {
+        sqlx::query!(
+            r#"
+            INSERT INTO queue_notification (repository, pr_number, username)
+            VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+            "#,
+            repo as &GithubRepoName,
+            pr_number.0 as i32,
+            username
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn get_queue_notifications(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+) -> anyhow::Result<Vec<QueueNotificationModel>> {
+    measure_db_query("get_queue_notifications", || async {
+        let notifications = sqlx::query_as!(
+            QueueNotificationModel,
+            r#"
+            SELECT
+                id,
+                repository as "repository: GithubRepoName",
+                pr_number as "pr_number: i64",
+                username,
+                created_at as "created_at: DateTime<Utc>"
+            FROM queue_notification
+            WHERE repository = $1
+            ORDER BY created_at
+            "#,
+            repo as &GithubRepoName,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(notifications)
+    })
+    .await
+}
+
+pub(crate) async fn delete_queue_notifications(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    pr_number: PullRequestNumber,
+) -> anyhow::Result<()> {
+    measure_db_query("delete_queue_notifications", || async {
+        sqlx::query!(
+            "DELETE FROM queue_notification WHERE repository = $1 AND pr_number = $2",
+            repo as &GithubRepoName,
+            pr_number.0 as i32,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
 pub(crate) async fn clear_auto_build(
     executor: impl PgExecutor<'_>,
     pr_id: i32,
@@ -1113,3 +2190,1085 @@ pub(crate) async fn clear_auto_build(
     })
     .await
 }
+
+/// How long an outbox entry is reserved for the delivery that has claimed it. Until then, no
+/// other delivery performs it, so that an action is not performed twice concurrently. If the
+/// delivery crashes, the entry becomes due again once the lease expires.
+const OUTBOX_LEASE_SECS: f64 = 600.0;
+
+/// Records an outbox action. The entry is claimed by the caller, who is expected to perform it
+/// right away.
+pub(crate) async fn enqueue_outbox_action(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    pr_number: PullRequestNumber,
+    action: &OutboxAction,
+) -> anyhow::Result<OutboxModel> {
+    let payload = serde_json::to_string(action)?;
+    measure_db_query("enqueue_outbox_action", || async {
+        let entry = sqlx::query_as!(
+            OutboxModel,
+            r#"
+            INSERT INTO outbox (repository, pr_number, payload, next_attempt_at)
+            VALUES ($1, $2, $3, NOW() + $4 * INTERVAL '1 second')
+            RETURNING
+                id,
+                repository as "repository: GithubRepoName",
+                pr_number as "pr_number: i64",
+                payload as "action: OutboxAction",
+                attempts,
+                next_attempt_at as "next_attempt_at: DateTime<Utc>",
+                created_at as "created_at: DateTime<Utc>"
+            "#,
+            repo as &GithubRepoName,
+            pr_number.0 as i32,
+            payload.as_str(),
+            OUTBOX_LEASE_SECS
+        )
+        .fetch_one(executor)
+        .await?;
+        Ok(entry)
+    })
+    .await
+}
+
+/// Records an outbox action whose first attempt is postponed by `delay_secs`, plus a random
+/// jitter of up to one minute. Actions with no delay are claimed by the caller, who is expected
+/// to perform them right away.
+pub(crate) async fn enqueue_delayed_outbox_action(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    pr_number: PullRequestNumber,
+    action: &OutboxAction,
+    delay_secs: i32,
+) -> anyhow::Result<OutboxModel> {
+    let payload = serde_json::to_string(action)?;
+    measure_db_query("enqueue_delayed_outbox_action", || async {
+        let entry = sqlx::query_as!(
+            OutboxModel,
+            r#"
+            INSERT INTO outbox (repository, pr_number, payload, next_attempt_at)
+            VALUES (
+                $1,
+                $2,
+                $3,
+                CASE
+                    WHEN $4 = 0 THEN NOW() + $5 * INTERVAL '1 second'
+                    ELSE NOW() + INTERVAL '1 second' * $4 + INTERVAL '1 minute' * RANDOM()
+                END
+            )
+            RETURNING
+                id,
+                repository as "repository: GithubRepoName",
+                pr_number as "pr_number: i64",
+                payload as "action: OutboxAction",
+                attempts,
+                next_attempt_at as "next_attempt_at: DateTime<Utc>",
+                created_at as "created_at: DateTime<Utc>"
+            "#,
+            repo as &GithubRepoName,
+            pr_number.0 as i32,
+            payload.as_str(),
+            delay_secs,
+            OUTBOX_LEASE_SECS
+        )
+        .fetch_one(executor)
+        .await?;
+        Ok(entry)
+    })
+    .await
+}
+
+/// Reserves `count` notification slots in the current (or in a following) notification window of
+/// the repository, each window allowing `per_minute` notifications. Windows start when the first
+/// notification is sent after the previous window has ended, and last for one minute.
+/// Returns the first window in which slots were reserved, or `None` if the repository is not
+/// known.
+pub(crate) async fn reserve_notification_slots(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    count: i32,
+    per_minute: i32,
+) -> anyhow::Result<Option<NotificationWindow>> {
+    measure_db_query("reserve_notification_slots", || async {
+        let window = sqlx::query_as!(
+            NotificationWindow,
+            r#"
+            WITH current AS (
+                SELECT
+                    id,
+                    CASE
+                        WHEN notification_window_start > NOW() - INTERVAL '1 minute'
+                            THEN notification_window_start
+                        ELSE NOW()
+                    END AS start,
+                    CASE
+                        WHEN notification_window_start > NOW() - INTERVAL '1 minute'
+                            THEN notification_window_count
+                        ELSE 0
+                    END AS reserved
+                FROM repository
+                WHERE name = $1
+                FOR UPDATE
+            )
+            UPDATE repository
+            SET
+                notification_window_start =
+                    current.start + INTERVAL '1 minute' * ((current.reserved + $2 - 1) / $3),
+                notification_window_count =
+                    current.reserved + $2 - (current.reserved + $2 - 1) / $3 * $3
+            FROM current
+            WHERE repository.id = current.id
+            RETURNING
+                current.start as "start!: DateTime<Utc>",
+                current.reserved as "reserved!",
+                NOW() as "now!: DateTime<Utc>"
+            "#,
+            repo as &GithubRepoName,
+            count,
+            per_minute
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(window)
+    })
+    .await
+}
+
+/// Returns outbox entries of the given repository whose next attempt is due, in the order in
+/// which they were recorded.
+pub(crate) async fn get_due_outbox_actions(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+) -> anyhow::Result<Vec<OutboxModel>> {
+    measure_db_query("get_due_outbox_actions", || async {
+        let entries = sqlx::query_as!(
+            OutboxModel,
+            r#"
+            SELECT
+                id,
+                repository as "repository: GithubRepoName",
+                pr_number as "pr_number: i64",
+                payload as "action: OutboxAction",
+                attempts,
+                next_attempt_at as "next_attempt_at: DateTime<Utc>",
+                created_at as "created_at: DateTime<Utc>"
+            FROM outbox
+            WHERE repository = $1 AND next_attempt_at <= NOW()
+            ORDER BY id
+            "#,
+            repo as &GithubRepoName,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(entries)
+    })
+    .await
+}
+
+/// Claims the outbox entries of the given repository whose next attempt is due, and returns them
+/// in the order in which they were recorded. Entries that are being claimed concurrently are
+/// skipped, and the claimed entries are leased, so that each entry is only performed by a single
+/// delivery.
+pub(crate) async fn claim_due_outbox_actions(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+) -> anyhow::Result<Vec<OutboxModel>> {
+    measure_db_query("claim_due_outbox_actions", || async {
+        let entries = sqlx::query_as!(
+            OutboxModel,
+            r#"
+            WITH claimed AS (
+                UPDATE outbox
+                SET next_attempt_at = NOW() + $2 * INTERVAL '1 second'
+                WHERE id IN (
+                    SELECT id
+                    FROM outbox
+                    WHERE repository = $1 AND next_attempt_at <= NOW()
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING *
+            )
+            SELECT
+                id,
+                repository as "repository: GithubRepoName",
+                pr_number as "pr_number: i64",
+                payload as "action: OutboxAction",
+                attempts,
+                next_attempt_at as "next_attempt_at: DateTime<Utc>",
+                created_at as "created_at: DateTime<Utc>"
+            FROM claimed
+            ORDER BY id
+            "#,
+            repo as &GithubRepoName,
+            OUTBOX_LEASE_SECS
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(entries)
+    })
+    .await
+}
+
+/// Releases the claim of outbox entries that were not attempted, so that they are due again.
+pub(crate) async fn release_outbox_actions(
+    executor: impl PgExecutor<'_>,
+    ids: &[i32],
+) -> anyhow::Result<()> {
+    measure_db_query("release_outbox_actions", || async {
+        sqlx::query!(
+            "UPDATE outbox SET next_attempt_at = NOW() WHERE id = ANY($1)",
+            ids
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn delete_outbox_action(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> anyhow::Result<()> {
+    measure_db_query("delete_outbox_action", || async {
+        sqlx::query!("DELETE FROM outbox WHERE id = $1", id)
+            .execute(executor)
+            .await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Records a failed attempt to perform an outbox action.
+/// The first retry happens immediately, further retries are postponed exponentially, up to
+/// one hour.
+pub(crate) async fn record_outbox_action_failure(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> anyhow::Result<()> {
+    measure_db_query("record_outbox_action_failure", || async {
+        sqlx::query!(
+            r#"
+            UPDATE outbox
+            SET attempts = attempts + 1,
+                next_attempt_at = NOW() + LEAST(
+                    INTERVAL '1 minute' * (POWER(2, attempts) - 1),
+                    INTERVAL '1 hour'
+                )
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn insert_dead_letter(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    event_type: &str,
+    payload: &str,
+    error: &str,
+) -> anyhow::Result<DeadLetterModel> {
+    measure_db_query("insert_dead_letter", || async {
+        let dead_letter = sqlx::query_as!(
+            DeadLetterModel,
+            r#"
+            INSERT INTO webhook_dead_letter (repository, event_type, payload, error)
+            VALUES ($1, $2, $3, $4)
+            RETURNING
+                id,
+                repository as "repository: GithubRepoName",
+                event_type,
+                payload,
+                error,
+                attempts,
+                replay_requested,
+                created_at as "created_at: DateTime<Utc>"
+            "#,
+            repo as &GithubRepoName,
+            event_type,
+            payload,
+            error
+        )
+        .fetch_one(executor)
+        .await?;
+        Ok(dead_letter)
+    })
+    .await
+}
+
+/// Returns all webhooks in the dead-letter queue, in the order in which they have failed.
+pub(crate) async fn get_dead_letters(
+    executor: impl PgExecutor<'_>,
+) -> anyhow::Result<Vec<DeadLetterModel>> {
+    measure_db_query("get_dead_letters", || async {
+        let dead_letters = sqlx::query_as!(
+            DeadLetterModel,
+            r#"
+            SELECT
+                id,
+                repository as "repository: GithubRepoName",
+                event_type,
+                payload,
+                error,
+                attempts,
+                replay_requested,
+                created_at as "created_at: DateTime<Utc>"
+            FROM webhook_dead_letter
+            ORDER BY id
+            "#
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(dead_letters)
+    })
+    .await
+}
+
+/// Returns webhooks of the given repository whose replay has been requested, in the order in
+/// which they have failed.
+pub(crate) async fn get_dead_letters_to_replay(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+) -> anyhow::Result<Vec<DeadLetterModel>> {
+    measure_db_query("get_dead_letters_to_replay", || async {
+        let dead_letters = sqlx::query_as!(
+            DeadLetterModel,
+            r#"
+            SELECT
+                id,
+                repository as "repository: GithubRepoName",
+                event_type,
+                payload,
+                error,
+                attempts,
+                replay_requested,
+                created_at as "created_at: DateTime<Utc>"
+            FROM webhook_dead_letter
+            WHERE repository = $1 AND replay_requested
+            ORDER BY id
+            "#,
+            repo as &GithubRepoName,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(dead_letters)
+    })
+    .await
+}
+
+/// Marks the dead letter with the given `id`, or all dead letters if `id` is `None`, to be
+/// replayed. Returns the number of marked dead letters.
+pub(crate) async fn request_dead_letter_replay(
+    executor: impl PgExecutor<'_>,
+    id: Option<i32>,
+) -> anyhow::Result<u64> {
+    measure_db_query("request_dead_letter_replay", || async {
+        let result = sqlx::query!(
+            r#"
+            UPDATE webhook_dead_letter
+            SET replay_requested = TRUE
+            WHERE $1::INT IS NULL OR id = $1
+            "#,
+            id
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected())
+    })
+    .await
+}
+
+pub(crate) async fn delete_dead_letter(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> anyhow::Result<()> {
+    measure_db_query("delete_dead_letter", || async {
+        sqlx::query!("DELETE FROM webhook_dead_letter WHERE id = $1", id)
+            .execute(executor)
+            .await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Records a failed replay of a dead letter. The dead letter is not replayed again until another
+/// replay is requested.
+pub(crate) async fn record_dead_letter_failure(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+    error: &str,
+) -> anyhow::Result<()> {
+    measure_db_query("record_dead_letter_failure", || async {
+        sqlx::query!(
+            r#"
+            UPDATE webhook_dead_letter
+            SET attempts = attempts + 1,
+                error = $2,
+                replay_requested = FALSE
+            WHERE id = $1
+            "#,
+            id,
+            error
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Returns the emergency stop, if it is currently active.
+pub(crate) async fn get_emergency_stop(
+    executor: impl PgExecutor<'_>,
+) -> anyhow::Result<Option<EmergencyStopModel>> {
+    measure_db_query("get_emergency_stop", || async {
+        let stop = sqlx::query_as!(
+            EmergencyStopModel,
+            r#"
+            SELECT
+                reason,
+                created_at as "created_at: DateTime<Utc>"
+            FROM emergency_stop
+            "#
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(stop)
+    })
+    .await
+}
+
+/// Activates the emergency stop. If it is already active, only its reason is updated.
+pub(crate) async fn set_emergency_stop(
+    executor: impl PgExecutor<'_>,
+    reason: Option<&str>,
+) -> anyhow::Result<()> {
+    measure_db_query("set_emergency_stop", || async {
+        sqlx::query!(
+            r#"
+            INSERT INTO emergency_stop (id, reason)
+            VALUES (1, $1)
+            ON CONFLICT (id) DO UPDATE SET reason = EXCLUDED.reason
+            "#,
+            reason
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Clears the emergency stop.
+/// Returns `true` if the emergency stop was active.
+pub(crate) async fn clear_emergency_stop(executor: impl PgExecutor<'_>) -> anyhow::Result<bool> {
+    measure_db_query("clear_emergency_stop", || async {
+        let result = sqlx::query!("DELETE FROM emergency_stop")
+            .execute(executor)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    })
+    .await
+}
+
+/// Performs a trivial query, to check that the database can be reached.
+pub(crate) async fn check_connection(executor: impl PgExecutor<'_>) -> anyhow::Result<()> {
+    measure_db_query("check_connection", || async {
+        sqlx::query_scalar!(r#"SELECT 1 as "one!""#)
+            .fetch_one(executor)
+            .await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Tries to acquire a session-level advisory lock with the given `key`, which is held until it
+/// is released by [`advisory_unlock`] or until the connection is closed. Returns `false` if the
+/// lock is held by someone else.
+pub(crate) async fn try_advisory_lock(
+    executor: impl PgExecutor<'_>,
+    key: i64,
+) -> anyhow::Result<bool> {
+    measure_db_query("try_advisory_lock", || async {
+        let locked = sqlx::query_scalar!(r#"SELECT pg_try_advisory_lock($1) as "locked!""#, key)
+            .fetch_one(executor)
+            .await?;
+        Ok(locked)
+    })
+    .await
+}
+
+/// Releases a session-level advisory lock acquired by [`try_advisory_lock`].
+pub(crate) async fn advisory_unlock(executor: impl PgExecutor<'_>, key: i64) -> anyhow::Result<()> {
+    measure_db_query("advisory_unlock", || async {
+        sqlx::query_scalar!(r#"SELECT pg_advisory_unlock($1) as "unlocked!""#, key)
+            .fetch_one(executor)
+            .await?;
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn get_merge_queue_pause(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+) -> anyhow::Result<Option<MergeQueuePauseModel>> {
+    measure_db_query("get_merge_queue_pause", || async {
+        let pause = sqlx::query_as!(
+            MergeQueuePauseModel,
+            r#"
+            SELECT
+                repository as "repository: GithubRepoName",
+                paused_by,
+                created_at as "created_at: DateTime<Utc>"
+            FROM merge_queue_pause
+            WHERE repository = $1
+            "#,
+            repo as &GithubRepoName
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(pause)
+    })
+    .await
+}
+
+/// Pauses the merge queue of a repository. If it is already paused, nothing happens.
+pub(crate) async fn pause_merge_queue(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    paused_by: &str,
+) -> anyhow::Result<()> {
+    measure_db_query("pause_merge_queue", || async {
+        sqlx::query!(
+            r#"
+            INSERT INTO merge_queue_pause (repository, paused_by)
+            VALUES ($1, $2)
+            ON CONFLICT (repository) DO NOTHING
+            "#,
+            repo as &GithubRepoName,
+            paused_by
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Resumes the merge queue of a repository.
+/// Returns `true` if the queue was paused.
+pub(crate) async fn resume_merge_queue(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+) -> anyhow::Result<bool> {
+    measure_db_query("resume_merge_queue", || async {
+        let result = sqlx::query!(
+            "DELETE FROM merge_queue_pause WHERE repository = $1",
+            repo as &GithubRepoName
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    })
+    .await
+}
+
+pub(crate) async fn get_queue_check_runs(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+) -> anyhow::Result<Vec<QueueCheckRunModel>> {
+    measure_db_query("get_queue_check_runs", || async {
+        let check_runs = sqlx::query_as!(
+            QueueCheckRunModel,
+            r#"
+            SELECT
+                id,
+                repository as "repository: GithubRepoName",
+                pr_number as "pr_number: i64",
+                check_run_id,
+                head_sha,
+                position,
+                created_at as "created_at: DateTime<Utc>"
+            FROM queue_check_run
+            WHERE repository = $1
+            "#,
+            repo as &GithubRepoName,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(check_runs)
+    })
+    .await
+}
+
+/// Records the queue check run of a PR, replacing the previous one, if any.
+pub(crate) async fn upsert_queue_check_run(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    pr_number: PullRequestNumber,
+    check_run_id: i64,
+    head_sha: &str,
+    position: i32,
+) -> anyhow::Result<()> {
+    measure_db_query("upsert_queue_check_run", || async {
+        sqlx::query!(
+            r#"
+            INSERT INTO queue_check_run (repository, pr_number, check_run_id, head_sha, position)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (repository, pr_number)
+            DO UPDATE SET
+                check_run_id = EXCLUDED.check_run_id,
+                head_sha = EXCLUDED.head_sha,
+                position = EXCLUDED.position
+            "#,
+            repo as &GithubRepoName,
+            pr_number.0 as i32,
+            check_run_id,
+            head_sha,
+            position
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn delete_queue_check_run(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> anyhow::Result<()> {
+    measure_db_query("delete_queue_check_run", || async {
+        sqlx::query!("DELETE FROM queue_check_run WHERE id = $1", id)
+            .execute(executor)
+            .await?;
+        Ok(())
+    })
+    .await
+}
+
+pub(crate) async fn create_build_retry(
+    executor: impl PgExecutor<'_>,
+    pr_id: i32,
+    build_id: i32,
+    retried_by: Option<&str>,
+    reason: Option<&str>,
+    head_sha: Option<&str>,
+) -> anyhow::Result<()> {
+    measure_db_query("create_build_retry", || async {
+        sqlx::query!(
+            r#"
+INSERT INTO build_retry (pull_request_id, build_id, retried_by, reason, head_sha)
+VALUES ($1, $2, $3, $4, $5)
+"#,
+            pr_id,
+            build_id,
+            retried_by,
+            reason,
+            head_sha
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Returns the retries of builds of a PR, ordered from the oldest one.
+pub(crate) async fn get_build_retries(
+    executor: impl PgExecutor<'_>,
+    pr_id: i32,
+) -> anyhow::Result<Vec<BuildRetryModel>> {
+    measure_db_query("get_build_retries", || async {
+        let retries = sqlx::query_as!(
+            BuildRetryModel,
+            r#"
+SELECT
+    build_retry.id,
+    build_retry.pull_request_id,
+    (
+        build.id,
+        build.repository,
+        build.branch,
+        build.commit_sha,
+        build.status,
+        build.parent,
+        build.created_at,
+        build.check_run_id
+    ) AS "build!: BuildModel",
+    build_retry.retried_by,
+    build_retry.reason,
+    build_retry.head_sha,
+    build_retry.created_at
+FROM build_retry
+    JOIN build ON build_retry.build_id = build.id
+WHERE build_retry.pull_request_id = $1
+ORDER BY build_retry.id
+"#,
+            pr_id
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(retries)
+    })
+    .await
+}
+
+/// Returns the build retries of all the given PRs, in the order in which they were recorded.
+pub(crate) async fn get_build_retries_for_prs(
+    executor: impl PgExecutor<'_>,
+    pr_ids: &[i32],
+) -> anyhow::Result<Vec<BuildRetryModel>> {
+    measure_db_query("get_build_retries_for_prs", || async {
+        let retries = sqlx::query_as!(
+            BuildRetryModel,
+            r#"
+SELECT
+    build_retry.id,
+    build_retry.pull_request_id,
+    (
+        build.id,
+        build.repository,
+        build.branch,
+        build.commit_sha,
+        build.status,
+        build.parent,
+        build.created_at,
+        build.check_run_id
+    ) AS "build!: BuildModel",
+    build_retry.retried_by,
+    build_retry.reason,
+    build_retry.head_sha,
+    build_retry.created_at
+FROM build_retry
+    JOIN build ON build_retry.build_id = build.id
+WHERE build_retry.pull_request_id = ANY($1)
+ORDER BY build_retry.id
+"#,
+            pr_ids
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(retries)
+    })
+    .await
+}
+
+/// Records that the given PR has reached `milestone` now.
+pub(crate) async fn record_pr_milestone(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    pr_number: PullRequestNumber,
+    milestone: PrMilestone,
+) -> anyhow::Result<()> {
+    measure_db_query("record_pr_milestone", || async {
+        sqlx::query!(
+            r#"
+            INSERT INTO pr_timeline (
+                repository,
+                pr_number,
+                opened_at,
+                first_command_at,
+                approved_at,
+                queued_at,
+                build_started_at,
+                merged_at
+            )
+            VALUES (
+                $1,
+                $2,
+                CASE WHEN $3 = 'opened' THEN NOW() END,
+                CASE WHEN $3 = 'first_command' THEN NOW() END,
+                CASE WHEN $3 = 'approved' THEN NOW() END,
+                CASE WHEN $3 = 'approved' THEN NOW() END,
+                CASE WHEN $3 = 'build_started' THEN NOW() END,
+                CASE WHEN $3 = 'merged' THEN NOW() END
+            )
+            ON CONFLICT (repository, pr_number) DO UPDATE SET
+                opened_at = COALESCE(pr_timeline.opened_at, EXCLUDED.opened_at),
+                first_command_at = COALESCE(pr_timeline.first_command_at, EXCLUDED.first_command_at),
+                approved_at = COALESCE(pr_timeline.approved_at, EXCLUDED.approved_at),
+                queued_at = COALESCE(EXCLUDED.queued_at, pr_timeline.queued_at),
+                build_started_at = COALESCE(EXCLUDED.build_started_at, pr_timeline.build_started_at),
+                merged_at = COALESCE(pr_timeline.merged_at, EXCLUDED.merged_at)
+            "#,
+            repo as &GithubRepoName,
+            pr_number.0 as i32,
+            milestone.as_str()
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Returns review latency metrics of the PRs of the given repository merged in the last `weeks`
+/// weeks, grouped by the week of the merge.
+pub(crate) async fn get_weekly_latencies(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    weeks: i32,
+) -> anyhow::Result<Vec<WeeklyLatencyModel>> {
+    measure_db_query("get_weekly_latencies", || async {
+        let latencies = sqlx::query_as!(
+            WeeklyLatencyModel,
+            r#"
+            SELECT
+                date_trunc('week', merged_at) as "week!: DateTime<Utc>",
+                COUNT(*) as "merged!",
+                percentile_cont(0.5) WITHIN GROUP (
+                    ORDER BY EXTRACT(EPOCH FROM first_command_at - opened_at)
+                ) as open_to_first_command,
+                percentile_cont(0.5) WITHIN GROUP (
+                    ORDER BY EXTRACT(EPOCH FROM approved_at - opened_at)
+                ) as open_to_approval,
+                percentile_cont(0.5) WITHIN GROUP (
+                    ORDER BY EXTRACT(EPOCH FROM build_started_at - queued_at)
+                ) as queue_to_build_start,
+                percentile_cont(0.5) WITHIN GROUP (
+                    ORDER BY EXTRACT(EPOCH FROM merged_at - build_started_at)
+                ) as build_start_to_merge,
+                percentile_cont(0.5) WITHIN GROUP (
+                    ORDER BY EXTRACT(EPOCH FROM merged_at - approved_at)
+                ) as approval_to_merge
+            FROM pr_timeline
+            WHERE repository = $1
+                AND merged_at IS NOT NULL
+                AND merged_at >= date_trunc('week', NOW()) - make_interval(weeks => $2 - 1)
+            GROUP BY 1
+            ORDER BY 1
+            "#,
+            repo as &GithubRepoName,
+            weeks
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(latencies)
+    })
+    .await
+}
+
+/// Returns statistics of the builds of the given repository started in the last `days` days,
+/// separately for try builds and auto builds.
+pub(crate) async fn get_build_stats(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    days: i32,
+) -> anyhow::Result<Vec<BuildStatsModel>> {
+    measure_db_query("get_build_stats", || async {
+        let stats = sqlx::query_as!(
+            BuildStatsModel,
+            r#"
+            SELECT
+                CASE
+                    WHEN EXISTS (SELECT 1 FROM pr_try_build WHERE build_id = build.id) THEN 'try'
+                    ELSE 'auto'
+                END as "kind!",
+                COUNT(*) as "builds!",
+                COUNT(*) FILTER (WHERE status = 'success') as "succeeded!",
+                COUNT(*) FILTER (WHERE status IN ('failure', 'timeouted')) as "failed!",
+                COUNT(*) FILTER (WHERE merged_at IS NOT NULL) as "merged!",
+                COALESCE(SUM(retry.retries), 0)::BIGINT as "retries!",
+                COUNT(*) FILTER (WHERE status IN ('failure', 'timeouted'))::FLOAT8
+                    / NULLIF(COUNT(*) FILTER (WHERE status IN ('success', 'failure', 'timeouted')), 0)
+                    as failure_rate,
+                COALESCE(SUM(retry.retries), 0)::FLOAT8
+                    / NULLIF(COUNT(*) FILTER (WHERE merged_at IS NOT NULL), 0)
+                    as retries_per_merge,
+                percentile_cont(0.5) WITHIN GROUP (
+                    ORDER BY EXTRACT(EPOCH FROM build.created_at - queued_at)
+                ) as queue_wait,
+                percentile_cont(0.5) WITHIN GROUP (
+                    ORDER BY EXTRACT(EPOCH FROM finished_at - build.created_at)
+                ) FILTER (WHERE status IN ('success', 'failure')) as ci_duration,
+                percentile_cont(0.5) WITHIN GROUP (
+                    ORDER BY EXTRACT(EPOCH FROM merged_at - finished_at)
+                ) as finish_to_merge
+            FROM build
+            LEFT JOIN (
+                SELECT build_id, COUNT(*) as retries
+                FROM build_retry
+                GROUP BY build_id
+            ) retry ON retry.build_id = build.id
+            WHERE build.repository = $1
+                AND build.created_at >= NOW() - make_interval(days => $2)
+            GROUP BY 1
+            ORDER BY 1
+            "#,
+            repo as &GithubRepoName,
+            days
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(stats)
+    })
+    .await
+}
+
+#[cfg(test)]
+pub(crate) async fn set_pr_approved_at(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    pr_number: PullRequestNumber,
+    approved_at: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    measure_db_query("set_pr_approved_at", || async {
+        sqlx::query!(
+            "UPDATE pull_request SET approved_at = $1 WHERE repository = $2 AND number = $3",
+            approved_at,
+            repo as &GithubRepoName,
+            pr_number.0 as i32
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Appends an entry to the audit log.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn record_audit_entry(
+    executor: impl PgExecutor<'_>,
+    repo: Option<&GithubRepoName>,
+    pr_number: Option<PullRequestNumber>,
+    actor: Option<&str>,
+    action: &str,
+    details: Option<&str>,
+    outcome: &str,
+) -> anyhow::Result<()> {
+    measure_db_query("record_audit_entry", || async {
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_log (repository, pr_number, actor, action, details, outcome)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            repo.map(|repo| repo.to_string()),
+            pr_number.map(|pr| pr.0 as i64),
+            actor,
+            action,
+            details,
+            outcome
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Returns the last `limit` audit log entries of the given repository, optionally only those
+/// concerning the given PR, from the oldest one.
+pub(crate) async fn get_audit_log(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    pr_number: Option<PullRequestNumber>,
+    limit: i64,
+) -> anyhow::Result<Vec<AuditLogEntryModel>> {
+    measure_db_query("get_audit_log", || async {
+        let entries = sqlx::query!(
+            r#"
+            SELECT
+                id,
+                repository as "repository!: GithubRepoName",
+                pr_number,
+                actor,
+                action,
+                details,
+                outcome,
+                created_at as "created_at: DateTime<Utc>"
+            FROM (
+                SELECT *
+                FROM audit_log
+                WHERE repository = $1
+                  AND ($2::BIGINT IS NULL OR pr_number = $2)
+                ORDER BY id DESC
+                LIMIT $3
+            ) entries
+            ORDER BY id
+            "#,
+            repo as &GithubRepoName,
+            pr_number.map(|pr| pr.0 as i64),
+            limit
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| AuditLogEntryModel {
+                id: entry.id,
+                repository: entry.repository,
+                pr_number: entry.pr_number.map(PullRequestNumber::from),
+                actor: entry.actor,
+                action: entry.action,
+                details: entry.details,
+                outcome: entry.outcome,
+                created_at: entry.created_at,
+            })
+            .collect())
+    })
+    .await
+}
+
+/// Returns the last `limit` commands on the given PR that bors has rejected, i.e. that could not
+/// be parsed or that their author was not allowed to use, from the oldest one.
+pub(crate) async fn get_rejected_commands(
+    executor: impl PgExecutor<'_>,
+    repo: &GithubRepoName,
+    pr_number: PullRequestNumber,
+    limit: i64,
+) -> anyhow::Result<Vec<AuditLogEntryModel>> {
+    measure_db_query("get_rejected_commands", || async {
+        let entries = sqlx::query!(
+            r#"
+            SELECT
+                id,
+                repository as "repository!: GithubRepoName",
+                pr_number,
+                actor,
+                action,
+                details,
+                outcome,
+                created_at as "created_at: DateTime<Utc>"
+            FROM (
+                SELECT *
+                FROM audit_log
+                WHERE repository = $1
+                  AND pr_number = $2
+                  AND actor IS NOT NULL
+                  AND outcome LIKE 'rejected:%'
+                ORDER BY id DESC
+                LIMIT $3
+            ) entries
+            ORDER BY id
+            "#,
+            repo as &GithubRepoName,
+            pr_number.0 as i64,
+            limit
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| AuditLogEntryModel {
+                id: entry.id,
+                repository: entry.repository,
+                pr_number: entry.pr_number.map(PullRequestNumber::from),
+                actor: entry.actor,
+                action: entry.action,
+                details: entry.details,
+                outcome: entry.outcome,
+                created_at: entry.created_at,
+            })
+            .collect())
+    })
+    .await
+}
@@ -0,0 +1,168 @@
+//! Comparison of the database schema with the migrations embedded in the binary.
+//!
+//! When several instances of bors share a database (e.g. during a deployment), a migration
+//! applied by a new instance must not break the instances that still run the previous version.
+//! Migrations that only add things are safe to apply automatically, but migrations that remove or
+//! rewrite data should be applied by an operator, once the old instances are gone.
+use anyhow::Context;
+use sqlx::PgPool;
+use sqlx::migrate::{Migrate, Migrator};
+
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// SQL statements that can remove or rewrite existing data, or break queries of a binary that
+/// still expects the previous schema.
+const DESTRUCTIVE_STATEMENTS: [&str; 6] = [
+    "DROP ",
+    "RENAME ",
+    "TRUNCATE ",
+    "DELETE FROM ",
+    "SET NOT NULL",
+    "SET DATA TYPE ",
+];
+
+/// A migration that has not been applied to the database yet.
+pub struct PendingMigration {
+    pub version: i64,
+    pub description: String,
+    pub sql: String,
+}
+
+impl PendingMigration {
+    /// Returns true if the migration could remove or rewrite data, or break instances of bors
+    /// that still run the previous version.
+    pub fn is_destructive(&self) -> bool {
+        is_destructive_sql(&self.sql)
+    }
+}
+
+/// State of the database schema compared to the migrations of this binary.
+pub enum SchemaStatus {
+    /// All migrations of this binary were applied, and there are no other migrations.
+    UpToDate { version: i64 },
+    /// Some migrations of this binary were not applied yet.
+    Outdated { pending: Vec<PendingMigration> },
+    /// The database contains migrations that this binary does not know about, so it was most
+    /// likely migrated by a newer version of bors.
+    Newer { unknown_versions: Vec<i64> },
+}
+
+/// Returns the version of the latest migration embedded in this binary.
+pub fn expected_schema_version() -> i64 {
+    up_migrations()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or_default()
+}
+
+/// Compares the migrations applied to the database with the migrations of this binary.
+pub async fn get_schema_status(pool: &PgPool) -> anyhow::Result<SchemaStatus> {
+    let mut conn = pool
+        .acquire()
+        .await
+        .context("Cannot acquire database connection")?;
+    conn.ensure_migrations_table()
+        .await
+        .context("Cannot create migrations table")?;
+    let applied: Vec<i64> = conn
+        .list_applied_migrations()
+        .await
+        .context("Cannot load applied migrations")?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+
+    let unknown_versions: Vec<i64> = applied
+        .iter()
+        .copied()
+        .filter(|version| !MIGRATOR.version_exists(*version))
+        .collect();
+    if !unknown_versions.is_empty() {
+        return Ok(SchemaStatus::Newer { unknown_versions });
+    }
+
+    let pending: Vec<PendingMigration> = up_migrations()
+        .filter(|migration| !applied.contains(&migration.version))
+        .map(|migration| PendingMigration {
+            version: migration.version,
+            description: migration.description.to_string(),
+            sql: migration.sql.to_string(),
+        })
+        .collect();
+    if pending.is_empty() {
+        Ok(SchemaStatus::UpToDate {
+            version: expected_schema_version(),
+        })
+    } else {
+        Ok(SchemaStatus::Outdated { pending })
+    }
+}
+
+/// Applies all pending migrations of this binary.
+pub async fn run_migrations(pool: &PgPool) -> anyhow::Result<()> {
+    MIGRATOR
+        .run(pool)
+        .await
+        .context("Cannot run database migrations")
+}
+
+fn up_migrations() -> impl Iterator<Item = &'static sqlx::migrate::Migration> {
+    MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+}
+
+fn is_destructive_sql(sql: &str) -> bool {
+    let normalized = sql
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("--"))
+        .flat_map(|line| line.split_whitespace())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_uppercase()
+        + " ";
+    DESTRUCTIVE_STATEMENTS
+        .iter()
+        .any(|statement| normalized.contains(statement))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SchemaStatus, expected_schema_version, get_schema_status, is_destructive_sql};
+
+    #[test]
+    fn additive_migration_is_not_destructive() {
+        assert!(!is_destructive_sql(
+            "-- Add up migration script here\nALTER TABLE build ADD COLUMN duration INTEGER;"
+        ));
+        assert!(!is_destructive_sql(
+            "CREATE TABLE foo (id SERIAL PRIMARY KEY);\nCREATE INDEX foo_idx ON foo (id);"
+        ));
+    }
+
+    #[test]
+    fn destructive_migrations() {
+        assert!(is_destructive_sql("DROP TABLE foo;"));
+        assert!(is_destructive_sql("alter table foo\n  drop column bar;"));
+        assert!(is_destructive_sql("ALTER TABLE foo RENAME COLUMN a TO b;"));
+        assert!(is_destructive_sql(
+            "ALTER TABLE foo ALTER COLUMN a SET NOT NULL;"
+        ));
+        assert!(is_destructive_sql("DELETE FROM foo WHERE id = 1;"));
+    }
+
+    #[test]
+    fn destructive_keyword_in_comment() {
+        assert!(!is_destructive_sql(
+            "-- We cannot DROP the table yet\nCREATE TABLE foo (id INTEGER);"
+        ));
+    }
+
+    #[sqlx::test]
+    async fn schema_up_to_date(pool: sqlx::PgPool) {
+        match get_schema_status(&pool).await.unwrap() {
+            SchemaStatus::UpToDate { version } => assert_eq!(version, expected_schema_version()),
+            _ => panic!("Schema should be up to date"),
+        }
+    }
+}
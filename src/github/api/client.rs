@@ -1,27 +1,37 @@
 use anyhow::Context;
+use http::header::{ETAG, IF_NONE_MATCH};
+use http::{HeaderMap, HeaderValue, StatusCode};
 use octocrab::Octocrab;
 use octocrab::models::checks::CheckRun;
-use octocrab::models::{App, CheckRunId, CheckSuiteId, RunId};
+use octocrab::models::reactions::ReactionContent;
+use octocrab::models::{App, CheckRunId, CheckSuiteId, CommentId, JobId, RunId, UserId};
 use octocrab::params::checks::{CheckRunConclusion, CheckRunStatus};
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
+use std::sync::Arc;
 use tracing::log;
 
-use crate::bors::event::PullRequestComment;
-use crate::bors::{Comment, WorkflowRun};
+use crate::bors::event::{PullRequestComment, ReactionTarget};
+use crate::bors::{BuildArtifact, BuildWorkflowRun, Comment, RunningWorkflowRun, WorkflowRun};
 use crate::config::{CONFIG_FILE_PATH, RepositoryConfig};
 use crate::database::WorkflowStatus;
+use crate::github::api::GitHubUrls;
+use crate::github::api::etag_cache::{ETAG_CACHE_METRICS, EtagCache};
 use crate::github::api::operations::{
     BranchUpdateError, ForcePush, MergeError, create_check_run, merge_branches,
-    set_branch_to_commit, update_check_run,
+    set_branch_to_commit, update_check_run, update_check_run_output,
+};
+use crate::github::api::rate_limit::RateLimitExceeded;
+use crate::github::{
+    CommitSha, GithubRepoName, GithubUser, PullRequest, PullRequestNumber, parse_workflow_job_names,
 };
-use crate::github::{CommitSha, GithubRepoName, PullRequest, PullRequestNumber};
 use crate::utils::timing::{RetryMethod, RetryableOpError, ShouldRetry, perform_retryable};
 use futures::TryStreamExt;
 use octocrab::models::workflows::Job;
 use serde::de::DeserializeOwned;
 
 /// Provides access to a single app installation (repository) using the GitHub API.
+#[derive(Clone)]
 pub struct GithubRepositoryClient {
     app: App,
     /// The client caches the access token for this given repository and refreshes it once it
@@ -30,14 +40,19 @@ pub struct GithubRepositoryClient {
     // We store the name separately, because repository has an optional owner, but at this point
     // we must always have some owner of the repo.
     repo_name: GithubRepoName,
+    urls: GitHubUrls,
+    /// Responses of frequently read endpoints, used for conditional requests.
+    etag_cache: Arc<EtagCache>,
 }
 
 impl GithubRepositoryClient {
-    pub fn new(app: App, client: Octocrab, repo_name: GithubRepoName) -> Self {
+    pub fn new(app: App, client: Octocrab, repo_name: GithubRepoName, urls: GitHubUrls) -> Self {
         Self {
             app,
             client,
             repo_name,
+            urls,
+            etag_cache: Default::default(),
         }
     }
 
@@ -49,6 +64,24 @@ impl GithubRepositoryClient {
         &self.repo_name
     }
 
+    /// Returns the "noreply" e-mail address of `user`, which attributes commits to their account
+    /// without revealing their real e-mail address.
+    fn noreply_email(&self, user: &GithubUser) -> String {
+        let host = self
+            .urls
+            .web
+            .split("://")
+            .last()
+            .unwrap_or_default()
+            .trim_end_matches('/');
+        format!("{}+{}@users.noreply.{host}", user.id, user.username)
+    }
+
+    /// Returns the URL of the repository in the GitHub web interface.
+    pub fn repository_url(&self) -> String {
+        format!("{}/{}", self.urls.web, self.repo_name)
+    }
+
     /// Was the comment created by the bot?
     pub async fn is_comment_internal(&self, comment: &PullRequestComment) -> anyhow::Result<bool> {
         Ok(comment.author.html_url == self.app.html_url)
@@ -104,32 +137,474 @@ impl GithubRepositoryClient {
         let commit_sha = perform_retryable("get_branch_sha", RetryMethod::default(), || async {
             // https://docs.github.com/en/rest/branches/branches?apiVersion=2022-11-28#get-a-branch
             let branch: octocrab::models::repos::Branch = self
-                .get_request(&format!("branches/{name}"))
+                .get_cached_request(&format!("branches/{name}"))
                 .await
-                .context("Cannot deserialize branch")?;
-            anyhow::Ok(CommitSha(branch.commit.sha))
+                .context("Cannot deserialize branch")
+                .map_err(|error| {
+                    // There is no need to retry if the branch does not exist
+                    if error.is::<ResourceNotFound>() {
+                        ShouldRetry::No(error)
+                    } else {
+                        ShouldRetry::from(error)
+                    }
+                })?;
+            Ok::<_, ShouldRetry<anyhow::Error>>(CommitSha(branch.commit.sha))
         })
         .await?;
         Ok(commit_sha)
     }
 
+    /// Returns true if `ancestor` is reachable from `descendant` (or if both commits are the
+    /// same). Returns false if one of the commits does not exist.
+    pub async fn is_ancestor(
+        &self,
+        ancestor: &CommitSha,
+        descendant: &CommitSha,
+    ) -> anyhow::Result<bool> {
+        #[derive(serde::Deserialize, Debug)]
+        struct CompareResponse {
+            status: String,
+        }
+
+        let reachable = perform_retryable("is_ancestor", RetryMethod::default(), || async {
+            // https://docs.github.com/en/rest/commits/commits?apiVersion=2022-11-28#compare-two-commits
+            match self
+                .get_request::<CompareResponse>(&format!("compare/{ancestor}...{descendant}"))
+                .await
+            {
+                Ok(response) => Ok(matches!(response.status.as_str(), "ahead" | "identical")),
+                Err(error) if error.is::<ResourceNotFound>() => Ok(false),
+                Err(error) => Err(ShouldRetry::from(error)),
+            }
+        })
+        .await?;
+        Ok(reachable)
+    }
+
+    /// Returns true if `head` changes any files compared to `base`, i.e. if the diff between
+    /// `head` and its merge base with `base` is not empty.
+    pub async fn has_changes(&self, base: &CommitSha, head: &CommitSha) -> anyhow::Result<bool> {
+        #[derive(serde::Deserialize, Debug)]
+        struct CompareResponse {
+            files: Option<Vec<serde_json::Value>>,
+        }
+
+        let changed = perform_retryable("has_changes", RetryMethod::default(), || async {
+            // https://docs.github.com/en/rest/commits/commits?apiVersion=2022-11-28#compare-two-commits
+            let response: CompareResponse = self
+                .get_request(&format!("compare/{base}...{head}"))
+                .await
+                .with_context(|| format!("Cannot compare {base} with {head}"))?;
+            // If the list of files is missing, assume that there are changes
+            anyhow::Ok(response.files.is_none_or(|files| !files.is_empty()))
+        })
+        .await?;
+        Ok(changed)
+    }
+
+    /// Returns true if `old_head` and `new_head` make the same changes compared to their merge
+    /// bases with `base`, e.g. if `new_head` is a rebase of `old_head` that did not modify any
+    /// of its changes. Line numbers of the changes are ignored. If the diff of a file is not
+    /// available (e.g. because it is binary or too large), the diffs are considered different.
+    pub async fn has_same_diff(
+        &self,
+        base: &CommitSha,
+        old_head: &CommitSha,
+        new_head: &CommitSha,
+    ) -> anyhow::Result<bool> {
+        let old_diff = self.get_diff(base, old_head).await?;
+        let new_diff = self.get_diff(base, new_head).await?;
+        Ok(old_diff.is_some() && old_diff == new_diff)
+    }
+
+    /// Returns the changed files of `head` compared to its merge base with `base`, sorted by
+    /// their name, with the line numbers removed from their diffs. Returns `None` if the diff of
+    /// some file is not available.
+    async fn get_diff(
+        &self,
+        base: &CommitSha,
+        head: &CommitSha,
+    ) -> anyhow::Result<Option<Vec<(String, String, String)>>> {
+        #[derive(serde::Deserialize, Debug)]
+        struct CompareResponse {
+            files: Option<Vec<ComparedFile>>,
+        }
+
+        #[derive(serde::Deserialize, Debug)]
+        struct ComparedFile {
+            filename: String,
+            status: String,
+            patch: Option<String>,
+        }
+
+        let diff = perform_retryable("get_diff", RetryMethod::default(), || async {
+            // https://docs.github.com/en/rest/commits/commits?apiVersion=2022-11-28#compare-two-commits
+            let response: CompareResponse = self
+                .get_request(&format!("compare/{base}...{head}"))
+                .await
+                .with_context(|| format!("Cannot compare {base} with {head}"))?;
+            let Some(files) = response.files else {
+                return anyhow::Ok(None);
+            };
+            let mut diff = Vec::with_capacity(files.len());
+            for file in files {
+                let Some(patch) = file.patch else {
+                    return Ok(None);
+                };
+                // Hunk headers contain the line numbers, which change when the base is modified
+                let patch = patch
+                    .lines()
+                    .filter(|line| !line.starts_with("@@"))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                diff.push((file.filename, file.status, patch));
+            }
+            diff.sort();
+            Ok(Some(diff))
+        })
+        .await?;
+        Ok(diff)
+    }
+
+    /// Returns the parents of each commit that is reachable from `head`, but not from `base`,
+    /// from the oldest commit. At most 250 commits are returned.
+    pub async fn get_commit_parents_between(
+        &self,
+        base: &CommitSha,
+        head: &CommitSha,
+    ) -> anyhow::Result<Vec<Vec<CommitSha>>> {
+        #[derive(serde::Deserialize, Debug)]
+        struct CompareResponse {
+            commits: Vec<ComparedCommit>,
+        }
+
+        #[derive(serde::Deserialize, Debug)]
+        struct ComparedCommit {
+            parents: Vec<CommitParent>,
+        }
+
+        #[derive(serde::Deserialize, Debug)]
+        struct CommitParent {
+            sha: String,
+        }
+
+        let parents = perform_retryable(
+            "get_commit_parents_between",
+            RetryMethod::default(),
+            || async {
+                // https://docs.github.com/en/rest/commits/commits?apiVersion=2022-11-28#compare-two-commits
+                let response: CompareResponse = self
+                    .get_request(&format!("compare/{base}...{head}"))
+                    .await
+                    .with_context(|| format!("Cannot compare {base} with {head}"))?;
+                anyhow::Ok(
+                    response
+                        .commits
+                        .into_iter()
+                        .map(|commit| {
+                            commit
+                                .parents
+                                .into_iter()
+                                .map(|parent| CommitSha(parent.sha))
+                                .collect()
+                        })
+                        .collect(),
+                )
+            },
+        )
+        .await?;
+        Ok(parents)
+    }
+
+    /// Checks that the GitHub App token used for this repository is valid, by loading its rate
+    /// limit status, which does not count against the rate limit.
+    pub async fn check_token(&self) -> anyhow::Result<()> {
+        // https://docs.github.com/en/rest/rate-limit/rate-limit?apiVersion=2022-11-28#get-rate-limit-status-for-the-authenticated-user
+        self.client
+            .get::<serde_json::Value, _, _>("/rate_limit", None::<&()>)
+            .await
+            .context("Cannot load the rate limit status")?;
+        Ok(())
+    }
+
+    /// Return the contexts of status checks required by the branch protection of the given
+    /// branch. Returns no contexts if the branch is not protected.
+    pub async fn get_required_status_checks(&self, branch: &str) -> anyhow::Result<Vec<String>> {
+        #[derive(serde::Deserialize, Debug)]
+        struct RequiredStatusChecksResponse {
+            contexts: Vec<String>,
+        }
+
+        let contexts = perform_retryable(
+            "get_required_status_checks",
+            RetryMethod::default(),
+            || async {
+                // https://docs.github.com/en/rest/branches/branch-protection?apiVersion=2022-11-28#get-status-checks-protection
+                let url = format!(
+                    "/repos/{}/branches/{branch}/protection/required_status_checks",
+                    self.repository()
+                );
+                let response = self.client._get(url.as_str()).await?;
+                let status = response.status();
+                if let Some(limit) = RateLimitExceeded::from_response(status, response.headers()) {
+                    return Err(limit.into());
+                }
+                let text = self.client.body_to_string(response).await?;
+                match status {
+                    StatusCode::OK => {
+                        let response: RequiredStatusChecksResponse = serde_json::from_str(&text)
+                            .context("Cannot deserialize required status checks")?;
+                        anyhow::Ok(response.contexts)
+                    }
+                    StatusCode::NOT_FOUND => anyhow::Ok(vec![]),
+                    _ => Err(anyhow::anyhow!(
+                        "Cannot load required status checks of branch {branch}: {status} ({text})"
+                    )),
+                }
+            },
+        )
+        .await?;
+        Ok(contexts)
+    }
+
+    /// Return the IDs and names of the jobs defined in the GitHub Actions workflows of the
+    /// repository at the given commit. Returns no names if the repository has no workflows.
+    pub async fn get_workflow_job_names(&self, sha: &CommitSha) -> anyhow::Result<Vec<String>> {
+        let files = self.get_workflow_files(sha).await?;
+        Ok(files
+            .iter()
+            .flat_map(|(_, content)| parse_workflow_job_names(content))
+            .collect())
+    }
+
+    /// Return the paths and contents of the GitHub Actions workflow files of the repository at
+    /// the given commit. Returns no files if the repository has no workflows.
+    pub async fn get_workflow_files(
+        &self,
+        sha: &CommitSha,
+    ) -> anyhow::Result<Vec<(String, String)>> {
+        let files = perform_retryable("get_workflow_files", RetryMethod::default(), || async {
+            // https://docs.github.com/en/rest/repos/contents?apiVersion=2022-11-28#get-repository-content
+            let url = format!(
+                "/repos/{}/contents/.github/workflows?ref={sha}",
+                self.repository()
+            );
+            let response = self.client._get(url.as_str()).await?;
+            let status = response.status();
+            if let Some(limit) = RateLimitExceeded::from_response(status, response.headers()) {
+                return Err(limit.into());
+            }
+            let text = self.client.body_to_string(response).await?;
+            let files: Vec<octocrab::models::repos::Content> = match status {
+                StatusCode::OK => {
+                    serde_json::from_str(&text).context("Cannot deserialize workflow files")?
+                }
+                StatusCode::NOT_FOUND => return anyhow::Ok(vec![]),
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Cannot list workflow files: {status} ({text})"
+                    ));
+                }
+            };
+
+            let mut contents = vec![];
+            for file in files {
+                if file.r#type != "file"
+                    || !(file.name.ends_with(".yml") || file.name.ends_with(".yaml"))
+                {
+                    continue;
+                }
+                let content: octocrab::models::repos::Content = self
+                    .get_request(&format!("contents/{}?ref={sha}", file.path))
+                    .await
+                    .with_context(|| format!("Cannot load workflow file {}", file.path))?;
+                let content = content
+                    .decoded_content()
+                    .ok_or_else(|| anyhow::anyhow!("Cannot decode workflow file {}", file.path))?;
+                contents.push((file.path, content));
+            }
+            anyhow::Ok(contents)
+        })
+        .await?;
+        Ok(files)
+    }
+
+    /// Return the content of the configuration file at the given commit, or `None` if the
+    /// file does not exist at that commit.
+    pub async fn get_config_content(&self, sha: &CommitSha) -> anyhow::Result<Option<String>> {
+        let content = perform_retryable("get_config_content", RetryMethod::default(), || async {
+            // https://docs.github.com/en/rest/repos/contents?apiVersion=2022-11-28#get-repository-content
+            let url = format!(
+                "/repos/{}/contents/{CONFIG_FILE_PATH}?ref={sha}",
+                self.repository()
+            );
+            let response = self.client._get(url.as_str()).await?;
+            let status = response.status();
+            if let Some(limit) = RateLimitExceeded::from_response(status, response.headers()) {
+                return Err(limit.into());
+            }
+            let text = self.client.body_to_string(response).await?;
+            match status {
+                StatusCode::OK => {
+                    let content: octocrab::models::repos::Content = serde_json::from_str(&text)
+                        .with_context(|| format!("Cannot deserialize {CONFIG_FILE_PATH}"))?;
+                    let content = content.decoded_content().ok_or_else(|| {
+                        anyhow::anyhow!("Cannot decode {CONFIG_FILE_PATH} at {sha}")
+                    })?;
+                    anyhow::Ok(Some(content))
+                }
+                StatusCode::NOT_FOUND => anyhow::Ok(None),
+                _ => Err(anyhow::anyhow!(
+                    "Cannot load {CONFIG_FILE_PATH} at {sha}: {status} ({text})"
+                )),
+            }
+        })
+        .await?;
+        Ok(content)
+    }
+
+    /// Return the paths of all files modified by the given pull request.
+    pub async fn get_pull_request_files(
+        &self,
+        pr: PullRequestNumber,
+    ) -> anyhow::Result<Vec<String>> {
+        let files = perform_retryable("get_pull_request_files", RetryMethod::default(), || async {
+            let stream = self
+                .client
+                .pulls(self.repository().owner(), self.repository().name())
+                .list_files(pr.0)
+                .await
+                .with_context(|| format!("Could not list files of {}", self.format_pr(pr)))?
+                .into_stream(&self.client);
+
+            let mut stream = std::pin::pin!(stream);
+            let mut files = Vec::new();
+            while let Some(file) = stream.try_next().await? {
+                files.push(file.filename);
+            }
+            anyhow::Ok(files)
+        })
+        .await?;
+        Ok(files)
+    }
+
+    /// Return the SHAs of the commits of the given pull request, from the oldest one.
+    /// GitHub only returns the first 250 commits of a pull request.
+    pub async fn get_pull_request_commits(
+        &self,
+        pr: PullRequestNumber,
+    ) -> anyhow::Result<Vec<CommitSha>> {
+        #[derive(serde::Deserialize, Debug)]
+        struct PullRequestCommit {
+            sha: String,
+        }
+
+        const PER_PAGE: usize = 100;
+
+        let commits = perform_retryable(
+            "get_pull_request_commits",
+            RetryMethod::default(),
+            || async {
+                // https://docs.github.com/en/rest/pulls/pulls?apiVersion=2022-11-28#list-commits-on-a-pull-request
+                let mut commits = Vec::new();
+                for page in 1.. {
+                    let response: Vec<PullRequestCommit> = self
+                        .get_request(&format!(
+                            "pulls/{pr}/commits?per_page={PER_PAGE}&page={page}"
+                        ))
+                        .await
+                        .with_context(|| {
+                            format!("Could not list commits of {}", self.format_pr(pr))
+                        })?;
+                    let last_page = response.len() < PER_PAGE;
+                    commits.extend(response.into_iter().map(|commit| CommitSha(commit.sha)));
+                    if last_page {
+                        break;
+                    }
+                }
+                anyhow::Ok(commits)
+            },
+        )
+        .await?;
+        Ok(commits)
+    }
+
     /// Resolve a pull request from this repository by it's number.
     pub async fn get_pull_request(&self, pr: PullRequestNumber) -> anyhow::Result<PullRequest> {
         let prs = perform_retryable("get_pull_request", RetryMethod::default(), || async {
-            let pr = self
-                .client
-                .pulls(self.repository().owner(), self.repository().name())
-                .get(pr.0)
+            // https://docs.github.com/en/rest/pulls/pulls?apiVersion=2022-11-28#get-a-pull-request
+            let pr: octocrab::models::pulls::PullRequest = self
+                .get_cached_request(&format!("pulls/{}", pr.0))
                 .await
-                .map_err(|error| {
-                    anyhow::anyhow!("Could not get PR {}/{}: {error:?}", self.repository(), pr.0)
-                })?;
+                .with_context(|| format!("Could not get PR {}/{}", self.repository(), pr.0))?;
             anyhow::Ok(PullRequest::from(pr))
         })
         .await?;
         Ok(prs)
     }
 
+    /// Returns the ID of the GitHub user with the given login, or `None` if there is no such user.
+    pub async fn get_user_id(&self, login: &str) -> anyhow::Result<Option<UserId>> {
+        #[derive(serde::Deserialize, Debug)]
+        struct User {
+            id: UserId,
+        }
+
+        let user_id = perform_retryable("get_user_id", RetryMethod::default(), || async {
+            // https://docs.github.com/en/rest/users/users?apiVersion=2022-11-28#get-a-user
+            match self
+                .get_url_request::<User>(format!("/users/{login}"))
+                .await
+            {
+                Ok(user) => Ok(Some(user.id)),
+                Err(error) if error.is::<ResourceNotFound>() => Ok(None),
+                Err(error) => Err(ShouldRetry::from(error)),
+            }
+        })
+        .await?;
+        Ok(user_id)
+    }
+
+    /// Returns the IDs of the members of the given team, including the members of its child
+    /// teams, or `None` if there is no such team (or if the app cannot see it).
+    pub async fn get_team_member_ids(
+        &self,
+        org: &str,
+        team: &str,
+    ) -> anyhow::Result<Option<Vec<UserId>>> {
+        #[derive(serde::Deserialize, Debug)]
+        struct Member {
+            id: UserId,
+        }
+
+        const PER_PAGE: usize = 100;
+
+        let members = perform_retryable("get_team_member_ids", RetryMethod::default(), || async {
+            // https://docs.github.com/en/rest/teams/members?apiVersion=2022-11-28#list-team-members
+            let mut members = Vec::new();
+            for page in 1.. {
+                let response: Vec<Member> = match self
+                    .get_url_request(format!(
+                        "/orgs/{org}/teams/{team}/members?per_page={PER_PAGE}&page={page}"
+                    ))
+                    .await
+                {
+                    Ok(response) => response,
+                    Err(error) if error.is::<ResourceNotFound>() => return Ok(None),
+                    Err(error) => return Err(ShouldRetry::from(error)),
+                };
+                let last_page = response.len() < PER_PAGE;
+                members.extend(response.into_iter().map(|member| member.id));
+                if last_page {
+                    break;
+                }
+            }
+            Ok(Some(members))
+        })
+        .await?;
+        Ok(members)
+    }
+
     /// Post a comment to the pull request with the given number.
     /// The comment will be posted as the Github App user of the bot.
     pub async fn post_comment(
@@ -148,6 +623,84 @@ impl GithubRepositoryClient {
         Ok(comment)
     }
 
+    /// Closes the pull request with the given number.
+    pub async fn close_pull_request(&self, pr: PullRequestNumber) -> anyhow::Result<()> {
+        perform_retryable("close_pull_request", RetryMethod::default(), || async {
+            self.client
+                .pulls(self.repository().owner(), self.repository().name())
+                .update(pr.0)
+                .state(octocrab::params::pulls::State::Closed)
+                .send()
+                .await
+                .with_context(|| format!("Cannot close {}", self.format_pr(pr)))
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Reply to the review comment thread started by the comment with the given `thread` ID.
+    pub async fn reply_to_review_comment(
+        &self,
+        pr: PullRequestNumber,
+        thread: CommentId,
+        comment: Comment,
+    ) -> anyhow::Result<()> {
+        let url = format!(
+            "/repos/{}/{}/pulls/{}/comments/{}/replies",
+            self.repo_name.owner(),
+            self.repo_name.name(),
+            pr.0,
+            thread.0
+        );
+        let body = serde_json::json!({ "body": comment.render() });
+        perform_retryable(
+            "reply_to_review_comment",
+            RetryMethod::default(),
+            || async {
+                self.client
+                    .post::<_, serde_json::Value>(url.as_str(), Some(&body))
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "Cannot reply to review comment {thread} on {}",
+                            self.format_pr(pr)
+                        )
+                    })
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Add a reaction to the given comment.
+    pub async fn add_reaction(
+        &self,
+        target: ReactionTarget,
+        reaction: ReactionContent,
+    ) -> anyhow::Result<()> {
+        let (kind, id) = match target {
+            ReactionTarget::IssueComment(id) => ("issues", id),
+            ReactionTarget::ReviewComment(id) => ("pulls", id),
+        };
+        let url = format!(
+            "/repos/{}/{}/{kind}/comments/{}/reactions",
+            self.repo_name.owner(),
+            self.repo_name.name(),
+            id.0
+        );
+        let body = serde_json::json!({ "content": reaction });
+        perform_retryable("add_reaction", RetryMethod::default(), || async {
+            self.client
+                .post::<_, serde_json::Value>(url.as_str(), Some(&body))
+                .await
+                .with_context(|| {
+                    format!("Cannot add reaction to comment {id} in {}", self.repo_name)
+                })
+        })
+        .await?;
+        Ok(())
+    }
+
     /// Set the given branch to a commit with the given `sha`.
     pub async fn set_branch_to_sha(
         &self,
@@ -162,6 +715,16 @@ impl GithubRepositoryClient {
                     error @ (BranchUpdateError::Conflict(_)
                     | BranchUpdateError::ValidationFailed(_)
                     | BranchUpdateError::BranchNotFound(_)) => ShouldRetry::No(error),
+                    BranchUpdateError::RateLimited(limit) => ShouldRetry::RateLimited(
+                        BranchUpdateError::RateLimited(limit.clone()),
+                        limit,
+                    ),
+                    BranchUpdateError::OctocrabError(error) => {
+                        match RateLimitExceeded::from_octocrab(&error) {
+                            Some(limit) => ShouldRetry::RateLimited(error.into(), limit),
+                            None => ShouldRetry::Yes(error.into()),
+                        }
+                    }
                     error => ShouldRetry::Yes(error),
                 })
         })
@@ -186,6 +749,15 @@ impl GithubRepositoryClient {
                     error @ (MergeError::AlreadyMerged
                     | MergeError::Conflict
                     | MergeError::NotFound) => ShouldRetry::No(error),
+                    MergeError::RateLimited(limit) => {
+                        ShouldRetry::RateLimited(MergeError::RateLimited(limit.clone()), limit)
+                    }
+                    MergeError::NetworkError(error) => {
+                        match RateLimitExceeded::from_octocrab(&error) {
+                            Some(limit) => ShouldRetry::RateLimited(error.into(), limit),
+                            None => ShouldRetry::Yes(error.into()),
+                        }
+                    }
                     error => ShouldRetry::Yes(error),
                 })
         })
@@ -196,6 +768,143 @@ impl GithubRepositoryClient {
         })
     }
 
+    /// Create a commit with the same content (tree) as the commit `sha`, but with `parent` as its
+    /// only parent, and the given commit message and author.
+    /// This squashes all changes between `parent` and `sha` into a single commit.
+    pub async fn create_squashed_commit(
+        &self,
+        sha: &CommitSha,
+        parent: &CommitSha,
+        message: &str,
+        author: &GithubUser,
+    ) -> anyhow::Result<CommitSha> {
+        self.create_commit_with_tree_of_by(
+            "create_squashed_commit",
+            sha,
+            parent,
+            message,
+            Some(author),
+        )
+        .await
+    }
+
+    /// Create a commit on top of `commit` that reverts it, i.e. that restores the content (tree)
+    /// of `commit_parent`, the parent of `commit`.
+    pub async fn create_revert_commit(
+        &self,
+        commit: &CommitSha,
+        commit_parent: &CommitSha,
+        message: &str,
+    ) -> anyhow::Result<CommitSha> {
+        self.create_commit_with_tree_of("create_revert_commit", commit_parent, commit, message)
+            .await
+    }
+
+    /// Create a commit with the same content (tree) as the commit `sha`, with `parent` as its
+    /// only parent.
+    pub(super) async fn create_commit_with_tree_of(
+        &self,
+        operation: &'static str,
+        sha: &CommitSha,
+        parent: &CommitSha,
+        message: &str,
+    ) -> anyhow::Result<CommitSha> {
+        self.create_commit_with_tree_of_by(operation, sha, parent, message, None)
+            .await
+    }
+
+    /// Same as [`Self::create_commit_with_tree_of`], but the commit is authored by `author`.
+    /// Without an author, GitHub attributes the commit to the app.
+    async fn create_commit_with_tree_of_by(
+        &self,
+        operation: &'static str,
+        sha: &CommitSha,
+        parent: &CommitSha,
+        message: &str,
+        author: Option<&GithubUser>,
+    ) -> anyhow::Result<CommitSha> {
+        #[derive(serde::Deserialize, Debug)]
+        struct GitObject {
+            sha: String,
+        }
+
+        #[derive(serde::Deserialize, Debug)]
+        struct GitCommit {
+            sha: String,
+            tree: GitObject,
+        }
+
+        let commit_sha = perform_retryable(operation, RetryMethod::default(), || async {
+            // https://docs.github.com/en/rest/git/commits?apiVersion=2022-11-28#get-a-commit-object
+            let commit: GitCommit = self
+                .get_request(&format!("git/commits/{sha}"))
+                .await
+                .context("Cannot load commit")?;
+
+            // https://docs.github.com/en/rest/git/commits?apiVersion=2022-11-28#create-a-commit
+            let url = format!("/repos/{}/git/commits", self.repository());
+            let mut body = serde_json::json!({
+                "message": message,
+                "tree": commit.tree.sha,
+                "parents": [parent.as_ref()],
+            });
+            if let Some(author) = author {
+                body["author"] = serde_json::json!({
+                    "name": author.username,
+                    "email": self.noreply_email(author),
+                });
+            }
+            let created: GitCommit = self
+                .client
+                .post(url.as_str(), Some(&body))
+                .await
+                .context("Cannot create commit")?;
+            anyhow::Ok(CommitSha(created.sha))
+        })
+        .await?;
+        Ok(commit_sha)
+    }
+
+    /// Open a pull request from the `head` branch into the `base` branch.
+    /// The request is not retried, to avoid opening the same pull request twice.
+    pub async fn create_pull_request(
+        &self,
+        title: &str,
+        head: &str,
+        base: &str,
+        body: &str,
+    ) -> anyhow::Result<PullRequest> {
+        let pr = self
+            .client
+            .pulls(self.repository().owner(), self.repository().name())
+            .create(title, head, base)
+            .body(body)
+            .send()
+            .await
+            .with_context(|| format!("Could not open a PR from `{head}` into `{base}`"))?;
+        Ok(PullRequest::from(pr))
+    }
+
+    /// Find an open pull request whose head is the `head` branch of this repository.
+    pub async fn find_open_pull_request(&self, head: &str) -> anyhow::Result<Option<PullRequest>> {
+        let pr = perform_retryable("find_open_pull_request", RetryMethod::default(), || async {
+            // https://docs.github.com/en/rest/pulls/pulls?apiVersion=2022-11-28#list-pull-requests
+            let page = self
+                .client
+                .pulls(self.repo_name.owner(), self.repo_name.name())
+                .list()
+                .state(octocrab::params::State::Open)
+                .head(format!("{}:{head}", self.repo_name.owner()))
+                .per_page(1)
+                .send()
+                .await
+                .with_context(|| format!("Could not find an open PR from `{head}`"))?;
+            anyhow::Ok(page.items.into_iter().next().map(PullRequest::from))
+        })
+        .await?;
+        Ok(pr)
+    }
+
     /// Create a check run for the given commit.
     pub async fn create_check_run(
         &self,
@@ -208,7 +917,7 @@ impl GithubRepositoryClient {
         let check_run = perform_retryable("create_check_run", RetryMethod::no_retry(), || {
             let output = output.clone();
             async {
-                create_check_run(self, name, head_sha, status, output, external_id)
+                create_check_run(self, name, head_sha, status, None, output, external_id)
                     .await
                     .context("Cannot create check run")
             }
@@ -217,6 +926,60 @@ impl GithubRepositoryClient {
         Ok(check_run)
     }
 
+    /// Create an already completed check run for the given commit.
+    /// This can be used for check runs that only present information, and which do not track
+    /// the progress of anything.
+    pub async fn create_completed_check_run(
+        &self,
+        name: &str,
+        head_sha: &CommitSha,
+        conclusion: CheckRunConclusion,
+        output: CheckRunOutput,
+        external_id: &str,
+    ) -> anyhow::Result<CheckRun> {
+        let check_run = perform_retryable(
+            "create_completed_check_run",
+            RetryMethod::no_retry(),
+            || {
+                let output = output.clone();
+                async {
+                    create_check_run(
+                        self,
+                        name,
+                        head_sha,
+                        CheckRunStatus::Completed,
+                        Some(conclusion),
+                        output,
+                        external_id,
+                    )
+                    .await
+                    .context("Cannot create check run")
+                }
+            },
+        )
+        .await?;
+        Ok(check_run)
+    }
+
+    /// Update the title and summary of a check run with the given check run ID.
+    pub async fn update_check_run_output(
+        &self,
+        check_run_id: CheckRunId,
+        output: CheckRunOutput,
+    ) -> anyhow::Result<CheckRun> {
+        let check_run =
+            perform_retryable("update_check_run_output", RetryMethod::no_retry(), || {
+                let output = output.clone();
+                async {
+                    update_check_run_output(self, check_run_id, output)
+                        .await
+                        .context("Cannot update check run")
+                }
+            })
+            .await?;
+        Ok(check_run)
+    }
+
     /// Update a check run with the given check run ID.
     pub async fn update_check_run(
         &self,
@@ -250,43 +1013,301 @@ impl GithubRepositoryClient {
             workflow_runs: Vec<WorkflowRunResponse>,
         }
 
-        let runs = perform_retryable("get_workflows_for_check_suite", RetryMethod::default(), || async {
-            // We use a manual query, because octocrab currently doesn't allow filtering by
-            // check_suite_id when listing workflow runs.
-            // Note: we don't handle paging here, as we don't expect to get more than 30 workflows
-            // per check suite.
-            let response: WorkflowRunsResponse = self
-                .get_request(&format!("actions/runs?check_suite_id={check_suite_id}"))
-                .await
-                .context("Cannot fetch workflow runs for a check suite")?;
-
-            fn get_status(run: &WorkflowRunResponse) -> WorkflowStatus {
-                match run.status.as_str() {
-                    "completed" => match run.conclusion.as_deref() {
-                        Some("success") => WorkflowStatus::Success,
-                        Some(_) => WorkflowStatus::Failure,
-                        None => {
-                            tracing::warn!("Received completed status with empty conclusion for workflow run {}", run.id);
-                            WorkflowStatus::Failure
+        let runs = perform_retryable(
+            "get_workflows_for_check_suite",
+            RetryMethod::default(),
+            || async {
+                // We use a manual query, because octocrab currently doesn't allow filtering by
+                // check_suite_id when listing workflow runs.
+                // Note: we don't handle paging here, as we don't expect to get more than 30 workflows
+                // per check suite.
+                let response: WorkflowRunsResponse = self
+                    .get_cached_request(&format!("actions/runs?check_suite_id={check_suite_id}"))
+                    .await
+                    .context("Cannot fetch workflow runs for a check suite")?;
+
+                let runs: Vec<WorkflowRun> = response
+                    .workflow_runs
+                    .into_iter()
+                    .map(|run| WorkflowRun {
+                        id: run.id,
+                        status: workflow_run_status(run.id, &run.status, run.conclusion.as_deref()),
+                    })
+                    .collect();
+                anyhow::Ok(runs)
+            },
+        )
+        .await?;
+        Ok(runs)
+    }
+
+    /// Find all workflow runs started for the given commit on the given branch.
+    pub async fn get_workflow_runs_for_commit(
+        &self,
+        branch: &str,
+        sha: &CommitSha,
+    ) -> anyhow::Result<Vec<BuildWorkflowRun>> {
+        #[derive(serde::Deserialize, Debug)]
+        struct WorkflowRunResponse {
+            id: RunId,
+            name: String,
+            html_url: String,
+            check_suite_id: CheckSuiteId,
+            status: String,
+            conclusion: Option<String>,
+            created_at: chrono::DateTime<chrono::Utc>,
+            updated_at: chrono::DateTime<chrono::Utc>,
+        }
+
+        #[derive(serde::Deserialize, Debug)]
+        struct WorkflowRunsResponse {
+            workflow_runs: Vec<WorkflowRunResponse>,
+        }
+
+        let runs = perform_retryable(
+            "get_workflow_runs_for_commit",
+            RetryMethod::default(),
+            || async {
+                // Note: we don't handle paging here, as we don't expect to have more than 100
+                // workflows per build.
+                let response: WorkflowRunsResponse = self
+                    .get_cached_request(&format!(
+                        "actions/runs?branch={branch}&head_sha={sha}&per_page=100"
+                    ))
+                    .await
+                    .context("Cannot fetch workflow runs for a commit")?;
+                let runs: Vec<BuildWorkflowRun> = response
+                    .workflow_runs
+                    .into_iter()
+                    .map(|run| {
+                        let status =
+                            workflow_run_status(run.id, &run.status, run.conclusion.as_deref());
+                        BuildWorkflowRun {
+                            id: run.id,
+                            name: run.name,
+                            url: run.html_url,
+                            check_suite_id: run.check_suite_id,
+                            running_time: (status != WorkflowStatus::Pending)
+                                .then(|| run.updated_at - run.created_at),
+                            status,
                         }
-                    },
-                    "failure" | "startup_failure" => WorkflowStatus::Failure,
-                    _ => WorkflowStatus::Pending
+                    })
+                    .collect();
+                anyhow::Ok(runs)
+            },
+        )
+        .await?;
+        Ok(runs)
+    }
+
+    /// Returns the names of the branches of the repository that start with the given prefix.
+    pub async fn get_branches_with_prefix(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+        #[derive(serde::Deserialize, Debug)]
+        struct GitRef {
+            #[serde(rename = "ref")]
+            name: String,
+        }
+
+        let branches = perform_retryable(
+            "get_branches_with_prefix",
+            RetryMethod::default(),
+            || async {
+                // https://docs.github.com/en/rest/git/refs?apiVersion=2022-11-28#list-matching-references
+                let refs: Vec<GitRef> = self
+                    .get_request(&format!("git/matching-refs/heads/{prefix}"))
+                    .await
+                    .with_context(|| format!("Cannot list branches starting with {prefix}"))?;
+                anyhow::Ok(
+                    refs.into_iter()
+                        .filter_map(|git_ref| {
+                            git_ref
+                                .name
+                                .strip_prefix("refs/heads/")
+                                .map(|name| name.to_string())
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            },
+        )
+        .await?;
+        Ok(branches)
+    }
+
+    /// Find all workflow runs on the given branch that are queued or in progress.
+    pub async fn get_running_workflow_runs(
+        &self,
+        branch: &str,
+    ) -> anyhow::Result<Vec<RunningWorkflowRun>> {
+        #[derive(serde::Deserialize, Debug)]
+        struct WorkflowRunResponse {
+            id: RunId,
+            head_branch: String,
+            head_sha: String,
+        }
+
+        #[derive(serde::Deserialize, Debug)]
+        struct WorkflowRunsResponse {
+            workflow_runs: Vec<WorkflowRunResponse>,
+        }
+
+        const PER_PAGE: usize = 100;
+
+        let runs = perform_retryable(
+            "get_running_workflow_runs",
+            RetryMethod::default(),
+            || async {
+                let mut runs = vec![];
+                // GitHub only allows filtering by a single status at a time.
+                for status in ["queued", "in_progress"] {
+                    for page in 1.. {
+                        let path = format!(
+                            "actions/runs?branch={branch}&status={status}&per_page={PER_PAGE}&page={page}"
+                        );
+                        let response: WorkflowRunsResponse = self
+                            .get_cached_request(&path)
+                            .await
+                            .context("Cannot fetch running workflow runs")?;
+                        let last_page = response.workflow_runs.len() < PER_PAGE;
+                        runs.extend(response.workflow_runs.into_iter().map(|run| {
+                            RunningWorkflowRun {
+                                id: run.id,
+                                branch: run.head_branch,
+                                commit_sha: CommitSha(run.head_sha),
+                            }
+                        }));
+                        if last_page {
+                            break;
+                        }
+                    }
                 }
-            }
+                anyhow::Ok(runs)
+            },
+        )
+        .await?;
+        Ok(runs)
+    }
 
-            let runs: Vec<WorkflowRun> = response
-                .workflow_runs
-                .into_iter()
-                .map(|run| WorkflowRun {
-                    id: run.id,
-                    status: get_status(&run),
-                })
-                .collect();
-            anyhow::Ok(runs)
+    /// Find all (non-expired) artifacts uploaded by a workflow run with the given ID.
+    pub async fn get_artifacts_for_workflow_run(
+        &self,
+        run_id: RunId,
+    ) -> anyhow::Result<Vec<BuildArtifact>> {
+        #[derive(serde::Deserialize, Debug)]
+        struct ArtifactResponse {
+            id: u64,
+            name: String,
+            size_in_bytes: u64,
+            expired: bool,
+        }
+
+        #[derive(serde::Deserialize, Debug)]
+        struct ArtifactsResponse {
+            artifacts: Vec<ArtifactResponse>,
+        }
+
+        let artifacts = perform_retryable(
+            "get_artifacts_for_workflow_run",
+            RetryMethod::default(),
+            || async {
+                // Note: we don't handle paging here, as we don't expect to get more than 100
+                // artifacts per workflow run.
+                let response: ArtifactsResponse = self
+                    .get_request(&format!("actions/runs/{run_id}/artifacts?per_page=100"))
+                    .await
+                    .context("Cannot fetch artifacts for a workflow run")?;
+                anyhow::Ok(response)
+            },
+        )
+        .await?;
+
+        Ok(artifacts
+            .artifacts
+            .into_iter()
+            .filter(|artifact| !artifact.expired)
+            .map(|artifact| BuildArtifact {
+                url: format!(
+                    "{}/actions/runs/{run_id}/artifacts/{}",
+                    self.repository_url(),
+                    artifact.id
+                ),
+                name: artifact.name,
+                size: Some(artifact.size_in_bytes),
+            })
+            .collect())
+    }
+
+    /// Delete all (non-expired) artifacts uploaded by a workflow run with the given ID.
+    /// Returns the number of deleted artifacts.
+    pub async fn delete_artifacts_for_workflow_run(&self, run_id: RunId) -> anyhow::Result<usize> {
+        #[derive(serde::Deserialize, Debug)]
+        struct ArtifactResponse {
+            id: u64,
+            expired: bool,
+        }
+
+        #[derive(serde::Deserialize, Debug)]
+        struct ArtifactsResponse {
+            artifacts: Vec<ArtifactResponse>,
+        }
+
+        let deleted = perform_retryable(
+            "delete_artifacts_for_workflow_run",
+            RetryMethod::default(),
+            || async {
+                let response: ArtifactsResponse = self
+                    .get_request(&format!("actions/runs/{run_id}/artifacts?per_page=100"))
+                    .await
+                    .context("Cannot fetch artifacts for a workflow run")?;
+                let mut deleted = 0;
+                for artifact in response.artifacts.iter().filter(|a| !a.expired) {
+                    // https://docs.github.com/en/rest/actions/artifacts?apiVersion=2022-11-28#delete-an-artifact
+                    let url = format!(
+                        "/repos/{}/actions/artifacts/{}",
+                        self.repository(),
+                        artifact.id
+                    );
+                    let response = self.client._delete(url, None::<&()>).await?;
+                    let status = response.status();
+                    // The artifact could have been deleted in the meantime
+                    if !status.is_success() && status != StatusCode::NOT_FOUND {
+                        return Err(anyhow::anyhow!(
+                            "Cannot delete artifact {} of workflow run {run_id}: {status}",
+                            artifact.id
+                        ));
+                    }
+                    deleted += 1;
+                }
+                anyhow::Ok(deleted)
+            },
+        )
+        .await?;
+        Ok(deleted)
+    }
+
+    /// Dispatch the GitHub Actions workflow with the given file name on the given branch.
+    pub async fn dispatch_workflow(
+        &self,
+        workflow: &str,
+        branch: &str,
+        inputs: serde_json::Value,
+    ) -> anyhow::Result<()> {
+        perform_retryable("dispatch_workflow", RetryMethod::default(), || async {
+            self.client
+                .actions()
+                .create_workflow_dispatch(
+                    self.repo_name.owner(),
+                    self.repo_name.name(),
+                    workflow,
+                    branch,
+                )
+                .inputs(inputs.clone())
+                .send()
+                .await
+                .with_context(|| format!("Cannot dispatch workflow {workflow}"))?;
+            anyhow::Ok(())
         })
         .await?;
-        Ok(runs)
+        Ok(())
     }
 
     /// Find all jobs for the latest execution of a workflow run with the given ID.
@@ -320,6 +1341,27 @@ impl GithubRepositoryClient {
         Ok(jobs)
     }
 
+    /// Download the plain text log of a workflow job with the given ID.
+    pub async fn get_job_logs(&self, job_id: JobId) -> anyhow::Result<String> {
+        let logs = perform_retryable("get_job_logs", RetryMethod::default(), || async {
+            // https://docs.github.com/en/rest/actions/workflow-jobs?apiVersion=2022-11-28#download-job-logs-for-a-workflow-run
+            // The endpoint redirects to a temporary URL from which the logs can be downloaded.
+            let url = format!("/repos/{}/actions/jobs/{job_id}/logs", self.repository());
+            let response = self.client._get(url.as_str()).await?;
+            let response = self.client.follow_location_to_data(response).await?;
+            let status = response.status();
+            let text = self.client.body_to_string(response).await?;
+            if !status.is_success() {
+                return Err(anyhow::anyhow!(
+                    "Cannot download logs of job {job_id}: {status}"
+                ));
+            }
+            anyhow::Ok(text)
+        })
+        .await?;
+        Ok(logs)
+    }
+
     /// Cancels Github Actions workflows.
     pub async fn cancel_workflows(&self, run_ids: &[RunId]) -> anyhow::Result<()> {
         perform_retryable("cancel_workflows", RetryMethod::no_retry(), || async {
@@ -410,9 +1452,7 @@ impl GithubRepositoryClient {
                     .per_page(100)
                     .send()
                     .await
-                    .map_err(|error| {
-                        anyhow::anyhow!("Could not fetch PRs from {}: {error:?}", self.repo_name)
-                    })?
+                    .with_context(|| format!("Could not fetch PRs from {}", self.repo_name))?
                     .into_stream(&self.client);
 
                 let mut stream = std::pin::pin!(stream);
@@ -433,8 +1473,79 @@ impl GithubRepositoryClient {
             self.repo_name.owner(),
             self.repo_name.name(),
         );
+        self.get_url_request(url).await
+    }
+
+    /// Sends a conditional GET request to an API endpoint of the repository. If the response has
+    /// not changed since the last request of the same path, the cached response is used, which
+    /// does not count against the rate limit.
+    async fn get_cached_request<T: DeserializeOwned + Debug>(
+        &self,
+        path: &str,
+    ) -> anyhow::Result<T> {
+        let url = format!(
+            "/repos/{}/{}/{path}",
+            self.repo_name.owner(),
+            self.repo_name.name(),
+        );
+        tracing::debug!("Sending conditional request to {url}");
+        let cached = self.etag_cache.get(&url);
+        let mut headers = HeaderMap::new();
+        if let Some(cached) = &cached {
+            headers.insert(IF_NONE_MATCH, HeaderValue::from_str(&cached.etag)?);
+        }
+        let response = self
+            .client
+            ._get_with_headers(url.as_str(), Some(headers))
+            .await?;
+        let status = response.status();
+        if let Some(limit) = RateLimitExceeded::from_response(status, response.headers()) {
+            return Err(limit.into());
+        }
+        let body = match (status, cached) {
+            (StatusCode::NOT_MODIFIED, Some(cached)) => {
+                ETAG_CACHE_METRICS.record_hit();
+                cached.body
+            }
+            (StatusCode::OK, _) => {
+                ETAG_CACHE_METRICS.record_miss();
+                let etag = response
+                    .headers()
+                    .get(ETAG)
+                    .and_then(|etag| etag.to_str().ok())
+                    .map(|etag| etag.to_string());
+                let body = self.client.body_to_string(response).await?;
+                if let Some(etag) = etag {
+                    self.etag_cache.insert(&url, etag, body.clone());
+                }
+                body
+            }
+            (StatusCode::NOT_FOUND, _) => return Err(ResourceNotFound(url).into()),
+            _ => {
+                let text = self.client.body_to_string(response).await?;
+                return Err(anyhow::anyhow!(
+                    "Request to {url} failed: {status} ({text})"
+                ));
+            }
+        };
+        let response: T = serde_json::from_str(&body)
+            .with_context(|| format!("Cannot deserialize response of {url}"))?;
+        tracing::debug!("Received response: {response:?}");
+        Ok(response)
+    }
+
+    /// Sends a GET request to an API endpoint that does not belong to the repository.
+    async fn get_url_request<T: DeserializeOwned + Debug>(&self, url: String) -> anyhow::Result<T> {
         tracing::debug!("Sending request to {url}");
-        let response: T = self.client.get(url.as_str(), None::<&()>).await?;
+        let response: T = match self.client.get(url.as_str(), None::<&()>).await {
+            Ok(response) => response,
+            Err(octocrab::Error::GitHub { source, .. })
+                if source.status_code == StatusCode::NOT_FOUND =>
+            {
+                return Err(ResourceNotFound(url).into());
+            }
+            Err(error) => return Err(error.into()),
+        };
         tracing::debug!("Received response: {response:?}");
         Ok(response)
     }
@@ -470,9 +1581,10 @@ impl GithubRepositoryClient {
             result: T,
         }
 
+        // The GraphQL endpoint is not under the REST API URL on GitHub Enterprise Server
         let response = self
             .client
-            .graphql::<RawResponse<T>>(&Payload { query, variables })
+            .post::<_, RawResponse<T>>(&self.urls.graphql, Some(&Payload { query, variables }))
             .await
             .context("GraphQL request failed")?;
 
@@ -602,6 +1714,11 @@ impl GithubRepositoryClient {
     }
 }
 
+/// The resource requested by [`GithubRepositoryClient::get_request`] does not exist.
+#[derive(Debug, thiserror::Error)]
+#[error("Resource {0} was not found")]
+struct ResourceNotFound(String);
+
 /// The reasons a piece of content can be reported or hidden.
 ///
 /// GitHub Docs: <https://docs.github.com/en/graphql/reference/enums#reportedcontentclassifiers>
@@ -629,6 +1746,24 @@ pub struct CheckRunOutput {
     pub summary: String,
 }
 
+/// Converts the status and conclusion of a workflow run returned by the GitHub API.
+fn workflow_run_status(run_id: RunId, status: &str, conclusion: Option<&str>) -> WorkflowStatus {
+    match status {
+        "completed" => match conclusion {
+            Some("success") => WorkflowStatus::Success,
+            Some(_) => WorkflowStatus::Failure,
+            None => {
+                tracing::warn!(
+                    "Received completed status with empty conclusion for workflow run {run_id}"
+                );
+                WorkflowStatus::Failure
+            }
+        },
+        "failure" | "startup_failure" => WorkflowStatus::Failure,
+        _ => WorkflowStatus::Pending,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::github::GithubRepoName;
@@ -661,7 +1796,9 @@ mod tests {
         .await;
         let client = mock.github_client();
         let team_api_client = mock.team_api_client();
-        let mut repos = load_repositories(&client, &team_api_client).await.unwrap();
+        let mut repos = load_repositories(&client, &mock.github_urls(), &team_api_client)
+            .await
+            .unwrap();
         assert_eq!(repos.len(), 3);
 
         let repo = repos
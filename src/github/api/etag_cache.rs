@@ -0,0 +1,141 @@
+//! Cache of GitHub API responses for conditional requests.
+//!
+//! Responses of frequently read endpoints (e.g. PRs, branches and workflow runs) are stored
+//! together with their `ETag`. When the same URL is requested again, the `ETag` is sent in the
+//! `If-None-Match` header, and if the resource has not changed, GitHub responds with
+//! `304 Not Modified`, which does not count against the rate limit. This makes repeated ticks of
+//! an idle merge queue almost free. Hits and misses are collected in [`ETAG_CACHE_METRICS`].
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::Serialize;
+
+/// Maximum number of responses kept by a single cache. Once it is full, an arbitrary response is
+/// evicted to make room for a new one.
+const MAX_CACHED_RESPONSES: usize = 500;
+
+/// Responses of a single GitHub client, keyed by URL.
+#[derive(Default)]
+pub struct EtagCache {
+    responses: Mutex<HashMap<String, CachedResponse>>,
+}
+
+#[derive(Clone)]
+pub struct CachedResponse {
+    pub etag: String,
+    pub body: String,
+}
+
+impl EtagCache {
+    /// Returns the cached response of the given URL, if there is any.
+    pub fn get(&self, url: &str) -> Option<CachedResponse> {
+        self.responses.lock().unwrap().get(url).cloned()
+    }
+
+    /// Stores the response of the given URL, replacing any previous one.
+    pub fn insert(&self, url: &str, etag: String, body: String) {
+        let mut responses = self.responses.lock().unwrap();
+        if responses.len() >= MAX_CACHED_RESPONSES
+            && !responses.contains_key(url)
+            && let Some(evicted) = responses.keys().next().cloned()
+        {
+            responses.remove(&evicted);
+        }
+        responses.insert(url.to_string(), CachedResponse { etag, body });
+    }
+}
+
+/// Usage of the caches of all GitHub clients of this bors instance.
+pub struct EtagCacheMetrics {
+    /// Requests answered with `304 Not Modified`, so the cached response was used.
+    hits: AtomicU64,
+    /// Requests of cacheable endpoints that returned a new response.
+    misses: AtomicU64,
+}
+
+/// Cache metrics of all GitHub clients of this bors instance.
+pub static ETAG_CACHE_METRICS: EtagCacheMetrics = EtagCacheMetrics {
+    hits: AtomicU64::new(0),
+    misses: AtomicU64::new(0),
+};
+
+impl EtagCacheMetrics {
+    pub(crate) fn record_hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> EtagCacheSnapshot {
+        EtagCacheSnapshot {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A point-in-time view of [`EtagCacheMetrics`].
+#[derive(Debug, Serialize)]
+pub struct EtagCacheSnapshot {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use super::{EtagCache, MAX_CACHED_RESPONSES};
+    use crate::tests::{BorsTester, run_test};
+
+    #[test]
+    fn replace_cached_response() {
+        let cache = EtagCache::default();
+        assert!(cache.get("/a").is_none());
+        cache.insert("/a", "1".to_string(), "first".to_string());
+        cache.insert("/a", "2".to_string(), "second".to_string());
+        let response = cache.get("/a").unwrap();
+        assert_eq!(response.etag, "2");
+        assert_eq!(response.body, "second");
+    }
+
+    #[test]
+    fn evict_when_full() {
+        let cache = EtagCache::default();
+        for i in 0..MAX_CACHED_RESPONSES + 10 {
+            cache.insert(&format!("/{i}"), i.to_string(), String::new());
+        }
+        assert_eq!(cache.responses.lock().unwrap().len(), MAX_CACHED_RESPONSES);
+        assert!(
+            cache
+                .get(&format!("/{}", MAX_CACHED_RESPONSES + 9))
+                .is_some()
+        );
+    }
+
+    #[sqlx::test]
+    async fn unchanged_pr_is_read_from_cache(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            // Each command loads the PR from GitHub (unlike `ping`, which is answered right away)
+            tester.post_comment("@bors info").await?;
+            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors info").await?;
+            tester.expect_comments((), 1).await;
+
+            let (status, body) = tester
+                .web_request(http::Method::GET, "/api/v1/etag-cache", None)
+                .await?;
+            assert_eq!(status, StatusCode::OK);
+            let metrics: serde_json::Value = serde_json::from_str(&body)?;
+            // Metrics are shared by all tests running in parallel
+            assert!(metrics["hits"].as_u64().unwrap() >= 1);
+            assert!(metrics["misses"].as_u64().unwrap() >= 1);
+            Ok(())
+        })
+        .await;
+    }
+}
@@ -16,20 +16,66 @@ use crate::github::GithubRepoName;
 use crate::permissions::TeamApiClient;
 
 pub mod client;
+pub(crate) mod etag_cache;
 pub(crate) mod operations;
+pub(crate) mod rate_limit;
 
 pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// URL of the REST API of github.com.
+pub const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// URLs of the GitHub instance that bors talks to, either github.com or a GitHub Enterprise
+/// Server installation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GitHubUrls {
+    /// Base URL of the REST API.
+    pub api: String,
+    /// URL of the GraphQL endpoint.
+    pub graphql: String,
+    /// URL of the web interface, used for links to repositories and artifacts.
+    pub web: String,
+}
+
+impl GitHubUrls {
+    /// Creates the URLs of the instance with the given REST API URL.
+    /// URLs that are not provided are derived from the API URL. GitHub Enterprise Server serves
+    /// the REST API at `https://<host>/api/v3` and the GraphQL API at `https://<host>/api/graphql`.
+    pub fn new(api: &str, graphql: Option<&str>, web: Option<&str>) -> Self {
+        let api = api.trim_end_matches('/');
+        let (default_graphql, default_web) = if api == GITHUB_API_URL {
+            (format!("{api}/graphql"), "https://github.com".to_string())
+        } else if let Some(host) = api.strip_suffix("/api/v3") {
+            (format!("{host}/api/graphql"), host.to_string())
+        } else {
+            (format!("{api}/graphql"), api.to_string())
+        };
+        Self {
+            api: api.to_string(),
+            graphql: graphql.map(str::to_string).unwrap_or(default_graphql),
+            web: web
+                .map(|web| web.trim_end_matches('/').to_string())
+                .unwrap_or(default_web),
+        }
+    }
+}
+
+impl Default for GitHubUrls {
+    fn default() -> Self {
+        Self::new(GITHUB_API_URL, None, None)
+    }
+}
+
 pub fn create_github_client(
     app_id: AppId,
-    github_url: String,
+    urls: &GitHubUrls,
     private_key: SecretString,
 ) -> anyhow::Result<Octocrab> {
     let key = jsonwebtoken::EncodingKey::from_rsa_pem(private_key.expose_secret().as_bytes())
         .context("Could not encode private key")?;
 
     Octocrab::builder()
-        .base_uri(github_url)?
+        .base_uri(urls.api.as_str())?
         .app(app_id, key)
         .set_read_timeout(Some(DEFAULT_REQUEST_TIMEOUT))
         .set_write_timeout(Some(DEFAULT_REQUEST_TIMEOUT))
@@ -44,6 +90,7 @@ pub fn create_github_client(
 /// a warning when we reload the state during the bot's execution.
 pub async fn load_repositories(
     client: &Octocrab,
+    urls: &GitHubUrls,
     team_api_client: &TeamApiClient,
 ) -> anyhow::Result<HashMap<GithubRepoName, anyhow::Result<RepositoryState>>> {
     let installations = client
@@ -94,6 +141,7 @@ pub async fn load_repositories(
             let repo_state = create_repo_state(
                 app.clone(),
                 installation_client.clone(),
+                urls.clone(),
                 team_api_client,
                 name.clone(),
             )
@@ -144,24 +192,25 @@ fn parse_repo_name(repo: &Repository) -> anyhow::Result<GithubRepoName> {
 async fn create_repo_state(
     app: App,
     repo_client: Octocrab,
+    urls: GitHubUrls,
     team_api_client: &TeamApiClient,
     name: GithubRepoName,
 ) -> anyhow::Result<RepositoryState> {
     tracing::info!("Found repository {name}");
 
-    let client = GithubRepositoryClient::new(app, repo_client, name.clone());
+    let client = GithubRepositoryClient::new(app, repo_client, name.clone(), urls);
+
+    let config = load_config(&client).await?;
 
     let permissions = team_api_client
-        .load_permissions(&name)
+        .load_repository_permissions(&client, &config.permissions)
         .await
         .with_context(|| format!("Could not load permissions for repository {name}"))?;
 
-    let config = load_config(&client).await?;
-
     Ok(RepositoryState {
         client,
         config: ArcSwap::new(Arc::new(config)),
-        permissions: ArcSwap::new(Arc::new(permissions)),
+        permissions: ArcSwap::new(permissions),
     })
 }
 
@@ -177,3 +226,35 @@ async fn load_config(client: &GithubRepositoryClient) -> anyhow::Result<Reposito
         )),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::GitHubUrls;
+
+    #[test]
+    fn github_com_urls() {
+        let urls = GitHubUrls::default();
+        assert_eq!(urls.api, "https://api.github.com");
+        assert_eq!(urls.graphql, "https://api.github.com/graphql");
+        assert_eq!(urls.web, "https://github.com");
+    }
+
+    #[test]
+    fn enterprise_urls() {
+        let urls = GitHubUrls::new("https://github.acme.com/api/v3/", None, None);
+        assert_eq!(urls.api, "https://github.acme.com/api/v3");
+        assert_eq!(urls.graphql, "https://github.acme.com/api/graphql");
+        assert_eq!(urls.web, "https://github.acme.com");
+    }
+
+    #[test]
+    fn explicit_urls() {
+        let urls = GitHubUrls::new(
+            "https://api.acme.com",
+            Some("https://graphql.acme.com"),
+            Some("https://git.acme.com/"),
+        );
+        assert_eq!(urls.graphql, "https://graphql.acme.com");
+        assert_eq!(urls.web, "https://git.acme.com");
+    }
+}
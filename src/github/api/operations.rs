@@ -7,6 +7,7 @@ use thiserror::Error;
 
 use crate::github::CommitSha;
 use crate::github::api::client::{CheckRunOutput, GithubRepositoryClient};
+use crate::github::api::rate_limit::RateLimitExceeded;
 
 #[derive(Copy, Clone)]
 pub enum ForcePush {
@@ -28,6 +29,8 @@ pub enum MergeError {
     NetworkError(#[from] octocrab::Error),
     #[error("Request timed out")]
     Timeout,
+    #[error(transparent)]
+    RateLimited(RateLimitExceeded),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -70,6 +73,9 @@ pub async fn merge_branches(
     match response {
         Ok(response) => {
             let status = response.status();
+            if let Some(limit) = RateLimitExceeded::from_response(status, response.headers()) {
+                return Err(MergeError::RateLimited(limit));
+            }
             let text = client.body_to_string(response).await.unwrap_or_default();
 
             tracing::trace!(
@@ -152,6 +158,8 @@ pub enum BranchUpdateError {
     OctocrabError(#[from] octocrab::Error),
     #[error("Unknown error: {0}")]
     Custom(String),
+    #[error(transparent)]
+    RateLimited(RateLimitExceeded),
 }
 
 /// Force update the branch with the given `branch_name` to the given `sha`.
@@ -181,6 +189,9 @@ async fn update_branch(
         .await?;
 
     let status = res.status();
+    if let Some(limit) = RateLimitExceeded::from_response(status, res.headers()) {
+        return Err(BranchUpdateError::RateLimited(limit));
+    }
     tracing::trace!(
         "Updating branch response: status={}, text={:?}",
         status,
@@ -203,17 +214,25 @@ pub async fn create_check_run(
     name: &str,
     head_sha: &CommitSha,
     status: CheckRunStatus,
+    conclusion: Option<CheckRunConclusion>,
     output: CheckRunOutput,
     external_id: &str,
 ) -> Result<CheckRun, octocrab::Error> {
-    repo.client()
-        .checks(repo.repository().owner(), repo.repository().name())
+    let checks = repo
+        .client()
+        .checks(repo.repository().owner(), repo.repository().name());
+
+    let mut request = checks
         .create_check_run(name, head_sha.to_string())
         .external_id(external_id)
         .status(status)
-        .output(output.into())
-        .send()
-        .await
+        .output(output.into());
+
+    if let Some(conclusion) = conclusion {
+        request = request.conclusion(conclusion);
+    }
+
+    request.send().await
 }
 
 impl From<CheckRunOutput> for octocrab::params::checks::CheckRunOutput {
@@ -248,6 +267,19 @@ pub async fn update_check_run(
     request.send().await
 }
 
+pub async fn update_check_run_output(
+    repo: &GithubRepositoryClient,
+    check_run_id: CheckRunId,
+    output: CheckRunOutput,
+) -> Result<CheckRun, octocrab::Error> {
+    repo.client()
+        .checks(repo.repository().owner(), repo.repository().name())
+        .update_check_run(check_run_id)
+        .output(output.into())
+        .send()
+        .await
+}
+
 /// Attempts to merge a head commit into a base commit using a specified branch.
 pub async fn attempt_merge(
     client: &GithubRepositoryClient,
@@ -285,3 +317,57 @@ pub async fn attempt_merge(
         Err(error) => Err(error.into()),
     }
 }
+
+/// Cherry-picks `commit`, whose parent is `commit_parent`, onto `target_sha` using the specified
+/// branch. On success, the branch points to the cherry-picked commit.
+///
+/// GitHub has no API for cherry-picking, so it is emulated with a merge: `commit` is merged into
+/// a temporary commit that has the content of `commit_parent` and `target_sha` as its parent.
+/// The changes of the merge then correspond to the changes of `commit`, and its content is
+/// committed on top of `target_sha`.
+pub async fn cherry_pick(
+    client: &GithubRepositoryClient,
+    branch_name: &str,
+    commit: &CommitSha,
+    commit_parent: &CommitSha,
+    target_sha: &CommitSha,
+    message: &str,
+) -> anyhow::Result<MergeResult> {
+    tracing::debug!("Cherry-picking {commit} onto {target_sha} using branch {branch_name}");
+
+    let base_sha = client
+        .create_commit_with_tree_of(
+            "cherry_pick",
+            commit_parent,
+            target_sha,
+            &format!("Temporary commit for cherry-picking {commit}"),
+        )
+        .await?;
+    client
+        .set_branch_to_sha(branch_name, &base_sha, ForcePush::Yes)
+        .await
+        .map_err(|error| {
+            anyhow::anyhow!("Cannot set cherry-pick branch {branch_name} to {base_sha}: {error:?}")
+        })?;
+
+    let merge_sha = match client.merge_branches(branch_name, commit, message).await {
+        Ok(merge_sha) => merge_sha,
+        Err(MergeError::Conflict) => {
+            tracing::warn!("Cherry-pick conflict");
+            return Ok(MergeResult::Conflict);
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    let sha = client
+        .create_commit_with_tree_of("cherry_pick", &merge_sha, target_sha, message)
+        .await?;
+    client
+        .set_branch_to_sha(branch_name, &sha, ForcePush::Yes)
+        .await
+        .map_err(|error| {
+            anyhow::anyhow!("Cannot set cherry-pick branch {branch_name} to {sha}: {error:?}")
+        })?;
+    tracing::debug!("Cherry-pick successful, SHA: {sha}");
+    Ok(MergeResult::Success(sha))
+}
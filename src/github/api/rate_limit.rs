@@ -0,0 +1,271 @@
+//! Handling of GitHub API rate limits.
+//!
+//! When GitHub refuses a request because of a (primary or secondary) rate limit, the request is
+//! retried once the limit resets, instead of failing the operation right away. Limits that do not
+//! reset soon enough still fail the operation, so that e.g. the merge queue is not blocked for an
+//! hour. Usage of the rate limits is collected in [`RATE_LIMIT_METRICS`].
+
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+use http::{HeaderMap, StatusCode};
+use serde::Serialize;
+
+/// How long to wait before retrying a request that has exceeded a rate limit, if GitHub does not
+/// say how long to wait. GitHub recommends waiting at least one minute for secondary rate limits.
+#[cfg(not(test))]
+pub(crate) const DEFAULT_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(60);
+#[cfg(test)]
+pub(crate) const DEFAULT_RATE_LIMIT_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Requests whose rate limit resets later than this are not retried.
+pub(crate) const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60 * 2);
+
+/// How many times can a single request be retried because of rate limits.
+pub(crate) const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+
+/// The GitHub API refused a request because a rate limit was exceeded.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("GitHub API rate limit exceeded")]
+pub struct RateLimitExceeded {
+    /// How long to wait before the request can be retried, if GitHub has told us.
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitExceeded {
+    /// How long to wait before retrying the refused request.
+    pub fn backoff(&self) -> Duration {
+        self.retry_after.unwrap_or(DEFAULT_RATE_LIMIT_BACKOFF)
+    }
+
+    /// Checks if a raw GitHub API response refused the request because of a rate limit.
+    /// The `Retry-After` and `X-RateLimit-Reset` headers are used to find out when the request
+    /// can be retried. The rate limit usage reported by the response is also recorded in
+    /// [`RATE_LIMIT_METRICS`].
+    pub fn from_response(status: StatusCode, headers: &HeaderMap) -> Option<Self> {
+        RATE_LIMIT_METRICS.record_headers(headers);
+        if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+            return None;
+        }
+
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<i64>().ok())
+        };
+        if let Some(seconds) = header("retry-after") {
+            return Some(Self {
+                retry_after: Some(Duration::from_secs(seconds.max(0) as u64)),
+            });
+        }
+        if header("x-ratelimit-remaining") == Some(0) {
+            let retry_after = header("x-ratelimit-reset").map(|reset| {
+                let seconds = reset - chrono::Utc::now().timestamp();
+                Duration::from_secs(seconds.max(0) as u64)
+            });
+            return Some(Self { retry_after });
+        }
+        // Secondary rate limits are sometimes returned without any headers
+        (status == StatusCode::TOO_MANY_REQUESTS).then_some(Self { retry_after: None })
+    }
+
+    /// Checks if an octocrab error was caused by a rate limit.
+    /// Octocrab does not keep the response headers, so GitHub's hint of how long to wait is lost.
+    pub fn from_octocrab(error: &octocrab::Error) -> Option<Self> {
+        match error {
+            octocrab::Error::GitHub { source, .. }
+                if (source.status_code == StatusCode::FORBIDDEN
+                    || source.status_code == StatusCode::TOO_MANY_REQUESTS)
+                    && source.message.to_lowercase().contains("rate limit") =>
+            {
+                Some(Self { retry_after: None })
+            }
+            _ => None,
+        }
+    }
+
+    /// Finds a rate limit error in the chain of causes of the given error.
+    pub fn find(error: &anyhow::Error) -> Option<Self> {
+        error.chain().find_map(|cause| {
+            if let Some(limit) = cause.downcast_ref::<RateLimitExceeded>() {
+                Some(limit.clone())
+            } else {
+                cause
+                    .downcast_ref::<octocrab::Error>()
+                    .and_then(Self::from_octocrab)
+            }
+        })
+    }
+}
+
+/// Usage of the GitHub API rate limits by this bors instance.
+pub struct RateLimitMetrics {
+    /// Requests that were refused because of a rate limit.
+    rate_limited_requests: AtomicU64,
+    /// Refused requests that could not be retried, because the rate limit did not reset soon
+    /// enough.
+    failed_requests: AtomicU64,
+    /// Total time spent waiting for rate limits to reset, in milliseconds.
+    wait_time_ms: AtomicU64,
+    /// The last observed size of the rate limit, or -1 if unknown.
+    limit: AtomicI64,
+    /// The last observed number of remaining requests, or -1 if unknown.
+    remaining: AtomicI64,
+    /// The last observed time (UNIX timestamp) when the rate limit resets, or -1 if unknown.
+    reset: AtomicI64,
+}
+
+/// Rate limit metrics of all GitHub clients of this bors instance.
+pub static RATE_LIMIT_METRICS: RateLimitMetrics = RateLimitMetrics {
+    rate_limited_requests: AtomicU64::new(0),
+    failed_requests: AtomicU64::new(0),
+    wait_time_ms: AtomicU64::new(0),
+    limit: AtomicI64::new(-1),
+    remaining: AtomicI64::new(-1),
+    reset: AtomicI64::new(-1),
+};
+
+impl RateLimitMetrics {
+    pub(crate) fn record_rate_limited(&self) {
+        self.rate_limited_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_failure(&self) {
+        self.failed_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_wait(&self, duration: Duration) {
+        self.wait_time_ms
+            .fetch_add(duration.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Remembers the rate limit usage reported in the headers of a GitHub API response.
+    fn record_headers(&self, headers: &HeaderMap) {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<i64>().ok())
+        };
+        if let (Some(limit), Some(remaining), Some(reset)) = (
+            header("x-ratelimit-limit"),
+            header("x-ratelimit-remaining"),
+            header("x-ratelimit-reset"),
+        ) {
+            self.limit.store(limit, Ordering::Relaxed);
+            self.remaining.store(remaining, Ordering::Relaxed);
+            self.reset.store(reset, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> RateLimitSnapshot {
+        let known = |value: &AtomicI64| Some(value.load(Ordering::Relaxed)).filter(|v| *v >= 0);
+        RateLimitSnapshot {
+            rate_limited_requests: self.rate_limited_requests.load(Ordering::Relaxed),
+            failed_requests: self.failed_requests.load(Ordering::Relaxed),
+            wait_time_seconds: self.wait_time_ms.load(Ordering::Relaxed) as f64 / 1000.0,
+            limit: known(&self.limit),
+            remaining: known(&self.remaining),
+            reset: known(&self.reset),
+        }
+    }
+}
+
+/// A point-in-time view of [`RateLimitMetrics`].
+#[derive(Debug, Serialize)]
+pub struct RateLimitSnapshot {
+    pub rate_limited_requests: u64,
+    pub failed_requests: u64,
+    pub wait_time_seconds: f64,
+    /// The last observed size of the rate limit.
+    pub limit: Option<i64>,
+    /// The last observed number of remaining requests.
+    pub remaining: Option<i64>,
+    /// The last observed time (UNIX timestamp) when the rate limit resets.
+    pub reset: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use http::{HeaderMap, HeaderValue, StatusCode};
+
+    use super::RateLimitExceeded;
+    use crate::tests::{BorsTester, default_repo_name, run_test};
+
+    fn headers(values: &[(&'static str, String)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in values {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn retry_after_header() {
+        let limit = RateLimitExceeded::from_response(
+            StatusCode::FORBIDDEN,
+            &headers(&[("retry-after", "30".to_string())]),
+        )
+        .unwrap();
+        assert_eq!(limit.retry_after, Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn exhausted_primary_rate_limit() {
+        let reset = chrono::Utc::now().timestamp() + 100;
+        let limit = RateLimitExceeded::from_response(
+            StatusCode::FORBIDDEN,
+            &headers(&[
+                ("x-ratelimit-limit", "5000".to_string()),
+                ("x-ratelimit-remaining", "0".to_string()),
+                ("x-ratelimit-reset", reset.to_string()),
+            ]),
+        )
+        .unwrap();
+        let retry_after = limit.retry_after.unwrap();
+        assert!(retry_after > Duration::from_secs(90) && retry_after <= Duration::from_secs(100));
+    }
+
+    #[test]
+    fn forbidden_without_rate_limit() {
+        assert!(
+            RateLimitExceeded::from_response(
+                StatusCode::FORBIDDEN,
+                &headers(&[("x-ratelimit-remaining", "100".to_string())]),
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn secondary_rate_limit_without_headers() {
+        let limit =
+            RateLimitExceeded::from_response(StatusCode::TOO_MANY_REQUESTS, &HeaderMap::new())
+                .unwrap();
+        assert_eq!(limit.retry_after, None);
+    }
+
+    #[sqlx::test]
+    async fn rate_limited_comment_is_retried(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .modify_repo(&default_repo_name(), |repo| repo.rate_limited_comments = 2)
+                .await;
+            tester.post_comment("@bors ping").await?;
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @"Pong 🏓!");
+
+            let (status, body) = tester
+                .web_request(http::Method::GET, "/api/v1/rate-limit", None)
+                .await?;
+            assert_eq!(status, StatusCode::OK);
+            let metrics: serde_json::Value = serde_json::from_str(&body)?;
+            // Metrics are shared by all tests running in parallel
+            assert!(metrics["rate_limited_requests"].as_u64().unwrap() >= 2);
+            Ok(())
+        })
+        .await;
+    }
+}
@@ -11,6 +11,10 @@ pub enum LabelTrigger {
     AutoBuildSucceeded,
     /// An auto build triggered from the merge queue has failed.
     AutoBuildFailed,
+    /// An approved PR has developed merge conflicts with its base branch.
+    Conflicted,
+    /// A backport PR was opened by `@bors backport`. The labels are modified on the backport PR.
+    Backport,
 }
 
 #[derive(Debug, Eq, PartialEq)]
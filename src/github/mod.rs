@@ -11,11 +11,14 @@ mod error;
 mod labels;
 pub mod server;
 mod webhook;
+pub(crate) mod webhook_lag;
+mod workflow_jobs;
 
-pub use api::operations::{MergeResult, attempt_merge};
+pub use api::operations::{MergeResult, attempt_merge, cherry_pick};
 pub use error::AppError;
 pub use labels::{LabelModification, LabelTrigger};
-pub use webhook::WebhookSecret;
+pub use webhook::{WebhookPayload, WebhookSecret, WebhookSignature};
+pub use workflow_jobs::{is_triggered_by_push, jobs_outside_environment, parse_workflow_job_names};
 
 use crate::bors::PullRequestStatus;
 
@@ -122,10 +125,24 @@ pub struct PullRequest {
     pub assignees: Vec<GithubUser>,
     pub status: PullRequestStatus,
     pub labels: Vec<String>,
+    /// Was the PR opened from a fork of the repository?
+    pub from_fork: bool,
 }
 
 impl From<octocrab::models::pulls::PullRequest> for PullRequest {
     fn from(pr: octocrab::models::pulls::PullRequest) -> Self {
+        let repo_name = |repo: &octocrab::models::Repository| {
+            repo.owner
+                .as_ref()
+                .map(|owner| GithubRepoName::new(&owner.login, &repo.name))
+        };
+        let from_fork = match (pr.head.repo.as_ref(), pr.base.repo.as_ref()) {
+            (Some(head), Some(base)) => repo_name(head) != repo_name(base),
+            // The head repository is missing if the fork was deleted
+            (None, Some(_)) => true,
+            _ => false,
+        };
+
         Self {
             number: pr.number.into(),
             head_label: pr.head.label.unwrap_or_else(|| "<unknown>".to_string()),
@@ -164,6 +181,7 @@ impl From<octocrab::models::pulls::PullRequest> for PullRequest {
                 .into_iter()
                 .map(|l| l.name)
                 .collect(),
+            from_fork,
         }
     }
 }
@@ -1,56 +1,89 @@
-use crate::bors::event::BorsEvent;
-use crate::bors::merge_queue::{MergeQueueSender, start_merge_queue};
+use crate::bors::dead_letter::store_dead_letter;
+use crate::bors::event::{BorsEvent, QueuedRepositoryEvent};
+use crate::bors::health::{HEALTH_METRICS, check_health};
+use crate::bors::merge_queue::{MergeQueueSender, queue_overflow, start_merge_queue};
 use crate::bors::mergeability_queue::{
     MergeabilityQueueReceiver, MergeabilityQueueSender, check_mergeability,
     create_mergeability_queue,
 };
+use crate::bors::queue_skips::QUEUE_SKIPS;
+use crate::bors::status::check_status;
 use crate::bors::{
-    BorsContext, CommandPrefix, RepositoryState, RollupMode, handle_bors_global_event,
-    handle_bors_repository_event,
+    BorsContext, CommandPrefix, RepositoryState, RollupMode, deliver_outbox,
+    handle_bors_global_event, handle_bors_repository_event,
 };
 use crate::database::QueueStatus;
 use crate::github::webhook::GitHubWebhook;
-use crate::github::webhook::WebhookSecret;
+use crate::github::webhook::{WebhookSecret, WebhookSignature};
+use crate::github::webhook_lag::WEBHOOK_LAG_METRICS;
+use crate::secrets::{Secrets, SecretsProvider};
 use crate::templates::{
-    HelpTemplate, HtmlTemplate, NotFoundTemplate, PullRequestStats, QueueTemplate, RepositoryView,
+    BuildStatsTemplate, HelpTemplate, HtmlTemplate, NotFoundTemplate, PullRequestStats,
+    QueueTemplate, RepositoryView, StatusTemplate,
 };
-use crate::{BorsGlobalEvent, BorsRepositoryEvent, PgDbClient, TeamApiClient};
+use crate::utils::sort_queue::sort_queue_prs;
+use crate::{BorsGlobalEvent, PgDbClient};
 
 use super::AppError;
 use anyhow::Error;
-use axum::Router;
-use axum::extract::{Path, State};
+use arc_swap::ArcSwap;
+use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Redirect, Response};
 use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use chrono::Utc;
 use octocrab::Octocrab;
+use octocrab::models::AppId;
 use std::any::Any;
 use std::collections::HashMap;
 use std::future::Future;
+use std::net::SocketAddr;
 use std::pin::Pin;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
 use tower::limit::ConcurrencyLimitLayer;
 use tower_http::catch_panic::CatchPanicLayer;
 use tracing::{Instrument, Span};
 
 use super::GithubRepoName;
+use super::api::GitHubUrls;
+
+mod api;
+mod graphql;
+
+pub use api::{ApiToken, Role};
 
 /// Shared server state for all axum handlers.
 pub struct ServerState {
-    repository_event_queue: mpsc::Sender<BorsRepositoryEvent>,
+    repository_event_queue: mpsc::Sender<QueuedRepositoryEvent>,
     global_event_queue: mpsc::Sender<BorsGlobalEvent>,
-    webhook_secret: WebhookSecret,
+    /// Secrets used to authenticate requests. They can be swapped at runtime when they are
+    /// rotated.
+    secrets: ArcSwap<ServerSecrets>,
+    /// Backend from which the secrets are reloaded.
+    /// If it is not set, reloading secrets is disabled.
+    secrets_provider: Option<SecretsProvider>,
+    /// GitHub App whose client is created again with the reloaded private key.
+    /// If it is not set, the private key is not reloaded.
+    github_app: Option<(AppId, GitHubUrls)>,
     repositories: HashMap<GithubRepoName, Arc<RepositoryState>>,
     db: Arc<PgDbClient>,
     cmd_prefix: CommandPrefix,
+    webhook_signature: WebhookSignature,
 }
 
 impl ServerState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        repository_event_queue: mpsc::Sender<BorsRepositoryEvent>,
+        repository_event_queue: mpsc::Sender<QueuedRepositoryEvent>,
         global_event_queue: mpsc::Sender<BorsGlobalEvent>,
         webhook_secret: WebhookSecret,
+        api_token: Option<ApiToken>,
+        repository_api_tokens: HashMap<GithubRepoName, ApiToken>,
+        role_tokens: Vec<(ApiToken, Role)>,
         repositories: HashMap<GithubRepoName, Arc<RepositoryState>>,
         db: Arc<PgDbClient>,
         cmd_prefix: CommandPrefix,
@@ -58,15 +91,65 @@ impl ServerState {
         Self {
             repository_event_queue,
             global_event_queue,
-            webhook_secret,
+            secrets: ArcSwap::from_pointee(ServerSecrets {
+                webhook_secret,
+                api_token,
+                repository_api_tokens,
+                role_tokens,
+            }),
+            secrets_provider: None,
+            github_app: None,
             repositories,
             db,
             cmd_prefix,
+            webhook_signature: WebhookSignature::default(),
         }
     }
 
-    pub fn get_webhook_secret(&self) -> &WebhookSecret {
-        &self.webhook_secret
+    /// Enables reloading of secrets from the given `provider` at runtime.
+    pub fn with_secrets_provider(mut self, provider: SecretsProvider) -> Self {
+        self.secrets_provider = Some(provider);
+        self
+    }
+
+    /// Creates a new client of the given GitHub App whenever the secrets are reloaded, so that its
+    /// private key can be rotated.
+    pub fn with_github_app(mut self, app_id: AppId, urls: GitHubUrls) -> Self {
+        self.github_app = Some((app_id, urls));
+        self
+    }
+
+    /// Sets the algorithm with which the incoming webhooks are expected to be signed.
+    pub fn with_webhook_signature(mut self, signature: WebhookSignature) -> Self {
+        self.webhook_signature = signature;
+        self
+    }
+
+    pub fn webhook_signature(&self) -> WebhookSignature {
+        self.webhook_signature
+    }
+
+    pub fn get_secrets(&self) -> Arc<ServerSecrets> {
+        self.secrets.load_full()
+    }
+
+    /// Replaces the secrets used to authenticate requests.
+    /// Requests that are already being handled keep using the previous secrets.
+    fn set_secrets(&self, secrets: &Secrets) {
+        self.secrets.store(Arc::new(ServerSecrets {
+            webhook_secret: secrets.webhook_secret(),
+            api_token: secrets.api_token(),
+            repository_api_tokens: secrets.repository_api_tokens(),
+            role_tokens: secrets.role_tokens(),
+        }));
+    }
+
+    /// Returns the priority aging interval configured for the given repository, used to order
+    /// its merge queue.
+    fn priority_aging_interval(&self, repo: &GithubRepoName) -> Option<Duration> {
+        self.repositories
+            .get(repo)
+            .and_then(|repo| repo.config.load().priority_aging_interval)
     }
 
     pub fn get_cmd_prefix(&self) -> &CommandPrefix {
@@ -74,19 +157,218 @@ impl ServerState {
     }
 }
 
+/// Secrets used by the server to authenticate requests.
+pub struct ServerSecrets {
+    webhook_secret: WebhookSecret,
+    /// Token used to authenticate build results reported by external CI systems.
+    /// It can report results of builds of any repository.
+    api_token: Option<ApiToken>,
+    /// Tokens that can only report results of builds of a single repository.
+    /// If there are no tokens and `api_token` is not set, reporting build results is disabled.
+    repository_api_tokens: HashMap<GithubRepoName, ApiToken>,
+    /// Tokens used to authenticate administrative requests, such as the emergency stop, along
+    /// with the role that each token grants.
+    /// If there are no tokens, the administrative API is disabled.
+    role_tokens: Vec<(ApiToken, Role)>,
+}
+
+impl ServerSecrets {
+    pub fn get_webhook_secret(&self) -> &WebhookSecret {
+        &self.webhook_secret
+    }
+}
+
 pub type ServerStateRef = Arc<ServerState>;
 
+/// Group of routes of the HTTP server. Each listener of the server can serve a different set
+/// of groups, e.g. the webhook endpoint can be public, while the API is only served on
+/// localhost.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RouteGroup {
+    /// The GitHub webhook endpoint (`/github`).
+    Webhook,
+    /// The web pages (`/`, `/help` and `/queue/<repo>`).
+    Web,
+    /// The REST and GraphQL APIs (`/api/...`).
+    Api,
+}
+
+impl FromStr for RouteGroup {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "webhook" => Ok(RouteGroup::Webhook),
+            "web" => Ok(RouteGroup::Web),
+            "api" => Ok(RouteGroup::Api),
+            _ => Err(format!(
+                "Unknown route group `{s}`, expected `webhook`, `web` or `api`"
+            )),
+        }
+    }
+}
+
+/// A socket address on which the HTTP server listens, along with the routes served on it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ListenerConfig {
+    pub address: SocketAddr,
+    /// Prefix of the paths of all routes served by the listener (e.g. `/bors`), useful when the
+    /// server is deployed behind a reverse proxy. Empty if the routes are served from the root.
+    pub path_prefix: String,
+    /// Groups of routes served by the listener. The health check (`/health`) and the status page
+    /// (`/status`) are always served.
+    pub routes: Vec<RouteGroup>,
+}
+
+impl Default for ListenerConfig {
+    fn default() -> Self {
+        Self {
+            address: SocketAddr::from(([0, 0, 0, 0], 8080)),
+            path_prefix: String::new(),
+            routes: vec![RouteGroup::Webhook, RouteGroup::Web, RouteGroup::Api],
+        }
+    }
+}
+
+/// Parses a listener in the `[<group>+<group>...@]<address>[/<prefix>]` format, e.g.
+/// `webhook@0.0.0.0:8080/bors` or `api@127.0.0.1:9000`. If no groups are specified, all routes
+/// are served.
+impl FromStr for ListenerConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (routes, rest) = match s.split_once('@') {
+            Some((groups, rest)) => (
+                groups
+                    .split('+')
+                    .map(RouteGroup::from_str)
+                    .collect::<Result<Vec<_>, _>>()?,
+                rest,
+            ),
+            None => (ListenerConfig::default().routes, s),
+        };
+        let (address, path_prefix) = match rest.split_once('/') {
+            Some((address, prefix)) => (address, format!("/{}", prefix.trim_end_matches('/'))),
+            None => (rest, String::new()),
+        };
+        let address = address
+            .parse()
+            .map_err(|error| format!("Invalid listener address `{address}`: {error}"))?;
+        Ok(Self {
+            address,
+            path_prefix: if path_prefix == "/" {
+                String::new()
+            } else {
+                path_prefix
+            },
+            routes,
+        })
+    }
+}
+
+/// Path prefix of the listener that is serving a request, used to create links to other routes.
+#[derive(Clone)]
+struct PathPrefix(String);
+
+/// Creates a router that serves all routes from the root path.
 pub fn create_app(state: ServerState) -> Router {
-    Router::new()
-        .route("/", get(index_handler))
-        .route("/help", get(help_handler))
-        .route("/queue/{repo_name}", get(queue_handler))
-        .route("/github", post(github_webhook_handler))
+    create_listener_app(Arc::new(state), &ListenerConfig::default())
+}
+
+/// Creates a router that serves the routes of the given `listener`.
+/// The state can be shared by the routers of several listeners.
+pub fn create_listener_app(state: ServerStateRef, listener: &ListenerConfig) -> Router {
+    let mut router = Router::new()
         .route("/health", get(health_handler))
+        .route("/status", get(status_handler));
+    if listener.routes.contains(&RouteGroup::Webhook) {
+        router = router.route("/github", post(github_webhook_handler));
+    }
+    if listener.routes.contains(&RouteGroup::Web) {
+        router = router
+            .route("/", get(index_handler))
+            .route("/help", get(help_handler))
+            .route("/queue/{repo_name}", get(queue_handler))
+            .route("/stats/{repo_name}", get(stats_handler));
+    }
+    if listener.routes.contains(&RouteGroup::Api) {
+        router = router
+            .route("/api/v1/builds/{build_id}", get(api::get_build_handler))
+            .route(
+                "/api/v1/repos/{owner}/{repo}/queue",
+                get(api::get_queue_handler),
+            )
+            .route(
+                "/api/v1/repos/{owner}/{repo}/queue/trace",
+                get(api::get_queue_trace_handler),
+            )
+            .route(
+                "/api/v1/repos/{owner}/{repo}/queue/skips",
+                get(api::get_queue_skips_handler),
+            )
+            .route(
+                "/api/v1/repos/{owner}/{repo}/latency",
+                get(api::get_latency_handler),
+            )
+            .route(
+                "/api/v1/repos/{owner}/{repo}/build-stats",
+                get(api::get_build_stats_handler),
+            )
+            .route(
+                "/api/v1/repos/{owner}/{repo}/audit-log",
+                get(api::get_audit_log_handler),
+            )
+            .route(
+                "/api/v1/repos/{owner}/{repo}/config",
+                get(api::get_config_handler),
+            )
+            .route(
+                "/api/v1/repos/{owner}/{repo}/reload",
+                post(api::reload_repository_handler),
+            )
+            .route(
+                "/api/v1/repos/{owner}/{repo}/prs/{number}",
+                get(api::get_pull_request_handler),
+            )
+            .route(
+                "/api/v1/builds/{build_id}/result",
+                post(api::report_build_result_handler),
+            )
+            .route(
+                "/api/v1/emergency-stop",
+                get(api::get_emergency_stop_handler)
+                    .post(api::set_emergency_stop_handler)
+                    .delete(api::clear_emergency_stop_handler),
+            )
+            .route("/api/v1/secrets/reload", post(api::reload_secrets_handler))
+            .route("/api/v1/rate-limit", get(api::get_rate_limit_handler))
+            .route("/api/v1/etag-cache", get(api::get_etag_cache_handler))
+            .route("/api/v1/workers", get(api::get_workers_handler))
+            .route(
+                "/api/v1/zombie-workflows",
+                get(api::get_zombie_workflows_handler),
+            )
+            .route("/api/v1/webhook-lag", get(api::get_webhook_lag_handler))
+            .route("/api/v1/dead-letters", get(api::get_dead_letters_handler))
+            .route(
+                "/api/v1/dead-letters/replay",
+                post(api::replay_dead_letters_handler),
+            )
+            .route("/api/graphql", post(graphql::graphql_handler));
+    }
+    let router = router.fallback(not_found_handler);
+    let router = if listener.path_prefix.is_empty() {
+        router
+    } else {
+        Router::new()
+            .nest(&listener.path_prefix, router)
+            .fallback(not_found_handler)
+    };
+    router
+        .layer(Extension(PathPrefix(listener.path_prefix.clone())))
         .layer(ConcurrencyLimitLayer::new(100))
         .layer(CatchPanicLayer::custom(handle_panic))
-        .with_state(Arc::new(state))
-        .fallback(not_found_handler)
+        .with_state(state)
 }
 
 fn handle_panic(err: Box<dyn Any + Send + 'static>) -> Response {
@@ -94,20 +376,71 @@ fn handle_panic(err: Box<dyn Any + Send + 'static>) -> Response {
     (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response()
 }
 
-async fn not_found_handler() -> impl IntoResponse {
-    (StatusCode::NOT_FOUND, HtmlTemplate(NotFoundTemplate {}))
+async fn not_found_handler(
+    Extension(PathPrefix(prefix)): Extension<PathPrefix>,
+) -> impl IntoResponse {
+    (
+        StatusCode::NOT_FOUND,
+        HtmlTemplate(NotFoundTemplate { prefix }),
+    )
+}
+
+/// Reports the health of the components that bors depends on.
+/// Responds with `503 Service Unavailable` if any of them is unhealthy.
+async fn health_handler(State(state): State<ServerStateRef>) -> impl IntoResponse {
+    let repos: Vec<Arc<RepositoryState>> = state.repositories.values().cloned().collect();
+    let report = check_health(&state.db, &repos).await;
+    let status = if report.healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
+#[derive(serde::Deserialize)]
+struct StatusQuery {
+    format: Option<String>,
 }
 
-async fn health_handler() -> impl IntoResponse {
-    (StatusCode::OK, "")
+/// Public status page of this bors instance, rendered as HTML, or as JSON if it is requested
+/// with `?format=json` or with an `Accept: application/json` header.
+/// Responds with `503 Service Unavailable` if any component is unhealthy.
+async fn status_handler(
+    Query(query): Query<StatusQuery>,
+    headers: http::HeaderMap,
+    State(state): State<ServerStateRef>,
+) -> impl IntoResponse {
+    let repos: Vec<Arc<RepositoryState>> = state.repositories.values().cloned().collect();
+    let report = check_status(&state.db, &repos).await;
+    let status = if report.healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let json = match query.format.as_deref() {
+        Some(format) => format == "json",
+        None => headers
+            .get(http::header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .is_some_and(|accept| accept.contains("application/json")),
+    };
+    if json {
+        (status, Json(report)).into_response()
+    } else {
+        (status, HtmlTemplate(StatusTemplate::from(report))).into_response()
+    }
 }
 
-async fn index_handler(State(state): State<ServerStateRef>) -> impl IntoResponse {
+async fn index_handler(
+    State(state): State<ServerStateRef>,
+    Extension(PathPrefix(prefix)): Extension<PathPrefix>,
+) -> impl IntoResponse {
     // If we manage exactly one repo, redirect to its queue page directly
     if let Some(repo_name) = state.repositories.keys().next()
         && state.repositories.len() == 1
     {
-        return Redirect::temporary(&format!("/queue/{}", repo_name.name)).into_response();
+        return Redirect::temporary(&format!("{prefix}/queue/{}", repo_name.name)).into_response();
     }
     help_handler(State(state)).await.into_response()
 }
@@ -149,9 +482,12 @@ async fn queue_handler(
         }
     };
 
-    let prs = state.db.get_nonclosed_pull_requests(&repo.name).await?;
+    let prs = sort_queue_prs(
+        state.db.get_nonclosed_pull_requests(&repo.name).await?,
+        state.priority_aging_interval(&repo.name),
+    );
 
-    let (in_queue_count, failed_count, rolled_up_count) =
+    let (in_queue_count, failed_count, rolled_up_count): (usize, usize, usize) =
         prs.iter()
             .fold((0, 0, 0), |(in_queue, failed, rolled_up), pr| {
                 let (in_queue_inc, failed_inc) = match pr.queue_status() {
@@ -169,26 +505,74 @@ async fn queue_handler(
                 )
             });
 
+    let max_queue_size = state
+        .repositories
+        .get(&repo.name)
+        .and_then(|repo| repo.config.load().max_queue_size);
+    let overflow_count = queue_overflow(&prs, max_queue_size).len();
+
     Ok(HtmlTemplate(QueueTemplate {
         repo_name: repo.name.name().to_string(),
-        repo_url: format!("https://github.com/{}", repo.name),
+        repo_url: state
+            .repositories
+            .get(&repo.name)
+            .map(|repo| repo.client.repository_url())
+            .unwrap_or_else(|| format!("https://github.com/{}", repo.name)),
         tree_state: repo.tree_state,
         stats: PullRequestStats {
             total_count: prs.len(),
             in_queue_count,
             failed_count,
             rolled_up_count,
+            overflow_count,
         },
+        max_queue_size,
+        skip_reasons: QUEUE_SKIPS.latest(&repo.name),
         prs,
     })
     .into_response())
 }
 
+async fn stats_handler(
+    Path(repo_name): Path<String>,
+    Query(query): Query<api::BuildStatsQuery>,
+    State(state): State<ServerStateRef>,
+) -> Result<impl IntoResponse, AppError> {
+    let repo = match state.db.repo_by_name(&repo_name).await? {
+        Some(repo) => repo,
+        None => {
+            return Ok((
+                StatusCode::NOT_FOUND,
+                format!("Repository {repo_name} not found"),
+            )
+                .into_response());
+        }
+    };
+    let days = query.days.unwrap_or(api::DEFAULT_BUILD_STATS_DAYS);
+    if days < 1 {
+        return Ok((StatusCode::BAD_REQUEST, "`days` has to be positive").into_response());
+    }
+
+    let builds = state.db.get_build_stats(&repo.name, days).await?;
+    Ok(HtmlTemplate(BuildStatsTemplate {
+        repo_name: repo.name.name().to_string(),
+        repo_url: state
+            .repositories
+            .get(&repo.name)
+            .map(|repo| repo.client.repository_url())
+            .unwrap_or_else(|| format!("https://github.com/{}", repo.name)),
+        days,
+        builds: builds.into_iter().map(Into::into).collect(),
+    })
+    .into_response())
+}
+
 /// Axum handler that receives a webhook and sends it to a webhook channel.
 pub async fn github_webhook_handler(
     State(state): State<ServerStateRef>,
-    GitHubWebhook(event): GitHubWebhook,
+    GitHubWebhook(event, webhook): GitHubWebhook,
 ) -> impl IntoResponse {
+    HEALTH_METRICS.record_webhook(Utc::now());
     match event {
         BorsEvent::Global(e) => match state.global_event_queue.send(e).await {
             Ok(_) => (StatusCode::OK, ""),
@@ -197,7 +581,14 @@ pub async fn github_webhook_handler(
                 (StatusCode::INTERNAL_SERVER_ERROR, "")
             }
         },
-        BorsEvent::Repository(e) => match state.repository_event_queue.send(e).await {
+        BorsEvent::Repository(event) => match state
+            .repository_event_queue
+            .send(QueuedRepositoryEvent {
+                event,
+                webhook: Some(webhook),
+            })
+            .await
+        {
             Ok(_) => (StatusCode::OK, ""),
             Err(err) => {
                 tracing::error!("Could not send webhook repository event: {err:?}");
@@ -208,7 +599,7 @@ pub async fn github_webhook_handler(
 }
 
 pub struct BorsProcess {
-    pub repository_tx: mpsc::Sender<BorsRepositoryEvent>,
+    pub repository_tx: mpsc::Sender<QueuedRepositoryEvent>,
     pub global_tx: mpsc::Sender<BorsGlobalEvent>,
     pub merge_queue_tx: MergeQueueSender,
     pub mergeability_queue_tx: MergeabilityQueueSender,
@@ -220,10 +611,9 @@ pub struct BorsProcess {
 pub fn create_bors_process(
     ctx: BorsContext,
     gh_client: Octocrab,
-    team_api: TeamApiClient,
     merge_queue_max_interval: chrono::Duration,
 ) -> BorsProcess {
-    let (repository_tx, repository_rx) = mpsc::channel::<BorsRepositoryEvent>(1024);
+    let (repository_tx, repository_rx) = mpsc::channel::<QueuedRepositoryEvent>(1024);
     let (global_tx, global_rx) = mpsc::channel::<BorsGlobalEvent>(1024);
     let (mergeability_queue_tx, mergeability_queue_rx) = create_mergeability_queue();
     let mergeability_queue_tx2 = mergeability_queue_tx.clone();
@@ -234,52 +624,49 @@ pub fn create_bors_process(
         start_merge_queue(ctx.clone(), merge_queue_max_interval);
     let merge_queue_tx2 = merge_queue_tx.clone();
 
+    let merge_queue_tx3 = merge_queue_tx.clone();
+    let mergeability_queue_tx3 = mergeability_queue_tx.clone();
     let service = async move {
-        // In tests, we shutdown these futures by dropping the channel sender,
-        // In that case, we need to wait until both of these futures resolve,
-        // to make sure that they are able to handle all the events in the queue
-        // before finishing.
-        #[cfg(test)]
-        {
-            let _ = tokio::join!(
-                consume_repository_events(
+        // The process shuts down gracefully once all senders of the repository and global event
+        // queues have been dropped, i.e. once the webhook server and the periodic refresh have
+        // stopped. Events that were already received are still handled.
+        // The futures are large, so they are boxed to avoid overflowing the stack
+        let events = async {
+            tokio::join!(
+                Box::pin(consume_repository_events(
                     ctx.clone(),
                     repository_rx,
                     mergeability_queue_tx2.clone(),
                     merge_queue_tx2.clone()
-                ),
-                consume_global_events(
+                )),
+                Box::pin(consume_global_events(
                     ctx.clone(),
                     global_rx,
                     mergeability_queue_tx2,
                     merge_queue_tx2,
-                    gh_client,
-                    team_api
-                ),
-                consume_mergeability_queue(ctx.clone(), mergeability_queue_rx),
-                merge_queue_fut
+                    gh_client
+                )),
             );
+            tracing::info!("Event queues were closed, shutting down the merge queue");
+            // A merge queue tick that is in progress is finished before the shutdown is
+            // received, so that a branch push is never interrupted halfway
+            merge_queue_tx3.shutdown();
+            mergeability_queue_tx3.shutdown();
+        };
+        tokio::join!(
+            events,
+            Box::pin(consume_mergeability_queue(
+                ctx.clone(),
+                mergeability_queue_rx
+            )),
+            Box::pin(merge_queue_fut)
+        );
+
+        // Post comments and labels that could not be delivered before
+        if let Err(error) = deliver_outbox(&ctx).await {
+            tracing::error!("Cannot deliver outbox during shutdown: {error:?}");
         }
-        // In real execution, the bot runs forever. If there is something that finishes
-        // the futures early, it's essentially a bug.
-        // FIXME: maybe we could just use join for both versions of the code.
-        #[cfg(not(test))]
-        {
-            tokio::select! {
-                _ = consume_repository_events(ctx.clone(), repository_rx, mergeability_queue_tx2.clone(), merge_queue_tx2.clone()) => {
-                    tracing::error!("Repository event handling process has ended");
-                }
-                _ = consume_global_events(ctx.clone(), global_rx, mergeability_queue_tx2, merge_queue_tx2, gh_client, team_api) => {
-                    tracing::error!("Global event handling process has ended");
-                }
-                _ = consume_mergeability_queue(ctx.clone(), mergeability_queue_rx) => {
-                    tracing::error!("Mergeability queue handling process has ended")
-                }
-                _ = merge_queue_fut => {
-                    tracing::error!("Merge queue handling process has ended");
-                }
-            }
-        }
+        tracing::info!("Bors process has shut down");
     };
 
     BorsProcess {
@@ -293,21 +680,34 @@ pub fn create_bors_process(
 
 async fn consume_repository_events(
     ctx: Arc<BorsContext>,
-    mut repository_rx: mpsc::Receiver<BorsRepositoryEvent>,
+    mut repository_rx: mpsc::Receiver<QueuedRepositoryEvent>,
     mergeability_queue_tx: MergeabilityQueueSender,
     merge_queue_tx: MergeQueueSender,
 ) {
-    while let Some(event) = repository_rx.recv().await {
+    while let Some(QueuedRepositoryEvent { event, webhook }) = repository_rx.recv().await {
         let ctx = ctx.clone();
+        let db = ctx.db.clone();
         let mergeability_queue_tx = mergeability_queue_tx.clone();
+        let repository = event.repository().clone();
 
         let span = tracing::info_span!("RepositoryEvent");
         tracing::debug!("Received repository event: {event:?}");
-        if let Err(error) =
+        let result =
             handle_bors_repository_event(event, ctx, mergeability_queue_tx, merge_queue_tx.clone())
                 .instrument(span.clone())
-                .await
+                .await;
+        if let Some(webhook) = &webhook
+            && let Some(event_time) = webhook.event_time()
         {
+            WEBHOOK_LAG_METRICS.record(&webhook.event_type, event_time, Utc::now());
+        }
+        if let Err(error) = result {
+            // Keep the webhook, so that it can be replayed once the failure is resolved
+            if let Some(webhook) = webhook {
+                store_dead_letter(&db, &repository, &webhook, &error)
+                    .instrument(span.clone())
+                    .await;
+            }
             handle_root_error(span, error);
         }
     }
@@ -318,8 +718,7 @@ async fn consume_global_events(
     mut global_rx: mpsc::Receiver<BorsGlobalEvent>,
     mergeability_queue_tx: MergeabilityQueueSender,
     merge_queue_tx: MergeQueueSender,
-    gh_client: Octocrab,
-    team_api: TeamApiClient,
+    mut gh_client: Octocrab,
 ) {
     while let Some(event) = global_rx.recv().await {
         let ctx = ctx.clone();
@@ -331,8 +730,7 @@ async fn consume_global_events(
         if let Err(error) = handle_bors_global_event(
             event,
             ctx,
-            &gh_client,
-            &team_api,
+            &mut gh_client,
             mergeability_queue_tx,
             merge_queue_tx,
         )
@@ -379,3 +777,210 @@ fn handle_root_error(span: Span, error: Error) {
         span.log_error(error);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ListenerConfig, RouteGroup};
+    use crate::tests::{BorsTester, Comment, default_repo_name, run_test};
+
+    #[sqlx::test]
+    async fn queue_page_sorts_prs(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+            let pr3 = tester.open_pr(default_repo_name(), |_| {}).await?;
+            tester
+                .post_comment(Comment::new(pr2.id(), "@bors info"))
+                .await?;
+            tester.expect_comments(pr2.id(), 1).await;
+            tester
+                .post_comment(Comment::new(pr3.id(), "@bors r+"))
+                .await?;
+            tester.expect_comments(pr3.id(), 1).await;
+
+            let (status, body) = tester
+                .web_request(http::Method::GET, "/queue/borstest", None)
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+
+            // The approved PR comes first, even though it has a higher number
+            let pr2_pos = body.find("/pull/2\"").unwrap();
+            let pr3_pos = body.find("/pull/3\"").unwrap();
+            assert!(pr3_pos < pr2_pos);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn build_stats_page(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let (status, body) = tester
+                .web_request(http::Method::GET, "/stats/borstest", None)
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+            assert!(body.contains("No builds were started in this period."));
+
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester.finish_auto_build(()).await?;
+
+            let (status, body) = tester
+                .web_request(http::Method::GET, "/stats/borstest?days=7", None)
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+            assert!(body.contains("Builds started in the last 7 days"));
+            assert!(body.contains("<td>auto</td>"));
+            assert!(body.contains("<td>0.0%</td>"));
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn queue_page_theme_toggle(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let (status, body) = tester
+                .web_request(http::Method::GET, "/queue/borstest", None)
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+            assert!(body.contains(r#"id="theme-toggle""#));
+            assert!(body.contains("theme=${theme}; path=/"));
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn health_endpoint(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let (status, body) = tester
+                .web_request(http::Method::GET, "/health", None)
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+            let report: serde_json::Value = serde_json::from_str(&body)?;
+            assert_eq!(report["healthy"], true);
+            assert_eq!(report["database"]["healthy"], true);
+            assert_eq!(report["github"]["details"], "1 app token valid");
+            // Activity is shared by all tests running in parallel
+            assert_eq!(report["webhooks"]["healthy"], true);
+            assert_eq!(report["merge_queue"]["healthy"], true);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn status_page_json(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let (status, body) = tester
+                .web_request(http::Method::GET, "/status?format=json", None)
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+            let report: serde_json::Value = serde_json::from_str(&body)?;
+            assert_eq!(report["healthy"], true);
+            assert_eq!(report["version"], env!("CARGO_PKG_VERSION"));
+            assert_eq!(report["health"]["database"]["healthy"], true);
+            assert_eq!(report["emergency_stop"], serde_json::Value::Null);
+            assert_eq!(
+                report["repositories"],
+                serde_json::json!([{
+                    "name": "rust-lang/borstest",
+                    "merge_queue": "running",
+                    "tree_closed": false
+                }])
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn status_page_html(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors queue pause").await?;
+            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors treeclosed=100").await?;
+            tester.expect_comments((), 1).await;
+
+            let (status, body) = tester
+                .web_request(http::Method::GET, "/status", None)
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+            assert!(body.contains("All systems operational."));
+            assert!(body.contains("<td>rust-lang/borstest</td>"));
+            assert!(body.contains("<td>paused</td>"));
+            assert!(body.contains("<td>closed</td>"));
+            Ok(())
+        })
+        .await;
+    }
+
+    #[test]
+    fn parse_listener_address() {
+        let listener: ListenerConfig = "127.0.0.1:9000".parse().unwrap();
+        assert_eq!(listener.address.to_string(), "127.0.0.1:9000");
+        assert_eq!(listener.path_prefix, "");
+        assert_eq!(listener.routes, ListenerConfig::default().routes);
+    }
+
+    #[test]
+    fn parse_listener_routes_and_prefix() {
+        let listener: ListenerConfig = "webhook+web@0.0.0.0:8080/bors/".parse().unwrap();
+        assert_eq!(listener.address.to_string(), "0.0.0.0:8080");
+        assert_eq!(listener.path_prefix, "/bors");
+        assert_eq!(listener.routes, vec![RouteGroup::Webhook, RouteGroup::Web]);
+    }
+
+    #[test]
+    fn parse_listener_invalid() {
+        assert!("foo@0.0.0.0:8080".parse::<ListenerConfig>().is_err());
+        assert!("localhost".parse::<ListenerConfig>().is_err());
+    }
+
+    #[sqlx::test]
+    async fn listener_serves_only_its_routes(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let listener: ListenerConfig = "api@127.0.0.1:9000".parse().unwrap();
+            let (status, _, _) = tester
+                .listener_web_request(&listener, http::Method::GET, "/api/v1/workers")
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+            let (status, _, _) = tester
+                .listener_web_request(&listener, http::Method::GET, "/help")
+                .await?;
+            assert_eq!(status, http::StatusCode::NOT_FOUND);
+            let (status, _, _) = tester
+                .listener_web_request(&listener, http::Method::POST, "/github")
+                .await?;
+            assert_eq!(status, http::StatusCode::NOT_FOUND);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn listener_with_path_prefix(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let listener: ListenerConfig = "web@0.0.0.0:8080/bors".parse().unwrap();
+            let (status, _, headers) = tester
+                .listener_web_request(&listener, http::Method::GET, "/bors")
+                .await?;
+            assert_eq!(status, http::StatusCode::TEMPORARY_REDIRECT);
+            assert_eq!(headers[http::header::LOCATION], "/bors/queue/borstest");
+
+            let (status, body, _) = tester
+                .listener_web_request(&listener, http::Method::GET, "/bors/queue/borstest")
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+            assert!(body.contains(r#"href="../help""#));
+
+            let (status, body, _) = tester
+                .listener_web_request(&listener, http::Method::GET, "/queue/borstest")
+                .await?;
+            assert_eq!(status, http::StatusCode::NOT_FOUND);
+            assert!(body.contains(r#"href="/bors/""#));
+            Ok(())
+        })
+        .await;
+    }
+}
@@ -0,0 +1,2297 @@
+//! JSON API that can be used by CI jobs and other tools to query the state of bors.
+use std::collections::BTreeMap;
+use std::fmt;
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+use super::ServerStateRef;
+use crate::BorsGlobalEvent;
+use crate::bors::event::{BorsRepositoryEvent, BuildResultReported, QueuedRepositoryEvent};
+use crate::bors::queue_skips::{QUEUE_SKIPS, SkipReason, SkippedPr};
+use crate::bors::workers::WORKER_POOL_METRICS;
+use crate::bors::{BuildArtifact, ZOMBIE_WORKFLOW_METRICS};
+use crate::database::{
+    AuditLogEntryModel, BuildArtifactModel, BuildModel, BuildStatsModel, BuildStatus,
+    BuildTimingModel, DeadLetterModel, EmergencyStopModel, MergeableState, PullRequestModel,
+    QueueStatus, RepoModel, TreeState, WeeklyLatencyModel, WorkflowModel, WorkflowStatus,
+    WorkflowType,
+};
+use crate::github::api::create_github_client;
+use crate::github::api::etag_cache::ETAG_CACHE_METRICS;
+use crate::github::api::rate_limit::RATE_LIMIT_METRICS;
+use crate::github::webhook_lag::WEBHOOK_LAG_METRICS;
+use crate::github::{AppError, GithubRepoName, PullRequestNumber};
+use crate::utils::sort_queue::{QUEUE_SORT_CRITERIA, sort_queue_prs, trace_queue_order};
+
+/// Token that has to be sent in the `Authorization: Bearer <token>` header of authenticated API
+/// requests, e.g. when external CI systems report build results.
+pub struct ApiToken(SecretString);
+
+impl ApiToken {
+    pub fn new(token: String) -> Self {
+        Self(token.into())
+    }
+
+    fn expose(&self) -> &str {
+        self.0.expose_secret()
+    }
+
+    /// Compares the token with `value` in constant time, so that the duration of the comparison
+    /// does not reveal how much of the token was guessed correctly.
+    fn matches(&self, value: &str) -> bool {
+        self.expose().as_bytes().ct_eq(value.as_bytes()).into()
+    }
+}
+
+/// Role of a client of the administrative API, determined by the API token that it sends.
+/// Each role can do everything that the roles before it can do.
+///
+/// Roles are only assigned through the static tokens of the secrets. They are not mapped from
+/// GitHub organization teams, because API requests do not carry a GitHub identity, and they do
+/// not apply to bors commands, whose permissions are managed by the permission source of each
+/// repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// Can only read the state of bors. Requests without a token have this role.
+    Viewer,
+    /// Can perform actions needed during incidents, such as stopping the merge queues.
+    Operator,
+    /// Can perform all administrative actions.
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Viewer => write!(f, "viewer"),
+            Role::Operator => write!(f, "operator"),
+            Role::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct BuildResponse {
+    id: i32,
+    repository: String,
+    branch: String,
+    commit_sha: String,
+    parent: String,
+    status: String,
+    created_at: String,
+    /// When the PR of an auto build entered the merge queue.
+    queued_at: Option<String>,
+    /// When the build stopped being pending.
+    finished_at: Option<String>,
+    /// When the merge commit of an auto build was pushed to the base branch.
+    merged_at: Option<String>,
+    check_run_id: Option<i64>,
+    workflows: Vec<WorkflowResponse>,
+    artifacts: Vec<ArtifactResponse>,
+}
+
+impl BuildResponse {
+    fn new(
+        build: BuildModel,
+        timing: Option<BuildTimingModel>,
+        workflows: Vec<WorkflowModel>,
+        artifacts: Vec<BuildArtifactModel>,
+    ) -> Self {
+        let timing = timing.as_ref();
+        let format = |time: Option<DateTime<Utc>>| time.map(|time| time.to_rfc3339());
+        Self {
+            id: build.id,
+            repository: build.repository.to_string(),
+            branch: build.branch,
+            commit_sha: build.commit_sha,
+            parent: build.parent,
+            status: build.status.to_string(),
+            created_at: build.created_at.to_rfc3339(),
+            queued_at: format(timing.and_then(|timing| timing.queued_at)),
+            finished_at: format(timing.and_then(|timing| timing.finished_at)),
+            merged_at: format(timing.and_then(|timing| timing.merged_at)),
+            check_run_id: build.check_run_id,
+            workflows: workflows.into_iter().map(WorkflowResponse::from).collect(),
+            artifacts: artifacts.into_iter().map(ArtifactResponse::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct WorkflowResponse {
+    name: String,
+    url: String,
+    run_id: u64,
+    #[serde(rename = "type")]
+    workflow_type: &'static str,
+    status: &'static str,
+}
+
+impl From<WorkflowModel> for WorkflowResponse {
+    fn from(workflow: WorkflowModel) -> Self {
+        Self {
+            name: workflow.name,
+            url: workflow.url,
+            run_id: workflow.run_id.0,
+            workflow_type: workflow_type_name(&workflow.workflow_type),
+            status: workflow_status_name(&workflow.status),
+        }
+    }
+}
+
+pub(super) fn workflow_type_name(workflow_type: &WorkflowType) -> &'static str {
+    match workflow_type {
+        WorkflowType::Github => "github",
+        WorkflowType::External => "external",
+    }
+}
+
+pub(super) fn workflow_status_name(status: &WorkflowStatus) -> &'static str {
+    match status {
+        WorkflowStatus::Pending => "pending",
+        WorkflowStatus::Success => "success",
+        WorkflowStatus::Failure => "failure",
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct ArtifactResponse {
+    name: String,
+    url: String,
+    /// Size of the artifact in bytes, if it is known.
+    size: Option<i64>,
+}
+
+impl From<BuildArtifactModel> for ArtifactResponse {
+    fn from(artifact: BuildArtifactModel) -> Self {
+        Self {
+            name: artifact.name,
+            url: artifact.url,
+            size: artifact.size,
+        }
+    }
+}
+
+/// Returns information about a single build.
+/// The build ID is available to CI in the `Bors-Build-Id` trailer of the tested merge commit,
+/// and in the `external_id` of the check run created for the build.
+pub(super) async fn get_build_handler(
+    Path(build_id): Path<i32>,
+    State(state): State<ServerStateRef>,
+) -> Result<Response, AppError> {
+    let Some(build) = state.db.get_build(build_id).await? else {
+        return Ok((StatusCode::NOT_FOUND, format!("Build {build_id} not found")).into_response());
+    };
+    let timing = state.db.get_build_timing(&build).await?;
+    let workflows = state.db.get_workflows_for_build(&build).await?;
+    let artifacts = state.db.get_artifacts_for_build(&build).await?;
+    Ok(Json(BuildResponse::new(build, timing, workflows, artifacts)).into_response())
+}
+
+#[derive(Serialize)]
+pub(super) struct QueueResponse {
+    repository: String,
+    tree_state: TreeStateResponse,
+    /// Non-closed pull requests, in the order in which the merge queue would process them.
+    prs: Vec<PullRequestResponse>,
+}
+
+#[derive(Serialize)]
+pub(super) struct TreeStateResponse {
+    closed: bool,
+    /// PRs with priority lower than this value cannot be merged while the tree is closed.
+    priority: Option<u32>,
+    /// URL of the comment that closed the tree.
+    source: Option<String>,
+    /// Why the tree was closed.
+    reason: Option<String>,
+    /// When the tree will be reopened automatically (RFC 3339).
+    until: Option<String>,
+}
+
+impl From<TreeState> for TreeStateResponse {
+    fn from(tree_state: TreeState) -> Self {
+        match tree_state {
+            TreeState::Open => Self {
+                closed: false,
+                priority: None,
+                source: None,
+                reason: None,
+                until: None,
+            },
+            TreeState::Closed {
+                priority,
+                source,
+                reason,
+                until,
+            } => Self {
+                closed: true,
+                priority: Some(priority),
+                source: Some(source),
+                reason,
+                until: until.map(|until| until.to_rfc3339()),
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct PullRequestResponse {
+    number: u64,
+    title: String,
+    author: String,
+    assignees: Vec<String>,
+    status: String,
+    base_branch: String,
+    mergeable: &'static str,
+    approved_by: Option<String>,
+    approved_sha: Option<String>,
+    delegated: Option<String>,
+    priority: Option<i32>,
+    rollup: Option<String>,
+    queue_status: &'static str,
+    try_build: Option<BuildSummaryResponse>,
+    auto_build: Option<BuildSummaryResponse>,
+}
+
+impl From<PullRequestModel> for PullRequestResponse {
+    fn from(pr: PullRequestModel) -> Self {
+        let queue_status = queue_status_name(&pr.queue_status());
+        let delegated = pr.active_delegation().map(|perm| perm.to_string());
+        Self {
+            number: pr.number.0,
+            approved_by: pr.approver().map(|approver| approver.to_string()),
+            approved_sha: pr.approved_sha().map(|sha| sha.to_string()),
+            title: pr.title,
+            author: pr.author,
+            assignees: pr.assignees,
+            status: pr.pr_status.to_string(),
+            base_branch: pr.base_branch,
+            mergeable: mergeable_state_name(&pr.mergeable_state),
+            delegated,
+            priority: pr.priority,
+            rollup: pr.rollup.map(|rollup| rollup.to_string()),
+            queue_status,
+            try_build: pr.try_build.map(BuildSummaryResponse::from),
+            auto_build: pr.auto_build.map(BuildSummaryResponse::from),
+        }
+    }
+}
+
+pub(super) fn queue_status_name(status: &QueueStatus) -> &'static str {
+    match status {
+        QueueStatus::NotApproved => "not_approved",
+        QueueStatus::Approved(_) => "approved",
+        QueueStatus::Pending(..) => "pending",
+        QueueStatus::ReadyForMerge(..) => "ready_for_merge",
+        QueueStatus::Stalled(..) => "stalled",
+    }
+}
+
+pub(super) fn mergeable_state_name(state: &MergeableState) -> &'static str {
+    match state {
+        MergeableState::Mergeable => "mergeable",
+        MergeableState::HasConflicts => "has_conflicts",
+        MergeableState::Unknown => "unknown",
+    }
+}
+
+/// Short description of a build attached to a pull request.
+/// The full build can be loaded from the build endpoint using its ID.
+#[derive(Serialize)]
+pub(super) struct BuildSummaryResponse {
+    id: i32,
+    commit_sha: String,
+    /// Commit on top of which the PR was merged for the build.
+    parent: String,
+    status: String,
+}
+
+impl From<BuildModel> for BuildSummaryResponse {
+    fn from(build: BuildModel) -> Self {
+        Self {
+            id: build.id,
+            commit_sha: build.commit_sha,
+            parent: build.parent,
+            status: build.status.to_string(),
+        }
+    }
+}
+
+/// Returns the state of the merge queue of a repository.
+pub(super) async fn get_queue_handler(
+    Path((owner, name)): Path<(String, String)>,
+    State(state): State<ServerStateRef>,
+) -> Result<Response, AppError> {
+    let repo_name = GithubRepoName::new(&owner, &name);
+    let Some(repo) = get_repo(&state, &repo_name).await? else {
+        return Ok(repo_not_found(&repo_name));
+    };
+
+    let prs = sort_queue_prs(
+        state.db.get_nonclosed_pull_requests(&repo_name).await?,
+        state.priority_aging_interval(&repo_name),
+    );
+    Ok(Json(QueueResponse {
+        repository: repo_name.to_string(),
+        tree_state: repo.tree_state.into(),
+        prs: prs.into_iter().map(PullRequestResponse::from).collect(),
+    })
+    .into_response())
+}
+
+#[derive(Serialize)]
+struct QueueTraceResponse {
+    repository: String,
+    /// Names of the criteria by which the queue is ordered, in the order in which they are
+    /// compared.
+    criteria: &'static [&'static str],
+    prs: Vec<QueueTraceEntry>,
+}
+
+/// The values that were used to place a pull request in the merge queue.
+#[derive(Serialize)]
+struct QueueTraceEntry {
+    number: u64,
+    pending_build: bool,
+    held: bool,
+    parked: bool,
+    approved: bool,
+    auto_build_status: Option<String>,
+    mergeable: &'static str,
+    priority: Option<i32>,
+    /// Priority after aging, which is the value that is actually compared.
+    effective_priority: i64,
+    rollup: Option<String>,
+    /// The first criterion in which this PR differs from the previous PR in the queue, i.e. the
+    /// reason why it comes after it. `None` for the first PR.
+    decided_by: Option<&'static str>,
+}
+
+/// Returns the order of the merge queue of a repository, together with the values of all
+/// the criteria that were used to compute it, to help with debugging surprising orderings.
+pub(super) async fn get_queue_trace_handler(
+    Path((owner, name)): Path<(String, String)>,
+    State(state): State<ServerStateRef>,
+) -> Result<Response, AppError> {
+    let repo_name = GithubRepoName::new(&owner, &name);
+    if get_repo(&state, &repo_name).await?.is_none() {
+        return Ok(repo_not_found(&repo_name));
+    }
+
+    let trace = trace_queue_order(
+        state.db.get_nonclosed_pull_requests(&repo_name).await?,
+        state.priority_aging_interval(&repo_name),
+    );
+    let prs = trace
+        .iter()
+        .enumerate()
+        .map(|(index, (pr, key))| QueueTraceEntry {
+            number: pr.number.0,
+            pending_build: key.blocking == 0,
+            held: pr.held,
+            parked: pr.parked,
+            approved: pr.is_approved(),
+            auto_build_status: pr.auto_build.as_ref().map(|build| build.status.to_string()),
+            mergeable: mergeable_state_name(&pr.mergeable_state),
+            priority: pr.priority,
+            effective_priority: key.priority.0,
+            rollup: pr.rollup.map(|rollup| rollup.to_string()),
+            decided_by: index
+                .checked_sub(1)
+                .and_then(|previous| key.deciding_criterion(&trace[previous].1)),
+        })
+        .collect();
+    Ok(Json(QueueTraceResponse {
+        repository: repo_name.to_string(),
+        criteria: &QUEUE_SORT_CRITERIA,
+        prs,
+    })
+    .into_response())
+}
+
+/// How many weeks of review latency metrics are returned by default.
+const DEFAULT_LATENCY_WEEKS: i32 = 12;
+
+#[derive(Deserialize)]
+pub(super) struct LatencyQuery {
+    /// How many weeks (including the current one) to return.
+    weeks: Option<i32>,
+}
+
+#[derive(Serialize)]
+struct LatencyResponse {
+    repository: String,
+    weeks: Vec<WeeklyLatencyModel>,
+}
+
+/// Returns review latency metrics of a repository, e.g. how long it takes for PRs to be approved
+/// and merged, per week in which the PRs were merged.
+pub(super) async fn get_latency_handler(
+    Path((owner, name)): Path<(String, String)>,
+    Query(query): Query<LatencyQuery>,
+    State(state): State<ServerStateRef>,
+) -> Result<Response, AppError> {
+    let repo_name = GithubRepoName::new(&owner, &name);
+    if get_repo(&state, &repo_name).await?.is_none() {
+        return Ok(repo_not_found(&repo_name));
+    }
+    let weeks = query.weeks.unwrap_or(DEFAULT_LATENCY_WEEKS);
+    if weeks < 1 {
+        return Ok((StatusCode::BAD_REQUEST, "`weeks` has to be positive").into_response());
+    }
+
+    let weeks = state.db.get_weekly_latencies(&repo_name, weeks).await?;
+    Ok(Json(LatencyResponse {
+        repository: repo_name.to_string(),
+        weeks,
+    })
+    .into_response())
+}
+
+#[derive(Serialize)]
+struct QueueSkipsResponse {
+    repository: String,
+    /// When the merge queue was last processed, as an RFC 3339 timestamp.
+    recorded_at: Option<String>,
+    prs: Vec<SkippedPr>,
+    totals: BTreeMap<SkipReason, u64>,
+}
+
+/// Returns why the approved PRs of a repository were not selected by the merge queue when it was
+/// last processed, and how often each reason was recorded since bors was started.
+pub(super) async fn get_queue_skips_handler(
+    Path((owner, name)): Path<(String, String)>,
+    State(state): State<ServerStateRef>,
+) -> Result<Response, AppError> {
+    let repo_name = GithubRepoName::new(&owner, &name);
+    if get_repo(&state, &repo_name).await?.is_none() {
+        return Ok(repo_not_found(&repo_name));
+    }
+
+    let skips = QUEUE_SKIPS.snapshot(&repo_name);
+    Ok(Json(QueueSkipsResponse {
+        repository: repo_name.to_string(),
+        recorded_at: skips.recorded_at.map(|time| time.to_rfc3339()),
+        prs: skips.prs,
+        totals: skips.totals,
+    })
+    .into_response())
+}
+
+/// How many days of build statistics are returned by default.
+pub(super) const DEFAULT_BUILD_STATS_DAYS: i32 = 30;
+
+#[derive(Deserialize)]
+pub(super) struct BuildStatsQuery {
+    /// Over how many days (counted back from now) the builds are aggregated.
+    pub(super) days: Option<i32>,
+}
+
+#[derive(Serialize)]
+struct BuildStatsResponse {
+    repository: String,
+    days: i32,
+    /// Statistics of auto and try builds, if any were started in the period.
+    builds: Vec<BuildStatsModel>,
+}
+
+/// Returns statistics of the builds of a repository, e.g. how long PRs wait in the merge queue,
+/// how long CI takes and how often builds fail.
+pub(super) async fn get_build_stats_handler(
+    Path((owner, name)): Path<(String, String)>,
+    Query(query): Query<BuildStatsQuery>,
+    State(state): State<ServerStateRef>,
+) -> Result<Response, AppError> {
+    let repo_name = GithubRepoName::new(&owner, &name);
+    if get_repo(&state, &repo_name).await?.is_none() {
+        return Ok(repo_not_found(&repo_name));
+    }
+    let days = query.days.unwrap_or(DEFAULT_BUILD_STATS_DAYS);
+    if days < 1 {
+        return Ok((StatusCode::BAD_REQUEST, "`days` has to be positive").into_response());
+    }
+
+    let builds = state.db.get_build_stats(&repo_name, days).await?;
+    Ok(Json(BuildStatsResponse {
+        repository: repo_name.to_string(),
+        days,
+        builds,
+    })
+    .into_response())
+}
+
+/// How many audit log entries are returned by default.
+const DEFAULT_AUDIT_LOG_LIMIT: i64 = 100;
+
+#[derive(Deserialize)]
+pub(super) struct AuditLogQuery {
+    /// Only return entries concerning the PR with this number.
+    pr: Option<u64>,
+    /// How many of the latest entries to return.
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct AuditLogResponse {
+    repository: String,
+    entries: Vec<AuditLogEntryResponse>,
+}
+
+#[derive(Serialize)]
+struct AuditLogEntryResponse {
+    id: i32,
+    pr: Option<u64>,
+    /// Author of the command, or `null` for actions performed by bors.
+    actor: Option<String>,
+    action: String,
+    details: Option<String>,
+    outcome: String,
+    /// RFC 3339.
+    created_at: String,
+}
+
+impl From<AuditLogEntryModel> for AuditLogEntryResponse {
+    fn from(entry: AuditLogEntryModel) -> Self {
+        Self {
+            id: entry.id,
+            pr: entry.pr_number.map(|pr| pr.0),
+            actor: entry.actor,
+            action: entry.action,
+            details: entry.details,
+            outcome: entry.outcome,
+            created_at: entry.created_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Returns the latest entries of the audit log of a repository, i.e. the bors commands used on
+/// its PRs, the actions that bors performed on its own and the decisions about requests to the
+/// administrative API, from the oldest one. Requires the operator role.
+pub(super) async fn get_audit_log_handler(
+    Path((owner, name)): Path<(String, String)>,
+    Query(query): Query<AuditLogQuery>,
+    State(state): State<ServerStateRef>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let repo_name = GithubRepoName::new(&owner, &name);
+    if let Err(response) = authorize_role(
+        &state,
+        &headers,
+        Role::Operator,
+        "view audit log",
+        Some(&repo_name),
+    )
+    .await?
+    {
+        return Ok(response);
+    }
+    if get_repo(&state, &repo_name).await?.is_none() {
+        return Ok(repo_not_found(&repo_name));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_AUDIT_LOG_LIMIT);
+    if limit < 1 {
+        return Ok((StatusCode::BAD_REQUEST, "`limit` has to be positive").into_response());
+    }
+
+    let entries = state
+        .db
+        .get_audit_log(&repo_name, query.pr.map(PullRequestNumber), limit)
+        .await?;
+    Ok(Json(AuditLogResponse {
+        repository: repo_name.to_string(),
+        entries: entries
+            .into_iter()
+            .map(AuditLogEntryResponse::from)
+            .collect(),
+    })
+    .into_response())
+}
+
+/// Parts of the names of config fields whose values are redacted in the config view.
+const SECRET_FIELD_MARKERS: [&str; 3] = ["secret", "token", "password"];
+
+/// Returns the configuration that bors has currently loaded for a repository, including the
+/// default values of options that are not set in its config file, so that operators can check
+/// that a config change was picked up. Requires the admin role.
+/// Values of fields that look like secrets are redacted.
+pub(super) async fn get_config_handler(
+    Path((owner, name)): Path<(String, String)>,
+    State(state): State<ServerStateRef>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let repo_name = GithubRepoName::new(&owner, &name);
+    if let Err(response) = authorize_role(
+        &state,
+        &headers,
+        Role::Admin,
+        "view config",
+        Some(&repo_name),
+    )
+    .await?
+    {
+        return Ok(response);
+    }
+    let Some(repo) = state.repositories.get(&repo_name) else {
+        return Ok(repo_not_found(&repo_name));
+    };
+
+    let mut config = serde_json::to_value(&**repo.config.load())?;
+    redact_secrets(&mut config);
+    Ok(Json(config).into_response())
+}
+
+/// Replaces the values of all fields whose name contains one of `SECRET_FIELD_MARKERS`.
+fn redact_secrets(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (name, value) in fields.iter_mut() {
+                let name = name.to_lowercase();
+                if SECRET_FIELD_MARKERS
+                    .iter()
+                    .any(|marker| name.contains(marker))
+                {
+                    *value = serde_json::Value::String("<redacted>".to_string());
+                } else {
+                    redact_secrets(value);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact_secrets),
+        _ => {}
+    }
+}
+
+/// Returns information about a single pull request.
+pub(super) async fn get_pull_request_handler(
+    Path((owner, name, number)): Path<(String, String, u64)>,
+    State(state): State<ServerStateRef>,
+) -> Result<Response, AppError> {
+    let repo_name = GithubRepoName::new(&owner, &name);
+    if get_repo(&state, &repo_name).await?.is_none() {
+        return Ok(repo_not_found(&repo_name));
+    }
+
+    let Some(pr) = state
+        .db
+        .get_pull_request(&repo_name, PullRequestNumber(number))
+        .await?
+    else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            format!("Pull request {repo_name}#{number} not found"),
+        )
+            .into_response());
+    };
+    Ok(Json(PullRequestResponse::from(pr)).into_response())
+}
+
+/// Only repositories managed by this bors instance are exposed through the API.
+pub(super) async fn get_repo(
+    state: &ServerStateRef,
+    repo_name: &GithubRepoName,
+) -> anyhow::Result<Option<RepoModel>> {
+    if !state.repositories.contains_key(repo_name) {
+        return Ok(None);
+    }
+    state.db.repo_db(repo_name).await
+}
+
+fn repo_not_found(repo_name: &GithubRepoName) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        format!("Repository {repo_name} not found"),
+    )
+        .into_response()
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ReportedBuildStatus {
+    Success,
+    Failure,
+}
+
+#[derive(Deserialize)]
+pub(super) struct BuildResultRequest {
+    status: ReportedBuildStatus,
+    /// Name of the CI system (or job) that produced the result.
+    name: String,
+    /// Link to the CI run that produced the result.
+    url: String,
+    /// Artifacts produced by the build.
+    #[serde(default)]
+    artifacts: Vec<ArtifactRequest>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct ArtifactRequest {
+    name: String,
+    url: String,
+    /// Size of the artifact in bytes.
+    size: Option<u64>,
+}
+
+/// Receives the result of a build from an external CI system.
+/// The result decides the outcome of the whole build, so only a single result can be reported
+/// for each build.
+pub(super) async fn report_build_result_handler(
+    Path(build_id): Path<i32>,
+    State(state): State<ServerStateRef>,
+    headers: HeaderMap,
+    Json(request): Json<BuildResultRequest>,
+) -> Result<Response, AppError> {
+    let secrets = state.get_secrets();
+    if secrets.api_token.is_none() && secrets.repository_api_tokens.is_empty() {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            "Reporting build results is not enabled",
+        )
+            .into_response());
+    }
+    let Some(token) = bearer_token(&headers) else {
+        return Ok((StatusCode::UNAUTHORIZED, "Invalid API token").into_response());
+    };
+
+    let build = state.db.get_build(build_id).await?;
+    // Repository tokens only authorize builds of their repository. Unauthorized requests cannot
+    // find out whether a build exists.
+    let authorized = secrets
+        .api_token
+        .as_ref()
+        .is_some_and(|api_token| api_token.matches(token))
+        || build.as_ref().is_some_and(|build| {
+            secrets
+                .repository_api_tokens
+                .get(&build.repository)
+                .is_some_and(|repo_token| repo_token.matches(token))
+        });
+    if !authorized {
+        return Ok((StatusCode::UNAUTHORIZED, "Invalid API token").into_response());
+    }
+    let Some(build) = build else {
+        return Ok((StatusCode::NOT_FOUND, format!("Build {build_id} not found")).into_response());
+    };
+    if !state.repositories.contains_key(&build.repository) {
+        return Ok((StatusCode::NOT_FOUND, format!("Build {build_id} not found")).into_response());
+    }
+    if build.status != BuildStatus::Pending {
+        return Ok((
+            StatusCode::CONFLICT,
+            format!("Build {build_id} has already been completed"),
+        )
+            .into_response());
+    }
+
+    let event = BorsRepositoryEvent::BuildResultReported(BuildResultReported {
+        repository: build.repository,
+        build_id,
+        name: request.name,
+        url: request.url,
+        status: match request.status {
+            ReportedBuildStatus::Success => WorkflowStatus::Success,
+            ReportedBuildStatus::Failure => WorkflowStatus::Failure,
+        },
+        artifacts: request
+            .artifacts
+            .into_iter()
+            .map(|artifact| BuildArtifact {
+                name: artifact.name,
+                url: artifact.url,
+                size: artifact.size,
+            })
+            .collect(),
+    });
+    state
+        .repository_event_queue
+        .send(QueuedRepositoryEvent {
+            event,
+            webhook: None,
+        })
+        .await?;
+    Ok((StatusCode::ACCEPTED, "").into_response())
+}
+
+#[derive(Serialize)]
+pub(super) struct EmergencyStopResponse {
+    active: bool,
+    /// Why the merge queues were stopped.
+    reason: Option<String>,
+    /// When the merge queues were stopped (RFC 3339).
+    since: Option<String>,
+}
+
+impl From<Option<EmergencyStopModel>> for EmergencyStopResponse {
+    fn from(stop: Option<EmergencyStopModel>) -> Self {
+        match stop {
+            Some(stop) => Self {
+                active: true,
+                reason: stop.reason,
+                since: Some(stop.created_at.to_rfc3339()),
+            },
+            None => Self {
+                active: false,
+                reason: None,
+                since: None,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(super) struct EmergencyStopRequest {
+    reason: Option<String>,
+}
+
+/// Returns the state of the instance-wide emergency stop.
+pub(super) async fn get_emergency_stop_handler(
+    State(state): State<ServerStateRef>,
+) -> Result<Response, AppError> {
+    let stop = state.db.get_emergency_stop().await?;
+    Ok(Json(EmergencyStopResponse::from(stop)).into_response())
+}
+
+/// Pauses the merge queues of all repositories, e.g. during a security incident.
+/// Requires the operator role.
+/// Running builds are not cancelled, but no PRs are merged and no new auto builds are started
+/// until the stop is cleared. The stop is persisted, so it survives restarts of bors.
+pub(super) async fn set_emergency_stop_handler(
+    State(state): State<ServerStateRef>,
+    headers: HeaderMap,
+    Json(request): Json<EmergencyStopRequest>,
+) -> Result<Response, AppError> {
+    if let Err(response) =
+        authorize_role(&state, &headers, Role::Operator, "emergency stop", None).await?
+    {
+        return Ok(response);
+    }
+
+    tracing::warn!(
+        "Emergency stop activated (reason: {})",
+        request.reason.as_deref().unwrap_or("not specified")
+    );
+    state
+        .db
+        .set_emergency_stop(request.reason.as_deref())
+        .await?;
+    let stop = state.db.get_emergency_stop().await?;
+    Ok(Json(EmergencyStopResponse::from(stop)).into_response())
+}
+
+/// Clears the emergency stop, so that the merge queues resume.
+/// Requires the admin role, so that an operator cannot undo a stop made by someone else.
+pub(super) async fn clear_emergency_stop_handler(
+    State(state): State<ServerStateRef>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    if let Err(response) =
+        authorize_role(&state, &headers, Role::Admin, "clear emergency stop", None).await?
+    {
+        return Ok(response);
+    }
+
+    if state.db.clear_emergency_stop().await? {
+        tracing::warn!("Emergency stop cleared");
+    }
+    Ok(Json(EmergencyStopResponse::from(None)).into_response())
+}
+
+#[derive(Serialize)]
+pub(super) struct DeadLetterResponse {
+    id: i32,
+    repository: String,
+    event_type: String,
+    error: String,
+    attempts: i32,
+    replay_requested: bool,
+    /// When the handling of the webhook first failed (RFC 3339).
+    created_at: String,
+}
+
+impl From<DeadLetterModel> for DeadLetterResponse {
+    fn from(dead_letter: DeadLetterModel) -> Self {
+        Self {
+            id: dead_letter.id,
+            repository: dead_letter.repository.to_string(),
+            event_type: dead_letter.event_type,
+            error: dead_letter.error,
+            attempts: dead_letter.attempts,
+            replay_requested: dead_letter.replay_requested,
+            created_at: dead_letter.created_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Returns the webhooks whose handling has failed. Requires the admin role, because the errors
+/// can reveal internal details of the bors instance.
+pub(super) async fn get_dead_letters_handler(
+    State(state): State<ServerStateRef>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    if let Err(response) =
+        authorize_role(&state, &headers, Role::Admin, "list dead letters", None).await?
+    {
+        return Ok(response);
+    }
+
+    let dead_letters: Vec<DeadLetterResponse> = state
+        .db
+        .get_dead_letters()
+        .await?
+        .into_iter()
+        .map(DeadLetterResponse::from)
+        .collect();
+    Ok(Json(dead_letters).into_response())
+}
+
+#[derive(Deserialize)]
+pub(super) struct ReplayDeadLettersRequest {
+    /// The dead letter that should be replayed. If not set, all dead letters are replayed.
+    id: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub(super) struct ReplayDeadLettersResponse {
+    /// How many dead letters will be replayed.
+    requested: u64,
+}
+
+/// Replays webhooks whose handling has failed, e.g. after the bug that caused the failure was
+/// fixed. Requires the admin role.
+/// The webhooks are replayed asynchronously, webhooks whose replay fails stay in the dead-letter
+/// queue.
+pub(super) async fn replay_dead_letters_handler(
+    State(state): State<ServerStateRef>,
+    headers: HeaderMap,
+    Json(request): Json<ReplayDeadLettersRequest>,
+) -> Result<Response, AppError> {
+    if let Err(response) =
+        authorize_role(&state, &headers, Role::Admin, "replay dead letters", None).await?
+    {
+        return Ok(response);
+    }
+
+    let requested = state.db.request_dead_letter_replay(request.id).await?;
+    if let Some(id) = request.id
+        && requested == 0
+    {
+        return Ok((StatusCode::NOT_FOUND, format!("Dead letter {id} not found")).into_response());
+    }
+    tracing::warn!("Replay of {requested} dead letter(s) was requested");
+    state
+        .global_event_queue
+        .send(BorsGlobalEvent::ReplayDeadLetters)
+        .await?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(ReplayDeadLettersResponse { requested }),
+    )
+        .into_response())
+}
+
+/// Returns the usage of the GitHub API rate limits, e.g. how many requests were refused because
+/// of a rate limit and how much time bors has spent waiting for the limits to reset.
+pub(super) async fn get_rate_limit_handler() -> Response {
+    Json(RATE_LIMIT_METRICS.snapshot()).into_response()
+}
+
+/// Returns how many conditional GitHub API requests were answered from the cache of responses,
+/// i.e. without counting against the rate limit.
+pub(super) async fn get_etag_cache_handler() -> Response {
+    Json(ETAG_CACHE_METRICS.snapshot()).into_response()
+}
+
+/// Returns the usage of the pool of background workers that perform slow GitHub operations, e.g.
+/// how many operations are waiting for a worker and how many took too long to be waited for.
+pub(super) async fn get_workers_handler() -> Response {
+    Json(WORKER_POOL_METRICS.snapshot()).into_response()
+}
+
+/// Returns how many zombie workflow runs, i.e. runs on bors branches that did not belong to any
+/// pending build, were cancelled.
+pub(super) async fn get_zombie_workflows_handler() -> Response {
+    Json(ZOMBIE_WORKFLOW_METRICS.snapshot()).into_response()
+}
+
+/// Returns histograms of the lag between the creation of webhook events by GitHub and the end of
+/// their handling by bors, per event type, so that alerts can be raised when bors falls behind.
+pub(super) async fn get_webhook_lag_handler() -> Response {
+    Json(WEBHOOK_LAG_METRICS.snapshot()).into_response()
+}
+
+/// Loads the secrets again from the secrets provider, so that the webhook secret and API tokens
+/// can be rotated without restarting bors. Requires the admin role.
+/// The GitHub App private key is only used on startup, so rotating it requires a restart.
+pub(super) async fn reload_secrets_handler(
+    State(state): State<ServerStateRef>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    if let Err(response) =
+        authorize_role(&state, &headers, Role::Admin, "reload secrets", None).await?
+    {
+        return Ok(response);
+    }
+    let Some(provider) = &state.secrets_provider else {
+        return Ok((StatusCode::FORBIDDEN, "Reloading secrets is not enabled").into_response());
+    };
+
+    let secrets = provider.load().await?;
+    // The client is created before any secret is replaced, so that an invalid private key does
+    // not leave bors with a partially reloaded set of secrets
+    let app_client = state
+        .github_app
+        .as_ref()
+        .map(|(app_id, urls)| create_github_client(*app_id, urls, secrets.private_key()))
+        .transpose()?;
+    state.set_secrets(&secrets);
+    if let Some(client) = app_client {
+        state
+            .global_event_queue
+            .send(BorsGlobalEvent::AppClientChanged(Box::new(client)))
+            .await?;
+    }
+    tracing::warn!("Secrets were reloaded");
+    Ok((StatusCode::OK, "Secrets were reloaded").into_response())
+}
+
+/// Reloads the configuration and permissions of a repository right away, instead of waiting for
+/// the next periodic refresh. The reload is performed asynchronously. Requires the admin role.
+pub(super) async fn reload_repository_handler(
+    Path((owner, name)): Path<(String, String)>,
+    State(state): State<ServerStateRef>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let repo_name = GithubRepoName::new(&owner, &name);
+    if let Err(response) = authorize_role(
+        &state,
+        &headers,
+        Role::Admin,
+        "reload repository",
+        Some(&repo_name),
+    )
+    .await?
+    {
+        return Ok(response);
+    }
+    if !state.repositories.contains_key(&repo_name) {
+        return Ok(repo_not_found(&repo_name));
+    }
+
+    tracing::info!("Reload of repository {repo_name} was requested");
+    state
+        .global_event_queue
+        .send(BorsGlobalEvent::ReloadRepository(repo_name.clone()))
+        .await?;
+    Ok((
+        StatusCode::ACCEPTED,
+        format!("Reload of repository {repo_name} was requested"),
+    )
+        .into_response())
+}
+
+/// Checks that the request carries the token of a role that is allowed to perform `action`.
+/// Requests without a token have the viewer role. Every decision is recorded in the audit log,
+/// under the repository that the request concerns, if any.
+#[allow(clippy::result_large_err)]
+pub(super) async fn authorize_role(
+    state: &ServerStateRef,
+    headers: &HeaderMap,
+    required: Role,
+    action: &str,
+    repo: Option<&GithubRepoName>,
+) -> anyhow::Result<Result<Role, Response>> {
+    let secrets = state.get_secrets();
+    let role = if secrets.role_tokens.is_empty() {
+        Err((
+            StatusCode::FORBIDDEN,
+            "The administrative API is not enabled",
+        ))
+    } else {
+        match bearer_token(headers) {
+            Some(value) => secrets
+                .role_tokens
+                .iter()
+                .find(|(token, _)| token.matches(value))
+                .map(|(_, role)| *role)
+                .ok_or((StatusCode::UNAUTHORIZED, "Invalid API token")),
+            None => Ok(Role::Viewer),
+        }
+    };
+
+    let (details, outcome, result) = match role {
+        Ok(role) if role >= required => (
+            format!("role: {role}, required: {required}"),
+            "allowed".to_string(),
+            Ok(role),
+        ),
+        Ok(role) => (
+            format!("role: {role}, required: {required}"),
+            "denied".to_string(),
+            Err((
+                StatusCode::FORBIDDEN,
+                format!("The {required} role is required to perform {action}"),
+            )
+                .into_response()),
+        ),
+        Err((status, message)) => (
+            format!("required: {required}"),
+            format!("denied: {message}"),
+            Err((status, message).into_response()),
+        ),
+    };
+    tracing::info!(
+        target: "audit",
+        action,
+        details,
+        outcome,
+        "Role decision for an administrative request"
+    );
+    state
+        .db
+        .record_api_decision(repo, action, &details, &outcome)
+        .await?;
+    Ok(result)
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bors::PullRequestStatus;
+    use crate::database::BuildStatus;
+    use crate::github::GithubRepoName;
+    use crate::tests::{
+        BorsBuilder, BorsTester, Comment, GitHubState, Repo, TEST_ADMIN_TOKEN, TEST_API_TOKEN,
+        TEST_OPERATOR_TOKEN, TEST_REPOSITORY_API_TOKEN, default_repo_name, run_test,
+    };
+
+    #[sqlx::test]
+    async fn get_build(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+            tester.workflow_start(tester.try_branch().await).await?;
+
+            let (status, body) = tester
+                .web_request(http::Method::GET, "/api/v1/builds/1", None)
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+
+            let mut build: serde_json::Value = serde_json::from_str(&body)?;
+            build.as_object_mut().unwrap().remove("created_at");
+            insta::assert_snapshot!(serde_json::to_string_pretty(&build)?, @r#"
+            {
+              "artifacts": [],
+              "branch": "automation/bors/try",
+              "check_run_id": 0,
+              "commit_sha": "merge-0-pr-1",
+              "finished_at": null,
+              "id": 1,
+              "merged_at": null,
+              "parent": "main-sha1",
+              "queued_at": null,
+              "repository": "rust-lang/borstest",
+              "status": "pending",
+              "workflows": [
+                {
+                  "name": "Workflow1",
+                  "run_id": 1,
+                  "status": "pending",
+                  "type": "github",
+                  "url": "https://github.com/rust-lang/borstest/actions/runs/1"
+                }
+              ]
+            }
+            "#);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn get_unknown_build(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let (status, _) = tester
+                .web_request(http::Method::GET, "/api/v1/builds/42", None)
+                .await?;
+            assert_eq!(status, http::StatusCode::NOT_FOUND);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn report_build_success(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+
+            let status = tester
+                .report_build_result(
+                    1,
+                    TEST_API_TOKEN,
+                    r#"{"status": "success", "name": "buildkite", "url": "https://ci.example.com/1"}"#,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::ACCEPTED);
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r#"
+            :sunny: Try build successful ([buildkite](https://ci.example.com/1))
+            Build commit: merge-0-pr-1 (`merge-0-pr-1`, parent: `main-sha1`)
+
+            <!-- homu: {"type":"TryBuildCompleted","merge_sha":"merge-0-pr-1"} -->
+            "#);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn report_build_failure(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+
+            let status = tester
+                .report_build_result(
+                    1,
+                    TEST_API_TOKEN,
+                    r#"{"status": "failure", "name": "buildkite", "url": "https://ci.example.com/1"}"#,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::ACCEPTED);
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+            :broken_heart: Test for merge-0-pr-1 failed: [buildkite](https://ci.example.com/1)
+            ");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn report_build_result_invalid_token(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+
+            let status = tester
+                .report_build_result(
+                    1,
+                    "wrong-token",
+                    r#"{"status": "success", "name": "buildkite", "url": "https://ci.example.com/1"}"#,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::UNAUTHORIZED);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn report_build_result_repository_token(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+
+            let status = tester
+                .report_build_result(
+                    1,
+                    TEST_REPOSITORY_API_TOKEN,
+                    r#"{"status": "failure", "name": "buildkite", "url": "https://ci.example.com/1"}"#,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::ACCEPTED);
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r"
+            :broken_heart: Test for merge-0-pr-1 failed: [buildkite](https://ci.example.com/1)
+            ");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn report_build_result_token_of_other_repository(pool: sqlx::PgPool) {
+        let other = GithubRepoName::new("rust-lang", "other");
+        let default_repo = Repo::default();
+        let mut other_repo =
+            Repo::new(other.clone(), default_repo.permissions, default_repo.config);
+        other_repo.add_pr();
+        let github = GitHubState::default().with_repo(other_repo);
+        BorsBuilder::new(pool)
+            .github(github)
+            .run_test(async |tester: &mut BorsTester| {
+                tester
+                    .post_comment(Comment::new((other.clone(), 1), "@bors try"))
+                    .await?;
+                tester.expect_comments((other.clone(), 1), 1).await;
+
+                let status = tester
+                    .report_build_result(
+                        1,
+                        TEST_REPOSITORY_API_TOKEN,
+                        r#"{"status": "success", "name": "buildkite", "url": "https://ci.example.com/1"}"#,
+                    )
+                    .await?;
+                assert_eq!(status, http::StatusCode::UNAUTHORIZED);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn report_result_of_unknown_build_with_repository_token(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let status = tester
+                .report_build_result(
+                    42,
+                    TEST_REPOSITORY_API_TOKEN,
+                    r#"{"status": "success", "name": "buildkite", "url": "https://ci.example.com/1"}"#,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::UNAUTHORIZED);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn report_result_of_completed_build(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+            tester.workflow_full_success(tester.try_branch().await).await?;
+            tester.expect_comments((), 1).await;
+
+            let status = tester
+                .report_build_result(
+                    1,
+                    TEST_API_TOKEN,
+                    r#"{"status": "failure", "name": "buildkite", "url": "https://ci.example.com/1"}"#,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::CONFLICT);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn get_queue(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+            tester
+                .post_comment(Comment::new(pr2.id(), "@bors r+ p=5 rollup=never"))
+                .await?;
+            tester.expect_comments(pr2.id(), 1).await;
+            tester
+                .post_comment(r#"@bors treeclosed=10 reason="CI outage""#)
+                .await?;
+            tester.expect_comments((), 1).await;
+
+            let (status, body) = tester
+                .web_request(
+                    http::Method::GET,
+                    "/api/v1/repos/rust-lang/borstest/queue",
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+
+            let queue: serde_json::Value = serde_json::from_str(&body)?;
+            assert_eq!(queue["repository"], "rust-lang/borstest");
+            assert_eq!(queue["tree_state"]["closed"], true);
+            assert_eq!(queue["tree_state"]["priority"], 10);
+            assert_eq!(queue["tree_state"]["reason"], "CI outage");
+            assert!(queue["tree_state"]["until"].is_null());
+
+            let prs = queue["prs"].as_array().unwrap();
+            assert_eq!(prs.len(), 2);
+            // The approved PR comes first
+            assert_eq!(prs[0]["number"], 2);
+            assert_eq!(prs[0]["queue_status"], "approved");
+            assert_eq!(prs[0]["approved_by"], "default-user");
+            assert_eq!(prs[0]["priority"], 5);
+            assert_eq!(prs[0]["rollup"], "never");
+            assert_eq!(prs[1]["number"], 1);
+            assert_eq!(prs[1]["queue_status"], "not_approved");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn get_queue_trace(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+            let pr3 = tester.open_pr(default_repo_name(), |_| {}).await?;
+            tester.approve(()).await?;
+            tester
+                .post_comment(Comment::new(pr2.id(), "@bors r+ p=5"))
+                .await?;
+            tester.expect_comments(pr2.id(), 1).await;
+            tester
+                .post_comment(Comment::new(pr3.id(), "@bors r+ rollup"))
+                .await?;
+            tester.expect_comments(pr3.id(), 1).await;
+
+            let (status, body) = tester
+                .web_request(
+                    http::Method::GET,
+                    "/api/v1/repos/rust-lang/borstest/queue/trace",
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+
+            let trace: serde_json::Value = serde_json::from_str(&body)?;
+            assert_eq!(trace["repository"], "rust-lang/borstest");
+            assert_eq!(trace["criteria"][6], "priority");
+            let prs = trace["prs"].as_array().unwrap();
+            let order: Vec<_> = prs
+                .iter()
+                .map(|pr| (pr["number"].clone(), pr["decided_by"].clone()))
+                .collect();
+            insta::assert_debug_snapshot!(order, @r#"
+                [
+                    (
+                        Number(2),
+                        Null,
+                    ),
+                    (
+                        Number(1),
+                        String("priority"),
+                    ),
+                    (
+                        Number(3),
+                        String("rollup"),
+                    ),
+                ]
+                "#);
+            assert_eq!(prs[0]["approved"], true);
+            assert_eq!(prs[0]["priority"], 5);
+            assert_eq!(prs[0]["effective_priority"], 5);
+            assert_eq!(prs[0]["pending_build"], false);
+            assert!(prs[1]["rollup"].is_null());
+            assert_eq!(prs[2]["rollup"], "always");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn get_queue_trace_unknown_repo(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let (status, _) = tester
+                .web_request(
+                    http::Method::GET,
+                    "/api/v1/repos/rust-lang/unknown/queue/trace",
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::NOT_FOUND);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn get_audit_log(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors p=3").await?;
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+
+            let (status, body) = tester
+                .authenticated_web_request(
+                    http::Method::GET,
+                    "/api/v1/repos/rust-lang/borstest/audit-log?pr=1&limit=2",
+                    TEST_OPERATOR_TOKEN,
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+
+            let log: serde_json::Value = serde_json::from_str(&body)?;
+            assert_eq!(log["repository"], "rust-lang/borstest");
+            let entries = log["entries"].as_array().unwrap();
+            assert_eq!(entries.len(), 2);
+            assert_eq!(entries[0]["pr"], 1);
+            assert_eq!(entries[0]["actor"], "default-user");
+            assert_eq!(entries[0]["action"], "approve");
+            assert_eq!(entries[0]["details"], "@bors r+");
+            assert_eq!(entries[0]["outcome"], "success");
+            assert_eq!(entries[1]["actor"], serde_json::Value::Null);
+            assert_eq!(entries[1]["action"], "auto_build_started");
+
+            let (status, _) = tester
+                .authenticated_web_request(
+                    http::Method::GET,
+                    "/api/v1/repos/rust-lang/borstest/audit-log?limit=0",
+                    TEST_OPERATOR_TOKEN,
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::BAD_REQUEST);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn audit_log_records_role_decisions(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let (status, body) = tester
+                .web_request(
+                    http::Method::GET,
+                    "/api/v1/repos/rust-lang/borstest/audit-log",
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::FORBIDDEN);
+            insta::assert_snapshot!(body, @"The operator role is required to perform view audit log");
+
+            let (status, body) = tester
+                .authenticated_web_request(
+                    http::Method::GET,
+                    "/api/v1/repos/rust-lang/borstest/audit-log",
+                    TEST_OPERATOR_TOKEN,
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+            let log: serde_json::Value = serde_json::from_str(&body)?;
+            let decisions: Vec<String> = log["entries"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|entry| {
+                    format!(
+                        "{}: {} ({})",
+                        entry["action"].as_str().unwrap(),
+                        entry["outcome"].as_str().unwrap(),
+                        entry["details"].as_str().unwrap()
+                    )
+                })
+                .collect();
+            insta::assert_debug_snapshot!(decisions, @r#"
+            [
+                "api: view audit log: denied (role: viewer, required: operator)",
+                "api: view audit log: allowed (role: operator, required: operator)",
+            ]
+            "#);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn get_queue_skips(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+
+            let (status, body) = tester
+                .web_request(
+                    http::Method::GET,
+                    "/api/v1/repos/rust-lang/borstest/queue/skips",
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+            let skips: serde_json::Value = serde_json::from_str(&body)?;
+            assert_eq!(skips["repository"], "rust-lang/borstest");
+            // The skip reasons are shared by all tests running in parallel
+            assert!(skips["recorded_at"].is_string());
+            assert!(skips["prs"].is_array());
+            assert!(skips["totals"].is_object());
+
+            let (status, _) = tester
+                .web_request(
+                    http::Method::GET,
+                    "/api/v1/repos/rust-lang/unknown/queue/skips",
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::NOT_FOUND);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn get_latency(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let pr = tester.open_pr(default_repo_name(), |_| {}).await?;
+            tester.approve(pr.id()).await?;
+            tester.start_auto_build(pr.id()).await?;
+            tester.finish_auto_build(pr.id()).await?;
+
+            let (status, body) = tester
+                .web_request(
+                    http::Method::GET,
+                    "/api/v1/repos/rust-lang/borstest/latency?weeks=4",
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+
+            let latency: serde_json::Value = serde_json::from_str(&body)?;
+            assert_eq!(latency["repository"], "rust-lang/borstest");
+            let weeks = latency["weeks"].as_array().unwrap();
+            assert_eq!(weeks.len(), 1);
+            assert_eq!(weeks[0]["merged"], 1);
+            for metric in [
+                "open_to_first_command",
+                "open_to_approval",
+                "queue_to_build_start",
+                "build_start_to_merge",
+                "approval_to_merge",
+            ] {
+                assert!(weeks[0][metric].as_f64().unwrap() >= 0.0, "{metric}");
+            }
+
+            let (status, _) = tester
+                .web_request(
+                    http::Method::GET,
+                    "/api/v1/repos/rust-lang/borstest/latency?weeks=0",
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::BAD_REQUEST);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn get_build_stats(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+            tester
+                .workflow_full_failure(tester.try_branch().await)
+                .await?;
+            tester.expect_comments((), 1).await;
+
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester.finish_auto_build(()).await?;
+
+            let (status, body) = tester
+                .web_request(
+                    http::Method::GET,
+                    "/api/v1/repos/rust-lang/borstest/build-stats?days=7",
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+
+            let stats: serde_json::Value = serde_json::from_str(&body)?;
+            assert_eq!(stats["repository"], "rust-lang/borstest");
+            assert_eq!(stats["days"], 7);
+            let builds = stats["builds"].as_array().unwrap();
+            assert_eq!(builds.len(), 2);
+
+            let auto = &builds[0];
+            assert_eq!(auto["kind"], "auto");
+            assert_eq!(auto["builds"], 1);
+            assert_eq!(auto["succeeded"], 1);
+            assert_eq!(auto["merged"], 1);
+            assert_eq!(auto["failure_rate"], 0.0);
+            assert_eq!(auto["retries_per_merge"], 0.0);
+            for metric in ["queue_wait", "ci_duration", "finish_to_merge"] {
+                assert!(auto[metric].as_f64().unwrap() >= 0.0, "{metric}");
+            }
+
+            let try_stats = &builds[1];
+            assert_eq!(try_stats["kind"], "try");
+            assert_eq!(try_stats["builds"], 1);
+            assert_eq!(try_stats["failed"], 1);
+            assert_eq!(try_stats["failure_rate"], 1.0);
+            assert_eq!(try_stats["queue_wait"], serde_json::Value::Null);
+            assert_eq!(try_stats["retries_per_merge"], serde_json::Value::Null);
+            assert!(try_stats["ci_duration"].as_f64().unwrap() >= 0.0);
+
+            let (status, _) = tester
+                .web_request(
+                    http::Method::GET,
+                    "/api/v1/repos/rust-lang/borstest/build-stats?days=0",
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::BAD_REQUEST);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn get_merged_build_timing(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester.finish_auto_build(()).await?;
+
+            let (status, body) = tester
+                .web_request(http::Method::GET, "/api/v1/builds/1", None)
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+            let build: serde_json::Value = serde_json::from_str(&body)?;
+            for timestamp in ["created_at", "queued_at", "finished_at", "merged_at"] {
+                assert!(build[timestamp].is_string(), "{timestamp}");
+            }
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn get_worker_pool_metrics(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            // Approval labels are added by the worker pool
+            tester.approve(()).await?;
+
+            let (status, body) = tester
+                .web_request(http::Method::GET, "/api/v1/workers", None)
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+            let metrics: serde_json::Value = serde_json::from_str(&body)?;
+            assert_eq!(metrics["workers"], 4);
+            // Metrics are shared by all tests running in parallel
+            assert!(metrics["completed"].as_u64().unwrap() >= 1);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn get_pull_request(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+
+            let (status, body) = tester
+                .web_request(
+                    http::Method::GET,
+                    "/api/v1/repos/rust-lang/borstest/prs/1",
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+
+            let pr: serde_json::Value = serde_json::from_str(&body)?;
+            assert_eq!(pr["number"], 1);
+            assert_eq!(pr["title"], "Title of PR 1");
+            assert_eq!(pr["author"], "default-user");
+            assert_eq!(pr["status"], "open");
+            assert_eq!(pr["queue_status"], "not_approved");
+            assert_eq!(pr["try_build"]["id"], 1);
+            assert_eq!(pr["try_build"]["status"], "pending");
+            assert_eq!(pr["try_build"]["parent"], "main-sha1");
+            assert!(pr["auto_build"].is_null());
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn get_pull_request_unknown_repo(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let (status, _) = tester
+                .web_request(http::Method::GET, "/api/v1/repos/foo/bar/prs/1", None)
+                .await?;
+            assert_eq!(status, http::StatusCode::NOT_FOUND);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn report_build_result_with_artifacts(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+
+            let status = tester
+                .report_build_result(
+                    1,
+                    TEST_API_TOKEN,
+                    r#"{
+                        "status": "success",
+                        "name": "buildkite",
+                        "url": "https://ci.example.com/1",
+                        "artifacts": [
+                            {"name": "dist", "url": "https://ci.example.com/1/dist.tar.gz", "size": 2048},
+                            {"name": "docs", "url": "https://ci.example.com/1/docs.zip"}
+                        ]
+                    }"#,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::ACCEPTED);
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @r#"
+            :sunny: Try build successful ([buildkite](https://ci.example.com/1))
+            Build commit: merge-0-pr-1 (`merge-0-pr-1`, parent: `main-sha1`)
+            Artifacts: [dist](https://ci.example.com/1/dist.tar.gz) (2.0 KiB), [docs](https://ci.example.com/1/docs.zip)
+
+            <!-- homu: {"type":"TryBuildCompleted","merge_sha":"merge-0-pr-1"} -->
+            "#);
+
+            let (_, body) = tester
+                .web_request(http::Method::GET, "/api/v1/builds/1", None)
+                .await?;
+            let build: serde_json::Value = serde_json::from_str(&body)?;
+            insta::assert_snapshot!(serde_json::to_string_pretty(&build["artifacts"])?, @r#"
+            [
+              {
+                "name": "dist",
+                "size": 2048,
+                "url": "https://ci.example.com/1/dist.tar.gz"
+              },
+              {
+                "name": "docs",
+                "size": null,
+                "url": "https://ci.example.com/1/docs.zip"
+              }
+            ]
+            "#);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn emergency_stop_requires_operator_role(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let (status, _) = tester
+                .web_request(
+                    http::Method::POST,
+                    "/api/v1/emergency-stop",
+                    Some("{}".to_string()),
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::FORBIDDEN);
+            let (status, _) = tester
+                .authenticated_web_request(
+                    http::Method::POST,
+                    "/api/v1/emergency-stop",
+                    TEST_API_TOKEN,
+                    Some("{}".to_string()),
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::UNAUTHORIZED);
+            assert!(tester.db().get_emergency_stop().await?.is_none());
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn emergency_stop_pauses_merge_queue(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            let (status, _) = tester
+                .authenticated_web_request(
+                    http::Method::POST,
+                    "/api/v1/emergency-stop",
+                    TEST_ADMIN_TOKEN,
+                    Some(r#"{"reason": "Compromised CI secret"}"#.to_string()),
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+
+            let (_, body) = tester
+                .web_request(http::Method::GET, "/api/v1/emergency-stop", None)
+                .await?;
+            let mut stop: serde_json::Value = serde_json::from_str(&body)?;
+            stop.as_object_mut().unwrap().remove("since");
+            insta::assert_snapshot!(serde_json::to_string_pretty(&stop)?, @r#"
+            {
+              "active": true,
+              "reason": "Compromised CI secret"
+            }
+            "#);
+
+            tester.process_merge_queue().await;
+            tester.get_pr_copy(()).await.expect_no_auto_build();
+
+            let (status, _) = tester
+                .authenticated_web_request(
+                    http::Method::DELETE,
+                    "/api/v1/emergency-stop",
+                    TEST_ADMIN_TOKEN,
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+            tester.start_auto_build(()).await?;
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn operator_cannot_clear_emergency_stop(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let (status, _) = tester
+                .authenticated_web_request(
+                    http::Method::POST,
+                    "/api/v1/emergency-stop",
+                    TEST_OPERATOR_TOKEN,
+                    Some("{}".to_string()),
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+            let (status, body) = tester
+                .authenticated_web_request(
+                    http::Method::DELETE,
+                    "/api/v1/emergency-stop",
+                    TEST_OPERATOR_TOKEN,
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::FORBIDDEN);
+            insta::assert_snapshot!(body, @"The admin role is required to perform clear emergency stop");
+            assert!(tester.db().get_emergency_stop().await?.is_some());
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn get_config(pool: sqlx::PgPool) {
+        BorsBuilder::new(pool)
+            .github(GitHubState::default().with_default_config(
+                r#"
+timeout = 600
+spurious_failure_patterns = ["runner .* lost"]
+fork_try_builds = { restricted = { environment = "fork" } }
+
+[labels]
+approved = ["+approved", "-waiting"]
+"#,
+            ))
+            .run_test(async |tester: &mut BorsTester| {
+                let (status, _) = tester
+                    .web_request(
+                        http::Method::GET,
+                        "/api/v1/repos/rust-lang/borstest/config",
+                        None,
+                    )
+                    .await?;
+                assert_eq!(status, http::StatusCode::FORBIDDEN);
+
+                let (status, body) = tester
+                    .authenticated_web_request(
+                        http::Method::GET,
+                        "/api/v1/repos/rust-lang/borstest/config",
+                        TEST_ADMIN_TOKEN,
+                        None,
+                    )
+                    .await?;
+                assert_eq!(status, http::StatusCode::OK);
+                let config: serde_json::Value = serde_json::from_str(&body)?;
+                insta::assert_snapshot!(serde_json::to_string_pretty(&config)?, @r#"
+                {
+                  "accessible_comments": false,
+                  "acknowledge_with_reaction": false,
+                  "approval_on_reopen": "restore",
+                  "approve_on_review": false,
+                  "auto_build_timeout": null,
+                  "block_labels": [],
+                  "branch_protection_checks": false,
+                  "build_env": {},
+                  "close_already_merged": false,
+                  "command_aliases": {},
+                  "command_cooldown": null,
+                  "comment_signature": null,
+                  "comment_style": "emoji",
+                  "dry_run": false,
+                  "fork_try_builds": {
+                    "restricted": {
+                      "environment": "fork"
+                    }
+                  },
+                  "hide_outdated_comments": false,
+                  "info_rejected_commands": false,
+                  "labels": {
+                    "approved": [
+                      "+approved",
+                      "-waiting"
+                    ],
+                    "unapproved": [
+                      "-approved",
+                      "+waiting"
+                    ]
+                  },
+                  "labels_blocking_approval": [],
+                  "language": "en",
+                  "lint": {
+                    "forbidden_markers": [],
+                    "max_subject_length": null,
+                    "require_issue_reference": false,
+                    "title_pattern": null
+                  },
+                  "maintenance_windows": [],
+                  "max_parallel_auto_builds": 1,
+                  "max_queue_size": null,
+                  "merge_queue_branches": [],
+                  "merge_queue_enabled": false,
+                  "min_ci_time": null,
+                  "notifications_per_minute": 10,
+                  "path_queues": {},
+                  "permissions": {
+                    "reviewers": [],
+                    "try_users": []
+                  },
+                  "preempt_on_priority": false,
+                  "priority_aging_interval": null,
+                  "queue_check_runs": false,
+                  "required_approvals": 1,
+                  "required_checklist_items": [],
+                  "required_status_contexts": [],
+                  "restart_auto_build_on_edit": true,
+                  "rollup_failure_iffy": false,
+                  "spurious_failure_patterns": [
+                    "runner .* lost"
+                  ],
+                  "spurious_failure_retries": 3,
+                  "squash_merge": false,
+                  "stale_approval": "require_reapproval",
+                  "timeout": 600,
+                  "tracking_issue": null,
+                  "tree_closure_notifications": false,
+                  "try_artifact_retention": null,
+                  "try_build_comment_template": null,
+                  "try_enabled": true,
+                  "unapprove_on_push": true,
+                  "unreachable_try_parent": "warn",
+                  "validate_try_jobs": false
+                }
+                "#);
+                Ok(())
+            })
+            .await;
+    }
+
+    #[test]
+    fn redact_config_secrets() {
+        let mut value = serde_json::json!({
+            "timeout": 3600,
+            "notifications": [{"url": "https://example.com", "auth_token": "abc"}],
+            "webhook_secret": "def",
+        });
+        super::redact_secrets(&mut value);
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "timeout": 3600,
+                "notifications": [{"url": "https://example.com", "auth_token": "<redacted>"}],
+                "webhook_secret": "<redacted>",
+            })
+        );
+    }
+
+    #[sqlx::test]
+    async fn dead_letters_require_admin_role(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let (status, _) = tester
+                .authenticated_web_request(
+                    http::Method::GET,
+                    "/api/v1/dead-letters",
+                    TEST_OPERATOR_TOKEN,
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::FORBIDDEN);
+            let (status, body) = tester
+                .authenticated_web_request(
+                    http::Method::POST,
+                    "/api/v1/dead-letters/replay",
+                    TEST_OPERATOR_TOKEN,
+                    Some("{}".to_string()),
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::FORBIDDEN);
+            insta::assert_snapshot!(body, @"The admin role is required to perform replay dead letters");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn reload_repository(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let (status, body) = tester
+                .authenticated_web_request(
+                    http::Method::POST,
+                    "/api/v1/repos/rust-lang/borstest/reload",
+                    TEST_OPERATOR_TOKEN,
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::FORBIDDEN);
+            insta::assert_snapshot!(body, @"The admin role is required to perform reload repository");
+            let (status, _) = tester
+                .authenticated_web_request(
+                    http::Method::POST,
+                    "/api/v1/repos/rust-lang/unknown/reload",
+                    TEST_ADMIN_TOKEN,
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::NOT_FOUND);
+
+            tester
+                .modify_repo(&default_repo_name(), |repo| {
+                    repo.config = format!("min_ci_time = 60\n{}", repo.config);
+                })
+                .await;
+            let (status, body) = tester
+                .authenticated_web_request(
+                    http::Method::POST,
+                    "/api/v1/repos/rust-lang/borstest/reload",
+                    TEST_ADMIN_TOKEN,
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::ACCEPTED);
+            insta::assert_snapshot!(body, @"Reload of repository rust-lang/borstest was requested");
+            tester
+                .wait_for(|| async {
+                    let log = tester
+                        .db()
+                        .get_audit_log(&default_repo_name(), None, 10)
+                        .await?;
+                    Ok(log.iter().any(|entry| {
+                        entry.action == "config_reloaded"
+                            && entry.details.as_deref() == Some("min_ci_time")
+                    }))
+                })
+                .await?;
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn replay_dead_letter(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester
+                .with_dead_lettered_webhooks(async |tester: &mut BorsTester| {
+                    tester.post_comment("@bors ping").await
+                })
+                .await?;
+            let (status, body) = tester
+                .authenticated_web_request(
+                    http::Method::GET,
+                    "/api/v1/dead-letters",
+                    TEST_ADMIN_TOKEN,
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+            let mut dead_letters: serde_json::Value = serde_json::from_str(&body)?;
+            let id = dead_letters[0]["id"].as_i64().unwrap();
+            dead_letters[0]
+                .as_object_mut()
+                .unwrap()
+                .remove("created_at");
+            dead_letters[0].as_object_mut().unwrap().remove("id");
+            insta::assert_snapshot!(serde_json::to_string_pretty(&dead_letters)?, @r#"
+            [
+              {
+                "attempts": 1,
+                "error": "Handler failed",
+                "event_type": "issue_comment",
+                "replay_requested": false,
+                "repository": "rust-lang/borstest"
+              }
+            ]
+            "#);
+
+            let (status, _) = tester
+                .authenticated_web_request(
+                    http::Method::POST,
+                    "/api/v1/dead-letters/replay",
+                    TEST_ADMIN_TOKEN,
+                    Some(format!(r#"{{"id": {}}}"#, id + 1)),
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::NOT_FOUND);
+
+            let (status, body) = tester
+                .authenticated_web_request(
+                    http::Method::POST,
+                    "/api/v1/dead-letters/replay",
+                    TEST_ADMIN_TOKEN,
+                    Some(format!(r#"{{"id": {id}}}"#)),
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::ACCEPTED);
+            insta::assert_snapshot!(body, @r#"{"requested":1}"#);
+            insta::assert_snapshot!(tester.get_next_comment_text(()).await?, @"Pong 🏓!");
+            tester.replay_dead_letters().await;
+            assert!(tester.db().get_dead_letters().await?.is_empty());
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn reload_secrets_requires_provider(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let (status, _) = tester
+                .authenticated_web_request(
+                    http::Method::POST,
+                    "/api/v1/secrets/reload",
+                    TEST_OPERATOR_TOKEN,
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::FORBIDDEN);
+            let (status, body) = tester
+                .authenticated_web_request(
+                    http::Method::POST,
+                    "/api/v1/secrets/reload",
+                    TEST_ADMIN_TOKEN,
+                    None,
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::FORBIDDEN);
+            insta::assert_snapshot!(body, @"Reloading secrets is not enabled");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn emergency_stop_does_not_merge_finished_build(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.approve(()).await?;
+            tester.start_auto_build(()).await?;
+            tester.db().set_emergency_stop(None).await?;
+            tester
+                .workflow_full_success(tester.auto_branch().await)
+                .await?;
+            tester.process_merge_queue().await;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_status(PullRequestStatus::Open)
+                .expect_auto_build(|build| build.status == BuildStatus::Success);
+            Ok(())
+        })
+        .await;
+    }
+}
@@ -0,0 +1,1274 @@
+//! Read-only GraphQL API over the data stored by bors, meant for dashboards that need more
+//! flexible queries than the JSON API offers.
+//!
+//! The schema is small, so queries are resolved by hand instead of with a GraphQL server library.
+//! Fields can be aliased and take arguments (including variables), and queries can use named and
+//! inline fragments. Directives are not supported.
+//!
+//! The API requires an operator token. Queries that are nested too deeply, that use too many
+//! aliases, or that would resolve too many fields are rejected before they are executed. Nested
+//! fields of the nodes of a connection are loaded with one database query for the whole page.
+//!
+//! ```graphql
+//! type Query {
+//!   repository(name: String!): Repository
+//!   pullRequest(repository: String!, number: Int!): PullRequest
+//!   build(id: Int!): Build
+//! }
+//!
+//! type Repository {
+//!   name: String!
+//!   treeState: TreeState!
+//!   # Non-closed PRs, in the order in which the merge queue would process them.
+//!   pullRequests(status: String, queueStatus: String, baseBranch: String, first: Int, after: String): PullRequestConnection!
+//!   # Builds of the repository, newest first.
+//!   builds(status: String, first: Int, after: String): BuildConnection!
+//! }
+//!
+//! type TreeState { closed: Boolean!, priority: Int, source: String, reason: String, until: String }
+//! type PullRequestConnection { totalCount: Int!, nodes: [PullRequest!]!, pageInfo: PageInfo! }
+//! type BuildConnection { nodes: [Build!]!, pageInfo: PageInfo! }
+//! type PageInfo { hasNextPage: Boolean!, endCursor: String }
+//!
+//! type PullRequest {
+//!   number: Int!, title: String!, author: String!, assignees: [String!]!, status: String!,
+//!   baseBranch: String!, mergeable: String!, approvedBy: String, approvedSha: String,
+//!   priority: Int, rollup: String, queueStatus: String!, held: Boolean!,
+//!   parked: Boolean!, parkReason: String, tryBuild: Build, autoBuild: Build, retries: [BuildRetry!]!
+//! }
+//! type BuildRetry { buildId: Int!, retriedBy: String, reason: String, createdAt: String! }
+//!
+//! type Build {
+//!   id: Int!, repository: String!, branch: String!, commitSha: String!, parent: String!,
+//!   status: String!, createdAt: String!, checkRunId: Int,
+//!   workflows(status: String): [Workflow!]!, artifacts: [Artifact!]!
+//! }
+//! type Workflow { name: String!, url: String!, runId: Int!, type: String!, status: String! }
+//! type Artifact { name: String!, url: String!, size: Int }
+//! ```
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use axum::Json;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use graphql_parser::query::{
+    Definition, Document, Field, FragmentDefinition, OperationDefinition, Selection, SelectionSet,
+    TypeCondition, Value,
+};
+use http::HeaderMap;
+use serde::Deserialize;
+use serde_json::{Map, Value as JsonValue, json};
+
+use super::ServerStateRef;
+use super::api::{
+    Role, TreeStateResponse, authorize_role, get_repo, mergeable_state_name, queue_status_name,
+    workflow_status_name, workflow_type_name,
+};
+use crate::database::{
+    BuildArtifactModel, BuildModel, BuildRetryModel, BuildStatus, PullRequestModel, RepoModel,
+    WorkflowModel,
+};
+use crate::github::{AppError, GithubRepoName, PullRequestNumber};
+use crate::utils::sort_queue::sort_queue_prs;
+
+/// Number of items returned from a connection if `first` is not specified.
+const DEFAULT_PAGE_SIZE: usize = 50;
+/// Maximum number of items that can be requested from a connection at once.
+const MAX_PAGE_SIZE: usize = 100;
+/// Maximum nesting depth of the fields of a query.
+const MAX_QUERY_DEPTH: usize = 8;
+/// Maximum number of aliased fields in a query. Aliases allow resolving the same field many
+/// times, with different arguments.
+const MAX_QUERY_ALIASES: usize = 20;
+/// Maximum number of fields that a query can resolve. The fields below a connection are counted
+/// once for each node of the requested page.
+const MAX_QUERY_COMPLEXITY: usize = 5_000;
+
+// Names are parsed into owned strings, which keeps the resolver futures `Send`
+type Selections<'a> = SelectionSet<'a, String>;
+type QueryField<'a> = Field<'a, String>;
+type QueryValue<'a> = Value<'a, String>;
+type Fragment<'a> = FragmentDefinition<'a, String>;
+
+#[derive(Deserialize)]
+pub(super) struct GraphQlRequest {
+    query: String,
+    #[serde(default)]
+    variables: Option<Map<String, JsonValue>>,
+}
+
+/// Error caused by an invalid query, which is reported to the client in the `errors` field of
+/// the response.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+struct QueryError(String);
+
+/// Executes a GraphQL query.
+pub(super) async fn graphql_handler(
+    State(state): State<ServerStateRef>,
+    headers: HeaderMap,
+    Json(request): Json<GraphQlRequest>,
+) -> Result<Response, AppError> {
+    if let Err(response) = authorize_role(
+        &state,
+        &headers,
+        Role::Operator,
+        "query the GraphQL API",
+        None,
+    )
+    .await?
+    {
+        return Ok(response);
+    }
+    let variables = request.variables.unwrap_or_default();
+    match execute(&state, &variables, &request.query).await {
+        Ok(data) => Ok(Json(json!({ "data": data })).into_response()),
+        Err(error) => match error.downcast::<QueryError>() {
+            Ok(error) => Ok(Json(json!({
+                "data": null,
+                "errors": [{ "message": error.0 }],
+            }))
+            .into_response()),
+            Err(error) => Err(error.into()),
+        },
+    }
+}
+
+async fn execute(
+    state: &ServerStateRef,
+    variables: &Map<String, JsonValue>,
+    query: &str,
+) -> anyhow::Result<JsonValue> {
+    let document: Document<String> = graphql_parser::parse_query(query)
+        .map_err(|error| QueryError(format!("Invalid query: {error}")))?;
+    let mut operation = None;
+    let mut fragments = HashMap::new();
+    for definition in &document.definitions {
+        match definition {
+            Definition::Operation(definition) => {
+                if operation.replace(definition).is_some() {
+                    return Err(QueryError(
+                        "The request must contain a single operation".to_string(),
+                    )
+                    .into());
+                }
+            }
+            Definition::Fragment(fragment) => {
+                if fragments.insert(fragment.name.as_str(), fragment).is_some() {
+                    return Err(QueryError(format!(
+                        "Fragment `{}` is defined more than once",
+                        fragment.name
+                    ))
+                    .into());
+                }
+            }
+        }
+    }
+    let selections = match operation {
+        Some(OperationDefinition::SelectionSet(selections)) => selections,
+        Some(OperationDefinition::Query(query)) => &query.selection_set,
+        Some(_) => return Err(QueryError("Only queries are supported".to_string()).into()),
+        None => {
+            return Err(QueryError("The request must contain a query".to_string()).into());
+        }
+    };
+
+    let executor = Executor {
+        state,
+        variables,
+        fragments,
+        preloaded: Mutex::default(),
+    };
+    executor.check_limits(selections)?;
+    executor.resolve_query(selections).await
+}
+
+struct Executor<'a> {
+    state: &'a ServerStateRef,
+    variables: &'a Map<String, JsonValue>,
+    /// Named fragments defined in the query document.
+    fragments: HashMap<&'a str, &'a Fragment<'a>>,
+    preloaded: Mutex<Preloaded>,
+}
+
+/// Nested fields of the nodes of a connection, which are loaded with a single database query for
+/// all the nodes of a page, instead of one query per node.
+#[derive(Default)]
+struct Preloaded {
+    /// Workflows keyed by build ID.
+    workflows: HashMap<i32, Vec<WorkflowModel>>,
+    /// Artifacts keyed by build ID.
+    artifacts: HashMap<i32, Vec<BuildArtifactModel>>,
+    /// Build retries keyed by PR ID.
+    retries: HashMap<i32, Vec<BuildRetryModel>>,
+}
+
+/// Cost of a query, accumulated while its fields are visited.
+#[derive(Default)]
+struct QueryCost {
+    aliases: usize,
+    complexity: usize,
+}
+
+impl<'a> Executor<'a> {
+    /// Rejects queries that would be too expensive to execute, before any field is resolved.
+    /// Fragments are expanded, so that they cannot be used to get around the limits.
+    fn check_limits(&self, selections: &Selections<'a>) -> anyhow::Result<()> {
+        self.measure(selections, 1, 1, &mut Vec::new(), &mut QueryCost::default())
+    }
+
+    fn measure<'b>(
+        &'b self,
+        selections: &'b Selections<'a>,
+        depth: usize,
+        multiplier: usize,
+        spread_fragments: &mut Vec<&'b str>,
+        cost: &mut QueryCost,
+    ) -> anyhow::Result<()> {
+        for selection in &selections.items {
+            match selection {
+                Selection::Field(field) => {
+                    if depth > MAX_QUERY_DEPTH {
+                        return Err(QueryError(format!(
+                            "The query is nested more than {MAX_QUERY_DEPTH} levels deep"
+                        ))
+                        .into());
+                    }
+                    if field.alias.is_some() {
+                        cost.aliases += 1;
+                        if cost.aliases > MAX_QUERY_ALIASES {
+                            return Err(QueryError(format!(
+                                "The query contains more than {MAX_QUERY_ALIASES} aliases"
+                            ))
+                            .into());
+                        }
+                    }
+                    cost.complexity = cost.complexity.saturating_add(multiplier);
+                    if cost.complexity > MAX_QUERY_COMPLEXITY {
+                        return Err(QueryError(format!(
+                            "The query is too complex, it would resolve more than {MAX_QUERY_COMPLEXITY} fields"
+                        ))
+                        .into());
+                    }
+                    let multiplier = match field.name.as_str() {
+                        "pullRequests" | "builds" => {
+                            multiplier.saturating_mul(self.page_size(field)?)
+                        }
+                        _ => multiplier,
+                    };
+                    self.measure(
+                        &field.selection_set,
+                        depth + 1,
+                        multiplier,
+                        spread_fragments,
+                        cost,
+                    )?;
+                }
+                Selection::InlineFragment(fragment) => {
+                    self.measure(
+                        &fragment.selection_set,
+                        depth,
+                        multiplier,
+                        spread_fragments,
+                        cost,
+                    )?;
+                }
+                Selection::FragmentSpread(spread) => {
+                    let name = spread.fragment_name.as_str();
+                    if spread_fragments.contains(&name) {
+                        return Err(QueryError(format!("Fragment `{name}` spreads itself")).into());
+                    }
+                    let fragment = self.fragment(name)?;
+                    spread_fragments.push(name);
+                    self.measure(
+                        &fragment.selection_set,
+                        depth,
+                        multiplier,
+                        spread_fragments,
+                        cost,
+                    )?;
+                    spread_fragments.pop();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn fragment(&self, name: &str) -> anyhow::Result<&'a Fragment<'a>> {
+        self.fragments
+            .get(name)
+            .copied()
+            .ok_or_else(|| QueryError(format!("Unknown fragment `{name}`")).into())
+    }
+
+    /// Returns the fields selected on an object of type `typename`, including the fields of the
+    /// fragments spread into the selection set.
+    fn fields<'b>(
+        &'b self,
+        typename: &str,
+        selections: &'b Selections<'a>,
+    ) -> anyhow::Result<Vec<&'b QueryField<'a>>> {
+        let mut fields = Vec::new();
+        self.collect_fields(typename, selections, &mut fields)?;
+        Ok(fields)
+    }
+
+    fn collect_fields<'b>(
+        &'b self,
+        typename: &str,
+        selections: &'b Selections<'a>,
+        fields: &mut Vec<&'b QueryField<'a>>,
+    ) -> anyhow::Result<()> {
+        for selection in &selections.items {
+            match selection {
+                Selection::Field(field) => fields.push(field),
+                Selection::InlineFragment(fragment) => {
+                    check_type_condition(typename, fragment.type_condition.as_ref())?;
+                    self.collect_fields(typename, &fragment.selection_set, fields)?;
+                }
+                Selection::FragmentSpread(spread) => {
+                    let fragment = self.fragment(&spread.fragment_name)?;
+                    check_type_condition(typename, Some(&fragment.type_condition))?;
+                    self.collect_fields(typename, &fragment.selection_set, fields)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Selects the requested fields from an object whose fields are all scalars.
+    fn select_fields(
+        &self,
+        typename: &str,
+        value: JsonValue,
+        selections: &Selections<'a>,
+    ) -> anyhow::Result<JsonValue> {
+        let mut object = Map::new();
+        for field in self.fields(typename, selections)? {
+            let Some(value) = value.get(field.name.as_str()) else {
+                return Err(unknown_field(typename, &field.name));
+            };
+            object.insert(response_key(field).to_string(), value.clone());
+        }
+        Ok(JsonValue::Object(object))
+    }
+
+    /// Loads the nested fields requested from the given PRs in batches.
+    async fn preload_pull_requests(
+        &self,
+        prs: &[&PullRequestModel],
+        selections: &Selections<'a>,
+    ) -> anyhow::Result<()> {
+        if prs.is_empty() {
+            return Ok(());
+        }
+        for field in self.fields("PullRequest", selections)? {
+            match field.name.as_str() {
+                "retries" => {
+                    let mut retries: HashMap<i32, Vec<BuildRetryModel>> =
+                        prs.iter().map(|pr| (pr.id, vec![])).collect();
+                    for retry in self.state.db.get_build_retries_for_prs(prs).await? {
+                        retries
+                            .entry(retry.pull_request_id)
+                            .or_default()
+                            .push(retry);
+                    }
+                    self.preloaded.lock().unwrap().retries.extend(retries);
+                }
+                "tryBuild" | "autoBuild" => {
+                    let builds: Vec<&BuildModel> = prs
+                        .iter()
+                        .filter_map(|pr| {
+                            if field.name == "tryBuild" {
+                                pr.try_build.as_ref()
+                            } else {
+                                pr.auto_build.as_ref()
+                            }
+                        })
+                        .collect();
+                    self.preload_builds(&builds, &field.selection_set).await?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads the nested fields requested from the given builds in batches.
+    async fn preload_builds(
+        &self,
+        builds: &[&BuildModel],
+        selections: &Selections<'a>,
+    ) -> anyhow::Result<()> {
+        if builds.is_empty() {
+            return Ok(());
+        }
+        for field in self.fields("Build", selections)? {
+            match field.name.as_str() {
+                "workflows" => {
+                    let mut workflows: HashMap<i32, Vec<WorkflowModel>> =
+                        builds.iter().map(|build| (build.id, vec![])).collect();
+                    for workflow in self.state.db.get_workflows_for_builds(builds).await? {
+                        workflows
+                            .entry(workflow.build.id)
+                            .or_default()
+                            .push(workflow);
+                    }
+                    self.preloaded.lock().unwrap().workflows.extend(workflows);
+                }
+                "artifacts" => {
+                    let mut artifacts: HashMap<i32, Vec<BuildArtifactModel>> =
+                        builds.iter().map(|build| (build.id, vec![])).collect();
+                    for artifact in self.state.db.get_artifacts_for_builds(builds).await? {
+                        artifacts
+                            .entry(artifact.build_id)
+                            .or_default()
+                            .push(artifact);
+                    }
+                    self.preloaded.lock().unwrap().artifacts.extend(artifacts);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn resolve_query(&self, selections: &Selections<'a>) -> anyhow::Result<JsonValue> {
+        let mut object = Map::new();
+        for field in self.fields("Query", selections)? {
+            let value = match field.name.as_str() {
+                "repository" => {
+                    let repo_name = parse_repo_name(&self.required_string(field, "name")?)?;
+                    match get_repo(self.state, &repo_name).await? {
+                        Some(repo) => self.resolve_repository(&repo, &field.selection_set).await?,
+                        None => JsonValue::Null,
+                    }
+                }
+                "pullRequest" => {
+                    let repo_name = parse_repo_name(&self.required_string(field, "repository")?)?;
+                    let number = self.required_int(field, "number")?;
+                    let pr = match (
+                        get_repo(self.state, &repo_name).await?,
+                        u64::try_from(number),
+                    ) {
+                        (Some(_), Ok(number)) => {
+                            self.state
+                                .db
+                                .get_pull_request(&repo_name, PullRequestNumber(number))
+                                .await?
+                        }
+                        _ => None,
+                    };
+                    match pr {
+                        Some(pr) => self.resolve_pull_request(&pr, &field.selection_set).await?,
+                        None => JsonValue::Null,
+                    }
+                }
+                "build" => {
+                    let build = match i32::try_from(self.required_int(field, "id")?) {
+                        Ok(id) => self.state.db.get_build(id).await?,
+                        Err(_) => None,
+                    };
+                    match build {
+                        Some(build) if self.state.repositories.contains_key(&build.repository) => {
+                            self.resolve_build(&build, &field.selection_set).await?
+                        }
+                        _ => JsonValue::Null,
+                    }
+                }
+                name => return Err(unknown_field("Query", name)),
+            };
+            object.insert(response_key(field).to_string(), value);
+        }
+        Ok(JsonValue::Object(object))
+    }
+
+    async fn resolve_repository(
+        &self,
+        repo: &RepoModel,
+        selections: &Selections<'a>,
+    ) -> anyhow::Result<JsonValue> {
+        let mut object = Map::new();
+        for field in self.fields("Repository", selections)? {
+            let value = match field.name.as_str() {
+                "name" => json!(repo.name.to_string()),
+                "treeState" => self.select_fields(
+                    "TreeState",
+                    serde_json::to_value(TreeStateResponse::from(repo.tree_state.clone()))?,
+                    &field.selection_set,
+                )?,
+                "pullRequests" => self.resolve_pull_requests(repo, field).await?,
+                "builds" => self.resolve_builds(repo, field).await?,
+                name => return Err(unknown_field("Repository", name)),
+            };
+            object.insert(response_key(field).to_string(), value);
+        }
+        Ok(JsonValue::Object(object))
+    }
+
+    /// Resolves the non-closed PRs of a repository in merge queue order.
+    /// The cursor of a PR is its offset in the filtered queue.
+    async fn resolve_pull_requests(
+        &self,
+        repo: &RepoModel,
+        field: &QueryField<'a>,
+    ) -> anyhow::Result<JsonValue> {
+        let status = self.optional_string(field, "status")?;
+        let queue_status = self.optional_string(field, "queueStatus")?;
+        let base_branch = self.optional_string(field, "baseBranch")?;
+        let first = self.page_size(field)?;
+        let offset = match self.optional_string(field, "after")? {
+            Some(cursor) => cursor
+                .parse::<usize>()
+                .map_err(|_| invalid_cursor(&cursor))?,
+            None => 0,
+        };
+
+        let prs: Vec<PullRequestModel> = sort_queue_prs(
+            self.state
+                .db
+                .get_nonclosed_pull_requests(&repo.name)
+                .await?,
+            self.state.priority_aging_interval(&repo.name),
+        )
+        .into_iter()
+        .filter(|pr| {
+            status
+                .as_ref()
+                .is_none_or(|status| pr.pr_status.to_string() == *status)
+                && queue_status
+                    .as_ref()
+                    .is_none_or(|status| queue_status_name(&pr.queue_status()) == *status)
+                && base_branch
+                    .as_ref()
+                    .is_none_or(|branch| pr.base_branch == *branch)
+        })
+        .collect();
+        let total_count = prs.len();
+        let page: Vec<PullRequestModel> = prs.into_iter().skip(offset).take(first).collect();
+        let end = offset + page.len();
+        let page_info = json!({
+            "hasNextPage": end < total_count,
+            "endCursor": (!page.is_empty()).then(|| end.to_string()),
+        });
+
+        let mut object = Map::new();
+        for field in self.fields("PullRequestConnection", &field.selection_set)? {
+            let value = match field.name.as_str() {
+                "totalCount" => json!(total_count),
+                "pageInfo" => {
+                    self.select_fields("PageInfo", page_info.clone(), &field.selection_set)?
+                }
+                "nodes" => {
+                    let prs: Vec<&PullRequestModel> = page.iter().collect();
+                    self.preload_pull_requests(&prs, &field.selection_set)
+                        .await?;
+                    let mut nodes = Vec::with_capacity(page.len());
+                    for pr in &page {
+                        nodes.push(self.resolve_pull_request(pr, &field.selection_set).await?);
+                    }
+                    JsonValue::Array(nodes)
+                }
+                name => return Err(unknown_field("PullRequestConnection", name)),
+            };
+            object.insert(response_key(field).to_string(), value);
+        }
+        Ok(JsonValue::Object(object))
+    }
+
+    /// Resolves the builds of a repository, newest first.
+    /// The cursor of a build is its ID, so that pages stay stable when new builds are started.
+    async fn resolve_builds(
+        &self,
+        repo: &RepoModel,
+        field: &QueryField<'a>,
+    ) -> anyhow::Result<JsonValue> {
+        let status = self
+            .optional_string(field, "status")?
+            .map(|status| parse_build_status(&status))
+            .transpose()?;
+        let first = self.page_size(field)?;
+        let before_id = match self.optional_string(field, "after")? {
+            Some(cursor) => Some(cursor.parse::<i32>().map_err(|_| invalid_cursor(&cursor))?),
+            None => None,
+        };
+
+        // Load one more build to find out if there is a next page
+        let mut builds = self
+            .state
+            .db
+            .get_builds(&repo.name, status, before_id, first as i64 + 1)
+            .await?;
+        let has_next_page = builds.len() > first;
+        builds.truncate(first);
+        let page_info = json!({
+            "hasNextPage": has_next_page,
+            "endCursor": builds.last().map(|build| build.id.to_string()),
+        });
+
+        let mut object = Map::new();
+        for field in self.fields("BuildConnection", &field.selection_set)? {
+            let value = match field.name.as_str() {
+                "pageInfo" => {
+                    self.select_fields("PageInfo", page_info.clone(), &field.selection_set)?
+                }
+                "nodes" => {
+                    let page: Vec<&BuildModel> = builds.iter().collect();
+                    self.preload_builds(&page, &field.selection_set).await?;
+                    let mut nodes = Vec::with_capacity(builds.len());
+                    for build in &builds {
+                        nodes.push(self.resolve_build(build, &field.selection_set).await?);
+                    }
+                    JsonValue::Array(nodes)
+                }
+                name => return Err(unknown_field("BuildConnection", name)),
+            };
+            object.insert(response_key(field).to_string(), value);
+        }
+        Ok(JsonValue::Object(object))
+    }
+
+    async fn resolve_pull_request(
+        &self,
+        pr: &PullRequestModel,
+        selections: &Selections<'a>,
+    ) -> anyhow::Result<JsonValue> {
+        let mut object = Map::new();
+        for field in self.fields("PullRequest", selections)? {
+            let value = match field.name.as_str() {
+                "number" => json!(pr.number.0),
+                "title" => json!(pr.title),
+                "author" => json!(pr.author),
+                "assignees" => json!(pr.assignees),
+                "status" => json!(pr.pr_status.to_string()),
+                "baseBranch" => json!(pr.base_branch),
+                "mergeable" => json!(mergeable_state_name(&pr.mergeable_state)),
+                "approvedBy" => json!(pr.approver()),
+                "approvedSha" => json!(pr.approved_sha()),
+                "priority" => json!(pr.priority),
+                "rollup" => json!(pr.rollup.as_ref().map(|rollup| rollup.to_string())),
+                "queueStatus" => json!(queue_status_name(&pr.queue_status())),
+                "held" => json!(pr.held),
+                "parked" => json!(pr.parked),
+                "parkReason" => json!(pr.park_reason),
+                "tryBuild" | "autoBuild" => {
+                    let build = if field.name == "tryBuild" {
+                        &pr.try_build
+                    } else {
+                        &pr.auto_build
+                    };
+                    match build {
+                        Some(build) => self.resolve_build(build, &field.selection_set).await?,
+                        None => JsonValue::Null,
+                    }
+                }
+                "retries" => {
+                    let preloaded = self.preloaded.lock().unwrap().retries.remove(&pr.id);
+                    let retries = match preloaded {
+                        Some(retries) => retries,
+                        None => self.state.db.get_build_retries(pr).await?,
+                    };
+                    retries
+                        .into_iter()
+                        .map(|retry| {
+                            self.select_fields(
+                                "BuildRetry",
+                                json!({
+                                    "buildId": retry.build.id,
+                                    "retriedBy": retry.retried_by,
+                                    "reason": retry.reason,
+                                    "createdAt": retry.created_at.to_rfc3339(),
+                                }),
+                                &field.selection_set,
+                            )
+                        })
+                        .collect::<anyhow::Result<JsonValue>>()?
+                }
+                name => return Err(unknown_field("PullRequest", name)),
+            };
+            object.insert(response_key(field).to_string(), value);
+        }
+        Ok(JsonValue::Object(object))
+    }
+
+    async fn resolve_build(
+        &self,
+        build: &BuildModel,
+        selections: &Selections<'a>,
+    ) -> anyhow::Result<JsonValue> {
+        let mut object = Map::new();
+        for field in self.fields("Build", selections)? {
+            let value = match field.name.as_str() {
+                "id" => json!(build.id),
+                "repository" => json!(build.repository.to_string()),
+                "branch" => json!(build.branch),
+                "commitSha" => json!(build.commit_sha),
+                "parent" => json!(build.parent),
+                "status" => json!(build.status.to_string()),
+                "createdAt" => json!(build.created_at.to_rfc3339()),
+                "checkRunId" => json!(build.check_run_id),
+                "workflows" => {
+                    let status = self.optional_string(field, "status")?;
+                    let preloaded = self.preloaded.lock().unwrap().workflows.remove(&build.id);
+                    let workflows = match preloaded {
+                        Some(workflows) => workflows,
+                        None => self.state.db.get_workflows_for_build(build).await?,
+                    };
+                    workflows
+                        .into_iter()
+                        .filter(|workflow| {
+                            status.as_deref().is_none_or(|status| {
+                                workflow_status_name(&workflow.status) == status
+                            })
+                        })
+                        .map(|workflow| {
+                            self.select_fields(
+                                "Workflow",
+                                json!({
+                                    "name": workflow.name,
+                                    "url": workflow.url,
+                                    "runId": workflow.run_id.0,
+                                    "type": workflow_type_name(&workflow.workflow_type),
+                                    "status": workflow_status_name(&workflow.status),
+                                }),
+                                &field.selection_set,
+                            )
+                        })
+                        .collect::<anyhow::Result<JsonValue>>()?
+                }
+                "artifacts" => {
+                    let preloaded = self.preloaded.lock().unwrap().artifacts.remove(&build.id);
+                    let artifacts = match preloaded {
+                        Some(artifacts) => artifacts,
+                        None => self.state.db.get_artifacts_for_build(build).await?,
+                    };
+                    artifacts
+                        .into_iter()
+                        .map(|artifact| {
+                            self.select_fields(
+                                "Artifact",
+                                json!({
+                                    "name": artifact.name,
+                                    "url": artifact.url,
+                                    "size": artifact.size,
+                                }),
+                                &field.selection_set,
+                            )
+                        })
+                        .collect::<anyhow::Result<JsonValue>>()?
+                }
+                name => return Err(unknown_field("Build", name)),
+            };
+            object.insert(response_key(field).to_string(), value);
+        }
+        Ok(JsonValue::Object(object))
+    }
+
+    /// Returns the value of the argument `name` of `field`, with variables substituted.
+    /// Missing arguments and variables are `null`.
+    fn argument(&self, field: &QueryField<'_>, name: &str) -> JsonValue {
+        field
+            .arguments
+            .iter()
+            .find(|(argument, _)| *argument == name)
+            .map(|(_, value)| self.to_json(value))
+            .unwrap_or(JsonValue::Null)
+    }
+
+    fn to_json(&self, value: &QueryValue<'_>) -> JsonValue {
+        match value {
+            Value::Variable(name) => self.variables.get(name).cloned().unwrap_or_default(),
+            Value::Int(number) => json!(number.as_i64()),
+            Value::Float(number) => json!(number),
+            Value::String(value) => json!(value),
+            Value::Boolean(value) => json!(value),
+            Value::Null => JsonValue::Null,
+            Value::Enum(value) => json!(value),
+            Value::List(values) => values.iter().map(|value| self.to_json(value)).collect(),
+            Value::Object(fields) => fields
+                .iter()
+                .map(|(name, value)| (name.to_string(), self.to_json(value)))
+                .collect(),
+        }
+    }
+
+    fn optional_string(
+        &self,
+        field: &QueryField<'_>,
+        name: &str,
+    ) -> anyhow::Result<Option<String>> {
+        match self.argument(field, name) {
+            JsonValue::Null => Ok(None),
+            JsonValue::String(value) => Ok(Some(value)),
+            _ => Err(invalid_argument(field, name, "a string")),
+        }
+    }
+
+    fn required_string(&self, field: &QueryField<'_>, name: &str) -> anyhow::Result<String> {
+        self.optional_string(field, name)?
+            .ok_or_else(|| missing_argument(field, name))
+    }
+
+    fn optional_int(&self, field: &QueryField<'_>, name: &str) -> anyhow::Result<Option<i64>> {
+        match self.argument(field, name) {
+            JsonValue::Null => Ok(None),
+            value => value
+                .as_i64()
+                .map(Some)
+                .ok_or_else(|| invalid_argument(field, name, "an integer")),
+        }
+    }
+
+    fn required_int(&self, field: &QueryField<'_>, name: &str) -> anyhow::Result<i64> {
+        self.optional_int(field, name)?
+            .ok_or_else(|| missing_argument(field, name))
+    }
+
+    /// Returns the number of items requested from a connection with the `first` argument.
+    fn page_size(&self, field: &QueryField<'_>) -> anyhow::Result<usize> {
+        match self.optional_int(field, "first")? {
+            None => Ok(DEFAULT_PAGE_SIZE),
+            Some(first) if (1..=MAX_PAGE_SIZE as i64).contains(&first) => Ok(first as usize),
+            Some(_) => Err(QueryError(format!(
+                "Argument `first` of field `{}` must be between 1 and {MAX_PAGE_SIZE}",
+                field.name
+            ))
+            .into()),
+        }
+    }
+}
+
+/// Checks that a fragment with the given type condition can be spread on `typename`.
+fn check_type_condition(
+    typename: &str,
+    condition: Option<&TypeCondition<'_, String>>,
+) -> anyhow::Result<()> {
+    match condition {
+        Some(TypeCondition::On(name)) if name != typename => Err(QueryError(format!(
+            "Fragment on type `{name}` cannot be spread on type `{typename}`"
+        ))
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// Results are keyed by the alias of a field, if it has one.
+fn response_key<'a>(field: &'a QueryField<'_>) -> &'a str {
+    field.alias.as_deref().unwrap_or(&field.name)
+}
+
+fn parse_repo_name(name: &str) -> anyhow::Result<GithubRepoName> {
+    name.parse::<GithubRepoName>()
+        .map_err(|error| QueryError(format!("Invalid repository name `{name}`: {error}")).into())
+}
+
+fn parse_build_status(status: &str) -> anyhow::Result<BuildStatus> {
+    match status {
+        "pending" => Ok(BuildStatus::Pending),
+        "success" => Ok(BuildStatus::Success),
+        "failure" => Ok(BuildStatus::Failure),
+        "cancelled" => Ok(BuildStatus::Cancelled),
+        "timeouted" => Ok(BuildStatus::Timeouted),
+        _ => Err(QueryError(format!("Unknown build status `{status}`")).into()),
+    }
+}
+
+fn unknown_field(typename: &str, name: &str) -> anyhow::Error {
+    QueryError(format!("Unknown field `{name}` on type `{typename}`")).into()
+}
+
+fn missing_argument(field: &QueryField<'_>, name: &str) -> anyhow::Error {
+    QueryError(format!(
+        "Argument `{name}` of field `{}` is required",
+        field.name
+    ))
+    .into()
+}
+
+fn invalid_argument(field: &QueryField<'_>, name: &str, expected: &str) -> anyhow::Error {
+    QueryError(format!(
+        "Argument `{name}` of field `{}` must be {expected}",
+        field.name
+    ))
+    .into()
+}
+
+fn invalid_cursor(cursor: &str) -> anyhow::Error {
+    QueryError(format!("Invalid cursor `{cursor}`")).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{BorsTester, Comment, TEST_OPERATOR_TOKEN, default_repo_name, run_test};
+
+    async fn graphql(
+        tester: &mut BorsTester,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> anyhow::Result<serde_json::Value> {
+        let (status, body) = tester
+            .authenticated_web_request(
+                http::Method::POST,
+                "/api/graphql",
+                TEST_OPERATOR_TOKEN,
+                Some(serde_json::json!({ "query": query, "variables": variables }).to_string()),
+            )
+            .await?;
+        assert_eq!(status, http::StatusCode::OK);
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    #[sqlx::test]
+    async fn query_pull_request_with_builds(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+            tester.workflow_start(tester.try_branch().await).await?;
+
+            let response = graphql(
+                tester,
+                r#"
+query($number: Int!) {
+    pr: pullRequest(repository: "rust-lang/borstest", number: $number) {
+        number
+        queueStatus
+        autoBuild { id }
+        tryBuild {
+            id
+            status
+            parent
+            workflows { name status }
+        }
+    }
+}
+"#,
+                serde_json::json!({ "number": 1 }),
+            )
+            .await?;
+            insta::assert_snapshot!(serde_json::to_string_pretty(&response)?, @r#"
+            {
+              "data": {
+                "pr": {
+                  "autoBuild": null,
+                  "number": 1,
+                  "queueStatus": "not_approved",
+                  "tryBuild": {
+                    "id": 1,
+                    "parent": "main-sha1",
+                    "status": "pending",
+                    "workflows": [
+                      {
+                        "name": "Workflow1",
+                        "status": "pending"
+                      }
+                    ]
+                  }
+                }
+              }
+            }
+            "#);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn query_queue_pagination(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            // Load the unapproved PR into the database
+            tester.post_comment("@bors info").await?;
+            tester.expect_comments((), 1).await;
+            let pr2 = tester.open_pr(default_repo_name(), |_| {}).await?;
+            tester
+                .post_comment(Comment::new(pr2.id(), "@bors r+ p=5"))
+                .await?;
+            tester.expect_comments(pr2.id(), 1).await;
+
+            let query = r#"
+query($after: String) {
+    repository(name: "rust-lang/borstest") {
+        pullRequests(first: 1, after: $after) {
+            totalCount
+            nodes { number }
+            pageInfo { hasNextPage endCursor }
+        }
+    }
+}
+"#;
+            let response = graphql(tester, query, serde_json::json!({})).await?;
+            let prs = &response["data"]["repository"]["pullRequests"];
+            assert_eq!(prs["totalCount"], 2);
+            assert_eq!(prs["nodes"][0]["number"], 2);
+            assert_eq!(prs["pageInfo"]["hasNextPage"], true);
+
+            let after = prs["pageInfo"]["endCursor"].clone();
+            let response = graphql(tester, query, serde_json::json!({ "after": after })).await?;
+            let prs = &response["data"]["repository"]["pullRequests"];
+            assert_eq!(prs["nodes"][0]["number"], 1);
+            assert_eq!(prs["pageInfo"]["hasNextPage"], false);
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn query_filter_pull_requests(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.open_pr(default_repo_name(), |_| {}).await?;
+            tester.approve(()).await?;
+
+            let response = graphql(
+                tester,
+                r#"{ repository(name: "rust-lang/borstest") { pullRequests(queueStatus: "approved") { nodes { number approvedBy } } } }"#,
+                serde_json::json!({}),
+            )
+            .await?;
+            insta::assert_snapshot!(
+                response["data"]["repository"]["pullRequests"]["nodes"].to_string(),
+                @r#"[{"approvedBy":"default-user","number":1}]"#
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn query_builds(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+            tester.workflow_full_failure(tester.try_branch().await).await?;
+            tester.expect_comments((), 1).await;
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+
+            let response = graphql(
+                tester,
+                r#"{ repository(name: "rust-lang/borstest") { builds { nodes { id status workflows { status } } } failed: builds(status: "failure") { nodes { id } } } }"#,
+                serde_json::json!({}),
+            )
+            .await?;
+            insta::assert_snapshot!(
+                response["data"]["repository"].to_string(),
+                @r#"{"builds":{"nodes":[{"id":2,"status":"pending","workflows":[]},{"id":1,"status":"failure","workflows":[{"status":"failure"}]}]},"failed":{"nodes":[{"id":1}]}}"#
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn query_unknown_repository(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let response = graphql(
+                tester,
+                r#"{ repository(name: "foo/bar") { name } }"#,
+                serde_json::json!({}),
+            )
+            .await?;
+            assert!(response["data"]["repository"].is_null());
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn query_unknown_field(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let response = graphql(
+                tester,
+                r#"{ build(id: 1) { id } repository(name: "rust-lang/borstest") { foo } }"#,
+                serde_json::json!({}),
+            )
+            .await?;
+            insta::assert_snapshot!(
+                response.to_string(),
+                @r#"{"data":null,"errors":[{"message":"Unknown field `foo` on type `Repository`"}]}"#
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn query_requires_token(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let (status, body) = tester
+                .web_request(
+                    http::Method::POST,
+                    "/api/graphql",
+                    Some(serde_json::json!({ "query": "{ build(id: 1) { id } }" }).to_string()),
+                )
+                .await?;
+            assert_eq!(status, http::StatusCode::FORBIDDEN);
+            insta::assert_snapshot!(body, @"The operator role is required to perform query the GraphQL API");
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn query_with_fragments(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors try").await?;
+            tester.expect_comments((), 1).await;
+
+            let response = graphql(
+                tester,
+                r#"
+query {
+    repository(name: "rust-lang/borstest") {
+        pullRequests { nodes { ...PrFields } }
+    }
+}
+
+fragment PrFields on PullRequest {
+    number
+    tryBuild { ... on Build { id status } }
+}
+"#,
+                serde_json::json!({}),
+            )
+            .await?;
+            insta::assert_snapshot!(
+                response["data"]["repository"]["pullRequests"]["nodes"].to_string(),
+                @r#"[{"number":1,"tryBuild":{"id":1,"status":"pending"}}]"#
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn fragment_on_wrong_type(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let response = graphql(
+                tester,
+                r#"{ build(id: 1) { id } repository(name: "rust-lang/borstest") { ... on Build { id } } }"#,
+                serde_json::json!({}),
+            )
+            .await?;
+            insta::assert_snapshot!(
+                response["errors"][0]["message"],
+                @r#""Fragment on type `Build` cannot be spread on type `Repository`""#
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn fragment_cycle(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let response = graphql(
+                tester,
+                r#"
+{ build(id: 1) { ...A } }
+fragment A on Build { id ...B }
+fragment B on Build { status ...A }
+"#,
+                serde_json::json!({}),
+            )
+            .await?;
+            insta::assert_snapshot!(
+                response["errors"][0]["message"],
+                @r#""Fragment `A` spreads itself""#
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn query_too_deep(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let response = graphql(
+                tester,
+                r#"{ a { b { c { d { e { f { g { h { i } } } } } } } } }"#,
+                serde_json::json!({}),
+            )
+            .await?;
+            insta::assert_snapshot!(
+                response["errors"][0]["message"],
+                @r#""The query is nested more than 8 levels deep""#
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn query_too_many_aliases(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let query = format!(
+                "{{ {} }}",
+                (0..21)
+                    .map(|i| format!("b{i}: build(id: {i}) {{ id }}"))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            );
+            let response = graphql(tester, &query, serde_json::json!({})).await?;
+            insta::assert_snapshot!(
+                response["errors"][0]["message"],
+                @r#""The query contains more than 20 aliases""#
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn query_too_complex(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let response = graphql(
+                tester,
+                r#"
+{
+    repository(name: "rust-lang/borstest") {
+        pullRequests(first: 100) { nodes { ...Pr } }
+        builds(first: 100) { nodes { ...Build } }
+    }
+}
+fragment Pr on PullRequest {
+    number title author assignees status baseBranch mergeable approvedBy approvedSha priority
+    rollup queueStatus held parked parkReason
+    tryBuild { ...Build }
+    autoBuild { ...Build }
+}
+fragment Build on Build {
+    id repository branch commitSha parent status createdAt checkRunId
+    workflows { name url runId type status }
+    artifacts { name url size }
+}
+"#,
+                serde_json::json!({}),
+            )
+            .await?;
+            insta::assert_snapshot!(
+                response["errors"][0]["message"],
+                @r#""The query is too complex, it would resolve more than 5000 fields""#
+            );
+            Ok(())
+        })
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn mutations_are_not_supported(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            let response = graphql(
+                tester,
+                r#"mutation { approve(number: 1) { number } }"#,
+                serde_json::json!({}),
+            )
+            .await?;
+            assert_eq!(
+                response["errors"][0]["message"],
+                "Only queries are supported"
+            );
+            Ok(())
+        })
+        .await;
+    }
+}
@@ -4,24 +4,26 @@ use std::fmt::Debug;
 use axum::RequestExt;
 use axum::body::Bytes;
 use axum::extract::FromRequest;
-use axum::http::request::Parts;
 use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use chrono::{DateTime, Utc};
 use hmac::{Hmac, Mac};
 use octocrab::models::events::payload::{
     IssueCommentEventAction, IssueCommentEventPayload, PullRequestEventChangesFrom,
     PullRequestReviewCommentEventAction, PullRequestReviewCommentEventPayload,
 };
-use octocrab::models::pulls::{PullRequest, Review};
+use octocrab::models::pulls::{PullRequest, Review, ReviewState};
 use octocrab::models::webhook_events::payload::PullRequestWebhookEventAction;
-use octocrab::models::{Author, CheckSuiteId, Repository, workflows};
+use octocrab::models::{Author, CheckSuiteId, CommentId, Repository, workflows};
 use secrecy::{ExposeSecret, SecretString};
+use sha1::Sha1;
 use sha2::Sha256;
 
 use crate::bors::event::{
-    BorsEvent, BorsGlobalEvent, BorsRepositoryEvent, PullRequestAssigned, PullRequestClosed,
-    PullRequestComment, PullRequestConvertedToDraft, PullRequestEdited, PullRequestMerged,
-    PullRequestOpened, PullRequestPushed, PullRequestReadyForReview, PullRequestReopened,
-    PullRequestUnassigned, PushToBranch, WorkflowRunCompleted, WorkflowRunStarted,
+    BorsEvent, BorsGlobalEvent, BorsRepositoryEvent, CommitStatusChanged, PullRequestAssigned,
+    PullRequestClosed, PullRequestComment, PullRequestConvertedToDraft, PullRequestEdited,
+    PullRequestLabelsChanged, PullRequestMerged, PullRequestOpened, PullRequestPushed,
+    PullRequestReadyForReview, PullRequestReopened, PullRequestUnassigned, PushToBranch,
+    ReactionTarget, ReviewVerdict, WorkflowRunCompleted, WorkflowRunStarted,
 };
 use crate::database::{WorkflowStatus, WorkflowType};
 use crate::github::server::ServerStateRef;
@@ -41,6 +43,17 @@ impl WebhookSecret {
     }
 }
 
+/// Algorithm with which GitHub signs the payloads of webhooks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WebhookSignature {
+    /// HMAC-SHA256, sent in the `X-Hub-Signature-256` header.
+    #[default]
+    Sha256,
+    /// HMAC-SHA1, sent in the `X-Hub-Signature` header. Only useful for old GitHub Enterprise
+    /// Server installations that do not sign webhooks with SHA-256.
+    Sha1,
+}
+
 #[derive(serde::Deserialize, Debug)]
 struct WebhookPushToBranchEvent {
     repository: Repository,
@@ -55,6 +68,13 @@ struct WebhookRepository {
     repository: Repository,
 }
 
+/// https://docs.github.com/en/webhooks/webhook-events-and-payloads#repository
+#[derive(serde::Deserialize, Debug)]
+struct WebhookRepositoryEvent<'a> {
+    action: &'a str,
+    repository: Repository,
+}
+
 #[derive(serde::Deserialize, Debug)]
 struct WebhookWorkflowRun<'a> {
     action: &'a str,
@@ -69,6 +89,44 @@ struct WorkflowRunInner {
     run: workflows::Run,
 }
 
+/// https://docs.github.com/en/webhooks/webhook-events-and-payloads#status
+#[derive(serde::Deserialize, Debug)]
+struct WebhookCommitStatus {
+    sha: String,
+    context: String,
+    state: String,
+    target_url: Option<String>,
+    branches: Vec<WebhookCommitStatusBranch>,
+    repository: Repository,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct WebhookCommitStatusBranch {
+    name: String,
+}
+
+/// https://docs.github.com/en/webhooks/webhook-events-and-payloads#check_run
+#[derive(serde::Deserialize, Debug)]
+struct WebhookCheckRun<'a> {
+    action: &'a str,
+    check_run: WebhookCheckRunInner,
+    repository: Repository,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct WebhookCheckRunInner {
+    name: String,
+    head_sha: String,
+    conclusion: Option<String>,
+    details_url: Option<String>,
+    check_suite: WebhookCheckRunSuite,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct WebhookCheckRunSuite {
+    head_branch: Option<String>,
+}
+
 #[derive(Debug, serde::Deserialize)]
 struct WebhookPullRequestReviewEvent<'a> {
     action: &'a str,
@@ -91,6 +149,14 @@ struct WebhookPullRequestEvent {
 #[derive(Debug, serde::Deserialize)]
 struct WebhookPullRequestChanges {
     base: Option<WebhookPullRequestBaseChanges>,
+    title: Option<WebhookPullRequestTextChanges>,
+    body: Option<WebhookPullRequestTextChanges>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct WebhookPullRequestTextChanges {
+    /// GitHub sends `null` if the PR did not have a description.
+    from: Option<String>,
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -98,9 +164,71 @@ struct WebhookPullRequestBaseChanges {
     sha: Option<PullRequestEventChangesFrom>,
 }
 
+/// octocrab parses review comments as issue comments, which do not include the review thread.
+/// https://docs.github.com/en/webhooks/webhook-events-and-payloads#pull_request_review_comment
+#[derive(Debug, serde::Deserialize)]
+struct WebhookPullRequestReviewComment {
+    comment: WebhookReviewCommentThread,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct WebhookReviewCommentThread {
+    in_reply_to_id: Option<CommentId>,
+}
+
+/// The raw content of a webhook, which allows parsing the webhook again later.
+#[derive(Clone, Debug)]
+pub struct WebhookPayload {
+    /// Type of the event (the value of the `X-GitHub-Event` header).
+    pub event_type: String,
+    pub body: String,
+}
+
+impl WebhookPayload {
+    /// Parses the event from the webhook.
+    /// Returns `None` if the webhook does not represent an event that bors handles.
+    pub fn parse(&self) -> anyhow::Result<Option<BorsEvent>> {
+        parse_webhook_event(&self.event_type, self.body.as_bytes())
+    }
+
+    /// Returns the time when GitHub has created the event, if the payload of the event contains
+    /// it. GitHub does not send the time of the event in a header, so it is taken from the
+    /// timestamp of the object that the event is about.
+    pub fn event_time(&self) -> Option<DateTime<Utc>> {
+        let payload: serde_json::Value = serde_json::from_str(&self.body).ok()?;
+        let pointers: &[&str] = match self.event_type.as_str() {
+            "issue_comment" => &["/comment/created_at"],
+            "pull_request" => &["/pull_request/updated_at"],
+            "pull_request_review" => &["/review/submitted_at"],
+            "pull_request_review_comment" => &["/comment/updated_at", "/comment/created_at"],
+            "push" => &["/repository/pushed_at"],
+            "repository" => &["/repository/updated_at"],
+            "workflow_run" => &["/workflow_run/updated_at"],
+            "status" => &["/updated_at"],
+            "check_run" => &["/check_run/completed_at", "/check_run/started_at"],
+            _ => &[],
+        };
+        pointers
+            .iter()
+            .find_map(|pointer| match payload.pointer(pointer)? {
+                serde_json::Value::String(time) => DateTime::parse_from_rfc3339(time)
+                    .ok()
+                    .map(|time| time.to_utc()),
+                // Some timestamps (e.g. `pushed_at` of push events) are UNIX timestamps
+                serde_json::Value::Number(time) => DateTime::from_timestamp(time.as_i64()?, 0),
+                _ => None,
+            })
+    }
+}
+
 /// axum extractor for GitHub webhook events.
-#[derive(Debug)]
-pub struct GitHubWebhook(pub BorsEvent);
+pub struct GitHubWebhook(pub BorsEvent, pub WebhookPayload);
+
+impl Debug for GitHubWebhook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("GitHubWebhook").field(&self.0).finish()
+    }
+}
 
 const REQUEST_BODY_LIMIT: usize = 10 * 1024 * 1024;
 
@@ -123,16 +251,30 @@ impl FromRequest<ServerStateRef> for GitHubWebhook {
             })?;
 
         // Verify that the request is valid
-        if !verify_gh_signature(&parts.headers, &body, state.get_webhook_secret()) {
+        if !verify_gh_signature(
+            &parts.headers,
+            &body,
+            state.get_secrets().get_webhook_secret(),
+            state.webhook_signature(),
+        ) {
             tracing::error!("Webhook request failed, could not authenticate webhook");
             return Err(StatusCode::BAD_REQUEST);
         }
 
+        let Some(event_type) = parts.headers.get("x-github-event") else {
+            tracing::error!("Cannot parse webhook event: x-github-event header not found");
+            return Err(StatusCode::BAD_REQUEST);
+        };
+        let payload = WebhookPayload {
+            event_type: event_type.to_str().unwrap_or_default().to_string(),
+            body: String::from_utf8_lossy(&body).into_owned(),
+        };
+
         // Parse webhook content
-        match parse_webhook_event(parts, &body) {
+        match payload.parse() {
             Ok(Some(event)) => {
                 tracing::trace!("Received webhook event {event:?}");
-                Ok(GitHubWebhook(event))
+                Ok(GitHubWebhook(event, payload))
             }
             Ok(None) => Err(StatusCode::OK),
             Err(error) => {
@@ -143,29 +285,27 @@ impl FromRequest<ServerStateRef> for GitHubWebhook {
     }
 }
 
-fn parse_webhook_event(request: Parts, body: &[u8]) -> anyhow::Result<Option<BorsEvent>> {
-    let Some(event_type) = request.headers.get("x-github-event") else {
-        return Err(anyhow::anyhow!("x-github-event header not found"));
-    };
-
+fn parse_webhook_event(event_type: &str, body: &[u8]) -> anyhow::Result<Option<BorsEvent>> {
     tracing::trace!(
-        "Webhook: event_type `{}`, payload\n{}",
-        event_type.to_str().unwrap_or_default(),
+        "Webhook: event_type `{event_type}`, payload\n{}",
         std::str::from_utf8(body).unwrap_or_default()
     );
 
-    match event_type.as_bytes() {
-        b"push" => parse_push_event(body),
-        b"issue_comment" => parse_issue_comment_event(body),
-        b"pull_request" => parse_pull_request_events(body),
-        b"pull_request_review" => parse_pull_request_review_events(body),
-        b"pull_request_review_comment" => parse_pull_request_review_comment_events(body),
-        b"installation_repositories" | b"installation" => Ok(Some(BorsEvent::Global(
+    match event_type {
+        "push" => parse_push_event(body),
+        "issue_comment" => parse_issue_comment_event(body),
+        "pull_request" => parse_pull_request_events(body),
+        "pull_request_review" => parse_pull_request_review_events(body),
+        "pull_request_review_comment" => parse_pull_request_review_comment_events(body),
+        "installation_repositories" | "installation" => Ok(Some(BorsEvent::Global(
             BorsGlobalEvent::InstallationsChanged,
         ))),
-        b"workflow_run" => parse_workflow_run_events(body),
+        "repository" => parse_repository_events(body),
+        "workflow_run" => parse_workflow_run_events(body),
+        "status" => parse_commit_status_event(body),
+        "check_run" => parse_check_run_events(body),
         _ => {
-            tracing::debug!("Ignoring unknown event type {:?}", event_type.to_str());
+            tracing::debug!("Ignoring unknown event type {event_type:?}");
             Ok(None)
         }
     }
@@ -215,6 +355,8 @@ fn parse_pull_request_events(body: &[u8]) -> anyhow::Result<Option<BorsEvent>> {
                 BorsRepositoryEvent::PullRequestEdited(PullRequestEdited {
                     repository: repository_name,
                     pull_request: payload.pull_request.into(),
+                    from_title: changes.title.map(|title| title.from.unwrap_or_default()),
+                    from_body: changes.body.map(|body| body.from.unwrap_or_default()),
                     from_base_sha: changes
                         .base
                         .and_then(|base| base.sha)
@@ -278,6 +420,14 @@ fn parse_pull_request_events(body: &[u8]) -> anyhow::Result<Option<BorsEvent>> {
                 pull_request: payload.pull_request.into(),
             }),
         ))),
+        PullRequestWebhookEventAction::Labeled | PullRequestWebhookEventAction::Unlabeled => {
+            Ok(Some(BorsEvent::Repository(
+                BorsRepositoryEvent::PullRequestLabelsChanged(PullRequestLabelsChanged {
+                    repository: repository_name,
+                    pull_request: payload.pull_request.into(),
+                }),
+            )))
+        }
         _ => Ok(None),
     }
 }
@@ -299,7 +449,12 @@ fn parse_pull_request_review_comment_events(body: &[u8]) -> anyhow::Result<Optio
 
     let payload: PullRequestReviewCommentEventPayload = serde_json::from_slice(body)?;
     if payload.action == PullRequestReviewCommentEventAction::Created {
-        let comment = parse_pr_review_comment(repository_name, payload);
+        let review_comment: WebhookPullRequestReviewComment = serde_json::from_slice(body)?;
+        let comment = parse_pr_review_comment(
+            repository_name,
+            payload,
+            review_comment.comment.in_reply_to_id,
+        );
         Ok(Some(BorsEvent::Repository(BorsRepositoryEvent::Comment(
             comment,
         ))))
@@ -308,6 +463,17 @@ fn parse_pull_request_review_comment_events(body: &[u8]) -> anyhow::Result<Optio
     }
 }
 
+fn parse_repository_events(body: &[u8]) -> anyhow::Result<Option<BorsEvent>> {
+    let payload: WebhookRepositoryEvent = serde_json::from_slice(body)?;
+    let repository_name = parse_repository_name(&payload.repository)?;
+    let event = match payload.action {
+        "archived" => BorsRepositoryEvent::RepositoryArchived(repository_name),
+        "unarchived" => BorsRepositoryEvent::RepositoryUnarchived(repository_name),
+        _ => return Ok(None),
+    };
+    Ok(Some(BorsEvent::Repository(event)))
+}
+
 fn parse_workflow_run_events(body: &[u8]) -> anyhow::Result<Option<BorsEvent>> {
     let payload: WebhookWorkflowRun = serde_json::from_slice(body)?;
     let repository_name = parse_repository_name(&payload.repository)?;
@@ -358,10 +524,64 @@ fn parse_workflow_run_events(body: &[u8]) -> anyhow::Result<Option<BorsEvent>> {
     Ok(result)
 }
 
+fn parse_commit_status_event(body: &[u8]) -> anyhow::Result<Option<BorsEvent>> {
+    let payload: WebhookCommitStatus = serde_json::from_slice(body)?;
+    let repository = parse_repository_name(&payload.repository)?;
+    let status = match payload.state.as_str() {
+        "pending" => WorkflowStatus::Pending,
+        "success" => WorkflowStatus::Success,
+        _ => WorkflowStatus::Failure,
+    };
+    Ok(Some(BorsEvent::Repository(
+        BorsRepositoryEvent::CommitStatusChanged(CommitStatusChanged {
+            repository,
+            branches: payload
+                .branches
+                .into_iter()
+                .map(|branch| branch.name)
+                .collect(),
+            commit_sha: CommitSha(payload.sha),
+            context: payload.context,
+            url: payload.target_url,
+            status,
+        }),
+    )))
+}
+
+fn parse_check_run_events(body: &[u8]) -> anyhow::Result<Option<BorsEvent>> {
+    let payload: WebhookCheckRun = serde_json::from_slice(body)?;
+    let repository = parse_repository_name(&payload.repository)?;
+    let status = match payload.action {
+        "created" => WorkflowStatus::Pending,
+        "completed" => match payload.check_run.conclusion.as_deref() {
+            Some("success" | "neutral" | "skipped") => WorkflowStatus::Success,
+            _ => WorkflowStatus::Failure,
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(BorsEvent::Repository(
+        BorsRepositoryEvent::CommitStatusChanged(CommitStatusChanged {
+            repository,
+            branches: payload
+                .check_run
+                .check_suite
+                .head_branch
+                .into_iter()
+                .collect(),
+            commit_sha: CommitSha(payload.check_run.head_sha),
+            context: payload.check_run.name,
+            url: payload.check_run.details_url,
+            status,
+        }),
+    )))
+}
+
 fn parse_pr_review_comment(
     repo: GithubRepoName,
     payload: PullRequestReviewCommentEventPayload,
+    in_reply_to_id: Option<CommentId>,
 ) -> PullRequestComment {
+    let review_thread = in_reply_to_id.unwrap_or(payload.comment.id);
     let user = payload.comment.user.into();
     PullRequestComment {
         repository: repo,
@@ -369,6 +589,9 @@ fn parse_pr_review_comment(
         pr_number: PullRequestNumber(payload.pull_request.number),
         text: payload.comment.body.unwrap_or_default(),
         html_url: payload.comment.html_url.to_string(),
+        review_thread: Some(review_thread),
+        review_verdict: None,
+        reaction_target: Some(ReactionTarget::ReviewComment(payload.comment.id)),
     }
 }
 
@@ -377,6 +600,18 @@ fn parse_comment_from_pr_review(
 ) -> anyhow::Result<PullRequestComment> {
     let repository_name = parse_repository_name(&payload.repository)?;
     let user = payload.sender.into();
+    let review_verdict = match payload.review.state {
+        Some(ReviewState::Approved) => {
+            payload
+                .review
+                .commit_id
+                .map(|commit| ReviewVerdict::Approved {
+                    commit: CommitSha(commit),
+                })
+        }
+        Some(ReviewState::ChangesRequested) => Some(ReviewVerdict::ChangesRequested),
+        _ => None,
+    };
 
     Ok(PullRequestComment {
         repository: repository_name,
@@ -384,6 +619,9 @@ fn parse_comment_from_pr_review(
         pr_number: PullRequestNumber(payload.pull_request.number),
         text: payload.review.body.unwrap_or_default(),
         html_url: payload.review.html_url.to_string(),
+        review_thread: None,
+        review_verdict,
+        reaction_target: None,
     })
 }
 
@@ -403,6 +641,9 @@ fn parse_pr_comment(
         text: payload.comment.body.unwrap_or_default(),
         pr_number: PullRequestNumber(payload.issue.number),
         html_url: payload.comment.html_url.to_string(),
+        review_thread: None,
+        review_verdict: None,
+        reaction_target: Some(ReactionTarget::IssueComment(payload.comment.id)),
     })
 }
 
@@ -414,28 +655,39 @@ fn parse_repository_name(repository: &Repository) -> anyhow::Result<GithubRepoNa
     Ok(GithubRepoName::new(repo_owner, repo_name))
 }
 
-type HmacSha256 = Hmac<Sha256>;
-
-/// Verifies that the request is properly signed by GitHub with SHA-256 and the passed `secret`.
+/// Verifies that the request is properly signed by GitHub with the given `algorithm` and the
+/// passed `secret`.
 fn verify_gh_signature(
     headers: &HeaderMap<HeaderValue>,
     body: &[u8],
     secret: &WebhookSecret,
+    algorithm: WebhookSignature,
 ) -> bool {
-    let Some(signature) = headers.get("x-hub-signature-256").map(|v| v.as_bytes()) else {
+    let (header, prefix) = match algorithm {
+        WebhookSignature::Sha256 => ("x-hub-signature-256", "sha256="),
+        WebhookSignature::Sha1 => ("x-hub-signature", "sha1="),
+    };
+    let Some(signature) = headers.get(header).map(|v| v.as_bytes()) else {
         return false;
     };
     let Some(signature) = signature
-        .get(b"sha256=".len()..)
+        .strip_prefix(prefix.as_bytes())
         .and_then(|v| hex::decode(v).ok())
     else {
         return false;
     };
 
-    let mut mac =
-        HmacSha256::new_from_slice(secret.expose().as_bytes()).expect("Cannot create HMAC key");
+    let key = secret.expose().as_bytes();
+    match algorithm {
+        WebhookSignature::Sha256 => verify_hmac::<Hmac<Sha256>>(key, body, &signature),
+        WebhookSignature::Sha1 => verify_hmac::<Hmac<Sha1>>(key, body, &signature),
+    }
+}
+
+fn verify_hmac<M: Mac + hmac::digest::KeyInit>(key: &[u8], body: &[u8], signature: &[u8]) -> bool {
+    let mut mac = <M as Mac>::new_from_slice(key).expect("Cannot create HMAC key");
     mac.update(body);
-    mac.verify_slice(&signature).is_ok()
+    mac.verify_slice(signature).is_ok()
 }
 
 #[cfg(test)]
@@ -444,7 +696,10 @@ mod tests {
     use std::sync::Arc;
 
     use axum::extract::FromRequest;
+    use axum::http::HeaderMap;
+    use hmac::{Hmac, Mac};
     use hyper::StatusCode;
+    use sha1::Sha1;
     use sqlx::PgPool;
     use tokio::sync::mpsc;
 
@@ -452,18 +707,70 @@ mod tests {
     use crate::bors::event::{BorsEvent, BorsGlobalEvent};
     use crate::github::server::{ServerState, ServerStateRef};
     use crate::github::webhook::GitHubWebhook;
+    use crate::github::webhook::WebhookPayload;
     use crate::github::webhook::WebhookSecret;
     use crate::tests::default_cmd_prefix;
     use crate::tests::load_test_file;
     use crate::tests::{TEST_WEBHOOK_SECRET, create_webhook_request};
 
+    use super::{WebhookSignature, verify_gh_signature};
+
+    #[test]
+    fn verify_sha256_signature() {
+        let request = create_webhook_request("push", "{}");
+        let secret = WebhookSecret::new(TEST_WEBHOOK_SECRET.to_string());
+        assert!(verify_gh_signature(
+            request.headers(),
+            b"{}",
+            &secret,
+            WebhookSignature::Sha256
+        ));
+        assert!(!verify_gh_signature(
+            request.headers(),
+            b"{ }",
+            &secret,
+            WebhookSignature::Sha256
+        ));
+        assert!(!verify_gh_signature(
+            request.headers(),
+            b"{}",
+            &secret,
+            WebhookSignature::Sha1
+        ));
+    }
+
+    #[test]
+    fn verify_sha1_signature() {
+        let mut mac = Hmac::<Sha1>::new_from_slice(TEST_WEBHOOK_SECRET.as_bytes()).unwrap();
+        mac.update(b"{}");
+        let signature = format!("sha1={}", hex::encode(mac.finalize().into_bytes()));
+        let mut headers = HeaderMap::new();
+        headers.insert("x-hub-signature", signature.parse().unwrap());
+
+        let secret = WebhookSecret::new(TEST_WEBHOOK_SECRET.to_string());
+        assert!(verify_gh_signature(
+            &headers,
+            b"{}",
+            &secret,
+            WebhookSignature::Sha1
+        ));
+        // SHA-1 signatures are only accepted if they were configured
+        assert!(!verify_gh_signature(
+            &headers,
+            b"{}",
+            &secret,
+            WebhookSignature::Sha256
+        ));
+    }
+
     #[tokio::test]
     async fn installation_suspend() {
         assert!(matches!(
             check_webhook("webhook/installation-suspend.json", "installation").await,
-            Ok(GitHubWebhook(BorsEvent::Global(
-                BorsGlobalEvent::InstallationsChanged
-            )))
+            Ok(GitHubWebhook(
+                BorsEvent::Global(BorsGlobalEvent::InstallationsChanged),
+                _
+            ))
         ));
     }
 
@@ -471,12 +778,34 @@ mod tests {
     async fn installation_unsuspend() {
         assert!(matches!(
             check_webhook("webhook/installation-unsuspend.json", "installation").await,
-            Ok(GitHubWebhook(BorsEvent::Global(
-                BorsGlobalEvent::InstallationsChanged
-            )))
+            Ok(GitHubWebhook(
+                BorsEvent::Global(BorsGlobalEvent::InstallationsChanged),
+                _
+            ))
         ));
     }
 
+    #[tokio::test]
+    async fn repository_archived() {
+        insta::assert_debug_snapshot!(
+            check_webhook("webhook/repository-archived.json", "repository").await,
+            @r#"
+            Ok(
+                GitHubWebhook(
+                    Repository(
+                        RepositoryArchived(
+                            GithubRepoName {
+                                owner: "sakib25800",
+                                name: "bors-test-2",
+                            },
+                        ),
+                    ),
+                ),
+            )
+            "#
+        );
+    }
+
     #[tokio::test]
     async fn push_to_branch() {
         insta::assert_debug_snapshot!(
@@ -541,6 +870,15 @@ mod tests {
                             ),
                             text: "hello bors",
                             html_url: "https://github.com/Kobzol/bors-kindergarten/pull/5#issuecomment-1420770715",
+                            review_thread: None,
+                            review_verdict: None,
+                            reaction_target: Some(
+                                IssueComment(
+                                    CommentId(
+                                        1420770715,
+                                    ),
+                                ),
+                            ),
                         },
                     ),
                 ),
@@ -612,7 +950,10 @@ mod tests {
                                     "foobar",
                                     "baz",
                                 ],
+                                from_fork: false,
                             },
+                            from_title: None,
+                            from_body: None,
                             from_base_sha: Some(
                                 CommitSha(
                                     "1f1ee58e3067678d3752dd5f6f3abb936325fbb8",
@@ -689,6 +1030,7 @@ mod tests {
                                     "foobar",
                                     "baz",
                                 ],
+                                from_fork: false,
                             },
                         },
                     ),
@@ -739,6 +1081,9 @@ mod tests {
                             ),
                             text: "review comment",
                             html_url: "https://github.com/Kobzol/bors-kindergarten/pull/6#pullrequestreview-1476702458",
+                            review_thread: None,
+                            review_verdict: None,
+                            reaction_target: None,
                         },
                     ),
                 ),
@@ -806,6 +1151,7 @@ mod tests {
                                 assignees: [],
                                 status: Open,
                                 labels: [],
+                                from_fork: false,
                             },
                             draft: false,
                         },
@@ -877,6 +1223,7 @@ mod tests {
                                 labels: [
                                     "foo",
                                 ],
+                                from_fork: false,
                             },
                         },
                     ),
@@ -945,6 +1292,7 @@ mod tests {
                                 assignees: [],
                                 status: Merged,
                                 labels: [],
+                                from_fork: false,
                             },
                         },
                     ),
@@ -1015,6 +1363,7 @@ mod tests {
                                 labels: [
                                     "foo",
                                 ],
+                                from_fork: false,
                             },
                         },
                     ),
@@ -1083,6 +1432,7 @@ mod tests {
                                 assignees: [],
                                 status: Draft,
                                 labels: [],
+                                from_fork: false,
                             },
                             draft: true,
                         },
@@ -1152,6 +1502,7 @@ mod tests {
                                 assignees: [],
                                 status: Draft,
                                 labels: [],
+                                from_fork: false,
                             },
                         },
                     ),
@@ -1220,6 +1571,7 @@ mod tests {
                                 assignees: [],
                                 status: Open,
                                 labels: [],
+                                from_fork: false,
                             },
                         },
                     ),
@@ -1270,6 +1622,19 @@ mod tests {
                             ),
                             text: "Foo",
                             html_url: "https://github.com/Kobzol/bors-kindergarten/pull/6#discussion_r1227824551",
+                            review_thread: Some(
+                                CommentId(
+                                    1227824551,
+                                ),
+                            ),
+                            review_verdict: None,
+                            reaction_target: Some(
+                                ReviewComment(
+                                    CommentId(
+                                        1227824551,
+                                    ),
+                                ),
+                            ),
                         },
                     ),
                 ),
@@ -1359,6 +1724,7 @@ mod tests {
                                 ],
                                 status: Open,
                                 labels: [],
+                                from_fork: false,
                             },
                         },
                     ),
@@ -1427,6 +1793,7 @@ mod tests {
                                 assignees: [],
                                 status: Open,
                                 labels: [],
+                                from_fork: false,
                             },
                         },
                     ),
@@ -1523,6 +1890,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn event_time_of_comment() {
+        let payload = WebhookPayload {
+            event_type: "issue_comment".to_string(),
+            body: load_test_file("webhook/issue-comment.json"),
+        };
+        insta::assert_debug_snapshot!(payload.event_time(), @r"
+            Some(
+                2023-02-07T13:23:42Z,
+            )
+            ");
+    }
+
+    #[test]
+    fn event_time_of_push() {
+        let payload = WebhookPayload {
+            event_type: "push".to_string(),
+            body: load_test_file("webhook/push.json"),
+        };
+        insta::assert_debug_snapshot!(payload.event_time(), @r"
+            Some(
+                2023-02-07T13:32:58Z,
+            )
+            ");
+    }
+
+    #[test]
+    fn event_time_of_unknown_event() {
+        let payload = WebhookPayload {
+            event_type: "security_advisory".to_string(),
+            body: load_test_file("webhook/security-advisory-published.json"),
+        };
+        assert_eq!(payload.event_time(), None);
+    }
+
     async fn check_webhook(file: &str, event: &str) -> Result<GitHubWebhook, StatusCode> {
         let body = load_test_file(file);
         let request = create_webhook_request(event, &body);
@@ -1538,6 +1940,9 @@ mod tests {
             repository_tx,
             global_tx,
             WebhookSecret::new(TEST_WEBHOOK_SECRET.to_string()),
+            None,
+            HashMap::new(),
+            vec![],
             repos,
             db,
             default_cmd_prefix(),
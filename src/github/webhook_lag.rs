@@ -0,0 +1,179 @@
+//! Lag between the time when GitHub has created a webhook event and the time when bors has
+//! finished handling it. A growing lag means that bors is falling behind the incoming webhooks,
+//! e.g. during a burst of webhooks after a GitHub outage.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Upper bounds (in seconds) of the buckets of the lag histograms.
+const LAG_BUCKETS: [u64; 9] = [1, 5, 15, 30, 60, 300, 900, 3600, 21600];
+
+/// Histograms of the webhook processing lag, per GitHub event type.
+pub struct WebhookLagMetrics {
+    histograms: Mutex<BTreeMap<String, LagHistogram>>,
+}
+
+/// Webhook processing lag of this bors instance.
+pub static WEBHOOK_LAG_METRICS: WebhookLagMetrics = WebhookLagMetrics {
+    histograms: Mutex::new(BTreeMap::new()),
+};
+
+#[derive(Default)]
+struct LagHistogram {
+    /// Number of events whose lag was at most the corresponding bound of [`LAG_BUCKETS`].
+    /// The counts are not cumulative.
+    buckets: [u64; LAG_BUCKETS.len()],
+    count: u64,
+    sum: Duration,
+    max: Duration,
+}
+
+impl WebhookLagMetrics {
+    /// Records that handling of a webhook of the given type, created by GitHub at `event_time`,
+    /// has been finished at `handled_at`.
+    /// Lags below zero (caused by clock skew) are recorded as zero.
+    pub(crate) fn record(
+        &self,
+        event_type: &str,
+        event_time: DateTime<Utc>,
+        handled_at: DateTime<Utc>,
+    ) {
+        let lag = (handled_at - event_time).to_std().unwrap_or_default();
+        let mut histograms = self.histograms.lock().unwrap();
+        let histogram = histograms.entry(event_type.to_string()).or_default();
+        if let Some(bucket) = LAG_BUCKETS
+            .iter()
+            .position(|bound| lag <= Duration::from_secs(*bound))
+        {
+            histogram.buckets[bucket] += 1;
+        }
+        histogram.count += 1;
+        histogram.sum += lag;
+        histogram.max = histogram.max.max(lag);
+    }
+
+    pub fn snapshot(&self) -> WebhookLagSnapshot {
+        let histograms = self.histograms.lock().unwrap();
+        let event_types = histograms
+            .iter()
+            .map(|(event_type, histogram)| {
+                let mut cumulative = 0;
+                let buckets = LAG_BUCKETS
+                    .iter()
+                    .zip(histogram.buckets)
+                    .map(|(bound, count)| {
+                        cumulative += count;
+                        LagBucket {
+                            le_seconds: *bound,
+                            count: cumulative,
+                        }
+                    })
+                    .collect();
+                let snapshot = LagHistogramSnapshot {
+                    count: histogram.count,
+                    sum_seconds: histogram.sum.as_secs_f64(),
+                    max_seconds: histogram.max.as_secs_f64(),
+                    buckets,
+                };
+                (event_type.clone(), snapshot)
+            })
+            .collect();
+        WebhookLagSnapshot { event_types }
+    }
+}
+
+/// A point-in-time view of [`WebhookLagMetrics`].
+#[derive(Debug, Serialize)]
+pub struct WebhookLagSnapshot {
+    /// Lag histograms by GitHub event type (e.g. `issue_comment` or `workflow_run`).
+    pub event_types: BTreeMap<String, LagHistogramSnapshot>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LagHistogramSnapshot {
+    /// Number of handled events.
+    pub count: u64,
+    /// Total lag of all handled events.
+    pub sum_seconds: f64,
+    /// The largest observed lag.
+    pub max_seconds: f64,
+    /// Cumulative counts of events by lag. Events with a lag above the largest bound are only
+    /// included in `count`.
+    pub buckets: Vec<LagBucket>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LagBucket {
+    /// Upper bound of the lag of the events in this bucket.
+    pub le_seconds: u64,
+    /// Number of events with a lag of at most `le_seconds`.
+    pub count: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+
+    use super::{WEBHOOK_LAG_METRICS, WebhookLagMetrics};
+    use crate::tests::{BorsTester, run_test};
+
+    #[test]
+    fn lag_histogram() {
+        let metrics = WebhookLagMetrics {
+            histograms: Default::default(),
+        };
+        let now = Utc::now();
+        metrics.record("issue_comment", now - TimeDelta::seconds(3), now);
+        metrics.record("issue_comment", now - TimeDelta::seconds(100), now);
+        metrics.record("issue_comment", now - TimeDelta::days(1), now);
+        // Clock skew
+        metrics.record("workflow_run", now + TimeDelta::seconds(2), now);
+
+        let snapshot = metrics.snapshot();
+        let comments = &snapshot.event_types["issue_comment"];
+        assert_eq!(comments.count, 3);
+        assert_eq!(comments.max_seconds, 86400.0);
+        let counts: Vec<_> = comments.buckets.iter().map(|b| b.count).collect();
+        assert_eq!(counts, [0, 1, 1, 1, 1, 2, 2, 2, 2]);
+        let workflows = &snapshot.event_types["workflow_run"];
+        assert_eq!(workflows.buckets[0].count, 1);
+        assert_eq!(workflows.sum_seconds, 0.0);
+    }
+
+    #[sqlx::test]
+    async fn webhook_lag_is_recorded(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors ping").await?;
+            tester.expect_comments((), 1).await;
+
+            // The lag is recorded after the reply is posted
+            tester
+                .wait_for(|| async {
+                    Ok(WEBHOOK_LAG_METRICS
+                        .snapshot()
+                        .event_types
+                        .contains_key("issue_comment"))
+                })
+                .await?;
+
+            let (status, body) = tester
+                .web_request(http::Method::GET, "/api/v1/webhook-lag", None)
+                .await?;
+            assert_eq!(status, http::StatusCode::OK);
+            let metrics: serde_json::Value = serde_json::from_str(&body)?;
+            // Metrics are shared by all tests running in parallel
+            assert!(
+                metrics["event_types"]["issue_comment"]["count"]
+                    .as_u64()
+                    .unwrap()
+                    >= 1
+            );
+            Ok(())
+        })
+        .await;
+    }
+}
@@ -0,0 +1,270 @@
+//! Extraction of job names, triggers and environments from GitHub Actions workflow files.
+//!
+//! Workflow files are only scanned line by line, instead of being parsed as YAML. This is enough
+//! for the block style in which workflows are usually written, but jobs defined using flow style
+//! (e.g. `jobs: { build: ... }`) are not recognized.
+
+/// Returns the IDs of the jobs defined in the given workflow file, together with their names,
+/// if they have a name that does not contain an expression.
+pub fn parse_workflow_job_names(content: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut in_jobs = false;
+    let mut job_indent = None;
+    let mut property_indent = None;
+
+    for line in content.lines() {
+        let line = strip_comment(line).trim_end();
+        if line.trim().is_empty() {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        let line = line.trim_start();
+
+        if indent == 0 {
+            in_jobs = line == "jobs:";
+            job_indent = None;
+            property_indent = None;
+            continue;
+        }
+        if !in_jobs {
+            continue;
+        }
+
+        let job_indent = *job_indent.get_or_insert(indent);
+        if indent == job_indent {
+            if let Some(id) = line.strip_suffix(':') {
+                names.push(unquote(id).to_string());
+            }
+            property_indent = None;
+        } else if indent > job_indent
+            && *property_indent.get_or_insert(indent) == indent
+            && let Some(name) = line.strip_prefix("name:")
+        {
+            let name = unquote(name.trim());
+            if !name.is_empty() && !name.contains("${{") {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Returns true if the given workflow file is triggered by pushes, and thus runs for the merge
+/// commits that bors pushes. Branch filters of the trigger are not taken into account.
+pub fn is_triggered_by_push(content: &str) -> bool {
+    let mut in_on = false;
+    let mut trigger_indent = None;
+
+    for line in content.lines() {
+        let line = strip_comment(line).trim_end();
+        if line.trim().is_empty() {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        let line = line.trim_start();
+
+        if indent == 0 {
+            let value = ["on:", "\"on\":", "'on':"]
+                .iter()
+                .find_map(|key| line.strip_prefix(key));
+            in_on = value.is_some_and(|value| value.trim().is_empty());
+            trigger_indent = None;
+            if let Some(value) = value
+                && value
+                    .split(['[', ']', ','])
+                    .any(|trigger| unquote(trigger.trim()) == "push")
+            {
+                return true;
+            }
+            continue;
+        }
+        if in_on && *trigger_indent.get_or_insert(indent) == indent {
+            let trigger = line.strip_prefix("- ").unwrap_or(line);
+            let trigger = trigger.strip_suffix(':').unwrap_or(trigger);
+            if unquote(trigger.trim()) == "push" {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Returns the IDs of the jobs defined in the given workflow file that do not run in the given
+/// deployment environment, set either as `environment: <name>` or as `name` of an `environment`
+/// mapping. Jobs whose environment is set by an expression are considered to run outside of
+/// the environment, because it is not known in advance.
+pub fn jobs_outside_environment(content: &str, environment: &str) -> Vec<String> {
+    // (job ID, whether the job runs in the environment)
+    let mut jobs: Vec<(String, bool)> = Vec::new();
+    let mut in_jobs = false;
+    let mut job_indent = None;
+    let mut property_indent = None;
+    let mut in_environment = false;
+
+    for line in content.lines() {
+        let line = strip_comment(line).trim_end();
+        if line.trim().is_empty() {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        let line = line.trim_start();
+
+        if indent == 0 {
+            in_jobs = line == "jobs:";
+            job_indent = None;
+            property_indent = None;
+            continue;
+        }
+        if !in_jobs {
+            continue;
+        }
+
+        let job_indent = *job_indent.get_or_insert(indent);
+        if indent == job_indent {
+            if let Some(id) = line.strip_suffix(':') {
+                jobs.push((unquote(id).to_string(), false));
+            }
+            property_indent = None;
+            in_environment = false;
+        } else if indent > job_indent && *property_indent.get_or_insert(indent) == indent {
+            in_environment = false;
+            if let Some(value) = line.strip_prefix("environment:") {
+                let value = unquote(value.trim());
+                in_environment = value.is_empty();
+                if let Some(job) = jobs.last_mut() {
+                    job.1 = value == environment;
+                }
+            }
+        } else if in_environment
+            && let Some(name) = line.strip_prefix("name:")
+            && let Some(job) = jobs.last_mut()
+        {
+            job.1 = unquote(name.trim()) == environment;
+        }
+    }
+    jobs.into_iter()
+        .filter(|(_, in_environment)| !in_environment)
+        .map(|(id, _)| id)
+        .collect()
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find(" #") {
+        Some(index) => &line[..index],
+        None if line.trim_start().starts_with('#') => "",
+        None => line,
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .or_else(|| {
+            value
+                .strip_prefix('\'')
+                .and_then(|value| value.strip_suffix('\''))
+        })
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_triggered_by_push, jobs_outside_environment, parse_workflow_job_names};
+
+    #[test]
+    fn job_ids_and_names() {
+        let workflow = r#"
+name: CI
+on:
+  push:
+    branches:
+      - automation/bors/try
+
+jobs:
+  build:
+    name: Build (linux)
+    runs-on: ubuntu-latest
+    steps:
+      - name: Checkout
+        uses: actions/checkout@v4
+  # A job without a name
+  lint:
+    runs-on: ubuntu-latest
+  "test-windows":
+    name: "Test windows" # comment
+    runs-on: windows-latest
+"#;
+        assert_eq!(
+            parse_workflow_job_names(workflow),
+            vec![
+                "build",
+                "Build (linux)",
+                "lint",
+                "test-windows",
+                "Test windows"
+            ]
+        );
+    }
+
+    #[test]
+    fn skip_expression_names() {
+        let workflow = r#"
+jobs:
+  test:
+    name: Test ${{ matrix.os }}
+    strategy:
+      matrix:
+        os: [ubuntu-latest]
+"#;
+        assert_eq!(parse_workflow_job_names(workflow), vec!["test"]);
+    }
+
+    #[test]
+    fn no_jobs() {
+        assert!(parse_workflow_job_names("name: CI\non: push\n").is_empty());
+    }
+
+    #[test]
+    fn push_triggers() {
+        assert!(is_triggered_by_push("on: push\n"));
+        assert!(is_triggered_by_push("on: [pull_request, push]\n"));
+        assert!(is_triggered_by_push("on:\n  - pull_request\n  - push\n"));
+        assert!(is_triggered_by_push(
+            "\"on\":\n  push:\n    branches:\n      - automation/bors/try\n"
+        ));
+        assert!(!is_triggered_by_push("on: pull_request\n"));
+        assert!(!is_triggered_by_push(
+            "on:\n  pull_request:\n    branches:\n      - push\njobs:\n  push:\n"
+        ));
+    }
+
+    #[test]
+    fn jobs_with_environment() {
+        let workflow = r#"
+on: push
+jobs:
+  build:
+    runs-on: ubuntu-latest
+    environment: untrusted
+  test:
+    environment:
+      name: "untrusted"
+      url: https://example.com
+    steps:
+      - name: Test
+  deploy:
+    environment: production
+  lint:
+    runs-on: ubuntu-latest
+    steps:
+      - environment: untrusted
+  docs:
+    environment: ${{ inputs.environment }}
+"#;
+        assert_eq!(
+            jobs_outside_environment(workflow, "untrusted"),
+            vec!["deploy", "lint", "docs"]
+        );
+    }
+}
@@ -6,18 +6,32 @@ mod config;
 mod database;
 mod github;
 mod permissions;
+mod secrets;
 mod templates;
 mod utils;
 
-pub use bors::{BorsContext, CommandParser, event::BorsGlobalEvent, event::BorsRepositoryEvent};
-pub use database::{PgDbClient, TreeState};
+pub use bors::{
+    BorsCommand, BorsContext, CommandParser,
+    event::BorsGlobalEvent,
+    event::BorsRepositoryEvent,
+    hooks::{CommandHook, CommandHookContext},
+};
+pub use database::{
+    DeactivationReason, PendingMigration, PgDbClient, SchemaStatus, TreeState,
+    expected_schema_version, get_schema_status, run_migrations,
+};
 pub use github::{
-    AppError, WebhookSecret,
+    AppError, WebhookSecret, WebhookSignature,
+    api::GitHubUrls,
     api::create_github_client,
     api::load_repositories,
-    server::{BorsProcess, ServerState, create_app, create_bors_process},
+    server::{
+        ApiToken, BorsProcess, ListenerConfig, Role, RouteGroup, ServerState, create_app,
+        create_bors_process, create_listener_app,
+    },
 };
 pub use permissions::TeamApiClient;
+pub use secrets::{Secrets, SecretsProvider};
 
 #[cfg(test)]
 mod tests;
@@ -1,9 +1,19 @@
+use futures::FutureExt;
+use futures::future::{BoxFuture, Shared};
 use octocrab::models::UserId;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use crate::config::{PermissionEntry, PermissionsConfig};
 use crate::github::GithubRepoName;
+use crate::github::api::client::GithubRepositoryClient;
+
+/// How long are the members of teams listed in repository configs cached, before they are loaded
+/// from GitHub again.
+const TEAM_MEMBERS_CACHE_DURATION: Duration = Duration::from_secs(60 * 10);
 
 #[derive(Eq, PartialEq, Debug, Clone)]
 pub enum PermissionType {
@@ -48,70 +58,262 @@ pub(crate) struct UserPermissionsResponse {
     github_ids: HashSet<UserId>,
 }
 
+/// Request for the permissions of a repository that can be awaited by several callers.
+type PermissionsRequest =
+    Shared<BoxFuture<'static, Result<Arc<UserPermissions>, Arc<anyhow::Error>>>>;
+
+/// Loads the permissions of repositories from the Rust Team API, and resolves the users and teams
+/// listed in the permissions configuration of repositories using the GitHub API.
 pub struct TeamApiClient {
-    base_url: String,
+    /// `None` if permissions are not loaded from the Team API.
+    base_url: Option<String>,
+    /// Permission requests that are currently in flight, keyed by the normalized repository name.
+    /// Concurrent loads of the same permissions share a single request to the Team API.
+    in_flight: Mutex<HashMap<String, PermissionsRequest>>,
+    /// IDs of the GitHub users listed in repository configs, keyed by their lowercase login.
+    user_ids: Mutex<HashMap<String, UserId>>,
+    /// Members of the GitHub teams listed in repository configs, keyed by the lowercase
+    /// `@org/team-slug`, together with the time when they were loaded.
+    team_members: Mutex<HashMap<String, (Instant, HashSet<UserId>)>>,
 }
 
 impl TeamApiClient {
-    pub(crate) fn new(base_url: impl Into<String>) -> Self {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_base_url(Some(base_url.into()))
+    }
+
+    /// Creates a client that does not load any permissions from the Team API, so that only the
+    /// permissions listed in repository configs are used.
+    pub fn disabled() -> Self {
+        Self::with_base_url(None)
+    }
+
+    fn with_base_url(base_url: Option<String>) -> Self {
         Self {
-            base_url: base_url.into(),
+            base_url,
+            in_flight: Mutex::new(HashMap::new()),
+            user_ids: Mutex::new(HashMap::new()),
+            team_members: Mutex::new(HashMap::new()),
         }
     }
 
-    pub(crate) async fn load_permissions(
+    /// Loads the permissions of the repository from the Team API and adds the users and teams
+    /// listed in the given permissions configuration of the repository.
+    pub(crate) async fn load_repository_permissions(
         &self,
-        repo: &GithubRepoName,
-    ) -> anyhow::Result<UserPermissions> {
-        tracing::info!("Reloading permissions for repository {repo}");
-
-        let review_users: HashSet<UserId> = self
-            .load_users(repo.name(), PermissionType::Review)
-            .await
-            .map_err(|error| anyhow::anyhow!("Cannot load review users: {error:?}"))?;
-
-        let try_users = self
-            .load_users(repo.name(), PermissionType::Try)
-            .await
-            .map_err(|error| anyhow::anyhow!("Cannot load try users: {error:?}"))?;
+        client: &GithubRepositoryClient,
+        config: &PermissionsConfig,
+    ) -> anyhow::Result<Arc<UserPermissions>> {
+        let permissions = self.load_permissions(client.repository()).await?;
+        if config.is_empty() {
+            return Ok(permissions);
+        }
 
-        Ok(UserPermissions {
-            review_users,
-            try_users,
-        })
+        let reviewers = self.resolve_entries(client, &config.reviewers).await?;
+        let try_users = self.resolve_entries(client, &config.try_users).await?;
+        let mut permissions = UserPermissions {
+            review_users: permissions.review_users.clone(),
+            try_users: permissions.try_users.clone(),
+        };
+        permissions.review_users.extend(&reviewers);
+        permissions
+            .try_users
+            .extend(reviewers.into_iter().chain(try_users));
+        Ok(Arc::new(permissions))
     }
 
-    /// Loads users that are allowed to perform try/review from the Rust Team API.
-    async fn load_users(
+    /// Resolves the given users and teams to GitHub user IDs. Users and teams that do not exist
+    /// are skipped, so that a typo in the config does not prevent loading the other permissions.
+    async fn resolve_entries(
         &self,
-        repository_name: &str,
-        permission: PermissionType,
+        client: &GithubRepositoryClient,
+        entries: &[PermissionEntry],
     ) -> anyhow::Result<HashSet<UserId>> {
-        let permission = match permission {
-            PermissionType::Review => "review",
-            PermissionType::Try => "try",
+        let repo = client.repository();
+        let mut users = HashSet::new();
+        for entry in entries {
+            match entry {
+                PermissionEntry::User(login) => {
+                    let key = login.to_lowercase();
+                    let cached = self.user_ids.lock().unwrap().get(&key).copied();
+                    let user_id = match cached {
+                        Some(user_id) => Some(user_id),
+                        None => client.get_user_id(login).await?,
+                    };
+                    match user_id {
+                        Some(user_id) => {
+                            self.user_ids.lock().unwrap().insert(key, user_id);
+                            users.insert(user_id);
+                        }
+                        None => tracing::warn!(
+                            "User `{login}` from the permissions of {repo} does not exist"
+                        ),
+                    }
+                }
+                PermissionEntry::Team { org, slug } => {
+                    let key = entry.to_string().to_lowercase();
+                    let cached = self
+                        .team_members
+                        .lock()
+                        .unwrap()
+                        .get(&key)
+                        .filter(|(loaded_at, _)| loaded_at.elapsed() < TEAM_MEMBERS_CACHE_DURATION)
+                        .map(|(_, members)| members.clone());
+                    let members = match cached {
+                        Some(members) => members,
+                        None => match client.get_team_member_ids(org, slug).await? {
+                            Some(members) => {
+                                let members: HashSet<UserId> = members.into_iter().collect();
+                                self.team_members
+                                    .lock()
+                                    .unwrap()
+                                    .insert(key, (Instant::now(), members.clone()));
+                                members
+                            }
+                            None => {
+                                tracing::warn!(
+                                    "Team `{entry}` from the permissions of {repo} does not exist"
+                                );
+                                continue;
+                            }
+                        },
+                    };
+                    users.extend(members);
+                }
+            }
+        }
+        Ok(users)
+    }
+
+    pub(crate) async fn load_permissions(
+        &self,
+        repo: &GithubRepoName,
+    ) -> anyhow::Result<Arc<UserPermissions>> {
+        let Some(base_url) = &self.base_url else {
+            return Ok(Arc::new(UserPermissions::new(
+                HashSet::new(),
+                HashSet::new(),
+            )));
         };
+        let normalized_name = repo.name().replace('-', "_");
+        let request = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .entry(normalized_name.clone())
+            .or_insert_with(|| {
+                tracing::info!("Reloading permissions for repository {repo}");
+                load_permissions(base_url.clone(), normalized_name.clone())
+                    .boxed()
+                    .shared()
+            })
+            .clone();
+        let result = request.clone().await;
 
-        let normalized_name = repository_name.replace('-', "_");
-        let url = format!(
-            "{}/v1/permissions/bors.{normalized_name}.{permission}.json",
-            self.base_url
-        );
-        let users = reqwest::get(url)
-            .await
-            .and_then(|res| res.error_for_status())
-            .map_err(|error| anyhow::anyhow!("Cannot load users from team API: {error:?}"))?
-            .json::<UserPermissionsResponse>()
-            .await
-            .map_err(|error| {
-                anyhow::anyhow!("Cannot deserialize users from team API: {error:?}")
-            })?;
-        Ok(users.github_ids)
+        // Later loads should not reuse the result of this request
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight
+            .get(&normalized_name)
+            .is_some_and(|in_flight_request| in_flight_request.ptr_eq(&request))
+        {
+            in_flight.remove(&normalized_name);
+        }
+        result.map_err(|error| anyhow::anyhow!("{error:?}"))
     }
 }
 
+async fn load_permissions(
+    base_url: String,
+    normalized_name: String,
+) -> Result<Arc<UserPermissions>, Arc<anyhow::Error>> {
+    let (review_users, try_users) = tokio::try_join!(
+        async {
+            load_users(&base_url, &normalized_name, PermissionType::Review)
+                .await
+                .map_err(|error| anyhow::anyhow!("Cannot load review users: {error:?}"))
+        },
+        async {
+            load_users(&base_url, &normalized_name, PermissionType::Try)
+                .await
+                .map_err(|error| anyhow::anyhow!("Cannot load try users: {error:?}"))
+        }
+    )
+    .map_err(Arc::new)?;
+
+    Ok(Arc::new(UserPermissions {
+        review_users,
+        try_users,
+    }))
+}
+
+/// Loads users that are allowed to perform try/review from the Rust Team API.
+async fn load_users(
+    base_url: &str,
+    normalized_name: &str,
+    permission: PermissionType,
+) -> anyhow::Result<HashSet<UserId>> {
+    let permission = match permission {
+        PermissionType::Review => "review",
+        PermissionType::Try => "try",
+    };
+
+    let url = format!("{base_url}/v1/permissions/bors.{normalized_name}.{permission}.json");
+    let users = reqwest::get(url)
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(|error| anyhow::anyhow!("Cannot load users from team API: {error:?}"))?
+        .json::<UserPermissionsResponse>()
+        .await
+        .map_err(|error| anyhow::anyhow!("Cannot deserialize users from team API: {error:?}"))?;
+    Ok(users.github_ids)
+}
+
 impl Default for TeamApiClient {
     fn default() -> Self {
         Self::new("https://team-api.infra.rust-lang.org")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::TeamApiClient;
+    use crate::github::GithubRepoName;
+    use serde_json::json;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn coalesce_concurrent_permission_loads() {
+        let server = MockServer::start().await;
+        for permission in ["review", "try"] {
+            Mock::given(method("GET"))
+                .and(path(format!(
+                    "/v1/permissions/bors.my_repo.{permission}.json"
+                )))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_json(json!({ "github_ids": [1] }))
+                        .set_delay(Duration::from_millis(100)),
+                )
+                .expect(2)
+                .mount(&server)
+                .await;
+        }
+
+        let client = TeamApiClient::new(server.uri());
+        let repo_a = GithubRepoName::new("org-a", "my-repo");
+        let repo_b = GithubRepoName::new("org-b", "my_repo");
+        // Repositories with the same normalized name share their permissions
+        let (first, second) = tokio::join!(
+            client.load_permissions(&repo_a),
+            client.load_permissions(&repo_b),
+        );
+        assert!(Arc::ptr_eq(&first.unwrap(), &second.unwrap()));
+
+        // A load that starts after the previous one has finished sends a new request
+        client.load_permissions(&repo_a).await.unwrap();
+        server.verify().await;
+    }
+}
@@ -0,0 +1,287 @@
+//! Loading of the secrets used by bors (the GitHub App private key, the webhook secret and API
+//! tokens) from a configurable backend.
+//!
+//! Secrets are loaded once on startup, and they can be loaded again at runtime through the
+//! `/api/v1/secrets/reload` endpoint, so that they can be rotated without restarting bors.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use secrecy::{ExposeSecret, SecretString};
+
+use crate::github::server::{ApiToken, Role};
+use crate::github::{GithubRepoName, WebhookSecret};
+
+const PRIVATE_KEY: &str = "private_key";
+const WEBHOOK_SECRET: &str = "webhook_secret";
+const API_TOKEN: &str = "api_token";
+const REPOSITORY_API_TOKENS: &str = "repository_api_tokens";
+const ADMIN_TOKEN: &str = "admin_token";
+const OPERATOR_TOKEN: &str = "operator_token";
+
+const SECRET_NAMES: [&str; 6] = [
+    PRIVATE_KEY,
+    WEBHOOK_SECRET,
+    API_TOKEN,
+    REPOSITORY_API_TOKENS,
+    ADMIN_TOKEN,
+    OPERATOR_TOKEN,
+];
+
+/// Backend from which secrets are loaded.
+pub enum SecretsProvider {
+    /// Secrets are read from environment variables with upper-case names, e.g. `WEBHOOK_SECRET`.
+    Env,
+    /// Each secret is read from a file with a lower-case name in the given directory, e.g.
+    /// `<dir>/webhook_secret`. This works well with secrets mounted by Kubernetes.
+    Directory(PathBuf),
+    /// Secrets are read from a key-value (version 2) secret in HashiCorp Vault, whose keys have
+    /// lower-case names, e.g. `webhook_secret`.
+    Vault {
+        /// Address of the Vault server, e.g. `https://vault.example.com`.
+        address: String,
+        /// Token used to authenticate to Vault.
+        token: SecretString,
+        /// API path of the secret, e.g. `secret/data/bors`.
+        path: String,
+    },
+}
+
+impl SecretsProvider {
+    /// Loads all secrets from the backend.
+    pub async fn load(&self) -> anyhow::Result<Secrets> {
+        let mut values = match self {
+            SecretsProvider::Env => SECRET_NAMES
+                .iter()
+                .filter_map(|name| {
+                    std::env::var(name.to_uppercase())
+                        .ok()
+                        .map(|value| (name.to_string(), value))
+                })
+                .collect(),
+            SecretsProvider::Directory(dir) => load_from_directory(dir).await?,
+            SecretsProvider::Vault {
+                address,
+                token,
+                path,
+            } => load_from_vault(address, token, path).await?,
+        };
+        let mut take = |name: &str| {
+            values
+                .remove(name)
+                .filter(|value| !value.is_empty())
+                .map(SecretString::from)
+        };
+        Ok(Secrets {
+            private_key: take(PRIVATE_KEY).context("Secret `private_key` is missing")?,
+            webhook_secret: take(WEBHOOK_SECRET).context("Secret `webhook_secret` is missing")?,
+            api_token: take(API_TOKEN),
+            repository_api_tokens: take(REPOSITORY_API_TOKENS)
+                .map(|tokens| parse_repository_tokens(tokens.expose_secret()))
+                .transpose()?
+                .unwrap_or_default(),
+            admin_token: take(ADMIN_TOKEN),
+            operator_token: take(OPERATOR_TOKEN),
+        })
+    }
+}
+
+/// Secrets used by bors.
+pub struct Secrets {
+    /// Private key used to authenticate as a GitHub App.
+    private_key: SecretString,
+    /// Secret used to authenticate webhooks.
+    webhook_secret: SecretString,
+    /// Token used by external CI systems to report build results.
+    api_token: Option<SecretString>,
+    /// Tokens used by external CI systems to report results of builds of a single repository.
+    repository_api_tokens: HashMap<GithubRepoName, SecretString>,
+    /// Token that grants the admin role in the administrative API.
+    admin_token: Option<SecretString>,
+    /// Token that grants the operator role in the administrative API.
+    operator_token: Option<SecretString>,
+}
+
+impl Secrets {
+    pub fn private_key(&self) -> SecretString {
+        self.private_key.clone()
+    }
+
+    pub fn webhook_secret(&self) -> WebhookSecret {
+        WebhookSecret::new(self.webhook_secret.expose_secret().to_string())
+    }
+
+    pub fn api_token(&self) -> Option<ApiToken> {
+        self.api_token
+            .as_ref()
+            .map(|token| ApiToken::new(token.expose_secret().to_string()))
+    }
+
+    pub fn repository_api_tokens(&self) -> HashMap<GithubRepoName, ApiToken> {
+        self.repository_api_tokens
+            .iter()
+            .map(|(repo, token)| {
+                (
+                    repo.clone(),
+                    ApiToken::new(token.expose_secret().to_string()),
+                )
+            })
+            .collect()
+    }
+
+    pub fn role_tokens(&self) -> Vec<(ApiToken, Role)> {
+        [
+            (&self.admin_token, Role::Admin),
+            (&self.operator_token, Role::Operator),
+        ]
+        .into_iter()
+        .filter_map(|(token, role)| {
+            token
+                .as_ref()
+                .map(|token| (ApiToken::new(token.expose_secret().to_string()), role))
+        })
+        .collect()
+    }
+}
+
+/// Parses whitespace-separated `<owner>/<name>=<token>` entries.
+fn parse_repository_tokens(tokens: &str) -> anyhow::Result<HashMap<GithubRepoName, SecretString>> {
+    tokens
+        .split_whitespace()
+        .map(|entry| {
+            let (repo, token) = entry
+                .split_once('=')
+                .filter(|(repo, token)| repo.contains('/') && !token.is_empty())
+                .context(
+                    "Secret `repository_api_tokens` must contain `<owner>/<name>=<token>` entries",
+                )?;
+            let repo = repo
+                .parse::<GithubRepoName>()
+                .map_err(|error| anyhow::anyhow!(error))?;
+            Ok((repo, SecretString::from(token.to_string())))
+        })
+        .collect()
+}
+
+async fn load_from_directory(dir: &Path) -> anyhow::Result<HashMap<String, String>> {
+    let mut values = HashMap::new();
+    for name in SECRET_NAMES {
+        let path = dir.join(name);
+        match tokio::fs::read_to_string(&path).await {
+            Ok(value) => {
+                values.insert(name.to_string(), value.trim_end().to_string());
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => {
+                return Err(anyhow::anyhow!(
+                    "Cannot read secret from {}: {error:?}",
+                    path.display()
+                ));
+            }
+        }
+    }
+    Ok(values)
+}
+
+#[derive(serde::Deserialize)]
+struct VaultResponse {
+    data: VaultData,
+}
+
+#[derive(serde::Deserialize)]
+struct VaultData {
+    data: HashMap<String, String>,
+}
+
+async fn load_from_vault(
+    address: &str,
+    token: &SecretString,
+    path: &str,
+) -> anyhow::Result<HashMap<String, String>> {
+    let url = format!(
+        "{}/v1/{}",
+        address.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    );
+    let response = reqwest::Client::new()
+        .get(url)
+        .header("X-Vault-Token", token.expose_secret())
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(|error| anyhow::anyhow!("Cannot load secrets from Vault: {error:?}"))?
+        .json::<VaultResponse>()
+        .await
+        .map_err(|error| anyhow::anyhow!("Cannot deserialize secrets from Vault: {error:?}"))?;
+    Ok(response.data.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SecretsProvider;
+    use crate::github::GithubRepoName;
+
+    fn secrets_dir(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("bors-secrets-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, content) in files {
+            std::fs::write(dir.join(file), content).unwrap();
+        }
+        dir
+    }
+
+    #[tokio::test]
+    async fn load_secrets_from_directory() {
+        let dir = secrets_dir(
+            "load",
+            &[
+                ("private_key", "key"),
+                ("webhook_secret", "secret\n"),
+                ("operator_token", "operator"),
+            ],
+        );
+        let secrets = SecretsProvider::Directory(dir).load().await.unwrap();
+        assert_eq!(secrets.webhook_secret().expose(), "secret");
+        assert!(secrets.api_token().is_none());
+        assert!(secrets.repository_api_tokens().is_empty());
+        assert_eq!(secrets.role_tokens().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn load_repository_api_tokens() {
+        let dir = secrets_dir(
+            "repository-tokens",
+            &[
+                ("private_key", "key"),
+                ("webhook_secret", "secret"),
+                ("repository_api_tokens", "foo/bar=token1\nfoo/baz=token2\n"),
+            ],
+        );
+        let secrets = SecretsProvider::Directory(dir).load().await.unwrap();
+        let tokens = secrets.repository_api_tokens();
+        assert_eq!(tokens.len(), 2);
+        assert!(tokens.contains_key(&GithubRepoName::new("foo", "bar")));
+    }
+
+    #[tokio::test]
+    async fn load_invalid_repository_api_tokens() {
+        let dir = secrets_dir(
+            "invalid-repository-tokens",
+            &[
+                ("private_key", "key"),
+                ("webhook_secret", "secret"),
+                ("repository_api_tokens", "foo/bar"),
+            ],
+        );
+        let error = SecretsProvider::Directory(dir).load().await.err().unwrap();
+        insta::assert_snapshot!(error, @"Secret `repository_api_tokens` must contain `<owner>/<name>=<token>` entries");
+    }
+
+    #[tokio::test]
+    async fn load_secrets_missing_required_secret() {
+        let dir = secrets_dir("missing", &[("private_key", "key")]);
+        let error = SecretsProvider::Directory(dir).load().await.err().unwrap();
+        insta::assert_snapshot!(error, @"Secret `webhook_secret` is missing");
+    }
+}
@@ -1,7 +1,13 @@
-use crate::database::{MergeableState::*, PullRequestModel, QueueStatus::*, TreeState};
+use crate::bors::queue_skips::SkipReason;
+use crate::bors::status::StatusReport;
+use crate::database::{
+    BuildStatsModel, MergeableState::*, PullRequestModel, QueueStatus::*, TreeState,
+};
+use crate::github::PullRequestNumber;
 use askama::Template;
 use axum::response::{Html, IntoResponse, Response};
 use http::StatusCode;
+use std::collections::BTreeMap;
 
 pub struct HtmlTemplate<T>(pub T);
 
@@ -38,6 +44,8 @@ pub struct PullRequestStats {
     pub in_queue_count: usize,
     pub failed_count: usize,
     pub rolled_up_count: usize,
+    /// Number of queued PRs that are waiting for queue space.
+    pub overflow_count: usize,
 }
 
 #[derive(Template)]
@@ -48,8 +56,174 @@ pub struct QueueTemplate {
     pub stats: PullRequestStats,
     pub prs: Vec<PullRequestModel>,
     pub tree_state: TreeState,
+    pub max_queue_size: Option<usize>,
+    /// Why approved PRs were not selected when the merge queue was last processed.
+    pub skip_reasons: BTreeMap<PullRequestNumber, SkipReason>,
+}
+
+impl QueueTemplate {
+    fn skip_reason(&self, pr: &PullRequestModel) -> Option<&'static str> {
+        self.skip_reasons
+            .get(&pr.number)
+            .map(|reason| reason.description())
+    }
+}
+
+#[derive(Template)]
+#[template(path = "stats.html")]
+pub struct BuildStatsTemplate {
+    pub repo_name: String,
+    pub repo_url: String,
+    /// Over how many days the builds are aggregated.
+    pub days: i32,
+    pub builds: Vec<BuildStatsView>,
+}
+
+/// Build statistics formatted for display.
+pub struct BuildStatsView {
+    pub kind: String,
+    pub builds: i64,
+    pub succeeded: i64,
+    pub failed: i64,
+    pub merged: i64,
+    pub retries: i64,
+    pub failure_rate: Option<String>,
+    pub retries_per_merge: Option<String>,
+    pub queue_wait: Option<String>,
+    pub ci_duration: Option<String>,
+    pub finish_to_merge: Option<String>,
+}
+
+impl From<BuildStatsModel> for BuildStatsView {
+    fn from(stats: BuildStatsModel) -> Self {
+        Self {
+            kind: stats.kind,
+            builds: stats.builds,
+            succeeded: stats.succeeded,
+            failed: stats.failed,
+            merged: stats.merged,
+            retries: stats.retries,
+            failure_rate: stats
+                .failure_rate
+                .map(|rate| format!("{:.1}%", rate * 100.0)),
+            retries_per_merge: stats
+                .retries_per_merge
+                .map(|retries| format!("{retries:.2}")),
+            queue_wait: stats.queue_wait.map(format_duration),
+            ci_duration: stats.ci_duration.map(format_duration),
+            finish_to_merge: stats.finish_to_merge.map(format_duration),
+        }
+    }
+}
+
+/// Formats a duration in seconds, e.g. `2h 5m`.
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.max(0.0).round() as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+#[derive(Template)]
+#[template(path = "status.html")]
+pub struct StatusTemplate {
+    pub version: String,
+    pub healthy: bool,
+    pub components: Vec<ComponentView>,
+    /// Reason of an active emergency stop, if there is one.
+    pub emergency_stop: Option<String>,
+    pub webhook_lag: Option<WebhookLagView>,
+    pub repos: Vec<RepositoryStatusView>,
+}
+
+pub struct ComponentView {
+    pub name: &'static str,
+    pub healthy: bool,
+    pub details: String,
+}
+
+pub struct WebhookLagView {
+    pub count: u64,
+    pub average: String,
+    pub max: String,
+}
+
+pub struct RepositoryStatusView {
+    pub name: String,
+    pub merge_queue: &'static str,
+    pub tree: &'static str,
+}
+
+impl From<StatusReport> for StatusTemplate {
+    fn from(report: StatusReport) -> Self {
+        let health = report.health;
+        let components = [
+            ("Database", health.database),
+            ("GitHub", health.github),
+            ("Webhooks", health.webhooks),
+            ("Merge queue", health.merge_queue),
+        ]
+        .into_iter()
+        .map(|(name, component)| ComponentView {
+            name,
+            healthy: component.healthy,
+            details: component.details,
+        })
+        .collect();
+        let webhook_lag = report
+            .webhook_lag
+            .average_seconds
+            .map(|average| WebhookLagView {
+                count: report.webhook_lag.count,
+                average: format_duration(average),
+                max: format_duration(report.webhook_lag.max_seconds),
+            });
+        Self {
+            version: report.version.to_string(),
+            healthy: report.healthy,
+            components,
+            emergency_stop: report
+                .emergency_stop
+                .map(|stop| stop.reason.unwrap_or_else(|| "no reason given".to_string())),
+            webhook_lag,
+            repos: report
+                .repositories
+                .into_iter()
+                .map(|repo| RepositoryStatusView {
+                    name: repo.name,
+                    merge_queue: repo.merge_queue.as_str(),
+                    tree: match repo.tree_closed {
+                        Some(true) => "closed",
+                        Some(false) => "open",
+                        None => "unknown",
+                    },
+                })
+                .collect(),
+        }
+    }
 }
 
 #[derive(Template)]
 #[template(path = "not_found.html")]
-pub struct NotFoundTemplate {}
+pub struct NotFoundTemplate {
+    /// Path prefix of the index page.
+    pub prefix: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_duration;
+
+    #[test]
+    fn format_durations() {
+        assert_eq!(format_duration(0.4), "0s");
+        assert_eq!(format_duration(59.0), "59s");
+        assert_eq!(format_duration(125.0), "2m 5s");
+        assert_eq!(format_duration(7500.0), "2h 5m");
+    }
+}
@@ -0,0 +1,117 @@
+//! Snapshots of the whole state of the database.
+//!
+//! A snapshot taken in the middle of a test can be restored later, which simulates bors crashing
+//! at the time of the snapshot: everything that bors has stored since then is forgotten, while
+//! GitHub (the mock) has moved on. This allows expressing multi-stage scenarios (e.g. a crash in
+//! the middle of an auto build, followed by a restart and recovery) as integration tests.
+
+use anyhow::Context;
+use sqlx::PgPool;
+
+/// Tables that are not restored, because they are not part of the state of bors.
+const IGNORED_TABLES: &[&str] = &["_sqlx_migrations"];
+
+/// Contents of all tables of the database at some point in time.
+pub struct DatabaseSnapshot {
+    /// Rows of each table, serialized as JSON objects.
+    tables: Vec<(String, Vec<String>)>,
+}
+
+impl DatabaseSnapshot {
+    pub(super) async fn take(pool: &PgPool) -> anyhow::Result<Self> {
+        let mut tables = vec![];
+        for table in load_table_names(pool).await? {
+            let rows: Vec<String> =
+                sqlx::query_scalar(&format!("SELECT row_to_json(t)::text FROM {table} AS t"))
+                    .fetch_all(pool)
+                    .await
+                    .with_context(|| format!("Cannot snapshot table {table}"))?;
+            tables.push((table, rows));
+        }
+        Ok(Self { tables })
+    }
+
+    /// Replaces the contents of all tables with the contents from the snapshot.
+    /// Sequences are not reset, so rows created after the restore never reuse the IDs of rows
+    /// that existed before it.
+    pub(super) async fn restore(&self, pool: &PgPool) -> anyhow::Result<()> {
+        let mut tx = pool.begin().await?;
+        // Foreign keys are not checked while the tables are being filled in arbitrary order
+        sqlx::query("SET LOCAL session_replication_role = replica")
+            .execute(&mut *tx)
+            .await?;
+
+        let names: Vec<&str> = self.tables.iter().map(|(name, _)| name.as_str()).collect();
+        sqlx::query(&format!("TRUNCATE {}", names.join(", ")))
+            .execute(&mut *tx)
+            .await?;
+        for (table, rows) in &self.tables {
+            for row in rows {
+                sqlx::query(&format!(
+                    "INSERT INTO {table} SELECT * FROM json_populate_record(NULL::{table}, $1::json)"
+                ))
+                .bind(row)
+                .execute(&mut *tx)
+                .await
+                .with_context(|| format!("Cannot restore row {row} of table {table}"))?;
+            }
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Returns the number of rows of the given table in the snapshot.
+    pub fn row_count(&self, table: &str) -> usize {
+        self.tables
+            .iter()
+            .find(|(name, _)| name == table)
+            .map(|(_, rows)| rows.len())
+            .unwrap_or_else(|| panic!("Table {table} is not in the snapshot"))
+    }
+}
+
+async fn load_table_names(pool: &PgPool) -> anyhow::Result<Vec<String>> {
+    let tables: Vec<String> = sqlx::query_scalar(
+        "SELECT tablename::text FROM pg_tables WHERE schemaname = 'public' ORDER BY tablename",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(tables
+        .into_iter()
+        .filter(|table| !IGNORED_TABLES.contains(&table.as_str()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::PullRequestNumber;
+    use crate::tests::{BorsTester, default_repo_name, run_test};
+
+    #[sqlx::test]
+    async fn restore_forgets_later_changes(pool: sqlx::PgPool) {
+        run_test(pool, async |tester: &mut BorsTester| {
+            tester.post_comment("@bors info").await?;
+            tester.expect_comments((), 1).await;
+            let snapshot = tester.snapshot_database().await?;
+            assert_eq!(snapshot.row_count("pull_request"), 1);
+
+            tester.approve(()).await?;
+            tester.restore_database(&snapshot).await?;
+            let pr = tester
+                .db()
+                .get_pull_request(&default_repo_name(), PullRequestNumber(1))
+                .await?
+                .unwrap();
+            assert!(!pr.is_approved());
+
+            // Bors keeps working with the restored state
+            tester.approve(()).await?;
+            tester
+                .get_pr_copy(())
+                .await
+                .expect_approvers(&["default-user"]);
+            Ok(())
+        })
+        .await;
+    }
+}
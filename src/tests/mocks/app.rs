@@ -29,6 +29,14 @@ impl AppHandler {
             .respond_with(ResponseTemplate::new(200).set_body_json(InstallationToken::default()))
             .mount(mock_server)
             .await;
+        Mock::given(method("GET"))
+            .and(path("/rate_limit"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "resources": {},
+                "rate": {"limit": 5000, "used": 0, "remaining": 5000, "reset": 0}
+            })))
+            .mount(mock_server)
+            .await;
     }
 }
 
@@ -1,4 +1,5 @@
 use crate::github::api::client::HideCommentReason;
+use crate::tests::load_test_file;
 use crate::tests::mocks::User;
 use crate::tests::mocks::pull_request::PrIdentifier;
 use crate::tests::mocks::repository::GitHubRepository;
@@ -18,6 +19,8 @@ pub struct Comment {
     pub id: Option<u64>,
     pub node_id: Option<String>,
     pub hide_reason: Option<HideCommentReason>,
+    /// ID of the review thread in which the comment was posted.
+    pub review_thread: Option<u64>,
 }
 
 impl Comment {
@@ -29,6 +32,7 @@ impl Comment {
             id: None,
             node_id: None,
             hide_reason: None,
+            review_thread: None,
         }
     }
 
@@ -36,6 +40,13 @@ impl Comment {
         Self { author, ..self }
     }
 
+    pub fn in_review_thread(self, thread: u64) -> Self {
+        Self {
+            review_thread: Some(thread),
+            ..self
+        }
+    }
+
     pub fn with_ids(self, id: u64, node_id: String) -> Self {
         Self {
             id: Some(id),
@@ -51,6 +62,36 @@ impl<'a> From<&'a str> for Comment {
     }
 }
 
+/// Creates a payload of the `pull_request_review` webhook, for a review that was submitted by
+/// `author` on the `commit` of the given PR.
+/// `state` is the state of the review, e.g. `approved` or `changes_requested`.
+pub fn review_event_payload(
+    pr_ident: PrIdentifier,
+    author: User,
+    state: &str,
+    commit: &str,
+    body: Option<&str>,
+) -> serde_json::Value {
+    let mut payload: serde_json::Value =
+        serde_json::from_str(&load_test_file("webhook/pull-request-review.json")).unwrap();
+    payload["repository"] =
+        serde_json::to_value(GitHubRepository::from(pr_ident.repo.clone())).unwrap();
+    payload["pull_request"]["number"] = pr_ident.number.into();
+    payload["sender"] = serde_json::to_value(GitHubUser::from(author.clone())).unwrap();
+
+    let review = &mut payload["review"];
+    review["body"] = body.into();
+    review["state"] = state.into();
+    review["commit_id"] = commit.into();
+    review["html_url"] = format!(
+        "https://github.com/{}/pull/{}#pullrequestreview-1",
+        pr_ident.repo, pr_ident.number
+    )
+    .into();
+    review["user"] = serde_json::to_value(GitHubUser::from(author)).unwrap();
+    payload
+}
+
 // Copied from octocrab, since its version if #[non_exhaustive]
 #[derive(Serialize)]
 pub struct GitHubIssueCommentEventPayload {
@@ -111,6 +152,33 @@ impl From<Comment> for GitHubIssueCommentEventPayload {
     }
 }
 
+/// Creates a payload of the `pull_request_review_comment` webhook.
+/// It is based on a real webhook payload, to avoid having to replicate the large amount of
+/// fields of a review comment.
+pub fn review_comment_event_payload(comment: Comment) -> serde_json::Value {
+    let mut payload: serde_json::Value =
+        serde_json::from_str(&load_test_file("webhook/pull-request-review-comment.json")).unwrap();
+    let id = comment.id.unwrap();
+    let html_url = format!(
+        "https://github.com/{}/pull/{}#discussion_r{id}",
+        comment.pr_ident.repo, comment.pr_ident.number,
+    );
+
+    payload["repository"] =
+        serde_json::to_value(GitHubRepository::from(comment.pr_ident.repo.clone())).unwrap();
+    payload["pull_request"]["number"] = comment.pr_ident.number.into();
+    payload["sender"] = serde_json::to_value(GitHubUser::from(comment.author.clone())).unwrap();
+
+    let review_comment = &mut payload["comment"];
+    review_comment["id"] = id.into();
+    review_comment["node_id"] = comment.node_id.unwrap().into();
+    review_comment["in_reply_to_id"] = comment.review_thread.filter(|t| *t != id).into();
+    review_comment["body"] = comment.content.into();
+    review_comment["html_url"] = html_url.into();
+    review_comment["user"] = serde_json::to_value(GitHubUser::from(comment.author)).unwrap();
+    payload
+}
+
 // Copied from octocrab, since its version if #[non_exhaustive]
 #[derive(Serialize)]
 struct GitHubIssue {
@@ -5,12 +5,14 @@ use std::sync::Arc;
 use wiremock::{MockServer, Request, ResponseTemplate};
 
 use crate::create_github_client;
+use crate::github::api::GitHubUrls;
 use crate::github::api::client::HideCommentReason;
 use crate::tests::GitHubState;
 use crate::tests::mocks::app::{AppHandler, default_app_id};
 use crate::tests::mocks::dynamic_mock_req;
 use crate::tests::mocks::pull_request::CommentMsg;
 use crate::tests::mocks::repository::{mock_repo, mock_repo_list};
+use crate::tests::mocks::user::GitHubUser;
 
 pub struct GitHubMockServer {
     mock_server: MockServer,
@@ -25,6 +27,7 @@ impl GitHubMockServer {
             let gh_locked = github.lock().await;
             mock_repo_list(&gh_locked, &mock_server).await;
             mock_graphql(github.clone(), &mock_server).await;
+            mock_users_and_teams(&gh_locked, &mock_server).await;
 
             // Repositories are mocked separately to make it easier to
             // pass comm. channels to them.
@@ -44,12 +47,18 @@ impl GitHubMockServer {
     pub fn client(&self) -> Octocrab {
         create_github_client(
             default_app_id().into(),
-            self.mock_server.uri(),
+            &self.urls(),
             GITHUB_MOCK_PRIVATE_KEY.into(),
         )
         .unwrap()
     }
 
+    /// The API is served by the mock server, but links point to github.com, to keep them stable
+    /// across tests.
+    pub fn urls(&self) -> GitHubUrls {
+        GitHubUrls::new(&self.mock_server.uri(), None, Some("https://github.com"))
+    }
+
     /// Make sure that there are no leftover events left in the queues.
     pub async fn assert_empty_queues(self) {
         // This will remove all mocks and thus also any leftover
@@ -93,6 +102,57 @@ impl GitHubMockServer {
     }
 }
 
+async fn mock_users_and_teams(github: &GitHubState, mock_server: &MockServer) {
+    let users = github.users.clone();
+    dynamic_mock_req(
+        move |_req: &Request, [login]: [&str; 1]| match users
+            .iter()
+            .find(|user| user.name.eq_ignore_ascii_case(login))
+        {
+            Some(user) => ResponseTemplate::new(200)
+                .set_body_json(GitHubUser::new(&user.name, user.github_id)),
+            None => not_found("https://docs.github.com/rest/users/users#get-a-user"),
+        },
+        "GET",
+        "^/users/([^/]+)$".to_string(),
+    )
+    .mount(mock_server)
+    .await;
+
+    let teams = github.teams.clone();
+    dynamic_mock_req(
+        move |req: &Request, [org, slug]: [&str; 2]| {
+            let Some(members) = teams.get(&format!("{org}/{slug}")) else {
+                return not_found("https://docs.github.com/rest/teams/members#list-team-members");
+            };
+            // All members fit on the first page
+            let first_page = req.url.query_pairs().any(|(k, v)| k == "page" && v == "1");
+            let members: Vec<GitHubUser> = if first_page {
+                members
+                    .iter()
+                    .map(|user| GitHubUser::new(&user.name, user.github_id))
+                    .collect()
+            } else {
+                vec![]
+            };
+            ResponseTemplate::new(200).set_body_json(members)
+        },
+        "GET",
+        "^/orgs/([^/]+)/teams/([^/]+)/members$".to_string(),
+    )
+    .mount(mock_server)
+    .await;
+}
+
+/// GitHub describes the error in the body, without it the response is not recognized as a
+/// missing resource.
+fn not_found(documentation_url: &str) -> ResponseTemplate {
+    ResponseTemplate::new(404).set_body_json(serde_json::json!({
+        "message": "Not Found",
+        "documentation_url": documentation_url,
+    }))
+}
+
 async fn mock_graphql(github: Arc<tokio::sync::Mutex<GitHubState>>, mock_server: &MockServer) {
     dynamic_mock_req(
         move |request: &Request, []: [&str; 0]| {
@@ -1,5 +1,6 @@
 use crate::TeamApiClient;
 use crate::github::GithubRepoName;
+use crate::github::api::GitHubUrls;
 use crate::github::api::client::HideCommentReason;
 use crate::tests::Comment;
 use crate::tests::mocks::github::GitHubMockServer;
@@ -30,6 +31,10 @@ pub mod workflow;
 pub struct GitHubState {
     pub(super) repos: HashMap<GithubRepoName, Arc<Mutex<Repo>>>,
     comments: HashMap<String, Comment>,
+    /// Users that can be looked up by their login.
+    pub(super) users: Vec<User>,
+    /// Members of organization teams, keyed by `<org>/<team>`.
+    pub(super) teams: HashMap<String, Vec<User>>,
 }
 
 impl GitHubState {
@@ -50,6 +55,18 @@ impl GitHubState {
         self
     }
 
+    /// Adds a team of the given organization with the given members, which also become known
+    /// users.
+    pub fn with_team(mut self, org: &str, team: &str, members: &[User]) -> Self {
+        for member in members {
+            if !self.users.contains(member) {
+                self.users.push(member.clone());
+            }
+        }
+        self.teams.insert(format!("{org}/{team}"), members.to_vec());
+        self
+    }
+
     pub fn default_repo(&self) -> Arc<Mutex<Repo>> {
         self.get_repo(&default_repo_name())
     }
@@ -213,6 +230,14 @@ impl Default for GitHubState {
         Self {
             repos: HashMap::from([(repo.name.clone(), Arc::new(Mutex::new(repo)))]),
             comments: Default::default(),
+            users: vec![
+                User::default_pr_author(),
+                User::bors_bot(),
+                User::unprivileged(),
+                User::try_user(),
+                User::reviewer(),
+            ],
+            teams: Default::default(),
         }
     }
 }
@@ -236,6 +261,10 @@ impl ExternalHttpMock {
         self.gh_server.client()
     }
 
+    pub fn github_urls(&self) -> GitHubUrls {
+        self.gh_server.urls()
+    }
+
     pub fn team_api_client(&self) -> TeamApiClient {
         self.team_api_server.client()
     }
@@ -266,3 +295,24 @@ fn dynamic_mock_req<
             f(req, captured)
         })
 }
+
+/// Responds with the given body and its `ETag`, or with `304 Not Modified` if the request is
+/// conditional and the body has not changed, like GitHub does.
+fn etag_response<T: serde::Serialize>(req: &Request, body: T) -> ResponseTemplate {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    let body = serde_json::to_value(body).unwrap();
+    let mut hasher = DefaultHasher::new();
+    body.to_string().hash(&mut hasher);
+    let etag = format!("\"{:x}\"", hasher.finish());
+    if req
+        .headers
+        .get("if-none-match")
+        .is_some_and(|value| value.as_bytes() == etag.as_bytes())
+    {
+        return ResponseTemplate::new(304).insert_header("etag", etag.as_str());
+    }
+    ResponseTemplate::new(200)
+        .insert_header("etag", etag.as_str())
+        .set_body_json(body)
+}
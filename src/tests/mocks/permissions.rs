@@ -4,7 +4,7 @@ use serde_json::json;
 use std::collections::HashMap;
 use std::sync::Arc;
 use wiremock::matchers::path;
-use wiremock::{Mock, MockServer, ResponseTemplate, matchers::method};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate, matchers::method};
 
 use crate::TeamApiClient;
 use crate::tests::mocks::Repo;
@@ -32,21 +32,21 @@ impl TeamApiMockServer {
     pub async fn start(github: &GitHubState) -> Self {
         let mock_server = MockServer::start().await;
 
+        // The permissions are read when a request is received, so that tests can modify them
         let add_mock = |repo: Arc<Mutex<Repo>>, kind: PermissionType, name: &str| {
-            let repo = repo.lock();
-            let mut users = repo.permissions.users.iter().collect::<Vec<_>>();
-            users.sort_by_key(|p| p.0.github_id);
-            users.retain(|p| p.1.contains(&kind));
-            let permissions = json!({
-                "github_ids": users.into_iter().map(|(user, _)| user.github_id).collect::<Vec<_>>()
-            });
-
+            let repo_name = repo.lock().name.name().to_string();
             Mock::given(method("GET"))
-                .and(path(format!(
-                    "/v1/permissions/bors.{}.{name}.json",
-                    repo.name.name()
-                )))
-                .respond_with(ResponseTemplate::new(200).set_body_json(permissions))
+                .and(path(format!("/v1/permissions/bors.{repo_name}.{name}.json")))
+                .respond_with(move |_req: &Request| {
+                    let repo = repo.lock();
+                    let mut users = repo.permissions.users.iter().collect::<Vec<_>>();
+                    users.sort_by_key(|p| p.0.github_id);
+                    users.retain(|p| p.1.contains(&kind));
+                    let permissions = json!({
+                        "github_ids": users.into_iter().map(|(user, _)| user.github_id).collect::<Vec<_>>()
+                    });
+                    ResponseTemplate::new(200).set_body_json(permissions)
+                })
         };
 
         for repo in github.repos.values() {
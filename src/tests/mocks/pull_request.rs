@@ -1,8 +1,8 @@
 use super::{
     Repo, User,
     comment::{Comment, GitHubComment},
-    default_repo_name, dynamic_mock_req,
-    repository::GitHubRepository,
+    default_repo_name, dynamic_mock_req, etag_response,
+    repository::{CommentReaction, GitHubRepository},
     user::GitHubUser,
 };
 use crate::github::PullRequestNumber;
@@ -102,6 +102,10 @@ pub struct PullRequest {
     pub labels_removed_by_bors: Vec<String>,
     pub comment_counter: u64,
     pub head_sha: String,
+    /// Name of the branch from which the PR was opened.
+    pub head_branch: String,
+    /// Commits of the PR that precede its head commit, from the oldest one.
+    pub previous_commits: Vec<String>,
     pub author: User,
     pub base_branch: Branch,
     pub mergeable_state: MergeableState,
@@ -112,9 +116,14 @@ pub struct PullRequest {
     pub description: String,
     pub title: String,
     pub labels: Vec<String>,
+    /// Repository from which the PR was opened, if it comes from a fork.
+    pub fork: Option<GithubRepoName>,
     pub comment_queue_tx: Sender<CommentMsg>,
     pub comment_queue_rx: Arc<tokio::sync::Mutex<Receiver<CommentMsg>>>,
     pub comment_history: Vec<Comment>,
+    /// Files modified by the PR, with their content at the head of the PR.
+    /// The content is `None` if the PR removes the file.
+    pub changed_files: Vec<(String, Option<String>)>,
 }
 
 impl PullRequest {
@@ -129,6 +138,8 @@ impl PullRequest {
             labels_removed_by_bors: Vec::new(),
             comment_counter: 0,
             head_sha: format!("pr-{number}-sha"),
+            head_branch: format!("pr-{number}"),
+            previous_commits: Vec::new(),
             author,
             base_branch: Branch::default(),
             mergeable_state: MergeableState::Clean,
@@ -139,9 +150,11 @@ impl PullRequest {
             description: format!("Description of PR {number}"),
             title: format!("Title of PR {number}"),
             labels: Vec::new(),
+            fork: None,
             comment_queue_tx,
             comment_queue_rx: Arc::new(tokio::sync::Mutex::new(comment_queue_rx)),
             comment_history: Vec::new(),
+            changed_files: Vec::new(),
         }
     }
 }
@@ -217,14 +230,26 @@ pub async fn mock_pull_requests(repo: Arc<Mutex<Repo>>, mock_server: &MockServer
 
     Mock::given(method("GET"))
         .and(path(format!("/repos/{repo_name}/pulls")))
-        .respond_with(move |_: &Request| {
+        .respond_with(move |request: &Request| {
             let pull_request_error = repo_clone.lock().pull_request_error;
             if pull_request_error {
                 ResponseTemplate::new(500)
             } else {
+                // The `head` filter has the form `<owner>:<branch>`
+                let head = request
+                    .url
+                    .query_pairs()
+                    .find(|(key, _)| key == "head")
+                    .map(|(_, value)| value.to_string());
                 let prs = repo_clone.lock().pull_requests.clone();
                 ResponseTemplate::new(200).set_body_json(
                     prs.values()
+                        .filter(|pr| {
+                            head.as_ref().is_none_or(|head| {
+                                let owner = pr.fork.as_ref().unwrap_or(&pr.repo).owner();
+                                *head == format!("{owner}:{}", pr.head_branch)
+                            })
+                        })
                         .map(|pr| GitHubPullRequest::from(pr.clone()))
                         .filter(|pr| pr.closed_at.is_none())
                         .collect::<Vec<_>>(),
@@ -234,15 +259,49 @@ pub async fn mock_pull_requests(repo: Arc<Mutex<Repo>>, mock_server: &MockServer
         .mount(mock_server)
         .await;
 
+    let repo_clone = repo.clone();
+    Mock::given(method("POST"))
+        .and(path(format!("/repos/{repo_name}/pulls")))
+        .respond_with(move |request: &Request| {
+            #[derive(Deserialize)]
+            struct CreatePullRequestPayload {
+                title: String,
+                head: String,
+                base: String,
+                body: Option<String>,
+            }
+
+            let data: CreatePullRequestPayload = request.body_json().unwrap();
+            let mut repo = repo_clone.lock();
+            let number = repo.pull_requests.keys().max().copied().unwrap_or(0) + 1;
+            let mut pr = PullRequest::new(repo.name.clone(), number, User::bors_bot());
+            pr.head_sha = repo
+                .get_branch_by_name(&data.head)
+                .expect("Head branch of the new PR does not exist")
+                .get_sha()
+                .to_string();
+            pr.head_branch = data.head;
+            pr.base_branch = repo
+                .get_branch_by_name(&data.base)
+                .expect("Base branch of the new PR does not exist")
+                .clone();
+            pr.title = data.title;
+            pr.description = data.body.unwrap_or_default();
+            repo.pull_requests.insert(number, pr.clone());
+            ResponseTemplate::new(201).set_body_json(GitHubPullRequest::from(pr))
+        })
+        .mount(mock_server)
+        .await;
+
     let repo_clone = repo.clone();
     dynamic_mock_req(
-        move |_req: &Request, [pr_number]: [&str; 1]| {
+        move |req: &Request, [pr_number]: [&str; 1]| {
             let pr_number: u64 = pr_number.parse().unwrap();
             let pull_request_error = repo_clone.lock().pull_request_error;
             if pull_request_error {
                 ResponseTemplate::new(500)
             } else if let Some(pr) = repo_clone.lock().pull_requests.get(&pr_number) {
-                ResponseTemplate::new(200).set_body_json(GitHubPullRequest::from(pr.clone()))
+                etag_response(req, GitHubPullRequest::from(pr.clone()))
             } else {
                 ResponseTemplate::new(404)
             }
@@ -254,6 +313,11 @@ pub async fn mock_pull_requests(repo: Arc<Mutex<Repo>>, mock_server: &MockServer
     .await;
 
     mock_pr_comments(repo.clone(), mock_server).await;
+    mock_pr_review_comment_replies(repo.clone(), mock_server).await;
+    mock_comment_reactions(repo.clone(), mock_server).await;
+    mock_pr_files(repo.clone(), mock_server).await;
+    mock_pr_commits(repo.clone(), mock_server).await;
+    mock_pr_update(repo.clone(), mock_server).await;
     mock_pr_labels(repo.clone(), repo_name.clone(), mock_server).await;
 }
 
@@ -269,8 +333,19 @@ async fn mock_pr_comments(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
                 body: String,
             }
 
-            let comment_payload: CommentCreatePayload = req.body_json().unwrap();
             let mut repo = repo.lock();
+            // Retries of failed requests might not contain the body
+            if repo.comment_error {
+                return ResponseTemplate::new(500);
+            }
+            if repo.rate_limited_comments > 0 {
+                repo.rate_limited_comments -= 1;
+                return ResponseTemplate::new(403).set_body_json(serde_json::json!({
+                    "message": "You have exceeded a secondary rate limit. Please wait a few minutes before you try again.",
+                    "documentation_url": "https://docs.github.com/rest/overview/rate-limits-for-the-rest-api"
+                }));
+            }
+            let comment_payload: CommentCreatePayload = req.body_json().unwrap();
             let pr = repo.pull_requests.get_mut(&pr_number).unwrap_or_else(|| {
                 panic!("Received a comment for a non-existing PR {repo_name_clone}/{pr_number}")
             });
@@ -295,6 +370,166 @@ async fn mock_pr_comments(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
     .await;
 }
 
+async fn mock_pr_review_comment_replies(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
+    let repo_name = repo.lock().name.clone();
+    let repo_name_clone = repo_name.clone();
+    dynamic_mock_req(
+        move |req: &Request, [pr_number, thread]: [&str; 2]| {
+            let pr_number: u64 = pr_number.parse().unwrap();
+            let thread: u64 = thread.parse().unwrap();
+
+            #[derive(Deserialize)]
+            struct ReplyCreatePayload {
+                body: String,
+            }
+
+            let reply_payload: ReplyCreatePayload = req.body_json().unwrap();
+            let mut repo = repo.lock();
+            let pr = repo.pull_requests.get_mut(&pr_number).unwrap_or_else(|| {
+                panic!("Received a reply for a non-existing PR {repo_name_clone}/{pr_number}")
+            });
+            let (id, node_id) = pr.next_comment_ids();
+
+            let comment = Comment::new((repo_name_clone.clone(), pr_number), &reply_payload.body)
+                .with_author(User::bors_bot())
+                .with_ids(id, node_id)
+                .in_review_thread(thread);
+
+            pr.comment_queue_tx
+                .try_send(CommentMsg::Comment(comment.clone()))
+                .unwrap();
+            ResponseTemplate::new(201).set_body_json(serde_json::json!({
+                "id": id,
+                "in_reply_to_id": thread,
+                "body": reply_payload.body,
+            }))
+        },
+        "POST",
+        format!("^/repos/{repo_name}/pulls/([0-9]+)/comments/([0-9]+)/replies$"),
+    )
+    .mount(mock_server)
+    .await;
+}
+
+async fn mock_pr_files(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
+    let repo_name = repo.lock().name.clone();
+    dynamic_mock_req(
+        move |_req: &Request, [pr_number]: [&str; 1]| {
+            let pr_number: u64 = pr_number.parse().unwrap();
+            let repo = repo.lock();
+            let Some(pr) = repo.pull_requests.get(&pr_number) else {
+                return ResponseTemplate::new(404);
+            };
+            let files: Vec<serde_json::Value> = pr
+                .changed_files
+                .iter()
+                .map(|(path, content)| {
+                    serde_json::json!({
+                        "sha": null,
+                        "filename": path,
+                        "status": if content.is_some() { "modified" } else { "removed" },
+                        "additions": 0,
+                        "deletions": 0,
+                        "changes": 0,
+                        "blob_url": null,
+                        "raw_url": null,
+                        "contents_url": format!("https://api.github.com/repos/{}/contents/{path}", repo.name),
+                    })
+                })
+                .collect();
+            ResponseTemplate::new(200).set_body_json(files)
+        },
+        "GET",
+        format!("^/repos/{repo_name}/pulls/([0-9]+)/files$"),
+    )
+    .mount(mock_server)
+    .await;
+}
+
+async fn mock_pr_commits(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
+    let repo_name = repo.lock().name.clone();
+    dynamic_mock_req(
+        move |req: &Request, [pr_number]: [&str; 1]| {
+            let pr_number: u64 = pr_number.parse().unwrap();
+            let repo = repo.lock();
+            let Some(pr) = repo.pull_requests.get(&pr_number) else {
+                return ResponseTemplate::new(404);
+            };
+            // All commits fit on the first page
+            let first_page = req.url.query_pairs().any(|(k, v)| k == "page" && v == "1");
+            let commits: Vec<serde_json::Value> = if first_page {
+                pr.previous_commits
+                    .iter()
+                    .chain(std::iter::once(&pr.head_sha))
+                    .map(|sha| serde_json::json!({ "sha": sha }))
+                    .collect()
+            } else {
+                vec![]
+            };
+            ResponseTemplate::new(200).set_body_json(commits)
+        },
+        "GET",
+        format!("^/repos/{repo_name}/pulls/([0-9]+)/commits$"),
+    )
+    .mount(mock_server)
+    .await;
+}
+
+async fn mock_pr_update(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
+    let repo_name = repo.lock().name.clone();
+    dynamic_mock_req(
+        move |req: &Request, [pr_number]: [&str; 1]| {
+            #[derive(Deserialize)]
+            struct PullRequestUpdatePayload {
+                state: Option<String>,
+            }
+
+            let pr_number: u64 = pr_number.parse().unwrap();
+            let payload: PullRequestUpdatePayload = req.body_json().unwrap();
+            let mut repo = repo.lock();
+            let Some(pr) = repo.pull_requests.get_mut(&pr_number) else {
+                return ResponseTemplate::new(404);
+            };
+            if payload.state.as_deref() == Some("closed") {
+                pr.close_pr();
+            }
+            ResponseTemplate::new(200).set_body_json(GitHubPullRequest::from(pr.clone()))
+        },
+        "PATCH",
+        format!("^/repos/{repo_name}/pulls/([0-9]+)$"),
+    )
+    .mount(mock_server)
+    .await;
+}
+
+async fn mock_comment_reactions(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
+    let repo_name = repo.lock().name.clone();
+    dynamic_mock_req(
+        move |req: &Request, [kind, comment_id]: [&str; 2]| {
+            #[derive(Deserialize)]
+            struct ReactionCreatePayload {
+                content: String,
+            }
+
+            let payload: ReactionCreatePayload = req.body_json().unwrap();
+            let comment_id: u64 = comment_id.parse().unwrap();
+            repo.lock().comment_reactions.push(CommentReaction {
+                review_comment: kind == "pulls",
+                comment_id,
+                content: payload.content.clone(),
+            });
+            ResponseTemplate::new(201).set_body_json(serde_json::json!({
+                "id": 1,
+                "content": payload.content,
+            }))
+        },
+        "POST",
+        format!("^/repos/{repo_name}/(issues|pulls)/comments/([0-9]+)/reactions$"),
+    )
+    .mount(mock_server)
+    .await;
+}
+
 async fn mock_pr_labels(
     repo: Arc<Mutex<Repo>>,
     repo_name: GithubRepoName,
@@ -389,11 +624,13 @@ impl From<PullRequest> for GitHubPullRequest {
     fn from(pr: PullRequest) -> Self {
         let PullRequest {
             number,
-            repo: _,
+            repo,
             labels_added_by_bors: _,
             labels_removed_by_bors: _,
             comment_counter: _,
             head_sha,
+            head_branch,
+            previous_commits: _,
             author,
             base_branch,
             mergeable_state,
@@ -404,9 +641,11 @@ impl From<PullRequest> for GitHubPullRequest {
             description,
             title,
             labels,
+            fork,
             comment_queue_tx: _,
             comment_queue_rx: _,
             comment_history: _,
+            changed_files: _,
         } = pr;
         GitHubPullRequest {
             user: author.clone().into(),
@@ -419,12 +658,14 @@ impl From<PullRequest> for GitHubPullRequest {
             number: number.0,
             head: Box::new(GitHubHead {
                 label: format!("pr-{number}"),
-                ref_field: format!("pr-{number}"),
+                ref_field: head_branch,
                 sha: head_sha,
+                repo: fork.unwrap_or_else(|| repo.clone()).into(),
             }),
             base: Box::new(GitHubBase {
                 ref_field: base_branch.get_name().to_string(),
                 sha: base_branch.get_sha().to_string(),
+                repo: repo.into(),
             }),
             merged_at,
             closed_at,
@@ -460,6 +701,7 @@ struct GitHubHead {
     #[serde(rename = "ref")]
     ref_field: String,
     sha: String,
+    repo: GitHubRepository,
 }
 
 #[derive(Serialize)]
@@ -467,6 +709,7 @@ struct GitHubBase {
     #[serde(rename = "ref")]
     ref_field: String,
     sha: String,
+    repo: GitHubRepository,
 }
 
 #[derive(Serialize)]
@@ -496,6 +739,8 @@ impl GitHubPullRequestEventPayload {
 #[derive(Serialize)]
 struct GitHubPullRequestChanges {
     base: Option<GitHubPullRequestBaseChanges>,
+    title: Option<PullRequestEventChangesFrom>,
+    body: Option<PullRequestEventChangesFrom>,
 }
 
 #[derive(Serialize)]
@@ -520,13 +765,23 @@ impl From<PullRequestChangeEvent> for GitHubPullRequestChanges {
             None
         };
 
-        GitHubPullRequestChanges { base }
+        GitHubPullRequestChanges {
+            base,
+            title: value
+                .from_title
+                .map(|title| PullRequestEventChangesFrom { from: title }),
+            body: value
+                .from_body
+                .map(|body| PullRequestEventChangesFrom { from: body }),
+        }
     }
 }
 
 #[derive(Default)]
 pub struct PullRequestChangeEvent {
     pub from_base_sha: Option<String>,
+    pub from_title: Option<String>,
+    pub from_body: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::num::NonZeroU64;
 use std::sync::Arc;
 
@@ -6,8 +6,8 @@ use super::user::{GitHubUser, User};
 use crate::database::WorkflowStatus;
 use crate::github::GithubRepoName;
 use crate::permissions::PermissionType;
-use crate::tests::mocks::dynamic_mock_req;
 use crate::tests::mocks::pull_request::{PullRequest, mock_pull_requests};
+use crate::tests::mocks::{dynamic_mock_req, etag_response};
 use crate::tests::{GitHubState, Permissions, WorkflowJob, WorkflowRunData};
 use base64::Engine;
 use chrono::{DateTime, Utc};
@@ -84,17 +84,57 @@ pub struct Repo {
     pub config: String,
     pub branches: Vec<Branch>,
     pub commit_messages: HashMap<String, String>,
+    /// Explicit authors (`name <email>`) of commits created through the Git commit API.
+    pub commit_authors: HashMap<String, String>,
     pub workflows_cancelled_by_bors: Vec<u64>,
     pub workflow_cancel_error: bool,
     /// All workflows that we know about from the side of the test.
     pub workflow_runs: Vec<WorkflowRun>,
     pub pull_requests: HashMap<u64, PullRequest>,
     pub check_runs: Vec<CheckRunData>,
+    /// Status checks required by the branch protection of all branches.
+    /// If empty, branches are not protected.
+    pub required_status_checks: Vec<String>,
+    /// GitHub Actions workflow files of the repository, as (file name, content) pairs.
+    pub workflow_files: Vec<(String, String)>,
+    /// Logs of workflow jobs, by job ID.
+    pub job_logs: HashMap<u64, String>,
     /// Cause pull request fetch to fail.
     pub pull_request_error: bool,
+    /// Cause comment posting to fail.
+    pub comment_error: bool,
+    /// Number of the following comment posting requests that are refused because of a rate limit.
+    pub rate_limited_comments: u32,
+    /// Reactions that were added to comments, in the order in which they were added.
+    pub comment_reactions: Vec<CommentReaction>,
     /// Push error failure/success behaviour.
     pub push_behaviour: BranchPushBehaviour,
     pub pr_push_counter: u64,
+    /// Commits whose changes are already contained in the base branch (e.g. because they were
+    /// cherry-picked), so merging them does not change any files.
+    pub cherry_picked_commits: HashSet<String>,
+    /// Parents of merge commits that were pushed to PRs. Other commits of a PR have the previous
+    /// commit of the PR as their only parent.
+    pub merge_commit_parents: HashMap<String, Vec<String>>,
+    /// Commits pushed to PRs that make the same changes as the given earlier commit, e.g. because
+    /// they are rebases of it. Other commits change the diff of the PR.
+    pub same_diff_commits: HashMap<String, String>,
+    /// IDs of workflow artifacts that were deleted by bors.
+    pub deleted_artifacts: Vec<u64>,
+    /// Workflows that were dispatched by bors, as (workflow, JSON body) pairs.
+    pub dispatched_workflows: Vec<(String, serde_json::Value)>,
+    /// Is the app installed in the repository? Only installed repositories are listed by the
+    /// installation.
+    pub installed: bool,
+}
+
+/// A reaction added to a comment by bors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommentReaction {
+    /// Whether the comment was posted in a review thread.
+    pub review_comment: bool,
+    pub comment_id: u64,
+    pub content: String,
 }
 
 impl Repo {
@@ -106,13 +146,26 @@ impl Repo {
             pull_requests: Default::default(),
             branches: vec![Branch::default()],
             commit_messages: Default::default(),
+            commit_authors: Default::default(),
             workflows_cancelled_by_bors: vec![],
             workflow_cancel_error: false,
             workflow_runs: vec![],
             pull_request_error: false,
+            comment_error: false,
+            rate_limited_comments: 0,
+            comment_reactions: vec![],
             pr_push_counter: 0,
+            cherry_picked_commits: HashSet::new(),
+            merge_commit_parents: HashMap::new(),
+            same_diff_commits: HashMap::new(),
+            deleted_artifacts: vec![],
+            dispatched_workflows: vec![],
             check_runs: vec![],
+            required_status_checks: vec![],
+            workflow_files: vec![],
+            job_logs: HashMap::new(),
             push_behaviour: BranchPushBehaviour::default(),
+            installed: true,
         }
     }
 
@@ -127,6 +180,13 @@ impl Repo {
         self
     }
 
+    /// Adds a new PR opened by the default PR author, without notifying bors about it.
+    pub fn add_pr(&mut self) -> &mut PullRequest {
+        let number = self.pull_requests.keys().max().copied().unwrap_or(0) + 1;
+        let pr = PullRequest::new(self.name.clone(), number, User::default_pr_author());
+        self.pull_requests.entry(number).or_insert(pr)
+    }
+
     pub fn get_pr(&self, pr: u64) -> &PullRequest {
         self.pull_requests.get(&pr).unwrap()
     }
@@ -190,6 +250,10 @@ impl Repo {
         self.commit_messages
             .insert(sha.to_string(), message.to_string());
     }
+
+    pub fn get_commit_author(&self, sha: &str) -> Option<String> {
+        self.commit_authors.get(sha).cloned()
+    }
 }
 
 /// Represents the default repository for tests.
@@ -283,27 +347,28 @@ pub fn default_branch_sha() -> &'static str {
 }
 
 pub async fn mock_repo_list(github: &GitHubState, mock_server: &MockServer) {
-    let repos = GitHubRepositories {
-        total_count: github.repos.len() as u64,
-        repositories: github
-            .repos
-            .iter()
-            .enumerate()
-            .map(|(index, (_, repo))| {
-                let repo = repo.lock();
-                GitHubRepository {
-                    id: index as u64,
-                    owner: User::new(index as u64, repo.name.owner()).into(),
-                    name: repo.name.name().to_string(),
-                    url: format!("https://{}.foo", repo.name.name()).parse().unwrap(),
-                }
-            })
-            .collect(),
-    };
-
+    let repos: Vec<Arc<Mutex<Repo>>> = github.repos.values().cloned().collect();
     Mock::given(method("GET"))
         .and(path("/installation/repositories"))
-        .respond_with(ResponseTemplate::new(200).set_body_json(repos))
+        .respond_with(move |_: &Request| {
+            let repositories: Vec<GitHubRepository> = repos
+                .iter()
+                .enumerate()
+                .filter_map(|(index, repo)| {
+                    let repo = repo.lock();
+                    repo.installed.then(|| GitHubRepository {
+                        id: index as u64,
+                        owner: User::new(index as u64, repo.name.owner()).into(),
+                        name: repo.name.name().to_string(),
+                        url: format!("https://{}.foo", repo.name.name()).parse().unwrap(),
+                    })
+                })
+                .collect();
+            ResponseTemplate::new(200).set_body_json(GitHubRepositories {
+                total_count: repositories.len() as u64,
+                repositories,
+            })
+        })
         .mount(mock_server)
         .await;
 }
@@ -315,14 +380,48 @@ pub async fn mock_repo(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
     mock_check_runs(repo.clone(), mock_server).await;
     mock_workflow_runs(repo.clone(), mock_server).await;
     mock_workflow_jobs(repo.clone(), mock_server).await;
+    mock_job_logs(repo.clone(), mock_server).await;
+    mock_workflow_artifacts(repo.clone(), mock_server).await;
+    mock_workflow_dispatch(repo.clone(), mock_server).await;
     mock_config(repo.clone(), mock_server).await;
+    mock_workflow_files(repo.clone(), mock_server).await;
 }
 
 async fn mock_branches(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
+    // This has to be mounted before the branch mock, which would also match its path
+    mock_branch_protection(repo.clone(), mock_server).await;
     mock_get_branch(repo.clone(), mock_server).await;
+    mock_compare_commits(repo.clone(), mock_server).await;
     mock_create_branch(repo.clone(), mock_server).await;
     mock_update_branch(repo.clone(), mock_server).await;
     mock_merge_branch(repo.clone(), mock_server).await;
+    mock_git_commits(repo.clone(), mock_server).await;
+    mock_matching_refs(repo.clone(), mock_server).await;
+}
+
+async fn mock_matching_refs(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
+    let repo_name = repo.lock().name.clone();
+    dynamic_mock_req(
+        move |_req: &Request, [prefix]: [&str; 1]| {
+            let repo = repo.lock();
+            let refs: Vec<serde_json::Value> = repo
+                .branches
+                .iter()
+                .filter(|branch| branch.name.starts_with(prefix))
+                .map(|branch| {
+                    serde_json::json!({
+                        "ref": format!("refs/heads/{}", branch.name),
+                        "object": { "sha": branch.sha, "type": "commit" },
+                    })
+                })
+                .collect();
+            ResponseTemplate::new(200).set_body_json(refs)
+        },
+        "GET",
+        format!("^/repos/{repo_name}/git/matching-refs/heads/(.*)$"),
+    )
+    .mount(mock_server)
+    .await;
 }
 
 async fn mock_cancel_workflow(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
@@ -345,13 +444,38 @@ async fn mock_cancel_workflow(repo: Arc<Mutex<Repo>>, mock_server: &MockServer)
     .await;
 }
 
+async fn mock_branch_protection(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
+    let repo_name = repo.lock().name.clone();
+    dynamic_mock_req(
+        move |_req: &Request, [_branch_name]: [&str; 1]| {
+            let repo = repo.lock();
+            if repo.required_status_checks.is_empty() {
+                return ResponseTemplate::new(404);
+            }
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "strict": false,
+                "contexts": repo.required_status_checks,
+            }))
+        },
+        "GET",
+        format!("^/repos/{repo_name}/branches/(.*)/protection/required_status_checks$"),
+    )
+    .mount(mock_server)
+    .await;
+}
+
 async fn mock_get_branch(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
     let repo_name = repo.lock().name.clone();
     dynamic_mock_req(
-        move |_req: &Request, [branch_name]: [&str; 1]| {
+        move |req: &Request, [branch_name]: [&str; 1]| {
             let mut repo = repo.lock();
             let Some(branch) = repo.get_branch_by_name(branch_name) else {
-                return ResponseTemplate::new(404);
+                // GitHub describes the error in the body, without it the response is not
+                // recognized as a missing resource
+                return ResponseTemplate::new(404).set_body_json(serde_json::json!({
+                    "message": "Branch not found",
+                    "documentation_url": "https://docs.github.com/rest/branches/branches#get-a-branch",
+                }));
             };
             let branch = GitHubBranch {
                 name: branch.name.clone(),
@@ -363,7 +487,7 @@ async fn mock_get_branch(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
                 },
                 protected: false,
             };
-            ResponseTemplate::new(200).set_body_json(branch)
+            etag_response(req, branch)
         },
         "GET",
         format!("^/repos/{repo_name}/branches/(.*)$"),
@@ -372,6 +496,81 @@ async fn mock_get_branch(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
     .await;
 }
 
+/// The mock does not know the full commit graph, so a commit is considered to be an ancestor of
+/// `head` only if `head` is the current SHA of a branch, and the commit is in the SHA history of
+/// that branch. Only cherry-picked commits do not change any files, and each commit has a
+/// different diff, unless it is in `same_diff_commits`.
+async fn mock_compare_commits(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
+    let repo_name = repo.lock().name.clone();
+    dynamic_mock_req(
+        move |_req: &Request, [base, head]: [&str; 2]| {
+            let repo = repo.lock();
+            let status = if base == head {
+                "identical"
+            } else if repo
+                .branches
+                .iter()
+                .filter(|branch| branch.get_sha() == head)
+                .any(|branch| branch.get_sha_history().iter().any(|sha| sha == base))
+            {
+                "ahead"
+            } else {
+                "diverged"
+            };
+            let files = if repo.cherry_picked_commits.contains(head) {
+                serde_json::json!([])
+            } else {
+                let mut source = head;
+                while let Some(original) = repo.same_diff_commits.get(source) {
+                    source = original.as_str();
+                }
+                serde_json::json!([{
+                    "filename": "src/lib.rs",
+                    "status": "modified",
+                    "patch": format!("@@ -1 +1 @@\n+{source}")
+                }])
+            };
+            // If `head` is the head of a PR, the commits of the PR that follow `base` are returned
+            let commits: Vec<_> = repo
+                .pull_requests
+                .values()
+                .find(|pr| pr.head_sha == head)
+                .map(|pr| {
+                    let mut shas = pr.previous_commits.clone();
+                    shas.push(pr.head_sha.clone());
+                    let start = shas
+                        .iter()
+                        .position(|sha| sha == base)
+                        .map_or(shas.len(), |position| position + 1);
+                    (start..shas.len())
+                        .map(|index| {
+                            let parents = repo
+                                .merge_commit_parents
+                                .get(&shas[index])
+                                .cloned()
+                                .unwrap_or_else(|| vec![shas[index - 1].clone()]);
+                            serde_json::json!({
+                                "sha": shas[index],
+                                "parents": parents
+                                    .into_iter()
+                                    .map(|sha| serde_json::json!({ "sha": sha }))
+                                    .collect::<Vec<_>>()
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({ "status": status, "files": files, "commits": commits }),
+            )
+        },
+        "GET",
+        format!(r"^/repos/{repo_name}/compare/(.*)\.\.\.(.*)$"),
+    )
+    .mount(mock_server)
+    .await;
+}
+
 async fn mock_create_branch(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
     let repo_name = repo.lock().name.clone();
     Mock::given(method("POST"))
@@ -520,6 +719,9 @@ async fn mock_merge_branch(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
             base_branch.merge_counter += 1;
             base_branch.set_to_sha(&merge_sha);
             repo.set_commit_message(&merge_sha, &data.commit_message);
+            if repo.cherry_picked_commits.contains(&head_sha) {
+                repo.cherry_picked_commits.insert(merge_sha.clone());
+            }
 
             #[derive(serde::Serialize)]
             struct MergeResponse {
@@ -532,12 +734,69 @@ async fn mock_merge_branch(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
         .await;
 }
 
+/// Mocks the Git commit API. The tree of each commit is identified by the SHA of the commit, and
+/// a commit created from a tree gets the SHA `<tree>-squashed`.
+async fn mock_git_commits(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
+    let repo_name = repo.lock().name.clone();
+    dynamic_mock_req(
+        move |_req: &Request, [sha]: [&str; 1]| {
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "sha": sha,
+                "tree": { "sha": sha },
+            }))
+        },
+        "GET",
+        format!("^/repos/{repo_name}/git/commits/(.*)$"),
+    )
+    .mount(mock_server)
+    .await;
+
+    Mock::given(method("POST"))
+        .and(path(format!("/repos/{repo_name}/git/commits")))
+        .respond_with(move |request: &Request| {
+            #[derive(serde::Deserialize)]
+            struct CommitAuthor {
+                name: String,
+                email: String,
+            }
+
+            #[derive(serde::Deserialize)]
+            struct CreateCommitRequest {
+                message: String,
+                tree: String,
+                author: Option<CommitAuthor>,
+            }
+
+            let data: CreateCommitRequest = request.body_json().unwrap();
+            let sha = format!("{}-squashed", data.tree);
+            let mut repo = repo.lock();
+            repo.set_commit_message(&sha, &data.message);
+            if let Some(author) = data.author {
+                repo.commit_authors
+                    .insert(sha.clone(), format!("{} <{}>", author.name, author.email));
+            }
+            ResponseTemplate::new(201).set_body_json(serde_json::json!({
+                "sha": sha,
+                "tree": { "sha": data.tree },
+            }))
+        })
+        .mount(mock_server)
+        .await;
+}
+
 async fn mock_workflow_runs(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
     #[derive(serde::Serialize, Debug)]
     struct WorkflowRunResponse {
         id: octocrab::models::RunId,
+        name: String,
+        html_url: String,
+        check_suite_id: CheckSuiteId,
         status: Status,
         conclusion: Option<Conclusion>,
+        head_branch: String,
+        head_sha: String,
+        created_at: DateTime<Utc>,
+        updated_at: DateTime<Utc>,
     }
 
     #[derive(serde::Serialize, Debug)]
@@ -549,31 +808,73 @@ async fn mock_workflow_runs(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
     dynamic_mock_req(
         move |req: &Request, []| {
             let repo = repo.lock();
-            let check_suite_id: CheckSuiteId = get_query_param(req, "check_suite_id")
-                .parse::<u64>()
-                .unwrap()
-                .into();
-            let workflow_runs: Vec<WorkflowRun> = repo
-                .workflow_runs
-                .iter()
-                .filter(|w| w.workflow_run.check_suite_id == check_suite_id)
-                .cloned()
-                .collect();
+            let workflow_runs: Vec<WorkflowRun> = if has_query_param(req, "status") {
+                // Workflows are never queued in tests, they start running right away
+                let running = get_query_param(req, "status") == "in_progress";
+                let branch = get_query_param(req, "branch");
+                let per_page: usize = get_query_param(req, "per_page").parse().unwrap();
+                let page: usize = get_query_param(req, "page").parse().unwrap();
+                repo.workflow_runs
+                    .iter()
+                    .filter(|w| {
+                        running
+                            && w.status == WorkflowStatus::Pending
+                            && w.workflow_run.head_branch == branch
+                            && !repo
+                                .workflows_cancelled_by_bors
+                                .contains(&w.workflow_run.run_id.0)
+                    })
+                    .skip((page - 1) * per_page)
+                    .take(per_page)
+                    .cloned()
+                    .collect()
+            } else if has_query_param(req, "head_sha") {
+                let branch = get_query_param(req, "branch");
+                let head_sha = get_query_param(req, "head_sha");
+                repo.workflow_runs
+                    .iter()
+                    .filter(|w| {
+                        w.workflow_run.head_branch == branch && w.workflow_run.head_sha == head_sha
+                    })
+                    .cloned()
+                    .collect()
+            } else {
+                let check_suite_id: CheckSuiteId = get_query_param(req, "check_suite_id")
+                    .parse::<u64>()
+                    .unwrap()
+                    .into();
+                repo.workflow_runs
+                    .iter()
+                    .filter(|w| w.workflow_run.check_suite_id == check_suite_id)
+                    .cloned()
+                    .collect()
+            };
 
             let response = WorkflowRunsResponse {
                 workflow_runs: workflow_runs
                     .into_iter()
                     .map(|run| {
                         let (status, conclusion) = status_to_gh(run.status);
+                        let updated_at = Utc::now();
                         WorkflowRunResponse {
                             id: run.workflow_run.run_id,
+                            html_url: format!(
+                                "https://github.com/{}/actions/runs/{}",
+                                run.workflow_run.repository, run.workflow_run.run_id
+                            ),
+                            name: run.workflow_run.name,
+                            check_suite_id: run.workflow_run.check_suite_id,
                             status,
                             conclusion,
+                            created_at: updated_at - run.workflow_run.duration,
+                            updated_at,
+                            head_branch: run.workflow_run.head_branch,
+                            head_sha: run.workflow_run.head_sha,
                         }
                     })
                     .collect(),
             };
-            ResponseTemplate::new(200).set_body_json(response)
+            etag_response(req, response)
         },
         "GET",
         format!("^/repos/{repo_name}/actions/runs$"),
@@ -626,6 +927,99 @@ async fn mock_workflow_jobs(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
     .await;
 }
 
+async fn mock_job_logs(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
+    let repo_name = repo.lock().name.clone();
+    dynamic_mock_req(
+        move |_req: &Request, [job_id]: [&str; 1]| {
+            let job_id: u64 = job_id.parse().expect("Non-integer job id");
+            match repo.lock().job_logs.get(&job_id) {
+                Some(logs) => ResponseTemplate::new(200).set_body_string(logs.clone()),
+                None => ResponseTemplate::new(404),
+            }
+        },
+        "GET",
+        format!("^/repos/{repo_name}/actions/jobs/(.*)/logs$"),
+    )
+    .mount(mock_server)
+    .await;
+}
+
+async fn mock_workflow_artifacts(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
+    let repo_name = repo.lock().name.clone();
+    let repo_clone = repo.clone();
+    dynamic_mock_req(
+        move |_req: &Request, [run_id]: [&str; 1]| {
+            let repo = repo.lock();
+            let run_id: RunId = run_id.parse::<u64>().expect("Non-integer run id").into();
+            let artifacts = repo
+                .workflow_runs
+                .iter()
+                .find(|w| w.workflow_run.run_id == run_id)
+                .map(|w| w.workflow_run.artifacts.clone())
+                .unwrap_or_default();
+
+            let response = GitHubWorkflowArtifacts {
+                total_count: artifacts.len() as u64,
+                artifacts: artifacts
+                    .into_iter()
+                    .map(|artifact| GitHubWorkflowArtifact {
+                        id: artifact.id,
+                        name: artifact.name,
+                        size_in_bytes: artifact.size,
+                        expired: false,
+                    })
+                    .collect(),
+            };
+            ResponseTemplate::new(200).set_body_json(response)
+        },
+        "GET",
+        format!("^/repos/{repo_name}/actions/runs/(.*)/artifacts$"),
+    )
+    .mount(mock_server)
+    .await;
+
+    let repo_name = repo_clone.lock().name.clone();
+    dynamic_mock_req(
+        move |_req: &Request, [artifact_id]: [&str; 1]| {
+            let mut repo = repo_clone.lock();
+            let artifact_id: u64 = artifact_id.parse().expect("Non-integer artifact id");
+            for workflow_run in repo.workflow_runs.iter_mut() {
+                workflow_run
+                    .workflow_run
+                    .artifacts
+                    .retain(|artifact| artifact.id != artifact_id);
+            }
+            repo.deleted_artifacts.push(artifact_id);
+            ResponseTemplate::new(204)
+        },
+        "DELETE",
+        format!("^/repos/{repo_name}/actions/artifacts/(.*)$"),
+    )
+    .mount(mock_server)
+    .await;
+}
+
+async fn mock_workflow_dispatch(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
+    let repo_name = repo.lock().name.clone();
+    dynamic_mock_req(
+        move |req: &Request, [workflow]: [&str; 1]| {
+            let body: serde_json::Value = req.body_json().unwrap();
+            repo.lock()
+                .dispatched_workflows
+                .push((workflow.to_string(), body));
+            ResponseTemplate::new(204)
+        },
+        "POST",
+        format!("^/repos/{repo_name}/actions/workflows/(.*)/dispatches$"),
+    )
+    .mount(mock_server)
+    .await;
+}
+
+fn has_query_param(req: &Request, key: &str) -> bool {
+    req.url.query_pairs().any(|(k, _)| k == key)
+}
+
 fn get_query_param(req: &Request, key: &str) -> String {
     req.url
         .query_pairs()
@@ -635,21 +1029,83 @@ fn get_query_param(req: &Request, key: &str) -> String {
 }
 
 async fn mock_config(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
-    // Extracted into a block to avoid holding the lock over an await point
-    let mock = {
-        let repo = repo.lock();
-        Mock::given(method("GET"))
-            .and(path(format!(
-                "/repos/{}/contents/rust-bors.toml",
-                repo.name
-            )))
-            .respond_with(
-                ResponseTemplate::new(200)
+    let repo_name = repo.lock().name.clone();
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{repo_name}/contents/rust-bors.toml")))
+        .respond_with(move |request: &Request| {
+            let repo = repo.lock();
+            // A config file at the head of a PR that modifies it
+            let modified = request
+                .url
+                .query_pairs()
+                .find(|(key, _)| key == "ref")
+                .and_then(|(_, sha)| {
+                    repo.pull_requests
+                        .values()
+                        .find(|pr| pr.head_sha == sha)
+                        .and_then(|pr| {
+                            pr.changed_files
+                                .iter()
+                                .find(|(path, _)| path == "rust-bors.toml")
+                        })
+                        .map(|(_, content)| content.clone())
+                });
+            match modified {
+                Some(Some(content)) => ResponseTemplate::new(200)
+                    .set_body_json(GitHubContent::new("rust-bors.toml", &content)),
+                Some(None) => ResponseTemplate::new(404),
+                None => ResponseTemplate::new(200)
                     .set_body_json(GitHubContent::new("rust-bors.toml", &repo.config)),
-            )
-            .mount(mock_server)
-    };
-    mock.await;
+            }
+        })
+        .mount(mock_server)
+        .await;
+}
+
+async fn mock_workflow_files(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
+    let repo_name = repo.lock().name.clone();
+    let repo2 = repo.clone();
+    Mock::given(method("GET"))
+        .and(path(format!(
+            "/repos/{repo_name}/contents/.github/workflows"
+        )))
+        .respond_with(move |_: &Request| {
+            let repo = repo2.lock();
+            if repo.workflow_files.is_empty() {
+                return ResponseTemplate::new(404);
+            }
+            let files: Vec<GitHubContent> = repo
+                .workflow_files
+                .iter()
+                .map(|(name, content)| {
+                    GitHubContent::new(&format!(".github/workflows/{name}"), content)
+                })
+                .collect();
+            ResponseTemplate::new(200).set_body_json(files)
+        })
+        .mount(mock_server)
+        .await;
+
+    dynamic_mock_req(
+        move |_: &Request, [name]: [&str; 1]| {
+            let repo = repo.lock();
+            match repo
+                .workflow_files
+                .iter()
+                .find(|(file_name, _)| file_name == name)
+            {
+                Some((_, content)) => ResponseTemplate::new(200).set_body_json(GitHubContent::new(
+                    &format!(".github/workflows/{name}"),
+                    content,
+                )),
+                None => ResponseTemplate::new(404),
+            }
+        },
+        "GET",
+        format!("^/repos/{repo_name}/contents/\\.github/workflows/(.+)$"),
+    )
+    .mount(mock_server)
+    .await;
 }
 
 #[derive(serde::Deserialize)]
@@ -699,6 +1155,7 @@ async fn mock_check_runs(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
                     name: String,
                     head_sha: String,
                     status: String,
+                    conclusion: Option<String>,
                     output: CheckRunRequestOutput,
                     external_id: String,
                 }
@@ -710,7 +1167,7 @@ async fn mock_check_runs(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
                     name: data.name.clone(),
                     head_sha: data.head_sha.clone(),
                     status: data.status.clone(),
-                    conclusion: None,
+                    conclusion: data.conclusion.clone(),
                     title: data.output.title.clone(),
                     summary: data.output.summary.clone(),
                     text: data.output.text.clone().unwrap_or_default(),
@@ -733,7 +1190,7 @@ async fn mock_check_runs(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
                     html_url: format!("https://github.com/{repo_name}/runs/{check_run_id}"),
                     details_url: None,
                     status: data.status,
-                    conclusion: None,
+                    conclusion: data.conclusion,
                     started_at: time,
                     completed_at: None,
                     external_id: data.external_id,
@@ -765,8 +1222,9 @@ async fn mock_check_runs(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
             move |request: &Request| {
                 #[derive(serde::Deserialize)]
                 struct UpdateCheckRunRequest {
-                    status: String,
+                    status: Option<String>,
                     conclusion: Option<String>,
+                    output: Option<CheckRunRequestOutput>,
                 }
 
                 let path = request.url.path();
@@ -780,7 +1238,14 @@ async fn mock_check_runs(repo: Arc<Mutex<Repo>>, mock_server: &MockServer) {
                 let time = Utc::now().to_rfc3339();
 
                 let mut repo = repo.lock();
-                repo.update_check_run(check_run_id, data.status.clone(), data.conclusion.clone());
+                if let Some(status) = data.status {
+                    repo.update_check_run(check_run_id, status, data.conclusion);
+                }
+                if let Some(output) = data.output {
+                    let check_run = &mut repo.check_runs[check_run_id as usize];
+                    check_run.title = output.title;
+                    check_run.summary = output.summary;
+                }
 
                 let check_run = &repo.check_runs[check_run_id as usize];
 
@@ -849,6 +1314,22 @@ impl From<GithubRepoName> for GitHubRepository {
     }
 }
 
+/// https://docs.github.com/en/webhooks/webhook-events-and-payloads#repository
+#[derive(Serialize)]
+pub struct GitHubRepositoryEventPayload {
+    action: String,
+    repository: GitHubRepository,
+}
+
+impl GitHubRepositoryEventPayload {
+    pub fn new(repository: GithubRepoName, action: &str) -> Self {
+        Self {
+            action: action.to_string(),
+            repository: repository.into(),
+        }
+    }
+}
+
 /// Represents a file in a GitHub repository
 /// returns type for the `GET /repos/{owner}/{repo}/contents/{path}` endpoint
 #[derive(Serialize)]
@@ -920,6 +1401,20 @@ struct GitHubWorkflowJobs {
     jobs: Vec<GitHubWorkflowJob>,
 }
 
+#[derive(Serialize)]
+struct GitHubWorkflowArtifacts {
+    total_count: u64,
+    artifacts: Vec<GitHubWorkflowArtifact>,
+}
+
+#[derive(Serialize)]
+struct GitHubWorkflowArtifact {
+    id: u64,
+    name: String,
+    size_in_bytes: u64,
+    expired: bool,
+}
+
 #[derive(Serialize)]
 struct GitHubWorkflowJob {
     id: JobId,
@@ -51,17 +51,26 @@ pub struct WorkflowJob {
     pub status: WorkflowStatus,
 }
 
+/// An artifact uploaded by a workflow run.
+#[derive(Clone)]
+pub struct WorkflowArtifact {
+    pub id: u64,
+    pub name: String,
+    pub size: u64,
+}
+
 #[derive(Clone)]
 pub struct WorkflowRunData {
     pub repository: GithubRepoName,
-    name: String,
+    pub name: String,
     pub run_id: RunId,
     pub check_suite_id: CheckSuiteId,
     pub head_branch: String,
     pub jobs: Vec<WorkflowJob>,
-    head_sha: String,
+    pub artifacts: Vec<WorkflowArtifact>,
+    pub head_sha: String,
     /// How long did the workflow run for?
-    duration: Duration,
+    pub duration: Duration,
 }
 
 impl WorkflowRunData {
@@ -73,6 +82,7 @@ impl WorkflowRunData {
             check_suite_id: CheckSuiteId(1),
             head_branch: branch.get_name().to_string(),
             jobs: vec![],
+            artifacts: vec![],
             head_sha: branch.get_sha().to_string(),
             duration: Duration::from_secs(3600),
         }
@@ -95,6 +105,10 @@ impl WorkflowRunData {
     pub fn with_duration(self, duration: Duration) -> Self {
         Self { duration, ..self }
     }
+
+    pub fn with_artifacts(self, artifacts: Vec<WorkflowArtifact>) -> Self {
+        Self { artifacts, ..self }
+    }
 }
 
 impl From<Branch> for WorkflowRunData {
@@ -228,3 +242,37 @@ struct GitHubCommitAuthor {
     name: String,
     email: String,
 }
+
+/// Payload of a commit status reported by an external CI system.
+#[derive(Serialize)]
+pub struct GitHubCommitStatusEventPayload {
+    sha: String,
+    context: String,
+    state: String,
+    target_url: Option<String>,
+    branches: Vec<GitHubCommitStatusBranch>,
+    repository: GitHubRepository,
+}
+
+impl GitHubCommitStatusEventPayload {
+    pub fn new(branch: &Branch, context: &str, state: &str) -> Self {
+        Self {
+            sha: branch.get_sha().to_string(),
+            context: context.to_string(),
+            state: state.to_string(),
+            target_url: Some(format!(
+                "https://ci.example.com/{context}/{}",
+                branch.get_sha()
+            )),
+            branches: vec![GitHubCommitStatusBranch {
+                name: branch.get_name().to_string(),
+            }],
+            repository: default_repo_name().into(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct GitHubCommitStatusBranch {
+    name: String,
+}
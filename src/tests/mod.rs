@@ -1,3 +1,4 @@
+mod database;
 mod io;
 mod mocks;
 mod util;
@@ -17,44 +18,55 @@ use tokio::sync::mpsc::Sender;
 use tokio::task::{JoinError, JoinHandle};
 use tower::Service;
 
+use crate::bors::event::BorsEvent;
+use crate::bors::hooks::CommandHook;
 use crate::bors::merge_queue::MergeQueueSender;
-use crate::bors::mergeability_queue::MergeabilityQueueSender;
 use crate::bors::{
-    CommandPrefix, PullRequestStatus, RollupMode, WAIT_FOR_MERGE_QUEUE,
-    WAIT_FOR_MERGEABILITY_STATUS_REFRESH, WAIT_FOR_PR_STATUS_REFRESH,
-    WAIT_FOR_REFRESH_PENDING_BUILDS, WAIT_FOR_WORKFLOW_COMPLETED, WAIT_FOR_WORKFLOW_STARTED,
+    CommandPrefix, PullRequestStatus, RollupMode, WAIT_FOR_APP_CLIENT_CHANGE,
+    WAIT_FOR_BUILD_RESULT_REPORTED, WAIT_FOR_COMMIT_STATUS, WAIT_FOR_CONFIG_REFRESH,
+    WAIT_FOR_DEAD_LETTER_REPLAY, WAIT_FOR_MERGE_QUEUE, WAIT_FOR_MERGEABILITY_STATUS_REFRESH,
+    WAIT_FOR_OUTBOX_DELIVERY, WAIT_FOR_PENDING_BUILDS_RECONCILE, WAIT_FOR_PERMISSIONS_REFRESH,
+    WAIT_FOR_PR_STATUS_REFRESH, WAIT_FOR_REFRESH_PENDING_BUILDS, WAIT_FOR_TRY_ARTIFACT_CLEANUP,
+    WAIT_FOR_WORKFLOW_COMPLETED, WAIT_FOR_WORKFLOW_STARTED, WAIT_FOR_ZOMBIE_WORKFLOWS_CANCEL,
 };
 use crate::database::{
     BuildModel, BuildStatus, DelegatedPermission, MergeableState, OctocrabMergeableState,
     PullRequestModel, WorkflowStatus,
 };
-use crate::github::{GithubRepoName, PullRequestNumber};
+use crate::github::{GithubRepoName, PullRequestNumber, WebhookPayload};
 use crate::{
-    BorsContext, BorsGlobalEvent, BorsProcess, CommandParser, PgDbClient, ServerState, TreeState,
-    WebhookSecret, create_app, create_bors_process, load_repositories,
+    ApiToken, BorsContext, BorsGlobalEvent, BorsProcess, CommandParser, ListenerConfig, PgDbClient,
+    Role, ServerState, TreeState, WebhookSecret, create_bors_process, create_listener_app,
+    load_repositories,
 };
 
-use crate::tests::mocks::comment::GitHubIssueCommentEventPayload;
+use crate::tests::mocks::comment::{
+    GitHubIssueCommentEventPayload, review_comment_event_payload, review_event_payload,
+};
 use crate::tests::mocks::pull_request::{
     GitHubPullRequestEventPayload, GitHubPushEventPayload, PrIdentifier, PullRequest,
     PullRequestChangeEvent,
 };
+use crate::tests::mocks::repository::GitHubRepositoryEventPayload;
 use crate::tests::mocks::workflow::{
-    GitHubWorkflowEventPayload, TestWorkflowStatus, WorkflowEventKind,
+    GitHubCommitStatusEventPayload, GitHubWorkflowEventPayload, TestWorkflowStatus,
+    WorkflowEventKind,
 };
 
 // Public re-exports for use in tests
 use crate::github::api::client::HideCommentReason;
+pub use database::DatabaseSnapshot;
 pub use io::load_test_file;
 pub use mocks::ExternalHttpMock;
 pub use mocks::GitHubState;
 pub use mocks::comment::Comment;
 pub use mocks::permissions::Permissions;
 pub use mocks::repository::{
-    Branch, BranchPushBehaviour, BranchPushError, Repo, default_branch_name, default_repo_name,
+    Branch, BranchPushBehaviour, BranchPushError, CommentReaction, Repo, default_branch_name,
+    default_repo_name,
 };
 pub use mocks::user::User;
-pub use mocks::workflow::{WorkflowEvent, WorkflowJob, WorkflowRunData};
+pub use mocks::workflow::{WorkflowArtifact, WorkflowEvent, WorkflowJob, WorkflowRunData};
 pub use util::TestSyncMarker;
 pub use webhook::{TEST_WEBHOOK_SECRET, create_webhook_request};
 
@@ -62,6 +74,15 @@ pub use webhook::{TEST_WEBHOOK_SECRET, create_webhook_request};
 /// You can increase this if you want to do interactive debugging.
 const TEST_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// Token accepted by the build result API in tests.
+pub const TEST_API_TOKEN: &str = "test-api-token";
+/// Token accepted by the build result API in tests, only for builds of the default repository.
+pub const TEST_REPOSITORY_API_TOKEN: &str = "test-repository-api-token";
+
+/// Tokens accepted by the administrative API in tests.
+pub const TEST_ADMIN_TOKEN: &str = "test-admin-token";
+pub const TEST_OPERATOR_TOKEN: &str = "test-operator-token";
+
 pub fn default_cmd_prefix() -> CommandPrefix {
     "@bors".to_string().into()
 }
@@ -69,6 +90,7 @@ pub fn default_cmd_prefix() -> CommandPrefix {
 pub struct BorsBuilder {
     github: GitHubState,
     pool: PgPool,
+    command_hooks: Vec<Arc<dyn CommandHook>>,
 }
 
 impl BorsBuilder {
@@ -76,6 +98,7 @@ impl BorsBuilder {
         Self {
             pool,
             github: Default::default(),
+            command_hooks: vec![],
         }
     }
 
@@ -83,6 +106,11 @@ impl BorsBuilder {
         Self { github, ..self }
     }
 
+    pub fn command_hook(mut self, hook: Arc<dyn CommandHook>) -> Self {
+        self.command_hooks.push(hook);
+        self
+    }
+
     /// This closure is used to ensure that the test has to return `BorsTester`
     /// to us, so that we can call `finish()` on it. Without that call, we couldn't
     /// ensure that some async task within the bors process hasn't crashed.
@@ -92,7 +120,8 @@ impl BorsBuilder {
     ) -> GitHubState {
         // We return `tester` and `bors` separately, so that we can finish `bors`
         // even if `f` returns an error or times out, for better error propagation.
-        let (mut tester, mut bors) = BorsTester::new(self.pool, self.github).await;
+        let (mut tester, mut bors) =
+            BorsTester::new(self.pool, self.github, self.command_hooks).await;
 
         tokio::select! {
             // If the service ends sooner than the test itself, then the service has panicked.
@@ -147,26 +176,38 @@ pub async fn run_test<F: AsyncFnOnce(&mut BorsTester) -> anyhow::Result<()>>(
 /// send channels for the bors process, which should stop its async task.
 pub struct BorsTester {
     app: Router,
+    server_state: Arc<ServerState>,
     http_mock: ExternalHttpMock,
     github: Arc<tokio::sync::Mutex<GitHubState>>,
+    pool: PgPool,
     db: Arc<PgDbClient>,
-    mergeability_queue_tx: MergeabilityQueueSender,
     merge_queue_tx: MergeQueueSender,
     // Sender for bors global events
     global_tx: Sender<BorsGlobalEvent>,
     // When this field is false, no webhooks should be generated from BorsTester methods
     webhooks_active: bool,
+    // When this field is true, generated webhooks are stored in the dead-letter queue instead of
+    // being handled
+    dead_letter_webhooks: bool,
 }
 
 impl BorsTester {
-    async fn new(pool: PgPool, github: GitHubState) -> (Self, JoinHandle<()>) {
+    async fn new(
+        pool: PgPool,
+        github: GitHubState,
+        command_hooks: Vec<Arc<dyn CommandHook>>,
+    ) -> (Self, JoinHandle<()>) {
         let github = Arc::new(tokio::sync::Mutex::new(github));
         let mock = ExternalHttpMock::start(github.clone()).await;
-        let db = Arc::new(PgDbClient::new(pool));
+        let db = Arc::new(PgDbClient::new(pool.clone()));
 
-        let loaded_repos = load_repositories(&mock.github_client(), &mock.team_api_client())
-            .await
-            .unwrap();
+        let loaded_repos = load_repositories(
+            &mock.github_client(),
+            &mock.github_urls(),
+            &mock.team_api_client(),
+        )
+        .await
+        .unwrap();
         let mut repos = HashMap::default();
         for (name, repo) in loaded_repos {
             let repo = repo.unwrap();
@@ -179,46 +220,62 @@ impl BorsTester {
             }
         }
 
-        let ctx = BorsContext::new(
+        let mut ctx = BorsContext::new(
             CommandParser::new("@bors".to_string().into()),
             db.clone(),
             repos.clone(),
+            mock.team_api_client(),
             "https://test.com/bors",
-        );
+        )
+        .with_github_urls(mock.github_urls())
+        .with_refresh_jitter(Duration::ZERO);
+        for hook in command_hooks {
+            ctx = ctx.with_command_hook(hook);
+        }
 
         let BorsProcess {
             repository_tx,
             global_tx,
-            mergeability_queue_tx,
+            mergeability_queue_tx: _,
             merge_queue_tx,
             bors_process,
-        } = create_bors_process(
-            ctx,
-            mock.github_client(),
-            mock.team_api_client(),
-            chrono::Duration::seconds(1),
-        );
+        } = create_bors_process(ctx, mock.github_client(), chrono::Duration::seconds(1));
 
         let state = ServerState::new(
             repository_tx,
             global_tx.clone(),
             WebhookSecret::new(TEST_WEBHOOK_SECRET.to_string()),
+            Some(ApiToken::new(TEST_API_TOKEN.to_string())),
+            HashMap::from([(
+                default_repo_name(),
+                ApiToken::new(TEST_REPOSITORY_API_TOKEN.to_string()),
+            )]),
+            vec![
+                (ApiToken::new(TEST_ADMIN_TOKEN.to_string()), Role::Admin),
+                (
+                    ApiToken::new(TEST_OPERATOR_TOKEN.to_string()),
+                    Role::Operator,
+                ),
+            ],
             repos.clone(),
             db.clone(),
             default_cmd_prefix(),
         );
-        let app = create_app(state);
+        let server_state = Arc::new(state);
+        let app = create_listener_app(server_state.clone(), &ListenerConfig::default());
         let bors = tokio::spawn(bors_process);
         (
             Self {
                 app,
+                server_state,
                 http_mock: mock,
                 github,
+                pool,
                 db,
-                mergeability_queue_tx,
                 merge_queue_tx,
                 global_tx,
                 webhooks_active: true,
+                dead_letter_webhooks: false,
             },
             bors,
         )
@@ -229,6 +286,21 @@ impl BorsTester {
         self.db.clone()
     }
 
+    /// Takes a snapshot of the whole database, which can be restored later with
+    /// [`BorsTester::restore_database`].
+    pub async fn snapshot_database(&self) -> anyhow::Result<DatabaseSnapshot> {
+        DatabaseSnapshot::take(&self.pool).await
+    }
+
+    /// Restores the database to the state of a previous snapshot, as if bors has crashed at the
+    /// time when the snapshot was taken. The GitHub state is not changed.
+    ///
+    /// Only the database is restored, so use e.g. [`BorsTester::reconcile_pending_builds`]
+    /// afterwards to perform the recovery that bors performs when it is started again.
+    pub async fn restore_database(&self, snapshot: &DatabaseSnapshot) -> anyhow::Result<()> {
+        snapshot.restore(&self.pool).await
+    }
+
     pub async fn default_repo(&self) -> Arc<Mutex<Repo>> {
         self.get_repo(&default_repo_name()).await
     }
@@ -290,6 +362,15 @@ impl BorsTester {
         .await
     }
 
+    /// Wait until bors adds the given number of reactions to comments in the default repository
+    /// and return them.
+    pub async fn wait_for_reactions(&self, count: usize) -> anyhow::Result<Vec<CommentReaction>> {
+        let repo = self.default_repo().await;
+        self.wait_for(|| async { Ok(repo.lock().comment_reactions.len() >= count) })
+            .await?;
+        Ok(repo.lock().comment_reactions.clone())
+    }
+
     /// Creates a branch and returns a **copy** of it.
     pub async fn create_branch(&mut self, name: &str) -> Branch {
         let repo = self
@@ -354,6 +435,48 @@ impl BorsTester {
             .await
     }
 
+    /// Archives the default repository, and waits until bors has deactivated it.
+    pub async fn archive_repo(&mut self) -> anyhow::Result<()> {
+        self.send_webhook(
+            "repository",
+            GitHubRepositoryEventPayload::new(default_repo_name(), "archived"),
+        )
+        .await?;
+        self.wait_for(|| async {
+            let repo = self.db.repo_db(&default_repo_name()).await?;
+            let pending_builds = self.db.get_pending_builds(&default_repo_name()).await?;
+            Ok(repo.is_some_and(|repo| !repo.is_active()) && pending_builds.is_empty())
+        })
+        .await
+    }
+
+    /// Unarchives the default repository, and waits until bors has reactivated it.
+    pub async fn unarchive_repo(&mut self) -> anyhow::Result<()> {
+        self.send_webhook(
+            "repository",
+            GitHubRepositoryEventPayload::new(default_repo_name(), "unarchived"),
+        )
+        .await?;
+        self.wait_for(|| async {
+            let repo = self.db.repo_db(&default_repo_name()).await?;
+            Ok(repo.is_some_and(|repo| repo.is_active()))
+        })
+        .await
+    }
+
+    /// Installs or uninstalls the app in the given repository, and notifies bors that the
+    /// repositories of its installation have changed.
+    pub async fn set_repo_installed(
+        &mut self,
+        repo: &GithubRepoName,
+        installed: bool,
+    ) -> anyhow::Result<()> {
+        self.modify_repo(repo, |repo| repo.installed = installed)
+            .await;
+        self.send_webhook("installation_repositories", serde_json::json!({}))
+            .await
+    }
+
     pub async fn try_branch(&self) -> Branch {
         self.get_branch_copy("automation/bors/try").await
     }
@@ -425,6 +548,137 @@ impl BorsTester {
         .unwrap();
     }
 
+    pub async fn reconcile_pending_builds(&self) {
+        // Wait until the reconciliation is fully handled
+        wait_for_marker(
+            async || {
+                self.global_tx
+                    .send(BorsGlobalEvent::ReconcilePendingBuilds)
+                    .await
+                    .unwrap();
+                Ok(())
+            },
+            &WAIT_FOR_PENDING_BUILDS_RECONCILE,
+        )
+        .await
+        .unwrap();
+    }
+
+    pub async fn cancel_zombie_workflows(&self) {
+        // Wait until the cancellation is fully handled
+        wait_for_marker(
+            async || {
+                self.global_tx
+                    .send(BorsGlobalEvent::CancelZombieWorkflows)
+                    .await
+                    .unwrap();
+                Ok(())
+            },
+            &WAIT_FOR_ZOMBIE_WORKFLOWS_CANCEL,
+        )
+        .await
+        .unwrap();
+    }
+
+    pub async fn clean_up_try_artifacts(&self) {
+        // Wait until the clean-up is fully handled
+        wait_for_marker(
+            async || {
+                self.global_tx
+                    .send(BorsGlobalEvent::CleanUpTryArtifacts)
+                    .await
+                    .unwrap();
+                Ok(())
+            },
+            &WAIT_FOR_TRY_ARTIFACT_CLEANUP,
+        )
+        .await
+        .unwrap();
+    }
+
+    pub async fn deliver_outbox(&self) {
+        // Wait until the delivery is fully handled
+        wait_for_marker(
+            async || {
+                self.global_tx
+                    .send(BorsGlobalEvent::DeliverOutbox)
+                    .await
+                    .unwrap();
+                Ok(())
+            },
+            &WAIT_FOR_OUTBOX_DELIVERY,
+        )
+        .await
+        .unwrap();
+    }
+
+    pub async fn replay_dead_letters(&self) {
+        // Wait until the replay is fully handled
+        wait_for_marker(
+            async || {
+                self.global_tx
+                    .send(BorsGlobalEvent::ReplayDeadLetters)
+                    .await
+                    .unwrap();
+                Ok(())
+            },
+            &WAIT_FOR_DEAD_LETTER_REPLAY,
+        )
+        .await
+        .unwrap();
+    }
+
+    /// Replaces the GitHub App client, as if its private key was rotated.
+    pub async fn change_app_client(&self) {
+        // Wait until the repositories are reloaded with the new client
+        wait_for_marker(
+            async || {
+                self.global_tx
+                    .send(BorsGlobalEvent::AppClientChanged(Box::new(
+                        self.http_mock.github_client(),
+                    )))
+                    .await
+                    .unwrap();
+                Ok(())
+            },
+            &WAIT_FOR_APP_CLIENT_CHANGE,
+        )
+        .await
+        .unwrap();
+    }
+
+    pub async fn refresh_config(&self) {
+        // Wait until the refresh is fully handled
+        wait_for_marker(
+            async || {
+                self.global_tx
+                    .send(BorsGlobalEvent::RefreshConfig)
+                    .await
+                    .unwrap();
+                Ok(())
+            },
+            &WAIT_FOR_CONFIG_REFRESH,
+        )
+        .await
+        .unwrap();
+    }
+
+    pub async fn refresh_permissions(&self) {
+        // Wait until the refresh is fully handled
+        wait_for_marker(
+            async || {
+                self.global_tx
+                    .send(BorsGlobalEvent::RefreshPermissions)
+                    .await
+                    .unwrap();
+                Ok(())
+            },
+            &WAIT_FOR_PERMISSIONS_REFRESH,
+        )
+        .await
+        .unwrap();
+    }
+
     pub async fn update_mergeability_status(&self) {
         // Wait until the refresh is fully handled
         wait_for_marker(
@@ -499,6 +753,22 @@ impl BorsTester {
         wait_for_marker(async || self.webhook_workflow(event).await, marker).await
     }
 
+    /// Reports a commit status of an external CI system for the current commit of the given
+    /// branch, and waits until it has been handled by bors.
+    pub async fn commit_status(
+        &mut self,
+        branch: Branch,
+        context: &str,
+        state: &str,
+    ) -> anyhow::Result<()> {
+        let payload = GitHubCommitStatusEventPayload::new(&branch, context, state);
+        wait_for_marker(
+            async || self.send_webhook("status", &payload).await,
+            &WAIT_FOR_COMMIT_STATUS,
+        )
+        .await
+    }
+
     /// Start a workflow and wait until the workflow has been handled by bors.
     pub async fn workflow_start<W: Into<WorkflowRunData>>(
         &mut self,
@@ -671,14 +941,15 @@ impl BorsTester {
             let mut repo = repo.lock();
             let pr = repo.get_pr_mut(id.number);
             let base_before = pr.base_branch.clone();
+            let title_before = pr.title.clone();
+            let description_before = pr.description.clone();
             func(pr);
 
-            let changes = if base_before != pr.base_branch {
-                Some(PullRequestChangeEvent {
-                    from_base_sha: Some(base_before.get_sha().to_string()),
-                })
-            } else {
-                None
+            let changes = PullRequestChangeEvent {
+                from_base_sha: (base_before != pr.base_branch)
+                    .then(|| base_before.get_sha().to_string()),
+                from_title: (title_before != pr.title).then_some(title_before),
+                from_body: (description_before != pr.description).then_some(description_before),
             };
             (pr.clone(), changes)
         };
@@ -698,7 +969,9 @@ impl BorsTester {
                 .pull_requests
                 .get_mut(&id.number)
                 .expect("PR must be initialized before pushing to it");
-            pr.head_sha = format!("pr-{}-commit-{counter}", id.number);
+            let head_sha = format!("pr-{}-commit-{counter}", id.number);
+            let previous_head = std::mem::replace(&mut pr.head_sha, head_sha);
+            pr.previous_commits.push(previous_head);
             pr.mergeable_state = OctocrabMergeableState::Unknown;
             pr.clone()
         };
@@ -710,6 +983,54 @@ impl BorsTester {
         .await
     }
 
+    /// Push a commit that merges the base branch into the PR.
+    pub async fn merge_base_into_pr<Id: Into<PrIdentifier>>(
+        &mut self,
+        id: Id,
+    ) -> anyhow::Result<()> {
+        let id = id.into();
+        {
+            let repo = self.github.lock().await.get_repo(&id.repo);
+            let mut repo = repo.lock();
+            let pr = repo
+                .pull_requests
+                .get(&id.number)
+                .expect("PR must be initialized before pushing to it");
+            let base_name = pr.base_branch.get_name().to_string();
+            let head_sha = pr.head_sha.clone();
+            let base_sha = repo
+                .get_branch_by_name(&base_name)
+                .expect("Base branch of the PR must exist")
+                .get_sha()
+                .to_string();
+            let parents = vec![head_sha.clone(), base_sha];
+            let new_head_sha = format!("pr-{}-commit-{}", id.number, repo.pr_push_counter + 1);
+            repo.merge_commit_parents
+                .insert(new_head_sha.clone(), parents);
+            // Merging the base branch does not change the diff of the PR
+            repo.same_diff_commits.insert(new_head_sha, head_sha);
+        }
+        self.push_to_pr(id).await
+    }
+
+    /// Push a commit that rebases the PR without changing its diff.
+    pub async fn rebase_pr<Id: Into<PrIdentifier>>(&mut self, id: Id) -> anyhow::Result<()> {
+        let id = id.into();
+        {
+            let repo = self.github.lock().await.get_repo(&id.repo);
+            let mut repo = repo.lock();
+            let head_sha = repo
+                .pull_requests
+                .get(&id.number)
+                .expect("PR must be initialized before pushing to it")
+                .head_sha
+                .clone();
+            let new_head_sha = format!("pr-{}-commit-{}", id.number, repo.pr_push_counter + 1);
+            repo.same_diff_commits.insert(new_head_sha, head_sha);
+        }
+        self.push_to_pr(id).await
+    }
+
     pub async fn assign_pr<Id: Into<PrIdentifier>>(
         &mut self,
         id: Id,
@@ -740,6 +1061,36 @@ impl BorsTester {
         .await
     }
 
+    pub async fn add_label<Id: Into<PrIdentifier>>(
+        &mut self,
+        id: Id,
+        label: &str,
+    ) -> anyhow::Result<()> {
+        let pr = self
+            .modify_pr_state(id, |pr| pr.labels.push(label.to_string()))
+            .await;
+        self.send_webhook(
+            "pull_request",
+            GitHubPullRequestEventPayload::new(pr, "labeled", None),
+        )
+        .await
+    }
+
+    pub async fn remove_label<Id: Into<PrIdentifier>>(
+        &mut self,
+        id: Id,
+        label: &str,
+    ) -> anyhow::Result<()> {
+        let pr = self
+            .modify_pr_state(id, |pr| pr.labels.retain(|l| l != label))
+            .await;
+        self.send_webhook(
+            "pull_request",
+            GitHubPullRequestEventPayload::new(pr, "unlabeled", None),
+        )
+        .await
+    }
+
     /// Starts an auto build, with the expectation that it will start testing the given PR.
     pub async fn start_auto_build<Id: Into<PrIdentifier>>(&mut self, id: Id) -> anyhow::Result<()> {
         let id = id.into();
@@ -897,6 +1248,37 @@ impl BorsTester {
         result
     }
 
+    /// Store webhooks sent while `func` is executing in the dead-letter queue, to emulate a
+    /// situation where their handling has failed.
+    pub async fn with_dead_lettered_webhooks<T, F>(&mut self, func: F) -> T
+    where
+        F: AsyncFnOnce(&mut BorsTester) -> T,
+    {
+        let orig_dead_letter = self.dead_letter_webhooks;
+        self.dead_letter_webhooks = true;
+        let result = func(self).await;
+        self.dead_letter_webhooks = orig_dead_letter;
+        result
+    }
+
+    /// Submit a GitHub review on the current head commit of the given PR.
+    /// `state` is the state of the review, e.g. `approved` or `changes_requested`.
+    pub async fn submit_review<Id: Into<PrIdentifier>>(
+        &mut self,
+        id: Id,
+        author: User,
+        state: &str,
+        body: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let id = id.into();
+        let head_sha = self.get_pr_copy(id.clone()).await.get_gh_pr().head_sha;
+        self.send_webhook(
+            "pull_request_review",
+            review_event_payload(id, author, state, &head_sha, body),
+        )
+        .await
+    }
+
     /// Get a GitHub comment that might have been modified by API calls from bors.
     pub async fn get_comment_by_node_id(&self, node_id: &str) -> Option<Comment> {
         self.github
@@ -908,6 +1290,14 @@ impl BorsTester {
 
     //-- Internal helper functions --/
     async fn webhook_comment(&mut self, comment: Comment) -> anyhow::Result<()> {
+        if comment.review_thread.is_some() {
+            return self
+                .send_webhook(
+                    "pull_request_review_comment",
+                    review_comment_event_payload(comment),
+                )
+                .await;
+        }
         self.send_webhook(
             "issue_comment",
             // The Box is here to prevent a stack overflow in debug mode
@@ -924,21 +1314,143 @@ impl BorsTester {
     async fn pull_request_edited(
         &mut self,
         pr: PullRequest,
-        changes: Option<PullRequestChangeEvent>,
+        changes: PullRequestChangeEvent,
     ) -> anyhow::Result<()> {
         self.send_webhook(
             "pull_request",
-            GitHubPullRequestEventPayload::new(pr, "edited", Some(changes.unwrap_or_default())),
+            GitHubPullRequestEventPayload::new(pr, "edited", Some(changes)),
         )
         .await
     }
 
+    /// Sends a request to the given path of the bors web server.
+    /// Returns the status code and the body of the response.
+    pub async fn web_request(
+        &mut self,
+        method: http::Method,
+        path: &str,
+        body: Option<String>,
+    ) -> anyhow::Result<(http::StatusCode, String)> {
+        let request = http::Request::builder()
+            .method(method)
+            .uri(path)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(axum::body::Body::from(body.unwrap_or_default()))?;
+        self.send_web_request(request).await
+    }
+
+    /// Sends a web request to a server listener with the given configuration, instead of the
+    /// default listener that serves all routes.
+    pub async fn listener_web_request(
+        &mut self,
+        listener: &ListenerConfig,
+        method: http::Method,
+        path: &str,
+    ) -> anyhow::Result<(http::StatusCode, String, http::HeaderMap)> {
+        let request = http::Request::builder()
+            .method(method)
+            .uri(path)
+            .body(axum::body::Body::empty())?;
+        let response = create_listener_app(self.server_state.clone(), listener)
+            .call(request)
+            .await
+            .context("Cannot send web request")?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = axum::body::to_bytes(response.into_body(), 10 * 1024 * 1024).await?;
+        Ok((status, String::from_utf8(body.to_vec())?, headers))
+    }
+
+    /// Sends a web request authenticated with the given bearer `token`.
+    pub async fn authenticated_web_request(
+        &mut self,
+        method: http::Method,
+        path: &str,
+        token: &str,
+        body: Option<String>,
+    ) -> anyhow::Result<(http::StatusCode, String)> {
+        let request = http::Request::builder()
+            .method(method)
+            .uri(path)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(http::header::AUTHORIZATION, format!("Bearer {token}"))
+            .body(axum::body::Body::from(body.unwrap_or_default()))?;
+        self.send_web_request(request).await
+    }
+
+    /// Reports the result of a build through the build result API.
+    /// If the result was accepted, waits until it has been handled by bors.
+    pub async fn report_build_result(
+        &mut self,
+        build_id: i32,
+        token: &str,
+        body: &str,
+    ) -> anyhow::Result<http::StatusCode> {
+        let request = http::Request::builder()
+            .method(http::Method::POST)
+            .uri(format!("/api/v1/builds/{build_id}/result"))
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(http::header::AUTHORIZATION, format!("Bearer {token}"))
+            .body(axum::body::Body::from(body.to_string()))?;
+
+        WAIT_FOR_BUILD_RESULT_REPORTED.drain().await;
+        let (status, _) = self.send_web_request(request).await?;
+        if status.is_success() {
+            tokio::time::timeout(
+                Duration::from_secs(15),
+                WAIT_FOR_BUILD_RESULT_REPORTED.sync(),
+            )
+            .await
+            .map_err(|_| anyhow::anyhow!("Timed out waiting for the build result"))?;
+        }
+        Ok(status)
+    }
+
+    async fn send_web_request(
+        &mut self,
+        request: http::Request<axum::body::Body>,
+    ) -> anyhow::Result<(http::StatusCode, String)> {
+        let response = self
+            .app
+            .call(request)
+            .await
+            .context("Cannot send web request")?;
+        let status = response.status();
+        let body_text = String::from_utf8(
+            axum::body::to_bytes(response.into_body(), 10 * 1024 * 1024)
+                .await?
+                .to_vec(),
+        )?;
+        Ok((status, body_text))
+    }
+
     async fn send_webhook<S: Serialize>(&mut self, event: &str, content: S) -> anyhow::Result<()> {
         if !self.webhooks_active {
             return Ok(());
         }
 
         let serialized = serde_json::to_string(&content)?;
+        if self.dead_letter_webhooks {
+            let webhook = WebhookPayload {
+                event_type: event.to_string(),
+                body: serialized,
+            };
+            let Some(BorsEvent::Repository(parsed)) = webhook.parse()? else {
+                return Err(anyhow::anyhow!(
+                    "Only repository webhooks can be dead-lettered"
+                ));
+            };
+            self.db
+                .insert_dead_letter(
+                    parsed.repository(),
+                    &webhook.event_type,
+                    &webhook.body,
+                    "Handler failed",
+                )
+                .await?;
+            return Ok(());
+        }
+
         let webhook = create_webhook_request(event, &serialized);
         let response = self
             .app
@@ -961,11 +1473,10 @@ impl BorsTester {
     }
 
     async fn finish(self, bors: JoinHandle<()>) -> anyhow::Result<GitHubState> {
-        // Make sure that the event channel senders are closed
+        // Make sure that the event channel senders are closed, which shuts down the bors service
         drop(self.app);
+        drop(self.server_state);
         drop(self.global_tx);
-        self.merge_queue_tx.shutdown();
-        self.mergeability_queue_tx.shutdown();
         // Wait until all events are handled in the bors service
         match tokio::time::timeout(Duration::from_secs(5), bors).await {
             Ok(Ok(_)) => {}
@@ -1066,6 +1577,13 @@ impl PullRequestProxy {
         self.expect_added_labels(&["approved"])
     }
 
+    /// Checks the approvers of the PR, without checking its labels.
+    #[track_caller]
+    pub fn expect_approvers(&self, approvers: &[&str]) -> &Self {
+        assert_eq!(self.require_db_pr().approvers(), approvers);
+        self
+    }
+
     #[track_caller]
     pub fn expect_unapproved(&self) -> &Self {
         assert!(!self.require_db_pr().is_approved());
@@ -0,0 +1,233 @@
+//! Cron expressions, used to schedule recurring events such as maintenance windows.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, DurationRound, Timelike, Utc};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A cron expression with five fields (`<minute> <hour> <day of month> <month> <day of week>`),
+/// evaluated in UTC.
+///
+/// Each field is `*`, a number, a range (`1-5`), a step (`*/15` or `0-30/10`) or a
+/// comma-separated list of these. Days of the week are numbered from 0 (Sunday) to 7 (also
+/// Sunday). As in cron, if both the day of the month and the day of the week are restricted, a
+/// day matches if it matches either of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Whether the day of the month field is something else than `*`.
+    day_of_month_restricted: bool,
+    /// Whether the day of the week field is something else than `*`.
+    day_of_week_restricted: bool,
+}
+
+impl CronSchedule {
+    /// Does the schedule fire at the minute of the given time?
+    pub fn matches(&self, time: DateTime<Utc>) -> bool {
+        let day_of_month = contains(self.days_of_month, time.day());
+        let day_of_week = contains(self.days_of_week, time.weekday().num_days_from_sunday());
+        let day = if self.day_of_month_restricted && self.day_of_week_restricted {
+            day_of_month || day_of_week
+        } else {
+            day_of_month && day_of_week
+        };
+        day && contains(self.minutes, time.minute())
+            && contains(self.hours, time.hour())
+            && contains(self.months, time.month())
+    }
+
+    /// Returns the latest time at which the schedule fired, if it is not older than `within`.
+    pub fn latest_before(
+        &self,
+        now: DateTime<Utc>,
+        within: chrono::Duration,
+    ) -> Option<DateTime<Utc>> {
+        let mut time = now.duration_trunc(chrono::Duration::minutes(1)).ok()?;
+        while time > now - within {
+            if self.matches(time) {
+                return Some(time);
+            }
+            time -= chrono::Duration::minutes(1);
+        }
+        None
+    }
+}
+
+fn contains(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Parses a single field of a cron expression into a bit set of the allowed values.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut set = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step `{step}`"))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let parse_value = |value: &str| -> Result<u32, String> {
+            value
+                .parse()
+                .ok()
+                .filter(|value| (min..=max).contains(value))
+                .ok_or_else(|| format!("`{value}` is not a number between {min} and {max}"))
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (parse_value(start)?, parse_value(end)?),
+                // `5/10` means every 10th value starting at 5
+                None if step > 1 => (parse_value(range)?, max),
+                None => {
+                    let value = parse_value(range)?;
+                    (value, value)
+                }
+            },
+        };
+        if start > end {
+            return Err(format!("invalid range `{range}`"));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+impl FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+            return Err(format!(
+                "cron expression `{expression}` has to have five fields: <minute> <hour> <day of month> <month> <day of week>"
+            ));
+        };
+        let parse = |field: &str, min: u32, max: u32| {
+            parse_field(field, min, max)
+                .map_err(|error| format!("invalid cron expression `{expression}`: {error}"))
+        };
+        let mut days_of_week_set = parse(days_of_week, 0, 7)?;
+        // Both 0 and 7 are Sunday
+        if contains(days_of_week_set, 7) {
+            days_of_week_set |= 1;
+        }
+        Ok(Self {
+            expression: expression.to_string(),
+            minutes: parse(minutes, 0, 59)?,
+            hours: parse(hours, 0, 23)?,
+            days_of_month: parse(days_of_month, 1, 31)?,
+            months: parse(months, 1, 12)?,
+            days_of_week: days_of_week_set,
+            day_of_month_restricted: days_of_month != "*",
+            day_of_week_restricted: days_of_week != "*",
+        })
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+impl<'de> Deserialize<'de> for CronSchedule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let expression = String::deserialize(deserializer)?;
+        expression.parse().map_err(Error::custom)
+    }
+}
+
+impl Serialize for CronSchedule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.expression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CronSchedule;
+    use chrono::{DateTime, Utc};
+
+    fn time(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn match_weekly_schedule() {
+        // Fridays at 22:00
+        let schedule: CronSchedule = "0 22 * * 5".parse().unwrap();
+        assert!(schedule.matches(time("2025-09-19T22:00:30Z")));
+        assert!(!schedule.matches(time("2025-09-19T22:01:00Z")));
+        assert!(!schedule.matches(time("2025-09-20T22:00:00Z")));
+    }
+
+    #[test]
+    fn match_ranges_steps_and_lists() {
+        let schedule: CronSchedule = "*/15 9-17 * 1,7 1-5".parse().unwrap();
+        assert!(schedule.matches(time("2025-07-01T09:45:00Z")));
+        assert!(!schedule.matches(time("2025-07-01T09:50:00Z")));
+        assert!(!schedule.matches(time("2025-07-01T18:00:00Z")));
+        assert!(!schedule.matches(time("2025-08-01T10:00:00Z")));
+        // Saturday
+        assert!(!schedule.matches(time("2025-07-05T10:00:00Z")));
+    }
+
+    #[test]
+    fn match_sunday_as_seven() {
+        let schedule: CronSchedule = "0 0 * * 7".parse().unwrap();
+        assert!(schedule.matches(time("2025-09-21T00:00:00Z")));
+    }
+
+    #[test]
+    fn match_day_of_month_or_week() {
+        // The first day of the month, or any Monday
+        let schedule: CronSchedule = "0 0 1 * 1".parse().unwrap();
+        assert!(schedule.matches(time("2025-10-01T00:00:00Z")));
+        assert!(schedule.matches(time("2025-09-22T00:00:00Z")));
+        assert!(!schedule.matches(time("2025-09-23T00:00:00Z")));
+    }
+
+    #[test]
+    fn latest_before() {
+        let schedule: CronSchedule = "0 22 * * 5".parse().unwrap();
+        let now = time("2025-09-19T23:30:00Z");
+        assert_eq!(
+            schedule.latest_before(now, chrono::Duration::hours(2)),
+            Some(time("2025-09-19T22:00:00Z"))
+        );
+        assert_eq!(
+            schedule.latest_before(now, chrono::Duration::hours(1)),
+            None
+        );
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for expression in [
+            "",
+            "* * * *",
+            "60 * * * *",
+            "* * 0 * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+        ] {
+            assert!(expression.parse::<CronSchedule>().is_err(), "{expression}");
+        }
+    }
+}
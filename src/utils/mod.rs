@@ -1,3 +1,4 @@
+pub mod cron;
 pub mod logging;
 pub mod sort_queue;
 pub mod text;
@@ -1,36 +1,143 @@
+use std::cmp::Reverse;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
 use crate::bors::RollupMode;
 use crate::database::{BuildStatus, MergeableState, PullRequestModel};
+use crate::github::PullRequestNumber;
 
 /// Sorts pull requests according to merge queue priority rules.
-/// Ordered by pending builds > success builds > approval > mergeability > priority value > rollup > age.
-pub fn sort_queue_prs(mut prs: Vec<PullRequestModel>) -> Vec<PullRequestModel> {
-    prs.sort_by(|a, b| {
-        // 1. Pending builds come first (to block merge queue)
-        get_queue_blocking_priority(a)
-            .cmp(&get_queue_blocking_priority(b))
-            // 2. Compare approval status (approved PRs should come first)
-            .then_with(|| a.is_approved().cmp(&b.is_approved()).reverse())
-            // 3. Compare build status within approval groups
-            .then_with(|| get_status_priority(a).cmp(&get_status_priority(b)))
-            // 4. Compare mergeability state (0 = mergeable, 1 = conflicts/unknown)
-            .then_with(|| get_mergeable_priority(a).cmp(&get_mergeable_priority(b)))
-            // 5. Compare priority numbers (higher priority should come first)
-            .then_with(|| {
-                a.priority
-                    .unwrap_or(0)
-                    .cmp(&b.priority.unwrap_or(0))
-                    .reverse()
-            })
-            // 6. Compare rollup mode (-1 = never/iffy, 0 = maybe, 1 = always)
-            .then_with(|| {
-                get_rollup_priority(a.rollup.as_ref()).cmp(&get_rollup_priority(b.rollup.as_ref()))
-            })
-            // 7. Compare PR numbers (older first)
-            .then_with(|| a.number.cmp(&b.number))
-    });
+/// Ordered by pending builds > held PRs > PRs that are not parked > success builds > approval
+/// > mergeability > priority value > rollup > age.
+///
+/// If `aging_interval` is set, the priority of approved PRs grows with the time that they have
+/// spent in the queue, see [`effective_priority`].
+pub fn sort_queue_prs(
+    prs: Vec<PullRequestModel>,
+    aging_interval: Option<Duration>,
+) -> Vec<PullRequestModel> {
+    trace_queue_order(prs, aging_interval)
+        .into_iter()
+        .map(|(pr, _)| pr)
+        .collect()
+}
+
+/// Sorts pull requests like [`sort_queue_prs`], but also returns the sort key that was computed
+/// for each PR, so that the resulting order can be explained.
+pub fn trace_queue_order(
+    prs: Vec<PullRequestModel>,
+    aging_interval: Option<Duration>,
+) -> Vec<(PullRequestModel, QueueSortKey)> {
+    let now = Utc::now();
+    let mut prs: Vec<_> = prs
+        .into_iter()
+        .map(|pr| {
+            let key = QueueSortKey::new(&pr, aging_interval, now);
+            (pr, key)
+        })
+        .collect();
+    prs.sort_by_key(|(_, key)| *key);
     prs
 }
 
+/// Names of the criteria by which the merge queue is ordered, in the order in which they are
+/// compared. See [`QueueSortKey`].
+pub const QUEUE_SORT_CRITERIA: [&str; 9] = [
+    "pending_build",
+    "held",
+    "parked",
+    "approved",
+    "build_status",
+    "mergeable",
+    "priority",
+    "rollup",
+    "number",
+];
+
+/// The values that decide the position of a pull request in the merge queue.
+/// Keys are compared field by field, and PRs with a smaller key come first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct QueueSortKey {
+    /// 1. Pending builds come first (to block merge queue)
+    pub blocking: u32,
+    /// 2. PRs held with `@bors hold` sit at the top of the queue
+    pub held: Reverse<bool>,
+    /// 3. PRs parked with `@bors park` come after the PRs that can be merged
+    pub parked: bool,
+    /// 4. Approved PRs come first
+    pub approved: Reverse<bool>,
+    /// 5. Build status within approval groups
+    pub status: u32,
+    /// 6. Mergeability state (0 = mergeable, 1 = conflicts/unknown)
+    pub mergeable: u32,
+    /// 7. Higher (effective) priority comes first
+    pub priority: Reverse<i64>,
+    /// 8. Rollup mode (0 = never, 1 = iffy, 2 = maybe, 3 = always)
+    pub rollup: u32,
+    /// 9. PR numbers (older first)
+    pub number: PullRequestNumber,
+}
+
+impl QueueSortKey {
+    pub fn new(
+        pr: &PullRequestModel,
+        aging_interval: Option<Duration>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            blocking: get_queue_blocking_priority(pr),
+            held: Reverse(pr.held),
+            parked: pr.parked,
+            approved: Reverse(pr.is_approved()),
+            status: get_status_priority(pr),
+            mergeable: get_mergeable_priority(pr),
+            priority: Reverse(effective_priority(pr, aging_interval, now)),
+            rollup: get_rollup_priority(pr.rollup.as_ref()),
+            number: pr.number,
+        }
+    }
+
+    /// Returns the name (from [`QUEUE_SORT_CRITERIA`]) of the first criterion in which the keys
+    /// differ, i.e. the one that decides the relative order of the two PRs.
+    pub fn deciding_criterion(&self, other: &QueueSortKey) -> Option<&'static str> {
+        let differs = [
+            self.blocking != other.blocking,
+            self.held != other.held,
+            self.parked != other.parked,
+            self.approved != other.approved,
+            self.status != other.status,
+            self.mergeable != other.mergeable,
+            self.priority != other.priority,
+            self.rollup != other.rollup,
+            self.number != other.number,
+        ];
+        differs
+            .iter()
+            .position(|differs| *differs)
+            .map(|index| QUEUE_SORT_CRITERIA[index])
+    }
+}
+
+/// The priority of a PR used to order the merge queue at the time `now`.
+/// Without `aging_interval`, this is the priority of the PR. Otherwise, an approved PR gains one
+/// priority level for each `aging_interval` that has elapsed since its approval.
+pub fn effective_priority(
+    pr: &PullRequestModel,
+    aging_interval: Option<Duration>,
+    now: DateTime<Utc>,
+) -> i64 {
+    let priority = i64::from(pr.priority.unwrap_or(0));
+    let (Some(interval), Some(approved_at)) = (aging_interval, pr.approved_at) else {
+        return priority;
+    };
+    if !pr.is_approved() || interval.is_zero() {
+        return priority;
+    }
+    let waiting = (now - approved_at).to_std().unwrap_or_default();
+    priority + (waiting.as_secs_f64() / interval.as_secs_f64()) as i64
+}
+
 fn get_queue_blocking_priority(pr: &PullRequestModel) -> u32 {
     match &pr.auto_build {
         Some(build) => match build.status {
@@ -14,6 +14,39 @@ pub fn suppress_github_mentions(text: &str) -> String {
         .to_string()
 }
 
+/// Removes emoji, both GitHub shortcodes (e.g. `:pushpin:`) and Unicode emoji, from the text.
+pub fn strip_emoji(text: &str) -> String {
+    // Shortcodes must not be adjacent to other colons or words, to keep e.g. Rust paths intact
+    let shortcodes = Regex::new(r"(^|[^\w:]):[a-z][a-z0-9_]*:( |[^\w:]|$)").unwrap();
+    let unicode = Regex::new(r" ?\p{Emoji_Presentation}\x{FE0F}?").unwrap();
+    let text = shortcodes.replace_all(text, |caps: &Captures| match &caps[2] {
+        " " => caps[1].to_string(),
+        after => format!("{}{after}", &caps[1]),
+    });
+    unicode.replace_all(&text, "").to_string()
+}
+
+/// Replaces GitHub emoji shortcodes (e.g. `:sunny:`) with the words returned by `word`.
+/// A shortcode that is followed by text becomes a `WORD:` prefix of the text, other shortcodes
+/// are replaced by the word itself. Shortcodes without a word are kept unchanged.
+pub fn replace_emoji_shortcodes(text: &str, word: impl Fn(&str) -> Option<&'static str>) -> String {
+    let shortcodes = Regex::new(r"(^|[^\w:]):([a-z][a-z0-9_]*):([^\w:]|$)").unwrap();
+    shortcodes
+        .replace_all(text, |caps: &Captures| match word(&caps[2]) {
+            Some(word) if &caps[3] == " " => format!("{}{word}: ", &caps[1]),
+            Some(word) => format!("{}{word}{}", &caps[1], &caps[3]),
+            None => caps[0].to_string(),
+        })
+        .to_string()
+}
+
+/// Removes headings and bold emphasis from Markdown text, while keeping other Markdown
+/// elements, such as links, lists or code.
+pub fn strip_markdown_emphasis(text: &str) -> String {
+    let headings = Regex::new(r"(?m)^#{1,6} ").unwrap();
+    headings.replace_all(text, "").replace("**", "")
+}
+
 /// Pluralizes a piece of text.
 pub fn pluralize(base: &str, count: usize) -> Cow<'_, str> {
     if count == 1 {
@@ -23,6 +56,81 @@ pub fn pluralize(base: &str, count: usize) -> Cow<'_, str> {
     }
 }
 
+/// Formats a size in bytes in a human-readable form, e.g. `1.5 MiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next_unit in &UNITS[1..] {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next_unit;
+    }
+    format!("{size:.1} {unit}")
+}
+
+/// Returns true if the text references a GitHub issue or PR, either by its number (`#123` or
+/// `owner/repo#123`) or by its URL.
+pub fn contains_issue_reference(text: &str) -> bool {
+    let reference =
+        Regex::new(r"(?:^|[^\w&/.-])(?:[\w.-]+/[\w.-]+)?#\d+\b|/(?:issues|pull)/\d+\b").unwrap();
+    reference.is_match(text)
+}
+
+/// Returns the `items` that are not ticked as a Markdown task list item (`- [x] <item>`) in
+/// `text`. A ticked item only has to start with the text of the required item, so that the
+/// item can be followed e.g. by an explanation.
+pub fn unchecked_task_items<'a>(text: &str, items: &'a [String]) -> Vec<&'a str> {
+    let ticked: Vec<&str> = text
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim_start();
+            let line = line
+                .strip_prefix("- ")
+                .or_else(|| line.strip_prefix("* "))?
+                .trim_start();
+            line.strip_prefix("[x]")
+                .or_else(|| line.strip_prefix("[X]"))
+                .map(str::trim)
+        })
+        .collect();
+    items
+        .iter()
+        .map(|item| item.trim())
+        .filter(|item| !ticked.iter().any(|ticked| ticked.starts_with(item)))
+        .collect()
+}
+
+/// Returns the numbers of the PRs that were merged into a rollup, which are listed in the
+/// `Successful merges:` section of the rollup description (e.g. ` - #123 (Fix foo)`).
+/// Returns no PRs if the text is not a rollup description.
+pub fn rollup_constituents(text: &str) -> Vec<u64> {
+    let item = Regex::new(r"^[-*]\s+(?:[\w.-]+/[\w.-]+)?#(\d+)\b").unwrap();
+    let lines = text
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| *line != "Successful merges:")
+        .skip(1)
+        .skip_while(|line| line.is_empty());
+    let mut prs = vec![];
+    for line in lines {
+        let Some(caps) = item.captures(line) else {
+            break;
+        };
+        let number = caps[1].parse().unwrap();
+        if !prs.contains(&number) {
+            prs.push(number);
+        }
+    }
+    prs
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -37,6 +145,72 @@ mod tests {
         )
     }
 
+    #[test]
+    fn strip_emoji_shortcodes() {
+        assert_eq!(
+            strip_emoji(":pushpin: Commit abc has been approved"),
+            "Commit abc has been approved"
+        );
+        assert_eq!(strip_emoji("Failed :x: twice"), "Failed twice");
+        assert_eq!(
+            strip_emoji("Timed out at 10:30:00"),
+            "Timed out at 10:30:00"
+        );
+        assert_eq!(
+            strip_emoji("Cannot find std::fmt::Display"),
+            "Cannot find std::fmt::Display"
+        );
+    }
+
+    #[test]
+    fn strip_emoji_unicode() {
+        assert_eq!(strip_emoji("Pong 🏓!"), "Pong!");
+    }
+
+    #[test]
+    fn strip_markdown_emphasis_headings_and_bold() {
+        assert_eq!(
+            strip_markdown_emphasis("## Status\n- **Workflow**: [link](https://foo.bar)"),
+            "Status\n- Workflow: [link](https://foo.bar)"
+        );
+    }
+
+    #[test]
+    fn issue_references() {
+        assert!(contains_issue_reference("Fixes #123"));
+        assert!(contains_issue_reference("#1: fix foo"));
+        assert!(contains_issue_reference("See rust-lang/rust#456."));
+        assert!(contains_issue_reference(
+            "https://github.com/rust-lang/rust/issues/456"
+        ));
+        assert!(!contains_issue_reference("Fix foo"));
+        assert!(!contains_issue_reference("Issue #"));
+        assert!(!contains_issue_reference("&#123;"));
+        assert!(!contains_issue_reference("https://example.com/page#1"));
+    }
+
+    #[test]
+    fn rollup_constituents_successful_merges() {
+        let description = r"Successful merges:
+
+ - #123 (Fix foo)
+ - rust-lang/rust#456 (Add bar)
+ * #123 (Fix foo)
+
+Failed merges:
+
+ - #789 (Break baz)
+
+r? @ghost";
+        assert_eq!(rollup_constituents(description), vec![123, 456]);
+    }
+
+    #[test]
+    fn rollup_constituents_not_a_rollup() {
+        assert!(rollup_constituents("Fixes #123").is_empty());
+        assert!(rollup_constituents("Successful merges:\n\nNone").is_empty());
+    }
+
     #[test]
     fn pluralize_zero() {
         assert_eq!(pluralize("foo", 0), "foos");
@@ -51,4 +225,66 @@ mod tests {
     fn pluralize_two() {
         assert_eq!(pluralize("foo", 2), "foos");
     }
+
+    #[test]
+    fn format_size_bytes() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+    }
+
+    #[test]
+    fn format_size_units() {
+        assert_eq!(format_size(1024), "1.0 KiB");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+
+    #[test]
+    fn replace_emoji_shortcodes_with_words() {
+        let word = |shortcode: &str| match shortcode {
+            "sunny" => Some("SUCCESS"),
+            "x" => Some("FAILURE"),
+            _ => None,
+        };
+        assert_eq!(
+            replace_emoji_shortcodes(":sunny: Test successful", word),
+            "SUCCESS: Test successful"
+        );
+        assert_eq!(
+            replace_emoji_shortcodes("- [Workflow](url) :x:\n- Other", word),
+            "- [Workflow](url) FAILURE\n- Other"
+        );
+        assert_eq!(
+            replace_emoji_shortcodes(":v: Delegated", word),
+            ":v: Delegated"
+        );
+        assert_eq!(
+            replace_emoji_shortcodes("std::x::Display", word),
+            "std::x::Display"
+        );
+    }
+
+    #[test]
+    fn test_unchecked_task_items() {
+        let items = vec![
+            "Tests were added".to_string(),
+            "Docs were updated".to_string(),
+            "Changelog entry".to_string(),
+        ];
+        let text = r"
+## Checklist
+- [x] Tests were added (see `tests/foo.rs`)
+  * [ ] Docs were updated
+- [X] Changelog entry
+";
+        assert_eq!(
+            unchecked_task_items(text, &items),
+            vec!["Docs were updated"]
+        );
+        assert_eq!(
+            unchecked_task_items("", &items),
+            vec!["Tests were added", "Docs were updated", "Changelog entry"]
+        );
+    }
 }
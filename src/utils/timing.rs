@@ -1,4 +1,7 @@
 use crate::github::api::DEFAULT_REQUEST_TIMEOUT;
+use crate::github::api::rate_limit::{
+    MAX_RATE_LIMIT_RETRIES, MAX_RATE_LIMIT_WAIT, RATE_LIMIT_METRICS, RateLimitExceeded,
+};
 use itertools::Itertools;
 use std::fmt::Debug;
 use std::time::{Duration, Instant};
@@ -40,12 +43,19 @@ where
 pub enum ShouldRetry<E> {
     Yes(E),
     No(E),
+    /// The operation was refused because of a GitHub API rate limit. It should be retried once
+    /// the limit resets.
+    RateLimited(E, RateLimitExceeded),
 }
 
-/// If we have a general error, we convert it to `ShouldRetry::Yes` automatically.
+/// If we have a general error, we convert it to `ShouldRetry::Yes` automatically, unless it was
+/// caused by a rate limit.
 impl From<anyhow::Error> for ShouldRetry<anyhow::Error> {
     fn from(error: anyhow::Error) -> Self {
-        Self::Yes(error)
+        match RateLimitExceeded::find(&error) {
+            Some(limit) => Self::RateLimited(error, limit),
+            None => Self::Yes(error),
+        }
     }
 }
 
@@ -119,6 +129,9 @@ impl<E: Into<anyhow::Error>> From<RetryableOpError<E>> for anyhow::Error {
 /// The caller can explicitly specify which errors should be retried and which shouldn't.
 /// By default, all errors will be turned into `ShouldRetry::Yes` due to a blanket impl.
 ///
+/// Operations refused because of a rate limit are retried once the limit resets, even if
+/// `retry_method` does not retry errors. Such retries do not count towards the number of attempts.
+///
 /// This function uses rather complicated generic parameters and bounds to allow callers to
 /// automatically convert errors to `ShouldRetry` without doing it manually everywhere.
 /// If you hit an inference error, you might want to return e.g. `anyhow::Ok(...)` from `func`.
@@ -140,7 +153,10 @@ where
     );
 
     let mut errors = vec![];
-    for attempt in 1..=retry_method.max_retry_count {
+    let mut rate_limit_retries = 0;
+    let mut attempt = 0;
+    while attempt < retry_method.max_retry_count {
+        attempt += 1;
         let last_attempt = attempt == retry_method.max_retry_count;
 
         let start = Instant::now();
@@ -185,6 +201,26 @@ where
                 errors.push(anyhow::anyhow!("{error:?}"));
             }
             Some(Err(ShouldRetry::No(error))) => return Err(RetryableOpError::Err(error)),
+            Some(Err(ShouldRetry::RateLimited(error, limit))) => {
+                RATE_LIMIT_METRICS.record_rate_limited();
+                let backoff = limit.backoff();
+                if backoff > MAX_RATE_LIMIT_WAIT || rate_limit_retries == MAX_RATE_LIMIT_RETRIES {
+                    tracing::error!(
+                        "Operation was refused because of a rate limit that resets in {backoff:?}: {error:?}"
+                    );
+                    RATE_LIMIT_METRICS.record_failure();
+                    return Err(RetryableOpError::Err(error));
+                }
+                tracing::warn!(
+                    "Operation was refused because of a rate limit, retrying in {backoff:?}"
+                );
+                rate_limit_retries += 1;
+                // The operation was not performed, so this attempt does not count
+                attempt -= 1;
+                time::sleep(backoff).await;
+                RATE_LIMIT_METRICS.record_wait(backoff);
+                continue;
+            }
             None => {
                 tracing::error!("Operation timeouted");
                 errors.push(anyhow::anyhow!(
@@ -351,4 +387,56 @@ mod tests {
         assert_eq!(result, 42);
         assert_eq!(counter.get(), 3);
     }
+
+    #[tokio::test]
+    async fn test_rate_limit_retried_without_retry() {
+        let counter = RetryCounter::default();
+
+        let result = perform_retryable::<i32, anyhow::Error, _, _, _>(
+            "test_op",
+            RetryMethod::no_retry(),
+            || async {
+                counter.increment();
+                if counter.get() == 3 {
+                    Ok(42)
+                } else {
+                    Err(ShouldRetry::RateLimited(
+                        anyhow::anyhow!("FooBarFail"),
+                        RateLimitExceeded {
+                            retry_after: Some(Duration::from_millis(1)),
+                        },
+                    ))
+                }
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result, 42);
+        assert_eq!(counter.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_resetting_too_late() {
+        let counter = RetryCounter::default();
+
+        let error = perform_retryable::<i32, anyhow::Error, _, _, _>(
+            "test_op",
+            RetryMethod::default(),
+            || async {
+                counter.increment();
+                Err(ShouldRetry::RateLimited(
+                    anyhow::anyhow!("FooBarFail"),
+                    RateLimitExceeded {
+                        retry_after: Some(Duration::from_secs(60 * 60)),
+                    },
+                ))
+            },
+        )
+        .await
+        .expect_err("no error found");
+
+        assert!(format!("{error:?}").contains("FooBarFail"));
+        assert_eq!(counter.get(), 1);
+    }
 }
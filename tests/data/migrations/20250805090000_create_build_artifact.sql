@@ -0,0 +1,15 @@
+INSERT INTO
+    build_artifact (build_id, name, url, size)
+VALUES
+    (
+        1,
+        'dist',
+        'https://github.com/rust-lang/bors/actions/runs/4937812456/artifacts/1',
+        1048576
+    ),
+    (
+        1,
+        'docs',
+        'https://github.com/rust-lang/bors/actions/runs/4937812456/artifacts/2',
+        NULL
+    );
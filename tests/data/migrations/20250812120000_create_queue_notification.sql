@@ -0,0 +1,13 @@
+INSERT INTO
+    queue_notification (repository, pr_number, username)
+VALUES
+    (
+        'rust-lang/bors',
+        123,
+        'contributor'
+    ),
+    (
+        'rust-lang/rust',
+        123456,
+        'contributor'
+    );
@@ -0,0 +1,15 @@
+INSERT INTO
+    comment_outbox (repository, pr_number, text, attempts)
+VALUES
+    (
+        'rust-lang/bors',
+        123,
+        ':sunny: Test successful',
+        0
+    ),
+    (
+        'rust-lang/rust',
+        123456,
+        ':broken_heart: Test failed',
+        2
+    );
@@ -0,0 +1,10 @@
+INSERT INTO
+    branch_tree_state (repository, branch, tree_state, treeclosed_src)
+VALUES
+    ('rust-lang/bors', 'beta', NULL, NULL),
+    (
+        'rust-lang/rust',
+        'stable',
+        100,
+        'https://github.com/rust-lang/rust/pull/109831#issuecomment-2045783212'
+    );
@@ -0,0 +1,15 @@
+INSERT INTO
+    outbox (repository, pr_number, payload, attempts)
+VALUES
+    (
+        'rust-lang/bors',
+        123,
+        '{"type":"add_labels","labels":["merged-by-bors"]}',
+        0
+    ),
+    (
+        'rust-lang/rust',
+        123456,
+        '{"type":"complete_check_run","check_run_id":42,"success":false}',
+        1
+    );
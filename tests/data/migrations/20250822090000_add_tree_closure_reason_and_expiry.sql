@@ -0,0 +1,12 @@
+UPDATE repository
+SET
+    treeclosed_reason = 'CI outage',
+    treeclosed_until = '2025-08-22 12:00:00+00'
+WHERE
+    tree_state IS NOT NULL;
+
+UPDATE branch_tree_state
+SET
+    treeclosed_reason = 'Beta release'
+WHERE
+    branch = 'stable';
@@ -0,0 +1,5 @@
+UPDATE pull_request
+SET
+    delegation_expires_at = '2025-09-01 12:00:00+00'
+WHERE
+    delegated_permission IS NOT NULL;
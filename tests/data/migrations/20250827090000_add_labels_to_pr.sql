@@ -0,0 +1,5 @@
+UPDATE pull_request
+SET
+    labels = ARRAY['S-waiting-on-review', 'T-compiler']
+WHERE
+    id = 1;
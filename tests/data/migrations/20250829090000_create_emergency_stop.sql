@@ -0,0 +1,4 @@
+INSERT INTO
+    emergency_stop (reason)
+VALUES
+    ('Compromised CI secret');
@@ -0,0 +1,17 @@
+INSERT INTO
+    queue_check_run (repository, pr_number, check_run_id, head_sha, position)
+VALUES
+    (
+        'rust-lang/bors',
+        123,
+        1,
+        'abcdef',
+        0
+    ),
+    (
+        'rust-lang/rust',
+        123456,
+        2,
+        '123456',
+        3
+    );
@@ -0,0 +1,4 @@
+INSERT INTO
+    merge_queue_pause (repository, paused_by)
+VALUES
+    ('rust-lang/rust', 'default-user');
@@ -0,0 +1,5 @@
+UPDATE pull_request
+SET
+    squash = TRUE
+WHERE
+    id = 1;
@@ -0,0 +1,5 @@
+UPDATE pull_request
+SET
+    waiting_on_conflicts = TRUE
+WHERE
+    id = 1;
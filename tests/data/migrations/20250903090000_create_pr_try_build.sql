@@ -0,0 +1,6 @@
+-- The try builds of existing PRs were inserted by the migration
+UPDATE pr_try_build
+SET
+    jobs = ARRAY['ci', 'lint']
+WHERE
+    build_id = 3;
@@ -0,0 +1,9 @@
+INSERT INTO
+    build_retry (pull_request_id, build_id, retried_by, reason)
+VALUES
+    (
+        1,
+        3,
+        NULL,
+        'spurious failure'
+    );
@@ -0,0 +1,5 @@
+UPDATE pull_request
+SET
+    held = TRUE
+WHERE
+    id = 1;
@@ -0,0 +1,19 @@
+INSERT INTO
+    webhook_dead_letter (repository, event_type, payload, error, attempts, replay_requested)
+VALUES
+    (
+        'rust-lang/bors',
+        'issue_comment',
+        '{"action": "created"}',
+        'Cannot find PR',
+        1,
+        FALSE
+    ),
+    (
+        'rust-lang/rust',
+        'pull_request',
+        '{"action": "opened"}',
+        'Database error',
+        3,
+        TRUE
+    );
@@ -0,0 +1,23 @@
+INSERT INTO
+    pr_timeline (repository, pr_number, opened_at, first_command_at, approved_at, queued_at, build_started_at, merged_at)
+VALUES
+    (
+        'rust-lang/bors',
+        1,
+        '2025-09-01 10:00:00+00',
+        '2025-09-01 12:00:00+00',
+        '2025-09-01 12:00:00+00',
+        '2025-09-01 12:00:00+00',
+        '2025-09-01 13:00:00+00',
+        '2025-09-01 16:00:00+00'
+    ),
+    (
+        'rust-lang/rust',
+        2,
+        '2025-09-02 10:00:00+00',
+        NULL,
+        NULL,
+        NULL,
+        NULL,
+        NULL
+    );
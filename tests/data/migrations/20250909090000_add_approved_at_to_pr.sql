@@ -0,0 +1,5 @@
+UPDATE pull_request
+SET
+    approved_at = '2025-09-09 10:00:00+00'
+WHERE
+    id = 1;
@@ -0,0 +1,30 @@
+INSERT INTO
+    audit_log (repository, pr_number, actor, action, details, outcome, created_at)
+VALUES
+    (
+        'rust-lang/bors',
+        1,
+        'reviewer',
+        'approve',
+        '@bors r+',
+        'success',
+        '2025-09-10 10:00:00+00'
+    ),
+    (
+        'rust-lang/bors',
+        1,
+        NULL,
+        'auto_build_started',
+        NULL,
+        'success',
+        '2025-09-10 10:05:00+00'
+    ),
+    (
+        'rust-lang/bors',
+        NULL,
+        NULL,
+        'tree_opened',
+        NULL,
+        'success',
+        '2025-09-10 11:00:00+00'
+    );
@@ -0,0 +1,8 @@
+INSERT INTO
+    repository (name, deactivated_at, deactivation_reason)
+VALUES
+    (
+        'rust-lang/archived',
+        '2025-09-11 10:00:00+00',
+        'archived'
+    );
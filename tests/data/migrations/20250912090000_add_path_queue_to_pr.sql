@@ -0,0 +1,5 @@
+UPDATE pull_request
+SET
+    path_queue = 'frontend'
+WHERE
+    id = 1;
@@ -0,0 +1,5 @@
+UPDATE pr_try_build
+SET
+    head_sha = 'pr-1-sha'
+WHERE
+    build_id = 3;
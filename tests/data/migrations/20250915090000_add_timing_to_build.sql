@@ -0,0 +1,7 @@
+UPDATE build
+SET
+    queued_at = '2025-09-15 09:00:00+00',
+    finished_at = '2025-09-15 11:00:00+00',
+    merged_at = '2025-09-15 11:01:00+00'
+WHERE
+    id = 1;
@@ -0,0 +1,9 @@
+INSERT INTO
+    scheduled_tree_closure (repository, starts_at, ends_at, finished)
+VALUES
+    (
+        'rust-lang/rust',
+        '2025-09-19 22:00:00+00',
+        '2025-09-20 02:00:00+00',
+        TRUE
+    );
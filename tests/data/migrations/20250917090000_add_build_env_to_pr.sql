@@ -0,0 +1,5 @@
+UPDATE pull_request
+SET
+    build_env = ARRAY['profile=debug-assertions']
+WHERE
+    id = 1;
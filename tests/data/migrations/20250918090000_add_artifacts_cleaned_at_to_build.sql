@@ -0,0 +1,5 @@
+UPDATE build
+SET
+    artifacts_cleaned_at = '2025-09-18 09:00:00+00'
+WHERE
+    id = 1;
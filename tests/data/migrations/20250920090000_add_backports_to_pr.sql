@@ -0,0 +1,5 @@
+UPDATE pull_request
+SET
+    backports = ARRAY['beta']
+WHERE
+    id = 1;
@@ -0,0 +1,5 @@
+UPDATE pull_request
+SET
+    dry_run_head_sha = 'pr-1-sha'
+WHERE
+    id = 1;
@@ -0,0 +1,6 @@
+UPDATE pull_request
+SET
+    parked = TRUE,
+    park_reason = 'waiting for the release'
+WHERE
+    id = 1;
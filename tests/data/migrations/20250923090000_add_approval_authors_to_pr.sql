@@ -0,0 +1,5 @@
+UPDATE pull_request
+SET
+    approval_authors = ARRAY['reviewer']
+WHERE
+    id = 1;
@@ -0,0 +1,12 @@
+INSERT INTO
+    audit_log (repository, pr_number, actor, action, details, outcome, created_at)
+VALUES
+    (
+        NULL,
+        NULL,
+        NULL,
+        'api: emergency stop',
+        'role: operator, required: operator',
+        'allowed',
+        '2025-09-24 10:00:00+00'
+    );
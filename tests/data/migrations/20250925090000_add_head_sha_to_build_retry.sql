@@ -0,0 +1,10 @@
+INSERT INTO
+    build_retry (pull_request_id, build_id, retried_by, reason, head_sha)
+VALUES
+    (
+        1,
+        3,
+        NULL,
+        'spurious failure',
+        'pr-1-sha'
+    );
@@ -0,0 +1,8 @@
+INSERT INTO
+    repository (name, notification_window_start, notification_window_count)
+VALUES
+    (
+        'rust-lang/notified',
+        '2025-09-26 10:00:00+00',
+        3
+    );
@@ -0,0 +1,80 @@
+//! End-to-end tests that run against a real bors instance installed on a sandbox GitHub
+//! organization, e.g. to validate the staging deployment before it is rolled out to production.
+//! They are only built with the `e2e` feature, and they are configured with environment
+//! variables described in [sandbox::Sandbox].
+//!
+//! Each test creates its own repository in the sandbox organization, and removes it at the end.
+
+mod sandbox;
+
+use sandbox::{Sandbox, SandboxRepo};
+
+/// Creates a sandbox repository with the given bors configuration, runs `test` on it, and then
+/// removes the repository, even if the test has failed.
+async fn run_in_sandbox_repo(
+    name: &str,
+    config: &str,
+    test: impl AsyncFnOnce(&SandboxRepo<'_>) -> anyhow::Result<()>,
+) {
+    let sandbox = Sandbox::from_env()
+        .await
+        .expect("Cannot connect to the sandbox organization");
+    let repo = sandbox
+        .create_repo(name, config)
+        .await
+        .expect("Cannot create a sandbox repository");
+    let result = test(&repo).await;
+    let cleanup = repo.delete().await;
+    result.expect("Test has failed");
+    cleanup.expect("Cannot remove the sandbox repository");
+}
+
+#[tokio::test]
+async fn ping() {
+    run_in_sandbox_repo("ping", "", async |repo| {
+        let pr = repo.create_pr("Ping", &[("ping.txt", "ping")]).await?;
+        repo.command(pr, "ping").await?;
+        repo.wait_for_bot_comment(pr, "Pong").await?;
+        Ok(())
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn try_build() {
+    run_in_sandbox_repo("try", "", async |repo| {
+        let pr = repo.create_pr("Try build", &[("try.txt", "try")]).await?;
+        repo.command(pr, "try").await?;
+        repo.wait_for_bot_comment(pr, "Trying commit").await?;
+        repo.wait_for_bot_comment(pr, "Try build successful")
+            .await?;
+        Ok(())
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn try_build_failure() {
+    run_in_sandbox_repo("try-failure", "", async |repo| {
+        let pr = repo.create_pr("Failing try build", &[("FAIL", "")]).await?;
+        repo.command(pr, "try").await?;
+        repo.wait_for_bot_comment(pr, ":broken_heart: Test for")
+            .await?;
+        Ok(())
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn approve_and_merge() {
+    run_in_sandbox_repo("merge", "merge_queue_enabled = true", async |repo| {
+        let pr = repo.create_pr("Merge", &[("merge.txt", "merge")]).await?;
+        repo.command(pr, "r+").await?;
+        repo.wait_for_bot_comment(pr, "has been approved by")
+            .await?;
+        repo.wait_for_bot_comment(pr, "Test successful").await?;
+        repo.wait_for_merge(pr).await?;
+        Ok(())
+    })
+    .await;
+}
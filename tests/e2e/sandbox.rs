@@ -0,0 +1,319 @@
+//! Helpers for creating repositories and PRs in a sandbox GitHub organization, on which a real
+//! bors instance (e.g. the staging deployment) is installed.
+
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::Utc;
+use octocrab::Octocrab;
+use octocrab::models::issues::Comment;
+use octocrab::models::pulls::PullRequest;
+use octocrab::models::repos::Object;
+use octocrab::params::repos::Reference;
+
+/// Prefix of the names of repositories created by the end-to-end tests.
+const REPO_PREFIX: &str = "bors-e2e-";
+
+/// Repositories created by the tests are removed by later test runs once they are older than
+/// this, in case a test run did not clean them up (e.g. because it was killed).
+const STALE_REPO_AGE: chrono::Duration = chrono::Duration::hours(6);
+
+/// How often GitHub is polled while waiting for bors.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// CI workflow that is added to each sandbox repository. It succeeds on bors branches, unless the
+/// tested commit contains a `FAIL` file.
+const CI_WORKFLOW: &str = r#"name: CI
+on:
+  push:
+    branches:
+      - automation/bors/try
+      - automation/bors/auto
+jobs:
+  test:
+    runs-on: ubuntu-latest
+    steps:
+      - uses: actions/checkout@v5
+      - run: test ! -f FAIL
+"#;
+
+/// A sandbox organization, configured with environment variables:
+/// - `BORS_E2E_GITHUB_TOKEN`: token of a user that can create and delete repositories in the
+///   organization, and that can manage the installation of the bors GitHub App.
+/// - `BORS_E2E_ORG`: name of the organization.
+/// - `BORS_E2E_INSTALLATION_ID`: ID of the installation of the bors GitHub App in the
+///   organization. It has to be installed only on selected repositories.
+/// - `BORS_E2E_BOT`: login of the bot, e.g. `bors-staging[bot]`.
+/// - `BORS_E2E_CMD_PREFIX` (optional): command prefix of the bors instance. Defaults to `@bors`.
+/// - `BORS_E2E_TIMEOUT` (optional): how long (in seconds) to wait for bors. Defaults to 600.
+/// - `BORS_E2E_GITHUB_API_URL` (optional): base URL of the GitHub REST API.
+pub struct Sandbox {
+    client: Octocrab,
+    org: String,
+    installation_id: u64,
+    bot: String,
+    cmd_prefix: String,
+    timeout: Duration,
+    /// Login of the user that owns the token, who is a reviewer of the sandbox repositories.
+    user: String,
+}
+
+impl Sandbox {
+    pub async fn from_env() -> anyhow::Result<Self> {
+        let var = |name: &str| {
+            std::env::var(name).with_context(|| format!("Environment variable {name} is not set"))
+        };
+
+        let mut builder = Octocrab::builder().personal_token(var("BORS_E2E_GITHUB_TOKEN")?);
+        if let Ok(url) = var("BORS_E2E_GITHUB_API_URL") {
+            builder = builder.base_uri(url)?;
+        }
+        let client = builder.build()?;
+        let user = client
+            .current()
+            .user()
+            .await
+            .context("Cannot load the user of the token")?
+            .login;
+        let timeout = match var("BORS_E2E_TIMEOUT") {
+            Ok(timeout) => Duration::from_secs(timeout.parse().context("Invalid timeout")?),
+            Err(_) => Duration::from_secs(600),
+        };
+
+        let sandbox = Self {
+            client,
+            org: var("BORS_E2E_ORG")?,
+            installation_id: var("BORS_E2E_INSTALLATION_ID")?
+                .parse()
+                .context("Invalid installation ID")?,
+            bot: var("BORS_E2E_BOT")?,
+            cmd_prefix: var("BORS_E2E_CMD_PREFIX").unwrap_or_else(|_| "@bors".to_string()),
+            timeout,
+            user,
+        };
+        sandbox.remove_stale_repos().await?;
+        Ok(sandbox)
+    }
+
+    /// Creates a repository with the given bors configuration and a CI workflow, and installs
+    /// bors on it. The reviewers of the repository are configured to be the user of the token.
+    pub async fn create_repo(&self, name: &str, config: &str) -> anyhow::Result<SandboxRepo<'_>> {
+        let name = format!(
+            "{REPO_PREFIX}{name}-{}",
+            Utc::now().format("%Y%m%d%H%M%S%3f")
+        );
+        let repo: octocrab::models::Repository = self
+            .client
+            .post(
+                format!("/orgs/{}/repos", self.org),
+                Some(&serde_json::json!({
+                    "name": name,
+                    "private": true,
+                    "auto_init": true,
+                })),
+            )
+            .await
+            .with_context(|| format!("Cannot create repository {name}"))?;
+        let repo_id = repo.id;
+        let repo = SandboxRepo {
+            sandbox: self,
+            name,
+            default_branch: repo.default_branch.unwrap_or_else(|| "main".to_string()),
+        };
+
+        let config = format!(
+            r#"{config}
+
+[permissions]
+reviewers = ["{}"]
+"#,
+            self.user
+        );
+        repo.create_file(&repo.default_branch, "rust-bors.toml", &config)
+            .await?;
+        repo.create_file(
+            &repo.default_branch,
+            ".github/workflows/ci.yml",
+            CI_WORKFLOW,
+        )
+        .await?;
+
+        // bors loads the repository (and its configuration) once it is added to the installation
+        self.client
+            ._put(
+                format!(
+                    "/user/installations/{}/repositories/{}",
+                    self.installation_id, repo_id
+                ),
+                None::<&()>,
+            )
+            .await
+            .with_context(|| format!("Cannot install bors on {}", repo.name))?;
+        Ok(repo)
+    }
+
+    /// Removes repositories that were left behind by earlier test runs.
+    pub async fn remove_stale_repos(&self) -> anyhow::Result<()> {
+        let mut page = self
+            .client
+            .orgs(&self.org)
+            .list_repos()
+            .per_page(100)
+            .send()
+            .await?;
+        let mut stale = vec![];
+        loop {
+            stale.extend(page.items.into_iter().filter(|repo| {
+                repo.name.starts_with(REPO_PREFIX)
+                    && repo
+                        .created_at
+                        .is_some_and(|created_at| created_at < Utc::now() - STALE_REPO_AGE)
+            }));
+            match self.client.get_page(&page.next).await? {
+                Some(next) => page = next,
+                None => break,
+            }
+        }
+        for repo in stale {
+            tracing::info!("Removing stale sandbox repository {}", repo.name);
+            self.client.repos(&self.org, &repo.name).delete().await?;
+        }
+        Ok(())
+    }
+
+    /// Polls `check` until it returns a value, or until the timeout elapses.
+    async fn wait_for<T, F, Fut>(&self, description: &str, mut check: F) -> anyhow::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = anyhow::Result<Option<T>>>,
+    {
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        loop {
+            if let Some(value) = check().await? {
+                return Ok(value);
+            }
+            anyhow::ensure!(
+                tokio::time::Instant::now() < deadline,
+                "Timed out waiting for {description}"
+            );
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// A repository in the sandbox organization. It is deleted when [SandboxRepo::delete] is called.
+pub struct SandboxRepo<'a> {
+    sandbox: &'a Sandbox,
+    name: String,
+    default_branch: String,
+}
+
+impl SandboxRepo<'_> {
+    fn repos(&self) -> octocrab::repos::RepoHandler<'_> {
+        self.sandbox.client.repos(&self.sandbox.org, &self.name)
+    }
+
+    async fn create_file(&self, branch: &str, path: &str, content: &str) -> anyhow::Result<()> {
+        self.repos()
+            .create_file(path, format!("Add {path}"), content)
+            .branch(branch)
+            .send()
+            .await
+            .with_context(|| format!("Cannot create {path} on {branch}"))?;
+        Ok(())
+    }
+
+    /// Creates a PR against the default branch that adds the given files.
+    pub async fn create_pr(&self, title: &str, files: &[(&str, &str)]) -> anyhow::Result<u64> {
+        let base = self
+            .repos()
+            .get_ref(&Reference::Branch(self.default_branch.clone()))
+            .await?;
+        let Object::Commit { sha, .. } = base.object else {
+            anyhow::bail!("{} does not point to a commit", self.default_branch);
+        };
+        let branch = format!("pr-{}", Utc::now().format("%H%M%S%3f"));
+        self.repos()
+            .create_ref(&Reference::Branch(branch.clone()), sha)
+            .await?;
+        for (path, content) in files {
+            self.create_file(&branch, path, content).await?;
+        }
+
+        let pr = self
+            .sandbox
+            .client
+            .pulls(&self.sandbox.org, &self.name)
+            .create(title, &branch, &self.default_branch)
+            .send()
+            .await
+            .with_context(|| format!("Cannot create a PR from {branch}"))?;
+        Ok(pr.number)
+    }
+
+    /// Posts a bors command on the PR, e.g. `r+`.
+    pub async fn command(&self, pr: u64, command: &str) -> anyhow::Result<()> {
+        self.sandbox
+            .client
+            .issues(&self.sandbox.org, &self.name)
+            .create_comment(pr, format!("{} {command}", self.sandbox.cmd_prefix))
+            .await?;
+        Ok(())
+    }
+
+    /// Waits until bors posts a comment on the PR that contains `text`, and returns it.
+    pub async fn wait_for_bot_comment(&self, pr: u64, text: &str) -> anyhow::Result<String> {
+        self.sandbox
+            .wait_for(&format!("a comment containing `{text}`"), || async {
+                let comment = self
+                    .bot_comments(pr)
+                    .await?
+                    .into_iter()
+                    .filter_map(|comment| comment.body)
+                    .find(|body| body.contains(text));
+                Ok(comment)
+            })
+            .await
+    }
+
+    /// Waits until the PR is merged.
+    pub async fn wait_for_merge(&self, pr: u64) -> anyhow::Result<()> {
+        self.sandbox
+            .wait_for("the PR to be merged", || async {
+                Ok(self.get_pr(pr).await?.merged_at.map(|_| ()))
+            })
+            .await
+    }
+
+    pub async fn get_pr(&self, pr: u64) -> anyhow::Result<PullRequest> {
+        Ok(self
+            .sandbox
+            .client
+            .pulls(&self.sandbox.org, &self.name)
+            .get(pr)
+            .await?)
+    }
+
+    async fn bot_comments(&self, pr: u64) -> anyhow::Result<Vec<Comment>> {
+        let client = &self.sandbox.client;
+        let page = client
+            .issues(&self.sandbox.org, &self.name)
+            .list_comments(pr)
+            .per_page(100)
+            .send()
+            .await?;
+        let comments = client.all_pages(page).await?;
+        Ok(comments
+            .into_iter()
+            .filter(|comment| comment.user.login == self.sandbox.bot)
+            .collect())
+    }
+
+    /// Deletes the repository.
+    pub async fn delete(self) -> anyhow::Result<()> {
+        self.repos()
+            .delete()
+            .await
+            .with_context(|| format!("Cannot delete repository {}", self.name))
+    }
+}